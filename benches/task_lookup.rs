@@ -0,0 +1,57 @@
+//! `ParsedPlan::task_by_index` (index map) against the linear scan it replaced, on plans of
+//! increasing size. Each pass looks up every task once, the way the scheduler walks a plan.
+//!
+//! Run with `cargo bench --bench task_lookup`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use peal::plan::{ParsedPlan, Task, parse_plan};
+
+const PLAN_SIZES: [u32; 4] = [10, 100, 500, 2000];
+const MIN_SAMPLE_TIME: Duration = Duration::from_millis(200);
+
+fn build_plan(task_count: u32) -> ParsedPlan {
+    let content: String = (1..=task_count)
+        .map(|i| {
+            let parallel = if i % 4 == 0 { " (parallel)" } else { "" };
+            format!("## Task {i}{parallel}\nImplement step {i} in src/step_{i}.rs.\n\n")
+        })
+        .collect();
+    parse_plan(&content).expect("generated plan parses")
+}
+
+fn linear_lookup(plan: &ParsedPlan, index: u32) -> Option<&Task> {
+    plan.tasks.iter().find(|t| t.index == index)
+}
+
+/// Average time per lookup of `lookup` over whole-plan passes, repeated for at least
+/// `MIN_SAMPLE_TIME`.
+fn time_per_lookup(plan: &ParsedPlan, lookup: impl Fn(&ParsedPlan, u32) -> Option<&Task>) -> Duration {
+    let indices: Vec<u32> = plan.tasks.iter().map(|t| t.index).collect();
+    let start = Instant::now();
+    let mut lookups: u32 = 0;
+    while start.elapsed() < MIN_SAMPLE_TIME {
+        for &idx in &indices {
+            black_box(lookup(black_box(plan), black_box(idx)));
+        }
+        lookups += indices.len() as u32;
+    }
+    start.elapsed() / lookups
+}
+
+fn main() {
+    println!("{:>6} {:>14} {:>14} {:>9}", "tasks", "linear scan", "index map", "speedup");
+    for size in PLAN_SIZES {
+        let plan = build_plan(size);
+        let linear = time_per_lookup(&plan, linear_lookup);
+        let indexed = time_per_lookup(&plan, ParsedPlan::task_by_index);
+        println!(
+            "{:>6} {:>14?} {:>14?} {:>8.1}x",
+            size,
+            linear,
+            indexed,
+            linear.as_secs_f64() / indexed.as_secs_f64().max(f64::EPSILON)
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! `peal agent install`: download a pinned Cursor CLI into a peal-managed directory.
+//!
+//! Meant for bootstrapping machines without the Cursor CLI (CI images, fresh containers).
+//! [`install`] downloads the package for one `--version` with `curl`, checks it against the
+//! `--sha256` the caller pinned, unpacks it with `tar` under
+//! `<dir>/versions/<version>/`, and finds the agent binary in it. [`set_agent_cmd`] then points
+//! `agent_cmd` in the config file at that binary. Installing a version that is already there
+//! with the same checksum does nothing, so CI can run the command on every build.
+//!
+//! The default download URL follows the layout of Cursor's own install script; `--url`
+//! overrides it (e.g. for a mirror). `{version}`, `{os}` (`linux`, `darwin`), and `{arch}`
+//! (`x64`, `arm64`) are replaced in it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::PealError;
+use crate::hash::sha256_hex;
+use crate::subprocess;
+
+/// Download URL template for `peal agent install` (see the module docs).
+pub const DEFAULT_DOWNLOAD_URL: &str =
+    "https://downloads.cursor.com/lab/{version}/{os}/{arch}/agent-cli-package.tar.gz";
+
+/// Binary names looked for in the unpacked package, in order.
+const AGENT_BINARIES: [&str; 2] = ["cursor-agent", "agent"];
+
+/// Recorded next to an installed version: the checksum it was installed with.
+const CHECKSUM_FILE: &str = ".peal-sha256";
+
+/// How deep the unpacked package is searched for the binary.
+const MAX_SEARCH_DEPTH: usize = 4;
+
+/// What to install and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallOptions {
+    pub version: String,
+    /// Expected SHA-256 of the downloaded package, hex.
+    pub sha256: String,
+    /// URL template (see [`DEFAULT_DOWNLOAD_URL`]).
+    pub url: String,
+    /// peal-managed install directory (see [`default_install_dir`]).
+    pub dir: PathBuf,
+}
+
+/// The result of [`install`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Installed {
+    /// The agent binary, for `agent_cmd`.
+    pub binary: PathBuf,
+    /// False when the version was already installed with the same checksum.
+    pub downloaded: bool,
+}
+
+/// `$XDG_DATA_HOME/peal/agent`, else `~/.local/share/peal/agent`; `None` without a home
+/// directory.
+pub fn default_install_dir() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(data_home.join("peal").join("agent"))
+}
+
+/// The download URL for this platform: `template` with `{version}`, `{os}`, and `{arch}`
+/// replaced.
+pub fn download_url(template: &str, version: &str) -> String {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        other => other,
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        other => other,
+    };
+    template.replace("{version}", version).replace("{os}", os).replace("{arch}", arch)
+}
+
+/// Download, verify, and unpack `options.version`; see the module docs.
+pub fn install(options: &InstallOptions) -> Result<Installed, PealError> {
+    let failed = |detail: String| PealError::AgentInstallFailed { detail };
+    let expected = options.sha256.trim().to_ascii_lowercase();
+    if expected.len() != 64 || !expected.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(failed(format!("--sha256 must be 64 hex digits, got '{}'", options.sha256)));
+    }
+    if options.version.is_empty() || options.version.contains(['/', '\\']) || options.version.starts_with('.') {
+        return Err(failed(format!("invalid version '{}'", options.version)));
+    }
+
+    let target = options.dir.join("versions").join(&options.version);
+    if fs::read_to_string(target.join(CHECKSUM_FILE)).is_ok_and(|recorded| recorded.trim() == expected)
+        && let Some(binary) = find_binary(&target)
+    {
+        return Ok(Installed { binary, downloaded: false });
+    }
+
+    let downloads = options.dir.join("downloads");
+    fs::create_dir_all(&downloads).map_err(|e| failed(format!("create {}: {e}", downloads.display())))?;
+    let package = downloads.join(format!("{}.tar.gz", options.version));
+    let url = download_url(&options.url, &options.version);
+    let args = ["-fsSL", "--retry", "2", "-o", &package.to_string_lossy(), &url];
+    let result = subprocess::run_command("curl", &args, &options.dir, None)
+        .map_err(|e| failed(format!("could not run curl: {e}")))?;
+    if !result.success() {
+        let _ = fs::remove_file(&package);
+        return Err(failed(format!("download of {url} failed: {}", result.stderr.trim())));
+    }
+
+    let bytes = fs::read(&package).map_err(|e| failed(format!("read {}: {e}", package.display())))?;
+    let actual = sha256_hex(&bytes);
+    if actual != expected {
+        let _ = fs::remove_file(&package);
+        return Err(failed(format!("checksum mismatch for {url}: expected {expected}, got {actual}")));
+    }
+
+    // Unpack next to the target and move it into place, so a failed unpack never leaves a
+    // half-installed version behind.
+    let staging = options.dir.join("versions").join(format!(".{}.partial", options.version));
+    let _ = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging).map_err(|e| failed(format!("create {}: {e}", staging.display())))?;
+    let args = ["-xzf", &package.to_string_lossy(), "-C", &staging.to_string_lossy()];
+    let result = subprocess::run_command("tar", &args, &options.dir, None)
+        .map_err(|e| failed(format!("could not run tar: {e}")))?;
+    if !result.success() {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(failed(format!("unpacking {} failed: {}", package.display(), result.stderr.trim())));
+    }
+    if find_binary(&staging).is_none() {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(failed(format!("the package from {url} contains no {} binary", AGENT_BINARIES.join(" or "))));
+    }
+    fs::write(staging.join(CHECKSUM_FILE), format!("{expected}\n"))
+        .map_err(|e| failed(format!("write {}: {e}", staging.join(CHECKSUM_FILE).display())))?;
+    let _ = fs::remove_dir_all(&target);
+    fs::rename(&staging, &target).map_err(|e| failed(format!("move into {}: {e}", target.display())))?;
+    let _ = fs::remove_file(&package);
+
+    let binary = find_binary(&target)
+        .ok_or_else(|| failed(format!("no agent binary in {} after the move", target.display())))?;
+    Ok(Installed { binary, downloaded: true })
+}
+
+/// The first executable named like [`AGENT_BINARIES`] under `dir`, shallowest first.
+fn find_binary(dir: &Path) -> Option<PathBuf> {
+    let mut level = vec![dir.to_path_buf()];
+    for _ in 0..=MAX_SEARCH_DEPTH {
+        let mut next = Vec::new();
+        for parent in &level {
+            let Ok(entries) = fs::read_dir(parent) else { continue };
+            let mut entries: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+            entries.sort();
+            for name in AGENT_BINARIES {
+                if let Some(found) = entries
+                    .iter()
+                    .find(|p| p.file_name().is_some_and(|n| n == name) && crate::cursor::is_executable(p))
+                {
+                    return Some(found.clone());
+                }
+            }
+            next.extend(entries.into_iter().filter(|p| p.is_dir()));
+        }
+        level = next;
+    }
+    None
+}
+
+/// Set `agent_cmd` in the TOML config file at `path` to `binary`: the first `agent_cmd = ...`
+/// line (commented out or not) is replaced, else the key is added before the first table.
+/// Other lines, comments included, are kept. A missing file is created.
+pub fn set_agent_cmd(path: &Path, binary: &Path) -> std::io::Result<()> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let line = format!("agent_cmd = {}", toml::Value::String(binary.to_string_lossy().into_owned()));
+    let mut lines: Vec<String> = content.lines().map(str::to_owned).collect();
+    let is_key = |l: &str| {
+        let l = l.trim_start().trim_start_matches('#').trim_start();
+        l.strip_prefix("agent_cmd").is_some_and(|rest| rest.trim_start().starts_with('='))
+    };
+    let existing = lines
+        .iter()
+        .position(|l| !l.trim_start().starts_with('#') && is_key(l))
+        .or_else(|| lines.iter().position(|l| is_key(l)));
+    match existing {
+        Some(i) => lines[i] = line,
+        None => {
+            let at = lines.iter().position(|l| l.trim_start().starts_with('[')).unwrap_or(lines.len());
+            lines.insert(at, line);
+        }
+    }
+    fs::write(path, lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn install_verifies_the_checksum_and_is_idempotent() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("pkg/dist-package");
+        fs::create_dir_all(&package_dir).unwrap();
+        let agent = package_dir.join("cursor-agent");
+        fs::write(&agent, "#!/bin/sh\necho agent\n").unwrap();
+        fs::set_permissions(&agent, fs::Permissions::from_mode(0o755)).unwrap();
+        let tarball = dir.path().join("agent-1.2.3.tar.gz");
+        let status = std::process::Command::new("tar")
+            .arg("-czf")
+            .arg(&tarball)
+            .arg("-C")
+            .arg(dir.path().join("pkg"))
+            .arg("dist-package")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let sha256 = sha256_hex(&fs::read(&tarball).unwrap());
+
+        let mut options = InstallOptions {
+            version: "1.2.3".to_owned(),
+            sha256: "0".repeat(64),
+            url: format!("file://{}/agent-{{version}}.tar.gz", dir.path().display()),
+            dir: dir.path().join("managed"),
+        };
+        let err = install(&options).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"), "{err}");
+        assert!(!options.dir.join("versions/1.2.3").exists());
+
+        options.sha256 = sha256.to_ascii_uppercase();
+        let installed = install(&options).unwrap();
+        assert_eq!(installed.binary, options.dir.join("versions/1.2.3/dist-package/cursor-agent"));
+        assert!(installed.downloaded);
+        assert!(crate::cursor::resolve_agent_cmd(&installed.binary.to_string_lossy()).is_ok());
+
+        fs::remove_file(&tarball).unwrap();
+        let again = install(&options).unwrap();
+        assert_eq!(again, Installed { binary: installed.binary, downloaded: false });
+    }
+
+    #[test]
+    fn set_agent_cmd_replaces_the_key_or_adds_it_before_the_first_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peal.toml");
+        let binary = Path::new("/opt/peal/agent/versions/1.2.3/cursor-agent");
+
+        fs::write(&path, "# agent_cmd = \"agent\"\nplan_path = \"plan.md\"\n\n[model_rates.gpt]\ninput_per_mtok = 1.0\n").unwrap();
+        set_agent_cmd(&path, binary).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "agent_cmd = \"/opt/peal/agent/versions/1.2.3/cursor-agent\"\nplan_path = \"plan.md\"\n\n[model_rates.gpt]\ninput_per_mtok = 1.0\n"
+        );
+
+        fs::write(&path, "plan_path = \"plan.md\"\n\n[model_rates.gpt]\n").unwrap();
+        set_agent_cmd(&path, binary).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().nth(2), Some("agent_cmd = \"/opt/peal/agent/versions/1.2.3/cursor-agent\""));
+        let parsed: toml::Value = toml::from_str(&content).unwrap();
+        assert_eq!(parsed["agent_cmd"].as_str(), Some("/opt/peal/agent/versions/1.2.3/cursor-agent"));
+    }
+}
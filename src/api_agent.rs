@@ -0,0 +1,404 @@
+//! HTTP agent backend for text-only calls (`agent_backend = "api"`).
+//!
+//! Phase 1, plan normalization, Phase 3 triage, and task summaries only need text back, so
+//! they can go to an OpenAI-compatible chat completions endpoint instead of `agent_cmd`.
+//! Phases 2 and 3 always run `agent_cmd`, since they need tool use.
+//!
+//! Requests are sent with `curl`, which keeps HTTPS support out of peal's dependencies. The
+//! request (URL, headers including the bearer token, and body) is handed to curl as a config
+//! file on stdin, so the API key never shows up in a process listing. The reply is mapped
+//! onto a [`CommandResult`] so callers keep their timeout, retry, and error handling: the
+//! message content becomes stdout; an HTTP or transport error becomes a failed result with
+//! the detail in stderr.
+
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use crate::config::PealConfig;
+use crate::subprocess::{self, CommandResult};
+
+/// Program used to send requests.
+const CURL: &str = "curl";
+
+/// Separates the response body from the HTTP status curl appends via `--write-out`.
+const STATUS_MARKER: &str = "\n__peal_http_status:";
+
+/// curl's exit code for "operation timed out".
+const CURL_TIMEOUT_EXIT: i32 = 28;
+
+/// Extra time the subprocess gets beyond curl's own `--max-time` before it is killed.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// Upper bound on error-body bytes carried into stderr.
+const MAX_ERROR_BODY_BYTES: usize = 2048;
+
+/// Send `prompt` as a single user message and return the reply as a [`CommandResult`].
+///
+/// Spawn failures (e.g. curl not installed) and a missing API key or model are returned as
+/// `Err`, like a CLI agent that cannot be started.
+pub fn complete(
+    config: &PealConfig,
+    prompt: &str,
+    timeout: Duration,
+) -> std::io::Result<CommandResult> {
+    complete_with(CURL, config, prompt, timeout, |name| std::env::var(name).ok())
+}
+
+fn complete_with(
+    curl: &str,
+    config: &PealConfig,
+    prompt: &str,
+    timeout: Duration,
+    env_fn: impl Fn(&str) -> Option<String>,
+) -> std::io::Result<CommandResult> {
+    let key = env_fn(&config.api_key_env)
+        .filter(|k| !k.trim().is_empty())
+        .ok_or_else(|| {
+            std::io::Error::other(format!(
+                "environment variable {} (api_key_env) is not set",
+                config.api_key_env
+            ))
+        })?;
+    let model = config
+        .api_model()
+        .ok_or_else(|| std::io::Error::other("no api_model (or model) configured"))?;
+
+    let url = completions_url(&config.api_base_url);
+    let body = json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": prompt }],
+    })
+    .to_string();
+    let max_time = timeout.as_secs().max(1).to_string();
+    let write_out = format!("{STATUS_MARKER}%{{http_code}}");
+    let args = [
+        "--silent",
+        "--show-error",
+        "--max-time",
+        max_time.as_str(),
+        "--write-out",
+        write_out.as_str(),
+        "--config",
+        "-",
+    ];
+
+    let raw = subprocess::run_command_with_input(
+        curl,
+        &args,
+        &config.repo_path,
+        Some(timeout + KILL_GRACE),
+        &curl_config(&url, key.trim(), &body),
+    )?;
+    Ok(interpret(raw, &url))
+}
+
+/// `<base>/chat/completions`, tolerating a trailing slash on the base URL.
+fn completions_url(base_url: &str) -> String {
+    format!("{}/chat/completions", base_url.trim().trim_end_matches('/'))
+}
+
+/// curl config (read via `--config -`) for one POST request.
+fn curl_config(url: &str, key: &str, body: &str) -> String {
+    [
+        format!("url = {}", quote(url)),
+        format!("header = {}", quote("Content-Type: application/json")),
+        format!("header = {}", quote(&format!("Authorization: Bearer {key}"))),
+        format!("data-binary = {}", quote(body)),
+    ]
+    .join("\n")
+        + "\n"
+}
+
+/// Double-quote a curl config value, escaping what curl unescapes.
+fn quote(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Turn curl's result into the phase-facing result (see the module docs).
+fn interpret(raw: CommandResult, url: &str) -> CommandResult {
+    let failed = |stderr: String| CommandResult {
+        stdout: String::new(),
+        stderr,
+        exit_code: Some(1),
+        timed_out: false,
+        limit_exceeded: None,
+        signal: None,
+    };
+
+    if raw.timed_out || raw.exit_code == Some(CURL_TIMEOUT_EXIT) {
+        return CommandResult {
+            stdout: String::new(),
+            stderr: raw.stderr,
+            exit_code: None,
+            timed_out: true,
+            limit_exceeded: None,
+            signal: None,
+        };
+    }
+    if !raw.success() {
+        return raw;
+    }
+
+    let Some((body, status)) = raw.stdout.rsplit_once(STATUS_MARKER) else {
+        return failed(format!("{url}: no HTTP status in curl output"));
+    };
+    let status = status.trim();
+    let parsed: Option<Value> = serde_json::from_str(body).ok();
+    if !status.starts_with('2') {
+        let detail = parsed
+            .as_ref()
+            .and_then(|v| v.pointer("/error/message"))
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .unwrap_or_else(|| subprocess::truncate_utf8(body.trim(), MAX_ERROR_BODY_BYTES).to_owned());
+        return failed(format!("{url}: HTTP {status}: {detail}"));
+    }
+
+    match parsed.as_ref().and_then(message_content) {
+        Some(content) => CommandResult {
+            stdout: content,
+            stderr: raw.stderr,
+            exit_code: Some(0),
+            timed_out: false,
+            limit_exceeded: None,
+            signal: None,
+        },
+        None => failed(format!(
+            "{url}: response has no choices[0].message.content: {}",
+            subprocess::truncate_utf8(body.trim(), MAX_ERROR_BODY_BYTES)
+        )),
+    }
+}
+
+/// `choices[0].message.content`, as a string or as a list of `{"type": "text"}` parts.
+fn message_content(response: &Value) -> Option<String> {
+    let content = response.pointer("/choices/0/message/content")?;
+    if let Some(text) = content.as_str() {
+        return Some(text.to_owned());
+    }
+    let parts: Vec<&str> = content
+        .as_array()?
+        .iter()
+        .filter_map(|p| p.get("text").and_then(Value::as_str))
+        .collect();
+    (!parts.is_empty()).then(|| parts.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn test_config(repo: &Path) -> PealConfig {
+        PealConfig {
+            agent_cmd: "agent".to_owned(),
+            plan_path: PathBuf::from("plan.md"),
+            repo_path: repo.to_path_buf(),
+            stet_commands: vec![],
+            sandbox: "disabled".to_owned(),
+            model: None,
+            max_address_rounds: 3,
+            on_findings_remaining: "fail".to_owned(),
+            state_dir: repo.join(".peal"),
+            phase_timeout_sec: 1800,
+            phase_retry_count: 0,
+            phase_3_retry_count: 0,
+            parallel: false,
+            max_parallel: 4,
+            continue_with_remaining_tasks: false,
+            log_level: None,
+            log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
+            stet_path: None,
+            stet_start_ref: None,
+            stet_start_extra_args: vec![],
+            stet_run_extra_args: vec![],
+            stet_disable_llm_triage: false,
+            stet_dismiss_patterns: vec![],
+            on_stet_fail: "fail".to_owned(),
+            post_run_commands: vec![],
+            post_run_timeout_sec: None,
+            normalize_plan: false,
+            normalize_retry_count: 0,
+            normalize_prompt_path: None,
+            validate_plan_text: false,
+            min_plan_text_len: None,
+            run_summary_path: None,
+            max_consecutive_task_failures: None,
+            commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "api".to_owned(),
+            api_base_url: "https://llm.example/v1/".to_owned(),
+            api_key_env: "TEST_API_KEY".to_owned(),
+            api_model: Some("gpt-test".to_owned()),
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
+        }
+    }
+
+    /// A fake curl in `dir` that saves its stdin and argv and prints `dir/response`.
+    #[cfg(unix)]
+    fn fake_curl(dir: &Path, response: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+        let script = dir.join("curl");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nd=$(dirname \"$0\")\ncat > \"$d/request\"\necho \"$@\" > \"$d/argv\"\ncat \"$d/response\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(dir.join("response"), response).unwrap();
+        script.to_str().unwrap().to_owned()
+    }
+
+    fn key(name: &str) -> Option<String> {
+        (name == "TEST_API_KEY").then(|| "sk-secret".to_owned())
+    }
+
+    #[test]
+    fn quote_escapes_what_curl_unescapes() {
+        assert_eq!(quote(r#"a "b" \ c"#), r#""a \"b\" \\ c""#);
+        assert_eq!(quote("x\ny\tz"), r#""x\ny\tz""#);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn complete_returns_message_content_and_keeps_key_out_of_argv() {
+        let dir = tempfile::tempdir().unwrap();
+        let curl = fake_curl(
+            dir.path(),
+            "{\"choices\":[{\"message\":{\"role\":\"assistant\",\"content\":\"the plan\"}}]}\n__peal_http_status:200",
+        );
+        let config = test_config(dir.path());
+
+        let result =
+            complete_with(&curl, &config, "plan \"this\"", Duration::from_secs(30), key).unwrap();
+
+        assert!(result.success(), "{result:?}");
+        assert_eq!(result.stdout, "the plan");
+        let request = std::fs::read_to_string(dir.path().join("request")).unwrap();
+        assert!(request.contains(r#"url = "https://llm.example/v1/chat/completions""#));
+        assert!(request.contains("Authorization: Bearer sk-secret"));
+        assert!(request.contains(r#"\"model\":\"gpt-test\""#), "{request}");
+        let argv = std::fs::read_to_string(dir.path().join("argv")).unwrap();
+        assert!(!argv.contains("sk-secret"));
+        assert!(argv.contains("--max-time 30"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn complete_maps_http_error_to_failed_result_with_api_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let curl = fake_curl(
+            dir.path(),
+            "{\"error\":{\"message\":\"Invalid API key\"}}\n__peal_http_status:401",
+        );
+        let config = test_config(dir.path());
+
+        let result = complete_with(&curl, &config, "p", Duration::from_secs(5), key).unwrap();
+
+        assert!(!result.success());
+        assert!(!result.timed_out);
+        assert!(result.stderr.contains("HTTP 401: Invalid API key"), "{}", result.stderr);
+    }
+
+    #[test]
+    fn complete_without_api_key_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let err = complete_with("curl", &config, "p", Duration::from_secs(5), |_| None).unwrap_err();
+        assert!(err.to_string().contains("TEST_API_KEY"), "{err}");
+    }
+
+    #[test]
+    fn interpret_treats_curl_timeout_as_timed_out_and_accepts_content_parts() {
+        let raw = |stdout: &str, exit_code| CommandResult {
+            stdout: stdout.to_owned(),
+            stderr: String::new(),
+            exit_code: Some(exit_code),
+            timed_out: false,
+            limit_exceeded: None,
+            signal: None,
+        };
+        assert!(interpret(raw("", CURL_TIMEOUT_EXIT), "u").timed_out);
+
+        let parts = r#"{"choices":[{"message":{"content":[{"type":"text","text":"a"},{"type":"text","text":"b"}]}}]}"#;
+        let ok = interpret(raw(&format!("{parts}{STATUS_MARKER}200"), 0), "u");
+        assert_eq!(ok.stdout, "ab");
+    }
+}
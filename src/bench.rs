@@ -0,0 +1,433 @@
+//! `peal bench`: run one plan once per model and compare the runs.
+//!
+//! Each model gets its own git worktree of the repo at `HEAD` (under the system temp
+//! directory, so the runs cannot see each other's edits) and its own state directory,
+//! `{state_dir}/bench/<run id>/<model>/`. The plan runs there as a child `peal run` with the
+//! bench's own options plus `--repo`, `--state-dir`, `--model`, `--plan`, and
+//! `--run-summary-path` pointing at them; the child's output goes to `output.log` in its state
+//! directory. Models run one after another.
+//!
+//! After each run the bench reads the run summary and state, runs every task's `peal-verify`
+//! commands in the worktree, and removes the worktree (unless `--keep-worktrees`). The
+//! comparison ([`BenchReport`]) is printed and written as `report.md` and `report.json` next to
+//! the per-model directories. Uncommitted changes in the repo are not part of the worktrees.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config::PealConfig;
+use crate::error::PealError;
+use crate::estimate::format_duration;
+use crate::plan::ParsedPlan;
+use crate::{run_id, run_summary, state, vcs};
+
+/// `peal run` options the bench sets for each child run; the user's values are dropped.
+const OVERRIDDEN_OPTIONS: [&str; 6] = ["--repo", "--state-dir", "--model", "--plan", "--config", "--run-summary-path"];
+
+/// Bench-only options (and `run` flags that make no sense in a child run), dropped from the
+/// child's argv. `--models` takes a value; the others are flags.
+const BENCH_OPTIONS: [&str; 1] = ["--models"];
+const BENCH_FLAGS: [&str; 3] = ["--keep-worktrees", "--no-config", "--detach"];
+
+/// Outcome of one model's run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModelResult {
+    pub model: String,
+    /// Exit code of the child `peal run` (0 or 2 when the run finished); `None` when it was
+    /// killed by a signal.
+    pub exit_code: Option<i32>,
+    pub wall_time_ms: u64,
+    /// Phase 1–3 time recorded in the run's state (`phase_durations`).
+    pub agent_time_ms: u64,
+    /// Task counts from the run summary; `None` when the run wrote none (it failed early).
+    pub tasks_completed: Option<usize>,
+    pub tasks_failed: Option<usize>,
+    pub tasks_with_remaining_findings: Option<usize>,
+    /// `peal-verify` commands of the plan's tasks that pass in the worktree after the run.
+    pub verify_passed: usize,
+    pub verify_total: usize,
+    /// State directory of the run (state, run summary, `output.log`).
+    pub state_dir: PathBuf,
+    /// Worktree the run edited; removed after the bench unless `--keep-worktrees`.
+    pub worktree: PathBuf,
+}
+
+/// Comparison of the runs of one `peal bench`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BenchReport {
+    pub run_id: String,
+    pub plan_path: PathBuf,
+    /// Commit the worktrees were created from.
+    pub base_commit: String,
+    pub results: Vec<ModelResult>,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |n: Option<usize>| n.map_or("-".to_owned(), |n| n.to_string());
+        writeln!(f, "# peal bench {}", self.run_id)?;
+        writeln!(f)?;
+        writeln!(f, "plan: {}, base commit: {}", self.plan_path.display(), self.base_commit)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "| model | exit | wall time | agent time | completed | failed | remaining findings | verify passed |"
+        )?;
+        writeln!(f, "|---|---|---|---|---|---|---|---|")?;
+        for r in &self.results {
+            let verify = if r.verify_total == 0 {
+                "-".to_owned()
+            } else {
+                format!(
+                    "{}/{} ({}%)",
+                    r.verify_passed,
+                    r.verify_total,
+                    r.verify_passed * 100 / r.verify_total
+                )
+            };
+            writeln!(
+                f,
+                "| {} | {} | {} | {} | {} | {} | {} | {} |",
+                r.model,
+                r.exit_code.map_or("signal".to_owned(), |c| c.to_string()),
+                format_duration(r.wall_time_ms as f64 / 1000.0),
+                format_duration(r.agent_time_ms as f64 / 1000.0),
+                count(r.tasks_completed),
+                count(r.tasks_failed),
+                count(r.tasks_with_remaining_findings),
+                verify,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Directory of this bench's reports and per-model state: `{state_dir}/bench/<run id>`.
+pub fn bench_dir(state_dir: &Path) -> PathBuf {
+    state_dir.join("bench").join(run_id::current())
+}
+
+/// Run the plan of `config` once per model with the `peal` binary `exe`. `args` is the
+/// bench's argv after the subcommand name; it is passed on to each child `peal run` (see
+/// [`child_args`]). Fails when the repo is not a git repo or a worktree cannot be created;
+/// a failed child run is just reported.
+pub fn run_bench(
+    exe: &Path,
+    config: &PealConfig,
+    config_path: Option<&Path>,
+    models: &[String],
+    args: &[OsString],
+    keep_worktrees: bool,
+) -> Result<BenchReport, PealError> {
+    let git = vcs::for_config(config);
+    if git.name() != "git" {
+        return Err(PealError::VcsUnsupported {
+            option: "peal bench",
+            vcs: git.name().to_owned(),
+        });
+    }
+    let base_commit = git.head(&config.repo_path).ok_or_else(|| PealError::BenchFailed {
+        model: String::new(),
+        detail: "cannot resolve HEAD of the repo".to_owned(),
+    })?;
+    let absolute = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf());
+    let plan_path = absolute(&config.plan_path);
+    let config_path = config_path.map(absolute);
+    let out_dir = absolute(&bench_dir(&config.state_dir));
+    let worktree_root = std::env::temp_dir().join(format!("peal-bench-{}", run_id::current()));
+    let plan = crate::plan::parse_plan_file(&plan_path).ok();
+
+    let mut results = Vec::new();
+    for model in models {
+        let slug = model_slug(model);
+        let state_dir = out_dir.join(&slug);
+        let worktree = worktree_root.join(&slug);
+        fs::create_dir_all(&state_dir).map_err(|e| PealError::BenchFailed {
+            model: model.clone(),
+            detail: format!("cannot create {}: {e}", state_dir.display()),
+        })?;
+        add_worktree(&config.repo_path, &worktree, &base_commit).map_err(|detail| PealError::BenchFailed {
+            model: model.clone(),
+            detail,
+        })?;
+
+        let mut child = child_args(args);
+        for (option, value) in [
+            ("--repo", worktree.as_os_str()),
+            ("--state-dir", state_dir.as_os_str()),
+            ("--model", model.as_ref()),
+            ("--plan", plan_path.as_os_str()),
+        ] {
+            child.push(option.into());
+            child.push(value.to_owned());
+        }
+        child.push("--run-summary-path".into());
+        child.push(state_dir.join("run_summary.json").into_os_string());
+        match &config_path {
+            Some(path) => {
+                child.push("--config".into());
+                child.push(path.clone().into_os_string());
+            }
+            None => child.push("--no-config".into()),
+        }
+
+        info!(model, worktree = %worktree.display(), "bench: running plan");
+        eprintln!("bench: {model}: running in {} (log: {})", worktree.display(), state_dir.join("output.log").display());
+        let start = Instant::now();
+        let exit_code = run_child(exe, &child, &state_dir).map_err(|detail| PealError::BenchFailed {
+            model: model.clone(),
+            detail,
+        })?;
+        let wall_time = start.elapsed();
+
+        let summary = run_summary::load_summary(&state_dir.join("run_summary.json")).ok();
+        let agent_time_ms = state::load_state(&state_dir)
+            .ok()
+            .flatten()
+            .map_or(0, |s| s.phase_durations.values().map(|d| d.total_ms()).sum());
+        let work_dir = config.repo_subdir.as_ref().map_or(worktree.clone(), |d| worktree.join(d));
+        let (verify_passed, verify_total) = plan.as_ref().map_or((0, 0), |p| run_verify(config, p, &work_dir));
+
+        if !keep_worktrees {
+            remove_worktree(&config.repo_path, &worktree);
+        }
+        let result = ModelResult {
+            model: model.clone(),
+            exit_code,
+            wall_time_ms: u64::try_from(wall_time.as_millis()).unwrap_or(u64::MAX),
+            agent_time_ms,
+            tasks_completed: summary.as_ref().map(|s| s.tasks_completed.len()),
+            tasks_failed: summary.as_ref().map(|s| s.tasks_failed.len()),
+            tasks_with_remaining_findings: summary.as_ref().map(|s| s.tasks_with_remaining_findings.len()),
+            verify_passed,
+            verify_total,
+            state_dir,
+            worktree,
+        };
+        eprintln!(
+            "bench: {model}: exit {} after {}",
+            result.exit_code.map_or("signal".to_owned(), |c| c.to_string()),
+            format_duration(wall_time.as_secs_f64())
+        );
+        results.push(result);
+    }
+    if !keep_worktrees {
+        let _ = fs::remove_dir(&worktree_root);
+    }
+
+    Ok(BenchReport {
+        run_id: run_id::current().to_owned(),
+        plan_path,
+        base_commit,
+        results,
+    })
+}
+
+/// Write `report` as `report.md` and `report.json` in [`bench_dir`]; returns the Markdown path.
+pub fn write_report(state_dir: &Path, report: &BenchReport) -> Result<PathBuf, PealError> {
+    let dir = bench_dir(state_dir);
+    let md = dir.join("report.md");
+    let write_err = |e: std::io::Error| PealError::BenchFailed {
+        model: String::new(),
+        detail: format!("cannot write report in {}: {e}", dir.display()),
+    };
+    fs::create_dir_all(&dir).map_err(write_err)?;
+    fs::write(&md, report.to_string()).map_err(write_err)?;
+    let json = serde_json::to_string_pretty(report).unwrap_or_default();
+    fs::write(dir.join("report.json"), json).map_err(write_err)?;
+    Ok(md)
+}
+
+/// The bench's argv (after the subcommand name) as `peal run` argv: `run` followed by every
+/// argument except the bench-only ones and those in [`OVERRIDDEN_OPTIONS`], in both the
+/// `--opt value` and `--opt=value` forms.
+pub fn child_args(args: &[OsString]) -> Vec<OsString> {
+    let options: Vec<&str> = OVERRIDDEN_OPTIONS.iter().chain(&BENCH_OPTIONS).copied().collect();
+    run_args_without(args, &options, &BENCH_FLAGS)
+}
+
+/// `run` followed by `args` minus `options` (with their values, in both the `--opt value` and
+/// `--opt=value` forms) and `flags`. Shared with `peal run-many`.
+pub(crate) fn run_args_without(args: &[OsString], options: &[&str], flags: &[&str]) -> Vec<OsString> {
+    let mut out: Vec<OsString> = vec!["run".into()];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let text = arg.to_string_lossy();
+        let takes_value = |name: &&str| text == **name;
+        let inline_value = |name: &&str| text.starts_with(&format!("{name}="));
+        if options.iter().any(takes_value) {
+            iter.next();
+        } else if !options.iter().any(inline_value) && !flags.contains(&text.as_ref()) {
+            out.push(arg.clone());
+        }
+    }
+    out
+}
+
+/// `model` as a directory name: anything but ASCII letters, digits, `.`, `-`, and `_`
+/// becomes `-`.
+pub(crate) fn model_slug(model: &str) -> String {
+    model
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '-' })
+        .collect()
+}
+
+fn add_worktree(repo: &Path, dir: &Path, commit: &str) -> Result<(), String> {
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["worktree", "add", "--detach"])
+        .arg(dir)
+        .arg(commit)
+        .output()
+        .map_err(|e| format!("git worktree add: {e}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "git worktree add failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+fn remove_worktree(repo: &Path, dir: &Path) {
+    let removed = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["worktree", "remove", "--force"])
+        .arg(dir)
+        .output()
+        .is_ok_and(|o| o.status.success());
+    if !removed {
+        warn!(worktree = %dir.display(), "bench: failed to remove worktree");
+    }
+}
+
+/// Run `exe args` with stdout and stderr appended to `{state_dir}/output.log`; returns its
+/// exit code.
+pub(crate) fn run_child(exe: &Path, args: &[OsString], state_dir: &Path) -> Result<Option<i32>, String> {
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(state_dir.join("output.log"))
+        .map_err(|e| format!("cannot open output.log: {e}"))?;
+    let log_err = log.try_clone().map_err(|e| e.to_string())?;
+    let status = Command::new(exe)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log)
+        .stderr(log_err)
+        .status()
+        .map_err(|e| format!("cannot run {}: {e}", exe.display()))?;
+    Ok(status.code())
+}
+
+/// Run every `peal-verify` command of `plan` in `work_dir`; returns (passed, total).
+fn run_verify(config: &PealConfig, plan: &ParsedPlan, work_dir: &Path) -> (usize, usize) {
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    let commands: Vec<&String> = plan.tasks.iter().flat_map(|t| &t.verify).collect();
+    let passed = commands
+        .iter()
+        .filter(|command| {
+            crate::subprocess::run_command_string_with_shell(command, &config.command_shell, work_dir, timeout)
+                .is_some_and(|r| r.is_ok_and(|r| r.success()))
+        })
+        .count();
+    (passed, commands.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Commands};
+    use clap::Parser;
+
+    fn os(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn child_args_drop_bench_and_overridden_options() {
+        let args = os(&[
+            "--models", "a,b", "--plan", "p.md", "--repo=.", "--model", "x", "--keep-worktrees",
+            "--max-parallel", "2", "--no-config", "--parallel",
+        ]);
+        assert_eq!(child_args(&args), os(&["run", "--max-parallel", "2", "--parallel"]));
+        assert_eq!(model_slug("openai/gpt-5.2 high"), "openai-gpt-5.2-high");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_bench_runs_each_model_in_its_own_worktree() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            let out = Command::new("git").arg("-C").arg(&repo).args(args).output().unwrap();
+            assert!(out.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&out.stderr));
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        fs::write(repo.join("plan.md"), "## Task 1\nWrite done.txt.\n```peal-verify\ntest -f done.txt\n```\n").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+
+        // Stand-in for `peal run`: model "good" writes done.txt and a summary; "bad" fails.
+        let fake = dir.path().join("fake_peal");
+        fs::write(
+            &fake,
+            "#!/bin/sh\nwhile [ $# -gt 0 ]; do case \"$1\" in\n\
+             --repo) repo=$2; shift;; --model) model=$2; shift;; --run-summary-path) summary=$2; shift;;\n\
+             esac; shift; done\n\
+             [ \"$model\" = bad ] && { echo failed >&2; exit 1; }\n\
+             touch \"$repo/done.txt\"\n\
+             echo '{\"tasks_completed\":[1],\"tasks_failed\":[],\"tasks_with_remaining_findings\":[]}' > \"$summary\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let argv = ["peal", "run", "--plan", "plan.md", "--repo", ".", "--command-shell", "sh"];
+        let Commands::Run(args) = Cli::try_parse_from(argv).unwrap().command else {
+            panic!("expected run command");
+        };
+        let mut config = PealConfig::load(None, &args).unwrap();
+        config.plan_path = repo.join("plan.md");
+        config.repo_path = repo.clone();
+        config.state_dir = dir.path().join(".peal");
+        let models = vec!["good".to_owned(), "bad".to_owned()];
+        let report = run_bench(&fake, &config, None, &models, &os(&["--models", "good,bad"]), false).unwrap();
+
+        assert_eq!(report.results.len(), 2);
+        let good = &report.results[0];
+        assert_eq!((good.exit_code, good.tasks_completed), (Some(0), Some(1)));
+        assert_eq!((good.verify_passed, good.verify_total), (1, 1));
+        assert!(!good.worktree.exists(), "worktree should be removed");
+        let bad = &report.results[1];
+        assert_eq!((bad.exit_code, bad.tasks_completed), (Some(1), None));
+        assert_eq!((bad.verify_passed, bad.verify_total), (0, 1));
+        let log = fs::read_to_string(bad.state_dir.join("output.log")).unwrap();
+        assert_eq!(log, "failed\n");
+        assert!(!repo.join("done.txt").exists(), "the repo itself is untouched");
+
+        let md = write_report(&config.state_dir, &report).unwrap();
+        let text = fs::read_to_string(md).unwrap();
+        assert!(text.contains("| good | 0 |"), "{text}");
+        assert!(text.contains("| 1/1 (100%) |"), "{text}");
+        assert!(bench_dir(&config.state_dir).join("report.json").is_file());
+    }
+}
@@ -14,12 +14,274 @@ pub struct Cli {
 }
 
 #[derive(Debug, Subcommand)]
+#[allow(clippy::large_enum_variant)]
 pub enum Commands {
     /// Run the orchestrator on a plan file against a target repo.
     Run(RunArgs),
 
     /// Print a prompt template for an LLM to produce a PEAL-compatible plan.
     Prompt(PromptArgs),
+
+    /// Inspect or edit the resume state (`state.json`).
+    State(StateArgs),
+
+    /// Compare two run summaries (`run_summary.json`) and print what changed.
+    DiffRuns(DiffRunsArgs),
+
+    /// Inspect a plan file without running it.
+    Plan(PlanArgs),
+
+    /// Inspect the configuration a run would use.
+    Config(ConfigArgs),
+
+    /// Scaffold a starter peal.toml, a .gitignore entry for .peal/, and an example plan.
+    Init(InitArgs),
+
+    /// Follow the log of a run started with `peal run --detach` until it exits.
+    Attach(DetachedRunArgs),
+
+    /// Ask a run started with `peal run --detach` to stop after its current task.
+    Stop(DetachedRunArgs),
+
+    /// Run a plan once per model, each in its own git worktree, and compare the runs.
+    Bench(BenchArgs),
+
+    /// Run several plans, each against its own repo, concurrently, and summarize the runs.
+    RunMany(RunManyArgs),
+
+    /// Manage the Cursor CLI used as the agent.
+    Agent(AgentArgs),
+}
+
+/// Arguments for the `agent` subcommand.
+#[derive(Debug, Clone, clap::Args)]
+pub struct AgentArgs {
+    #[command(subcommand)]
+    pub command: AgentCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum AgentCommand {
+    /// Download a pinned, checksum-verified Cursor CLI into a peal-managed directory and
+    /// point agent_cmd in the config file at it.
+    Install(AgentInstallArgs),
+}
+
+/// Arguments for `peal agent install`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct AgentInstallArgs {
+    /// Cursor CLI version to install (e.g. 2025.09.04-fc40cd1).
+    #[arg(long)]
+    pub version: String,
+
+    /// Expected SHA-256 of the downloaded package (hex); the install fails on a mismatch.
+    #[arg(long)]
+    pub sha256: String,
+
+    /// Download URL; {version}, {os}, and {arch} are replaced.
+    #[arg(long, default_value = crate::agent_install::DEFAULT_DOWNLOAD_URL)]
+    pub url: String,
+
+    /// Install directory. Default: $XDG_DATA_HOME/peal/agent, else ~/.local/share/peal/agent.
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+
+    /// Config file whose agent_cmd is updated (created if missing).
+    #[arg(long, default_value = "peal.toml", conflicts_with = "no_config")]
+    pub config: PathBuf,
+
+    /// Only install; leave the config file alone.
+    #[arg(long, default_value_t = false)]
+    pub no_config: bool,
+}
+
+/// Arguments for the `config` subcommand.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the merged configuration, with the source of each value (cli, env, plan
+    /// sidecar, config file, plan front matter, or default).
+    Show(ConfigShowArgs),
+}
+
+/// Arguments for `peal config show`: the same options as `peal run`, plus `--resolved` and
+/// `--diff-defaults`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ConfigShowArgs {
+    /// Also list keys left at their defaults (by default only values set somewhere are shown).
+    #[arg(long, default_value_t = false, conflicts_with = "diff_defaults")]
+    pub resolved: bool,
+
+    /// List only values that differ from the built-in defaults, wherever they were set.
+    #[arg(long, default_value_t = false)]
+    pub diff_defaults: bool,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+/// Arguments for `peal bench`: the models to compare, plus the options of `peal run`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct BenchArgs {
+    /// Models to compare (comma-separated); the plan runs once per model.
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub models: Vec<String>,
+
+    /// Keep each model's worktree after the bench (default: removed once it is measured).
+    #[arg(long, default_value_t = false)]
+    pub keep_worktrees: bool,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+/// Arguments for `peal run-many`: plans and their repos, plus the options of `peal run`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct RunManyArgs {
+    /// Plan files to run (space- or comma-separated).
+    #[arg(long, num_args = 1.., value_delimiter = ',', required = true)]
+    pub plans: Vec<PathBuf>,
+
+    /// Target repos, one per plan and in the same order; each repo may appear once.
+    #[arg(long, num_args = 1.., value_delimiter = ',', required = true)]
+    pub repos: Vec<PathBuf>,
+
+    /// How many plans run at once (default: all of them).
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_concurrent_plans: Option<u32>,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+/// Arguments for `peal attach` and `peal stop`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct DetachedRunArgs {
+    /// State directory of the detached run. Default: PEAL_STATE_DIR, else ".peal".
+    #[arg(long)]
+    pub state_dir: Option<PathBuf>,
+}
+
+/// Arguments for `peal init`. Options not given are asked for interactively (unless `--yes`).
+#[derive(Debug, Clone, clap::Args)]
+pub struct InitArgs {
+    /// Directory to scaffold (default: current directory).
+    #[arg(long, default_value = ".")]
+    pub dir: PathBuf,
+
+    /// Cursor CLI binary name or path to write as agent_cmd.
+    #[arg(long)]
+    pub agent_cmd: Option<String>,
+
+    /// Plan path to write as plan_path, relative to --dir.
+    #[arg(long)]
+    pub plan: Option<PathBuf>,
+
+    /// Do not write an example plan.
+    #[arg(long, default_value_t = false)]
+    pub no_example_plan: bool,
+
+    /// Accept defaults for anything not given instead of prompting.
+    #[arg(long, short = 'y', default_value_t = false)]
+    pub yes: bool,
+
+    /// Overwrite an existing peal.toml.
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+/// Arguments for the `plan` subcommand.
+#[derive(Debug, Clone, clap::Args)]
+pub struct PlanArgs {
+    #[command(subcommand)]
+    pub command: PlanCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum PlanCommand {
+    /// Print the execution schedule (segments, parallel blocks, stages) as a graph.
+    Graph(PlanGraphArgs),
+    /// Convert a plan between canonical Markdown and structured YAML or JSON.
+    Convert(PlanConvertArgs),
+}
+
+/// Arguments for `peal plan graph`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct PlanGraphArgs {
+    /// Plan file in canonical format (`## Task N` headings).
+    pub plan: PathBuf,
+
+    /// Output format: "mermaid" (flowchart) or "dot" (Graphviz).
+    #[arg(long, default_value = "mermaid", value_parser = ["mermaid", "dot"])]
+    pub format: String,
+
+    /// Write the graph to this file instead of stdout.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Arguments for `peal plan convert`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct PlanConvertArgs {
+    /// Plan file to convert.
+    pub input: PathBuf,
+
+    /// Input format; inferred from the input file extension when omitted (Markdown otherwise).
+    #[arg(long, value_parser = ["md", "yaml", "json"])]
+    pub from: Option<String>,
+
+    /// Output format.
+    #[arg(long, value_parser = ["md", "yaml", "json"])]
+    pub to: String,
+
+    /// Write the converted plan to this file instead of stdout.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Arguments for `peal diff-runs`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct DiffRunsArgs {
+    /// Earlier run summary (e.g. a copy of `.peal/run_summary.json` from the previous attempt).
+    pub before: PathBuf,
+
+    /// Later run summary to compare against `before`.
+    pub after: PathBuf,
+}
+
+/// Arguments for the `state` subcommand.
+#[derive(Debug, Clone, clap::Args)]
+pub struct StateArgs {
+    #[command(subcommand)]
+    pub command: StateCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum StateCommand {
+    /// Mark tasks as not completed so the next resume reruns them.
+    Reset(StateResetArgs),
+}
+
+/// Arguments for `peal state reset`. Exactly one of `--task` or `--from-task` is required.
+#[derive(Debug, Clone, clap::Args)]
+#[command(group(clap::ArgGroup::new("selection").required(true).args(["tasks", "from_task"])))]
+pub struct StateResetArgs {
+    /// State directory containing state.json. Default: PEAL_STATE_DIR, else ".peal".
+    #[arg(long)]
+    pub state_dir: Option<PathBuf>,
+
+    /// Task index to reset (repeatable: `--task 4 --task 7`).
+    #[arg(long = "task", value_name = "N")]
+    pub tasks: Vec<u32>,
+
+    /// Reset task N and every later task.
+    #[arg(long, value_name = "N")]
+    pub from_task: Option<u32>,
 }
 
 /// Arguments for the `prompt` subcommand.
@@ -36,6 +298,7 @@ pub struct PromptArgs {
 /// (`PEAL_PLAN_PATH`, `PEAL_REPO_PATH`). Precedence: CLI > env > file.
 /// Re-running with the same --plan and --repo resumes from the last completed task.
 #[derive(Debug, Clone, clap::Args)]
+#[command(group(clap::ArgGroup::new("task_selection").args(["tasks", "from_task"])))]
 pub struct RunArgs {
     /// Path to the markdown plan file.
     #[arg(long)]
@@ -53,10 +316,30 @@ pub struct RunArgs {
     #[arg(long)]
     pub normalize_retry_count: Option<u32>,
 
-    /// Path to a TOML configuration file.
+    /// After normalization, open the normalized plan in $VISUAL / $EDITOR (or print it and
+    /// ask for confirmation) before the run proceeds. The normalized plan is always saved
+    /// next to the source plan as `<name>.normalized.md`.
+    #[arg(long, default_value_t = false)]
+    pub review_normalized: bool,
+
+    /// Renumber `## Task N` headings sequentially (1, 2, 3, …) in document order before
+    /// parsing, instead of failing on duplicate indices. Not layered from config or env.
+    #[arg(long, default_value_t = false)]
+    pub renumber: bool,
+
+    /// With --renumber, write the renumbered plan back to the plan file.
+    #[arg(long, default_value_t = false, requires = "renumber")]
+    pub write: bool,
+
+    /// Path to a TOML configuration file. When omitted, `peal.toml` or `.peal/config.toml`
+    /// is looked up in the repo directory, then the current directory.
     #[arg(long)]
     pub config: Option<PathBuf>,
 
+    /// Disable config file auto-detection (only meaningful without --config).
+    #[arg(long, default_value_t = false, conflicts_with = "config")]
+    pub no_config: bool,
+
     /// Cursor CLI binary name or path (default: "agent").
     #[arg(long)]
     pub agent_cmd: Option<String>,
@@ -102,7 +385,8 @@ pub struct RunArgs {
     pub max_address_rounds: Option<u32>,
 
     /// Behavior when stet findings persist after all address rounds.
-    /// "fail" (default) returns an error; "warn" logs a warning and continues.
+    /// "fail" (default) returns an error; "warn" logs a warning and continues;
+    /// "create_task" continues and queues a follow-up task to address the findings later.
     #[arg(long)]
     pub on_findings_remaining: Option<String>,
 
@@ -110,14 +394,32 @@ pub struct RunArgs {
     #[arg(long)]
     pub on_stet_fail: Option<String>,
 
-    /// Run only the task with this index.
-    #[arg(long, conflicts_with = "from_task")]
-    pub task: Option<u32>,
+    /// Run only the task with this index (repeatable: `--task 4 --task 7`).
+    #[arg(long = "task", value_name = "N", conflicts_with = "from_task")]
+    pub tasks: Vec<u32>,
 
     /// Run from this task index to the end of the plan.
-    #[arg(long, conflicts_with = "task")]
+    #[arg(long, conflicts_with = "tasks")]
     pub from_task: Option<u32>,
 
+    /// With --task or --from-task: mark the selected tasks incomplete in state before
+    /// scheduling, so they run again even if an earlier run completed them.
+    #[arg(long, requires = "task_selection")]
+    pub force_rerun: bool,
+
+    /// Run only tasks tagged with at least one of these tags (comma-separated or
+    /// repeated), from `## Task N (tags: backend, db)` headings.
+    #[arg(long, value_delimiter = ',')]
+    pub tags: Vec<String>,
+
+    /// Skip tasks tagged with any of these tags (comma-separated or repeated).
+    #[arg(long, value_delimiter = ',')]
+    pub exclude_tags: Vec<String>,
+
+    /// Run only the tasks of this plan stage (`# Stage: <name>` heading; case-insensitive).
+    #[arg(long)]
+    pub stage: Option<String>,
+
     /// Log level filter (default: "info"). Supports tracing directives
     /// (e.g. "debug", "peal=trace,warn"). Overridden by PEAL_LOG env var.
     #[arg(long)]
@@ -128,13 +430,22 @@ pub struct RunArgs {
     #[arg(long)]
     pub log_file: Option<PathBuf>,
 
+    /// Rotate the log file once it would exceed this size in MiB. When omitted, the file grows without bound.
+    #[arg(long)]
+    pub log_max_size_mb: Option<u64>,
+
+    /// Number of rotated log files to keep (default: 5). 0 truncates the log file on rotation.
+    #[arg(long)]
+    pub log_keep_files: Option<u32>,
+
     /// Explicit path to the stet binary. When omitted, stet is
     /// auto-detected on PATH; if not found, Phase 3 is skipped.
     #[arg(long)]
     pub stet_path: Option<PathBuf>,
 
     /// Git ref passed to `stet start <ref>`. When omitted, `stet start`
-    /// is invoked without a ref argument.
+    /// is invoked without a ref argument. `{{BASE_BRANCH}}` and
+    /// `{{MERGE_BASE}}` are resolved with git at run start.
     #[arg(long)]
     pub stet_start_ref: Option<String>,
 
@@ -179,6 +490,354 @@ pub struct RunArgs {
     /// When stet is not installed, peal still commits so work is saved incrementally. Default: false.
     #[arg(long, default_value_t = false)]
     pub commit_after_phase2: bool,
+
+    /// Use stet as a gate: fail the task as soon as the first review has findings,
+    /// without triage or address rounds. Default: false.
+    #[arg(long, default_value_t = false)]
+    pub fail_fast_findings: bool,
+
+    /// Global limit on Cursor CLI invocations per minute, shared across parallel tasks
+    /// (all phases, triage and address calls). When omitted, calls are not rate limited.
+    #[arg(long)]
+    pub agent_max_requests_per_min: Option<u32>,
+
+    /// Memory limit (MiB RSS) for each agent phase process and its descendants (Linux).
+    /// Exceeding it kills the process group and fails the phase.
+    #[arg(long)]
+    pub phase_max_rss_mb: Option<u64>,
+
+    /// CPU-time limit (seconds) per agent phase process, via RLIMIT_CPU (Unix).
+    #[arg(long)]
+    pub phase_max_cpu_sec: Option<u64>,
+
+    /// Plan template variable, repeatable: `--var SERVICE_NAME=billing` replaces
+    /// `{{SERVICE_NAME}}` in the plan body. Overrides the same key from config or env.
+    #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_var)]
+    pub vars: Vec<(String, String)>,
+
+    /// After each task, ask the agent for a short summary of what it did (stored in the
+    /// run summary). One extra agent call per task. Default: false.
+    #[arg(long, default_value_t = false)]
+    pub summarize_tasks: bool,
+
+    /// Before the run, invoke the agent once with a trivial prompt and fail fast if it
+    /// is unreachable or misconfigured. Default: false.
+    #[arg(long, default_value_t = false)]
+    pub agent_health_check: bool,
+
+    /// Timeout in seconds for the agent health check. Default: 60.
+    #[arg(long)]
+    pub agent_health_check_timeout_sec: Option<u64>,
+
+    /// OTLP/HTTP collector endpoint (e.g. http://localhost:4318). When set, run, task,
+    /// phase, and stet spans are exported as OpenTelemetry traces.
+    #[arg(long)]
+    pub otel_endpoint: Option<String>,
+
+    /// Run `stet run --output=json --stream` and log NDJSON findings as they arrive.
+    /// Default: false.
+    #[arg(long, default_value_t = false)]
+    pub stet_stream: bool,
+
+    /// With --stet-stream, stop stet early once more than this many findings have
+    /// streamed in. When omitted, stet always runs to completion.
+    #[arg(long)]
+    pub stet_stream_abort_threshold: Option<u32>,
+
+    /// How post_run_commands and stet_commands run: "none" (default) splits on
+    /// whitespace with no shell; "sh" runs each entry via `sh -c`.
+    #[arg(long)]
+    pub command_shell: Option<String>,
+
+    /// When state records a stet session an earlier run never finished: "finish"
+    /// (default) runs `stet finish` before starting a new one; "reattach" reuses it.
+    #[arg(long)]
+    pub on_stale_stet_session: Option<String>,
+
+    /// Backend for text-only agent calls (Phase 1, normalization, triage, summaries):
+    /// "cli" (default) runs agent_cmd; "api" calls an OpenAI-compatible HTTP API.
+    #[arg(long)]
+    pub agent_backend: Option<String>,
+
+    /// Chat completions base URL for --agent-backend api (default https://api.openai.com/v1).
+    #[arg(long)]
+    pub api_base_url: Option<String>,
+
+    /// Environment variable holding the API key (default OPENAI_API_KEY).
+    #[arg(long)]
+    pub api_key_env: Option<String>,
+
+    /// Model for --agent-backend api (defaults to --model).
+    #[arg(long)]
+    pub api_model: Option<String>,
+
+    /// Agent command for the Phase 3 triage question only (defaults to --agent-cmd).
+    #[arg(long)]
+    pub triage_agent_cmd: Option<String>,
+
+    /// Model for the Phase 3 triage question only (defaults to --model / --api-model).
+    #[arg(long)]
+    pub triage_model: Option<String>,
+
+    /// Skip the startup git checks (git installed, repo is a git worktree), e.g. for
+    /// jujutsu repos. Git-based features such as commit_after_phase2 still need git.
+    #[arg(long, default_value_t = false)]
+    pub no_git_checks: bool,
+
+    /// With --continue-with-remaining-tasks, roll a failed sequential task back to the HEAD
+    /// recorded before it started (partial work is stashed, its commits kept under
+    /// refs/peal/failed/task-N).
+    #[arg(long, default_value_t = false)]
+    pub rollback_failed_tasks: bool,
+
+    /// Serve live run status over HTTP at this address (e.g. 127.0.0.1:7777): GET /status
+    /// returns JSON (state plus running task/phase), GET /events streams task and phase
+    /// events as Server-Sent Events.
+    #[arg(long, value_name = "ADDR")]
+    pub serve_status: Option<String>,
+
+    /// Barrier commands run after each `# Stage:` section completes (comma-separated);
+    /// `{stage}` expands to the stage name. A failing command stops the run.
+    #[arg(long)]
+    pub stage_commands: Option<String>,
+
+    /// Refuse (or, with --on-prompt-too-large warn, only warn about) agent prompts estimated
+    /// above this many tokens (~4 characters per token).
+    #[arg(long)]
+    pub max_prompt_tokens: Option<u64>,
+
+    /// When a prompt exceeds max_prompt_tokens: "fail" (default) or "warn".
+    #[arg(long)]
+    pub on_prompt_too_large: Option<String>,
+
+    /// Pass the files each task changed to `stet run` so Phase 3 reviews only that task's
+    /// changes (built-in stet phase only).
+    #[arg(long)]
+    pub stet_scope_to_task: bool,
+
+    /// Arguments added to `stet run` per changed file with --stet-scope-to-task; `{path}`
+    /// expands to the repo-relative path (e.g. "--path {path}"). Space-separated.
+    #[arg(long)]
+    pub stet_path_args: Option<String>,
+
+    /// Abort the run (state is saved) once this many agent invocations have been made.
+    #[arg(long)]
+    pub max_agent_calls: Option<u64>,
+
+    /// Run only this phase (1, 2, or 3) for the selected tasks, ignoring completion
+    /// state. Phase 2 uses plan text saved by an earlier Phase 1; Phase 3 reviews the
+    /// current tree. Developer mode; not layered from config or env.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=3))]
+    pub only_phase: Option<u8>,
+
+    /// With --continue-with-remaining-tasks, retry tasks that failed in a concurrent parallel
+    /// block (Phase 1/2) once more, one at a time, before the run ends.
+    #[arg(long, default_value_t = false)]
+    pub retry_failed_parallel_tasks: bool,
+
+    /// Dotenv-style file of `PEAL_*` variables, applied under the process environment
+    /// (variables already exported win).
+    #[arg(long)]
+    pub env_file: Option<PathBuf>,
+
+    /// After each segment whose tasks (and Phase 3) completed, `git push` the branch.
+    #[arg(long, default_value_t = false)]
+    pub push_after_segment: bool,
+
+    /// Remote for --push-after-segment (default "origin").
+    #[arg(long)]
+    pub push_remote: Option<String>,
+
+    /// Remote branch for --push-after-segment (default: the current branch's name).
+    #[arg(long)]
+    pub push_branch: Option<String>,
+
+    /// Behavior when a segment push fails: "warn" (default) or "fail".
+    #[arg(long)]
+    pub on_push_fail: Option<String>,
+
+    /// Cross-check Phase 2 against git: "off" (default), "warn", "retry" (once, with a firmer
+    /// prompt), or "fail" when the agent reports success but no files changed.
+    #[arg(long)]
+    pub phase2_change_check: Option<String>,
+
+    /// Models to escalate to, in order, when a phase fails or Phase 3 leaves findings
+    /// (comma-separated, e.g. "gpt-5.2,o3-pro").
+    #[arg(long)]
+    pub escalation_models: Option<String>,
+
+    /// Skip Phase 3 (stet review and address rounds) for this run, even when stet is found.
+    #[arg(long, visible_alias = "skip-review", conflicts_with = "only_phase")]
+    pub no_phase3: bool,
+
+    /// Run the plan as stet sessions of about N tasks each (own stet start/finish and
+    /// post-run commands per session).
+    #[arg(long)]
+    pub session_size: Option<u32>,
+
+    /// Parse the plan and print expected agent calls, tokens, duration, and cost per pending
+    /// task (rates from `model_rates`), then exit without invoking the agent or stet.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// With --parallel, run Phase 3 of a parallel block concurrently across its tasks; only
+    /// stet invocations take turns.
+    #[arg(long)]
+    pub parallel_phase3: bool,
+
+    /// Fail on a corrupt state file instead of moving it aside and recovering from the backup.
+    #[arg(long)]
+    pub strict_state: bool,
+
+    /// Log every agent prompt in full at info level.
+    #[arg(long)]
+    pub log_prompts: bool,
+
+    /// Agent stdout logged after each call: "none" (default), "truncated", or "full".
+    #[arg(long)]
+    pub log_agent_output: Option<String>,
+
+    /// Keep agent prompts and output out of the logs (log_prompts = false,
+    /// log_agent_output = "none"), overriding env and config file.
+    #[arg(long, conflicts_with_all = ["log_prompts", "log_agent_output"])]
+    pub quiet_agent: bool,
+
+    /// On resume, what to do with completed tasks whose content changed since they ran
+    /// (e.g. the plan was renumbered): "rerun" (default) runs them again; "trust" keeps them done.
+    #[arg(long)]
+    pub on_renumber: Option<String>,
+
+    /// Version control system of the repo: "auto" (default; detects jj and Sapling, else git),
+    /// "git", "jj", or "sapling".
+    #[arg(long)]
+    pub vcs: Option<String>,
+
+    /// Command that checks the stet session before each task's Phase 3 (e.g. "stet status");
+    /// when it fails, `stet start` runs again.
+    #[arg(long)]
+    pub stet_health_command: Option<String>,
+
+    /// Scope the run to this subdirectory of the repo (e.g. services/payments): agent
+    /// workspace, peal-verify commands, and Phase 2 change checks. Stet and commits still use
+    /// the repo root.
+    #[arg(long, value_name = "PATH")]
+    pub repo_subdir: Option<PathBuf>,
+
+    /// Seconds a cached Phase 3 triage answer stays valid (default 86400; 0 disables the cache).
+    #[arg(long, value_name = "SECS")]
+    pub triage_cache_ttl_sec: Option<u64>,
+
+    /// Do not read or write the triage cache ({state_dir}/cache/triage) for this run.
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Commands run before each task's Phase 2 (comma-separated); {{TASK_INDEX}} expands to
+    /// the task index.
+    #[arg(long)]
+    pub pre_phase2_commands: Option<String>,
+
+    /// Commands run after each task's Phase 2 succeeds (comma-separated); {{TASK_INDEX}}
+    /// expands to the task index.
+    #[arg(long)]
+    pub post_phase2_commands: Option<String>,
+
+    /// What a failing Phase 2 hook command does: "fail" (default) fails the task, "warn" logs it.
+    #[arg(long)]
+    pub on_phase_hook_fail: Option<String>,
+
+    /// Put a summary of stet's review with the top N findings in a "findings remain" error
+    /// instead of stet's full output (which is saved under the run directory).
+    #[arg(long)]
+    pub findings_summary_top_n: Option<u32>,
+
+    /// Run each task's Phase 2 in a sparse worktree limited to its (dir: ...) and (context: ...)
+    /// paths, then apply the edits back to the repo. Tasks without such paths use the full checkout.
+    #[arg(long)]
+    pub sparse_checkout: bool,
+
+    /// Write this run's manifest (agent and stet versions, model, prompt and plan hashes) to
+    /// this file at run start.
+    #[arg(long)]
+    pub lock_path: Option<PathBuf>,
+
+    /// Fail before any task runs when the run's manifest differs from the one at lock_path,
+    /// instead of rewriting it. Not layered from config or env.
+    #[arg(long)]
+    pub locked: bool,
+
+    /// Print the merged config with the source of each value (like `peal config show
+    /// --resolved`) and exit without running.
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Phase 3 of a parallel block: "task" (default) reviews each task, "block" reviews the
+    /// whole block once after all its tasks ran Phase 2.
+    #[arg(long)]
+    pub phase3_scope: Option<String>,
+
+    /// What a failing `expect:` line of a task does: "fail" (default) fails the task, "warn"
+    /// logs it and goes on.
+    #[arg(long)]
+    pub on_expect_fail: Option<String>,
+
+    /// Most stet findings one triage pass may dismiss without confirmation; above it peal asks
+    /// on an interactive terminal and otherwise addresses every finding. Unset: no limit.
+    #[arg(long)]
+    pub dismiss_requires_confirmation: Option<u32>,
+
+    /// Line Phase 2 must end its answer with; `{{TASK_INDEX}}` is replaced by the task index
+    /// (e.g. "PEAL_TASK_DONE: {{TASK_INDEX}}"). Unset: no marker.
+    #[arg(long)]
+    pub done_marker: Option<String>,
+
+    /// What a Phase 2 answer without the done marker does: "retry" (default) runs Phase 2
+    /// once more, "fail" fails the task.
+    #[arg(long)]
+    pub on_missing_done_marker: Option<String>,
+
+    /// Severities whose stet findings triage never dismisses (comma-separated, e.g. "error,critical").
+    #[arg(long)]
+    pub triage_skip_severities: Option<String>,
+
+    /// Seconds to save state, write the run summary, and flush logs after SIGTERM/SIGINT/SIGHUP
+    /// before exiting anyway (default 10; 0 exits right away).
+    #[arg(long, value_name = "SECS")]
+    pub shutdown_grace_sec: Option<u64>,
+
+    /// Agent attempts at resolving conflicts when a sparse worktree's changes no longer apply to
+    /// the repo (default 1, at most 3; 0 fails the task right away).
+    #[arg(long, value_name = "N")]
+    pub merge_conflict_retry_count: Option<u32>,
+
+    /// Run in the background, detached from the terminal: logs go to {state_dir}/detach.log.
+    /// Follow with `peal attach`; stop gracefully with `peal stop`. Unix only.
+    #[arg(long, conflicts_with = "dry_run")]
+    pub detach: bool,
+
+    /// Trial run: run only the first N pending tasks in schedule order, then stop with state
+    /// saved. The run summary is marked `partial_run`. Not layered from config or env.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "only_phase")]
+    pub limit_tasks: Option<u32>,
+
+    /// Before starting a new stet session, run `stet finish` for the session an earlier run
+    /// left open (e.g. its `stet finish` failed); the run stops if it fails again. Not layered
+    /// from config or env.
+    #[arg(long)]
+    pub cleanup_stet: bool,
+}
+
+/// Parse a `--var` argument of the form `KEY=VALUE` (KEY: letters, digits, `_`; not starting with a digit).
+fn parse_var(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{s}'"))?;
+    let key = key.trim();
+    let valid = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("invalid variable name '{key}'"));
+    }
+    Ok((key.to_owned(), value.to_owned()))
 }
 
 #[cfg(test)]
@@ -196,7 +855,7 @@ mod tests {
                 assert_eq!(args.plan, Some(PathBuf::from("tasks.md")));
                 assert_eq!(args.repo, Some(PathBuf::from("/tmp/repo")));
             }
-            Commands::Prompt(_) => unreachable!("test uses run subcommand"),
+            _ => unreachable!("test uses run subcommand"),
         }
     }
 
@@ -210,7 +869,7 @@ mod tests {
                 assert_eq!(args.plan, None);
                 assert_eq!(args.repo, None);
             }
-            Commands::Prompt(_) => unreachable!("test uses run subcommand"),
+            _ => unreachable!("test uses run subcommand"),
         }
     }
 
@@ -280,7 +939,7 @@ mod tests {
                 assert_eq!(args.post_run_timeout_sec, Some(90));
                 assert!(args.normalize);
             }
-            Commands::Prompt(_) => unreachable!("test uses run subcommand"),
+            _ => unreachable!("test uses run subcommand"),
         }
     }
 
@@ -300,7 +959,7 @@ mod tests {
 
         match cli.command {
             Commands::Run(args) => assert_eq!(args.normalize_retry_count, Some(2)),
-            Commands::Prompt(_) => unreachable!("test uses run subcommand"),
+            _ => unreachable!("test uses run subcommand"),
         }
     }
 
@@ -320,7 +979,7 @@ mod tests {
 
         match cli.command {
             Commands::Run(args) => assert_eq!(args.phase_3_retry_count, Some(1)),
-            Commands::Prompt(_) => unreachable!("test uses run subcommand"),
+            _ => unreachable!("test uses run subcommand"),
         }
     }
 
@@ -344,7 +1003,7 @@ mod tests {
                 assert!(args.validate_plan_text);
                 assert_eq!(args.min_plan_text_len, Some(500));
             }
-            Commands::Prompt(_) => unreachable!("test uses run subcommand"),
+            _ => unreachable!("test uses run subcommand"),
         }
     }
 
@@ -357,11 +1016,13 @@ mod tests {
 
         match cli.command {
             Commands::Run(args) => {
-                assert_eq!(args.task, Some(5));
+                assert_eq!(args.tasks, vec![5]);
                 assert_eq!(args.from_task, None);
             }
-            Commands::Prompt(_) => unreachable!("test uses run subcommand"),
+            _ => unreachable!("test uses run subcommand"),
         }
+        let cli = Cli::try_parse_from(["peal", "run", "--task", "2", "--task", "7"]).unwrap();
+        assert!(matches!(cli.command, Commands::Run(args) if args.tasks == [2, 7]));
     }
 
     #[test]
@@ -380,13 +1041,22 @@ mod tests {
 
         match cli.command {
             Commands::Run(args) => {
-                assert_eq!(args.task, None);
+                assert!(args.tasks.is_empty());
                 assert_eq!(args.from_task, Some(3));
             }
-            Commands::Prompt(_) => unreachable!("test uses run subcommand"),
+            _ => unreachable!("test uses run subcommand"),
         }
     }
 
+    #[test]
+    fn force_rerun_requires_task_or_from_task() {
+        let base = ["peal", "run", "--plan", "p.md", "--repo", "/r", "--force-rerun"];
+        assert!(Cli::try_parse_from(base).is_err());
+        let cli = Cli::try_parse_from(base.iter().copied().chain(["--task", "3"])).unwrap();
+        assert!(matches!(cli.command, Commands::Run(args) if args.force_rerun && args.tasks == [3]));
+        assert!(Cli::try_parse_from(base.iter().copied().chain(["--from-task", "2"])).is_ok());
+    }
+
     #[test]
     fn task_and_from_task_conflict() {
         let result = Cli::try_parse_from([
@@ -459,7 +1129,299 @@ mod tests {
 
         match cli.command {
             Commands::Run(args) => assert_eq!(args.max_consecutive_task_failures, Some(3)),
-            Commands::Prompt(_) => unreachable!("test uses run subcommand"),
+            _ => unreachable!("test uses run subcommand"),
+        }
+    }
+
+
+    #[test]
+    fn only_phase_flag_parses_and_rejects_out_of_range() {
+        let cli = Cli::try_parse_from([
+            "peal", "run", "--plan", "p.md", "--repo", "/r", "--only-phase", "3",
+        ])
+        .expect("should parse --only-phase");
+
+        match cli.command {
+            Commands::Run(args) => assert_eq!(args.only_phase, Some(3)),
+            _ => unreachable!("test uses run subcommand"),
+        }
+
+        for bad in ["0", "4"] {
+            let result = Cli::try_parse_from([
+                "peal", "run", "--plan", "p.md", "--repo", "/r", "--only-phase", bad,
+            ]);
+            assert!(result.is_err(), "--only-phase {bad} should be rejected");
+        }
+    }
+
+
+    #[test]
+    fn no_config_flag_parses_and_conflicts_with_config() {
+        let cli = Cli::try_parse_from(["peal", "run", "--no-config"]).expect("should parse --no-config");
+        match cli.command {
+            Commands::Run(args) => assert!(args.no_config),
+            _ => unreachable!("test uses run subcommand"),
+        }
+
+        let err = Cli::try_parse_from(["peal", "run", "--no-config", "--config", "peal.toml"])
+            .expect_err("--no-config and --config should conflict");
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+
+    #[test]
+    fn quiet_agent_flag_conflicts_with_agent_logging_flags() {
+        let cli = Cli::try_parse_from(["peal", "run", "--quiet-agent"]).expect("should parse --quiet-agent");
+        match cli.command {
+            Commands::Run(args) => assert!(args.quiet_agent),
+            _ => unreachable!("test uses run subcommand"),
+        }
+
+        for other in [&["--log-prompts"][..], &["--log-agent-output", "full"][..]] {
+            let mut argv = vec!["peal", "run", "--quiet-agent"];
+            argv.extend_from_slice(other);
+            let err = Cli::try_parse_from(argv).expect_err("--quiet-agent should conflict");
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        }
+    }
+
+    #[test]
+    fn detach_attach_and_stop_parse() {
+        let cli = Cli::try_parse_from(["peal", "run", "--detach"]).expect("should parse --detach");
+        assert!(matches!(cli.command, Commands::Run(args) if args.detach));
+        let err = Cli::try_parse_from(["peal", "run", "--detach", "--dry-run"]).expect_err("--detach should conflict");
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+
+        let cli = Cli::try_parse_from(["peal", "attach", "--state-dir", "s"]).expect("should parse attach");
+        assert!(matches!(cli.command, Commands::Attach(args) if args.state_dir == Some(PathBuf::from("s"))));
+        let cli = Cli::try_parse_from(["peal", "stop"]).expect("should parse stop");
+        assert!(matches!(cli.command, Commands::Stop(args) if args.state_dir.is_none()));
+    }
+
+    #[test]
+    fn limit_tasks_must_be_positive() {
+        let cli = Cli::try_parse_from(["peal", "run", "--limit-tasks", "2"]).expect("should parse --limit-tasks");
+        assert!(matches!(cli.command, Commands::Run(args) if args.limit_tasks == Some(2)));
+        assert!(Cli::try_parse_from(["peal", "run", "--limit-tasks", "0"]).is_err());
+    }
+
+    #[test]
+    fn config_show_takes_run_options() {
+        let cli = Cli::try_parse_from(["peal", "config", "show", "--resolved", "--max-parallel", "2"])
+            .expect("should parse config show");
+        match cli.command {
+            Commands::Config(ConfigArgs { command: ConfigCommand::Show(show) }) => {
+                assert!(show.resolved);
+                assert_eq!(show.run.max_parallel, Some(2));
+            }
+            _ => unreachable!("test uses config show"),
+        }
+    }
+
+    #[test]
+    fn diff_defaults_conflicts_with_resolved() {
+        let cli = Cli::try_parse_from(["peal", "config", "show", "--diff-defaults"]).expect("should parse");
+        assert!(matches!(cli.command, Commands::Config(ConfigArgs { command: ConfigCommand::Show(show) }) if show.diff_defaults));
+        assert!(Cli::try_parse_from(["peal", "config", "show", "--diff-defaults", "--resolved"]).is_err());
+    }
+
+    #[test]
+    fn var_flag_repeats_and_validates_key() {
+        let cli = Cli::try_parse_from([
+            "peal", "run", "--var", "SERVICE_NAME=billing", "--var", "URL=http://x?a=b",
+        ])
+        .expect("should parse --var");
+        match cli.command {
+            Commands::Run(args) => assert_eq!(
+                args.vars,
+                vec![
+                    ("SERVICE_NAME".to_owned(), "billing".to_owned()),
+                    ("URL".to_owned(), "http://x?a=b".to_owned()),
+                ]
+            ),
+            _ => unreachable!("test uses run subcommand"),
+        }
+
+        for bad in ["NOEQUALS", "1X=v", "a-b=v", "=v"] {
+            assert!(
+                Cli::try_parse_from(["peal", "run", "--var", bad]).is_err(),
+                "--var {bad} should be rejected"
+            );
+        }
+    }
+
+
+    #[test]
+    fn state_reset_parses_tasks_or_from_task() {
+        let cli = Cli::try_parse_from(["peal", "state", "reset", "--task", "4", "--task", "7"])
+            .expect("should parse repeated --task");
+        match cli.command {
+            Commands::State(StateArgs {
+                command: StateCommand::Reset(args),
+            }) => {
+                assert_eq!(args.tasks, vec![4, 7]);
+                assert_eq!(args.from_task, None);
+            }
+            _ => unreachable!("test uses state subcommand"),
+        }
+
+        let cli = Cli::try_parse_from(["peal", "state", "reset", "--from-task", "4", "--state-dir", "s"])
+            .expect("should parse --from-task");
+        match cli.command {
+            Commands::State(StateArgs {
+                command: StateCommand::Reset(args),
+            }) => {
+                assert_eq!(args.from_task, Some(4));
+                assert_eq!(args.state_dir, Some(PathBuf::from("s")));
+            }
+            _ => unreachable!("test uses state subcommand"),
+        }
+    }
+
+    #[test]
+    fn state_reset_requires_exactly_one_selection() {
+        assert!(Cli::try_parse_from(["peal", "state", "reset"]).is_err());
+        let err = Cli::try_parse_from(["peal", "state", "reset", "--task", "1", "--from-task", "2"])
+            .expect_err("--task and --from-task should conflict");
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+
+    #[test]
+    fn tags_and_exclude_tags_accept_commas_and_repeats() {
+        let cli = Cli::try_parse_from([
+            "peal",
+            "run",
+            "--plan",
+            "p.md",
+            "--repo",
+            "/r",
+            "--tags",
+            "backend,db",
+            "--tags",
+            "api",
+            "--exclude-tags",
+            "slow",
+        ])
+        .expect("should parse --tags");
+
+        match cli.command {
+            Commands::Run(args) => {
+                assert_eq!(args.tags, vec!["backend", "db", "api"]);
+                assert_eq!(args.exclude_tags, vec!["slow"]);
+            }
+            _ => unreachable!("test uses run subcommand"),
+        }
+    }
+
+
+    #[test]
+    fn diff_runs_takes_two_summary_paths() {
+        let cli = Cli::try_parse_from(["peal", "diff-runs", "old.json", "new.json"])
+            .expect("should parse diff-runs");
+        match cli.command {
+            Commands::DiffRuns(args) => {
+                assert_eq!(args.before, PathBuf::from("old.json"));
+                assert_eq!(args.after, PathBuf::from("new.json"));
+            }
+            _ => unreachable!("test uses diff-runs subcommand"),
+        }
+        assert!(Cli::try_parse_from(["peal", "diff-runs", "old.json"]).is_err());
+    }
+
+    #[test]
+    fn bench_takes_models_and_run_options() {
+        let cli = Cli::try_parse_from(["peal", "bench", "--models", "a,b", "--plan", "p.md", "--keep-worktrees"])
+            .expect("should parse bench");
+        match cli.command {
+            Commands::Bench(args) => {
+                assert_eq!(args.models, vec!["a", "b"]);
+                assert!(args.keep_worktrees);
+                assert_eq!(args.run.plan, Some(PathBuf::from("p.md")));
+            }
+            _ => unreachable!("test uses bench subcommand"),
+        }
+        assert!(Cli::try_parse_from(["peal", "bench", "--plan", "p.md"]).is_err());
+    }
+
+    #[test]
+    fn run_many_takes_plans_repos_and_run_options() {
+        let cli = Cli::try_parse_from([
+            "peal", "run-many", "--plans", "a.md", "b.md", "--repos", "rA,rB", "--max-concurrent-plans", "2",
+            "--model", "m",
+        ])
+        .expect("should parse run-many");
+        match cli.command {
+            Commands::RunMany(args) => {
+                assert_eq!(args.plans, [PathBuf::from("a.md"), PathBuf::from("b.md")]);
+                assert_eq!(args.repos, [PathBuf::from("rA"), PathBuf::from("rB")]);
+                assert_eq!(args.max_concurrent_plans, Some(2));
+                assert_eq!(args.run.model.as_deref(), Some("m"));
+            }
+            _ => unreachable!("test uses run-many subcommand"),
+        }
+        assert!(Cli::try_parse_from(["peal", "run-many", "--plans", "a.md"]).is_err());
+        assert!(Cli::try_parse_from(["peal", "run-many", "--plans", "a.md", "--repos", "r", "--max-concurrent-plans", "0"]).is_err());
+    }
+
+    #[test]
+    fn plan_graph_defaults_to_mermaid_and_rejects_unknown_formats() {
+        let cli = Cli::try_parse_from(["peal", "plan", "graph", "plan.md"])
+            .expect("should parse plan graph");
+        match cli.command {
+            Commands::Plan(PlanArgs { command: PlanCommand::Graph(args) }) => {
+                assert_eq!(args.plan, PathBuf::from("plan.md"));
+                assert_eq!(args.format, "mermaid");
+                assert!(args.output.is_none());
+            }
+            _ => unreachable!("test uses plan graph subcommand"),
+        }
+        assert!(Cli::try_parse_from(["peal", "plan", "graph", "plan.md", "--format", "dot"]).is_ok());
+        assert!(Cli::try_parse_from(["peal", "plan", "graph", "plan.md", "--format", "svg"]).is_err());
+    }
+
+    #[test]
+    fn plan_convert_requires_to_and_accepts_known_formats() {
+        let cli = Cli::try_parse_from(["peal", "plan", "convert", "plan.md", "--to", "yaml"])
+            .expect("should parse plan convert");
+        match cli.command {
+            Commands::Plan(PlanArgs { command: PlanCommand::Convert(args) }) => {
+                assert_eq!(args.input, PathBuf::from("plan.md"));
+                assert!(args.from.is_none());
+                assert_eq!(args.to, "yaml");
+            }
+            _ => unreachable!("test uses plan convert subcommand"),
+        }
+        assert!(Cli::try_parse_from(["peal", "plan", "convert", "plan.md"]).is_err());
+        assert!(Cli::try_parse_from(["peal", "plan", "convert", "p.yaml", "--from", "yaml", "--to", "md"]).is_ok());
+        assert!(Cli::try_parse_from(["peal", "plan", "convert", "p.md", "--to", "toml"]).is_err());
+    }
+
+    #[test]
+    fn init_parses_defaults_and_flags() {
+        let cli = Cli::try_parse_from(["peal", "init"]).expect("should parse init");
+        match cli.command {
+            Commands::Init(args) => {
+                assert_eq!(args.dir, PathBuf::from("."));
+                assert!(args.agent_cmd.is_none() && args.plan.is_none());
+                assert!(!args.yes && !args.force && !args.no_example_plan);
+            }
+            _ => unreachable!("test uses init subcommand"),
+        }
+
+        let cli = Cli::try_parse_from([
+            "peal", "init", "--dir", "repo", "--agent-cmd", "cursor-agent", "--plan", "p.md", "-y",
+            "--force", "--no-example-plan",
+        ])
+        .expect("should parse init flags");
+        match cli.command {
+            Commands::Init(args) => {
+                assert_eq!(args.dir, PathBuf::from("repo"));
+                assert_eq!(args.agent_cmd.as_deref(), Some("cursor-agent"));
+                assert_eq!(args.plan, Some(PathBuf::from("p.md")));
+                assert!(args.yes && args.force && args.no_example_plan);
+            }
+            _ => unreachable!("test uses init subcommand"),
         }
     }
 }
@@ -0,0 +1,167 @@
+//! Wall-clock jump detection: host suspend/resume and clock changes during a run.
+//!
+//! [`Stamp`] pairs a monotonic [`Instant`] with the wall-clock time. The monotonic clock
+//! does not advance while the host is suspended (Linux `CLOCK_MONOTONIC`, macOS uptime), so
+//! when wall-clock time between two stamps exceeds monotonic time by more than
+//! [`JUMP_THRESHOLD`], the process was frozen (laptop lid closed) or the clock was set
+//! forward; falling behind means it was set back. Such gaps often explain agent timeouts
+//! and dropped connections that are otherwise a mystery.
+//!
+//! [`start_heartbeat`] compares stamps every [`HEARTBEAT_INTERVAL`] for the whole run and
+//! records each jump process-wide, for the run summary ([`jumps`]). The runner also stamps
+//! each phase and calls [`check_phase`] at its end, which names the phase in a warning.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::estimate::format_duration;
+
+/// Wall-clock and monotonic time may drift apart by this much before it counts as a jump.
+pub const JUMP_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// How often the heartbeat compares the two clocks.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A point in time on both clocks.
+#[derive(Debug, Clone, Copy)]
+pub struct Stamp {
+    pub mono: Instant,
+    pub wall: SystemTime,
+}
+
+impl Stamp {
+    pub fn now() -> Self {
+        Self {
+            mono: Instant::now(),
+            wall: SystemTime::now(),
+        }
+    }
+
+    /// Monotonic time since this stamp (what timeouts and phase durations use).
+    pub fn elapsed(&self) -> Duration {
+        self.mono.elapsed()
+    }
+}
+
+/// A gap between the clocks seen between two stamps, as reported in the run summary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockJump {
+    /// RFC 3339 wall-clock time of the earlier stamp.
+    pub from: String,
+    /// RFC 3339 wall-clock time of the later stamp.
+    pub to: String,
+    /// Wall-clock time minus monotonic time between them, in seconds: positive when the
+    /// host was suspended or the clock was set forward, negative when it was set back.
+    pub gap_sec: i64,
+}
+
+impl ClockJump {
+    /// One-line description for logs and the end of the run, e.g. "this run was suspended
+    /// (or the clock jumped forward) for 3h00m, from … to …".
+    pub fn describe(&self) -> String {
+        let amount = format_duration(self.gap_sec.unsigned_abs() as f64);
+        if self.gap_sec > 0 {
+            format!("this run was suspended (or the clock jumped forward) for {amount}, from {} to {}", self.from, self.to)
+        } else {
+            format!("the wall clock was set back by {amount} at {}", self.to)
+        }
+    }
+}
+
+/// The jump between `earlier` and `later`, when the clocks drifted apart by more than
+/// [`JUMP_THRESHOLD`].
+pub fn jump_between(earlier: &Stamp, later: &Stamp) -> Option<ClockJump> {
+    let mono_ms = i128::try_from(later.mono.saturating_duration_since(earlier.mono).as_millis()).unwrap_or(i128::MAX);
+    let wall_ms = match later.wall.duration_since(earlier.wall) {
+        Ok(d) => i128::try_from(d.as_millis()).unwrap_or(i128::MAX),
+        Err(e) => -i128::try_from(e.duration().as_millis()).unwrap_or(i128::MAX),
+    };
+    let gap_ms = wall_ms - mono_ms;
+    if gap_ms.unsigned_abs() <= JUMP_THRESHOLD.as_millis() {
+        return None;
+    }
+    Some(ClockJump {
+        from: DateTime::<Utc>::from(earlier.wall).to_rfc3339(),
+        to: DateTime::<Utc>::from(later.wall).to_rfc3339(),
+        gap_sec: i64::try_from(gap_ms / 1000).unwrap_or(i64::MAX),
+    })
+}
+
+static JUMPS: Mutex<Vec<ClockJump>> = Mutex::new(Vec::new());
+
+/// Jumps the heartbeat has recorded so far in this process.
+pub fn jumps() -> Vec<ClockJump> {
+    JUMPS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn record(jump: ClockJump) {
+    warn!(gap_sec = jump.gap_sec, from = %jump.from, to = %jump.to, "{}", jump.describe());
+    JUMPS.lock().unwrap_or_else(|e| e.into_inner()).push(jump);
+}
+
+/// Start the heartbeat thread (once per process; later calls do nothing).
+pub fn start_heartbeat() {
+    static STARTED: OnceLock<()> = OnceLock::new();
+    STARTED.get_or_init(|| {
+        let spawned = std::thread::Builder::new().name("peal-clock".to_owned()).spawn(|| {
+            let mut last = Stamp::now();
+            loop {
+                std::thread::sleep(HEARTBEAT_INTERVAL);
+                let now = Stamp::now();
+                if let Some(jump) = jump_between(&last, &now) {
+                    record(jump);
+                }
+                last = now;
+            }
+        });
+        if let Err(e) = spawned {
+            warn!(err = %e, "could not start the clock heartbeat; suspends will not be reported");
+        }
+    });
+}
+
+/// Warn when the wall clock ran away from the monotonic clock while `phase` of
+/// `task_index` ran since `start`, so a timeout or failure in it can be read in that light.
+pub fn check_phase(task_index: u32, phase: u32, start: &Stamp) {
+    if let Some(jump) = jump_between(start, &Stamp::now()) {
+        warn!(
+            task_index,
+            phase,
+            gap_sec = jump.gap_sec,
+            "phase {phase} of task {task_index}: {}; agent timeouts or dropped connections in it may come from that",
+            jump.describe()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jump_between_reports_wall_clock_drift_past_the_threshold() {
+        let earlier = Stamp::now();
+        let later = |mono_secs: u64, wall_secs: i64| Stamp {
+            mono: earlier.mono + Duration::from_secs(mono_secs),
+            wall: if wall_secs >= 0 {
+                earlier.wall + Duration::from_secs(wall_secs as u64)
+            } else {
+                earlier.wall - Duration::from_secs(wall_secs.unsigned_abs())
+            },
+        };
+        assert_eq!(jump_between(&earlier, &later(10, 10)), None);
+        assert_eq!(jump_between(&earlier, &later(10, 70)), None);
+
+        let suspended = jump_between(&earlier, &later(10, 10 + 3 * 3600)).unwrap();
+        assert_eq!(suspended.gap_sec, 3 * 3600);
+        assert!(suspended.describe().starts_with("this run was suspended (or the clock jumped forward) for 3h00m"), "{}", suspended.describe());
+
+        let set_back = jump_between(&earlier, &later(10, -600)).unwrap();
+        assert_eq!(set_back.gap_sec, -610);
+        assert!(set_back.describe().starts_with("the wall clock was set back by 10m10s"), "{}", set_back.describe());
+    }
+}
@@ -1,12 +1,13 @@
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::cli::RunArgs;
 use crate::error::PealError;
+use crate::plan::PlanMetadata;
 
 // Precedence: CLI > env > file > defaults.
 
@@ -21,6 +22,31 @@ const DEFAULT_PHASE_3_RETRY_COUNT: u32 = 0;
 const DEFAULT_NORMALIZE_RETRY_COUNT: u32 = 0;
 const DEFAULT_MAX_PARALLEL: u32 = 4;
 const DEFAULT_ON_STET_FAIL: &str = "fail";
+const DEFAULT_COMMAND_SHELL: &str = "none";
+const DEFAULT_ON_STALE_STET_SESSION: &str = "finish";
+const DEFAULT_PUSH_REMOTE: &str = "origin";
+const DEFAULT_ON_PUSH_FAIL: &str = "warn";
+const DEFAULT_PHASE2_CHANGE_CHECK: &str = "off";
+const DEFAULT_LOG_AGENT_OUTPUT: &str = "none";
+const DEFAULT_ON_RENUMBER: &str = "rerun";
+const DEFAULT_TRIAGE_CACHE_TTL_SEC: u64 = 86_400;
+const DEFAULT_ON_PHASE_HOOK_FAIL: &str = "fail";
+const DEFAULT_PHASE3_SCOPE: &str = "task";
+const DEFAULT_ON_EXPECT_FAIL: &str = "fail";
+const DEFAULT_ON_MISSING_DONE_MARKER: &str = "retry";
+const DEFAULT_SHUTDOWN_GRACE_SEC: u64 = 10;
+const DEFAULT_MERGE_CONFLICT_RETRY_COUNT: u32 = 1;
+const DEFAULT_AGENT_BACKEND: &str = "cli";
+const DEFAULT_API_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_API_KEY_ENV: &str = "OPENAI_API_KEY";
+const DEFAULT_ON_PROMPT_TOO_LARGE: &str = "fail";
+const DEFAULT_STET_PATH_ARG: &str = "{path}";
+const DEFAULT_LOG_KEEP_FILES: u32 = 5;
+const DEFAULT_AGENT_HEALTH_CHECK_TIMEOUT_SEC: u64 = 60;
+
+/// Config files picked up automatically when `--config` is not given, in lookup order,
+/// relative to the repo directory and then the current directory.
+pub const AUTO_CONFIG_FILES: [&str; 2] = ["peal.toml", ".peal/config.toml"];
 
 /// Valid dismiss reasons for stet (must match `stet dismiss <id> <reason>`).
 pub const STET_DISMISS_REASONS: [&str; 4] = [
@@ -31,7 +57,7 @@ pub const STET_DISMISS_REASONS: [&str; 4] = [
 ];
 
 /// One pattern to match finding message/path; when matched, dismiss with the given reason.
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct StetDismissPattern {
     pub pattern: String,
     pub reason: String,
@@ -44,12 +70,111 @@ impl StetDismissPattern {
     }
 }
 
+/// Rates for one model in the `--dry-run` estimate (`[model_rates."<model>"]` in TOML; the
+/// `default` entry applies to models without their own).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelRate {
+    /// USD per million prompt tokens.
+    #[serde(default)]
+    pub input_usd_per_mtok: f64,
+    /// USD per million generated tokens.
+    #[serde(default)]
+    pub output_usd_per_mtok: f64,
+    /// Generation speed, in output tokens per second.
+    #[serde(default = "default_output_tokens_per_sec")]
+    pub output_tokens_per_sec: f64,
+    /// Fixed time per agent call (startup, tool calls, edits, tests), in seconds.
+    #[serde(default = "default_call_overhead_sec")]
+    pub call_overhead_sec: f64,
+}
+
+fn default_output_tokens_per_sec() -> f64 {
+    50.0
+}
+
+fn default_call_overhead_sec() -> f64 {
+    30.0
+}
+
+impl Default for ModelRate {
+    fn default() -> Self {
+        Self {
+            input_usd_per_mtok: 0.0,
+            output_usd_per_mtok: 0.0,
+            output_tokens_per_sec: default_output_tokens_per_sec(),
+            call_overhead_sec: default_call_overhead_sec(),
+        }
+    }
+}
+
+/// Retention of per-run artifact directories (`[artifact_retention]` in TOML), enforced at
+/// run start by [`crate::run_id::prune_runs`]. Unset limits do not apply.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArtifactRetention {
+    /// Earlier runs to keep (the newest ones).
+    #[serde(default)]
+    pub keep_runs: Option<u32>,
+    /// Upper bound on the total size of all run directories, in MiB; the oldest earlier runs
+    /// go first.
+    #[serde(default)]
+    pub max_total_mb: Option<u64>,
+}
+
+/// One step of the prompt middleware chain (`[[prompt_middleware]]` in TOML, applied in file
+/// order; see [`crate::prompt_middleware`]).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PromptMiddlewareSpec {
+    /// Replace matches of these regexes with `[REDACTED]` in prompts and agent output.
+    Redact { patterns: Vec<String> },
+    /// Cut the middle out of prompts longer than `max_chars` characters.
+    MaxLength { max_chars: usize },
+    /// Put `text`, or the contents of `file` (relative to the repo), before every prompt.
+    Prefix {
+        #[serde(default)]
+        text: Option<String>,
+        #[serde(default)]
+        file: Option<PathBuf>,
+    },
+}
+
+/// Container execution environment (`[execution]` in TOML; see [`crate::container`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Execution {
+    /// Image agent and verify commands run in. Unset: they run on the host.
+    #[serde(default)]
+    pub container_image: Option<String>,
+    /// `docker` or `podman`. Default: `docker`.
+    #[serde(default)]
+    pub container_runtime: Option<String>,
+    /// Extra arguments for `<runtime> run`, before the image (e.g. `["-v", "/home/me/.cursor:/root/.cursor"]`).
+    #[serde(default)]
+    pub container_args: Vec<String>,
+    /// Host environment variables passed into the container (`-e NAME`).
+    #[serde(default)]
+    pub container_env: Vec<String>,
+    /// Run stet in the container too. Default: stet runs on the host.
+    #[serde(default)]
+    pub stet_in_container: bool,
+}
+
+impl Execution {
+    /// Container runtime binary: `container_runtime` or `docker`.
+    pub fn runtime(&self) -> &str {
+        self.container_runtime.as_deref().unwrap_or("docker")
+    }
+}
+
 const ENV_PREFIX: &str = "PEAL_";
 
 /// Resolved configuration for a PEAL run.
 ///
-/// Built from three layers with precedence CLI > env > file > defaults.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Built from layers with precedence CLI > env > plan sidecar ([`plan_sidecar_path`]) > file >
+/// plan front matter > defaults.
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct PealConfig {
     pub agent_cmd: String,
     pub plan_path: PathBuf,
@@ -71,6 +196,10 @@ pub struct PealConfig {
     pub continue_with_remaining_tasks: bool,
     pub log_level: Option<String>,
     pub log_file: Option<PathBuf>,
+    /// When set (and > 0), rotate log_file once it would exceed this many MiB. None = no rotation.
+    pub log_max_size_mb: Option<u64>,
+    /// Number of rotated log files to keep (`log_file.1` .. `log_file.N`). Default 5; 0 = truncate on rotation.
+    pub log_keep_files: u32,
     pub stet_path: Option<PathBuf>,
     pub stet_start_ref: Option<String>,
     /// Extra arguments passed through to `stet start` (e.g. `--allow-dirty`).
@@ -111,6 +240,223 @@ pub struct PealConfig {
     /// (stet only reviews committed changes). When stet is not installed, peal still commits so work is saved incrementally.
     /// Default false for backward compatibility.
     pub commit_after_phase2: bool,
+    /// When true, stet acts as a gate: if the first review has findings, the task fails immediately
+    /// (no triage, no address rounds), regardless of on_findings_remaining. Default false.
+    pub fail_fast_findings: bool,
+    /// Global budget for Cursor CLI invocations per minute, shared by all worker threads
+    /// (phases 1–3, triage, address, normalization). None or 0 = unlimited.
+    pub agent_max_requests_per_min: Option<u32>,
+    /// Per-invocation memory limit (MiB RSS) for agent phase processes and their descendants.
+    /// Enforced on Linux by a watchdog that kills the process group. None = unlimited.
+    pub phase_max_rss_mb: Option<u64>,
+    /// Per-process CPU-time limit (seconds) for agent phase processes, via `RLIMIT_CPU` on Unix.
+    /// None = unlimited.
+    pub phase_max_cpu_sec: Option<u64>,
+    /// Plan template variables: `{{NAME}}` in the plan body is replaced by the value before
+    /// parsing. Merged key-by-key across layers (CLI `--var` > env `VARS` > `[vars]` table).
+    pub vars: BTreeMap<String, String>,
+    /// When true, after each task the agent writes a 2–3 sentence summary of what it did
+    /// (from Phase 2 stdout and the diff), stored in the task result and run summary. Default false.
+    pub summarize_tasks: bool,
+    /// When true, ping the agent with a trivial prompt before the run and fail fast if it does
+    /// not answer sanely within `agent_health_check_timeout_sec`. Default false.
+    pub agent_health_check: bool,
+    /// Timeout in seconds for the agent health check. Default 60.
+    pub agent_health_check_timeout_sec: u64,
+    /// OTLP/HTTP collector URL (e.g. `http://localhost:4318`). When set, run, task, phase, and
+    /// stet spans are exported as OpenTelemetry traces. Only `http://` is supported.
+    pub otel_endpoint: Option<String>,
+    /// When true, invoke `stet run --output=json --stream` and consume NDJSON findings as they arrive,
+    /// logging each one live. Default false (single JSON document).
+    pub stet_stream: bool,
+    /// With `stet_stream`, stop stet early once more than this many findings have streamed in;
+    /// the partial findings are then addressed as usual. `None` (default) never aborts.
+    pub stet_stream_abort_threshold: Option<u32>,
+    /// How `post_run_commands` and `stet_commands` entries are executed: "none" (default) splits
+    /// the string on whitespace and runs it without a shell; "sh" runs it via `sh -c`.
+    pub command_shell: String,
+    /// What to do when state records a stet session an earlier run started but never finished:
+    /// "finish" (default) runs `stet finish` before `stet start`; "reattach" reuses that session.
+    pub on_stale_stet_session: String,
+    /// Backend for text-only agent calls (Phase 1, plan normalization, triage, task summaries): "cli"
+    /// (default) runs `agent_cmd`; "api" POSTs to an OpenAI-compatible chat completions endpoint.
+    /// Phases 2 and 3 always use `agent_cmd`, since they need tool use.
+    pub agent_backend: String,
+    /// Base URL of the chat completions API for `agent_backend = "api"`; `/chat/completions` is
+    /// appended. Default `https://api.openai.com/v1`.
+    pub api_base_url: String,
+    /// Name of the environment variable holding the API key (sent as a bearer token).
+    /// Default `OPENAI_API_KEY`. The key itself is never read from config files.
+    pub api_key_env: String,
+    /// Model name sent to the API. Falls back to `model`; one of the two is required with
+    /// `agent_backend = "api"`.
+    pub api_model: Option<String>,
+    /// Agent command for the Phase 3 triage question only (name on PATH or path). Falls back to
+    /// `agent_cmd` when unset, so a cheaper or faster binary can answer triage.
+    pub triage_agent_cmd: Option<String>,
+    /// Model for the Phase 3 triage question (`--model` for the CLI agent, or the API model with
+    /// `agent_backend = "api"`). Falls back to `model` / `api_model` when unset.
+    pub triage_model: Option<String>,
+    /// When true, skip the startup git checks (git on PATH, `repo_path` inside a git worktree) for
+    /// environments where git is missing or the repo is managed by another VCS (e.g. jujutsu).
+    pub no_git_checks: bool,
+    /// When true, a sequential task that fails while `continue_with_remaining_tasks` is set is rolled
+    /// back to the HEAD recorded before it started: its uncommitted work is stashed and any commits it
+    /// made are kept under `refs/peal/failed/task-<N>`. Default false.
+    pub rollback_failed_tasks: bool,
+    /// Address (`host:port`) for the live status HTTP server (`/status`, `/events`); off when unset.
+    pub serve_status: Option<String>,
+    /// Barrier commands run after each `# Stage:` section of the plan completes; `{stage}` expands to the stage name.
+    pub stage_commands: Vec<String>,
+    /// Estimated prompt size (tokens, ~4 characters each) above which a phase is not invoked; unset = no limit.
+    pub max_prompt_tokens: Option<u64>,
+    /// What to do when a prompt exceeds `max_prompt_tokens`: "fail" (default) or "warn" (invoke anyway).
+    pub on_prompt_too_large: String,
+    /// When true, built-in Phase 3 passes the files the current task changed to `stet run` (see
+    /// `stet_path_args`) so stet reviews only that task's changes. Default false.
+    pub stet_scope_to_task: bool,
+    /// Arguments appended to `stet run` for each changed file with `stet_scope_to_task`;
+    /// `{path}` expands to the repo-relative path. Default `["{path}"]` (positional paths).
+    pub stet_path_args: Vec<String>,
+    /// Hard cap on agent invocations per run (phases, triage, normalization, summaries); the run
+    /// aborts with `AgentCallCapReached` instead of making call N+1. Unset = no cap.
+    pub max_agent_calls: Option<u64>,
+    /// With `continue_with_remaining_tasks`, retry tasks whose Phase 1 or 2 failed in a concurrent
+    /// parallel block once more, sequentially, at the end of the run. Default false.
+    pub retry_failed_parallel_tasks: bool,
+    /// Dotenv-style file whose `PEAL_*` entries fill in the env layer for variables not set in the
+    /// process environment (`KEY=VALUE` lines, `#` comments, optional `export` and quotes).
+    pub env_file: Option<PathBuf>,
+    /// When true, `git push` the branch after each segment whose tasks (and Phase 3) completed,
+    /// so progress is backed up remotely during long runs.
+    pub push_after_segment: bool,
+    /// Remote for `push_after_segment` (default "origin").
+    pub push_remote: String,
+    /// Remote branch for `push_after_segment`; when unset the current branch is pushed to the
+    /// branch of the same name.
+    pub push_branch: Option<String>,
+    /// Behavior when a `push_after_segment` push fails: "warn" (default; log and keep going) or
+    /// "fail" (stop the run).
+    pub on_push_fail: String,
+    /// Cross-check Phase 2 against git: "off" (default), "warn", "retry", or "fail" when the agent
+    /// reports success but no files changed (or none of the files its transcript says it edited).
+    /// "retry" runs Phase 2 once more with a firmer prompt and fails the task if it is still a no-op.
+    pub phase2_change_check: String,
+    /// Models to fall back to, in order, when a phase fails with an agent error or Phase 3 leaves
+    /// findings after its address rounds: the phase is retried once with each until one succeeds.
+    pub escalation_models: Vec<String>,
+    /// When false, Phase 3 (stet review and address rounds) is skipped for the whole run, even when
+    /// stet is installed or `stet_commands` are set.
+    pub enable_phase3: bool,
+    /// Split the plan into stet sessions of about this many tasks (parallel blocks are kept whole):
+    /// each session gets its own `stet start` / `stet finish` and post-run commands. Unset or 0 = one session.
+    pub session_size: Option<u32>,
+    /// Per-model rates for the `--dry-run` estimate, keyed by model name (`default` for the rest).
+    /// Config file only.
+    pub model_rates: BTreeMap<String, ModelRate>,
+    /// When true (with `parallel`), Phase 3 of a parallel block runs concurrently across its tasks
+    /// (up to `max_parallel`): address-loop agent calls overlap while `stet run` / `stet dismiss`
+    /// invocations take turns. Default false (Phase 3 runs task by task after the block).
+    pub parallel_phase3: bool,
+    /// When true, a corrupt `state.json` fails the run instead of being moved aside and recovered
+    /// from `state.json.bak` (or a fresh start). Default false.
+    pub strict_state: bool,
+    /// When true, every agent prompt is logged in full at info level. Default false (argv is logged
+    /// at debug with the prompt replaced by its length).
+    pub log_prompts: bool,
+    /// How much agent stdout is logged at info level after each call: "none" (default), "truncated"
+    /// (first `phase::AGENT_OUTPUT_LOG_BYTES` bytes), or "full". State, artifacts, and the run summary
+    /// keep the full output either way.
+    pub log_agent_output: String,
+    /// What to do on resume when a completed task's content no longer matches the hash recorded
+    /// when it completed (e.g. a task was inserted and the plan renumbered): "rerun" (default) marks
+    /// those tasks incomplete again; "trust" keeps them completed. Both warn.
+    pub on_renumber: String,
+    /// Version control system of the target repository (see [`crate::vcs`]): "auto" (default)
+    /// detects it from the repo (a `.jj` or `.sl` directory, otherwise git); "git", "jj" (Jujutsu), or
+    /// "sapling" choose one explicitly.
+    pub vcs: String,
+    /// Command run (per `command_shell`, in the repo) before Phase 3 of each task to check that the
+    /// built-in stet session is still there: exit 0 means it is; any other exit code means it is gone and
+    /// peal runs `stet start` again under `on_stet_fail`. Unset (default): no check.
+    pub stet_health_command: Option<String>,
+    /// Which earlier runs' artifact directories (`{state_dir}/runs/<run id>`) to keep; older ones are
+    /// deleted at run start (`[artifact_retention]` in TOML). Default: keep everything.
+    pub artifact_retention: ArtifactRetention,
+    /// Subdirectory of `repo_path` the run is scoped to (monorepos): the agent's workspace and cwd,
+    /// `peal-verify` commands, and the Phase 2 change check use it (see [`Self::work_dir`]), while the
+    /// repository check, commits, and stet still use `repo_path`. Relative to `repo_path`; unset: the whole repo.
+    pub repo_subdir: Option<PathBuf>,
+    /// How long a cached Phase 3 triage answer stays valid, in seconds (see [`crate::triage_cache`]).
+    /// `0` turns the cache off. Default 86400 (one day).
+    pub triage_cache_ttl_sec: u64,
+    /// Skip the triage cache for this run: neither read nor write it.
+    pub no_cache: bool,
+    /// Commands run in the repo before each task's Phase 2 (per `command_shell`, with
+    /// `phase_timeout_sec` as the timeout); `{{TASK_INDEX}}` expands to the task index. A failure is
+    /// handled per `on_phase_hook_fail`.
+    pub pre_phase2_commands: Vec<String>,
+    /// Commands run in the repo after each task's Phase 2 succeeds, like `pre_phase2_commands`.
+    pub post_phase2_commands: Vec<String>,
+    /// What a failing `pre_phase2_commands` / `post_phase2_commands` entry does: "fail" (default)
+    /// fails the task; "warn" logs it and goes on.
+    pub on_phase_hook_fail: String,
+    /// Commands run in the repo at the end of each stet session when `stet_commands` are set, in
+    /// place of `stet finish` (best-effort; see [`crate::stet::expand_command`] for placeholders).
+    pub stet_finish_commands: Vec<String>,
+    /// When set, a `StetFindingsRemain` error carries a summary of stet's review (counts by
+    /// severity/category and the top N findings) instead of its full output, which is kept in
+    /// the run directory (see [`crate::stet::summarize_review`]). Unset (default) embeds the
+    /// full output.
+    pub findings_summary_top_n: Option<u32>,
+    /// Run each task's Phase 2 in a sparse git worktree holding only the task's `(dir: ...)`
+    /// directories and the directories of its `(context: ...)` files; the edits are applied back to
+    /// the repo afterwards. Tasks without such paths, and any setup failure, use the full checkout
+    /// (see [`crate::sparse`]).
+    pub sparse_checkout: bool,
+    /// Where each run writes its manifest (agent and stet versions, model, prompt and plan
+    /// hashes; see [`crate::lock`]). `--locked` checks a run against it instead. Unset (default)
+    /// writes none.
+    pub lock_path: Option<PathBuf>,
+    /// What one Phase 3 (stet review + address loop) covers in a parallel block: "task" (default)
+    /// reviews each task after its Phase 2; "block" reviews the whole block once, after all its
+    /// tasks ran Phase 2, under the block's last task.
+    pub phase3_scope: String,
+    /// What a failing `expect:` line of a task does: "fail" (default) fails the task; "warn" logs
+    /// it and goes on.
+    pub on_expect_fail: String,
+    /// When set to N, a triage pass that would dismiss more than N stet findings asks for
+    /// confirmation on an interactive terminal and otherwise dismisses none, so every finding is
+    /// addressed (see [`crate::stet::dismiss_non_actionable_and_rerun`]). Unset (default): no limit.
+    pub dismiss_requires_confirmation: Option<u32>,
+    /// Line Phase 2 must end its answer with (`{{TASK_INDEX}}` is replaced by the task index), e.g.
+    /// `PEAL_TASK_DONE: {{TASK_INDEX}}`. The Phase 2 prompt asks for it; an answer without it is
+    /// handled per `on_missing_done_marker`. Unset (default): no marker.
+    pub done_marker: Option<String>,
+    /// What a Phase 2 answer without `done_marker` does: "retry" (default) runs Phase 2 once more,
+    /// telling the agent the marker was missing, and fails the task if it is still missing; "fail"
+    /// fails the task at once.
+    pub on_missing_done_marker: String,
+    /// Severities whose stet findings are never dismissed by Phase 3 triage (LLM or
+    /// `stet_dismiss_patterns`): they always go to the address loop. Compared case-insensitively.
+    /// Empty (default): every finding is eligible for triage.
+    pub triage_skip_severities: Vec<String>,
+    /// Seconds a run stopped by SIGTERM, SIGINT, or SIGHUP gets to save state, write the run summary
+    /// (status "interrupted"), and flush logs before it exits anyway; 0 exits right after killing the
+    /// agent. Default 10.
+    pub shutdown_grace_sec: u64,
+    /// Prompt middleware chain (`[[prompt_middleware]]` in TOML), applied in order to every agent
+    /// prompt before it is sent and, in reverse order, to agent output after it is captured (see
+    /// [`crate::prompt_middleware`]). Empty (default): prompts go out as built.
+    pub prompt_middleware: Vec<PromptMiddlewareSpec>,
+    /// Container execution (`[execution]` in TOML, see [`crate::container`]). With
+    /// `container_image` set, agent and verify commands run inside that image with the repo
+    /// bind-mounted; unset (default): they run on the host.
+    pub execution: Execution,
+    /// Agent attempts (Phase 2.5) at resolving conflicts when a sparse worktree's changes no longer
+    /// apply cleanly to the repo (see [`crate::sparse`]). Capped at 3; 0 fails the task with
+    /// `SparseApplyFailed` as before. Default 1.
+    pub merge_conflict_retry_count: u32,
 }
 
 /// TOML-deserializable config file representation. All fields optional.
@@ -134,6 +480,8 @@ struct FileConfig {
     continue_with_remaining_tasks: Option<bool>,
     log_level: Option<String>,
     log_file: Option<PathBuf>,
+    log_max_size_mb: Option<u64>,
+    log_keep_files: Option<u32>,
     stet_path: Option<PathBuf>,
     stet_start_ref: Option<String>,
     stet_start_extra_args: Option<Vec<String>>,
@@ -151,10 +499,78 @@ struct FileConfig {
     run_summary_path: Option<PathBuf>,
     max_consecutive_task_failures: Option<u32>,
     commit_after_phase2: Option<bool>,
+    fail_fast_findings: Option<bool>,
+    agent_max_requests_per_min: Option<u32>,
+    phase_max_rss_mb: Option<u64>,
+    phase_max_cpu_sec: Option<u64>,
+    vars: Option<BTreeMap<String, String>>,
+    summarize_tasks: Option<bool>,
+    agent_health_check: Option<bool>,
+    agent_health_check_timeout_sec: Option<u64>,
+    otel_endpoint: Option<String>,
+    stet_stream: Option<bool>,
+    stet_stream_abort_threshold: Option<u32>,
+    command_shell: Option<String>,
+    on_stale_stet_session: Option<String>,
+    agent_backend: Option<String>,
+    api_base_url: Option<String>,
+    api_key_env: Option<String>,
+    api_model: Option<String>,
+    triage_agent_cmd: Option<String>,
+    triage_model: Option<String>,
+    no_git_checks: Option<bool>,
+    rollback_failed_tasks: Option<bool>,
+    serve_status: Option<String>,
+    stage_commands: Option<Vec<String>>,
+    max_prompt_tokens: Option<u64>,
+    on_prompt_too_large: Option<String>,
+    stet_scope_to_task: Option<bool>,
+    stet_path_args: Option<Vec<String>>,
+    max_agent_calls: Option<u64>,
+    retry_failed_parallel_tasks: Option<bool>,
+    env_file: Option<PathBuf>,
+    push_after_segment: Option<bool>,
+    push_remote: Option<String>,
+    push_branch: Option<String>,
+    on_push_fail: Option<String>,
+    phase2_change_check: Option<String>,
+    escalation_models: Option<Vec<String>>,
+    enable_phase3: Option<bool>,
+    session_size: Option<u32>,
+    model_rates: Option<BTreeMap<String, ModelRate>>,
+    parallel_phase3: Option<bool>,
+    strict_state: Option<bool>,
+    log_prompts: Option<bool>,
+    log_agent_output: Option<String>,
+    on_renumber: Option<String>,
+    vcs: Option<String>,
+    stet_health_command: Option<String>,
+    artifact_retention: Option<ArtifactRetention>,
+    repo_subdir: Option<PathBuf>,
+    triage_cache_ttl_sec: Option<u64>,
+    no_cache: Option<bool>,
+    pre_phase2_commands: Option<Vec<String>>,
+    post_phase2_commands: Option<Vec<String>>,
+    on_phase_hook_fail: Option<String>,
+    stet_finish_commands: Option<Vec<String>>,
+    findings_summary_top_n: Option<u32>,
+    sparse_checkout: Option<bool>,
+    lock_path: Option<PathBuf>,
+    phase3_scope: Option<String>,
+    on_expect_fail: Option<String>,
+    dismiss_requires_confirmation: Option<u32>,
+    done_marker: Option<String>,
+    on_missing_done_marker: Option<String>,
+    triage_skip_severities: Option<Vec<String>>,
+    shutdown_grace_sec: Option<u64>,
+    prompt_middleware: Option<Vec<PromptMiddlewareSpec>>,
+    execution: Option<Execution>,
+    merge_conflict_retry_count: Option<u32>,
 }
 
 /// Intermediate layer where every field is optional, used to merge sources.
-#[derive(Debug, Default)]
+/// Serializes with the same keys as [`PealConfig`]; unset fields are `null`.
+#[derive(Debug, Clone, Default, Serialize)]
 struct ConfigLayer {
     agent_cmd: Option<String>,
     plan_path: Option<PathBuf>,
@@ -173,6 +589,8 @@ struct ConfigLayer {
     continue_with_remaining_tasks: Option<bool>,
     log_level: Option<String>,
     log_file: Option<PathBuf>,
+    log_max_size_mb: Option<u64>,
+    log_keep_files: Option<u32>,
     stet_path: Option<PathBuf>,
     stet_start_ref: Option<String>,
     stet_start_extra_args: Option<Vec<String>>,
@@ -190,9 +608,283 @@ struct ConfigLayer {
     run_summary_path: Option<PathBuf>,
     max_consecutive_task_failures: Option<u32>,
     commit_after_phase2: Option<bool>,
+    fail_fast_findings: Option<bool>,
+    agent_max_requests_per_min: Option<u32>,
+    phase_max_rss_mb: Option<u64>,
+    phase_max_cpu_sec: Option<u64>,
+    vars: Option<BTreeMap<String, String>>,
+    summarize_tasks: Option<bool>,
+    agent_health_check: Option<bool>,
+    agent_health_check_timeout_sec: Option<u64>,
+    otel_endpoint: Option<String>,
+    stet_stream: Option<bool>,
+    stet_stream_abort_threshold: Option<u32>,
+    command_shell: Option<String>,
+    on_stale_stet_session: Option<String>,
+    agent_backend: Option<String>,
+    api_base_url: Option<String>,
+    api_key_env: Option<String>,
+    api_model: Option<String>,
+    triage_agent_cmd: Option<String>,
+    triage_model: Option<String>,
+    no_git_checks: Option<bool>,
+    rollback_failed_tasks: Option<bool>,
+    serve_status: Option<String>,
+    stage_commands: Option<Vec<String>>,
+    max_prompt_tokens: Option<u64>,
+    on_prompt_too_large: Option<String>,
+    stet_scope_to_task: Option<bool>,
+    stet_path_args: Option<Vec<String>>,
+    max_agent_calls: Option<u64>,
+    retry_failed_parallel_tasks: Option<bool>,
+    env_file: Option<PathBuf>,
+    push_after_segment: Option<bool>,
+    push_remote: Option<String>,
+    push_branch: Option<String>,
+    on_push_fail: Option<String>,
+    phase2_change_check: Option<String>,
+    escalation_models: Option<Vec<String>>,
+    enable_phase3: Option<bool>,
+    session_size: Option<u32>,
+    model_rates: Option<BTreeMap<String, ModelRate>>,
+    parallel_phase3: Option<bool>,
+    strict_state: Option<bool>,
+    log_prompts: Option<bool>,
+    log_agent_output: Option<String>,
+    on_renumber: Option<String>,
+    vcs: Option<String>,
+    stet_health_command: Option<String>,
+    artifact_retention: Option<ArtifactRetention>,
+    repo_subdir: Option<PathBuf>,
+    triage_cache_ttl_sec: Option<u64>,
+    no_cache: Option<bool>,
+    pre_phase2_commands: Option<Vec<String>>,
+    post_phase2_commands: Option<Vec<String>>,
+    on_phase_hook_fail: Option<String>,
+    stet_finish_commands: Option<Vec<String>>,
+    findings_summary_top_n: Option<u32>,
+    sparse_checkout: Option<bool>,
+    lock_path: Option<PathBuf>,
+    phase3_scope: Option<String>,
+    on_expect_fail: Option<String>,
+    dismiss_requires_confirmation: Option<u32>,
+    done_marker: Option<String>,
+    on_missing_done_marker: Option<String>,
+    triage_skip_severities: Option<Vec<String>>,
+    shutdown_grace_sec: Option<u64>,
+    prompt_middleware: Option<Vec<PromptMiddlewareSpec>>,
+    execution: Option<Execution>,
+    merge_conflict_retry_count: Option<u32>,
+}
+
+/// Where a resolved config value came from, highest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    Cli,
+    /// The process environment or `env_file`.
+    Env,
+    PlanSidecar,
+    File,
+    PlanFrontMatter,
+    Default,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ConfigSource::Cli => "cli",
+            ConfigSource::Env => "env",
+            ConfigSource::PlanSidecar => "plan sidecar",
+            ConfigSource::File => "config file",
+            ConfigSource::PlanFrontMatter => "plan front matter",
+            ConfigSource::Default => "default",
+        })
+    }
+}
+
+/// One key of `peal config show`: the resolved value (TOML syntax, `None` when unset) and
+/// where it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedValue {
+    pub key: String,
+    pub value: Option<String>,
+    pub source: ConfigSource,
+    /// Set when the value was derived from another key rather than given, e.g. `parallel`
+    /// turned on by `max_parallel`.
+    pub derived_from: Option<&'static str>,
+    /// The built-in default (TOML syntax); `None` for unset-by-default and required keys.
+    pub default: Option<String>,
+}
+
+impl ResolvedValue {
+    /// Provenance comment: `from cli`, `from env PEAL_MODEL`, `from config file`, ..., or
+    /// `default`.
+    fn provenance(&self) -> String {
+        let key = self.derived_from.unwrap_or(&self.key);
+        let from = match self.source {
+            ConfigSource::Default => "default".to_owned(),
+            ConfigSource::Env => format!("from env PEAL_{}", key.to_uppercase()),
+            source => format!("from {source}"),
+        };
+        match self.derived_from {
+            Some(key) => format!("{from} (via {key})"),
+            None => from,
+        }
+    }
+}
+
+/// Which keys `peal config show` lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShowKeys {
+    /// Keys set by some layer (the default).
+    Set,
+    /// Every key (`--resolved`).
+    All,
+    /// Keys whose value differs from the built-in default, whatever set them (`--diff-defaults`).
+    NonDefault,
+}
+
+/// Text of `peal config show`: one `key = value  # provenance` line per key of `shown`, in
+/// key order. Unset optional keys are shown commented out. [`PealConfig::warnings`] follow as
+/// comments.
+pub fn render_resolved(values: &[ResolvedValue], warnings: &[String], shown: ShowKeys) -> String {
+    let mut out = String::new();
+    for v in values {
+        let listed = match shown {
+            ShowKeys::Set => v.source != ConfigSource::Default,
+            ShowKeys::All => true,
+            ShowKeys::NonDefault => v.value != v.default,
+        };
+        if !listed {
+            continue;
+        }
+        let provenance = v.provenance();
+        match &v.value {
+            Some(value) => out.push_str(&format!("{} = {value}  # {provenance}\n", v.key)),
+            None => out.push_str(&format!("# {} is unset  # {provenance}\n", v.key)),
+        }
+    }
+    for w in warnings {
+        out.push_str(&format!("# warning: {w}\n"));
+    }
+    out
+}
+
+/// The config layers before merging, kept apart so each value's source can be reported.
+#[derive(Default)]
+struct Layers {
+    plan: ConfigLayer,
+    file: ConfigLayer,
+    sidecar: ConfigLayer,
+    env: ConfigLayer,
+    cli: ConfigLayer,
+}
+
+impl Layers {
+    fn load(
+        config_path: Option<&Path>,
+        cli_args: &RunArgs,
+        env_fn: fn(&str) -> Option<String>,
+        plan_metadata: Option<&PlanMetadata>,
+    ) -> anyhow::Result<Self> {
+        let file = match config_path {
+            Some(path) => load_file_layer(path)?,
+            None => ConfigLayer::default(),
+        };
+        let plan = plan_metadata.map(plan_layer_from).unwrap_or_default();
+        // The env file fills in PEAL_* variables the process environment does not set.
+        let env_file = cli_args
+            .env_file
+            .clone()
+            .or_else(|| env_fn("ENV_FILE").map(PathBuf::from))
+            .or_else(|| file.env_file.clone());
+        let dotenv = match &env_file {
+            Some(path) => load_env_file(path)?,
+            None => BTreeMap::new(),
+        };
+        let env =
+            load_env_layer(&|suffix: &str| env_fn(suffix).or_else(|| dotenv.get(suffix).cloned()))?;
+        let cli = cli_layer_from(cli_args);
+
+        // A `<plan>.peal.toml` sidecar overrides the config file for that plan only.
+        let sidecar_path = cli
+            .plan_path
+            .as_ref()
+            .or(env.plan_path.as_ref())
+            .or(file.plan_path.as_ref())
+            .filter(|_| !cli_args.no_config)
+            .map(|plan| plan_sidecar_path(plan))
+            .filter(|path| path.is_file());
+        let sidecar = match sidecar_path {
+            Some(path) => {
+                let mut sidecar = load_file_layer(&path)?;
+                // The sidecar is found through the plan path, so it cannot move the plan or repo.
+                sidecar.plan_path = None;
+                sidecar.repo_path = None;
+                sidecar
+            }
+            None => ConfigLayer::default(),
+        };
+        Ok(Self { plan, file, sidecar, env, cli })
+    }
+
+    /// All layers merged: CLI > env > plan sidecar > file > plan front matter.
+    fn merged(&self) -> ConfigLayer {
+        // Plan metadata sits below the file: merge it as the lowest of three layers.
+        let file = merge_layers(self.plan.clone(), ConfigLayer::default(), self.file.clone());
+        let file = merge_layers(file, ConfigLayer::default(), self.sidecar.clone());
+        merge_layers(file, self.env.clone(), self.cli.clone())
+    }
+
+    /// The highest-precedence layer that sets `key`, else `Default`.
+    fn source(&self, key: &str) -> ConfigSource {
+        [
+            (&self.cli, ConfigSource::Cli),
+            (&self.env, ConfigSource::Env),
+            (&self.sidecar, ConfigSource::PlanSidecar),
+            (&self.file, ConfigSource::File),
+            (&self.plan, ConfigSource::PlanFrontMatter),
+        ]
+        .into_iter()
+        .find(|(layer, _)| serde_json::to_value(layer).is_ok_and(|v| !v[key].is_null()))
+        .map_or(ConfigSource::Default, |(_, source)| source)
+    }
 }
 
 impl PealConfig {
+    /// Resource limits applied to each agent phase invocation.
+    pub fn phase_resource_limits(&self) -> crate::subprocess::ResourceLimits {
+        crate::subprocess::ResourceLimits {
+            max_rss_mb: self.phase_max_rss_mb.filter(|&n| n > 0),
+            max_cpu_sec: self.phase_max_cpu_sec.filter(|&n| n > 0),
+        }
+    }
+
+    /// Directory the agent and `peal-verify` commands work in: `repo_path` joined with
+    /// `repo_subdir`, or `repo_path` when the run is not scoped.
+    pub fn work_dir(&self) -> PathBuf {
+        match &self.repo_subdir {
+            Some(subdir) => self.repo_path.join(subdir),
+            None => self.repo_path.clone(),
+        }
+    }
+
+    /// Arguments after the program in `agent_cmd` (see [`crate::cursor::split_agent_cmd`]),
+    /// passed first in every agent invocation. Empty when `agent_cmd` does not split.
+    pub fn agent_args(&self) -> Vec<String> {
+        crate::cursor::split_agent_cmd(&self.agent_cmd).map(|(_, args)| args).unwrap_or_default()
+    }
+
+    /// True when text-only agent calls go to the HTTP API (`agent_backend = "api"`).
+    pub fn uses_api_backend(&self) -> bool {
+        self.agent_backend == "api"
+    }
+
+    /// Model sent to the HTTP API: `api_model`, else `model`.
+    pub fn api_model(&self) -> Option<&str> {
+        self.api_model.as_deref().or(self.model.as_deref())
+    }
+
     /// Load configuration with precedence: CLI > env > file > defaults.
     ///
     /// `config_path` — optional path to a TOML config file.
@@ -201,9 +893,105 @@ impl PealConfig {
         Self::load_with_env(config_path, cli_args, real_env_var)
     }
 
+    /// Load configuration with plan front-matter metadata as an extra layer below the
+    /// config file: CLI > env > file > plan metadata > defaults.
+    pub fn load_with_plan_metadata(
+        config_path: Option<&Path>,
+        cli_args: &RunArgs,
+        metadata: &PlanMetadata,
+    ) -> anyhow::Result<Self> {
+        Self::load_with_env_and_plan(config_path, cli_args, real_env_var, Some(metadata))
+    }
+
+    /// Load like [`Self::load_with_plan_metadata`] and also report each value's source, for
+    /// `peal config show`.
+    pub fn load_with_sources(
+        config_path: Option<&Path>,
+        cli_args: &RunArgs,
+        metadata: Option<&PlanMetadata>,
+    ) -> anyhow::Result<(Self, Vec<ResolvedValue>)> {
+        Self::load_with_sources_env(config_path, cli_args, real_env_var, metadata)
+    }
+
+    fn load_with_sources_env(
+        config_path: Option<&Path>,
+        cli_args: &RunArgs,
+        env_fn: fn(&str) -> Option<String>,
+        metadata: Option<&PlanMetadata>,
+    ) -> anyhow::Result<(Self, Vec<ResolvedValue>)> {
+        let layers = Layers::load(config_path, cli_args, env_fn, metadata)?;
+        let config = Self::from_layers(&layers)?;
+        // The required keys are the only ones a default config needs.
+        let defaults = Self::from_layers(&Layers {
+            cli: ConfigLayer {
+                plan_path: Some(config.plan_path.clone()),
+                repo_path: Some(config.repo_path.clone()),
+                ..ConfigLayer::default()
+            },
+            ..Layers::default()
+        })?;
+        let toml_text = |value: &serde_json::Value| {
+            (!value.is_null())
+                .then(|| toml::Value::try_from(value).ok())
+                .flatten()
+                .map(|v| v.to_string())
+        };
+        let serde_json::Value::Object(fields) = serde_json::to_value(&config)? else {
+            anyhow::bail!("config did not serialize to a table");
+        };
+        let default_fields = serde_json::to_value(&defaults)?;
+        let values = fields
+            .into_iter()
+            .map(|(key, value)| {
+                let mut source = layers.source(&key);
+                let mut derived_from = None;
+                // An explicit max_parallel turns parallel on when parallel itself is not set.
+                if key == "parallel" && source == ConfigSource::Default && config.parallel {
+                    source = layers.source("max_parallel");
+                    derived_from = Some("max_parallel");
+                }
+                let default = match key.as_str() {
+                    "plan_path" | "repo_path" => None,
+                    _ => toml_text(&default_fields[&key]),
+                };
+                ResolvedValue {
+                    value: toml_text(&value),
+                    key,
+                    source,
+                    derived_from,
+                    default,
+                }
+            })
+            .collect();
+        Ok((config, values))
+    }
+
+    /// Settings that are valid but do not do what they seem to, e.g. `max_parallel` with
+    /// `parallel = false`. `peal run` logs each as a warning; `peal config show` lists them.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        // An explicit max_parallel other than 0 turns parallel on unless parallel = false was set.
+        if !self.parallel && self.max_parallel != 0 && self.max_parallel != DEFAULT_MAX_PARALLEL {
+            out.push(format!(
+                "max_parallel = {} has no effect because parallel = false",
+                self.max_parallel
+            ));
+        }
+        if self.parallel && self.max_parallel == 1 {
+            out.push("parallel = true with max_parallel = 1 runs parallel blocks one task at a time".to_owned());
+        }
+        if self.parallel_phase3 && !self.parallel {
+            out.push("parallel_phase3 = true has no effect because parallel is off".to_owned());
+        }
+        if !self.stet_finish_commands.is_empty() && self.stet_commands.is_empty() {
+            out.push("stet_finish_commands have no effect without stet_commands (built-in stet runs `stet finish`)".to_owned());
+        }
+        out
+    }
+
     /// Validate that resolved paths satisfy filesystem requirements:
     /// plan_path must exist and be a regular file; repo_path must exist and
-    /// be a directory.
+    /// be a directory, and repo_subdir (if set) a relative directory inside it.
     pub fn validate(&self) -> Result<(), crate::error::PealError> {
         if !self.plan_path.exists() {
             return Err(crate::error::PealError::PlanFileNotFound {
@@ -225,12 +1013,47 @@ impl PealConfig {
                 path: self.repo_path.clone(),
             });
         }
-        if !is_git_repo(&self.repo_path) {
-            return Err(crate::error::PealError::RepoNotGitRepo {
-                path: self.repo_path.clone(),
+        if let Some(subdir) = &self.repo_subdir {
+            let invalid = |reason: &str| crate::error::PealError::InvalidRepoSubdir {
+                path: subdir.clone(),
+                reason: reason.to_owned(),
+            };
+            let escapes = subdir
+                .components()
+                .any(|c| !matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir));
+            if escapes {
+                return Err(invalid("must be a relative path inside repo_path"));
+            }
+            if !self.work_dir().is_dir() {
+                return Err(invalid("not a directory in repo_path"));
+            }
+        }
+        if !crate::vcs::VCS_SETTINGS.contains(&self.vcs.as_str()) {
+            return Err(crate::error::PealError::InvalidVcs {
+                value: self.vcs.clone(),
             });
         }
-        if self.on_findings_remaining != "fail" && self.on_findings_remaining != "warn" {
+        let vcs = crate::vcs::for_config(self);
+        if vcs.name() != "git" {
+            let git_only = [
+                (self.push_after_segment, "push_after_segment"),
+                (self.rollback_failed_tasks, "rollback_failed_tasks"),
+                (
+                    self.stet_start_ref.as_deref().is_some_and(crate::stet::start_ref_is_template),
+                    "stet_start_ref placeholders",
+                ),
+            ];
+            if let Some((_, option)) = git_only.into_iter().find(|(on, _)| *on) {
+                return Err(crate::error::PealError::VcsUnsupported {
+                    option,
+                    vcs: vcs.name().to_owned(),
+                });
+            }
+        }
+        if !self.no_git_checks {
+            vcs.check_repo(&self.repo_path)?;
+        }
+        if !matches!(self.on_findings_remaining.as_str(), "fail" | "warn" | "create_task") {
             return Err(crate::error::PealError::InvalidOnFindingsRemaining {
                 value: self.on_findings_remaining.clone(),
             });
@@ -243,6 +1066,97 @@ impl PealConfig {
                 value: self.on_stet_fail.clone(),
             });
         }
+        if !matches!(self.command_shell.as_str(), "none" | "sh") {
+            return Err(crate::error::PealError::InvalidCommandShell {
+                value: self.command_shell.clone(),
+            });
+        }
+        if !matches!(self.log_agent_output.as_str(), "none" | "truncated" | "full") {
+            return Err(crate::error::PealError::InvalidLogAgentOutput {
+                value: self.log_agent_output.clone(),
+            });
+        }
+        if !matches!(self.on_renumber.as_str(), "rerun" | "trust") {
+            return Err(crate::error::PealError::InvalidOnRenumber {
+                value: self.on_renumber.clone(),
+            });
+        }
+        if !matches!(self.phase2_change_check.as_str(), "off" | "warn" | "retry" | "fail") {
+            return Err(crate::error::PealError::InvalidPhase2ChangeCheck {
+                value: self.phase2_change_check.clone(),
+            });
+        }
+        if !matches!(self.on_phase_hook_fail.as_str(), "warn" | "fail") {
+            return Err(crate::error::PealError::InvalidOnPhaseHookFail {
+                value: self.on_phase_hook_fail.clone(),
+            });
+        }
+        if !matches!(self.phase3_scope.as_str(), "task" | "block") {
+            return Err(crate::error::PealError::InvalidPhase3Scope {
+                value: self.phase3_scope.clone(),
+            });
+        }
+        if !matches!(self.on_expect_fail.as_str(), "warn" | "fail") {
+            return Err(crate::error::PealError::InvalidOnExpectFail {
+                value: self.on_expect_fail.clone(),
+            });
+        }
+        if !matches!(self.on_missing_done_marker.as_str(), "retry" | "fail") {
+            return Err(crate::error::PealError::InvalidOnMissingDoneMarker {
+                value: self.on_missing_done_marker.clone(),
+            });
+        }
+        if self.done_marker.as_deref().is_some_and(|m| m.trim().is_empty()) {
+            return Err(crate::error::PealError::EmptyDoneMarker);
+        }
+        if !matches!(self.on_push_fail.as_str(), "warn" | "fail") {
+            return Err(crate::error::PealError::InvalidOnPushFail {
+                value: self.on_push_fail.clone(),
+            });
+        }
+        if !matches!(self.on_stale_stet_session.as_str(), "finish" | "reattach") {
+            return Err(crate::error::PealError::InvalidOnStaleStetSession {
+                value: self.on_stale_stet_session.clone(),
+            });
+        }
+        if !matches!(self.agent_backend.as_str(), "cli" | "api") {
+            return Err(crate::error::PealError::InvalidAgentBackend {
+                value: self.agent_backend.clone(),
+            });
+        }
+        if self.agent_backend == "api" && self.api_model().is_none() {
+            return Err(crate::error::PealError::ApiModelMissing);
+        }
+        if !matches!(self.on_prompt_too_large.as_str(), "fail" | "warn") {
+            return Err(crate::error::PealError::InvalidOnPromptTooLarge {
+                value: self.on_prompt_too_large.clone(),
+            });
+        }
+        if let Some(runtime) = &self.execution.container_runtime
+            && !matches!(runtime.as_str(), "docker" | "podman")
+        {
+            return Err(crate::error::PealError::InvalidExecution {
+                detail: format!("container_runtime must be docker or podman, got '{runtime}'"),
+            });
+        }
+        if self.execution.container_image.as_deref().is_some_and(|i| i.trim().is_empty()) {
+            return Err(crate::error::PealError::InvalidExecution {
+                detail: "container_image is empty".to_owned(),
+            });
+        }
+        if self.execution.container_image.is_none() && self.execution.stet_in_container {
+            return Err(crate::error::PealError::InvalidExecution {
+                detail: "stet_in_container needs container_image".to_owned(),
+            });
+        }
+        if let Some(addr) = &self.serve_status
+            && addr.parse::<std::net::SocketAddr>().is_err()
+        {
+            return Err(crate::error::PealError::InvalidServeStatusAddr {
+                value: addr.clone(),
+            });
+        }
+        crate::prompt_middleware::Chain::from_config(self)?;
         Ok(())
     }
 
@@ -253,14 +1167,30 @@ impl PealConfig {
         cli_args: &RunArgs,
         env_fn: fn(&str) -> Option<String>,
     ) -> anyhow::Result<Self> {
-        let file_layer = match config_path {
-            Some(path) => load_file_layer(path)?,
-            None => ConfigLayer::default(),
-        };
-        let env_layer = load_env_layer(env_fn)?;
-        let cli_layer = cli_layer_from(cli_args);
+        Self::load_with_env_and_plan(config_path, cli_args, env_fn, None)
+    }
+
+    fn load_with_env_and_plan(
+        config_path: Option<&Path>,
+        cli_args: &RunArgs,
+        env_fn: fn(&str) -> Option<String>,
+        plan_metadata: Option<&PlanMetadata>,
+    ) -> anyhow::Result<Self> {
+        Self::from_layers(&Layers::load(config_path, cli_args, env_fn, plan_metadata)?)
+    }
+
+    /// Merge `layers` and fill in defaults.
+    fn from_layers(layers: &Layers) -> anyhow::Result<Self> {
+        let merged = layers.merged();
 
-        let merged = merge_layers(file_layer, env_layer, cli_layer);
+        // max_parallel = 0 turns parallel execution off, so asking for both is a contradiction.
+        if merged.parallel == Some(true) && merged.max_parallel == Some(0) {
+            return Err(PealError::ParallelConflict {
+                parallel_source: layers.source("parallel").to_string(),
+                max_parallel_source: layers.source("max_parallel").to_string(),
+            }
+            .into());
+        }
 
         let plan_path = merged.plan_path.ok_or_else(|| {
             anyhow::anyhow!("plan_path is required (via --plan, PEAL_PLAN_PATH, or config file)")
@@ -310,6 +1240,8 @@ impl PealConfig {
             continue_with_remaining_tasks: merged.continue_with_remaining_tasks.unwrap_or(false),
             log_level: merged.log_level,
             log_file: merged.log_file,
+            log_max_size_mb: merged.log_max_size_mb,
+            log_keep_files: merged.log_keep_files.unwrap_or(DEFAULT_LOG_KEEP_FILES),
         stet_path: merged.stet_path,
         stet_start_ref: merged.stet_start_ref,
         stet_start_extra_args: merged
@@ -338,6 +1270,91 @@ impl PealConfig {
         run_summary_path: merged.run_summary_path,
         max_consecutive_task_failures: merged.max_consecutive_task_failures,
         commit_after_phase2: merged.commit_after_phase2.unwrap_or(false),
+        fail_fast_findings: merged.fail_fast_findings.unwrap_or(false),
+        agent_max_requests_per_min: merged.agent_max_requests_per_min,
+        phase_max_rss_mb: merged.phase_max_rss_mb,
+        phase_max_cpu_sec: merged.phase_max_cpu_sec,
+        vars: merged.vars.unwrap_or_default(),
+        summarize_tasks: merged.summarize_tasks.unwrap_or(false),
+        agent_health_check: merged.agent_health_check.unwrap_or(false),
+        agent_health_check_timeout_sec: merged
+            .agent_health_check_timeout_sec
+            .unwrap_or(DEFAULT_AGENT_HEALTH_CHECK_TIMEOUT_SEC),
+        otel_endpoint: merged.otel_endpoint,
+        stet_stream: merged.stet_stream.unwrap_or(false),
+        stet_stream_abort_threshold: merged.stet_stream_abort_threshold,
+        command_shell: merged
+            .command_shell
+            .unwrap_or_else(|| DEFAULT_COMMAND_SHELL.to_owned()),
+        on_stale_stet_session: merged
+            .on_stale_stet_session
+            .unwrap_or_else(|| DEFAULT_ON_STALE_STET_SESSION.to_owned()),
+        agent_backend: merged.agent_backend.unwrap_or_else(|| DEFAULT_AGENT_BACKEND.to_owned()),
+        api_base_url: merged.api_base_url.unwrap_or_else(|| DEFAULT_API_BASE_URL.to_owned()),
+        api_key_env: merged.api_key_env.unwrap_or_else(|| DEFAULT_API_KEY_ENV.to_owned()),
+        api_model: merged.api_model,
+        triage_agent_cmd: merged.triage_agent_cmd,
+        triage_model: merged.triage_model,
+        no_git_checks: merged.no_git_checks.unwrap_or(false),
+        rollback_failed_tasks: merged.rollback_failed_tasks.unwrap_or(false),
+        serve_status: merged.serve_status,
+        stage_commands: merged.stage_commands.unwrap_or_default(),
+        max_prompt_tokens: merged.max_prompt_tokens,
+        on_prompt_too_large: merged
+            .on_prompt_too_large
+            .unwrap_or_else(|| DEFAULT_ON_PROMPT_TOO_LARGE.to_owned()),
+        stet_scope_to_task: merged.stet_scope_to_task.unwrap_or(false),
+        stet_path_args: merged
+            .stet_path_args
+            .unwrap_or_else(|| vec![DEFAULT_STET_PATH_ARG.to_owned()]),
+        max_agent_calls: merged.max_agent_calls,
+        retry_failed_parallel_tasks: merged.retry_failed_parallel_tasks.unwrap_or(false),
+        env_file: merged.env_file,
+        push_after_segment: merged.push_after_segment.unwrap_or(false),
+        push_remote: merged.push_remote.unwrap_or_else(|| DEFAULT_PUSH_REMOTE.to_owned()),
+        push_branch: merged.push_branch,
+        on_push_fail: merged.on_push_fail.unwrap_or_else(|| DEFAULT_ON_PUSH_FAIL.to_owned()),
+        phase2_change_check: merged.phase2_change_check.unwrap_or_else(|| DEFAULT_PHASE2_CHANGE_CHECK.to_owned()),
+        escalation_models: merged.escalation_models.unwrap_or_default(),
+        enable_phase3: merged.enable_phase3.unwrap_or(true),
+        session_size: merged.session_size,
+        model_rates: merged.model_rates.unwrap_or_default(),
+        parallel_phase3: merged.parallel_phase3.unwrap_or(false),
+        strict_state: merged.strict_state.unwrap_or(false),
+        log_prompts: merged.log_prompts.unwrap_or(false),
+        log_agent_output: merged.log_agent_output.unwrap_or_else(|| DEFAULT_LOG_AGENT_OUTPUT.to_owned()),
+        on_renumber: merged.on_renumber.unwrap_or_else(|| DEFAULT_ON_RENUMBER.to_owned()),
+        vcs: merged.vcs.unwrap_or_else(|| crate::vcs::DEFAULT_VCS.to_owned()),
+        stet_health_command: merged.stet_health_command,
+        artifact_retention: merged.artifact_retention.unwrap_or_default(),
+        repo_subdir: merged.repo_subdir,
+        triage_cache_ttl_sec: merged.triage_cache_ttl_sec.unwrap_or(DEFAULT_TRIAGE_CACHE_TTL_SEC),
+        no_cache: merged.no_cache.unwrap_or(false),
+        pre_phase2_commands: merged.pre_phase2_commands.unwrap_or_default(),
+        post_phase2_commands: merged.post_phase2_commands.unwrap_or_default(),
+        on_phase_hook_fail: merged
+            .on_phase_hook_fail
+            .unwrap_or_else(|| DEFAULT_ON_PHASE_HOOK_FAIL.to_owned()),
+        stet_finish_commands: merged.stet_finish_commands.unwrap_or_default(),
+        findings_summary_top_n: merged.findings_summary_top_n,
+        sparse_checkout: merged.sparse_checkout.unwrap_or(false),
+        lock_path: merged.lock_path,
+        phase3_scope: merged
+            .phase3_scope
+            .unwrap_or_else(|| DEFAULT_PHASE3_SCOPE.to_owned()),
+        on_expect_fail: merged
+            .on_expect_fail
+            .unwrap_or_else(|| DEFAULT_ON_EXPECT_FAIL.to_owned()),
+        dismiss_requires_confirmation: merged.dismiss_requires_confirmation,
+        done_marker: merged.done_marker,
+        on_missing_done_marker: merged
+            .on_missing_done_marker
+            .unwrap_or_else(|| DEFAULT_ON_MISSING_DONE_MARKER.to_owned()),
+        triage_skip_severities: merged.triage_skip_severities.unwrap_or_default(),
+        shutdown_grace_sec: merged.shutdown_grace_sec.unwrap_or(DEFAULT_SHUTDOWN_GRACE_SEC),
+        prompt_middleware: merged.prompt_middleware.unwrap_or_default(),
+        execution: merged.execution.unwrap_or_default(),
+        merge_conflict_retry_count: merged.merge_conflict_retry_count.unwrap_or(DEFAULT_MERGE_CONFLICT_RETRY_COUNT),
     })
     }
 }
@@ -365,6 +1382,8 @@ fn load_file_layer(path: &Path) -> anyhow::Result<ConfigLayer> {
         continue_with_remaining_tasks: fc.continue_with_remaining_tasks,
         log_level: fc.log_level,
         log_file: fc.log_file,
+        log_max_size_mb: fc.log_max_size_mb,
+        log_keep_files: fc.log_keep_files,
         stet_path: fc.stet_path,
         stet_start_ref: fc.stet_start_ref,
         stet_start_extra_args: fc.stet_start_extra_args,
@@ -382,26 +1401,111 @@ fn load_file_layer(path: &Path) -> anyhow::Result<ConfigLayer> {
         run_summary_path: fc.run_summary_path,
         max_consecutive_task_failures: fc.max_consecutive_task_failures,
         commit_after_phase2: fc.commit_after_phase2,
+        fail_fast_findings: fc.fail_fast_findings,
+        agent_max_requests_per_min: fc.agent_max_requests_per_min,
+        phase_max_rss_mb: fc.phase_max_rss_mb,
+        phase_max_cpu_sec: fc.phase_max_cpu_sec,
+        vars: fc.vars,
+        summarize_tasks: fc.summarize_tasks,
+        agent_health_check: fc.agent_health_check,
+        agent_health_check_timeout_sec: fc.agent_health_check_timeout_sec,
+        otel_endpoint: fc.otel_endpoint,
+        stet_stream: fc.stet_stream,
+        stet_stream_abort_threshold: fc.stet_stream_abort_threshold,
+        command_shell: fc.command_shell,
+        on_stale_stet_session: fc.on_stale_stet_session,
+        agent_backend: fc.agent_backend,
+        api_base_url: fc.api_base_url,
+        api_key_env: fc.api_key_env,
+        api_model: fc.api_model,
+        triage_agent_cmd: fc.triage_agent_cmd,
+        triage_model: fc.triage_model,
+        no_git_checks: fc.no_git_checks,
+        rollback_failed_tasks: fc.rollback_failed_tasks,
+        serve_status: fc.serve_status,
+        stage_commands: fc.stage_commands,
+        max_prompt_tokens: fc.max_prompt_tokens,
+        on_prompt_too_large: fc.on_prompt_too_large,
+        stet_scope_to_task: fc.stet_scope_to_task,
+        stet_path_args: fc.stet_path_args,
+        max_agent_calls: fc.max_agent_calls,
+        retry_failed_parallel_tasks: fc.retry_failed_parallel_tasks,
+        env_file: fc.env_file,
+        push_after_segment: fc.push_after_segment,
+        push_remote: fc.push_remote,
+        push_branch: fc.push_branch,
+        on_push_fail: fc.on_push_fail,
+        phase2_change_check: fc.phase2_change_check,
+        escalation_models: fc.escalation_models,
+        enable_phase3: fc.enable_phase3,
+        session_size: fc.session_size,
+        model_rates: fc.model_rates,
+        parallel_phase3: fc.parallel_phase3,
+        strict_state: fc.strict_state,
+        log_prompts: fc.log_prompts,
+        log_agent_output: fc.log_agent_output,
+        on_renumber: fc.on_renumber,
+        vcs: fc.vcs,
+        stet_health_command: fc.stet_health_command,
+        artifact_retention: fc.artifact_retention,
+        repo_subdir: fc.repo_subdir,
+        triage_cache_ttl_sec: fc.triage_cache_ttl_sec,
+        no_cache: fc.no_cache,
+        pre_phase2_commands: fc.pre_phase2_commands,
+        post_phase2_commands: fc.post_phase2_commands,
+        on_phase_hook_fail: fc.on_phase_hook_fail,
+        stet_finish_commands: fc.stet_finish_commands,
+        findings_summary_top_n: fc.findings_summary_top_n,
+        sparse_checkout: fc.sparse_checkout,
+        lock_path: fc.lock_path,
+        phase3_scope: fc.phase3_scope,
+        on_expect_fail: fc.on_expect_fail,
+        dismiss_requires_confirmation: fc.dismiss_requires_confirmation,
+        done_marker: fc.done_marker,
+        on_missing_done_marker: fc.on_missing_done_marker,
+        triage_skip_severities: fc.triage_skip_severities,
+        shutdown_grace_sec: fc.shutdown_grace_sec,
+        prompt_middleware: fc.prompt_middleware,
+        execution: fc.execution,
+        merge_conflict_retry_count: fc.merge_conflict_retry_count,
     })
 }
 
-/// Returns true if `path` is the root of a git worktree (or inside one).
-fn is_git_repo(path: &Path) -> bool {
-    let output = match Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("rev-parse")
-        .arg("--is-inside-work-tree")
-        .output()
-    {
-        Ok(o) => o,
-        Err(_) => return false,
-    };
-    if !output.status.success() {
-        return false;
-    }
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    stdout.trim() == "true"
+/// Per-plan config sidecar for `plan_path`: `<stem>.peal.toml` next to it (e.g.
+/// `docs/tasks.md` → `docs/tasks.peal.toml`). When it exists, its values override the config
+/// file for runs of that plan (CLI > env > sidecar > file); `--no-config` skips it.
+pub fn plan_sidecar_path(plan_path: &Path) -> PathBuf {
+    let stem = plan_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "plan".to_owned());
+    plan_path.with_file_name(format!("{stem}.peal.toml"))
+}
+
+/// Find a config file to use when `--config` is not given: the first of
+/// [`AUTO_CONFIG_FILES`] that exists in the repo directory (`--repo` or `PEAL_REPO_PATH`),
+/// then in `cwd`. Returns `None` when `--config` or `--no-config` is set.
+pub fn discover_config_path(cli_args: &RunArgs, cwd: &Path) -> Option<PathBuf> {
+    discover_config_path_with_env(cli_args, cwd, real_env_var)
+}
+
+fn discover_config_path_with_env(
+    cli_args: &RunArgs,
+    cwd: &Path,
+    env_fn: fn(&str) -> Option<String>,
+) -> Option<PathBuf> {
+    if cli_args.config.is_some() || cli_args.no_config {
+        return None;
+    }
+    let repo = cli_args
+        .repo
+        .clone()
+        .or_else(|| env_fn("REPO_PATH").map(PathBuf::from));
+    repo.iter()
+        .map(PathBuf::as_path)
+        .chain(std::iter::once(cwd))
+        .flat_map(|dir| AUTO_CONFIG_FILES.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
 }
 
 fn real_env_var(suffix: &str) -> Option<String> {
@@ -409,8 +1513,49 @@ fn real_env_var(suffix: &str) -> Option<String> {
     env::var(&key).ok().filter(|v| !v.is_empty())
 }
 
+/// Read a dotenv-style file and return its `PEAL_*` entries keyed by suffix (without `PEAL_`).
+///
+/// Accepts `KEY=VALUE` lines with optional `export ` prefix; blank lines and `#` comments are
+/// skipped, and values wrapped in matching single or double quotes are unquoted. Empty values
+/// count as unset, as in the process environment. Other keys are ignored.
+fn load_env_file(path: &Path) -> Result<BTreeMap<String, String>, PealError> {
+    let content = fs::read_to_string(path).map_err(|e| PealError::InvalidEnvFile {
+        path: path.to_path_buf(),
+        detail: e.to_string(),
+    })?;
+    parse_env_file(&content).map_err(|detail| PealError::InvalidEnvFile {
+        path: path.to_path_buf(),
+        detail,
+    })
+}
+
+fn parse_env_file(content: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut vars = BTreeMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected KEY=VALUE", i + 1))?;
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|&q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+            .unwrap_or(value);
+        if let Some(suffix) = key.trim().strip_prefix(ENV_PREFIX)
+            && !value.is_empty()
+        {
+            vars.insert(suffix.to_owned(), value.to_owned());
+        }
+    }
+    Ok(vars)
+}
+
 fn load_env_layer(
-    env_fn: fn(&str) -> Option<String>,
+    env_fn: &dyn Fn(&str) -> Option<String>,
 ) -> Result<ConfigLayer, crate::error::PealError> {
     Ok(ConfigLayer {
         agent_cmd: env_fn("AGENT_CMD"),
@@ -431,6 +1576,8 @@ fn load_env_layer(
         continue_with_remaining_tasks: parse_env_bool(env_fn, "CONTINUE_WITH_REMAINING_TASKS")?,
         log_level: env_fn("LOG_LEVEL"),
         log_file: env_fn("LOG_FILE").map(PathBuf::from),
+        log_max_size_mb: parse_env_u64(env_fn, "LOG_MAX_SIZE_MB")?,
+        log_keep_files: parse_env_u32(env_fn, "LOG_KEEP_FILES")?,
         stet_path: env_fn("STET_PATH").map(PathBuf::from),
         stet_start_ref: env_fn("STET_START_REF"),
         stet_start_extra_args: env_fn("STET_START_EXTRA_ARGS")
@@ -453,6 +1600,96 @@ fn load_env_layer(
         run_summary_path: env_fn("RUN_SUMMARY_PATH").map(PathBuf::from),
         max_consecutive_task_failures: parse_env_u32(env_fn, "MAX_CONSECUTIVE_TASK_FAILURES")?,
         commit_after_phase2: parse_env_bool(env_fn, "COMMIT_AFTER_PHASE2")?,
+        fail_fast_findings: parse_env_bool(env_fn, "FAIL_FAST_FINDINGS")?,
+        agent_max_requests_per_min: parse_env_u32(env_fn, "AGENT_MAX_REQUESTS_PER_MIN")?,
+        phase_max_rss_mb: parse_env_u64(env_fn, "PHASE_MAX_RSS_MB")?,
+        phase_max_cpu_sec: parse_env_u64(env_fn, "PHASE_MAX_CPU_SEC")?,
+        vars: env_fn("VARS").as_deref().map(parse_vars_str),
+        summarize_tasks: parse_env_bool(env_fn, "SUMMARIZE_TASKS")?,
+        agent_health_check: parse_env_bool(env_fn, "AGENT_HEALTH_CHECK")?,
+        agent_health_check_timeout_sec: parse_env_u64(env_fn, "AGENT_HEALTH_CHECK_TIMEOUT_SEC")?,
+        otel_endpoint: env_fn("OTEL_ENDPOINT"),
+        stet_stream: parse_env_bool(env_fn, "STET_STREAM")?,
+        stet_stream_abort_threshold: parse_env_u32(env_fn, "STET_STREAM_ABORT_THRESHOLD")?,
+        command_shell: env_fn("COMMAND_SHELL"),
+        on_stale_stet_session: env_fn("ON_STALE_STET_SESSION"),
+        agent_backend: env_fn("AGENT_BACKEND"),
+        api_base_url: env_fn("API_BASE_URL"),
+        api_key_env: env_fn("API_KEY_ENV"),
+        api_model: env_fn("API_MODEL"),
+        triage_agent_cmd: env_fn("TRIAGE_AGENT_CMD"),
+        triage_model: env_fn("TRIAGE_MODEL"),
+        no_git_checks: parse_env_bool(env_fn, "NO_GIT_CHECKS")?,
+        rollback_failed_tasks: parse_env_bool(env_fn, "ROLLBACK_FAILED_TASKS")?,
+        serve_status: env_fn("SERVE_STATUS"),
+        stage_commands: env_fn("STAGE_COMMANDS")
+            .map(|s| s.split(',').map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect()),
+        max_prompt_tokens: parse_env_u64(env_fn, "MAX_PROMPT_TOKENS")?,
+        on_prompt_too_large: env_fn("ON_PROMPT_TOO_LARGE"),
+        stet_scope_to_task: parse_env_bool(env_fn, "STET_SCOPE_TO_TASK")?,
+        stet_path_args: env_fn("STET_PATH_ARGS").as_deref().map(parse_extra_args_str),
+        max_agent_calls: parse_env_u64(env_fn, "MAX_AGENT_CALLS")?,
+        retry_failed_parallel_tasks: parse_env_bool(env_fn, "RETRY_FAILED_PARALLEL_TASKS")?,
+        env_file: env_fn("ENV_FILE").map(PathBuf::from),
+        push_after_segment: parse_env_bool(env_fn, "PUSH_AFTER_SEGMENT")?,
+        push_remote: env_fn("PUSH_REMOTE"),
+        push_branch: env_fn("PUSH_BRANCH"),
+        on_push_fail: env_fn("ON_PUSH_FAIL"),
+        phase2_change_check: env_fn("PHASE2_CHANGE_CHECK"),
+        escalation_models: env_fn("ESCALATION_MODELS")
+            .map(|s| s.split(',').map(|m| m.trim().to_owned()).filter(|m| !m.is_empty()).collect()),
+        enable_phase3: parse_env_bool(env_fn, "ENABLE_PHASE3")?,
+        session_size: parse_env_u32(env_fn, "SESSION_SIZE")?,
+        model_rates: None,
+        parallel_phase3: parse_env_bool(env_fn, "PARALLEL_PHASE3")?,
+        strict_state: parse_env_bool(env_fn, "STRICT_STATE")?,
+        log_prompts: parse_env_bool(env_fn, "LOG_PROMPTS")?,
+        log_agent_output: env_fn("LOG_AGENT_OUTPUT"),
+        on_renumber: env_fn("ON_RENUMBER"),
+        vcs: env_fn("VCS"),
+        stet_health_command: env_fn("STET_HEALTH_COMMAND"),
+        artifact_retention: None,
+        repo_subdir: env_fn("REPO_SUBDIR").map(PathBuf::from),
+        triage_cache_ttl_sec: parse_env_u64(env_fn, "TRIAGE_CACHE_TTL_SEC")?,
+        no_cache: parse_env_bool(env_fn, "NO_CACHE")?,
+        pre_phase2_commands: env_fn("PRE_PHASE2_COMMANDS")
+            .map(|s| s.split(',').map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect()),
+        post_phase2_commands: env_fn("POST_PHASE2_COMMANDS")
+            .map(|s| s.split(',').map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect()),
+        on_phase_hook_fail: env_fn("ON_PHASE_HOOK_FAIL"),
+        stet_finish_commands: env_fn("STET_FINISH_COMMANDS")
+            .map(|s| s.split(',').map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect()),
+        findings_summary_top_n: parse_env_u32(env_fn, "FINDINGS_SUMMARY_TOP_N")?,
+        sparse_checkout: parse_env_bool(env_fn, "SPARSE_CHECKOUT")?,
+        lock_path: env_fn("LOCK_PATH").map(PathBuf::from),
+        phase3_scope: env_fn("PHASE3_SCOPE"),
+        on_expect_fail: env_fn("ON_EXPECT_FAIL"),
+        dismiss_requires_confirmation: parse_env_u32(env_fn, "DISMISS_REQUIRES_CONFIRMATION")?,
+        done_marker: env_fn("DONE_MARKER"),
+        on_missing_done_marker: env_fn("ON_MISSING_DONE_MARKER"),
+        triage_skip_severities: env_fn("TRIAGE_SKIP_SEVERITIES")
+            .map(|s| s.split(',').map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect()),
+        shutdown_grace_sec: parse_env_u64(env_fn, "SHUTDOWN_GRACE_SEC")?,
+        prompt_middleware: None,
+        execution: None,
+        merge_conflict_retry_count: parse_env_u32(env_fn, "MERGE_CONFLICT_RETRY_COUNT")?,
+    })
+}
+
+/// Parse `KEY=VALUE` pairs separated by commas (env `PEAL_VARS`). Entries without `=` are skipped.
+fn parse_vars_str(s: &str) -> BTreeMap<String, String> {
+    s.split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.trim().to_owned(), v.trim().to_owned()))
+        .filter(|(k, _)| !k.is_empty())
+        .collect()
+}
+
+/// Key-wise merge of vars layers, lowest precedence first. None when no layer sets vars.
+fn merge_vars<const N: usize>(layers: [Option<BTreeMap<String, String>>; N]) -> Option<BTreeMap<String, String>> {
+    layers.into_iter().flatten().reduce(|mut acc, layer| {
+        acc.extend(layer);
+        acc
     })
 }
 
@@ -496,7 +1733,7 @@ fn validate_stet_dismiss_patterns(
 }
 
 fn parse_env_u32(
-    env_fn: fn(&str) -> Option<String>,
+    env_fn: &dyn Fn(&str) -> Option<String>,
     suffix: &str,
 ) -> Result<Option<u32>, crate::error::PealError> {
     match env_fn(suffix) {
@@ -513,7 +1750,7 @@ fn parse_env_u32(
 }
 
 fn parse_env_u64(
-    env_fn: fn(&str) -> Option<String>,
+    env_fn: &dyn Fn(&str) -> Option<String>,
     suffix: &str,
 ) -> Result<Option<u64>, crate::error::PealError> {
     match env_fn(suffix) {
@@ -530,7 +1767,7 @@ fn parse_env_u64(
 }
 
 fn parse_env_bool(
-    env_fn: fn(&str) -> Option<String>,
+    env_fn: &dyn Fn(&str) -> Option<String>,
     suffix: &str,
 ) -> Result<Option<bool>, crate::error::PealError> {
     match env_fn(suffix) {
@@ -546,6 +1783,17 @@ fn parse_env_bool(
     }
 }
 
+/// Per-plan defaults from front matter. Only keys that map to config are set.
+fn plan_layer_from(metadata: &PlanMetadata) -> ConfigLayer {
+    ConfigLayer {
+        model: metadata.model.clone(),
+        phase_timeout_sec: metadata.phase_timeout_sec,
+        post_run_timeout_sec: metadata.post_run_timeout_sec,
+        repo_subdir: metadata.repo_subdir.clone(),
+        ..ConfigLayer::default()
+    }
+}
+
 fn cli_layer_from(args: &RunArgs) -> ConfigLayer {
     ConfigLayer {
         plan_path: args.plan.clone(),
@@ -569,6 +1817,8 @@ fn cli_layer_from(args: &RunArgs) -> ConfigLayer {
         stet_commands: None,
         log_level: args.log_level.clone(),
         log_file: args.log_file.clone(),
+        log_max_size_mb: args.log_max_size_mb,
+        log_keep_files: args.log_keep_files,
         stet_path: args.stet_path.clone(),
         stet_start_ref: args.stet_start_ref.clone(),
         stet_start_extra_args: args
@@ -596,6 +1846,92 @@ fn cli_layer_from(args: &RunArgs) -> ConfigLayer {
         } else {
             None
         },
+        fail_fast_findings: if args.fail_fast_findings { Some(true) } else { None },
+        agent_max_requests_per_min: args.agent_max_requests_per_min,
+        phase_max_rss_mb: args.phase_max_rss_mb,
+        phase_max_cpu_sec: args.phase_max_cpu_sec,
+        vars: if args.vars.is_empty() {
+            None
+        } else {
+            Some(args.vars.iter().cloned().collect())
+        },
+        summarize_tasks: if args.summarize_tasks { Some(true) } else { None },
+        agent_health_check: if args.agent_health_check { Some(true) } else { None },
+        agent_health_check_timeout_sec: args.agent_health_check_timeout_sec,
+        otel_endpoint: args.otel_endpoint.clone(),
+        stet_stream: if args.stet_stream { Some(true) } else { None },
+        stet_stream_abort_threshold: args.stet_stream_abort_threshold,
+        command_shell: args.command_shell.clone(),
+        on_stale_stet_session: args.on_stale_stet_session.clone(),
+        agent_backend: args.agent_backend.clone(),
+        api_base_url: args.api_base_url.clone(),
+        api_key_env: args.api_key_env.clone(),
+        api_model: args.api_model.clone(),
+        triage_agent_cmd: args.triage_agent_cmd.clone(),
+        triage_model: args.triage_model.clone(),
+        no_git_checks: if args.no_git_checks { Some(true) } else { None },
+        rollback_failed_tasks: if args.rollback_failed_tasks { Some(true) } else { None },
+        serve_status: args.serve_status.clone(),
+        stage_commands: args
+            .stage_commands
+            .as_deref()
+            .map(|s| s.split(',').map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect()),
+        max_prompt_tokens: args.max_prompt_tokens,
+        on_prompt_too_large: args.on_prompt_too_large.clone(),
+        stet_scope_to_task: if args.stet_scope_to_task { Some(true) } else { None },
+        stet_path_args: args.stet_path_args.as_deref().map(parse_extra_args_str),
+        max_agent_calls: args.max_agent_calls,
+        retry_failed_parallel_tasks: if args.retry_failed_parallel_tasks { Some(true) } else { None },
+        env_file: args.env_file.clone(),
+        push_after_segment: if args.push_after_segment { Some(true) } else { None },
+        push_remote: args.push_remote.clone(),
+        push_branch: args.push_branch.clone(),
+        on_push_fail: args.on_push_fail.clone(),
+        phase2_change_check: args.phase2_change_check.clone(),
+        escalation_models: args
+            .escalation_models
+            .as_deref()
+            .map(|s| s.split(',').map(|m| m.trim().to_owned()).filter(|m| !m.is_empty()).collect()),
+        enable_phase3: if args.no_phase3 { Some(false) } else { None },
+        session_size: args.session_size,
+        model_rates: None,
+        parallel_phase3: if args.parallel_phase3 { Some(true) } else { None },
+        strict_state: if args.strict_state { Some(true) } else { None },
+        log_prompts: if args.quiet_agent { Some(false) } else if args.log_prompts { Some(true) } else { None },
+        log_agent_output: if args.quiet_agent { Some("none".to_owned()) } else { args.log_agent_output.clone() },
+        on_renumber: args.on_renumber.clone(),
+        vcs: args.vcs.clone(),
+        stet_health_command: args.stet_health_command.clone(),
+        artifact_retention: None,
+        repo_subdir: args.repo_subdir.clone(),
+        triage_cache_ttl_sec: args.triage_cache_ttl_sec,
+        no_cache: if args.no_cache { Some(true) } else { None },
+        pre_phase2_commands: args
+            .pre_phase2_commands
+            .as_deref()
+            .map(|s| s.split(',').map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect()),
+        post_phase2_commands: args
+            .post_phase2_commands
+            .as_deref()
+            .map(|s| s.split(',').map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect()),
+        on_phase_hook_fail: args.on_phase_hook_fail.clone(),
+        stet_finish_commands: None,
+        findings_summary_top_n: args.findings_summary_top_n,
+        sparse_checkout: if args.sparse_checkout { Some(true) } else { None },
+        lock_path: args.lock_path.clone(),
+        phase3_scope: args.phase3_scope.clone(),
+        on_expect_fail: args.on_expect_fail.clone(),
+        dismiss_requires_confirmation: args.dismiss_requires_confirmation,
+        done_marker: args.done_marker.clone(),
+        on_missing_done_marker: args.on_missing_done_marker.clone(),
+        triage_skip_severities: args
+            .triage_skip_severities
+            .as_deref()
+            .map(|s| s.split(',').map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect()),
+        shutdown_grace_sec: args.shutdown_grace_sec,
+        prompt_middleware: None,
+        execution: None,
+        merge_conflict_retry_count: args.merge_conflict_retry_count,
     }
 }
 
@@ -640,6 +1976,14 @@ fn merge_layers(file: ConfigLayer, env: ConfigLayer, cli: ConfigLayer) -> Config
             .or(file.continue_with_remaining_tasks),
         log_level: cli.log_level.or(env.log_level).or(file.log_level),
         log_file: cli.log_file.or(env.log_file).or(file.log_file),
+        log_max_size_mb: cli
+            .log_max_size_mb
+            .or(env.log_max_size_mb)
+            .or(file.log_max_size_mb),
+        log_keep_files: cli
+            .log_keep_files
+            .or(env.log_keep_files)
+            .or(file.log_keep_files),
         stet_path: cli.stet_path.or(env.stet_path).or(file.stet_path),
         stet_start_ref: cli
             .stet_start_ref
@@ -699,6 +2043,166 @@ fn merge_layers(file: ConfigLayer, env: ConfigLayer, cli: ConfigLayer) -> Config
             .commit_after_phase2
             .or(env.commit_after_phase2)
             .or(file.commit_after_phase2),
+        fail_fast_findings: cli
+            .fail_fast_findings
+            .or(env.fail_fast_findings)
+            .or(file.fail_fast_findings),
+        agent_max_requests_per_min: cli
+            .agent_max_requests_per_min
+            .or(env.agent_max_requests_per_min)
+            .or(file.agent_max_requests_per_min),
+        phase_max_rss_mb: cli
+            .phase_max_rss_mb
+            .or(env.phase_max_rss_mb)
+            .or(file.phase_max_rss_mb),
+        phase_max_cpu_sec: cli
+            .phase_max_cpu_sec
+            .or(env.phase_max_cpu_sec)
+            .or(file.phase_max_cpu_sec),
+        vars: merge_vars([file.vars, env.vars, cli.vars]),
+        summarize_tasks: cli
+            .summarize_tasks
+            .or(env.summarize_tasks)
+            .or(file.summarize_tasks),
+        agent_health_check: cli
+            .agent_health_check
+            .or(env.agent_health_check)
+            .or(file.agent_health_check),
+        agent_health_check_timeout_sec: cli
+            .agent_health_check_timeout_sec
+            .or(env.agent_health_check_timeout_sec)
+            .or(file.agent_health_check_timeout_sec),
+        otel_endpoint: cli.otel_endpoint.or(env.otel_endpoint).or(file.otel_endpoint),
+        stet_stream: cli.stet_stream.or(env.stet_stream).or(file.stet_stream),
+        stet_stream_abort_threshold: cli
+            .stet_stream_abort_threshold
+            .or(env.stet_stream_abort_threshold)
+            .or(file.stet_stream_abort_threshold),
+        command_shell: cli.command_shell.or(env.command_shell).or(file.command_shell),
+        on_stale_stet_session: cli
+            .on_stale_stet_session
+            .or(env.on_stale_stet_session)
+            .or(file.on_stale_stet_session),
+        agent_backend: cli.agent_backend.or(env.agent_backend).or(file.agent_backend),
+        api_base_url: cli.api_base_url.or(env.api_base_url).or(file.api_base_url),
+        api_key_env: cli.api_key_env.or(env.api_key_env).or(file.api_key_env),
+        api_model: cli.api_model.or(env.api_model).or(file.api_model),
+        triage_agent_cmd: cli.triage_agent_cmd.or(env.triage_agent_cmd).or(file.triage_agent_cmd),
+        triage_model: cli.triage_model.or(env.triage_model).or(file.triage_model),
+        no_git_checks: cli.no_git_checks.or(env.no_git_checks).or(file.no_git_checks),
+        rollback_failed_tasks: cli
+            .rollback_failed_tasks
+            .or(env.rollback_failed_tasks)
+            .or(file.rollback_failed_tasks),
+        serve_status: cli.serve_status.or(env.serve_status).or(file.serve_status),
+        stage_commands: cli.stage_commands.or(env.stage_commands).or(file.stage_commands),
+        max_prompt_tokens: cli
+            .max_prompt_tokens
+            .or(env.max_prompt_tokens)
+            .or(file.max_prompt_tokens),
+        on_prompt_too_large: cli
+            .on_prompt_too_large
+            .or(env.on_prompt_too_large)
+            .or(file.on_prompt_too_large),
+        stet_scope_to_task: cli
+            .stet_scope_to_task
+            .or(env.stet_scope_to_task)
+            .or(file.stet_scope_to_task),
+        stet_path_args: cli.stet_path_args.or(env.stet_path_args).or(file.stet_path_args),
+        max_agent_calls: cli.max_agent_calls.or(env.max_agent_calls).or(file.max_agent_calls),
+        retry_failed_parallel_tasks: cli
+            .retry_failed_parallel_tasks
+            .or(env.retry_failed_parallel_tasks)
+            .or(file.retry_failed_parallel_tasks),
+        env_file: cli.env_file.or(env.env_file).or(file.env_file),
+        push_after_segment: cli
+            .push_after_segment
+            .or(env.push_after_segment)
+            .or(file.push_after_segment),
+        push_remote: cli.push_remote.or(env.push_remote).or(file.push_remote),
+        push_branch: cli.push_branch.or(env.push_branch).or(file.push_branch),
+        on_push_fail: cli.on_push_fail.or(env.on_push_fail).or(file.on_push_fail),
+        phase2_change_check: cli
+            .phase2_change_check
+            .or(env.phase2_change_check)
+            .or(file.phase2_change_check),
+        escalation_models: cli
+            .escalation_models
+            .or(env.escalation_models)
+            .or(file.escalation_models),
+        enable_phase3: cli.enable_phase3.or(env.enable_phase3).or(file.enable_phase3),
+        session_size: cli.session_size.or(env.session_size).or(file.session_size),
+        model_rates: cli.model_rates.or(env.model_rates).or(file.model_rates),
+        parallel_phase3: cli.parallel_phase3.or(env.parallel_phase3).or(file.parallel_phase3),
+        strict_state: cli.strict_state.or(env.strict_state).or(file.strict_state),
+        log_prompts: cli.log_prompts.or(env.log_prompts).or(file.log_prompts),
+        log_agent_output: cli.log_agent_output.or(env.log_agent_output).or(file.log_agent_output),
+        on_renumber: cli.on_renumber.or(env.on_renumber).or(file.on_renumber),
+        vcs: cli.vcs.or(env.vcs).or(file.vcs),
+        stet_health_command: cli
+            .stet_health_command
+            .or(env.stet_health_command)
+            .or(file.stet_health_command),
+        artifact_retention: cli
+            .artifact_retention
+            .or(env.artifact_retention)
+            .or(file.artifact_retention),
+        repo_subdir: cli.repo_subdir.or(env.repo_subdir).or(file.repo_subdir),
+        triage_cache_ttl_sec: cli
+            .triage_cache_ttl_sec
+            .or(env.triage_cache_ttl_sec)
+            .or(file.triage_cache_ttl_sec),
+        no_cache: cli.no_cache.or(env.no_cache).or(file.no_cache),
+        pre_phase2_commands: cli
+            .pre_phase2_commands
+            .or(env.pre_phase2_commands)
+            .or(file.pre_phase2_commands),
+        post_phase2_commands: cli
+            .post_phase2_commands
+            .or(env.post_phase2_commands)
+            .or(file.post_phase2_commands),
+        on_phase_hook_fail: cli
+            .on_phase_hook_fail
+            .or(env.on_phase_hook_fail)
+            .or(file.on_phase_hook_fail),
+        stet_finish_commands: cli
+            .stet_finish_commands
+            .or(env.stet_finish_commands)
+            .or(file.stet_finish_commands),
+        findings_summary_top_n: cli
+            .findings_summary_top_n
+            .or(env.findings_summary_top_n)
+            .or(file.findings_summary_top_n),
+        sparse_checkout: cli.sparse_checkout.or(env.sparse_checkout).or(file.sparse_checkout),
+        lock_path: cli.lock_path.or(env.lock_path).or(file.lock_path),
+        phase3_scope: cli.phase3_scope.or(env.phase3_scope).or(file.phase3_scope),
+        on_expect_fail: cli.on_expect_fail.or(env.on_expect_fail).or(file.on_expect_fail),
+        dismiss_requires_confirmation: cli
+            .dismiss_requires_confirmation
+            .or(env.dismiss_requires_confirmation)
+            .or(file.dismiss_requires_confirmation),
+        done_marker: cli.done_marker.or(env.done_marker).or(file.done_marker),
+        on_missing_done_marker: cli
+            .on_missing_done_marker
+            .or(env.on_missing_done_marker)
+            .or(file.on_missing_done_marker),
+        triage_skip_severities: cli
+            .triage_skip_severities
+            .or(env.triage_skip_severities)
+            .or(file.triage_skip_severities),
+        shutdown_grace_sec: cli
+            .shutdown_grace_sec
+            .or(env.shutdown_grace_sec)
+            .or(file.shutdown_grace_sec),
+        prompt_middleware: cli
+            .prompt_middleware
+            .or(env.prompt_middleware)
+            .or(file.prompt_middleware),
+        execution: cli.execution.or(env.execution).or(file.execution),
+        merge_conflict_retry_count: cli
+            .merge_conflict_retry_count
+            .or(env.merge_conflict_retry_count)
+            .or(file.merge_conflict_retry_count),
     }
 }
 
@@ -716,7 +2220,11 @@ mod tests {
             repo,
             normalize: false,
             normalize_retry_count: None,
+            review_normalized: false,
+            renumber: false,
+            write: false,
             config: None,
+            no_config: false,
             agent_cmd: None,
             model: None,
             sandbox: None,
@@ -730,10 +2238,16 @@ mod tests {
             max_address_rounds: None,
             on_findings_remaining: None,
             on_stet_fail: None,
-            task: None,
+            tasks: Vec::new(),
             from_task: None,
+            force_rerun: false,
+            tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            stage: None,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: None,
             stet_path: None,
             stet_start_ref: None,
             stet_start_args: None,
@@ -746,6 +2260,76 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: vec![],
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: None,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: None,
+            on_stale_stet_session: None,
+            agent_backend: None,
+            api_base_url: None,
+            api_key_env: None,
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: None,
+            max_prompt_tokens: None,
+            on_prompt_too_large: None,
+            stet_scope_to_task: false,
+            stet_path_args: None,
+            max_agent_calls: None,
+            only_phase: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: None,
+            push_branch: None,
+            on_push_fail: None,
+            phase2_change_check: None,
+            escalation_models: None,
+            no_phase3: false,
+            dry_run: false,
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: None,
+            quiet_agent: false,
+            on_renumber: None,
+            vcs: None,
+            stet_health_command: None,
+            repo_subdir: None,
+            triage_cache_ttl_sec: None,
+            no_cache: false,
+            pre_phase2_commands: None,
+            post_phase2_commands: None,
+            on_phase_hook_fail: None,
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: None,
+            on_expect_fail: None,
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: None,
+            triage_skip_severities: None,
+            shutdown_grace_sec: None,
+            merge_conflict_retry_count: None,
+            locked: false,
+            print_config: false,
+            detach: false,
+            limit_tasks: None,
+            cleanup_stet: false,
+            session_size: None,
         }
     }
 
@@ -970,7 +2554,11 @@ model = "from-file"
             repo: None,
             normalize: false,
             normalize_retry_count: None,
+            review_normalized: false,
+            renumber: false,
+            write: false,
             config: None,
+            no_config: false,
             agent_cmd: None,
             model: Some("from-cli".to_owned()),
             sandbox: None,
@@ -984,10 +2572,16 @@ model = "from-file"
             max_address_rounds: None,
             on_findings_remaining: None,
             on_stet_fail: None,
-            task: None,
+            tasks: Vec::new(),
             from_task: None,
+            force_rerun: false,
+            tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            stage: None,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: None,
             stet_path: None,
             stet_start_ref: None,
             stet_start_args: None,
@@ -1000,6 +2594,76 @@ model = "from-file"
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: vec![],
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: None,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: None,
+            on_stale_stet_session: None,
+            agent_backend: None,
+            api_base_url: None,
+            api_key_env: None,
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: None,
+            max_prompt_tokens: None,
+            on_prompt_too_large: None,
+            stet_scope_to_task: false,
+            stet_path_args: None,
+            max_agent_calls: None,
+            only_phase: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: None,
+            push_branch: None,
+            on_push_fail: None,
+            phase2_change_check: None,
+            escalation_models: None,
+            no_phase3: false,
+            dry_run: false,
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: None,
+            quiet_agent: false,
+            on_renumber: None,
+            vcs: None,
+            stet_health_command: None,
+            repo_subdir: None,
+            triage_cache_ttl_sec: None,
+            no_cache: false,
+            pre_phase2_commands: None,
+            post_phase2_commands: None,
+            on_phase_hook_fail: None,
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: None,
+            on_expect_fail: None,
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: None,
+            triage_skip_severities: None,
+            shutdown_grace_sec: None,
+            merge_conflict_retry_count: None,
+            locked: false,
+            print_config: false,
+            detach: false,
+            limit_tasks: None,
+            cleanup_stet: false,
+            session_size: None,
         };
         let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
 
@@ -1037,52 +2701,281 @@ agent_cmd = "from-file"
     }
 
     #[test]
-    fn cli_overrides_env() {
+    fn env_file_fills_env_layer_below_process_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        let env_path = dir.path().join(".env.peal");
+        fs::write(&cfg_path, "plan_path = \"p.md\"\nrepo_path = \"/r\"\nmodel = \"from-file\"\n").unwrap();
+        fs::write(
+            &env_path,
+            "# local overrides\nexport PEAL_MODEL=\"from-env-file\"\nPEAL_AGENT_CMD='dotenv-agent'\nOTHER=x\n\nPEAL_MAX_PARALLEL=7\n",
+        )
+        .unwrap();
+
         fn fake_env(suffix: &str) -> Option<String> {
-            if suffix == "AGENT_CMD" {
-                Some("from-env".to_owned())
-            } else {
-                None
-            }
+            (suffix == "AGENT_CMD").then(|| "from-env".to_owned())
         }
 
-        let args = RunArgs {
-            plan: Some(PathBuf::from("p.md")),
-            repo: Some(PathBuf::from("/r")),
-            normalize: false,
-            normalize_retry_count: None,
-            config: None,
-            agent_cmd: Some("from-cli".to_owned()),
-            model: None,
-            sandbox: None,
-            state_dir: None,
-            phase_timeout_sec: None,
-            phase_retry_count: None,
-            phase_3_retry_count: None,
-            parallel: false,
-            max_parallel: None,
-            continue_with_remaining_tasks: false,
-            max_address_rounds: None,
-            on_findings_remaining: None,
-            on_stet_fail: None,
-            task: None,
-            from_task: None,
-            log_level: None,
-            log_file: None,
-            stet_path: None,
-            stet_start_ref: None,
-            stet_start_args: None,
-            stet_run_args: None,
-            stet_disable_llm_triage: None,
-            post_run_commands: None,
-            post_run_timeout_sec: None,
-            validate_plan_text: false,
-            min_plan_text_len: None,
-            run_summary_path: None,
-            max_consecutive_task_failures: None,
-            commit_after_phase2: false,
-        };
-        let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
+        let mut args = minimal_cli_args(None, None);
+        args.env_file = Some(env_path);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, fake_env).unwrap();
+
+        assert_eq!(cfg.model.as_deref(), Some("from-env-file"), "env file wins over config file");
+        assert_eq!(cfg.agent_cmd, "from-env", "process env wins over env file");
+        assert_eq!(cfg.max_parallel, 7);
+    }
+
+    #[test]
+    fn parallel_with_max_parallel_zero_is_an_error_naming_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(&cfg_path, "plan_path = \"p.md\"\nrepo_path = \"/r\"\nmax_parallel = 0\n").unwrap();
+        fn fake_env(suffix: &str) -> Option<String> {
+            (suffix == "PARALLEL").then(|| "true".to_owned())
+        }
+
+        let args = minimal_cli_args(None, None);
+        let err = PealConfig::load_with_env(Some(&cfg_path), &args, fake_env).unwrap_err();
+        let msg = format!("{err}");
+        assert!(msg.contains("parallel = true (from env)"), "{msg}");
+        assert!(msg.contains("max_parallel = 0 (from config file)"), "{msg}");
+
+        // Without parallel, max_parallel = 0 still just turns parallel off.
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert!(!cfg.parallel);
+        assert!(cfg.warnings().is_empty());
+    }
+
+    #[test]
+    fn warnings_flag_parallel_settings_without_effect() {
+        let mut args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        args.max_parallel = Some(1);
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(cfg.parallel, "an explicit max_parallel turns parallel on");
+        assert_eq!(cfg.warnings(), ["parallel = true with max_parallel = 1 runs parallel blocks one task at a time"]);
+
+        fn parallel_off(suffix: &str) -> Option<String> {
+            (suffix == "PARALLEL").then(|| "false".to_owned())
+        }
+        args.max_parallel = Some(8);
+        args.parallel_phase3 = true;
+        let cfg = PealConfig::load_with_env(None, &args, parallel_off).unwrap();
+        assert_eq!(
+            cfg.warnings(),
+            [
+                "max_parallel = 8 has no effect because parallel = false",
+                "parallel_phase3 = true has no effect because parallel is off",
+            ]
+        );
+    }
+
+    #[test]
+    fn load_with_sources_reports_each_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&cfg_path, "repo_path = \"/r\"\nmodel = \"file-model\"\nsandbox = \"enabled\"\n").unwrap();
+        fs::write(dir.path().join("plan.peal.toml"), "sandbox = \"sidecar\"\n").unwrap();
+        fn fake_env(suffix: &str) -> Option<String> {
+            (suffix == "MAX_PARALLEL").then(|| "2".to_owned())
+        }
+        let metadata = PlanMetadata {
+            phase_timeout_sec: Some(60),
+            model: Some("plan-model".to_owned()),
+            ..PlanMetadata::default()
+        };
+
+        let args = minimal_cli_args(Some(plan_path), None);
+        let (cfg, values) =
+            PealConfig::load_with_sources_env(Some(&cfg_path), &args, fake_env, Some(&metadata)).unwrap();
+        let source = |key: &str| values.iter().find(|v| v.key == key).unwrap().source;
+        assert_eq!(source("plan_path"), ConfigSource::Cli);
+        assert_eq!(source("max_parallel"), ConfigSource::Env);
+        assert_eq!(source("sandbox"), ConfigSource::PlanSidecar);
+        assert_eq!(source("model"), ConfigSource::File);
+        assert_eq!(source("phase_timeout_sec"), ConfigSource::PlanFrontMatter);
+        assert_eq!(source("state_dir"), ConfigSource::Default);
+
+        let shown = render_resolved(&values, &cfg.warnings(), ShowKeys::Set);
+        assert!(shown.contains("sandbox = \"sidecar\"  # from plan sidecar\n"), "{shown}");
+        assert!(shown.contains("max_parallel = 2  # from env PEAL_MAX_PARALLEL\n"), "{shown}");
+        assert!(shown.contains("parallel = true  # from env PEAL_MAX_PARALLEL (via max_parallel)\n"), "{shown}");
+        assert!(!shown.contains("state_dir"), "defaults are left out: {shown}");
+        let all = render_resolved(&values, &[], ShowKeys::All);
+        assert!(all.contains("state_dir = \".peal\"  # default\n"), "{all}");
+        assert!(all.contains("# log_file is unset  # default\n"), "{all}");
+    }
+
+    #[test]
+    fn diff_defaults_lists_values_that_differ_whatever_set_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(&cfg_path, "repo_path = \"/r\"\nsandbox = \"disabled\"\nmodel = \"m\"\n").unwrap();
+        fn no_env(_: &str) -> Option<String> {
+            None
+        }
+
+        let args = minimal_cli_args(Some(PathBuf::from("plan.md")), None);
+        let (_, values) = PealConfig::load_with_sources_env(Some(&cfg_path), &args, no_env, None).unwrap();
+        let shown = render_resolved(&values, &[], ShowKeys::NonDefault);
+        let keys: Vec<&str> = shown.lines().map(|l| l.split(' ').next().unwrap()).collect();
+        // `sandbox` is set to its default value, so it is not a difference.
+        assert_eq!(keys, ["model", "plan_path", "repo_path"], "{shown}");
+        assert!(shown.contains("model = \"m\"  # from config file\n"), "{shown}");
+    }
+
+    #[test]
+    fn env_file_from_config_and_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        let env_path = dir.path().join("ci.env");
+        fs::write(&env_path, "PEAL_SANDBOX=enabled\n").unwrap();
+        fs::write(
+            &cfg_path,
+            format!("plan_path = \"p.md\"\nrepo_path = \"/r\"\nenv_file = {:?}\n", env_path.display().to_string()),
+        )
+        .unwrap();
+        let args = minimal_cli_args(None, None);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(cfg.sandbox, "enabled");
+        assert_eq!(cfg.env_file, Some(env_path.clone()));
+
+        fs::write(&env_path, "PEAL_SANDBOX\n").unwrap();
+        let err = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap_err();
+        assert!(format!("{err}").contains("line 1: expected KEY=VALUE"), "{err}");
+
+        let mut args = minimal_cli_args(None, None);
+        args.env_file = Some(dir.path().join("missing.env"));
+        assert!(PealConfig::load_with_env(Some(&cfg_path), &args, no_env).is_err());
+    }
+
+    #[test]
+    fn cli_overrides_env() {
+        fn fake_env(suffix: &str) -> Option<String> {
+            if suffix == "AGENT_CMD" {
+                Some("from-env".to_owned())
+            } else {
+                None
+            }
+        }
+
+        let args = RunArgs {
+            plan: Some(PathBuf::from("p.md")),
+            repo: Some(PathBuf::from("/r")),
+            normalize: false,
+            normalize_retry_count: None,
+            review_normalized: false,
+            renumber: false,
+            write: false,
+            config: None,
+            no_config: false,
+            agent_cmd: Some("from-cli".to_owned()),
+            model: None,
+            sandbox: None,
+            state_dir: None,
+            phase_timeout_sec: None,
+            phase_retry_count: None,
+            phase_3_retry_count: None,
+            parallel: false,
+            max_parallel: None,
+            continue_with_remaining_tasks: false,
+            max_address_rounds: None,
+            on_findings_remaining: None,
+            on_stet_fail: None,
+            tasks: Vec::new(),
+            from_task: None,
+            force_rerun: false,
+            tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            stage: None,
+            log_level: None,
+            log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: None,
+            stet_path: None,
+            stet_start_ref: None,
+            stet_start_args: None,
+            stet_run_args: None,
+            stet_disable_llm_triage: None,
+            post_run_commands: None,
+            post_run_timeout_sec: None,
+            validate_plan_text: false,
+            min_plan_text_len: None,
+            run_summary_path: None,
+            max_consecutive_task_failures: None,
+            commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: vec![],
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: None,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: None,
+            on_stale_stet_session: None,
+            agent_backend: None,
+            api_base_url: None,
+            api_key_env: None,
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: None,
+            max_prompt_tokens: None,
+            on_prompt_too_large: None,
+            stet_scope_to_task: false,
+            stet_path_args: None,
+            max_agent_calls: None,
+            only_phase: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: None,
+            push_branch: None,
+            on_push_fail: None,
+            phase2_change_check: None,
+            escalation_models: None,
+            no_phase3: false,
+            dry_run: false,
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: None,
+            quiet_agent: false,
+            on_renumber: None,
+            vcs: None,
+            stet_health_command: None,
+            repo_subdir: None,
+            triage_cache_ttl_sec: None,
+            no_cache: false,
+            pre_phase2_commands: None,
+            post_phase2_commands: None,
+            on_phase_hook_fail: None,
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: None,
+            on_expect_fail: None,
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: None,
+            triage_skip_severities: None,
+            shutdown_grace_sec: None,
+            merge_conflict_retry_count: None,
+            locked: false,
+            print_config: false,
+            detach: false,
+            limit_tasks: None,
+            cleanup_stet: false,
+            session_size: None,
+        };
+        let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
 
         assert_eq!(cfg.agent_cmd, "from-cli", "CLI wins over env");
     }
@@ -1157,7 +3050,11 @@ bogus_key = true
             repo: Some(PathBuf::from("/r")),
             normalize: false,
             normalize_retry_count: None,
+            review_normalized: false,
+            renumber: false,
+            write: false,
             config: None,
+            no_config: false,
             agent_cmd: None,
             model: None,
             sandbox: None,
@@ -1171,10 +3068,16 @@ bogus_key = true
             max_address_rounds: None,
             on_findings_remaining: None,
             on_stet_fail: None,
-            task: None,
+            tasks: Vec::new(),
             from_task: None,
+            force_rerun: false,
+            tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            stage: None,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: None,
             stet_path: None,
             stet_start_ref: None,
             stet_start_args: None,
@@ -1187,6 +3090,76 @@ bogus_key = true
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: vec![],
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: None,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: None,
+            on_stale_stet_session: None,
+            agent_backend: None,
+            api_base_url: None,
+            api_key_env: None,
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: None,
+            max_prompt_tokens: None,
+            on_prompt_too_large: None,
+            stet_scope_to_task: false,
+            stet_path_args: None,
+            max_agent_calls: None,
+            only_phase: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: None,
+            push_branch: None,
+            on_push_fail: None,
+            phase2_change_check: None,
+            escalation_models: None,
+            no_phase3: false,
+            dry_run: false,
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: None,
+            quiet_agent: false,
+            on_renumber: None,
+            vcs: None,
+            stet_health_command: None,
+            repo_subdir: None,
+            triage_cache_ttl_sec: None,
+            no_cache: false,
+            pre_phase2_commands: None,
+            post_phase2_commands: None,
+            on_phase_hook_fail: None,
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: None,
+            on_expect_fail: None,
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: None,
+            triage_skip_severities: None,
+            shutdown_grace_sec: None,
+            merge_conflict_retry_count: None,
+            locked: false,
+            print_config: false,
+            detach: false,
+            limit_tasks: None,
+            cleanup_stet: false,
+            session_size: None,
         };
         let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
 
@@ -1280,6 +3253,29 @@ bogus_key = true
         );
     }
 
+    #[test]
+    fn validate_checks_repo_subdir_is_a_directory_inside_the_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+        fs::create_dir(dir.path().join("svc")).unwrap();
+
+        let args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        let mut cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        cfg.no_git_checks = true;
+        for (subdir, reason) in [("../svc", "relative path inside"), ("missing", "not a directory")] {
+            cfg.repo_subdir = Some(PathBuf::from(subdir));
+            let err = cfg.validate().unwrap_err();
+            assert!(
+                matches!(&err, crate::error::PealError::InvalidRepoSubdir { reason: r, .. } if r.contains(reason)),
+                "{subdir}: got {err:?}"
+            );
+        }
+        cfg.repo_subdir = Some(PathBuf::from("svc"));
+        cfg.validate().unwrap();
+        assert_eq!(cfg.work_dir(), dir.path().join("svc"));
+    }
+
     #[test]
     fn validate_fails_when_repo_not_git() {
         let dir = tempfile::tempdir().unwrap();
@@ -1298,6 +3294,251 @@ bogus_key = true
         );
     }
 
+    #[test]
+    fn validate_skips_git_checks_with_no_git_checks() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        args.no_git_checks = true;
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(cfg.no_git_checks);
+        cfg.validate().expect("non-git repo accepted with no_git_checks");
+
+        args.no_git_checks = false;
+        let cfg = PealConfig::load_with_env(None, &args, |k| {
+            (k == "NO_GIT_CHECKS").then(|| "true".to_owned())
+        })
+        .unwrap();
+        assert!(cfg.no_git_checks);
+    }
+
+    #[test]
+    fn push_after_segment_defaults_and_rejects_invalid_on_push_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(!cfg.push_after_segment);
+        assert_eq!(cfg.push_remote, "origin");
+        assert!(cfg.push_branch.is_none());
+        assert_eq!(cfg.on_push_fail, "warn");
+
+        args.push_after_segment = true;
+        args.on_push_fail = Some("ignore".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(cfg.push_after_segment);
+        let err = cfg.validate().unwrap_err();
+        assert!(format!("{err}").contains("Invalid on_push_fail"), "got: {err}");
+    }
+
+    #[test]
+    fn escalation_models_load_from_file_and_split_cli_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(&cfg_path, "escalation_models = [\"gpt-5.2\", \"o3-pro\"]\n").unwrap();
+
+        let mut args = minimal_cli_args(Some(dir.path().join("plan.md")), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(cfg.escalation_models.is_empty());
+
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(cfg.escalation_models, ["gpt-5.2", "o3-pro"]);
+
+        args.escalation_models = Some("o3-pro, ,gpt-5.2".to_owned());
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(cfg.escalation_models, ["o3-pro", "gpt-5.2"]);
+    }
+
+    #[test]
+    fn enable_phase3_defaults_true_and_no_phase3_flag_turns_it_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(&cfg_path, "enable_phase3 = false\n").unwrap();
+
+        let mut args = minimal_cli_args(Some(dir.path().join("plan.md")), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(cfg.enable_phase3);
+
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert!(!cfg.enable_phase3);
+
+        args.no_phase3 = true;
+        let env = |k: &str| (k == "ENABLE_PHASE3").then(|| "true".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, env).unwrap();
+        assert!(!cfg.enable_phase3, "--no-phase3 wins over env");
+    }
+
+    #[test]
+    fn model_rates_load_from_file_with_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            "[model_rates.\"gpt-5.2\"]\ninput_usd_per_mtok = 1.25\noutput_usd_per_mtok = 10.0\n\n\
+             [model_rates.default]\ncall_overhead_sec = 45.0\n",
+        )
+        .unwrap();
+
+        let args = minimal_cli_args(Some(dir.path().join("plan.md")), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        let rate = &cfg.model_rates["gpt-5.2"];
+        assert_eq!(rate.input_usd_per_mtok, 1.25);
+        assert_eq!(rate.output_usd_per_mtok, 10.0);
+        assert_eq!(rate.output_tokens_per_sec, ModelRate::default().output_tokens_per_sec);
+        assert_eq!(cfg.model_rates["default"].call_overhead_sec, 45.0);
+
+        fs::write(&cfg_path, "[model_rates.default]\ncost = 1.0\n").unwrap();
+        assert!(PealConfig::load_with_env(Some(&cfg_path), &args, no_env).is_err());
+    }
+
+    #[test]
+    fn artifact_retention_loads_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(&cfg_path, "[artifact_retention]\nkeep_runs = 5\n").unwrap();
+
+        let args = minimal_cli_args(Some(dir.path().join("plan.md")), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert_eq!(cfg.artifact_retention, ArtifactRetention::default());
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(cfg.artifact_retention, ArtifactRetention { keep_runs: Some(5), max_total_mb: None });
+
+        fs::write(&cfg_path, "[artifact_retention]\nkeep = 5\n").unwrap();
+        assert!(PealConfig::load_with_env(Some(&cfg_path), &args, no_env).is_err());
+    }
+
+    #[test]
+    fn plan_sidecar_overrides_file_but_not_env_or_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(&cfg_path, "model = \"file-model\"\nphase_timeout_sec = 100\nmax_address_rounds = 2\n").unwrap();
+        let plan_path = dir.path().join("tasks.md");
+        assert_eq!(plan_sidecar_path(&plan_path), dir.path().join("tasks.peal.toml"));
+        fs::write(
+            plan_sidecar_path(&plan_path),
+            "model = \"sidecar-model\"\nphase_timeout_sec = 900\nrepo_path = \"/elsewhere\"\n",
+        )
+        .unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(cfg.model.as_deref(), Some("sidecar-model"));
+        assert_eq!(cfg.phase_timeout_sec, 900);
+        assert_eq!(cfg.max_address_rounds, 2, "unset in sidecar, kept from file");
+        assert_eq!(cfg.repo_path, dir.path(), "sidecar cannot move the repo");
+
+        let env = |k: &str| (k == "PHASE_TIMEOUT_SEC").then(|| "60".to_owned());
+        args.model = Some("cli-model".to_owned());
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, env).unwrap();
+        assert_eq!(cfg.model.as_deref(), Some("cli-model"));
+        assert_eq!(cfg.phase_timeout_sec, 60);
+
+        args.no_config = true;
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert_eq!(cfg.phase_timeout_sec, DEFAULT_PHASE_TIMEOUT_SEC, "--no-config skips the sidecar");
+    }
+
+    #[test]
+    fn parallel_phase3_defaults_off_and_loads_from_env_and_cli() {
+        let mut args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        assert!(!PealConfig::load_with_env(None, &args, no_env).unwrap().parallel_phase3);
+
+        let env = |k: &str| (k == "PARALLEL_PHASE3").then(|| "true".to_owned());
+        assert!(PealConfig::load_with_env(None, &args, env).unwrap().parallel_phase3);
+
+        args.parallel_phase3 = true;
+        assert!(PealConfig::load_with_env(None, &args, no_env).unwrap().parallel_phase3);
+    }
+
+    #[test]
+    fn quiet_agent_overrides_agent_logging_from_env() {
+        let env = |k: &str| match k {
+            "LOG_PROMPTS" => Some("true".to_owned()),
+            "LOG_AGENT_OUTPUT" => Some("full".to_owned()),
+            _ => None,
+        };
+        let mut args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(!cfg.log_prompts);
+        assert_eq!(cfg.log_agent_output, "none");
+
+        let cfg = PealConfig::load_with_env(None, &args, env).unwrap();
+        assert!(cfg.log_prompts);
+        assert_eq!(cfg.log_agent_output, "full");
+
+        args.quiet_agent = true;
+        let cfg = PealConfig::load_with_env(None, &args, env).unwrap();
+        assert!(!cfg.log_prompts);
+        assert_eq!(cfg.log_agent_output, "none");
+    }
+
+    #[test]
+    fn validate_rejects_invalid_log_agent_output() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        args.log_agent_output = Some("verbose".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        let err = cfg.validate().unwrap_err();
+        assert!(format!("{err}").contains("Invalid log_agent_output"), "got: {err}");
+    }
+
+    #[test]
+    fn validate_rejects_invalid_on_renumber() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        args.on_renumber = Some("skip".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        let err = cfg.validate().unwrap_err();
+        assert!(format!("{err}").contains("Invalid on_renumber"), "got: {err}");
+    }
+
+    #[test]
+    fn validate_checks_vcs_and_rejects_git_only_options_for_other_backends() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert_eq!(cfg.vcs, "auto");
+        cfg.validate().unwrap();
+
+        args.vcs = Some("svn".to_owned());
+        let err = PealConfig::load_with_env(None, &args, no_env).unwrap().validate().unwrap_err();
+        assert!(format!("{err}").contains("Invalid vcs"), "got: {err}");
+
+        args.vcs = Some("jj".to_owned());
+        args.no_git_checks = true;
+        args.rollback_failed_tasks = true;
+        let err = PealConfig::load_with_env(None, &args, no_env).unwrap().validate().unwrap_err();
+        assert!(
+            matches!(err, crate::error::PealError::VcsUnsupported { option: "rollback_failed_tasks", .. }),
+            "got: {err:?}"
+        );
+
+        args.rollback_failed_tasks = false;
+        args.stet_start_ref = Some("{{MERGE_BASE}}".to_owned());
+        let err = PealConfig::load_with_env(None, &args, no_env).unwrap().validate().unwrap_err();
+        assert!(
+            matches!(err, crate::error::PealError::VcsUnsupported { option: "stet_start_ref placeholders", .. }),
+            "got: {err:?}"
+        );
+    }
+
     #[test]
     fn validate_rejects_invalid_on_stet_fail() {
         let dir = tempfile::tempdir().unwrap();
@@ -1371,7 +3612,11 @@ sandbox = "file-sandbox"
             repo: None,
             normalize: false,
             normalize_retry_count: None,
+            review_normalized: false,
+            renumber: false,
+            write: false,
             config: None,
+            no_config: false,
             agent_cmd: Some("from-cli".to_owned()),
             model: None,
             sandbox: None,
@@ -1385,10 +3630,16 @@ sandbox = "file-sandbox"
             max_address_rounds: None,
             on_findings_remaining: None,
             on_stet_fail: None,
-            task: None,
+            tasks: Vec::new(),
             from_task: None,
+            force_rerun: false,
+            tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            stage: None,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: None,
             stet_path: None,
             stet_start_ref: None,
             stet_start_args: None,
@@ -1401,6 +3652,76 @@ sandbox = "file-sandbox"
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: vec![],
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: None,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: None,
+            on_stale_stet_session: None,
+            agent_backend: None,
+            api_base_url: None,
+            api_key_env: None,
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: None,
+            max_prompt_tokens: None,
+            on_prompt_too_large: None,
+            stet_scope_to_task: false,
+            stet_path_args: None,
+            max_agent_calls: None,
+            only_phase: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: None,
+            push_branch: None,
+            on_push_fail: None,
+            phase2_change_check: None,
+            escalation_models: None,
+            no_phase3: false,
+            dry_run: false,
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: None,
+            quiet_agent: false,
+            on_renumber: None,
+            vcs: None,
+            stet_health_command: None,
+            repo_subdir: None,
+            triage_cache_ttl_sec: None,
+            no_cache: false,
+            pre_phase2_commands: None,
+            post_phase2_commands: None,
+            on_phase_hook_fail: None,
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: None,
+            on_expect_fail: None,
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: None,
+            triage_skip_severities: None,
+            shutdown_grace_sec: None,
+            merge_conflict_retry_count: None,
+            locked: false,
+            print_config: false,
+            detach: false,
+            limit_tasks: None,
+            cleanup_stet: false,
+            session_size: None,
         };
         let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, fake_env).unwrap();
 
@@ -1542,15 +3863,118 @@ stet_run_extra_args = ["--verify", "--context", "256k"]
     }
 
     #[test]
-    fn stet_extra_args_from_env() {
-        fn fake_env(suffix: &str) -> Option<String> {
-            match suffix {
-                "STET_START_EXTRA_ARGS" => Some("--allow-dirty".to_owned()),
-                "STET_RUN_EXTRA_ARGS" => Some("--verify --context 256k".to_owned()),
-                _ => None,
-            }
-        }
-
+    fn prompt_middleware_from_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            r#"
+plan_path = "p.md"
+repo_path = "/r"
+
+[[prompt_middleware]]
+kind = "prefix"
+file = "docs/STANDARDS.md"
+
+[[prompt_middleware]]
+kind = "redact"
+patterns = ["sk-[A-Za-z0-9]+", "("]
+
+[[prompt_middleware]]
+kind = "max_length"
+max_chars = 200000
+"#,
+        )
+        .unwrap();
+
+        let args = minimal_cli_args(None, None);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(
+            cfg.prompt_middleware,
+            vec![
+                PromptMiddlewareSpec::Prefix {
+                    text: None,
+                    file: Some(PathBuf::from("docs/STANDARDS.md")),
+                },
+                PromptMiddlewareSpec::Redact {
+                    patterns: vec!["sk-[A-Za-z0-9]+".to_owned(), "(".to_owned()],
+                },
+                PromptMiddlewareSpec::MaxLength { max_chars: 200000 },
+            ]
+        );
+        let err = crate::prompt_middleware::Chain::from_config(&cfg).err().unwrap();
+        assert!(
+            matches!(err, PealError::InvalidPromptMiddleware { index: 0, ref detail } if detail.contains("STANDARDS.md")),
+            "got: {err:?}"
+        );
+        let mut cfg = cfg;
+        cfg.repo_path = dir.path().to_path_buf();
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/STANDARDS.md"), "Use tabs.").unwrap();
+        let err = crate::prompt_middleware::Chain::from_config(&cfg).err().unwrap();
+        assert!(matches!(err, PealError::InvalidPromptMiddleware { index: 1, .. }), "got: {err:?}");
+
+        fs::write(&cfg_path, "plan_path = \"p.md\"\nrepo_path = \"/r\"\n[[prompt_middleware]]\nkind = \"shout\"\n").unwrap();
+        assert!(PealConfig::load_with_env(Some(&cfg_path), &args, no_env).is_err());
+    }
+
+    #[test]
+    fn execution_from_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            r#"
+plan_path = "p.md"
+repo_path = "/r"
+
+[execution]
+container_image = "ghcr.io/acme/toolchain:1"
+container_runtime = "podman"
+container_env = ["CURSOR_API_KEY"]
+stet_in_container = true
+"#,
+        )
+        .unwrap();
+
+        let args = minimal_cli_args(None, None);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(
+            cfg.execution,
+            Execution {
+                container_image: Some("ghcr.io/acme/toolchain:1".to_owned()),
+                container_runtime: Some("podman".to_owned()),
+                container_args: Vec::new(),
+                container_env: vec!["CURSOR_API_KEY".to_owned()],
+                stet_in_container: true,
+            }
+        );
+        assert_eq!(cfg.execution.runtime(), "podman");
+
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let mut cfg = cfg;
+        cfg.plan_path = dir.path().join("plan.md");
+        cfg.repo_path = dir.path().to_path_buf();
+        fs::write(&cfg.plan_path, "## Task 1\nDo it.").unwrap();
+        cfg.validate().unwrap();
+        let mut bad = cfg.clone();
+        bad.execution.container_runtime = Some("lxc".to_owned());
+        assert!(matches!(bad.validate(), Err(PealError::InvalidExecution { ref detail }) if detail.contains("lxc")));
+        let mut bad = cfg;
+        bad.execution.container_image = None;
+        assert!(matches!(bad.validate(), Err(PealError::InvalidExecution { ref detail }) if detail.contains("stet_in_container")));
+    }
+
+    #[test]
+    fn stet_extra_args_from_env() {
+        fn fake_env(suffix: &str) -> Option<String> {
+            match suffix {
+                "STET_START_EXTRA_ARGS" => Some("--allow-dirty".to_owned()),
+                "STET_RUN_EXTRA_ARGS" => Some("--verify --context 256k".to_owned()),
+                _ => None,
+            }
+        }
+
         let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
         let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
 
@@ -2014,4 +4438,562 @@ max_consecutive_task_failures = 10
         let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, fake_env).unwrap();
         assert_eq!(cfg.max_consecutive_task_failures, Some(2), "CLI wins");
     }
+
+    #[test]
+    fn log_rotation_defaults() {
+        let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert_eq!(cfg.log_max_size_mb, None);
+        assert_eq!(cfg.log_keep_files, 5);
+    }
+
+    #[test]
+    fn log_rotation_from_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            r#"
+plan_path = "p.md"
+repo_path = "/r"
+log_max_size_mb = 50
+log_keep_files = 2
+"#,
+        )
+        .unwrap();
+
+        let args = minimal_cli_args(None, None);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(cfg.log_max_size_mb, Some(50));
+        assert_eq!(cfg.log_keep_files, 2);
+    }
+
+    #[test]
+    fn log_rotation_from_env() {
+        fn fake_env(suffix: &str) -> Option<String> {
+            match suffix {
+                "LOG_MAX_SIZE_MB" => Some("10".to_owned()),
+                "LOG_KEEP_FILES" => Some("0".to_owned()),
+                _ => None,
+            }
+        }
+
+        let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
+        assert_eq!(cfg.log_max_size_mb, Some(10));
+        assert_eq!(cfg.log_keep_files, 0);
+    }
+
+    #[test]
+    fn log_rotation_cli_overrides_env() {
+        fn fake_env(suffix: &str) -> Option<String> {
+            match suffix {
+                "LOG_MAX_SIZE_MB" => Some("10".to_owned()),
+                "LOG_KEEP_FILES" => Some("3".to_owned()),
+                _ => None,
+            }
+        }
+
+        let mut args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        args.log_max_size_mb = Some(100);
+        args.log_keep_files = Some(7);
+        let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
+        assert_eq!(cfg.log_max_size_mb, Some(100));
+        assert_eq!(cfg.log_keep_files, 7);
+    }
+
+    #[test]
+    fn plan_metadata_fills_defaults() {
+        let metadata = PlanMetadata {
+            model: Some("plan-model".to_owned()),
+            phase_timeout_sec: Some(60),
+            post_run_timeout_sec: Some(30),
+            repo_subdir: Some(PathBuf::from("services/auth")),
+            ..Default::default()
+        };
+        let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env_and_plan(None, &args, no_env, Some(&metadata)).unwrap();
+        assert_eq!(cfg.model.as_deref(), Some("plan-model"));
+        assert_eq!(cfg.phase_timeout_sec, 60);
+        assert_eq!(cfg.post_run_timeout_sec, Some(30));
+        assert_eq!(cfg.repo_subdir, Some(PathBuf::from("services/auth")));
+    }
+
+    #[test]
+    fn plan_metadata_below_file_env_and_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            r#"
+plan_path = "p.md"
+repo_path = "/r"
+phase_timeout_sec = 900
+"#,
+        )
+        .unwrap();
+
+        fn fake_env(suffix: &str) -> Option<String> {
+            if suffix == "POST_RUN_TIMEOUT_SEC" {
+                Some("45".to_owned())
+            } else {
+                None
+            }
+        }
+
+        let metadata = PlanMetadata {
+            model: Some("plan-model".to_owned()),
+            phase_timeout_sec: Some(60),
+            post_run_timeout_sec: Some(30),
+            ..Default::default()
+        };
+        let mut args = minimal_cli_args(None, None);
+        args.model = Some("cli-model".to_owned());
+        let cfg =
+            PealConfig::load_with_env_and_plan(Some(&cfg_path), &args, fake_env, Some(&metadata))
+                .unwrap();
+        assert_eq!(cfg.model.as_deref(), Some("cli-model"), "CLI wins");
+        assert_eq!(cfg.post_run_timeout_sec, Some(45), "env wins");
+        assert_eq!(cfg.phase_timeout_sec, 900, "file wins");
+    }
+
+    #[test]
+    fn fail_fast_findings_defaults_to_false() {
+        let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(!cfg.fail_fast_findings);
+    }
+
+    #[test]
+    fn fail_fast_findings_from_toml_env_and_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            r#"
+plan_path = "p.md"
+repo_path = "/r"
+fail_fast_findings = true
+"#,
+        )
+        .unwrap();
+        let args = minimal_cli_args(None, None);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert!(cfg.fail_fast_findings);
+
+        fn fake_env(suffix: &str) -> Option<String> {
+            if suffix == "FAIL_FAST_FINDINGS" {
+                Some("true".to_owned())
+            } else {
+                None
+            }
+        }
+        let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
+        assert!(cfg.fail_fast_findings);
+
+        let mut args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        args.fail_fast_findings = true;
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(cfg.fail_fast_findings);
+    }
+
+
+    #[test]
+    fn agent_max_requests_per_min_precedence_cli_over_env_over_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            r#"
+plan_path = "p.md"
+repo_path = "/r"
+agent_max_requests_per_min = 60
+"#,
+        )
+        .unwrap();
+        let args = minimal_cli_args(None, None);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(cfg.agent_max_requests_per_min, Some(60));
+
+        fn fake_env(suffix: &str) -> Option<String> {
+            if suffix == "AGENT_MAX_REQUESTS_PER_MIN" {
+                Some("30".to_owned())
+            } else {
+                None
+            }
+        }
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, fake_env).unwrap();
+        assert_eq!(cfg.agent_max_requests_per_min, Some(30), "env wins over file");
+
+        let mut args = minimal_cli_args(None, None);
+        args.agent_max_requests_per_min = Some(10);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, fake_env).unwrap();
+        assert_eq!(cfg.agent_max_requests_per_min, Some(10), "CLI wins");
+
+        let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert_eq!(cfg.agent_max_requests_per_min, None, "unlimited by default");
+    }
+
+
+    // -- config file auto-detection --
+
+    #[test]
+    fn discover_config_prefers_repo_peal_toml_then_dot_peal() {
+        let repo = tempfile::tempdir().unwrap();
+        let cwd = tempfile::tempdir().unwrap();
+        let args = minimal_cli_args(None, Some(repo.path().to_path_buf()));
+
+        assert_eq!(discover_config_path_with_env(&args, cwd.path(), no_env), None);
+
+        fs::write(cwd.path().join("peal.toml"), "").unwrap();
+        assert_eq!(
+            discover_config_path_with_env(&args, cwd.path(), no_env),
+            Some(cwd.path().join("peal.toml")),
+            "falls back to the current directory"
+        );
+
+        fs::create_dir_all(repo.path().join(".peal")).unwrap();
+        fs::write(repo.path().join(".peal/config.toml"), "").unwrap();
+        assert_eq!(
+            discover_config_path_with_env(&args, cwd.path(), no_env),
+            Some(repo.path().join(".peal/config.toml")),
+            "repo directory wins over cwd"
+        );
+
+        fs::write(repo.path().join("peal.toml"), "").unwrap();
+        assert_eq!(
+            discover_config_path_with_env(&args, cwd.path(), no_env),
+            Some(repo.path().join("peal.toml")),
+            "peal.toml wins over .peal/config.toml"
+        );
+    }
+
+    #[test]
+    fn discover_config_uses_repo_from_env() {
+        let cwd = tempfile::tempdir().unwrap();
+        let args = minimal_cli_args(None, None);
+        fn fake_env(suffix: &str) -> Option<String> {
+            if suffix == "REPO_PATH" {
+                Some("/nonexistent-peal-repo".to_owned())
+            } else {
+                None
+            }
+        }
+        assert_eq!(discover_config_path_with_env(&args, cwd.path(), fake_env), None);
+
+        fs::write(cwd.path().join("peal.toml"), "").unwrap();
+        assert_eq!(
+            discover_config_path_with_env(&args, cwd.path(), fake_env),
+            Some(cwd.path().join("peal.toml"))
+        );
+    }
+
+    #[test]
+    fn discover_config_disabled_by_explicit_config_or_no_config() {
+        let cwd = tempfile::tempdir().unwrap();
+        fs::write(cwd.path().join("peal.toml"), "").unwrap();
+
+        let mut args = minimal_cli_args(None, None);
+        args.no_config = true;
+        assert_eq!(discover_config_path_with_env(&args, cwd.path(), no_env), None);
+
+        let mut args = minimal_cli_args(None, None);
+        args.config = Some(PathBuf::from("other.toml"));
+        assert_eq!(discover_config_path_with_env(&args, cwd.path(), no_env), None);
+    }
+
+
+    #[test]
+    fn phase_resource_limits_from_toml_env_and_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            r#"
+plan_path = "p.md"
+repo_path = "/r"
+phase_max_rss_mb = 4096
+phase_max_cpu_sec = 0
+"#,
+        )
+        .unwrap();
+        let args = minimal_cli_args(None, None);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, no_env).unwrap();
+        assert_eq!(cfg.phase_max_rss_mb, Some(4096));
+        assert_eq!(cfg.phase_max_cpu_sec, Some(0));
+        let limits = cfg.phase_resource_limits();
+        assert_eq!(limits.max_rss_mb, Some(4096));
+        assert_eq!(limits.max_cpu_sec, None, "0 means unlimited");
+
+        fn fake_env(suffix: &str) -> Option<String> {
+            match suffix {
+                "PHASE_MAX_CPU_SEC" => Some("600".to_owned()),
+                _ => None,
+            }
+        }
+        let mut args = minimal_cli_args(None, None);
+        args.phase_max_rss_mb = Some(1024);
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, fake_env).unwrap();
+        assert_eq!(cfg.phase_max_rss_mb, Some(1024), "CLI wins");
+        assert_eq!(cfg.phase_max_cpu_sec, Some(600), "env wins over file");
+    }
+
+
+    #[test]
+    fn vars_merge_key_wise_across_file_env_and_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            r#"
+plan_path = "p.md"
+repo_path = "/r"
+
+[vars]
+SERVICE_NAME = "billing"
+REGION = "us"
+OWNER = "team-a"
+"#,
+        )
+        .unwrap();
+
+        fn fake_env(suffix: &str) -> Option<String> {
+            if suffix == "VARS" {
+                Some("REGION=eu, TIER = gold".to_owned())
+            } else {
+                None
+            }
+        }
+        let mut args = minimal_cli_args(None, None);
+        args.vars = vec![("OWNER".to_owned(), "team-b".to_owned())];
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &args, fake_env).unwrap();
+
+        let expected: BTreeMap<String, String> = [
+            ("SERVICE_NAME", "billing"),
+            ("REGION", "eu"),
+            ("OWNER", "team-b"),
+            ("TIER", "gold"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .collect();
+        assert_eq!(cfg.vars, expected);
+    }
+
+
+    #[test]
+    fn summarize_tasks_defaults_off_and_layers() {
+        let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(!cfg.summarize_tasks);
+
+        fn fake_env(suffix: &str) -> Option<String> {
+            (suffix == "SUMMARIZE_TASKS").then(|| "true".to_owned())
+        }
+        let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
+        assert!(cfg.summarize_tasks);
+
+        let mut args = args;
+        args.summarize_tasks = true;
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(cfg.summarize_tasks);
+    }
+
+
+    #[test]
+    fn agent_health_check_defaults_and_layers() {
+        let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(!cfg.agent_health_check);
+        assert_eq!(cfg.agent_health_check_timeout_sec, 60);
+
+        fn fake_env(suffix: &str) -> Option<String> {
+            match suffix {
+                "AGENT_HEALTH_CHECK" => Some("true".to_owned()),
+                "AGENT_HEALTH_CHECK_TIMEOUT_SEC" => Some("15".to_owned()),
+                _ => None,
+            }
+        }
+        let mut args = args;
+        args.agent_health_check_timeout_sec = Some(5);
+        let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
+        assert!(cfg.agent_health_check);
+        assert_eq!(cfg.agent_health_check_timeout_sec, 5, "CLI overrides env");
+    }
+
+
+    #[test]
+    fn otel_endpoint_layers_cli_over_env() {
+        fn fake_env(suffix: &str) -> Option<String> {
+            (suffix == "OTEL_ENDPOINT").then(|| "http://env:4318".to_owned())
+        }
+        let mut args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        assert_eq!(PealConfig::load_with_env(None, &args, no_env).unwrap().otel_endpoint, None);
+        let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
+        assert_eq!(cfg.otel_endpoint.as_deref(), Some("http://env:4318"));
+        args.otel_endpoint = Some("http://cli:4318".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, fake_env).unwrap();
+        assert_eq!(cfg.otel_endpoint.as_deref(), Some("http://cli:4318"));
+    }
+
+
+    #[test]
+    fn stet_stream_defaults_off_and_layers_from_toml_and_env() {
+        let args = minimal_cli_args(Some(PathBuf::from("p.md")), Some(PathBuf::from("/r")));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(!cfg.stet_stream);
+        assert_eq!(cfg.stet_stream_abort_threshold, None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_path = dir.path().join("peal.toml");
+        fs::write(
+            &cfg_path,
+            "plan_path = \"p.md\"\nrepo_path = \"/r\"\nstet_stream = true\nstet_stream_abort_threshold = 5\n",
+        )
+        .unwrap();
+        let cfg = PealConfig::load_with_env(Some(&cfg_path), &minimal_cli_args(None, None), |k| {
+            (k == "STET_STREAM_ABORT_THRESHOLD").then(|| "2".to_owned())
+        })
+        .unwrap();
+        assert!(cfg.stet_stream);
+        assert_eq!(cfg.stet_stream_abort_threshold, Some(2), "env wins over file");
+    }
+
+
+    #[test]
+    fn command_shell_defaults_to_none_and_validate_accepts_only_none_or_sh() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert_eq!(cfg.command_shell, "none");
+        cfg.validate().unwrap();
+
+        let cfg = PealConfig::load_with_env(None, &args, |k| {
+            (k == "COMMAND_SHELL").then(|| "sh".to_owned())
+        })
+        .unwrap();
+        assert_eq!(cfg.command_shell, "sh");
+        cfg.validate().unwrap();
+
+        args.command_shell = Some("bash".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        let msg = format!("{}", cfg.validate().unwrap_err());
+        assert!(msg.contains("Invalid command_shell"), "got: {msg}");
+    }
+
+
+    #[test]
+    fn agent_backend_api_layers_settings_and_requires_a_model() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert_eq!(cfg.agent_backend, "cli");
+        assert!(!cfg.uses_api_backend());
+        assert_eq!(cfg.api_base_url, "https://api.openai.com/v1");
+        assert_eq!(cfg.api_key_env, "OPENAI_API_KEY");
+
+        args.agent_backend = Some("api".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        let msg = format!("{}", cfg.validate().unwrap_err());
+        assert!(msg.contains("needs a model"), "got: {msg}");
+
+        args.model = Some("cursor-model".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, |k| match k {
+            "API_MODEL" => Some("gpt-x".to_owned()),
+            "API_KEY_ENV" => Some("MY_KEY".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        cfg.validate().unwrap();
+        assert!(cfg.uses_api_backend());
+        assert_eq!(cfg.api_model(), Some("gpt-x"), "api_model wins over model");
+        assert_eq!(cfg.api_key_env, "MY_KEY");
+
+        args.agent_backend = Some("grpc".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        let msg = format!("{}", cfg.validate().unwrap_err());
+        assert!(msg.contains("Invalid agent_backend"), "got: {msg}");
+    }
+
+
+    #[test]
+    fn serve_status_must_be_a_socket_address() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, |k| {
+            (k == "SERVE_STATUS").then(|| "127.0.0.1:7777".to_owned())
+        })
+        .unwrap();
+        assert_eq!(cfg.serve_status.as_deref(), Some("127.0.0.1:7777"));
+        cfg.validate().unwrap();
+
+        let cfg = PealConfig::load_with_env(None, &args, |k| {
+            (k == "SERVE_STATUS").then(|| "localhost".to_owned())
+        })
+        .unwrap();
+        let msg = format!("{}", cfg.validate().unwrap_err());
+        assert!(msg.contains("invalid serve_status address 'localhost'"), "got: {msg}");
+    }
+
+
+    #[test]
+    fn max_prompt_tokens_layers_and_on_prompt_too_large_is_validated() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert_eq!(cfg.max_prompt_tokens, None);
+        assert_eq!(cfg.on_prompt_too_large, "fail");
+
+        args.max_prompt_tokens = Some(1000);
+        let cfg = PealConfig::load_with_env(None, &args, |k| {
+            (k == "MAX_PROMPT_TOKENS").then(|| "50".to_owned())
+        })
+        .unwrap();
+        assert_eq!(cfg.max_prompt_tokens, Some(1000), "CLI wins over env");
+
+        args.on_prompt_too_large = Some("ignore".to_owned());
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        let msg = format!("{}", cfg.validate().unwrap_err());
+        assert!(msg.contains("Invalid on_prompt_too_large value 'ignore'"), "got: {msg}");
+    }
+
+
+    #[test]
+    fn stet_path_args_default_to_positional_paths_and_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        let mut args = minimal_cli_args(Some(plan_path), Some(dir.path().to_path_buf()));
+        let cfg = PealConfig::load_with_env(None, &args, no_env).unwrap();
+        assert!(!cfg.stet_scope_to_task);
+        assert_eq!(cfg.stet_path_args, vec!["{path}"]);
+
+        args.stet_scope_to_task = true;
+        let cfg = PealConfig::load_with_env(None, &args, |k| {
+            (k == "STET_PATH_ARGS").then(|| "--path {path}".to_owned())
+        })
+        .unwrap();
+        assert!(cfg.stet_scope_to_task);
+        assert_eq!(cfg.stet_path_args, vec!["--path", "{path}"]);
+    }
 }
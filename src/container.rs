@@ -0,0 +1,477 @@
+//! Container execution environment: agent and verify commands run in a Docker or Podman
+//! container (`[execution]` in the config).
+//!
+//! With `container_image` set, each agent invocation and each task `verify` command becomes
+//! `<runtime> run --rm` of that image, with the repo bind-mounted at the same path (so
+//! `--workspace` and the working directory mean the same thing inside), the host user mapped
+//! in (`--user uid:gid` for Docker, `--userns=keep-id` for Podman) so files the agent writes
+//! stay owned by the user, and the phase resource limits passed on as `--memory` and
+//! `--ulimit cpu`. When `repo_path` is inside a git working tree, the mount is the whole
+//! working tree plus the git common dir when it lies outside (a linked worktree such as a
+//! sparse-checkout one, see [`crate::sparse`]), so git works inside the container too.
+//! Nothing else is mounted or passed; credentials the agent needs go in with
+//! `container_env` and `container_args`. Stet runs on the host unless `stet_in_container` is
+//! set, in which case [`route_stet`] sends every stet invocation of the run to the container.
+//!
+//! Killing `<runtime> run` (on a timeout or at shutdown) does not stop the container, so each
+//! one gets a unique name and is removed with `<runtime> rm -f` when its command was cut short
+//! or is still running when peal exits ([`remove_live`]).
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use crate::config::{Execution, PealConfig};
+use crate::error::PealError;
+use crate::subprocess::{self, CommandResult, LimitExceeded, ResourceLimits};
+
+/// Exit status `<runtime> run` reports for a container killed with SIGKILL (the OOM killer).
+const EXIT_KILLED: i32 = 128 + 9;
+
+/// Exit status `<runtime> run` reports for a container stopped with SIGXCPU.
+const EXIT_CPU: i32 = 128 + 24;
+
+/// How long `<runtime> rm -f` may take.
+const REMOVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The container commands of a run are sent to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    execution: Execution,
+    /// Host paths bind-mounted at the same path.
+    mounts: Vec<PathBuf>,
+}
+
+impl Container {
+    /// A container that mounts `repo` only.
+    pub fn new(execution: Execution, repo: PathBuf) -> Self {
+        Self { execution, mounts: vec![repo] }
+    }
+
+    /// The container of `config`, or `None` when commands run on the host.
+    pub fn from_config(config: &PealConfig) -> Option<Self> {
+        config.execution.container_image.as_ref()?;
+        Some(Self {
+            execution: config.execution.clone(),
+            mounts: repo_mounts(&config.repo_path),
+        })
+    }
+
+    /// Arguments of `<runtime>` that run `program args` in `cwd` in a container called `name`.
+    pub fn run_args<S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        program: &str,
+        args: &[S],
+        cwd: &Path,
+        limits: ResourceLimits,
+    ) -> Vec<String> {
+        let mut argv: Vec<String> = vec![
+            "run".to_owned(),
+            "--rm".to_owned(),
+            "--init".to_owned(),
+            "--name".to_owned(),
+            name.to_owned(),
+            "--label".to_owned(),
+            format!("peal.run_id={}", crate::run_id::current()),
+        ];
+        for mount in &self.mounts {
+            let mount = mount.to_string_lossy();
+            argv.extend(["-v".to_owned(), format!("{mount}:{mount}")]);
+        }
+        argv.extend(["-w".to_owned(), cwd.to_string_lossy().into_owned()]);
+        argv.extend(self.user_args());
+        if let Some(mb) = limits.max_rss_mb {
+            argv.extend(["--memory".to_owned(), format!("{mb}m")]);
+        }
+        if let Some(sec) = limits.max_cpu_sec {
+            argv.extend(["--ulimit".to_owned(), format!("cpu={sec}:{sec}")]);
+        }
+        for var in &self.execution.container_env {
+            argv.extend(["-e".to_owned(), var.clone()]);
+        }
+        argv.extend(self.execution.container_args.iter().cloned());
+        argv.push(self.execution.container_image.clone().unwrap_or_default());
+        argv.push(program.to_owned());
+        argv.extend(args.iter().map(|a| a.as_ref().to_string_lossy().into_owned()));
+        argv
+    }
+
+    #[cfg(unix)]
+    fn user_args(&self) -> Vec<String> {
+        if self.execution.runtime() == "podman" {
+            return vec!["--userns=keep-id".to_owned()];
+        }
+        // SAFETY: getuid/getgid cannot fail and touch no memory.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        vec!["--user".to_owned(), format!("{uid}:{gid}")]
+    }
+
+    #[cfg(not(unix))]
+    fn user_args(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Like [`subprocess::run_command_with_limits`], in the container. `limits` are enforced by
+    /// the runtime; a container killed (exit 137) or stopped by SIGXCPU (exit 152) under a
+    /// limit is reported as having exceeded it.
+    pub fn run<S: AsRef<OsStr>>(
+        &self,
+        program: &str,
+        args: &[S],
+        cwd: &Path,
+        timeout: Option<Duration>,
+        limits: ResourceLimits,
+    ) -> std::io::Result<CommandResult> {
+        let name = next_name();
+        let argv = self.run_args(&name, program, args, cwd, limits);
+        debug!(runtime = self.execution.runtime(), container = %name, program, "running in container");
+        let _live = Live::register(&name, self.execution.runtime());
+        let mut result = subprocess::run_command(self.execution.runtime(), &argv, cwd, timeout)?;
+        if result.timed_out {
+            remove(self.execution.runtime(), &name);
+        }
+        result.limit_exceeded = result.limit_exceeded.or(match result.exit_code {
+            Some(EXIT_KILLED) => limits.max_rss_mb.map(|max_rss_mb| LimitExceeded::Memory { max_rss_mb }),
+            Some(EXIT_CPU) => limits.max_cpu_sec.map(|max_cpu_sec| LimitExceeded::Cpu { max_cpu_sec }),
+            _ => None,
+        });
+        Ok(result)
+    }
+
+    /// Like [`subprocess::run_command_string_with_shell`], in the container (`sh` there).
+    pub fn run_string(
+        &self,
+        command: &str,
+        shell: &str,
+        cwd: &Path,
+        timeout: Option<Duration>,
+    ) -> Option<std::io::Result<CommandResult>> {
+        let trimmed = command.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+        if shell == "sh" {
+            return Some(self.run("sh", &["-c", trimmed], cwd, timeout, ResourceLimits::default()));
+        }
+        let tokens: Vec<&str> = trimmed.split_ascii_whitespace().collect();
+        Some(self.run(tokens[0], &tokens[1..], cwd, timeout, ResourceLimits::default()))
+    }
+
+    /// Like [`subprocess::run_command_streaming`], in the container.
+    pub fn run_streaming<S, F>(
+        &self,
+        program: &str,
+        args: &[S],
+        cwd: &Path,
+        timeout: Option<Duration>,
+        on_line: F,
+    ) -> std::io::Result<(CommandResult, bool)>
+    where
+        S: AsRef<OsStr>,
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        let name = next_name();
+        let argv = self.run_args(&name, program, args, cwd, ResourceLimits::default());
+        let _live = Live::register(&name, self.execution.runtime());
+        let (result, aborted) = subprocess::run_command_streaming(self.execution.runtime(), &argv, cwd, timeout, on_line)?;
+        if result.timed_out || aborted {
+            remove(self.execution.runtime(), &name);
+        }
+        Ok((result, aborted))
+    }
+}
+
+/// A unique container name for this process: `peal-<run id>-<pid>-<n>`.
+/// Paths to mount for `repo`: the top level of its git working tree (which holds the `.git` a
+/// linked worktree points from), plus the git common dir when it is not inside that; `repo`
+/// alone when it is not in a git working tree.
+fn repo_mounts(repo: &Path) -> Vec<PathBuf> {
+    let args = ["rev-parse", "--path-format=absolute", "--show-toplevel", "--git-common-dir"];
+    let output = match subprocess::run_command("git", &args, repo, None) {
+        Ok(r) if r.success() => r.stdout,
+        _ => return vec![repo.to_path_buf()],
+    };
+    let mut lines = output.lines().map(PathBuf::from);
+    let (Some(top), Some(common)) = (lines.next(), lines.next()) else {
+        return vec![repo.to_path_buf()];
+    };
+    let mut mounts = vec![top];
+    if !common.starts_with(&mounts[0]) {
+        mounts.push(common);
+    }
+    mounts
+}
+
+fn next_name() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    format!(
+        "peal-{}-{}-{}",
+        crate::run_id::current().to_ascii_lowercase(),
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn remove(runtime: &str, name: &str) {
+    match subprocess::run_command(runtime, &["rm", "-f", name], Path::new("."), Some(REMOVE_TIMEOUT)) {
+        Ok(r) if r.success() => debug!(container = name, "container removed"),
+        Ok(r) => warn!(container = name, stderr = %r.stderr.trim(), "could not remove container"),
+        Err(e) => warn!(container = name, err = %e, "could not run {runtime} rm"),
+    }
+}
+
+/// Containers whose `<runtime> run` has not returned yet, by name, with their runtime.
+static LIVE: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Entry in [`LIVE`] for the duration of one `<runtime> run`.
+struct Live(String);
+
+impl Live {
+    fn register(name: &str, runtime: &str) -> Self {
+        LIVE.lock().unwrap_or_else(|e| e.into_inner()).insert(name.to_owned(), runtime.to_owned());
+        Self(name.to_owned())
+    }
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        LIVE.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.0);
+    }
+}
+
+/// Remove the containers of commands still running (at shutdown, after their `<runtime> run`
+/// was killed). Returns how many there were.
+pub fn remove_live() -> usize {
+    let live = std::mem::take(&mut *LIVE.lock().unwrap_or_else(|e| e.into_inner()));
+    for (name, runtime) in &live {
+        remove(runtime, name);
+    }
+    live.len()
+}
+
+/// Program of agent command `cmd` (`agent_cmd` or `triage_agent_cmd`): as written when
+/// `config` runs agents in a container, where the image's `PATH` applies, otherwise resolved on
+/// the host with [`crate::cursor::resolve_agent_cmd`].
+pub fn agent_path(config: &PealConfig, cmd: &str) -> Result<PathBuf, PealError> {
+    if config.execution.container_image.is_some() {
+        let (program, _) = crate::cursor::split_agent_cmd(cmd)?;
+        return Ok(PathBuf::from(program));
+    }
+    crate::cursor::resolve_agent_cmd(cmd)
+}
+
+/// Run an agent command per `config`: in its container, or with
+/// [`subprocess::run_command_with_limits`] on the host.
+pub fn run_with_limits<S: AsRef<OsStr>>(
+    config: &PealConfig,
+    program: &str,
+    args: &[S],
+    cwd: &Path,
+    timeout: Option<Duration>,
+    limits: ResourceLimits,
+) -> std::io::Result<CommandResult> {
+    match Container::from_config(config) {
+        Some(container) => container.run(program, args, cwd, timeout, limits),
+        None => subprocess::run_command_with_limits(program, args, cwd, timeout, limits),
+    }
+}
+
+/// Run a configured command string (per `command_shell`) per `config`: in its container, or
+/// with [`subprocess::run_command_string_with_shell`] on the host.
+pub fn run_command_string(
+    config: &PealConfig,
+    command: &str,
+    cwd: &Path,
+    timeout: Option<Duration>,
+) -> Option<std::io::Result<CommandResult>> {
+    match Container::from_config(config) {
+        Some(container) => container.run_string(command, &config.command_shell, cwd, timeout),
+        None => subprocess::run_command_string_with_shell(command, &config.command_shell, cwd, timeout),
+    }
+}
+
+/// Container stet runs in for this process; `None`: on the host.
+static STET: Mutex<Option<Container>> = Mutex::new(None);
+
+/// Send this run's stet invocations to `config`'s container when `stet_in_container` is set.
+pub fn route_stet(config: &PealConfig) {
+    let container = Container::from_config(config).filter(|_| config.execution.stet_in_container);
+    *STET.lock().unwrap_or_else(|e| e.into_inner()) = container;
+}
+
+fn stet_container() -> Option<Container> {
+    STET.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// [`subprocess::run_command`] for stet, in the container after [`route_stet`].
+pub fn run_stet<S: AsRef<OsStr>>(
+    program: &str,
+    args: &[S],
+    cwd: &Path,
+    timeout: Option<Duration>,
+) -> std::io::Result<CommandResult> {
+    match stet_container() {
+        Some(container) => container.run(program, args, cwd, timeout, ResourceLimits::default()),
+        None => subprocess::run_command(program, args, cwd, timeout),
+    }
+}
+
+/// [`subprocess::run_command_string_with_shell`] for stet commands, in the container after
+/// [`route_stet`].
+pub fn run_stet_string(
+    command: &str,
+    shell: &str,
+    cwd: &Path,
+    timeout: Option<Duration>,
+) -> Option<std::io::Result<CommandResult>> {
+    match stet_container() {
+        Some(container) => container.run_string(command, shell, cwd, timeout),
+        None => subprocess::run_command_string_with_shell(command, shell, cwd, timeout),
+    }
+}
+
+/// [`subprocess::run_command_streaming`] for stet, in the container after [`route_stet`].
+pub fn run_stet_streaming<S, F>(
+    program: &str,
+    args: &[S],
+    cwd: &Path,
+    timeout: Option<Duration>,
+    on_line: F,
+) -> std::io::Result<(CommandResult, bool)>
+where
+    S: AsRef<OsStr>,
+    F: FnMut(&str) -> ControlFlow<()>,
+{
+    match stet_container() {
+        Some(container) => container.run_streaming(program, args, cwd, timeout, on_line),
+        None => subprocess::run_command_streaming(program, args, cwd, timeout, on_line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_args_mount_the_repo_and_pass_limits_env_and_extra_args() {
+        let execution = Execution {
+            container_image: Some("ghcr.io/acme/toolchain:1".to_owned()),
+            container_runtime: Some("podman".to_owned()),
+            container_args: vec!["--network=none".to_owned()],
+            container_env: vec!["CURSOR_API_KEY".to_owned()],
+            stet_in_container: false,
+        };
+        let container = Container::new(execution, PathBuf::from("/work/repo"));
+        let limits = ResourceLimits {
+            max_rss_mb: Some(2048),
+            max_cpu_sec: Some(600),
+        };
+        let argv = container.run_args("c1", "agent", &["--print", "do it"], Path::new("/work/repo/sub"), limits);
+        let label = format!("peal.run_id={}", crate::run_id::current());
+        assert_eq!(
+            argv,
+            [
+                "run", "--rm", "--init", "--name", "c1", "--label", &label, "-v", "/work/repo:/work/repo", "-w",
+                "/work/repo/sub", "--userns=keep-id", "--memory", "2048m", "--ulimit", "cpu=600:600", "-e",
+                "CURSOR_API_KEY", "--network=none", "ghcr.io/acme/toolchain:1", "agent", "--print", "do it",
+            ]
+        );
+    }
+
+    #[test]
+    fn sparse_worktrees_of_a_repo_subdir_mount_the_worktree_and_the_git_common_dir() {
+        use clap::Parser;
+        use std::process::Command;
+
+        use crate::cli::{Cli, Commands};
+        use crate::plan::Task;
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().canonicalize().unwrap().join("repo");
+        std::fs::create_dir_all(repo.join("sub/a")).unwrap();
+        let git = |args: &[&str]| {
+            let out = Command::new("git").arg("-C").arg(&repo).args(args).output().unwrap();
+            assert!(out.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&out.stderr));
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        std::fs::write(repo.join("sub/a/x.txt"), "x\n").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+
+        let sub = repo.join("sub");
+        let argv = ["peal", "run", "--plan", "plan.md", "--repo", sub.to_str().unwrap(), "--sparse-checkout"];
+        let Commands::Run(args) = Cli::try_parse_from(argv).unwrap().command else {
+            panic!("expected run command");
+        };
+        let mut config = PealConfig::load(None, &args).unwrap();
+        config.state_dir = dir.path().join(".peal");
+        config.execution.container_image = Some("img".to_owned());
+        let mounts = |c: &PealConfig| {
+            let argv = Container::from_config(c).unwrap().run_args("c1", "agent", &["x"], &c.repo_path, Default::default());
+            argv.windows(2).filter(|w| w[0] == "-v").map(|w| w[1].clone()).collect::<Vec<_>>()
+        };
+        let volume = |p: &Path| format!("{}:{}", p.display(), p.display());
+
+        // repo_path below the top level: the whole working tree, .git included.
+        assert_eq!(mounts(&config), vec![volume(&repo)]);
+
+        let task = Task {
+            index: 1,
+            content: "Edit a.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: vec!["a".to_owned()],
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        };
+        crate::sparse::run_in_sparse_worktree(Path::new("agent"), &config, &task, |c| {
+            // The linked worktree's top level holds the `.git` file pointing into the repo's.
+            let worktree = c.repo_path.parent().unwrap();
+            assert_eq!(mounts(c), vec![volume(worktree), volume(&repo.join(".git"))]);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_through_the_runtime_and_reports_the_memory_limit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A fake runtime that records its argv and exits like an OOM-killed container.
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("argv");
+        let runtime = dir.path().join("docker");
+        std::fs::write(&runtime, format!("#!/bin/sh\necho \"$@\" >> '{}'\nexit 137\n", log.display())).unwrap();
+        std::fs::set_permissions(&runtime, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let execution = Execution {
+            container_image: Some("img".to_owned()),
+            container_runtime: Some(runtime.to_string_lossy().into_owned()),
+            ..Execution::default()
+        };
+        let container = Container::new(execution, dir.path().to_path_buf());
+        let limits = ResourceLimits {
+            max_rss_mb: Some(64),
+            max_cpu_sec: None,
+        };
+        let result = container.run("make", &["test"], dir.path(), None, limits).unwrap();
+        assert_eq!(result.limit_exceeded, Some(LimitExceeded::Memory { max_rss_mb: 64 }));
+        let logged = std::fs::read_to_string(&log).unwrap();
+        assert!(logged.starts_with("run --rm --init --name peal-"), "{logged}");
+        assert!(logged.trim_end().ends_with("--memory 64m img make test"), "{logged}");
+        assert!(LIVE.lock().unwrap().is_empty());
+    }
+}
@@ -7,14 +7,74 @@
 //! **Windows:** For bare names with no extension (e.g. `agent`), resolution
 //! tries `.exe` in each PATH directory so that `agent` finds `agent.exe`.
 //! Explicit paths with no extension are tried once with `.exe` appended.
+//!
+//! `agent_cmd` may carry arguments (`npx cursor-agent --some-flag`): it is split into words
+//! without a shell ([`split_agent_cmd`]), only the first word is resolved, and the rest are
+//! passed before peal's own arguments in every agent invocation.
 
 use std::path::{Path, PathBuf};
 
 use crate::error::PealError;
 
-/// Resolve `agent_cmd` to an absolute path on the system.
+/// Split `agent_cmd` into the program and its leading arguments, shell-words style but
+/// without a shell: words are separated by whitespace, `'...'` is taken literally, and in
+/// `"..."` or outside quotes a backslash escapes a quote, a backslash, or whitespace (other
+/// backslashes are kept, so Windows paths need no doubling). Nothing is expanded.
+///
+/// A command that is itself an existing executable path (e.g. one with spaces in a directory
+/// name) is taken whole, with no arguments, as before arguments were supported.
+pub fn split_agent_cmd(cmd: &str) -> Result<(String, Vec<String>), PealError> {
+    let has_separator = cmd.contains(std::path::MAIN_SEPARATOR) || cmd.contains('/');
+    if has_separator && is_executable(Path::new(cmd)) {
+        return Ok((cmd.to_owned(), Vec::new()));
+    }
+    let invalid = |detail: &str| PealError::InvalidAgentCmd {
+        cmd: cmd.to_owned(),
+        detail: detail.to_owned(),
+    };
+    let mut words: Vec<String> = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = cmd.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let w = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => w.push(c),
+                        None => return Err(invalid("unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                let w = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.peek(), Some('"' | '\\')) => w.extend(chars.next()),
+                        Some(c) => w.push(c),
+                        None => return Err(invalid("unterminated double quote")),
+                    }
+                }
+            }
+            '\\' if chars.peek().is_some_and(|&n| n == '"' || n == '\'' || n == '\\' || n.is_whitespace()) => {
+                word.get_or_insert_with(String::new).extend(chars.next());
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    let mut words = words.into_iter();
+    let program = words.next().ok_or_else(|| invalid("empty command"))?;
+    Ok((program, words.collect()))
+}
+
+/// Resolve `agent_cmd` to an absolute path on the system: the program of
+/// [`split_agent_cmd`], looked up as follows.
 ///
-/// - If `cmd` contains a path separator it is treated as an explicit path
+/// - If it contains a path separator it is treated as an explicit path
 ///   and checked directly (on Windows, if the path has no extension, `.exe`
 ///   is tried once).
 /// - Otherwise the function searches each directory in `PATH`. On Windows,
@@ -24,7 +84,11 @@ use crate::error::PealError;
 /// Returns the first matching file path, or `PealError::AgentCmdNotFound`
 /// with an install link when the binary cannot be located.
 pub fn resolve_agent_cmd(cmd: &str) -> Result<PathBuf, PealError> {
-    resolve_agent_cmd_with(cmd, std::env::var_os("PATH"))
+    let (program, _) = split_agent_cmd(cmd)?;
+    resolve_agent_cmd_with(&program, std::env::var_os("PATH")).map_err(|e| match e {
+        PealError::AgentCmdNotFound { .. } => PealError::AgentCmdNotFound { cmd: cmd.to_owned() },
+        other => other,
+    })
 }
 
 /// Testable inner implementation that accepts an explicit `PATH` value.
@@ -118,6 +182,56 @@ mod tests {
         assert!(result.unwrap().is_file());
     }
 
+    #[test]
+    fn split_agent_cmd_tokenizes_without_a_shell() {
+        let split = |cmd: &str| split_agent_cmd(cmd).unwrap();
+        assert_eq!(split("agent"), ("agent".to_owned(), vec![]));
+        assert_eq!(
+            split("  npx cursor-agent --some-flag "),
+            ("npx".to_owned(), vec!["cursor-agent".to_owned(), "--some-flag".to_owned()])
+        );
+        assert_eq!(
+            split(r#"agent --rules 'a b $HOME' --note "say \"hi\"" my\ dir C:\tools"#),
+            (
+                "agent".to_owned(),
+                vec![
+                    "--rules".to_owned(),
+                    "a b $HOME".to_owned(),
+                    "--note".to_owned(),
+                    r#"say "hi""#.to_owned(),
+                    "my dir".to_owned(),
+                    r"C:\tools".to_owned(),
+                ]
+            )
+        );
+        assert_eq!(split("agent ''"), ("agent".to_owned(), vec![String::new()]));
+        assert!(matches!(split_agent_cmd("agent 'oops"), Err(PealError::InvalidAgentCmd { .. })));
+        assert!(matches!(split_agent_cmd("   "), Err(PealError::InvalidAgentCmd { .. })));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolves_only_the_first_word_of_agent_cmd() {
+        let dir = tempfile::tempdir().unwrap();
+        let spaced = dir.path().join("with space");
+        std::fs::create_dir(&spaced).unwrap();
+        let bin = spaced.join("agent");
+        std::fs::write(&bin, "#!/bin/sh\n").unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        // A whole executable path wins over splitting it at the space.
+        let whole = bin.to_str().unwrap();
+        assert_eq!(split_agent_cmd(whole).unwrap(), (whole.to_owned(), vec![]));
+        assert_eq!(resolve_agent_cmd(whole).unwrap(), bin);
+
+        let quoted = format!("'{whole}' --flag");
+        assert_eq!(resolve_agent_cmd(&quoted).unwrap(), bin);
+        assert!(resolve_agent_cmd("echo --flag").unwrap().ends_with("echo"));
+        let err = resolve_agent_cmd("peal-nonexistent-binary-xyz-999 --flag").unwrap_err();
+        assert!(err.to_string().contains("'peal-nonexistent-binary-xyz-999 --flag'"), "{err}");
+    }
+
     #[test]
     fn fails_for_nonexistent_command() {
         let result = resolve_agent_cmd("peal-nonexistent-binary-xyz-999");
@@ -171,6 +285,7 @@ mod tests {
             use std::os::unix::fs::OpenOptionsExt;
             std::fs::OpenOptions::new()
                 .create(true)
+                .truncate(true)
                 .write(true)
                 .mode(0o755)
                 .open(&bin)
@@ -0,0 +1,223 @@
+//! Detached runs: `peal run --detach`, `peal attach`, and `peal stop`.
+//!
+//! `peal run --detach` re-runs the same command line (without `--detach`) as a background
+//! process in its own session (`setsid`), so closing the terminal or an SSH disconnect does not
+//! send it `SIGHUP`. The child's stdin is `/dev/null` and its stdout and stderr are appended to
+//! `{state_dir}/detach.log`; with no `log_file` configured, that is where the run's logs go.
+//! Its pid is written to `{state_dir}/peal.pid`, and the run removes the file when it ends.
+//!
+//! `peal attach` prints the detach log and follows it until the run exits. `peal stop` asks
+//! the run to stop gracefully by creating `{state_dir}/stop`: the scheduler checks for it
+//! between segments, finishes the task in progress, saves state, and ends the run as if the
+//! plan were done (exit code 2), so the next `peal run` resumes where it stopped.
+//!
+//! Detaching is Unix-only; elsewhere `--detach` fails with `PealError::DetachFailed`.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::PealError;
+
+/// Pid of the detached run, written by [`spawn_detached`].
+pub fn pid_file_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("peal.pid")
+}
+
+/// stdout and stderr of the detached run, followed by `peal attach`.
+pub fn log_file_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("detach.log")
+}
+
+/// Marker created by `peal stop`; see [`stop_requested`].
+pub fn stop_file_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("stop")
+}
+
+/// Start `program args` in the background as a detached run: a new session, stdin from
+/// `/dev/null`, stdout and stderr appended to [`log_file_path`]. Writes the child's pid to
+/// [`pid_file_path`] and returns it. Fails when a detached run is already going in `state_dir`.
+#[cfg(unix)]
+pub fn spawn_detached(program: &Path, args: &[OsString], state_dir: &Path) -> Result<u32, PealError> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let fail = |detail: String| PealError::DetachFailed { detail };
+    if let Some(pid) = running_pid(state_dir) {
+        return Err(fail(format!(
+            "a detached run (pid {pid}) is already running in {}",
+            state_dir.display()
+        )));
+    }
+    fs::create_dir_all(state_dir).map_err(|e| fail(format!("{}: {e}", state_dir.display())))?;
+    let log_path = log_file_path(state_dir);
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(|e| fail(format!("{}: {e}", log_path.display())))?;
+    let log_err = log.try_clone().map_err(|e| fail(e.to_string()))?;
+    let _ = fs::remove_file(stop_file_path(state_dir));
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log)
+        .stderr(log_err);
+    // SAFETY: setsid is async-signal-safe and touches no memory of this process.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command
+        .spawn()
+        .map_err(|e| fail(format!("failed to start {}: {e}", program.display())))?;
+    let pid = child.id();
+    fs::write(pid_file_path(state_dir), format!("{pid}\n"))
+        .map_err(|e| fail(format!("{}: {e}", pid_file_path(state_dir).display())))?;
+    Ok(pid)
+}
+
+#[cfg(not(unix))]
+pub fn spawn_detached(_program: &Path, _args: &[OsString], _state_dir: &Path) -> Result<u32, PealError> {
+    Err(PealError::DetachFailed {
+        detail: "--detach is only supported on Unix".to_owned(),
+    })
+}
+
+/// Pid from [`pid_file_path`] when that process is still alive.
+pub fn running_pid(state_dir: &Path) -> Option<u32> {
+    let pid: u32 = fs::read_to_string(pid_file_path(state_dir)).ok()?.trim().parse().ok()?;
+    is_alive(pid).then_some(pid)
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 checks existence; EPERM means it exists but belongs to someone else.
+    // SAFETY: kill with signal 0 sends nothing.
+    unsafe { libc::kill(pid, 0) == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    false
+}
+
+/// Called by a run as it starts: drops a stop request left over from an earlier run.
+pub fn clear_stop_request(state_dir: &Path) {
+    let _ = fs::remove_file(stop_file_path(state_dir));
+}
+
+/// Called by the run itself when it ends: removes [`pid_file_path`] if it names this process,
+/// and any [`stop_file_path`] request it has honored.
+pub fn clear_run_files(state_dir: &Path) {
+    let ours = fs::read_to_string(pid_file_path(state_dir))
+        .ok()
+        .and_then(|s| s.trim().parse::<u32>().ok())
+        == Some(std::process::id());
+    if ours {
+        let _ = fs::remove_file(pid_file_path(state_dir));
+    }
+    let _ = fs::remove_file(stop_file_path(state_dir));
+}
+
+/// Ask the run using `state_dir` to stop after its current task. Returns the pid of the
+/// detached run, or `PealError::NoDetachedRun` when none is running.
+pub fn request_stop(state_dir: &Path) -> Result<u32, PealError> {
+    let pid = running_pid(state_dir).ok_or_else(|| PealError::NoDetachedRun {
+        state_dir: state_dir.to_path_buf(),
+    })?;
+    let path = stop_file_path(state_dir);
+    fs::write(&path, format!("{}\n", chrono::Utc::now().to_rfc3339()))
+        .map_err(|e| PealError::DetachFailed { detail: format!("{}: {e}", path.display()) })?;
+    Ok(pid)
+}
+
+/// True when `peal stop` has asked the run using `state_dir` to stop.
+pub fn stop_requested(state_dir: &Path) -> bool {
+    stop_file_path(state_dir).exists()
+}
+
+/// Copy the detach log to `out` and keep following it, checking every `poll`, until the
+/// detached run exits; then copy what is left and return. Fails with
+/// `PealError::NoDetachedRun` when there is no detach log in `state_dir`.
+pub fn attach(state_dir: &Path, out: &mut impl Write, poll: Duration) -> Result<(), PealError> {
+    let path = log_file_path(state_dir);
+    let mut log = fs::File::open(&path).map_err(|_| PealError::NoDetachedRun {
+        state_dir: state_dir.to_path_buf(),
+    })?;
+    let mut buf = Vec::new();
+    loop {
+        // Check liveness before reading, so output written just before exit is not lost.
+        let running = running_pid(state_dir).is_some();
+        buf.clear();
+        log.read_to_end(&mut buf)
+            .map_err(|e| PealError::DetachFailed { detail: format!("{}: {e}", path.display()) })?;
+        out.write_all(&buf)
+            .and_then(|()| out.flush())
+            .map_err(|e| PealError::DetachFailed { detail: e.to_string() })?;
+        if !running {
+            return Ok(());
+        }
+        std::thread::sleep(poll);
+        // Start over if the log was truncated underneath us.
+        let pos = log.stream_position().unwrap_or(0);
+        if fs::metadata(&path).map(|m| m.len() < pos).unwrap_or(false) {
+            log.seek(SeekFrom::Start(0))
+                .map_err(|e| PealError::DetachFailed { detail: e.to_string() })?;
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detached_child_writes_log_and_attach_follows_until_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let args: Vec<OsString> = ["-c", "echo started; sleep 0.3; echo done"].map(OsString::from).to_vec();
+
+        let pid = spawn_detached(Path::new("/bin/sh"), &args, &state_dir).unwrap();
+        assert_eq!(running_pid(&state_dir), Some(pid));
+        assert_eq!(fs::read_to_string(pid_file_path(&state_dir)).unwrap().trim(), pid.to_string());
+        let err = spawn_detached(Path::new("/bin/sh"), &args, &state_dir).unwrap_err();
+        assert!(err.to_string().contains("already running"), "{err}");
+
+        let mut out = Vec::new();
+        // This test process is the child's parent, so reap it for kill(0) to see the exit.
+        let reaper = std::thread::spawn(move || unsafe {
+            libc::waitpid(pid as libc::pid_t, std::ptr::null_mut(), 0);
+        });
+        attach(&state_dir, &mut out, Duration::from_millis(20)).unwrap();
+        reaper.join().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "started\ndone\n");
+    }
+
+    #[test]
+    fn stop_request_needs_a_running_detached_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = request_stop(dir.path()).unwrap_err();
+        assert!(matches!(err, PealError::NoDetachedRun { .. }), "{err:?}");
+
+        // Pretend this test process is the detached run.
+        fs::write(pid_file_path(dir.path()), std::process::id().to_string()).unwrap();
+        assert_eq!(request_stop(dir.path()).unwrap(), std::process::id());
+        assert!(stop_requested(dir.path()));
+
+        clear_run_files(dir.path());
+        assert!(!stop_requested(dir.path()));
+        assert!(!pid_file_path(dir.path()).exists());
+    }
+}
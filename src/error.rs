@@ -17,43 +17,194 @@ pub enum PealError {
     #[error("Repo path does not exist: {path}")]
     RepoPathNotFound { path: PathBuf },
 
+    #[error("Invalid repo_subdir {path}: {reason}")]
+    InvalidRepoSubdir { path: PathBuf, reason: String },
+
     #[error("Target path is not a git repository: {path}")]
     RepoNotGitRepo { path: PathBuf },
 
+    #[error(
+        "git was not found on PATH. peal uses git to check the target repository and for \
+         commit_after_phase2. Install it (e.g. `apt install git`, `brew install git`, or \
+         https://git-scm.com/downloads), or pass --no-git-checks to skip the repository check"
+    )]
+    GitNotFound,
+
+    #[error(
+        "{program} was not found on PATH. peal uses it ({vcs}) to check the target repository \
+         and for commit_after_phase2. Install it, set vcs to the repository's version control \
+         system, or pass --no-git-checks to skip the repository check"
+    )]
+    VcsNotFound { vcs: String, program: String },
+
+    #[error("Target path is not a {vcs} repository: {path}")]
+    RepoNotVcsRepo { vcs: String, path: PathBuf },
+
     #[error(
         "Cursor CLI command '{cmd}' not found on PATH. \
-         Install it from {CURSOR_CLI_INSTALL_URL}"
+         Install it from {CURSOR_CLI_INSTALL_URL}, or let peal download a pinned version with \
+         `peal agent install --version <VERSION> --sha256 <SHA256>`"
     )]
     AgentCmdNotFound { cmd: String },
 
+    #[error("Invalid agent_cmd '{cmd}': {detail}")]
+    InvalidAgentCmd { cmd: String, detail: String },
+
+    #[error("Agent install failed: {detail}")]
+    AgentInstallFailed { detail: String },
+
     #[error("Phase {phase} failed to start: {detail}")]
     PhaseSpawnFailed { phase: u32, detail: String },
 
     #[error("Phase {phase} timed out after {timeout_sec}s")]
     PhaseTimedOut { phase: u32, timeout_sec: u64 },
 
-    #[error("Phase {phase} exited with code {exit_code:?}")]
+    #[error("Phase {phase} exited with code {exit_code:?}{}", stderr_suffix(stderr))]
     PhaseNonZeroExit {
         phase: u32,
         exit_code: Option<i32>,
+        /// Tail of the agent's stderr, capped at `phase::STDERR_EXCERPT_BYTES`.
+        /// The full (capped) stderr is in the phase's stderr artifact.
         stderr: String,
     },
 
+    #[error("Phase {phase} was killed for exceeding its {limit}")]
+    PhaseResourceLimitExceeded { phase: u32, limit: String },
+
+    #[error("Phase {phase} was killed by signal {signal}{}", stderr_suffix(stderr))]
+    PhaseKilledBySignal {
+        phase: u32,
+        signal: i32,
+        /// Tail of the agent's stderr, capped at `phase::STDERR_EXCERPT_BYTES`.
+        stderr: String,
+    },
+
+    /// The agent ran but reported a failure itself (Cursor JSON `is_error` / `"type": "error"`).
+    #[error("Phase {phase}: agent reported an error: {message}")]
+    AgentReportedError { phase: u32, message: String },
+
+    /// The agent failed because it is not logged in or its credentials expired (see
+    /// `phase::agent_auth_failure`). Never retried; stops the run.
+    #[error(
+        "Agent is not authenticated ({call}): {detail}\n\
+         Log in to the Cursor CLI (`agent login`) or set CURSOR_API_KEY, then rerun; \
+         with agent_backend = \"api\", check the key named by api_key_env"
+    )]
+    AgentAuthFailed { call: String, detail: String },
+
+    #[error("Failed to detach the run: {detail}")]
+    DetachFailed { detail: String },
+
+    #[error("peal bench failed{}: {detail}", if model.is_empty() { String::new() } else { format!(" for model '{model}'") })]
+    BenchFailed { model: String, detail: String },
+
+    #[error("peal run-many failed: {detail}")]
+    RunManyFailed { detail: String },
+
+    #[error("No detached run in {state_dir} (start one with `peal run --detach`)")]
+    NoDetachedRun { state_dir: PathBuf },
+
     #[error("Failed to parse environment variable '{var}': {detail}")]
     ConfigEnvParseError { var: String, detail: String },
 
+    #[error("Failed to load env file {path}: {detail}")]
+    InvalidEnvFile { path: PathBuf, detail: String },
+
     #[error("Task {index} not found in plan (available: {available:?})")]
     TaskNotFound { index: u32, available: Vec<u32> },
 
+    #[error("Stage '{name}' not found in plan (available: {available:?})")]
+    StageNotFound { name: String, available: Vec<String> },
+
+    #[error("Stage '{name}' appears more than once in the plan; stage names must be unique")]
+    DuplicateStage { name: String },
+
+    #[error("task {task_index} {hook} command failed: {command}: {detail}")]
+    PhaseHookFailed {
+        task_index: u32,
+        hook: &'static str,
+        command: String,
+        detail: String,
+    },
+
+    #[error("task {task_index}: could not apply the sparse worktree's changes to the repo (patch kept at {patch}): {detail}")]
+    SparseApplyFailed {
+        task_index: u32,
+        patch: PathBuf,
+        detail: String,
+    },
+
+    #[error("task {task_index}: merge conflicts in {files} still unresolved after {attempts} phase 2.5 attempt(s); the repo's files were restored and the patch kept at {patch}")]
+    MergeConflictUnresolved {
+        task_index: u32,
+        files: String,
+        attempts: u32,
+        patch: PathBuf,
+    },
+
+    #[error("stage '{stage}' barrier command failed: {command}: {detail}")]
+    StageCommandFailed {
+        stage: String,
+        command: String,
+        detail: String,
+    },
+
+    #[error("task {task_index}: invalid expect line `{line}`: {detail}")]
+    InvalidExpectation {
+        task_index: u32,
+        line: String,
+        detail: String,
+    },
+
+    #[error("task {task_index} expectation failed: {expectation}: {detail}")]
+    TaskExpectationFailed {
+        task_index: u32,
+        expectation: String,
+        detail: String,
+    },
+
+    #[error("task {task_index} verify command failed after {rounds} fix round(s): {command}: {detail}")]
+    TaskVerifyFailed {
+        task_index: u32,
+        command: String,
+        rounds: u32,
+        detail: String,
+    },
+
+    #[error("No tasks match the tag filter (tags: {include:?}, exclude-tags: {exclude:?}; tags in plan: {available:?})")]
+    NoTasksMatchTags {
+        include: Vec<String>,
+        exclude: Vec<String>,
+        available: Vec<String>,
+    },
+
     #[error("Failed to read state file {path}: {detail}")]
     StateReadFailed { path: PathBuf, detail: String },
 
+    #[error("State file {path} is corrupt: {detail} (fix or remove it, or run without --strict-state to recover)")]
+    StateCorrupt { path: PathBuf, detail: String },
+
     #[error("Failed to write state file {path}: {detail}")]
     StateWriteFailed { path: PathBuf, detail: String },
 
+    #[error("Failed to access lock file {path}: {detail}")]
+    LockFileFailed { path: PathBuf, detail: String },
+
+    #[error("Run does not match lock file {path} (--locked): {detail}")]
+    LockMismatch { path: PathBuf, detail: String },
+
+    #[error("--locked needs lock_path (or --lock-path) naming the lock file to check against")]
+    LockedWithoutLockPath,
+
+    #[error("Failed to read run summary {path}: {detail}")]
+    RunSummaryReadFailed { path: PathBuf, detail: String },
+
     #[error("stet start failed: {detail}")]
     StetStartFailed { detail: String },
 
+    #[error("Cannot resolve stet_start_ref '{template}': {detail}")]
+    StetStartRefUnresolved { template: String, detail: String },
+
     #[error("stet run failed: {detail}")]
     StetRunFailed { detail: String },
 
@@ -71,7 +222,7 @@ pub enum PealError {
         stet_review: String,
     },
 
-    #[error("Invalid on_findings_remaining value '{value}' (expected \"fail\" or \"warn\")")]
+    #[error("Invalid on_findings_remaining value '{value}' (expected \"fail\", \"warn\", or \"create_task\")")]
     InvalidOnFindingsRemaining { value: String },
 
     #[error("Invalid stet_dismiss_patterns reason '{value}' (expected one of: false_positive, already_correct, wrong_suggestion, out_of_scope)")]
@@ -80,6 +231,89 @@ pub enum PealError {
     #[error("Invalid on_stet_fail value '{value}' (expected \"fail\", \"retry_once\", or \"skip\")")]
     InvalidOnStetFail { value: String },
 
+    #[error("Invalid command_shell value '{value}' (expected \"none\" or \"sh\")")]
+    InvalidCommandShell { value: String },
+
+    #[error("Invalid on_stale_stet_session value '{value}' (expected \"finish\" or \"reattach\")")]
+    InvalidOnStaleStetSession { value: String },
+
+    #[error("Invalid on_push_fail value '{value}' (expected \"warn\" or \"fail\")")]
+    InvalidOnPushFail { value: String },
+
+    #[error("Invalid on_phase_hook_fail value '{value}' (expected \"warn\" or \"fail\")")]
+    InvalidOnPhaseHookFail { value: String },
+
+    #[error("Invalid phase3_scope value '{value}' (expected \"task\" or \"block\")")]
+    InvalidPhase3Scope { value: String },
+
+    #[error("Invalid on_expect_fail value '{value}' (expected \"warn\" or \"fail\")")]
+    InvalidOnExpectFail { value: String },
+
+    #[error("Invalid on_missing_done_marker value '{value}' (expected \"retry\" or \"fail\")")]
+    InvalidOnMissingDoneMarker { value: String },
+
+    #[error("done_marker must not be empty")]
+    EmptyDoneMarker,
+
+    #[error("Invalid phase2_change_check value '{value}' (expected \"off\", \"warn\", \"retry\", or \"fail\")")]
+    InvalidPhase2ChangeCheck { value: String },
+
+    #[error("Invalid log_agent_output value '{value}' (expected \"none\", \"truncated\", or \"full\")")]
+    InvalidLogAgentOutput { value: String },
+
+    #[error("Invalid on_renumber value '{value}' (expected \"rerun\" or \"trust\")")]
+    InvalidOnRenumber { value: String },
+
+    #[error("Invalid vcs value '{value}' (expected \"auto\", \"git\", \"jj\", or \"sapling\")")]
+    InvalidVcs { value: String },
+
+    #[error("{option} requires git, but the repository uses {vcs} (see vcs)")]
+    VcsUnsupported { option: &'static str, vcs: String },
+
+    #[error("Invalid agent_backend value '{value}' (expected \"cli\" or \"api\")")]
+    InvalidAgentBackend { value: String },
+
+    #[error("agent_backend = \"api\" needs a model: set api_model (or model)")]
+    ApiModelMissing,
+
+    #[error(
+        "parallel = true (from {parallel_source}) conflicts with max_parallel = 0 (from {max_parallel_source}), which turns parallel execution off; set max_parallel to 1 or more, or drop parallel"
+    )]
+    ParallelConflict {
+        parallel_source: String,
+        max_parallel_source: String,
+    },
+
+    #[error("agent_backend = \"api\": environment variable {var} (api_key_env) is not set")]
+    ApiKeyMissing { var: String },
+
+    #[error(
+        "{what} prompt is ~{estimated} tokens, over max_prompt_tokens = {max}; \
+         largest section: {largest} ({breakdown})"
+    )]
+    PromptTooLarge {
+        what: String,
+        estimated: u64,
+        max: u64,
+        largest: String,
+        breakdown: String,
+    },
+
+    #[error("Invalid on_prompt_too_large value '{value}' (expected \"fail\" or \"warn\")")]
+    InvalidOnPromptTooLarge { value: String },
+
+    #[error("invalid serve_status address '{value}': expected host:port, e.g. 127.0.0.1:7777")]
+    InvalidServeStatusAddr { value: String },
+
+    #[error("Invalid prompt_middleware entry {index}: {detail}")]
+    InvalidPromptMiddleware { index: usize, detail: String },
+
+    #[error("Invalid [execution] config: {detail}")]
+    InvalidExecution { detail: String },
+
+    #[error("failed to start status server on {addr}: {detail}")]
+    StatusServerBindFailed { addr: String, detail: String },
+
     #[error("Plan normalization failed: {detail}")]
     NormalizationFailed { detail: String },
 
@@ -91,6 +325,22 @@ pub enum PealError {
     #[error("Normalized plan output could not be parsed (no canonical tasks found). Snippet:\n{snippet}")]
     NormalizationParseFailed { snippet: String },
 
+    #[error("Review of normalized plan {path} failed: {detail}")]
+    NormalizedPlanReviewFailed { path: PathBuf, detail: String },
+
+    #[error("Normalized plan {path} was not confirmed; edit it or the source plan and run again")]
+    NormalizedPlanRejected { path: PathBuf },
+
+    #[error("Invalid plan front matter: {detail}")]
+    InvalidPlanFrontMatter { detail: String },
+
+    /// Two or more `## Task N` headings share an index. `detail` lists each index with its lines.
+    #[error("Plan has duplicate task indices (use --renumber to renumber sequentially):\n{detail}")]
+    DuplicateTaskIndices { detail: String },
+
+    #[error("Agent health check failed (agent unreachable or misconfigured): {detail}")]
+    AgentHealthCheckFailed { detail: String },
+
     #[error("Phase 1 returned empty or invalid plan (task {task_index}): {detail}")]
     Phase1PlanTextInvalid { task_index: u32, detail: String },
 
@@ -99,6 +349,125 @@ pub enum PealError {
     #[error("Run stopped: {count} consecutive task failure(s) reached cap {cap}.")]
     ConsecutiveTaskFailuresCapReached { count: u32, cap: u32 },
 
+    /// Every agent invocation counts against `max_agent_calls`; the run stops before exceeding it.
+    #[error("Agent call cap reached: {max} call(s) made (max_agent_calls); stopping the run")]
+    AgentCallCapReached { max: u64 },
+
     #[error("Commit after Phase 2 failed: {detail}")]
     CommitAfterPhase2Failed { detail: String },
+
+    #[error("git push after segment failed: {detail}")]
+    PushAfterSegmentFailed { detail: String },
+
+    #[error("Task {task_index}: Phase 2 looks like a no-op: {detail}")]
+    Phase2NoChanges { task_index: u32, detail: String },
+
+    #[error("Task {task_index}: Phase 2 answer does not end with the done marker '{marker}' (refused or cut short?)")]
+    Phase2DoneMarkerMissing { task_index: u32, marker: String },
+
+    #[error("{path} already exists; pass --force to overwrite it")]
+    InitConfigExists { path: PathBuf },
+
+    #[error("peal init could not write {path}: {detail}")]
+    InitFailed { path: PathBuf, detail: String },
+
+    #[error("Plan conversion failed: {detail}")]
+    PlanConvertFailed { detail: String },
+
+    #[error("Task {task_index}: no Phase 1 plan text in state; run --only-phase 1 (or a full run) first")]
+    MissingPlanText { task_index: u32 },
+}
+
+/// Formats a stderr excerpt for an error message; empty when there is no stderr.
+fn stderr_suffix(stderr: &str) -> String {
+    let trimmed = stderr.trim();
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("\nstderr:\n{trimmed}")
+    }
+}
+
+impl PealError {
+    /// True when the error comes from a Cursor CLI (agent) invocation rather than from stet
+    /// or peal itself. Agent failures are not subject to `on_stet_fail`.
+    pub fn is_agent_failure(&self) -> bool {
+        matches!(
+            self,
+            PealError::PhaseSpawnFailed { .. }
+                | PealError::PhaseTimedOut { .. }
+                | PealError::PhaseNonZeroExit { .. }
+                | PealError::PhaseResourceLimitExceeded { .. }
+                | PealError::PhaseKilledBySignal { .. }
+                | PealError::AgentReportedError { .. }
+                | PealError::AgentAuthFailed { .. }
+                | PealError::PromptTooLarge { .. }
+                | PealError::AgentCallCapReached { .. }
+        )
+    }
+
+    /// True when another attempt of the same agent phase may succeed (`phase_retry_count`).
+    ///
+    /// Timeouts, plain non-zero exits, and deaths by signal (e.g. the kernel OOM killer's
+    /// `SIGKILL`) are retried. A configured resource limit, an error the agent reported
+    /// itself, and an interrupt or termination request (`SIGINT`, `SIGTERM`, `SIGHUP`) are not:
+    /// retrying would hit the same limit, repeat the same error, or ignore the request to stop.
+    pub fn is_retryable_phase_failure(&self) -> bool {
+        match self {
+            PealError::PhaseTimedOut { .. } | PealError::PhaseNonZeroExit { .. } => true,
+            PealError::PhaseKilledBySignal { signal, .. } => !matches!(*signal, 1 | 2 | 15),
+            _ => false,
+        }
+    }
+
+    /// True when the same phase may succeed with a different model (`escalation_models`):
+    /// retryable phase failures, errors the agent reported, output that failed validation,
+    /// and `peal-verify` commands it could not fix. Spawn failures and configured limits would recur with any model.
+    pub fn is_escalatable(&self) -> bool {
+        self.is_retryable_phase_failure()
+            || matches!(
+                self,
+                PealError::AgentReportedError { .. }
+                    | PealError::Phase1PlanTextInvalid { .. }
+                    | PealError::Phase2NoChanges { .. }
+                    | PealError::Phase2DoneMarkerMissing { .. }
+                    | PealError::TaskVerifyFailed { .. }
+            )
+    }
+
+    /// The part of a task's work that failed, for the failure digest: `phase N` (stet counts as
+    /// phase 3), `verify`, `expect`, the hook name, or `task` when the error does not say.
+    pub fn failed_phase(&self) -> String {
+        match self {
+            PealError::PhaseSpawnFailed { phase, .. }
+            | PealError::PhaseTimedOut { phase, .. }
+            | PealError::PhaseNonZeroExit { phase, .. }
+            | PealError::PhaseResourceLimitExceeded { phase, .. }
+            | PealError::PhaseKilledBySignal { phase, .. }
+            | PealError::AgentReportedError { phase, .. } => format!("phase {phase}"),
+            PealError::Phase1PlanTextInvalid { .. } | PealError::MissingPlanText { .. } => "phase 1".to_owned(),
+            PealError::Phase2NoChanges { .. }
+            | PealError::Phase2DoneMarkerMissing { .. }
+            | PealError::SparseApplyFailed { .. }
+            | PealError::CommitAfterPhase2Failed { .. } => "phase 2".to_owned(),
+            PealError::MergeConflictUnresolved { .. } => "phase 2.5".to_owned(),
+            PealError::StetStartFailed { .. }
+            | PealError::StetRunFailed { .. }
+            | PealError::StetFinishFailed { .. }
+            | PealError::StetFindingsRemain { .. } => "phase 3".to_owned(),
+            PealError::TaskVerifyFailed { .. } => "verify".to_owned(),
+            PealError::TaskExpectationFailed { .. } => "expect".to_owned(),
+            PealError::PhaseHookFailed { hook, .. } => (*hook).to_owned(),
+            _ => "task".to_owned(),
+        }
+    }
+
+    /// True for errors that stop the whole run even with `continue_with_remaining_tasks`:
+    /// the call cap, and an unauthenticated agent, which would fail every remaining task.
+    pub fn aborts_run(&self) -> bool {
+        matches!(
+            self,
+            PealError::AgentCallCapReached { .. } | PealError::AgentAuthFailed { .. }
+        )
+    }
 }
@@ -0,0 +1,343 @@
+//! Cost and latency estimate for `peal run --dry-run`.
+//!
+//! Nothing is invoked: prompt sizes come from [`prompt::estimate_tokens`] on the Phase 1
+//! prompt peal would build for each task, and everything the agent or stet would produce
+//! (plan text, Phase 2 output, review output) uses the fixed sizes below. Prices and speed
+//! come from `model_rates` ([`ModelRate`]), looked up by the configured model with the
+//! `default` entry as fallback.
+//!
+//! The expected call count assumes every phase succeeds first time and Phase 3 needs one
+//! address round; the maximum assumes every retry, address round, and escalation model is used.
+
+use std::fmt::Write as _;
+
+use crate::config::{ModelRate, PealConfig};
+use crate::plan::{ParsedPlan, Segment};
+use crate::prompt;
+
+/// Assumed size of the Phase 1 plan text (output of Phase 1, payload of Phase 2).
+pub const ASSUMED_PLAN_TOKENS: u64 = 1500;
+/// Assumed size of Phase 2 output.
+pub const ASSUMED_PHASE2_OUTPUT_TOKENS: u64 = 2000;
+/// Assumed size of one stet review (payload of triage and Phase 3 prompts).
+pub const ASSUMED_STET_OUTPUT_TOKENS: u64 = 1000;
+/// Assumed size of the output of one Phase 3 address round.
+pub const ASSUMED_ADDRESS_OUTPUT_TOKENS: u64 = 1000;
+/// Assumed size of one task's diff (payload of the summary prompt).
+pub const ASSUMED_DIFF_TOKENS: u64 = 1500;
+/// Assumed size of short answers (triage decisions, task summaries).
+pub const ASSUMED_SHORT_ANSWER_TOKENS: u64 = 200;
+
+/// Rate entry used when `model_rates` has neither the model nor `default`.
+pub const DEFAULT_RATE_KEY: &str = "default";
+
+/// Estimate for one pending task.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskEstimate {
+    pub task_index: u32,
+    /// Agent calls when every phase succeeds first time.
+    pub agent_calls: u32,
+    /// Agent calls when every retry, address round, and escalation is used.
+    pub max_agent_calls: u32,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Expected agent time, in seconds.
+    pub duration_sec: f64,
+    /// `None` when the model's rate has no prices.
+    pub cost_usd: Option<f64>,
+}
+
+/// Estimate for a whole run (pending tasks only).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunEstimate {
+    /// Model the rates were looked up for (`auto` when none is configured).
+    pub model: String,
+    pub tasks: Vec<TaskEstimate>,
+    /// Expected wall-clock time, in seconds: parallel blocks run in waves of `max_parallel`.
+    pub duration_sec: f64,
+}
+
+impl RunEstimate {
+    pub fn agent_calls(&self) -> u32 {
+        self.tasks.iter().map(|t| t.agent_calls).sum()
+    }
+
+    pub fn max_agent_calls(&self) -> u32 {
+        self.tasks.iter().map(|t| t.max_agent_calls).sum()
+    }
+
+    pub fn input_tokens(&self) -> u64 {
+        self.tasks.iter().map(|t| t.input_tokens).sum()
+    }
+
+    pub fn output_tokens(&self) -> u64 {
+        self.tasks.iter().map(|t| t.output_tokens).sum()
+    }
+
+    /// Total cost; `None` when the model's rate has no prices or nothing is pending.
+    pub fn cost_usd(&self) -> Option<f64> {
+        if self.tasks.is_empty() {
+            return None;
+        }
+        self.tasks.iter().map(|t| t.cost_usd).sum()
+    }
+
+    /// Table with one row per task and a total, for printing to stdout.
+    pub fn render(&self) -> String {
+        let priced = self.cost_usd().is_some();
+        let mut out = format!(
+            "dry run: {} pending task(s), model {}\n\n{:>6} {:>6} {:>6} {:>10} {:>10} {:>9}",
+            self.tasks.len(),
+            self.model,
+            "task",
+            "calls",
+            "max",
+            "in tok",
+            "out tok",
+            "time"
+        );
+        if priced {
+            out.push_str(&format!(" {:>9}", "cost"));
+        }
+        out.push('\n');
+        for t in &self.tasks {
+            let _ = write!(
+                out,
+                "{:>6} {:>6} {:>6} {:>10} {:>10} {:>9}",
+                t.task_index,
+                t.agent_calls,
+                t.max_agent_calls,
+                t.input_tokens,
+                t.output_tokens,
+                format_duration(t.duration_sec)
+            );
+            if let Some(cost) = t.cost_usd {
+                let _ = write!(out, " {:>9}", format!("${cost:.2}"));
+            }
+            out.push('\n');
+        }
+        let _ = write!(
+            out,
+            "{:>6} {:>6} {:>6} {:>10} {:>10} {:>9}",
+            "total",
+            self.agent_calls(),
+            self.max_agent_calls(),
+            self.input_tokens(),
+            self.output_tokens(),
+            format_duration(self.duration_sec)
+        );
+        if let Some(cost) = self.cost_usd() {
+            let _ = write!(out, " {:>9}", format!("${cost:.2}"));
+        }
+        out.push_str("\n\ntime is wall clock for the total (parallel blocks overlap); ");
+        out.push_str("outputs use fixed size assumptions");
+        if !priced {
+            out.push_str("; set [model_rates] in the config file to include cost");
+        }
+        out.push('\n');
+        out
+    }
+}
+
+/// The rate for `config.model`, falling back to the `default` entry, then [`ModelRate::default`].
+pub fn rate_for(config: &PealConfig) -> ModelRate {
+    config
+        .model
+        .as_deref()
+        .and_then(|m| config.model_rates.get(m))
+        .or_else(|| config.model_rates.get(DEFAULT_RATE_KEY))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// One agent call: prompt and output size in tokens.
+struct Call {
+    input: u64,
+    output: u64,
+}
+
+/// Estimate one task from its content. `phase3` is whether stet review will run.
+pub fn estimate_task(task_index: u32, content: &str, config: &PealConfig, phase3: bool) -> TaskEstimate {
+    let rate = rate_for(config);
+
+    let mut calls = vec![
+        Call {
+            input: prompt::estimate_tokens(&prompt::phase1(content)),
+            output: ASSUMED_PLAN_TOKENS,
+        },
+        Call {
+            input: prompt::estimate_tokens(&prompt::phase2("")) + ASSUMED_PLAN_TOKENS,
+            output: ASSUMED_PHASE2_OUTPUT_TOKENS,
+        },
+    ];
+    let mut max_calls = 2 * (1 + config.phase_retry_count) + u32::from(config.validate_plan_text);
+
+    if phase3 {
+        if !config.stet_disable_llm_triage {
+            calls.push(Call {
+                input: prompt::estimate_tokens(&prompt::triage_prompt("")) + ASSUMED_STET_OUTPUT_TOKENS,
+                output: ASSUMED_SHORT_ANSWER_TOKENS,
+            });
+            max_calls += 1;
+        }
+        if config.max_address_rounds > 0 {
+            calls.push(Call {
+                input: prompt::estimate_tokens(&prompt::phase3("")) + ASSUMED_STET_OUTPUT_TOKENS,
+                output: ASSUMED_ADDRESS_OUTPUT_TOKENS,
+            });
+            max_calls += config.max_address_rounds * (1 + config.phase_3_retry_count);
+        }
+    }
+    if config.summarize_tasks {
+        calls.push(Call {
+            input: prompt::estimate_tokens(&prompt::task_summary("", ""))
+                + ASSUMED_PHASE2_OUTPUT_TOKENS
+                + ASSUMED_DIFF_TOKENS,
+            output: ASSUMED_SHORT_ANSWER_TOKENS,
+        });
+        max_calls += 1;
+    }
+    max_calls *= 1 + config.escalation_models.len() as u32;
+
+    let input_tokens: u64 = calls.iter().map(|c| c.input).sum();
+    let output_tokens: u64 = calls.iter().map(|c| c.output).sum();
+    let duration_sec = calls
+        .iter()
+        .map(|c| rate.call_overhead_sec + c.output as f64 / rate.output_tokens_per_sec.max(1.0))
+        .sum();
+    let priced = rate.input_usd_per_mtok > 0.0 || rate.output_usd_per_mtok > 0.0;
+    let cost_usd = priced.then(|| {
+        (input_tokens as f64 * rate.input_usd_per_mtok + output_tokens as f64 * rate.output_usd_per_mtok)
+            / 1_000_000.0
+    });
+
+    TaskEstimate {
+        task_index,
+        agent_calls: calls.len() as u32,
+        max_agent_calls: max_calls,
+        input_tokens,
+        output_tokens,
+        duration_sec,
+        cost_usd,
+    }
+}
+
+/// Estimate the tasks of `plan` not in `completed`, in segment order.
+pub fn estimate_run(plan: &ParsedPlan, config: &PealConfig, completed: &[u32], phase3: bool) -> RunEstimate {
+    let estimate = |idx: u32| {
+        plan.task_by_index(idx)
+            .filter(|_| !completed.contains(&idx))
+            .map(|t| estimate_task(idx, &t.content, config, phase3))
+    };
+
+    let mut tasks = Vec::new();
+    let mut duration_sec = 0.0;
+    for segment in &plan.segments {
+        match segment {
+            Segment::Sequential(idx) => {
+                if let Some(t) = estimate(*idx) {
+                    duration_sec += t.duration_sec;
+                    tasks.push(t);
+                }
+            }
+            Segment::Parallel(indices) => {
+                let block: Vec<TaskEstimate> = indices.iter().filter_map(|&idx| estimate(idx)).collect();
+                let wave = if config.parallel { config.max_parallel.max(1) as usize } else { 1 };
+                duration_sec += block
+                    .chunks(wave)
+                    .map(|w| w.iter().map(|t| t.duration_sec).fold(0.0, f64::max))
+                    .sum::<f64>();
+                tasks.extend(block);
+            }
+        }
+    }
+
+    RunEstimate {
+        model: config.model.clone().unwrap_or_else(|| "auto".to_owned()),
+        tasks,
+        duration_sec,
+    }
+}
+
+/// `45s`, `3m20s`, `1h05m`.
+pub(crate) fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Commands};
+    use clap::Parser;
+
+    fn config(extra: &[&str]) -> PealConfig {
+        let mut argv = vec!["peal", "run", "--plan", "plan.md", "--repo", "."];
+        argv.extend_from_slice(extra);
+        let Commands::Run(args) = Cli::try_parse_from(argv).unwrap().command else {
+            panic!("expected run command");
+        };
+        PealConfig::load(None, &args).unwrap()
+    }
+
+    #[test]
+    fn expected_calls_follow_enabled_phases() {
+        let cfg = config(&[]);
+        let with_review = estimate_task(1, "Do it.", &cfg, true);
+        let without = estimate_task(1, "Do it.", &cfg, false);
+        assert!(with_review.agent_calls > without.agent_calls);
+        assert!(with_review.max_agent_calls >= with_review.agent_calls);
+        assert!(without.input_tokens > prompt::estimate_tokens("Do it."));
+        assert_eq!(without.cost_usd, None, "no rates configured");
+    }
+
+    #[test]
+    fn escalation_multiplies_max_calls_and_rates_give_cost() {
+        let mut cfg = config(&[]);
+        let base = estimate_task(1, "Do it.", &cfg, false);
+
+        cfg.escalation_models = vec!["o3-pro".to_owned()];
+        cfg.model = Some("gpt-5.2".to_owned());
+        cfg.model_rates.insert(
+            DEFAULT_RATE_KEY.to_owned(),
+            ModelRate {
+                output_usd_per_mtok: 1_000_000.0,
+                ..ModelRate::default()
+            },
+        );
+        let t = estimate_task(1, "Do it.", &cfg, false);
+        assert_eq!(t.max_agent_calls, 2 * base.max_agent_calls);
+        assert_eq!(t.cost_usd, Some(t.output_tokens as f64), "falls back to the default rate");
+    }
+
+    #[test]
+    fn run_skips_completed_and_overlaps_parallel_tasks() {
+        let plan = crate::plan::parse_plan(
+            "## Task 1\nA\n## Task 2 (parallel)\nB\n## Task 3 (parallel)\nC\n",
+        )
+        .unwrap();
+        let mut cfg = config(&["--parallel"]);
+        cfg.max_parallel = 4;
+
+        let est = estimate_run(&plan, &cfg, &[1], false);
+        assert_eq!(est.tasks.iter().map(|t| t.task_index).collect::<Vec<_>>(), [2, 3]);
+        let longest = est.tasks.iter().map(|t| t.duration_sec).fold(0.0, f64::max);
+        assert_eq!(est.duration_sec, longest, "one wave runs both parallel tasks at once");
+        assert!(est.render().contains("2 pending task(s)"));
+
+        cfg.parallel = false;
+        let est = estimate_run(&plan, &cfg, &[1], false);
+        assert_eq!(est.duration_sec, est.tasks.iter().map(|t| t.duration_sec).sum::<f64>());
+    }
+
+    #[test]
+    fn format_duration_picks_units() {
+        assert_eq!(format_duration(45.2), "45s");
+        assert_eq!(format_duration(200.0), "3m20s");
+        assert_eq!(format_duration(3900.0), "1h05m");
+    }
+}
@@ -0,0 +1,180 @@
+//! `expect:` lines: checks a task declares about its own result.
+//!
+//! A line of a task body of the form `expect: <check>` (outside fenced code blocks) is an
+//! expectation that peal evaluates once the task's work is done, after Phase 3 (or Phase 2
+//! when Phase 3 does not run). Checks:
+//!
+//! - `file exists <path>` / `file missing <path>`
+//! - `file <path> contains "<text>"`
+//! - `cmd "<command>" succeeds` / `cmd "<command>" fails`
+//!
+//! Paths are relative to the working directory (`repo_subdir` when set) and commands run there
+//! like `peal-verify` commands, with `phase_timeout_sec` as the timeout. Unlike `peal-verify`,
+//! the agent is not asked to fix a failing expectation: it fails the task, or is only logged
+//! with `on_expect_fail = "warn"`. The lines stay in the task body, so the agent sees them too.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::config::PealConfig;
+use crate::{phase, subprocess};
+
+/// One parsed `expect:` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    FileExists(String),
+    FileMissing(String),
+    FileContains { path: String, text: String },
+    Command { command: String, succeeds: bool },
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expectation::FileExists(path) => write!(f, "file exists {path}"),
+            Expectation::FileMissing(path) => write!(f, "file missing {path}"),
+            Expectation::FileContains { path, text } => write!(f, "file {path} contains \"{text}\""),
+            Expectation::Command { command, succeeds } => {
+                write!(f, "cmd \"{command}\" {}", if *succeeds { "succeeds" } else { "fails" })
+            }
+        }
+    }
+}
+
+impl Expectation {
+    /// Parse the text after `expect:`; the error says what is wrong with it.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        if let Some(rest) = spec.strip_prefix("cmd ") {
+            let (command, rest) = quoted(rest.trim_start())?;
+            if command.trim().is_empty() {
+                return Err("empty command".to_owned());
+            }
+            let succeeds = match rest.trim() {
+                "succeeds" => true,
+                "fails" => false,
+                other => return Err(format!("expected `succeeds` or `fails` after the command, got `{other}`")),
+            };
+            return Ok(Expectation::Command { command, succeeds });
+        }
+        if let Some(rest) = spec.strip_prefix("file ") {
+            let rest = rest.trim_start();
+            if let Some(path) = rest.strip_prefix("exists ") {
+                return Ok(Expectation::FileExists(non_empty_path(path)?));
+            }
+            if let Some(path) = rest.strip_prefix("missing ") {
+                return Ok(Expectation::FileMissing(non_empty_path(path)?));
+            }
+            if let Some((path, text)) = rest.split_once(" contains ") {
+                let (text, rest) = quoted(text.trim_start())?;
+                if !rest.trim().is_empty() {
+                    return Err(format!("unexpected `{}` after the quoted text", rest.trim()));
+                }
+                return Ok(Expectation::FileContains { path: non_empty_path(path)?, text });
+            }
+            return Err("expected `file exists <path>`, `file missing <path>`, or `file <path> contains \"<text>\"`".to_owned());
+        }
+        Err("expected `file ...` or `cmd \"<command>\" succeeds|fails`".to_owned())
+    }
+
+    /// Evaluate against the working tree; the error describes how it failed.
+    pub fn check(&self, config: &PealConfig) -> Result<(), String> {
+        let work_dir = config.work_dir();
+        match self {
+            Expectation::FileExists(path) => {
+                work_dir.join(path).exists().then_some(()).ok_or_else(|| "no such file".to_owned())
+            }
+            Expectation::FileMissing(path) => {
+                (!work_dir.join(path).exists()).then_some(()).ok_or_else(|| "file exists".to_owned())
+            }
+            Expectation::FileContains { path, text } => {
+                let content = std::fs::read_to_string(work_dir.join(path)).map_err(|e| e.to_string())?;
+                content.contains(text.as_str()).then_some(()).ok_or_else(|| "text not found".to_owned())
+            }
+            Expectation::Command { command, succeeds } => {
+                let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+                let result = subprocess::run_command_string_with_shell(command, &config.command_shell, &work_dir, timeout)
+                    .ok_or_else(|| "empty command".to_owned())?
+                    .map_err(|e| e.to_string())?;
+                if result.timed_out {
+                    return Err(format!("timed out after {}s", config.phase_timeout_sec));
+                }
+                if result.success() == *succeeds {
+                    return Ok(());
+                }
+                let status = match result.exit_code {
+                    Some(0) => "exited 0".to_owned(),
+                    Some(code) => format!("exit code {code}"),
+                    None => "killed by signal".to_owned(),
+                };
+                let stderr = phase::stderr_tail(result.stderr.trim(), phase::STDERR_EXCERPT_BYTES);
+                Err(if stderr.is_empty() { status } else { format!("{status}: {stderr}") })
+            }
+        }
+    }
+}
+
+/// Split `"text" rest` into `text` and `rest`. The text ends at the last `"`, so it may contain
+/// quotes itself.
+fn quoted(s: &str) -> Result<(String, &str), String> {
+    let inner = s.strip_prefix('"').ok_or_else(|| format!("expected a double-quoted string, got `{s}`"))?;
+    let end = inner.rfind('"').ok_or_else(|| "unterminated quoted string".to_owned())?;
+    Ok((inner[..end].to_owned(), &inner[end + 1..]))
+}
+
+fn non_empty_path(path: &str) -> Result<String, String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err("missing path".to_owned());
+    }
+    Ok(path.to_owned())
+}
+
+/// The `expect:` specs of a task body, in order: lines starting with `expect:` (after
+/// whitespace and an optional `- ` list marker) outside fenced code blocks.
+pub fn expect_lines<'a>(body_lines: &[&'a str]) -> Vec<&'a str> {
+    let mut specs = Vec::new();
+    let mut in_fence = false;
+    for line in body_lines.iter().map(|l| l.trim()) {
+        if line.starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence
+            && let Some(spec) = line.strip_prefix("- ").unwrap_or(line).strip_prefix("expect:")
+        {
+            specs.push(spec.trim());
+        }
+    }
+    specs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_form_and_round_trips_through_display() {
+        for spec in [
+            "file exists src/foo.rs",
+            "file missing old.txt",
+            "file README.md contains \"peal \"quoted\" text\"",
+            "cmd \"cargo test -p foo\" succeeds",
+            "cmd \"grep -q TODO src/lib.rs\" fails",
+        ] {
+            let expectation = Expectation::parse(spec).unwrap();
+            assert_eq!(expectation.to_string(), spec);
+        }
+        assert_eq!(
+            Expectation::parse("cmd \"make\" succeeds").unwrap(),
+            Expectation::Command { command: "make".to_owned(), succeeds: true }
+        );
+        for bad in ["file", "file exists ", "cmd make succeeds", "cmd \"make\"", "cmd \"\" fails", "dir exists x"] {
+            assert!(Expectation::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn expect_lines_skip_fenced_blocks() {
+        let body = ["Do it.", "expect: file exists a", "```", "expect: file exists b", "```", "  - expect: cmd \"true\" succeeds"];
+        assert_eq!(expect_lines(&body), ["file exists a", "cmd \"true\" succeeds"]);
+    }
+}
@@ -0,0 +1,166 @@
+//! Findings-to-task traceability for the Phase 3 address loop.
+//!
+//! Every stet review the loop sees for a task is saved next to the task's other artifacts,
+//! `{state_dir}/runs/{run_id}/artifacts/task-{N}/`, as `stet-round-{R}-{step}.json`, and one
+//! line per review is appended to [`TRACE_FILE`] there: the round, the step (`initial`,
+//! `after_dismiss`, `after_address`), the finding ids, which ids are new, which triage
+//! dismissed (and why), and which went away otherwise (fixed). A last `final` line records
+//! the outcome. Together they show which findings appeared, were dismissed, or were fixed in
+//! which round, where the run summary only keeps the last result.
+//!
+//! Best-effort, like [`crate::stet::review_artifact_path`]: a write failure is logged and the
+//! loop goes on.
+
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::config::PealConfig;
+use crate::stet::{self, StetRunResult};
+
+/// Name of the per-task trace (JSON lines) in the task's artifact directory.
+pub const TRACE_FILE: &str = "stet-trace.jsonl";
+
+/// A finding triage dismissed, with the reason given to `stet dismiss`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dismissal {
+    pub id: String,
+    pub reason: String,
+}
+
+/// One line of [`TRACE_FILE`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// Address round (0 for the review before the first round).
+    pub round: u32,
+    /// `initial`, `after_dismiss`, `after_address`, or `final`.
+    pub step: String,
+    /// Finding ids in this review; absent when stet's output was not a findings JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub findings: Option<Vec<String>>,
+    /// Ids not in the previous review.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub new: Vec<String>,
+    /// Findings of the previous review that triage dismissed before this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dismissed: Vec<Dismissal>,
+    /// Ids of the previous review gone from this one without being dismissed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixed: Vec<String>,
+    /// File name of the saved review, in the same directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<String>,
+    /// On the `final` line: whether the loop ended without findings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub findings_resolved: Option<bool>,
+}
+
+/// Directory the trace of `task_index` is written to in this run.
+pub fn trace_dir(config: &PealConfig, task_index: u32) -> PathBuf {
+    crate::run_id::run_dir(&config.state_dir)
+        .join("artifacts")
+        .join(format!("task-{task_index}"))
+}
+
+/// Read the trace of `task_index` in this run; empty when there is none.
+pub fn load(config: &PealConfig, task_index: u32) -> Vec<TraceEntry> {
+    fs::read_to_string(trace_dir(config, task_index).join(TRACE_FILE))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// The trace of one task's address loop. Starts a fresh [`TRACE_FILE`].
+pub struct FindingsTrace {
+    dir: PathBuf,
+    task_index: u32,
+    previous: Option<Vec<String>>,
+}
+
+impl FindingsTrace {
+    pub fn new(config: &PealConfig, task_index: u32) -> Self {
+        let dir = trace_dir(config, task_index);
+        // A block review or a rerun of the task in this run starts over.
+        let _ = fs::remove_file(dir.join(TRACE_FILE));
+        Self {
+            dir,
+            task_index,
+            previous: None,
+        }
+    }
+
+    /// Record `result` as the review of `step` in `round`, after `dismissed` were dismissed.
+    pub fn review(&mut self, round: u32, step: &str, result: &StetRunResult, dismissed: &[(String, String)]) {
+        let file = format!("stet-round-{round}-{step}.json");
+        let saved = fs::create_dir_all(&self.dir).and_then(|()| fs::write(self.dir.join(&file), &result.stdout));
+        if let Err(e) = &saved {
+            warn!(task_index = self.task_index, file, err = %e, "failed to save stet review for the findings trace");
+        }
+        let findings: Option<Vec<String>> =
+            stet::parse_findings_from_run_json(&result.stdout).map(|f| f.into_iter().map(|f| f.id).collect());
+        let dismissed: Vec<Dismissal> = dismissed
+            .iter()
+            .map(|(id, reason)| Dismissal {
+                id: id.clone(),
+                reason: reason.clone(),
+            })
+            .collect();
+        let (new, fixed) = match (&self.previous, &findings) {
+            (Some(before), Some(now)) => {
+                let before_set: BTreeSet<&String> = before.iter().collect();
+                let now_set: BTreeSet<&String> = now.iter().collect();
+                let new = now.iter().filter(|id| !before_set.contains(id)).cloned().collect();
+                let fixed = before
+                    .iter()
+                    .filter(|id| !now_set.contains(id) && !dismissed.iter().any(|d| &d.id == *id))
+                    .cloned()
+                    .collect();
+                (new, fixed)
+            }
+            (None, Some(now)) => (now.clone(), Vec::new()),
+            _ => (Vec::new(), Vec::new()),
+        };
+        self.previous = findings.clone();
+        self.append(&TraceEntry {
+            round,
+            step: step.to_owned(),
+            findings,
+            new,
+            dismissed,
+            fixed,
+            review: saved.ok().map(|()| file),
+            findings_resolved: None,
+        });
+    }
+
+    /// Record the loop's outcome after `rounds_used` rounds.
+    pub fn finish(&mut self, rounds_used: u32, findings_resolved: bool) {
+        self.append(&TraceEntry {
+            round: rounds_used,
+            step: "final".to_owned(),
+            findings: self.previous.clone(),
+            new: Vec::new(),
+            dismissed: Vec::new(),
+            fixed: Vec::new(),
+            review: None,
+            findings_resolved: Some(findings_resolved),
+        });
+    }
+
+    fn append(&self, entry: &TraceEntry) {
+        let path = self.dir.join(TRACE_FILE);
+        let written = fs::create_dir_all(&self.dir).and_then(|()| {
+            let line = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            writeln!(file, "{line}")
+        });
+        if let Err(e) = written {
+            warn!(task_index = self.task_index, path = %path.display(), err = %e, "failed to append to the findings trace");
+        }
+    }
+}
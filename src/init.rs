@@ -0,0 +1,296 @@
+//! `peal init`: first-run scaffolding for a target repository.
+//!
+//! Writes a starter `peal.toml` with the common options commented out, adds the state
+//! directory to `.gitignore`, and optionally an example plan in canonical format. The
+//! agent command and plan path are asked for interactively unless given on the command line.
+
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::PealError;
+
+/// Name of the config file written by [`scaffold`]; the first entry of
+/// [`crate::config::AUTO_CONFIG_FILES`], so `peal run` picks it up without `--config`.
+pub const CONFIG_FILE_NAME: &str = "peal.toml";
+
+/// Line added to `.gitignore` for the default state directory.
+const GITIGNORE_ENTRY: &str = ".peal/";
+
+/// Default agent command offered by the interactive prompt.
+pub const DEFAULT_AGENT_CMD: &str = "agent";
+
+/// Default plan path offered by the interactive prompt.
+pub const DEFAULT_PLAN_PATH: &str = "plan.md";
+
+/// Answers that shape the scaffolded files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitOptions {
+    pub agent_cmd: String,
+    /// Plan path written to `plan_path`, relative to the target directory.
+    pub plan_path: PathBuf,
+    /// Write an example plan at `plan_path` when no file exists there yet.
+    pub example_plan: bool,
+}
+
+/// What [`scaffold`] created or changed, for the closing message.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct InitReport {
+    pub config: Option<PathBuf>,
+    pub gitignore: Option<PathBuf>,
+    pub plan: Option<PathBuf>,
+}
+
+/// Ask for any option not already known on `input`, writing prompts to `output`.
+///
+/// An empty answer takes the shown default. `example_plan` defaults to yes.
+pub fn prompt_options(
+    agent_cmd: Option<String>,
+    plan_path: Option<PathBuf>,
+    example_plan: Option<bool>,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> std::io::Result<InitOptions> {
+    let mut ask = |question: &str, default: &str| -> std::io::Result<String> {
+        write!(output, "{question} [{default}]: ")?;
+        output.flush()?;
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        let answer = answer.trim();
+        Ok(if answer.is_empty() { default.to_owned() } else { answer.to_owned() })
+    };
+    let agent_cmd = match agent_cmd {
+        Some(cmd) => cmd,
+        None => ask("Agent command (Cursor CLI binary name or path)", DEFAULT_AGENT_CMD)?,
+    };
+    let plan_path = match plan_path {
+        Some(path) => path,
+        None => PathBuf::from(ask("Plan file path", DEFAULT_PLAN_PATH)?),
+    };
+    let example_plan = match example_plan {
+        Some(yes) => yes,
+        None => matches!(
+            ask("Write an example plan if the file does not exist? (y/n)", "y")?
+                .to_ascii_lowercase()
+                .as_str(),
+            "y" | "yes"
+        ),
+    };
+    Ok(InitOptions {
+        agent_cmd,
+        plan_path,
+        example_plan,
+    })
+}
+
+/// Create the starter files in `dir`.
+///
+/// Fails if `peal.toml` already exists unless `force` is set. `.gitignore` is only
+/// appended to when it does not already ignore `.peal`; an existing plan is never overwritten.
+pub fn scaffold(dir: &Path, options: &InitOptions, force: bool) -> Result<InitReport, PealError> {
+    let init_err = |path: &Path, e: std::io::Error| PealError::InitFailed {
+        path: path.to_path_buf(),
+        detail: e.to_string(),
+    };
+    let mut report = InitReport::default();
+
+    let config_path = dir.join(CONFIG_FILE_NAME);
+    if config_path.exists() && !force {
+        return Err(PealError::InitConfigExists { path: config_path });
+    }
+    fs::write(&config_path, starter_config(options)).map_err(|e| init_err(&config_path, e))?;
+    report.config = Some(config_path);
+
+    let gitignore_path = dir.join(".gitignore");
+    let existing = match fs::read_to_string(&gitignore_path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(init_err(&gitignore_path, e)),
+    };
+    if !ignores_state_dir(&existing) {
+        let mut updated = existing;
+        if !updated.is_empty() && !updated.ends_with('\n') {
+            updated.push('\n');
+        }
+        updated.push_str(GITIGNORE_ENTRY);
+        updated.push('\n');
+        fs::write(&gitignore_path, updated).map_err(|e| init_err(&gitignore_path, e))?;
+        report.gitignore = Some(gitignore_path);
+    }
+
+    if options.example_plan {
+        let plan_path = dir.join(&options.plan_path);
+        if !plan_path.exists() {
+            if let Some(parent) = plan_path.parent() {
+                fs::create_dir_all(parent).map_err(|e| init_err(parent, e))?;
+            }
+            fs::write(&plan_path, EXAMPLE_PLAN).map_err(|e| init_err(&plan_path, e))?;
+            report.plan = Some(plan_path);
+        }
+    }
+
+    Ok(report)
+}
+
+/// True when a `.gitignore` line already covers the default state directory.
+fn ignores_state_dir(gitignore: &str) -> bool {
+    gitignore
+        .lines()
+        .map(str::trim)
+        .any(|l| matches!(l, ".peal" | ".peal/" | "/.peal" | "/.peal/"))
+}
+
+/// Escape `s` as a TOML basic string.
+fn toml_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Starter `peal.toml`: required keys set, common options commented out at their defaults.
+fn starter_config(options: &InitOptions) -> String {
+    format!(
+        r#"# peal configuration. Precedence: CLI flags > PEAL_* env vars > this file > defaults.
+# Full reference: docs/configuration.md
+
+# Plan file in canonical format (`## Task 1`, `## Task 2`, ...). Relative paths are
+# resolved against the directory peal runs in.
+plan_path = {plan}
+repo_path = "."
+
+# Cursor CLI binary name or path.
+agent_cmd = {agent}
+
+# Model override; omit for Auto.
+# model = "gpt-5.2"
+
+# Directory for state.json, artifacts, and the run summary.
+# state_dir = ".peal"
+
+# Per-phase timeout and retries.
+# phase_timeout_sec = 1800
+# phase_retry_count = 0
+
+# Run consecutive `(parallel)` tasks concurrently.
+# parallel = false
+# max_parallel = 4
+
+# stet review (Phase 3): what to do when findings remain or stet fails.
+# max_address_rounds = 5
+# on_findings_remaining = "fail"   # "warn", "create_task"
+# on_stet_fail = "fail"            # "retry_once", "skip"
+
+# Commit after each Phase 2 so stet can review the changes.
+# commit_after_phase2 = false
+
+# Keep going after a task fails.
+# continue_with_remaining_tasks = false
+"#,
+        plan = toml_string(&options.plan_path.to_string_lossy()),
+        agent = toml_string(&options.agent_cmd),
+    )
+}
+
+/// Example plan in canonical format, written when the user asks for one.
+const EXAMPLE_PLAN: &str = r#"# Example plan
+
+Goal: replace these tasks with your own. Each `## Task N` section is planned, executed,
+and reviewed on its own; `peal prompt` prints instructions for having an LLM write one.
+
+## Task 1
+
+Add a `CONTRIBUTING.md` describing how to build and test the project.
+
+## Task 2 (parallel)
+
+Add a unit test for an untested function in the main module.
+
+## Task 3 (parallel)
+
+Fix any compiler or linter warnings in the project.
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> InitOptions {
+        InitOptions {
+            agent_cmd: "cursor-agent".to_owned(),
+            plan_path: PathBuf::from("plans/plan.md"),
+            example_plan: true,
+        }
+    }
+
+    #[test]
+    fn scaffold_writes_config_gitignore_and_example_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = scaffold(dir.path(), &options(), false).unwrap();
+
+        let config = fs::read_to_string(dir.path().join("peal.toml")).unwrap();
+        assert!(config.contains("plan_path = \"plans/plan.md\""));
+        assert!(config.contains("agent_cmd = \"cursor-agent\""));
+        let parsed: toml::Value = toml::from_str(&config).expect("starter config is valid TOML");
+        assert_eq!(parsed["repo_path"].as_str(), Some("."));
+
+        assert_eq!(fs::read_to_string(dir.path().join(".gitignore")).unwrap(), ".peal/\n");
+        let plan = fs::read_to_string(dir.path().join("plans/plan.md")).unwrap();
+        assert!(crate::plan::parse_plan(&plan).unwrap().tasks.len() == 3);
+        assert!(report.config.is_some() && report.gitignore.is_some() && report.plan.is_some());
+    }
+
+    #[test]
+    fn scaffold_refuses_existing_config_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("peal.toml"), "# mine\n").unwrap();
+        let err = scaffold(dir.path(), &options(), false).unwrap_err();
+        assert!(matches!(err, PealError::InitConfigExists { .. }));
+        assert_eq!(fs::read_to_string(dir.path().join("peal.toml")).unwrap(), "# mine\n");
+
+        scaffold(dir.path(), &options(), true).unwrap();
+        assert!(fs::read_to_string(dir.path().join("peal.toml")).unwrap().contains("agent_cmd"));
+    }
+
+    #[test]
+    fn scaffold_keeps_existing_gitignore_entry_and_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "target\n/.peal").unwrap();
+        fs::create_dir_all(dir.path().join("plans")).unwrap();
+        fs::write(dir.path().join("plans/plan.md"), "## Task 1\nmine\n").unwrap();
+
+        let report = scaffold(dir.path(), &options(), false).unwrap();
+        assert_eq!(report.gitignore, None);
+        assert_eq!(report.plan, None);
+        assert_eq!(fs::read_to_string(dir.path().join(".gitignore")).unwrap(), "target\n/.peal");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("plans/plan.md")).unwrap(),
+            "## Task 1\nmine\n"
+        );
+    }
+
+    #[test]
+    fn scaffold_appends_to_gitignore_without_trailing_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "target").unwrap();
+        scaffold(dir.path(), &options(), false).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join(".gitignore")).unwrap(), "target\n.peal/\n");
+    }
+
+    #[test]
+    fn prompt_options_uses_defaults_and_skips_known_values() {
+        let mut input: &[u8] = b"\n\nn\n";
+        let mut output = Vec::new();
+        let opts = prompt_options(None, None, None, &mut input, &mut output).unwrap();
+        assert_eq!(opts.agent_cmd, DEFAULT_AGENT_CMD);
+        assert_eq!(opts.plan_path, PathBuf::from(DEFAULT_PLAN_PATH));
+        assert!(!opts.example_plan);
+        assert!(String::from_utf8(output).unwrap().contains("Agent command"));
+
+        let mut input: &[u8] = b"docs/tasks.md\n";
+        let mut output = Vec::new();
+        let opts = prompt_options(Some("my-agent".to_owned()), None, Some(true), &mut input, &mut output)
+            .unwrap();
+        assert_eq!(opts.agent_cmd, "my-agent");
+        assert_eq!(opts.plan_path, PathBuf::from("docs/tasks.md"));
+        assert!(opts.example_plan);
+        assert!(!String::from_utf8(output).unwrap().contains("Agent command"));
+    }
+}
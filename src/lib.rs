@@ -1,14 +1,43 @@
+pub mod agent_install;
+pub mod api_agent;
+pub mod bench;
 pub mod cli;
+pub mod clock;
 pub mod config;
+pub mod container;
+pub mod crash;
 pub mod cursor;
+pub mod daemon;
 pub mod error;
+pub mod estimate;
+pub mod expect;
+pub mod findings_trace;
+pub mod hash;
+pub mod init;
+pub mod lock;
 pub mod logging;
+pub mod otel;
 pub mod phase;
 pub mod plan;
+pub mod plan_convert;
+pub mod plan_graph;
 pub mod plan_prompt;
 pub mod prompt;
+pub mod prompt_middleware;
+pub mod rate_limit;
+pub mod retries;
+pub mod run_id;
+pub mod run_many;
 pub mod runner;
 pub mod run_summary;
+pub mod shutdown;
+pub mod sparse;
 pub mod state;
+pub mod status_server;
 pub mod stet;
 pub mod subprocess;
+#[cfg(unix)]
+pub mod testing;
+pub mod timing;
+pub mod triage_cache;
+pub mod vcs;
@@ -0,0 +1,228 @@
+//! Run manifest (`lock_path`) and `--locked` runs.
+//!
+//! At run start peal resolves a [`RunManifest`]: the peal version, the agent binary and its
+//! `--version` output, the model, stet and its version, a SHA-256 of every built-in prompt template
+//! (and of a custom `normalize_prompt_path` template), and a SHA-256 of the plan after variable
+//! expansion. With `lock_path` set, a normal run writes the manifest there as TOML, like a
+//! `Cargo.lock`. A `--locked` run reads it back instead and fails before any task runs when
+//! anything differs, naming each changed entry, so a run can be shown to have used the same
+//! agent, prompts, and plan as the one that wrote the lock.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::config::PealConfig;
+use crate::error::PealError;
+use crate::hash::sha256_hex;
+use crate::{prompt, subprocess};
+
+/// How long `<tool> --version` may take before the version is recorded as unknown.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a run resolved to before its first task; see the module docs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub peal_version: String,
+    /// SHA-256 ([`sha256_hex`]) of the plan body after `{{NAME}}` variables are expanded.
+    pub plan_hash: String,
+    pub agent_path: String,
+    /// First line of `<agent> --version`; absent when it fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Absent when stet is not used (not found, or Phase 3 disabled).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stet_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stet_version: Option<String>,
+    /// Prompt template name → SHA-256 of the template.
+    #[serde(default)]
+    pub prompts: BTreeMap<String, String>,
+}
+
+impl RunManifest {
+    /// Resolve the manifest of a run: runs `--version` on the agent and on stet (when given).
+    pub fn resolve(config: &PealConfig, agent_path: &Path, stet_path: Option<&Path>, plan_body: &str) -> Self {
+        RunManifest {
+            peal_version: env!("CARGO_PKG_VERSION").to_owned(),
+            plan_hash: sha256_hex(plan_body.as_bytes()),
+            agent_path: agent_path.display().to_string(),
+            agent_version: tool_version(agent_path, &config.agent_args(), &config.repo_path),
+            model: config.model.clone(),
+            stet_path: stet_path.map(|p| p.display().to_string()),
+            stet_version: stet_path.and_then(|p| tool_version(p, &[], &config.repo_path)),
+            prompts: prompt_hashes(config),
+        }
+    }
+
+    /// Entries that differ from `locked`, as `name: locked value -> this run's value`, in
+    /// field order.
+    pub fn differences(&self, locked: &RunManifest) -> Vec<String> {
+        let (old, new) = (locked.entries(), self.entries());
+        let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+        let rank = |k: &str| ENTRY_ORDER.iter().position(|o| k.starts_with(o)).unwrap_or(ENTRY_ORDER.len());
+        keys.sort_by(|a, b| (rank(a), a).cmp(&(rank(b), b)));
+        keys.dedup();
+        let show = |v: Option<&String>| v.map_or_else(|| "(none)".to_owned(), |v| format!("{v:?}"));
+        keys.into_iter()
+            .filter(|k| old.get(*k) != new.get(*k))
+            .map(|k| format!("{k}: {} -> {}", show(old.get(k)), show(new.get(k))))
+            .collect()
+    }
+
+    /// Flat `name → value` view used by [`Self::differences`]; prompts are `prompts.<name>`.
+    fn entries(&self) -> BTreeMap<String, String> {
+        let mut entries = BTreeMap::new();
+        let mut put = |key: &str, value: Option<&String>| {
+            if let Some(v) = value {
+                entries.insert(key.to_owned(), v.clone());
+            }
+        };
+        put("peal_version", Some(&self.peal_version));
+        put("plan_hash", Some(&self.plan_hash));
+        put("agent_path", Some(&self.agent_path));
+        put("agent_version", self.agent_version.as_ref());
+        put("model", self.model.as_ref());
+        put("stet_path", self.stet_path.as_ref());
+        put("stet_version", self.stet_version.as_ref());
+        for (name, hash) in &self.prompts {
+            entries.insert(format!("prompts.{name}"), hash.clone());
+        }
+        entries
+    }
+}
+
+/// Order of entries in [`RunManifest::differences`] (the field order of the manifest).
+const ENTRY_ORDER: [&str; 8] = [
+    "peal_version",
+    "plan_hash",
+    "agent_path",
+    "agent_version",
+    "model",
+    "stet_path",
+    "stet_version",
+    "prompts.",
+];
+
+/// First non-empty line of `<tool> <leading> --version` (stdout, else stderr), or `None` when
+/// it fails. `leading` is the agent's arguments from `agent_cmd`.
+fn tool_version(tool: &Path, leading: &[String], cwd: &Path) -> Option<String> {
+    let args: Vec<&str> = leading.iter().map(String::as_str).chain(["--version"]).collect();
+    let result = subprocess::run_command(&tool.to_string_lossy(), &args, cwd, Some(VERSION_TIMEOUT)).ok()?;
+    if !result.success() {
+        debug!(tool = %tool.display(), exit_code = ?result.exit_code, "--version failed");
+        return None;
+    }
+    let first_line = |s: &str| s.lines().map(str::trim).find(|l| !l.is_empty()).map(String::from);
+    first_line(&result.stdout).or_else(|| first_line(&result.stderr))
+}
+
+/// SHA-256 of every prompt template peal may send, rendered with empty inputs so only the
+/// template text counts. A custom normalization template is hashed as read.
+fn prompt_hashes(config: &PealConfig) -> BTreeMap<String, String> {
+    let normalize = match &config.normalize_prompt_path {
+        Some(path) => fs::read_to_string(path).unwrap_or_default(),
+        None => prompt::normalize_plan_prompt(""),
+    };
+    [
+        ("phase1", prompt::phase1("")),
+        ("phase2", prompt::phase2("")),
+        ("phase2_noop_retry", prompt::phase2_noop_retry("", "")),
+        ("phase3", prompt::phase3_with_suggestions("", Some(""))),
+        ("triage", prompt::triage_prompt("")),
+        ("verify_fix", prompt::verify_fix("", "")),
+        ("task_summary", prompt::task_summary("", "")),
+        ("normalize", normalize),
+    ]
+    .into_iter()
+    .map(|(name, text)| (name.to_owned(), sha256_hex(text.as_bytes())))
+    .collect()
+}
+
+/// Write `manifest` to `path` as TOML (creating parent directories).
+pub fn write(path: &Path, manifest: &RunManifest) -> Result<(), PealError> {
+    let failed = |detail: String| PealError::LockFileFailed { path: path.to_path_buf(), detail };
+    let text = toml::to_string_pretty(manifest).map_err(|e| failed(e.to_string()))?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| failed(e.to_string()))?;
+    }
+    let header = "# Written by peal at run start; `peal run --locked` checks a run against it.\n";
+    fs::write(path, format!("{header}{text}")).map_err(|e| failed(e.to_string()))
+}
+
+/// Check `manifest` against the lock file at `path` (`--locked`).
+pub fn check(path: &Path, manifest: &RunManifest) -> Result<(), PealError> {
+    let text = fs::read_to_string(path).map_err(|e| PealError::LockFileFailed {
+        path: path.to_path_buf(),
+        detail: e.to_string(),
+    })?;
+    let locked: RunManifest = toml::from_str(&text).map_err(|e| PealError::LockFileFailed {
+        path: path.to_path_buf(),
+        detail: e.to_string(),
+    })?;
+    let differences = manifest.differences(&locked);
+    if differences.is_empty() {
+        return Ok(());
+    }
+    Err(PealError::LockMismatch {
+        path: path.to_path_buf(),
+        detail: differences.join("; "),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> RunManifest {
+        RunManifest {
+            peal_version: "0.1.0".to_owned(),
+            plan_hash: "aaaa".to_owned(),
+            agent_path: "/bin/agent".to_owned(),
+            agent_version: Some("agent 1.2".to_owned()),
+            model: None,
+            stet_path: None,
+            stet_version: None,
+            prompts: BTreeMap::from([("phase1".to_owned(), "p1".to_owned()), ("phase2".to_owned(), "p2".to_owned())]),
+        }
+    }
+
+    #[test]
+    fn differences_name_each_changed_entry_in_field_order() {
+        let locked = manifest();
+        assert!(manifest().differences(&locked).is_empty());
+
+        let mut run = manifest();
+        run.prompts.insert("phase2".to_owned(), "p2b".to_owned());
+        run.model = Some("m".to_owned());
+        run.agent_version = None;
+        assert_eq!(
+            run.differences(&locked),
+            vec![
+                "agent_version: \"agent 1.2\" -> (none)",
+                "model: (none) -> \"m\"",
+                "prompts.phase2: \"p2\" -> \"p2b\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn written_lock_checks_clean_and_reports_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locks/peal.lock");
+        write(&path, &manifest()).unwrap();
+        check(&path, &manifest()).unwrap();
+
+        let mut run = manifest();
+        run.plan_hash = "bbbb".to_owned();
+        let err = check(&path, &run).unwrap_err();
+        assert!(matches!(&err, PealError::LockMismatch { detail, .. } if detail == "plan_hash: \"aaaa\" -> \"bbbb\""), "{err}");
+        assert!(matches!(check(&dir.path().join("missing"), &run), Err(PealError::LockFileFailed { .. })));
+    }
+}
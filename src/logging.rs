@@ -1,8 +1,15 @@
-use std::path::Path;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
-use std::sync::Once;
+use std::sync::{Mutex, Once};
+
+use crate::crash::CrashLayer;
+use crate::otel;
+use crate::status_server::StatusLayer;
 
 use tracing::Level;
+use tracing_subscriber::filter::filter_fn;
 use tracing_subscriber::fmt::writer::MakeWriterExt;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
@@ -17,15 +24,44 @@ const ENV_VAR_NAME: &str = "PEAL_LOG";
 /// also goes to that file (appending). The env filter is resolved with
 /// precedence: `PEAL_LOG` env var > `log_level` argument > default (`info`).
 ///
+/// When `log_max_size_mb` is `Some(n)` with `n > 0`, the log file is rotated
+/// before a write would push it past `n` MiB: `peal.log` becomes `peal.log.1`,
+/// `peal.log.1` becomes `peal.log.2`, and so on, keeping at most
+/// `log_keep_files` rotated files. With `log_keep_files = 0` the file is
+/// truncated instead.
+///
+/// When `otel_endpoint` is set, spans (run, task, phase, stet) are also exported
+/// to that OTLP/HTTP collector (see `crate::otel`); an invalid endpoint is an error.
+///
+/// When `status` is set (`serve_status`), run, task, and phase spans also feed the
+/// live status server (see `crate::status_server`).
+///
+/// Recent log lines and the current task and phase are always kept for crash
+/// reports (see `crate::crash`).
+///
 /// Structured fields (phase, task_index, command, exit_code, duration_ms)
 /// are attached via `tracing::Span` and `tracing::event!` at call sites.
 static INIT: Once = Once::new();
 
-pub fn init(log_level: Option<&str>, log_file: Option<&Path>) -> anyhow::Result<()> {
+pub fn init(
+    log_level: Option<&str>,
+    log_file: Option<&Path>,
+    log_max_size_mb: Option<u64>,
+    log_keep_files: u32,
+    otel_endpoint: Option<&str>,
+    status: Option<StatusLayer>,
+) -> anyhow::Result<()> {
     let mut init_err: Option<anyhow::Error> = None;
 
     INIT.call_once(|| {
-        if let Err(e) = try_init(log_level, log_file) {
+        if let Err(e) = try_init(
+            log_level,
+            log_file,
+            log_max_size_mb,
+            log_keep_files,
+            otel_endpoint,
+            status,
+        ) {
             init_err = Some(e);
         }
     });
@@ -36,9 +72,21 @@ pub fn init(log_level: Option<&str>, log_file: Option<&Path>) -> anyhow::Result<
     }
 }
 
-fn try_init(log_level: Option<&str>, log_file: Option<&Path>) -> anyhow::Result<()> {
+fn try_init(
+    log_level: Option<&str>,
+    log_file: Option<&Path>,
+    log_max_size_mb: Option<u64>,
+    log_keep_files: u32,
+    otel_endpoint: Option<&str>,
+    status: Option<StatusLayer>,
+) -> anyhow::Result<()> {
     let filter = build_filter(log_level);
 
+    let otel_endpoint = otel_endpoint
+        .map(otel::Endpoint::parse)
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("invalid otel_endpoint {e}"))?;
+
     let stderr_layer = tracing_subscriber::fmt::layer()
         .with_writer(std::io::stderr)
         .with_target(false)
@@ -48,7 +96,10 @@ fn try_init(log_level: Option<&str>, log_file: Option<&Path>) -> anyhow::Result<
 
     let result: Result<(), TryInitError> = match log_file {
         Some(path) => {
-            let file = open_log_file(path)?;
+            let max_bytes = log_max_size_mb
+                .filter(|&mb| mb > 0)
+                .map(|mb| mb.saturating_mul(1024 * 1024));
+            let file = Mutex::new(RotatingFile::open(path, max_bytes, log_keep_files)?);
             let file_layer = tracing_subscriber::fmt::layer()
                 .with_writer(file.with_max_level(Level::TRACE))
                 .with_target(false)
@@ -59,16 +110,47 @@ fn try_init(log_level: Option<&str>, log_file: Option<&Path>) -> anyhow::Result<
             tracing_subscriber::registry()
                 .with(stderr_layer.with_filter(filter))
                 .with(file_layer)
+                .with(otel_endpoint.map(otel_layer))
+                .with(status.map(status_layer))
+                .with(crash_layer())
                 .try_init()
         }
         None => tracing_subscriber::registry()
             .with(stderr_layer.with_filter(filter))
+            .with(otel_endpoint.map(otel_layer))
+            .with(status.map(status_layer))
+            .with(crash_layer())
             .try_init(),
     };
 
     result.map_err(|e| anyhow::anyhow!("failed to initialize logging: {e}"))
 }
 
+/// OTLP export layer: spans plus ERROR events (which mark the enclosing span as failed).
+fn otel_layer<S>(endpoint: otel::Endpoint) -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    otel::OtlpLayer::new(endpoint)
+        .with_filter(filter_fn(|meta| meta.is_span() || *meta.level() == Level::ERROR))
+}
+
+/// Status server layer: spans plus ERROR events (which mark the enclosing task as failed).
+fn status_layer<S>(layer: StatusLayer) -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    layer.with_filter(filter_fn(|meta| meta.is_span() || *meta.level() == Level::ERROR))
+}
+
+/// Crash report layer: task, phase, and stet spans plus info-and-above events (see `crate::crash`).
+fn crash_layer<S>() -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    CrashLayer.with_filter(filter_fn(CrashLayer::wants))
+}
+
 fn build_filter(log_level: Option<&str>) -> EnvFilter {
     // PEAL_LOG env var takes highest precedence (handled by EnvFilter::try_from_env).
     EnvFilter::try_from_env(ENV_VAR_NAME).unwrap_or_else(|_| {
@@ -97,6 +179,85 @@ fn open_log_file(path: &Path) -> anyhow::Result<std::fs::File> {
         .map_err(|e| anyhow::anyhow!("failed to open log file {}: {e}", path.display()))
 }
 
+/// Log file writer with optional size-based rotation.
+///
+/// Each tracing event is written with a single `write` call, so rotation
+/// happens between events and never splits a JSON line.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    /// None = rotation disabled (append forever).
+    max_bytes: Option<u64>,
+    keep_files: u32,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_bytes: Option<u64>, keep_files: u32) -> anyhow::Result<Self> {
+        let file = open_log_file(path)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size,
+            max_bytes,
+            keep_files,
+        })
+    }
+
+    /// Path of the rotated file with the given index (`peal.log.1`, `peal.log.2`, ...).
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    /// Shift `path.N` to `path.N+1` (dropping the oldest), move the current
+    /// file to `path.1`, and reopen an empty file at `path`.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let oldest = self.rotated_path(self.keep_files);
+            if oldest.exists() {
+                std::fs::remove_file(&oldest)?;
+            }
+            for index in (1..self.keep_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(max) = self.max_bytes
+            && self.size > 0
+            && self.size + buf.len() as u64 > max
+        {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,11 +311,11 @@ mod tests {
 
         {
             let mut f = open_log_file(&log_path).unwrap();
-            write!(f, "line1\n").unwrap();
+            writeln!(f, "line1").unwrap();
         }
         {
             let mut f = open_log_file(&log_path).unwrap();
-            write!(f, "line2\n").unwrap();
+            writeln!(f, "line2").unwrap();
         }
 
         let contents = std::fs::read_to_string(&log_path).unwrap();
@@ -163,4 +324,85 @@ mod tests {
             "expected both lines, got: {contents}"
         );
     }
+
+    #[test]
+    fn rotating_file_without_limit_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("peal.log");
+
+        let mut f = RotatingFile::open(&log_path, None, 3).unwrap();
+        for _ in 0..10 {
+            f.write_all(b"0123456789\n").unwrap();
+        }
+
+        assert_eq!(std::fs::metadata(&log_path).unwrap().len(), 110);
+        assert!(!dir.path().join("peal.log.1").exists());
+    }
+
+    #[test]
+    fn rotating_file_rotates_when_limit_exceeded() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("peal.log");
+
+        let mut f = RotatingFile::open(&log_path, Some(20), 3).unwrap();
+        f.write_all(b"first-line\n").unwrap();
+        f.write_all(b"second-line\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&log_path).unwrap(), "second-line\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("peal.log.1")).unwrap(),
+            "first-line\n"
+        );
+    }
+
+    #[test]
+    fn rotating_file_keeps_at_most_keep_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("peal.log");
+
+        let mut f = RotatingFile::open(&log_path, Some(5), 2).unwrap();
+        for line in ["line-a\n", "line-b\n", "line-c\n", "line-d\n"] {
+            f.write_all(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(std::fs::read_to_string(&log_path).unwrap(), "line-d\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("peal.log.1")).unwrap(),
+            "line-c\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("peal.log.2")).unwrap(),
+            "line-b\n"
+        );
+        assert!(!dir.path().join("peal.log.3").exists());
+    }
+
+    #[test]
+    fn rotating_file_keep_zero_truncates() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("peal.log");
+
+        let mut f = RotatingFile::open(&log_path, Some(5), 0).unwrap();
+        f.write_all(b"old-line\n").unwrap();
+        f.write_all(b"new-line\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&log_path).unwrap(), "new-line\n");
+        assert!(!dir.path().join("peal.log.1").exists());
+    }
+
+    #[test]
+    fn rotating_file_rotates_existing_oversized_file_on_first_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("peal.log");
+        std::fs::write(&log_path, "previous run output\n").unwrap();
+
+        let mut f = RotatingFile::open(&log_path, Some(10), 1).unwrap();
+        f.write_all(b"new run\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&log_path).unwrap(), "new run\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("peal.log.1")).unwrap(),
+            "previous run output\n"
+        );
+    }
 }
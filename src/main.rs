@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::process::ExitCode;
 use std::time::Duration;
 
@@ -7,10 +8,12 @@ use std::os::unix::fs::PermissionsExt;
 use clap::Parser;
 use tracing::{error, info, warn};
 
-use peal::cli::{Cli, Commands};
+use peal::cli::{AgentCommand, Cli, Commands, ConfigCommand, PlanCommand, StateCommand};
 use peal::config::PealConfig;
-use peal::cursor;
+use peal::phase;
 use peal::plan;
+use peal::plan_convert;
+use peal::plan_graph;
 use peal::plan_prompt;
 use peal::runner;
 use peal::run_summary;
@@ -18,10 +21,30 @@ use peal::state;
 use peal::stet;
 
 fn main() -> ExitCode {
+    peal::shutdown::install();
+    peal::crash::install();
     let cli = Cli::parse();
 
-    match run(cli) {
-        Ok(CommandOutcome::PromptOk) => ExitCode::SUCCESS,
+    let result = run(cli);
+    peal::shutdown::reap_children(if result.is_ok() { "exit" } else { "error" });
+    // Spans are exported on a background thread; let it finish before the process exits.
+    peal::otel::flush();
+    match result {
+        Ok(
+            CommandOutcome::PromptOk
+            | CommandOutcome::StateOk
+            | CommandOutcome::DiffRunsOk
+            | CommandOutcome::ConfigOk
+            | CommandOutcome::PlanOk
+            | CommandOutcome::InitOk
+            | CommandOutcome::DryRunOk
+            | CommandOutcome::DetachOk
+            | CommandOutcome::AttachOk
+            | CommandOutcome::StopOk
+            | CommandOutcome::BenchOk
+            | CommandOutcome::AgentOk,
+        ) => ExitCode::SUCCESS,
+        Ok(CommandOutcome::RunManyOk { exit_code }) => ExitCode::from(exit_code),
         Ok(CommandOutcome::RunOk { has_issues, .. }) => {
             if has_issues {
                 ExitCode::from(2)
@@ -30,7 +53,12 @@ fn main() -> ExitCode {
             }
         }
         Err(e) => {
-            error!("{e:#}");
+            // Config errors happen before logging is set up; print those directly.
+            if tracing::dispatcher::has_been_set() {
+                error!("{e:#}");
+            } else {
+                eprintln!("error: {e:#}");
+            }
             let exit_code = if e.downcast_ref::<peal::error::PealError>()
                 .is_some_and(|pe| matches!(pe, peal::error::PealError::ConsecutiveTaskFailuresCapReached { .. }))
             {
@@ -43,16 +71,48 @@ fn main() -> ExitCode {
     }
 }
 
-/// Result of a successful run: Prompt has no summary; Run carries outcome and has_issues for exit code.
+/// Result of a successful run: Prompt, State, DiffRuns, Config, Plan, Init, DryRun, Detach, Attach, Stop, Bench, and Agent have no summary; RunMany carries the combined exit code; Run carries outcome and has_issues for exit code.
 #[derive(Debug)]
 pub enum CommandOutcome {
     PromptOk,
+    StateOk,
+    DiffRunsOk,
+    ConfigOk,
+    PlanOk,
+    InitOk,
+    DryRunOk,
+    DetachOk,
+    AttachOk,
+    StopOk,
+    BenchOk,
+    AgentOk,
+    RunManyOk {
+        exit_code: u8,
+    },
     RunOk {
         outcome: runner::RunOutcome,
         has_issues: bool,
     },
 }
 
+/// `--state-dir` for commands that do not load the config: the flag, else `PEAL_STATE_DIR`,
+/// else `.peal`.
+fn state_dir_or_default(state_dir: Option<PathBuf>) -> PathBuf {
+    state_dir
+        .or_else(|| std::env::var_os("PEAL_STATE_DIR").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(".peal"))
+}
+
+/// Removes the detached-run pid file and any honored stop request when a run ends, however
+/// it ends (see [`peal::daemon::clear_run_files`]).
+struct RunFilesGuard(PathBuf);
+
+impl Drop for RunFilesGuard {
+    fn drop(&mut self) {
+        peal::daemon::clear_run_files(&self.0);
+    }
+}
+
 fn run(cli: Cli) -> anyhow::Result<CommandOutcome> {
     match cli.command {
         Commands::Prompt(args) => {
@@ -68,167 +128,401 @@ fn run(cli: Cli) -> anyhow::Result<CommandOutcome> {
             }
             Ok(CommandOutcome::PromptOk)
         }
+        Commands::State(args) => match args.command {
+            StateCommand::Reset(reset) => {
+                let state_dir = state_dir_or_default(reset.state_dir.clone());
+                let state_path = state::PealState::state_file_path(&state_dir);
+                let Some(mut peal_state) = state::load_state(&state_dir)? else {
+                    println!("no state at {}; nothing to reset", state_path.display());
+                    return Ok(CommandOutcome::StateOk);
+                };
+                let removed = match reset.from_task {
+                    Some(from) => peal_state.reset_tasks(|i| i >= from),
+                    None => peal_state.reset_tasks(|i| reset.tasks.contains(&i)),
+                };
+                if removed.is_empty() {
+                    println!("no matching completed tasks in {}", state_path.display());
+                } else {
+                    state::save_state(&peal_state, &state_dir)?;
+                    println!("reset task(s) {:?} in {}", removed, state_path.display());
+                }
+                Ok(CommandOutcome::StateOk)
+            }
+        },
+        Commands::Attach(args) => {
+            let state_dir = state_dir_or_default(args.state_dir);
+            if let Some(pid) = peal::daemon::running_pid(&state_dir) {
+                eprintln!("attached to detached run (pid {pid}); Ctrl-C detaches again");
+            }
+            peal::daemon::attach(&state_dir, &mut std::io::stdout(), Duration::from_millis(500))?;
+            Ok(CommandOutcome::AttachOk)
+        }
+        Commands::Stop(args) => {
+            let state_dir = state_dir_or_default(args.state_dir);
+            let pid = peal::daemon::request_stop(&state_dir)?;
+            println!("asked detached run (pid {pid}) to stop after its current task");
+            Ok(CommandOutcome::StopOk)
+        }
+        Commands::DiffRuns(args) => {
+            print!("{}", run_summary::diff_runs(&args.before, &args.after)?);
+            Ok(CommandOutcome::DiffRunsOk)
+        }
+        Commands::Config(args) => match args.command {
+            ConfigCommand::Show(show) => {
+                let shown = if show.diff_defaults {
+                    peal::config::ShowKeys::NonDefault
+                } else if show.resolved {
+                    peal::config::ShowKeys::All
+                } else {
+                    peal::config::ShowKeys::Set
+                };
+                print_config(&show.run, shown)?;
+                Ok(CommandOutcome::ConfigOk)
+            }
+        },
+        Commands::Plan(args) => match args.command {
+            PlanCommand::Graph(graph) => {
+                let parsed = plan::parse_plan_file(&graph.plan)?;
+                let format = plan_graph::GraphFormat::parse(&graph.format)
+                    .expect("clap restricts --format to mermaid or dot");
+                let rendered = plan_graph::render(&parsed, format);
+                match &graph.output {
+                    Some(path) => {
+                        if let Some(parent) = path.parent() {
+                            std::fs::create_dir_all(parent)?;
+                        }
+                        std::fs::write(path, rendered)?;
+                    }
+                    None => print!("{rendered}"),
+                }
+                Ok(CommandOutcome::PlanOk)
+            }
+            PlanCommand::Convert(convert) => {
+                let from = match &convert.from {
+                    Some(name) => plan_convert::PlanFormat::parse(name)
+                        .expect("clap restricts --from to md, yaml, or json"),
+                    None => plan_convert::PlanFormat::from_path(&convert.input),
+                };
+                let to = plan_convert::PlanFormat::parse(&convert.to)
+                    .expect("clap restricts --to to md, yaml, or json");
+                let content = std::fs::read_to_string(&convert.input)?;
+                let converted = plan_convert::convert(&content, from, to)?;
+                match &convert.output {
+                    Some(path) => {
+                        if let Some(parent) = path.parent() {
+                            std::fs::create_dir_all(parent)?;
+                        }
+                        std::fs::write(path, converted)?;
+                    }
+                    None => print!("{converted}"),
+                }
+                Ok(CommandOutcome::PlanOk)
+            }
+        },
+        Commands::Init(args) => {
+            let options = if args.yes {
+                peal::init::InitOptions {
+                    agent_cmd: args
+                        .agent_cmd
+                        .clone()
+                        .unwrap_or_else(|| peal::init::DEFAULT_AGENT_CMD.to_owned()),
+                    plan_path: args
+                        .plan
+                        .clone()
+                        .unwrap_or_else(|| PathBuf::from(peal::init::DEFAULT_PLAN_PATH)),
+                    example_plan: !args.no_example_plan,
+                }
+            } else {
+                peal::init::prompt_options(
+                    args.agent_cmd.clone(),
+                    args.plan.clone(),
+                    args.no_example_plan.then_some(false),
+                    &mut std::io::stdin().lock(),
+                    &mut std::io::stderr(),
+                )?
+            };
+            let report = peal::init::scaffold(&args.dir, &options, args.force)?;
+            for path in [&report.config, &report.gitignore, &report.plan].into_iter().flatten() {
+                println!("wrote {}", path.display());
+            }
+            println!("next: edit the plan, then run `peal run` from {}", args.dir.display());
+            Ok(CommandOutcome::InitOk)
+        }
+        Commands::Bench(bench) => {
+            let config_path = bench.run.config.clone().or_else(|| {
+                std::env::current_dir()
+                    .ok()
+                    .and_then(|cwd| peal::config::discover_config_path(&bench.run, &cwd))
+            });
+            let config = PealConfig::load(config_path.as_deref(), &bench.run)?;
+            config.validate()?;
+            let exe = std::env::current_exe()?;
+            let args: Vec<std::ffi::OsString> = std::env::args_os()
+                .skip_while(|a| a != "bench")
+                .skip(1)
+                .collect();
+            let report = peal::bench::run_bench(
+                &exe,
+                &config,
+                config_path.as_deref(),
+                &bench.models,
+                &args,
+                bench.keep_worktrees,
+            )?;
+            print!("{report}");
+            let path = peal::bench::write_report(&config.state_dir, &report)?;
+            println!("\nreport: {}", path.display());
+            Ok(CommandOutcome::BenchOk)
+        }
+        Commands::RunMany(many) => {
+            let config_path = many.run.config.clone().or_else(|| {
+                std::env::current_dir()
+                    .ok()
+                    .and_then(|cwd| peal::config::discover_config_path(&many.run, &cwd))
+            });
+            let config = PealConfig::load(config_path.as_deref(), &many.run)?;
+            let exe = std::env::current_exe()?;
+            let args: Vec<std::ffi::OsString> = std::env::args_os()
+                .skip_while(|a| a != "run-many")
+                .skip(1)
+                .collect();
+            let max_concurrent = many.max_concurrent_plans.map_or(many.plans.len(), |n| n as usize);
+            let report = peal::run_many::run_many(
+                &exe,
+                &config.state_dir,
+                &many.plans,
+                &many.repos,
+                max_concurrent,
+                &args,
+            )?;
+            print!("{report}");
+            let path = peal::run_many::write_report(&config.state_dir, &report)?;
+            println!("\nsummary: {}", path.display());
+            Ok(CommandOutcome::RunManyOk { exit_code: report.exit_code() })
+        }
+        Commands::Agent(agent) => match agent.command {
+            AgentCommand::Install(args) => {
+                let dir = args
+                    .dir
+                    .clone()
+                    .or_else(peal::agent_install::default_install_dir)
+                    .ok_or_else(|| anyhow::anyhow!("no home directory; pass --dir"))?;
+                let options = peal::agent_install::InstallOptions {
+                    version: args.version.clone(),
+                    sha256: args.sha256.clone(),
+                    url: args.url.clone(),
+                    dir,
+                };
+                let installed = peal::agent_install::install(&options)?;
+                if installed.downloaded {
+                    println!("installed {}", installed.binary.display());
+                } else {
+                    println!("already installed: {}", installed.binary.display());
+                }
+                if !args.no_config {
+                    peal::agent_install::set_agent_cmd(&args.config, &installed.binary)?;
+                    println!("set agent_cmd in {}", args.config.display());
+                }
+                Ok(CommandOutcome::AgentOk)
+            }
+        },
         Commands::Run(args) => {
-            let config_path = args.config.clone();
+            if args.print_config {
+                print_config(&args, peal::config::ShowKeys::All)?;
+                return Ok(CommandOutcome::ConfigOk);
+            }
+            let discovered_config = std::env::current_dir()
+                .ok()
+                .and_then(|cwd| peal::config::discover_config_path(&args, &cwd));
+            let config_path = args.config.clone().or_else(|| discovered_config.clone());
             let config = PealConfig::load(config_path.as_deref(), &args)?;
 
-            peal::logging::init(config.log_level.as_deref(), config.log_file.as_deref())?;
-
-            config.validate()?;
+            if args.detach {
+                config.validate()?;
+                let exe = std::env::current_exe().map_err(|e| {
+                    peal::error::PealError::DetachFailed { detail: format!("cannot locate peal binary: {e}") }
+                })?;
+                let child_args: Vec<std::ffi::OsString> =
+                    std::env::args_os().skip(1).filter(|a| a != "--detach").collect();
+                let pid = peal::daemon::spawn_detached(&exe, &child_args, &config.state_dir)?;
+                println!(
+                    "peal running in the background (pid {pid}); log: {}\nfollow with `peal attach`, stop with `peal stop` (add --state-dir {} if not the default)",
+                    peal::daemon::log_file_path(&config.state_dir).display(),
+                    config.state_dir.display()
+                );
+                return Ok(CommandOutcome::DetachOk);
+            }
 
-            let agent_path = cursor::resolve_agent_cmd(&config.agent_cmd)?;
+            let status_hub = config
+                .serve_status
+                .as_ref()
+                .map(|_| peal::status_server::StatusHub::new(config.state_dir.clone()));
+            peal::logging::init(
+                config.log_level.as_deref(),
+                config.log_file.as_deref(),
+                config.log_max_size_mb,
+                config.log_keep_files,
+                config.otel_endpoint.as_deref(),
+                status_hub.as_ref().map(|hub| hub.layer()),
+            )?;
+
+            // Root span of the run; task, phase, and stet spans nest under it (OTLP export).
+            // Its run_id field tags every event of the run.
+            let _run_span = tracing::debug_span!(
+                "run",
+                run_id = peal::run_id::current(),
+                plan = %config.plan_path.display(),
+                repo = %config.repo_path.display()
+            )
+            .entered();
+            info!(run_id = peal::run_id::current(), "run started");
+            peal::crash::arm(&config);
+
+            if let Some(p) = &discovered_config {
+                info!(config_path = %p.display(), "using auto-detected config file (disable with --no-config)");
+            }
+            let sidecar = peal::config::plan_sidecar_path(&config.plan_path);
+            if !args.no_config && sidecar.is_file() {
+                info!(sidecar = %sidecar.display(), "applying per-plan config overrides");
+            }
 
-            let stet_path = stet::resolve_stet(config.stet_path.as_deref());
-            match &stet_path {
-                Some(p) => info!(stet_path = %p.display(), "stet found, phase 3 enabled"),
-                None => info!("stet not found, phase 3 will be skipped"),
+            config.validate()?;
+            for warning in config.warnings() {
+                warn!("{warning}");
+            }
+            peal::daemon::clear_stop_request(&config.state_dir);
+            let _run_files = RunFilesGuard(config.state_dir.clone());
+
+            if let (Some(addr), Some(hub)) = (&config.serve_status, &status_hub) {
+                let parsed = addr.parse().map_err(|_| {
+                    peal::error::PealError::InvalidServeStatusAddr { value: addr.clone() }
+                })?;
+                let bound = peal::status_server::serve(parsed, std::sync::Arc::clone(hub))
+                    .map_err(|e| peal::error::PealError::StatusServerBindFailed {
+                        addr: addr.clone(),
+                        detail: e.to_string(),
+                    })?;
+                info!(addr = %bound, "serving run status at /status and /events");
             }
 
-            let phase3_mode: Option<stet::StetPhase3Mode> = if !config.stet_commands.is_empty() {
-                info!(count = config.stet_commands.len(), "running custom stet_commands at session start");
-                let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
-                let mut session_ok = true;
-                for (i, cmd) in config.stet_commands.iter().enumerate() {
-                    let cmd = cmd.trim();
-                    if cmd.is_empty() {
-                        continue;
-                    }
-                    info!(index = i + 1, command = %cmd, "running stet_commands entry");
-                    let result = match peal::subprocess::run_command_string(cmd, &config.repo_path, timeout) {
-                        None => continue,
-                        Some(Ok(r)) => r,
-                        Some(Err(e)) => {
-                            let err = peal::error::PealError::StetStartFailed {
-                                detail: format!("spawn failed: {e}"),
-                            };
-                            if config.on_stet_fail == "retry_once" {
-                                warn!(err = %e, "custom stet command spawn failed, retrying once");
-                                match peal::subprocess::run_command_string(cmd, &config.repo_path, timeout) {
-                                    Some(Ok(r)) => r,
-                                    _ => return Err(err.into()),
-                                }
-                            } else if config.on_stet_fail == "skip" {
-                                warn!(err = %e, "custom stet command failed; stet phase skipped for this run");
-                                session_ok = false;
-                                continue;
-                            } else {
-                                return Err(err.into());
-                            }
-                        }
-                    };
-                    if result.timed_out {
-                        let err = peal::error::PealError::StetStartFailed {
-                            detail: "custom stet command timed out".to_owned(),
-                        };
-                        if config.on_stet_fail == "retry_once" {
-                            warn!("custom stet command timed out, retrying once");
-                            if let Some(Ok(r)) =
-                                peal::subprocess::run_command_string(cmd, &config.repo_path, timeout)
-                            {
-                                if r.timed_out {
-                                    return Err(peal::error::PealError::StetStartFailed {
-                                        detail: "custom stet command timed out (retry)".to_owned(),
-                                    }
-                                    .into());
-                                }
-                            } else {
-                                return Err(err.into());
-                            }
-                        } else if config.on_stet_fail == "skip" {
-                            warn!("custom stet command timed out; stet phase skipped for this run");
-                            session_ok = false;
-                            continue;
-                        } else {
-                            return Err(err.into());
-                        }
-                    } else if !result.success() && config.on_stet_fail == "fail" {
-                        return Err(peal::error::PealError::StetStartFailed {
-                            detail: format!("exit code {:?}: {}", result.exit_code, result.stderr.trim()),
-                        }.into());
-                    } else if !result.success() && config.on_stet_fail == "skip" {
-                        warn!(exit_code = ?result.exit_code, "custom stet command failed; stet phase skipped for this run");
-                        session_ok = false;
-                        continue;
-                    } else if !result.success() && config.on_stet_fail == "retry_once" {
-                        warn!(exit_code = ?result.exit_code, "custom stet command failed, retrying once");
-                        if let Some(Ok(r)) =
-                            peal::subprocess::run_command_string(cmd, &config.repo_path, timeout)
-                        {
-                            if !r.success() {
-                                return Err(peal::error::PealError::StetStartFailed {
-                                    detail: format!("exit code {:?}: {}", r.exit_code, r.stderr.trim()),
-                                }
-                                .into());
-                            }
-                        } else {
-                            return Err(peal::error::PealError::StetStartFailed {
-                                detail: "custom stet command failed on retry".to_owned(),
-                            }
-                            .into());
-                        }
+            let plan_content = std::fs::read_to_string(&config.plan_path).map_err(|e| {
+                let peal_err = if e.kind() == std::io::ErrorKind::NotFound {
+                    peal::error::PealError::PlanFileNotFound {
+                        path: config.plan_path.clone(),
                     }
-                }
-                if session_ok {
-                    Some(stet::StetPhase3Mode::CustomCommands(config.stet_commands.clone()))
                 } else {
-                    None
-                }
-            } else if let Some(ref sp) = stet_path {
-                info!("starting stet session");
-                let start_result = match config.on_stet_fail.as_str() {
-                    "retry_once" => {
-                        match stet::start_session(
-                            sp,
-                            config.stet_start_ref.as_deref(),
-                            &config.stet_start_extra_args,
-                            &config.repo_path,
-                            Some(Duration::from_secs(config.phase_timeout_sec)),
-                        ) {
-                            Ok(_) => Ok(Some(sp.clone())),
-                            Err(e) => {
-                                warn!(err = %e, "stet start failed, retrying once");
-                                stet::start_session(
-                                    sp,
-                                    config.stet_start_ref.as_deref(),
-                                    &config.stet_start_extra_args,
-                                    &config.repo_path,
-                                    Some(Duration::from_secs(config.phase_timeout_sec)),
-                                )
-                                .map(|_| Some(sp.clone()))
-                            }
-                        }
+                    peal::error::PealError::InvalidPlanFile {
+                        path: config.plan_path.clone(),
                     }
-                    "skip" => stet::start_session(
-                        sp,
-                        config.stet_start_ref.as_deref(),
-                        &config.stet_start_extra_args,
-                        &config.repo_path,
-                        Some(Duration::from_secs(config.phase_timeout_sec)),
-                    )
-                    .map(|_| Some(sp.clone()))
-                    .or_else(|e| {
-                        warn!(err = %e, "stet start failed; stet phase skipped for this run");
-                        Ok(None)
-                    }),
-                    _ => stet::start_session(
-                        sp,
-                        config.stet_start_ref.as_deref(),
-                        &config.stet_start_extra_args,
-                        &config.repo_path,
-                        Some(Duration::from_secs(config.phase_timeout_sec)),
-                    ).map(|_| Some(sp.clone())),
                 };
-                match start_result {
-                    Ok(Some(path)) => {
-                        info!("stet session started");
-                        Some(stet::StetPhase3Mode::BuiltIn(path))
+                anyhow::anyhow!(peal_err)
+            })?;
+
+            // Duplicate `## Task N` indices: renumber on request, otherwise fail with file line numbers.
+            let plan_content = if args.renumber {
+                let (renumbered, changed) = plan::renumber_tasks(&plan_content);
+                if changed && args.write {
+                    std::fs::write(&config.plan_path, &renumbered).map_err(|e| {
+                        anyhow::anyhow!(peal::error::PealError::InvalidPlanFile {
+                            path: config.plan_path.clone(),
+                        })
+                        .context(e)
+                    })?;
+                    info!(plan = %config.plan_path.display(), "renumbered tasks written back to plan file");
+                } else if changed {
+                    info!("tasks renumbered sequentially (plan file unchanged; add --write to persist)");
+                }
+                renumbered
+            } else {
+                plan::check_duplicate_task_indices(&plan_content)?;
+                plan_content
+            };
+
+            // Plan front matter supplies per-plan defaults below file/env/CLI.
+            let (plan_metadata, plan_body) = plan::split_front_matter(&plan_content)?;
+            let mut config = if plan_metadata.is_empty() {
+                config
+            } else {
+                info!(title = plan_metadata.title.as_deref().unwrap_or(""), "plan front matter found");
+                let config = PealConfig::load_with_plan_metadata(config_path.as_deref(), &args, &plan_metadata)?;
+                peal::crash::arm(&config);
+                config
+            };
+
+            if !config.vars.is_empty() {
+                info!(count = config.vars.len(), "expanding plan variables");
+            }
+            let plan_body = plan::expand_vars(&plan_body, &config.vars);
+
+            let agent_path = peal::container::agent_path(&config, &config.agent_cmd)?;
+            if let Some(image) = &config.execution.container_image {
+                info!(
+                    image,
+                    runtime = config.execution.runtime(),
+                    stet_in_container = config.execution.stet_in_container,
+                    "agent and verify commands run in a container"
+                );
+            }
+            peal::container::route_stet(&config);
+            if let Some(cmd) = &config.triage_agent_cmd {
+                let triage_path = peal::container::agent_path(&config, cmd)?;
+                info!(triage_agent = %triage_path.display(), "separate triage agent configured");
+            }
+            if config.agent_health_check && !args.dry_run {
+                phase::run_health_check(&agent_path, &config)?;
+            }
+            if config.uses_api_backend() && !args.dry_run {
+                let key = std::env::var(&config.api_key_env).ok();
+                if key.is_none_or(|k| k.trim().is_empty()) {
+                    return Err(peal::error::PealError::ApiKeyMissing {
+                        var: config.api_key_env.clone(),
                     }
-                    Ok(None) => None,
-                    Err(e) => return Err(e.into()),
+                    .into());
                 }
+                info!(
+                    api_base_url = %config.api_base_url,
+                    model = config.api_model().unwrap_or(""),
+                    "api backend for phase 1, normalization, triage, and summaries"
+                );
+            }
+
+            let stet_path = if config.enable_phase3 && config.execution.stet_in_container {
+                Some(config.stet_path.clone().unwrap_or_else(|| "stet".into()))
+            } else if config.enable_phase3 {
+                stet::resolve_stet(config.stet_path.as_deref())
             } else {
                 None
             };
+            match &stet_path {
+                _ if !config.enable_phase3 => {
+                    info!("phase 3 disabled (enable_phase3 = false or --no-phase3), stet review skipped for this run")
+                }
+                Some(p) => info!(stet_path = %p.display(), "stet found, phase 3 enabled"),
+                None => info!("stet not found, phase 3 will be skipped"),
+            }
+            // A templated baseline follows the branch point; resolve it once so every
+            // `stet start` of the run (and `{{START_REF}}`) reviews against the same commit.
+            if let Some(template) = config.stet_start_ref.clone().filter(|r| stet::start_ref_is_template(r))
+                && config.enable_phase3
+                && (stet_path.is_some() || !config.stet_commands.is_empty())
+            {
+                let resolved = stet::resolve_start_ref(&template, &config.repo_path)?;
+                info!(template, stet_start_ref = %resolved, "stet_start_ref resolved");
+                config.stet_start_ref = Some(resolved);
+            }
+
+            // Run manifest: `--locked` checks it before any task runs, otherwise it is rewritten.
+            if args.locked || config.lock_path.is_some() {
+                let lock_path = config.lock_path.as_ref().ok_or(peal::error::PealError::LockedWithoutLockPath)?;
+                let manifest = peal::lock::RunManifest::resolve(&config, &agent_path, stet_path.as_deref(), &plan_body);
+                if args.locked {
+                    peal::lock::check(lock_path, &manifest)?;
+                    info!(lock_path = %lock_path.display(), "run matches lock file");
+                } else if !args.dry_run {
+                    peal::lock::write(lock_path, &manifest)?;
+                    info!(lock_path = %lock_path.display(), "run manifest written");
+                }
+            }
 
             info!(
                 plan = %config.plan_path.display(),
@@ -241,42 +535,36 @@ fn run(cli: Cli) -> anyhow::Result<CommandOutcome> {
                 "config loaded"
             );
 
-            let plan_content = std::fs::read_to_string(&config.plan_path).map_err(|e| {
-                let peal_err = if e.kind() == std::io::ErrorKind::NotFound {
-                    peal::error::PealError::PlanFileNotFound {
-                        path: config.plan_path.clone(),
-                    }
-                } else {
-                    peal::error::PealError::InvalidPlanFile {
-                        path: config.plan_path.clone(),
-                    }
-                };
-                anyhow::anyhow!(peal_err)
-            })?;
-
-            let normalize_enabled = config.normalize_plan || args.normalize;
+            // Normalizing calls the agent, so a dry run needs a canonical plan.
+            let normalize_enabled = (config.normalize_plan || args.normalize) && !args.dry_run;
+            let mut normalization_retries: u32 = 0;
 
-            let parsed = if plan::is_canonical_plan_format(&plan_content) {
-                plan::parse_plan(&plan_content)?
+            let mut parsed = if plan::is_canonical_plan_format(&plan_body) {
+                plan::parse_plan(&plan_body)?
             } else if normalize_enabled {
                 info!("plan format not canonical, normalizing via agent");
                 let mut parsed_plan = None;
+                // A retry tells the agent why its previous output was rejected.
+                let mut rejected: Option<String> = None;
                 let attempts = 1 + config.normalize_retry_count;
                 for attempt in 0..attempts {
-                    let normalized = plan::normalize_via_agent(&plan_content, &agent_path, &config)
-                        .map_err(anyhow::Error::from)?;
+                    let normalized =
+                        plan::normalize_via_agent(&plan_body, &agent_path, &config, rejected.as_deref())
+                            .map_err(anyhow::Error::from)?;
                     match plan::parse_plan_or_fail_with_snippet(&normalized) {
                         Ok(p) => {
-                            parsed_plan = Some(p);
+                            parsed_plan = Some((p, normalized));
                             break;
                         }
                         Err(peal::error::PealError::NormalizationParseFailed { snippet }) => {
                             if attempt + 1 < attempts {
+                                normalization_retries += 1;
                                 warn!(
                                     attempt = attempt + 1,
                                     retries_left = attempts - attempt - 1,
                                     "normalized output did not parse, retrying normalization"
                                 );
+                                rejected = Some(normalized);
                             } else {
                                 return Err(peal::error::PealError::NormalizationParseFailed {
                                     snippet,
@@ -287,23 +575,81 @@ fn run(cli: Cli) -> anyhow::Result<CommandOutcome> {
                         Err(e) => return Err(e.into()),
                     }
                 }
-                parsed_plan.expect("normalize loop exits with Some(parsed) or return Err")
+                let (parsed_plan, normalized) =
+                    parsed_plan.expect("normalize loop exits with Some(parsed) or return Err");
+
+                // Keep the normalized plan next to the source for traceability (and review).
+                let normalized_path = plan::normalized_plan_path(&config.plan_path);
+                match std::fs::write(&normalized_path, &normalized) {
+                    Ok(()) => info!(path = %normalized_path.display(), "normalized plan saved"),
+                    Err(e) if args.review_normalized => {
+                        return Err(peal::error::PealError::NormalizedPlanReviewFailed {
+                            path: normalized_path,
+                            detail: e.to_string(),
+                        }
+                        .into());
+                    }
+                    Err(e) => warn!(
+                        path = %normalized_path.display(),
+                        err = %e,
+                        "failed to save normalized plan"
+                    ),
+                }
+
+                if args.review_normalized {
+                    let editor = ["VISUAL", "EDITOR"]
+                        .iter()
+                        .find_map(|k| std::env::var(k).ok().filter(|v| !v.trim().is_empty()));
+                    info!(path = %normalized_path.display(), editor = ?editor, "reviewing normalized plan");
+                    let reviewed = plan::review_normalized_plan(
+                        &normalized_path,
+                        editor.as_deref(),
+                        &mut std::io::stdin().lock(),
+                        &mut std::io::stderr(),
+                    )?;
+                    if reviewed == normalized {
+                        parsed_plan
+                    } else {
+                        info!("normalized plan edited during review; re-parsing");
+                        plan::parse_plan_or_fail_with_snippet(&reviewed)?
+                    }
+                } else {
+                    parsed_plan
+                }
             } else {
-                plan::parse_plan(&plan_content)?
+                plan::parse_plan(&plan_body)?
             };
+            parsed.metadata = plan_metadata;
 
-            let parsed = match (args.task, args.from_task) {
-                (Some(idx), None) => {
+            let parsed = match (args.tasks.as_slice(), args.from_task) {
+                ([idx], None) => {
                     info!(task_index = idx, "filtering plan to single task");
-                    parsed.filter_single_task(idx)?
+                    parsed.filter_single_task(*idx)?
                 }
-                (None, Some(idx)) => {
+                ([], Some(idx)) => {
                     info!(from_task = idx, "filtering plan from task onward");
                     parsed.filter_from_task(idx)?
                 }
-                (None, None) => parsed,
+                ([], None) => parsed,
+                (indices, None) => {
+                    info!(tasks = ?indices, "filtering plan to tasks");
+                    parsed.filter_tasks(indices)?
+                }
                 _ => unreachable!("clap prevents both --task and --from-task"),
             };
+            let parsed = if args.tags.is_empty() && args.exclude_tags.is_empty() {
+                parsed
+            } else {
+                info!(tags = ?args.tags, exclude_tags = ?args.exclude_tags, "filtering plan by tags");
+                parsed.filter_by_tags(&args.tags, &args.exclude_tags)?
+            };
+            let parsed = match &args.stage {
+                Some(stage) => {
+                    info!(stage = %stage, "filtering plan to one stage");
+                    parsed.filter_by_stage(stage)?
+                }
+                None => parsed,
+            };
 
             if parsed.tasks.is_empty() {
                 return Err(peal::error::PealError::InvalidPlanFile {
@@ -318,12 +664,18 @@ fn run(cli: Cli) -> anyhow::Result<CommandOutcome> {
                 "plan parsed"
             );
 
-            let mut peal_state = match state::load_state(&config.state_dir)? {
-                Some(s) if s.matches_context(&config.plan_path, &config.repo_path) => {
+            let loaded_state = if config.strict_state {
+                state::load_state_strict(&config.state_dir)?
+            } else {
+                state::load_state(&config.state_dir)?
+            };
+            let mut peal_state = match loaded_state {
+                Some(mut s) if s.matches_context(&config.plan_path, &config.repo_path) => {
                     info!(
                         completed = s.completed_task_indices.len(),
                         "resumed from existing state"
                     );
+                    s.record_canonical_paths();
                     s
                 }
                 Some(_) => {
@@ -336,6 +688,90 @@ fn run(cli: Cli) -> anyhow::Result<CommandOutcome> {
                 None => state::PealState::new(config.plan_path.clone(), config.repo_path.clone()),
             };
 
+            let changed = peal_state.changed_completed_tasks(&parsed.tasks);
+            if !changed.is_empty() {
+                eprintln!(
+                    "warning: completed task(s) {:?} changed since they ran (plan renumbered?); on_renumber = \"{}\"",
+                    changed, config.on_renumber
+                );
+                warn!(tasks = ?changed, on_renumber = %config.on_renumber, "completed task content changed since it ran");
+                if config.on_renumber == "rerun" {
+                    peal_state.reset_tasks(|i| changed.contains(&i));
+                    info!(tasks = ?changed, "marked changed tasks incomplete; they will run again");
+                }
+            }
+
+            if args.force_rerun {
+                let selected: Vec<u32> = parsed.tasks.iter().map(|t| t.index).collect();
+                let reset = peal_state.reset_tasks(|i| selected.contains(&i));
+                info!(tasks = ?reset, "--force-rerun: marked selected tasks incomplete");
+                if !reset.is_empty() && !args.dry_run {
+                    state::save_state(&peal_state, &config.state_dir)?;
+                }
+            }
+
+            // Trial run: keep only the first N pending tasks; the rest stay pending in state.
+            let pending_total = parsed
+                .tasks
+                .iter()
+                .filter(|t| !peal_state.is_task_completed(t.index))
+                .count();
+            let (parsed, partial_run) = match args.limit_tasks {
+                Some(limit) if (limit as usize) < pending_total => {
+                    let selected = parsed.first_pending(limit as usize, |i| peal_state.is_task_completed(i));
+                    info!(limit, tasks = ?selected, pending = pending_total, "--limit-tasks: partial run");
+                    (parsed.subset(&selected), true)
+                }
+                _ => (parsed, false),
+            };
+
+            if args.dry_run {
+                let estimate = peal::estimate::estimate_run(
+                    &parsed,
+                    &config,
+                    &peal_state.completed_task_indices,
+                    stet_path.is_some(),
+                );
+                print!("{}", estimate.render());
+                return Ok(CommandOutcome::DryRunOk);
+            }
+
+            // From here on a SIGTERM (e.g. a CI job timeout) still leaves state and a summary.
+            peal::shutdown::arm(&config, &parsed.metadata, &peal_state.completed_task_indices);
+            peal::clock::start_heartbeat();
+
+            if args.cleanup_stet {
+                cleanup_stet_session(&config, stet_path.as_ref(), &mut peal_state)?;
+            } else if let Some(failure) = &peal_state.stet_finish_failure {
+                eprintln!(
+                    "warning: stet finish failed in an earlier run ({}) and may have left its session open; rerun with --cleanup-stet to finish it before starting a new one",
+                    failure.detail
+                );
+                warn!(detail = %failure.detail, failed_at = %failure.failed_at, "stet finish failed in an earlier run");
+            }
+
+            peal::run_id::prune_runs(&config.state_dir, &config.artifact_retention);
+            peal_state.last_run_id = Some(peal::run_id::current().to_owned());
+            peal_state.eta = None;
+            if normalization_retries > 0 {
+                peal_state.normalization_retries += normalization_retries;
+                if let Err(e) = state::save_state(&peal_state, &config.state_dir) {
+                    warn!(%e, "failed to record normalization retries in state");
+                }
+            }
+
+            if config.parallel {
+                for overlap in parsed.parallel_path_overlaps() {
+                    let tasks: Vec<String> =
+                        overlap.task_indices.iter().map(|&idx| parsed.describe_task(idx)).collect();
+                    warn!(
+                        path = %overlap.path,
+                        tasks = %tasks.join(", "),
+                        "tasks in the same parallel block mention the same path; concurrent edits may conflict"
+                    );
+                }
+            }
+
             if !peal_state.completed_task_indices.is_empty() {
                 let completed: Vec<u32> = peal_state.completed_task_indices.clone();
                 let first_incomplete = parsed
@@ -351,101 +787,100 @@ fn run(cli: Cli) -> anyhow::Result<CommandOutcome> {
                 );
             }
 
-            let finish_path = phase3_mode.as_ref().and_then(|m| match m {
-                stet::StetPhase3Mode::BuiltIn(p) => Some(p.clone()),
-                stet::StetPhase3Mode::CustomCommands(_) => None,
-            });
-
-            let run_result = runner::run_scheduled(
-                &agent_path,
-                &config,
-                &parsed,
-                &mut peal_state,
-                &config.state_dir,
-                phase3_mode,
-            );
-
-            if let Some(ref sp) = finish_path {
-                let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
-                match stet::finish_session(sp, &config.repo_path, timeout) {
-                    Ok(out) => info!(
-                        stdout_len = out.stdout.len(),
-                        stderr_len = out.stderr.len(),
-                        "stet finish succeeded"
-                    ),
-                    Err(e) => warn!(%e, "stet finish failed (best-effort)"),
+            // Long plans run as several stet sessions of about `session_size` tasks each.
+            let sessions: Vec<Vec<u32>> = match config.session_size {
+                Some(size) if size > 0 && args.only_phase.is_none() => parsed.sessions(size as usize),
+                _ => vec![parsed.tasks.iter().map(|t| t.index).collect()],
+            };
+            if sessions.len() > 1 {
+                info!(session_count = sessions.len(), session_size = ?config.session_size, "plan split into sessions");
+            }
+            let recorded_sessions = if sessions.len() > 1 { sessions.clone() } else { Vec::new() };
+            if peal_state.sessions != recorded_sessions {
+                peal_state.sessions = recorded_sessions;
+                if let Err(e) = state::save_state(&peal_state, &config.state_dir) {
+                    warn!(%e, "failed to record session boundaries in state");
                 }
             }
 
-            let outcome = run_result?;
-            let results = &outcome.results;
+            // Custom `stet_commands` manage their own session; only built-in stet is recorded.
+            let stet_session_managed =
+                config.enable_phase3 && config.stet_commands.is_empty() && stet_path.is_some();
+            let mut outcome: Option<runner::RunOutcome> = None;
+            for (i, session) in sessions.iter().enumerate() {
+                let split = sessions.len() > 1;
+                // The last session always runs, so follow-up tasks still get their turn.
+                if split
+                    && i + 1 < sessions.len()
+                    && session.iter().all(|idx| peal_state.is_task_completed(*idx))
+                {
+                    info!(session = i + 1, "skipping fully-completed session");
+                    continue;
+                }
+                let session_plan;
+                let run_plan = if split {
+                    info!(session = i + 1, session_count = sessions.len(), tasks = ?session, "starting session");
+                    session_plan = parsed.subset(session);
+                    &session_plan
+                } else {
+                    &parsed
+                };
 
-            if !config.post_run_commands.is_empty() {
-                let timeout = config
-                    .post_run_timeout_sec
-                    .map(Duration::from_secs)
-                    .unwrap_or_else(|| Duration::from_secs(config.phase_timeout_sec));
-                info!(
-                    count = config.post_run_commands.len(),
-                    "running post-run command(s)"
-                );
-                for cmd in &config.post_run_commands {
-                    let cmd = cmd.trim();
-                    if cmd.is_empty() {
-                        continue;
+                let (phase3_mode, stet_session) = start_phase3_session(&config, stet_path.as_ref())?;
+                if stet_session_managed && peal_state.stet_session != stet_session {
+                    peal_state.stet_session = stet_session;
+                    if let Err(e) = state::save_state(&peal_state, &config.state_dir) {
+                        warn!(%e, "failed to record stet session in state");
                     }
-                    info!(command = %cmd, "running post-run command");
-                    match peal::subprocess::run_command_string(cmd, &config.repo_path, Some(timeout))
-                    {
-                        None => {}
-                        Some(Ok(result)) => {
-                            const TRUNCATE_BYTES: usize = 2048;
-                            let truncate = |s: &str, max: usize| -> String {
-                                if s.len() <= max {
-                                    s.to_string()
-                                } else {
-                                    let mut end = max.min(s.len());
-                                    while end > 0 && !s.is_char_boundary(end) {
-                                        end -= 1;
-                                    }
-                                    s[..end].to_string()
-                                }
-                            };
-                            if result.success() {
-                                info!(
-                                    stdout_len = result.stdout.len(),
-                                    stderr_len = result.stderr.len(),
-                                    exit_code = ?result.exit_code,
-                                    "post-run command succeeded"
-                                );
-                                if !result.stdout.is_empty() {
-                                    info!(stdout = %truncate(result.stdout.trim(), TRUNCATE_BYTES), "post-run stdout");
-                                }
-                                if !result.stderr.is_empty() {
-                                    info!(stderr = %truncate(result.stderr.trim(), TRUNCATE_BYTES), "post-run stderr");
-                                }
-                            } else {
-                                warn!(
-                                    stdout_len = result.stdout.len(),
-                                    stderr_len = result.stderr.len(),
-                                    exit_code = ?result.exit_code,
-                                    timed_out = result.timed_out,
-                                    "post-run command failed (best-effort)"
-                                );
-                                if !result.stdout.is_empty() {
-                                    warn!(stdout = %truncate(result.stdout.trim(), TRUNCATE_BYTES), "post-run stdout");
-                                }
-                                if !result.stderr.is_empty() {
-                                    warn!(stderr = %truncate(result.stderr.trim(), TRUNCATE_BYTES), "post-run stderr");
-                                }
-                            }
-                        }
-                        Some(Err(e)) => {
-                            warn!(error = %e, "post-run command spawn failed (best-effort)");
-                        }
+                }
+
+                let custom_session = matches!(phase3_mode, Some(stet::StetPhase3Mode::CustomCommands(_)));
+                let finish_path = phase3_mode.as_ref().and_then(|m| match m {
+                    stet::StetPhase3Mode::BuiltIn(p) => Some(p.clone()),
+                    stet::StetPhase3Mode::CustomCommands(_) => None,
+                });
+
+                let run_result = match args.only_phase {
+                    Some(phase) => {
+                        info!(only_phase = phase, "running single phase only");
+                        runner::run_only_phase(
+                            &agent_path,
+                            &config,
+                            run_plan,
+                            &mut peal_state,
+                            &config.state_dir,
+                            phase3_mode,
+                            phase,
+                        )
                     }
+                    None => runner::run_scheduled(
+                        &agent_path,
+                        &config,
+                        run_plan,
+                        &mut peal_state,
+                        &config.state_dir,
+                        phase3_mode,
+                    ),
+                };
+
+                if let Some(ref sp) = finish_path {
+                    finish_phase3_session(&config, sp, &mut peal_state);
+                } else if custom_session {
+                    finish_custom_session(&config);
+                }
+
+                let session_outcome = run_result?;
+                run_post_run_commands(&config);
+                match outcome.as_mut() {
+                    Some(o) => o.merge(session_outcome),
+                    None => outcome = Some(session_outcome),
+                }
+                if outcome.as_ref().is_some_and(|o| o.stopped) {
+                    break;
                 }
             }
+            let outcome = outcome.expect("the last session runs unless a stop request ended the run first");
+            let results = &outcome.results;
 
             for r in results {
                 info!(
@@ -458,15 +893,50 @@ fn run(cli: Cli) -> anyhow::Result<CommandOutcome> {
                 );
             }
 
-            let has_issues = !outcome.failed_task_indices.is_empty()
+            let has_issues = outcome.stopped
+                || !outcome.failed_task_indices.is_empty()
                 || results
                     .iter()
-                    .any(|r| r.phase3_outcome.as_ref().map_or(false, |o| !o.findings_resolved));
+                    .any(|r| r.phase3_outcome.as_ref().is_some_and(|o| !o.findings_resolved));
 
             let exit_code = if has_issues { 2 } else { 0 };
-            let summary = run_summary::build_summary(&outcome, &config, exit_code);
+            peal::shutdown::disarm();
+            let mut summary =
+                run_summary::build_summary(&outcome, &config, &parsed.metadata, exit_code);
+            summary.normalization_retries = normalization_retries;
+            summary.partial_run = partial_run;
+            summary.stet_finish_failed = peal_state
+                .stet_finish_failure
+                .as_ref()
+                .filter(|f| f.run_id.as_deref() == Some(peal::run_id::current()))
+                .map(|f| f.detail.clone());
+            summary.stages = run_summary::stage_summaries(&parsed, &summary);
+            if !summary.failures.is_empty() {
+                // The arguments after `run`; a run started some other way (tests) has none.
+                let run_args: Vec<String> =
+                    std::env::args_os().map(|arg| arg.to_string_lossy().into_owned()).skip_while(|arg| arg != "run").skip(1).collect();
+                summary.rerun_command = Some(run_summary::rerun_command(&run_args, &summary.tasks_failed));
+            }
             let summary_path = run_summary::summary_path(&config);
             run_summary::write_run_summary(&summary, &summary_path);
+            run_summary::write_run_summary(
+                &summary,
+                &peal::run_id::run_dir(&config.state_dir).join("run_summary.json"),
+            );
+
+            for jump in &summary.clock_jumps {
+                eprintln!("warning: {}", jump.describe());
+            }
+            if let Some(rerun) = &summary.rerun_command {
+                eprint!("{}", run_summary::failure_digest(&summary.failures, rerun));
+            }
+            if partial_run {
+                eprintln!(
+                    "partial run: --limit-tasks {} of {} pending task(s); rerun without --limit-tasks to continue",
+                    args.limit_tasks.unwrap_or_default(),
+                    pending_total
+                );
+            }
 
             Ok(CommandOutcome::RunOk {
                 outcome,
@@ -476,6 +946,345 @@ fn run(cli: Cli) -> anyhow::Result<CommandOutcome> {
     }
 }
 
+/// Print the config a `peal run` with `run_args` would use (`peal config show`,
+/// `peal run --print-config`). Nothing is validated; the plan only has to exist for its front
+/// matter to count.
+fn print_config(run_args: &peal::cli::RunArgs, shown: peal::config::ShowKeys) -> anyhow::Result<()> {
+    let config_path = run_args.config.clone().or_else(|| {
+        std::env::current_dir()
+            .ok()
+            .and_then(|cwd| peal::config::discover_config_path(run_args, &cwd))
+    });
+    // Plan front matter is a layer too; a plan that cannot be read just has none.
+    let config = PealConfig::load(config_path.as_deref(), run_args)?;
+    let metadata = std::fs::read_to_string(&config.plan_path)
+        .ok()
+        .and_then(|content| plan::split_front_matter(&content).ok())
+        .map(|(metadata, _)| metadata)
+        .filter(|metadata| !metadata.is_empty());
+    let (config, values) =
+        PealConfig::load_with_sources(config_path.as_deref(), run_args, metadata.as_ref())?;
+    if let Some(path) = &config_path {
+        println!("# config file: {}", path.display());
+    }
+    print!("{}", peal::config::render_resolved(&values, &config.warnings(), shown));
+    Ok(())
+}
+
+/// Phase 3 setup at the start of a stet session (once per run, or once per `session_size`
+/// session): runs the custom `stet_commands`, or `stet start` for built-in stet after settling
+/// a stale session (see `on_stale_stet_session`). Returns the Phase 3 mode (`None` when Phase 3
+/// is off or skipped by `on_stet_fail`) and the built-in session to record in state.
+fn start_phase3_session(
+    config: &PealConfig,
+    stet_path: Option<&PathBuf>,
+) -> anyhow::Result<(Option<stet::StetPhase3Mode>, Option<state::StetSession>)> {
+    let mut stet_session: Option<state::StetSession> = None;
+    let phase3_mode: Option<stet::StetPhase3Mode> = if !config.enable_phase3 {
+        None
+    } else if !config.stet_commands.is_empty() {
+        info!(count = config.stet_commands.len(), "running custom stet_commands at session start");
+        let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+        let run_stet_command = |cmd: &str| {
+            peal::subprocess::run_command_string_with_shell(
+                cmd,
+                &config.command_shell,
+                &config.repo_path,
+                timeout,
+            )
+        };
+        let mut session_ok = true;
+        for (i, cmd) in config.stet_commands.iter().enumerate() {
+            let cmd = stet::expand_command(cmd, config, None);
+            let cmd = cmd.trim();
+            if cmd.is_empty() {
+                continue;
+            }
+            info!(index = i + 1, command = %cmd, "running stet_commands entry");
+            let result = match run_stet_command(cmd) {
+                None => continue,
+                Some(Ok(r)) => r,
+                Some(Err(e)) => {
+                    let err = peal::error::PealError::StetStartFailed {
+                        detail: format!("spawn failed: {e}"),
+                    };
+                    if config.on_stet_fail == "retry_once" {
+                        warn!(err = %e, "custom stet command spawn failed, retrying once");
+                        match run_stet_command(cmd) {
+                            Some(Ok(r)) => r,
+                            _ => return Err(err.into()),
+                        }
+                    } else if config.on_stet_fail == "skip" {
+                        warn!(err = %e, "custom stet command failed; stet phase skipped for this run");
+                        session_ok = false;
+                        continue;
+                    } else {
+                        return Err(err.into());
+                    }
+                }
+            };
+            if result.timed_out {
+                let err = peal::error::PealError::StetStartFailed {
+                    detail: "custom stet command timed out".to_owned(),
+                };
+                if config.on_stet_fail == "retry_once" {
+                    warn!("custom stet command timed out, retrying once");
+                    if let Some(Ok(r)) = run_stet_command(cmd) {
+                        if r.timed_out {
+                            return Err(peal::error::PealError::StetStartFailed {
+                                detail: "custom stet command timed out (retry)".to_owned(),
+                            }
+                            .into());
+                        }
+                    } else {
+                        return Err(err.into());
+                    }
+                } else if config.on_stet_fail == "skip" {
+                    warn!("custom stet command timed out; stet phase skipped for this run");
+                    session_ok = false;
+                    continue;
+                } else {
+                    return Err(err.into());
+                }
+            } else if !result.success() && config.on_stet_fail == "fail" {
+                return Err(peal::error::PealError::StetStartFailed {
+                    detail: format!("exit code {:?}: {}", result.exit_code, result.stderr.trim()),
+                }.into());
+            } else if !result.success() && config.on_stet_fail == "skip" {
+                warn!(exit_code = ?result.exit_code, "custom stet command failed; stet phase skipped for this run");
+                session_ok = false;
+                continue;
+            } else if !result.success() && config.on_stet_fail == "retry_once" {
+                warn!(exit_code = ?result.exit_code, "custom stet command failed, retrying once");
+                if let Some(Ok(r)) = run_stet_command(cmd) {
+                    if !r.success() {
+                        return Err(peal::error::PealError::StetStartFailed {
+                            detail: format!("exit code {:?}: {}", r.exit_code, r.stderr.trim()),
+                        }
+                        .into());
+                    }
+                } else {
+                    return Err(peal::error::PealError::StetStartFailed {
+                        detail: "custom stet command failed on retry".to_owned(),
+                    }
+                    .into());
+                }
+            }
+        }
+        if session_ok {
+            Some(stet::StetPhase3Mode::CustomCommands(config.stet_commands.clone()))
+        } else {
+            None
+        }
+    } else if let Some(sp) = stet_path {
+        let stale_session = state::load_state(&config.state_dir)
+            .ok()
+            .flatten()
+            .filter(|s| s.matches_context(&config.plan_path, &config.repo_path))
+            .and_then(|s| s.stet_session);
+        let reattach = stale_session
+            .as_ref()
+            .is_some_and(|stale| stet::settle_stale_session(sp, config, stale));
+        let start_result = if reattach {
+            Ok(Some(sp.clone()))
+        } else {
+            info!("starting stet session");
+            match config.on_stet_fail.as_str() {
+                "retry_once" => {
+                    match stet::start_session(
+                        sp,
+                        config.stet_start_ref.as_deref(),
+                        &config.stet_start_extra_args,
+                        &config.repo_path,
+                        Some(Duration::from_secs(config.phase_timeout_sec)),
+                    ) {
+                        Ok(_) => Ok(Some(sp.clone())),
+                        Err(e) => {
+                            warn!(err = %e, "stet start failed, retrying once");
+                            stet::start_session(
+                                sp,
+                                config.stet_start_ref.as_deref(),
+                                &config.stet_start_extra_args,
+                                &config.repo_path,
+                                Some(Duration::from_secs(config.phase_timeout_sec)),
+                            )
+                            .map(|_| Some(sp.clone()))
+                        }
+                    }
+                }
+                "skip" => stet::start_session(
+                    sp,
+                    config.stet_start_ref.as_deref(),
+                    &config.stet_start_extra_args,
+                    &config.repo_path,
+                    Some(Duration::from_secs(config.phase_timeout_sec)),
+                )
+                .map(|_| Some(sp.clone()))
+                .or_else(|e| {
+                    warn!(err = %e, "stet start failed; stet phase skipped for this run");
+                    Ok(None)
+                }),
+                _ => stet::start_session(
+                    sp,
+                    config.stet_start_ref.as_deref(),
+                    &config.stet_start_extra_args,
+                    &config.repo_path,
+                    Some(Duration::from_secs(config.phase_timeout_sec)),
+                ).map(|_| Some(sp.clone())),
+            }
+        };
+        match start_result {
+            Ok(Some(path)) => {
+                stet_session = Some(match stale_session.filter(|_| reattach) {
+                    Some(stale) => stale,
+                    None => {
+                        info!("stet session started");
+                        state::StetSession::started_now(config.stet_start_ref.clone())
+                    }
+                });
+                Some(stet::StetPhase3Mode::BuiltIn(path))
+            }
+            Ok(None) => None,
+            Err(e) => return Err(e.into()),
+        }
+    } else {
+        None
+    };
+
+    Ok((phase3_mode, stet_session))
+}
+
+/// Best-effort `stet finish` for a built-in session; clears the session recorded in state
+/// when it succeeds.
+fn finish_phase3_session(config: &PealConfig, stet_path: &std::path::Path, peal_state: &mut state::PealState) {
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    match stet::finish_session(stet_path, &config.repo_path, timeout) {
+        Ok(out) => {
+            info!(
+                stdout_len = out.stdout.len(),
+                stderr_len = out.stderr.len(),
+                "stet finish succeeded"
+            );
+            if peal_state.stet_session.take().is_some()
+                && let Err(e) = state::save_state(peal_state, &config.state_dir)
+            {
+                warn!(%e, "failed to clear stet session in state");
+            }
+            if peal_state.stet_finish_failure.take().is_some()
+                && let Err(e) = state::save_state(peal_state, &config.state_dir)
+            {
+                warn!(%e, "failed to clear stet finish failure in state");
+            }
+        }
+        Err(e) => {
+            warn!(%e, "stet finish failed (best-effort); the session is left open, see --cleanup-stet");
+            peal_state.stet_finish_failure = Some(state::StetFinishFailure::now(e.to_string()));
+            if let Err(e) = state::save_state(peal_state, &config.state_dir) {
+                warn!(%e, "failed to record stet finish failure in state");
+            }
+        }
+    }
+}
+
+/// Best-effort end of a custom `stet_commands` session: runs `stet_finish_commands`.
+fn finish_custom_session(config: &PealConfig) {
+    if config.stet_finish_commands.is_empty() {
+        return;
+    }
+    match stet::finish_session_via_commands(config) {
+        Ok(()) => info!(count = config.stet_finish_commands.len(), "stet_finish_commands succeeded"),
+        Err(e) => warn!(%e, "stet_finish_commands failed (best-effort)"),
+    }
+}
+
+/// `--cleanup-stet`: run `stet finish` for the built-in session an earlier run left open (its
+/// finish failed, or it never got there) before a new session starts. Unlike the best-effort
+/// finish of `on_stale_stet_session`, a failure stops the run.
+fn cleanup_stet_session(
+    config: &PealConfig,
+    stet_path: Option<&PathBuf>,
+    peal_state: &mut state::PealState,
+) -> anyhow::Result<()> {
+    if peal_state.stet_session.is_none() && peal_state.stet_finish_failure.is_none() {
+        info!("--cleanup-stet: no stet session left open by an earlier run");
+        return Ok(());
+    }
+    let Some(sp) = stet_path else {
+        warn!("--cleanup-stet: stet not found (or Phase 3 disabled); the earlier session was not finished");
+        return Ok(());
+    };
+    info!("--cleanup-stet: finishing the stet session left open by an earlier run");
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    stet::finish_session(sp, &config.repo_path, timeout)?;
+    info!("--cleanup-stet: stet finish succeeded");
+    peal_state.stet_session = None;
+    peal_state.stet_finish_failure = None;
+    state::save_state(peal_state, &config.state_dir)?;
+    Ok(())
+}
+
+/// Run `post_run_commands` in `repo_path` (best-effort: failures are logged, never fatal).
+fn run_post_run_commands(config: &PealConfig) {
+        let timeout = config
+            .post_run_timeout_sec
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| Duration::from_secs(config.phase_timeout_sec));
+        info!(
+            count = config.post_run_commands.len(),
+            "running post-run command(s)"
+        );
+        for cmd in &config.post_run_commands {
+            let cmd = cmd.trim();
+            if cmd.is_empty() {
+                continue;
+            }
+            info!(command = %cmd, "running post-run command");
+            match peal::subprocess::run_command_string_with_shell(
+                cmd,
+                &config.command_shell,
+                &config.repo_path,
+                Some(timeout),
+            ) {
+                None => {}
+                Some(Ok(result)) => {
+                    const TRUNCATE_BYTES: usize = 2048;
+                    let truncate = peal::subprocess::truncate_utf8;
+                    if result.success() {
+                        info!(
+                            stdout_len = result.stdout.len(),
+                            stderr_len = result.stderr.len(),
+                            exit_code = ?result.exit_code,
+                            "post-run command succeeded"
+                        );
+                        if !result.stdout.is_empty() {
+                            info!(stdout = %truncate(result.stdout.trim(), TRUNCATE_BYTES), "post-run stdout");
+                        }
+                        if !result.stderr.is_empty() {
+                            info!(stderr = %truncate(result.stderr.trim(), TRUNCATE_BYTES), "post-run stderr");
+                        }
+                    } else {
+                        warn!(
+                            stdout_len = result.stdout.len(),
+                            stderr_len = result.stderr.len(),
+                            exit_code = ?result.exit_code,
+                            timed_out = result.timed_out,
+                            "post-run command failed (best-effort)"
+                        );
+                        if !result.stdout.is_empty() {
+                            warn!(stdout = %truncate(result.stdout.trim(), TRUNCATE_BYTES), "post-run stdout");
+                        }
+                        if !result.stderr.is_empty() {
+                            warn!(stderr = %truncate(result.stderr.trim(), TRUNCATE_BYTES), "post-run stderr");
+                        }
+                    }
+                }
+                Some(Err(e)) => {
+                    warn!(error = %e, "post-run command spawn failed (best-effort)");
+                }
+            }
+        }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -483,6 +1292,8 @@ mod tests {
 
     #[test]
     fn run_fails_when_plan_file_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -490,6 +1301,8 @@ mod tests {
             "/nonexistent/plan.md",
             "--repo",
             "/tmp",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
@@ -544,6 +1357,7 @@ mod tests {
             .output()
             .ok();
 
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -555,6 +1369,8 @@ mod tests {
             "echo",
             "--stet-path",
             "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
@@ -573,6 +1389,7 @@ mod tests {
         let plan_path = dir.path().join("plan.md");
         fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
 
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -584,6 +1401,8 @@ mod tests {
             "nonexistent-binary-xyz",
             "--stet-path",
             "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
@@ -603,6 +1422,7 @@ mod tests {
         let fake_repo = dir.path().join("not-a-dir.txt");
         fs::write(&fake_repo, "not a directory").unwrap();
 
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -610,6 +1430,8 @@ mod tests {
             plan_path.to_str().unwrap(),
             "--repo",
             fake_repo.to_str().unwrap(),
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
@@ -648,7 +1470,7 @@ mod tests {
         let outcome = run(cli).expect("should succeed with valid plan file and repo directory");
         match &outcome {
             CommandOutcome::RunOk { has_issues, .. } => assert!(!has_issues, "clean run should have has_issues false"),
-            CommandOutcome::PromptOk => panic!("expected RunOk"),
+            _ => panic!("expected RunOk"),
         }
 
         let summary_path = state_dir.join("run_summary.json");
@@ -658,6 +1480,14 @@ mod tests {
         assert!(summary.get("tasks_completed").is_some());
         assert_eq!(summary["tasks_completed"], serde_json::json!([1]));
         assert_eq!(summary["exit_code"], 0);
+
+        // The run ID ties the summary, its per-run copy, and state together.
+        let run_id = peal::run_id::current();
+        assert_eq!(summary["run_id"], run_id);
+        let copy = state_dir.join("runs").join(run_id).join("run_summary.json");
+        assert_eq!(fs::read_to_string(copy).unwrap(), content);
+        let state = state::load_state(&state_dir).unwrap().unwrap();
+        assert_eq!(state.last_run_id.as_deref(), Some(run_id));
     }
 
     #[test]
@@ -668,6 +1498,7 @@ mod tests {
         fs::write(&plan_path, "## Task 1\nDo something").unwrap();
 
         let custom_summary = dir.path().join("custom_run_summary.json");
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -681,6 +1512,8 @@ mod tests {
             "/nonexistent",
             "--run-summary-path",
             custom_summary.to_str().unwrap(),
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
@@ -691,6 +1524,43 @@ mod tests {
         assert_eq!(summary["tasks_completed"], serde_json::json!([1]));
     }
 
+    #[test]
+    fn run_summary_includes_plan_front_matter() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(
+            &plan_path,
+            "---\ntitle: Demo plan\nlabels: [demo]\nphase_timeout_sec: 120\n---\n## Task 1\nDo something",
+        )
+        .unwrap();
+
+        let state_dir = dir.path().join(".peal");
+        let cli = Cli::try_parse_from([
+            "peal",
+            "run",
+            "--plan",
+            plan_path.to_str().unwrap(),
+            "--repo",
+            dir.path().to_str().unwrap(),
+            "--agent-cmd",
+            "echo",
+            "--stet-path",
+            "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        run(cli).expect("run should succeed");
+        let content = fs::read_to_string(state_dir.join("run_summary.json")).unwrap();
+        let summary: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(summary["tasks_completed"], serde_json::json!([1]));
+        assert_eq!(summary["plan_metadata"]["title"], "Demo plan");
+        assert_eq!(summary["plan_metadata"]["labels"], serde_json::json!(["demo"]));
+        assert_eq!(summary["plan_metadata"]["phase_timeout_sec"], 120);
+    }
+
     /// Canonical plan with --normalize: no normalization call; parse directly and run.
     #[test]
     fn run_canonical_plan_with_normalize_flag_parses_without_agent_normalization() {
@@ -699,6 +1569,7 @@ mod tests {
         let plan_path = dir.path().join("plan.md");
         fs::write(&plan_path, "## Task 1\nDo something").unwrap();
 
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -711,6 +1582,8 @@ mod tests {
             "--normalize",
             "--stet-path",
             "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
@@ -732,6 +1605,7 @@ mod tests {
         perms.set_mode(0o755);
         fs::set_permissions(&stub, perms).unwrap();
 
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -744,10 +1618,15 @@ mod tests {
             "--normalize",
             "--stet-path",
             "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
         run(cli).expect("non-canonical + --normalize should invoke stub, then parse and run");
+        let saved = fs::read_to_string(dir.path().join("plan.normalized.md"))
+            .expect("normalized plan saved next to the source plan");
+        assert!(saved.contains("## Task 1"), "saved: {saved}");
     }
 
     /// Non-canonical + --normalize with stub that returns non-canonical output; retry 0 -> NormalizationParseFailed with snippet.
@@ -769,6 +1648,7 @@ mod tests {
         perms.set_mode(0o755);
         fs::set_permissions(&stub, perms).unwrap();
 
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -783,6 +1663,8 @@ mod tests {
             "0",
             "--stet-path",
             "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
@@ -803,6 +1685,7 @@ mod tests {
     #[cfg(unix)]
     fn run_normalize_retry_succeeds_on_second_attempt() {
         let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
         std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
         let plan_path = dir.path().join("plan.md");
         fs::write(&plan_path, "PRD. No ## Task headings.").unwrap();
@@ -821,13 +1704,147 @@ if [ "$c" -eq 1 ]; then
   echo "garbage"
   exit 0
 fi
+if [ "$c" -eq 2 ]; then
+  for a in "$@"; do last="$a"; done
+  printf '%s' "$last" > retry_prompt
+fi
 printf '%s\n' '## Task 1' 'Do it.'
 "#,
         )
         .unwrap();
-        let mut perms = fs::metadata(&stub).unwrap().permissions();
+        let mut perms = fs::metadata(&stub).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&stub, perms).unwrap();
+
+        let cli = Cli::try_parse_from([
+            "peal",
+            "run",
+            "--plan",
+            plan_path.to_str().unwrap(),
+            "--repo",
+            dir.path().to_str().unwrap(),
+            "--agent-cmd",
+            stub.to_str().unwrap(),
+            "--normalize",
+            "--normalize-retry-count",
+            "1",
+            "--stet-path",
+            "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        run(cli).expect("retry should succeed on second normalization");
+        let retry_prompt = fs::read_to_string(dir.path().join("retry_prompt")).unwrap();
+        assert!(
+            retry_prompt.contains("Your previous output was invalid because it has no task headings")
+                && retry_prompt.contains("---PREVIOUS OUTPUT---\ngarbage\n---PREVIOUS OUTPUT---"),
+            "retry prompt should explain the rejection: {retry_prompt}"
+        );
+
+        let state = state::load_state(&state_dir).unwrap().expect("state saved");
+        assert_eq!(state.normalization_retries, 1);
+        let summary = fs::read_to_string(state_dir.join("run_summary.json")).unwrap();
+        assert!(summary.contains(r#""normalization_retries": 1"#), "summary: {summary}");
+
+        let count: u32 = fs::read_to_string(dir.path().join("norm_count"))
+            .unwrap_or_default()
+            .trim()
+            .parse()
+            .unwrap_or(0);
+        // Two normalization attempts (first fails parse, second succeeds) plus phase 1 + phase 2 = 4 agent invocations.
+        assert_eq!(count, 4, "expected 4 agent invocations (2 normalize + phase1 + phase2), got {count}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_with_skip_review_never_invokes_stet() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.").unwrap();
+
+        // Any stet invocation (start, run, finish) leaves a marker in the repo.
+        let stet = dir.path().join("stet.sh");
+        fs::write(&stet, "#!/bin/sh\ntouch stet_ran\necho '{\"findings\":[]}'\n").unwrap();
+        let mut perms = fs::metadata(&stet).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&stet, perms).unwrap();
+
+        let cli = Cli::try_parse_from([
+            "peal",
+            "run",
+            "--plan",
+            plan_path.to_str().unwrap(),
+            "--repo",
+            dir.path().to_str().unwrap(),
+            "--agent-cmd",
+            "echo",
+            "--stet-path",
+            stet.to_str().unwrap(),
+            "--state-dir",
+            state_dir.to_str().unwrap(),
+            "--skip-review",
+        ])
+        .unwrap();
+
+        run(cli).expect("run without phase 3 should succeed");
+
+        assert!(!dir.path().join("stet_ran").exists(), "stet must not run with --skip-review");
+        let summary = fs::read_to_string(state_dir.join("run_summary.json")).unwrap();
+        assert!(summary.contains(r#""phase3_disabled": true"#), "summary: {summary}");
+        let state = state::load_state(&state_dir).unwrap().expect("state saved");
+        assert_eq!(state.completed_task_indices, vec![1]);
+    }
+
+    #[test]
+    fn dry_run_never_invokes_agent_and_leaves_state_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.\n## Task 2\nThen this.").unwrap();
+
+        let cli = Cli::try_parse_from([
+            "peal",
+            "run",
+            "--plan",
+            plan_path.to_str().unwrap(),
+            "--repo",
+            dir.path().to_str().unwrap(),
+            "--agent-cmd",
+            "false",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
+            "--dry-run",
+        ])
+        .unwrap();
+
+        assert!(matches!(run(cli).expect("dry run should succeed"), CommandOutcome::DryRunOk));
+        assert!(state::load_state(&state_dir).unwrap().is_none(), "dry run must not write state");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_with_session_size_starts_a_stet_session_per_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(
+            &plan_path,
+            "## Task 1\nA\n## Task 2 (parallel)\nB\n## Task 3 (parallel)\nC\n## Task 4\nD\n",
+        )
+        .unwrap();
+
+        // Logs each stet subcommand (cwd is the repo).
+        let stet = dir.path().join("stet.sh");
+        fs::write(&stet, "#!/bin/sh\necho \"$1\" >> stet.log\necho '{\"findings\":[]}'\n").unwrap();
+        let mut perms = fs::metadata(&stet).unwrap().permissions();
         perms.set_mode(0o755);
-        fs::set_permissions(&stub, perms).unwrap();
+        fs::set_permissions(&stet, perms).unwrap();
 
         let cli = Cli::try_parse_from([
             "peal",
@@ -837,24 +1854,35 @@ printf '%s\n' '## Task 1' 'Do it.'
             "--repo",
             dir.path().to_str().unwrap(),
             "--agent-cmd",
-            stub.to_str().unwrap(),
-            "--normalize",
-            "--normalize-retry-count",
-            "1",
+            "echo",
             "--stet-path",
-            "/nonexistent",
+            stet.to_str().unwrap(),
+            "--state-dir",
+            state_dir.to_str().unwrap(),
+            "--session-size",
+            "2",
+            "--command-shell",
+            "sh",
+            "--post-run-commands",
+            "echo done >> post_run.log",
         ])
         .unwrap();
 
-        run(cli).expect("retry should succeed on second normalization");
+        run(cli).expect("sessioned run should succeed");
 
-        let count: u32 = fs::read_to_string(dir.path().join("norm_count"))
-            .unwrap_or_default()
-            .trim()
-            .parse()
-            .unwrap_or(0);
-        // Two normalization attempts (first fails parse, second succeeds) plus phase 1 + phase 2 = 4 agent invocations.
-        assert_eq!(count, 4, "expected 4 agent invocations (2 normalize + phase1 + phase2), got {count}");
+        let log = fs::read_to_string(dir.path().join("stet.log")).unwrap();
+        let calls: Vec<&str> = log.lines().collect();
+        assert_eq!(
+            calls,
+            ["start", "run", "finish", "start", "run", "run", "finish", "start", "run", "finish"],
+            "the parallel block (tasks 2, 3) stays in one session"
+        );
+        let state = state::load_state(&state_dir).unwrap().expect("state saved");
+        assert_eq!(state.sessions, vec![vec![1], vec![2, 3], vec![4]]);
+        assert_eq!(state.completed_task_indices, vec![1, 2, 3, 4]);
+        assert!(state.stet_session.is_none());
+        let post_runs = fs::read_to_string(dir.path().join("post_run.log")).unwrap();
+        assert_eq!(post_runs.lines().count(), 3, "post-run commands run after each session");
     }
 
     #[test]
@@ -875,8 +1903,9 @@ printf '%s\n' '## Task 1' 'Do it.'
         )
         .unwrap();
 
-        let cli =
-            Cli::try_parse_from(["peal", "run", "--config", cfg_path.to_str().unwrap(), "--stet-path", "/nonexistent"]).unwrap();
+        let state_dir = dir.path().join(".peal");
+        let argv = ["peal", "run", "--config", cfg_path.to_str().unwrap(), "--stet-path", "/nonexistent"];
+        let cli = Cli::try_parse_from(argv.into_iter().chain(["--state-dir", state_dir.to_str().unwrap()])).unwrap();
 
         run(cli).expect("should succeed when plan and repo come from config file");
     }
@@ -963,9 +1992,91 @@ printf '%s\n' '## Task 1' 'Do it.'
         );
     }
 
+    #[test]
+    fn force_rerun_reruns_selected_completed_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nFirst\n\n## Task 2\nSecond\n\n## Task 3\nThird\n").unwrap();
+        let state_dir = dir.path().join(".peal");
+        let run_with = |extra: &[&str]| {
+            let mut argv = vec![
+                "peal",
+                "run",
+                "--plan",
+                plan_path.to_str().unwrap(),
+                "--repo",
+                dir.path().to_str().unwrap(),
+                "--agent-cmd",
+                "echo",
+                "--state-dir",
+                state_dir.to_str().unwrap(),
+                "--stet-path",
+                "/nonexistent",
+            ];
+            argv.extend_from_slice(extra);
+            run(Cli::try_parse_from(argv).unwrap()).expect("run should succeed");
+            peal::state::load_state(&state_dir).unwrap().unwrap()
+        };
+
+        let first = run_with(&[]);
+        assert_eq!(first.completed_task_indices, vec![1, 2, 3]);
+        let plan_text_before = first.plan_text_for(2).map(str::to_owned);
+
+        // Without --force-rerun a completed task is skipped: its saved plan text is untouched.
+        let mut marked = first.clone();
+        marked.record_plan_text(2, "marker");
+        state::save_state(&marked, &state_dir).unwrap();
+        let skipped = run_with(&["--task", "2"]);
+        assert_eq!(skipped.plan_text_for(2), Some("marker"));
+
+        let rerun = run_with(&["--from-task", "2", "--force-rerun"]);
+        assert_eq!(rerun.completed_task_indices, vec![1, 2, 3]);
+        assert_eq!(rerun.plan_text_for(2).map(str::to_owned), plan_text_before, "task 2 ran again");
+    }
+
+    #[test]
+    fn limit_tasks_runs_first_pending_tasks_and_marks_partial_run() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(
+            &plan_path,
+            "## Task 1\nFirst\n\n## Task 2 (parallel)\nSecond\n\n## Task 3 (parallel)\nThird\n\n## Task 4\nFourth\n",
+        )
+        .unwrap();
+        let state_dir = dir.path().join(".peal");
+        let run_limited = |limit: &str| {
+            let cli = Cli::try_parse_from([
+                "peal", "run", "--plan", plan_path.to_str().unwrap(), "--repo", dir.path().to_str().unwrap(),
+                "--agent-cmd", "echo", "--stet-path", "/nonexistent", "--state-dir", state_dir.to_str().unwrap(),
+                "--limit-tasks", limit,
+            ])
+            .unwrap();
+            let outcome = run(cli).expect("limited run should succeed");
+            assert!(matches!(outcome, CommandOutcome::RunOk { has_issues: false, .. }), "{outcome:?}");
+            let summary = fs::read_to_string(state_dir.join("run_summary.json")).unwrap();
+            let summary: serde_json::Value = serde_json::from_str(&summary).unwrap();
+            let state = state::load_state(&state_dir).unwrap().unwrap();
+            (summary, state.completed_task_indices)
+        };
+
+        // The limit can split a parallel block.
+        let (summary, completed) = run_limited("2");
+        assert_eq!(completed, vec![1, 2]);
+        assert_eq!(summary["partial_run"], true);
+
+        // The next run picks up after the completed tasks; a limit covering the rest is a full run.
+        let (summary, completed) = run_limited("5");
+        assert_eq!(completed, vec![1, 2, 3, 4]);
+        assert!(summary.get("partial_run").is_none(), "{summary}");
+    }
+
     #[test]
     fn run_fails_without_plan_or_repo() {
-        let cli = Cli::try_parse_from(["peal", "run"]).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let cli = Cli::try_parse_from(["peal", "run", "--state-dir", state_dir.to_str().unwrap()]).unwrap();
 
         let result = run(cli);
         let err_msg = format!("{}", result.unwrap_err());
@@ -1019,6 +2130,7 @@ printf '%s\n' '## Task 1' 'Do it.'
         )
         .unwrap();
 
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -1026,6 +2138,8 @@ printf '%s\n' '## Task 1' 'Do it.'
             cfg_path.to_str().unwrap(),
             "--stet-path",
             "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
@@ -1051,6 +2165,7 @@ printf '%s\n' '## Task 1' 'Do it.'
         )
         .unwrap();
 
+        let state_dir = dir.path().join(".peal");
         let cli = Cli::try_parse_from([
             "peal",
             "run",
@@ -1058,9 +2173,487 @@ printf '%s\n' '## Task 1' 'Do it.'
             cfg_path.to_str().unwrap(),
             "--stet-path",
             "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
         ])
         .unwrap();
 
         run(cli).expect("run with post_run_commands echo hello should succeed");
     }
+
+
+    #[cfg(unix)]
+    #[test]
+    fn run_summary_includes_phase_stderr_of_failed_task() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nFAIL here\n\n## Task 2\nDo something").unwrap();
+
+        // Agent fails (with a stderr message) only for task 1.
+        let agent = dir.path().join("agent-stub");
+        fs::write(
+            &agent,
+            "#!/bin/sh\ncase \"$*\" in *FAIL*) echo 'quota exceeded' >&2; exit 1;; esac\necho ok\n",
+        )
+        .unwrap();
+        fs::set_permissions(&agent, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let state_dir = dir.path().join(".peal");
+        let cli = Cli::try_parse_from([
+            "peal",
+            "run",
+            "--plan",
+            plan_path.to_str().unwrap(),
+            "--repo",
+            dir.path().to_str().unwrap(),
+            "--agent-cmd",
+            agent.to_str().unwrap(),
+            "--stet-path",
+            "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
+            "--continue-with-remaining-tasks",
+        ])
+        .unwrap();
+
+        run(cli).expect("run should complete with issues");
+        let artifact = peal::phase::stderr_artifact_path(&state_dir, 1, 1);
+        assert_eq!(fs::read_to_string(&artifact).unwrap().trim(), "quota exceeded");
+
+        let content = fs::read_to_string(state_dir.join("run_summary.json")).unwrap();
+        let summary: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(summary["tasks_failed"], serde_json::json!([1]));
+        assert_eq!(summary["phase_stderr"][0]["task_index"], 1);
+        assert_eq!(summary["phase_stderr"][0]["phase"], 1);
+        assert_eq!(summary["phase_stderr"][0]["excerpt"], "quota exceeded");
+        assert_eq!(summary["phase_stderr"].as_array().unwrap().len(), 1);
+    }
+
+
+    #[test]
+    fn run_auto_detects_repo_peal_toml_unless_no_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo something").unwrap();
+        let auto_summary = dir.path().join("auto_summary.json");
+        fs::write(
+            dir.path().join("peal.toml"),
+            format!("run_summary_path = {:?}\n", auto_summary.to_str().unwrap()),
+        )
+        .unwrap();
+        let state_dir = dir.path().join(".peal");
+
+        let args = |extra: &[&str]| {
+            let mut v = vec![
+                "peal",
+                "run",
+                "--plan",
+                plan_path.to_str().unwrap(),
+                "--repo",
+                dir.path().to_str().unwrap(),
+                "--agent-cmd",
+                "echo",
+                "--stet-path",
+                "/nonexistent",
+                "--state-dir",
+                state_dir.to_str().unwrap(),
+            ];
+            v.extend_from_slice(extra);
+            Cli::try_parse_from(v).unwrap()
+        };
+
+        run(args(&["--no-config"])).expect("run should succeed");
+        assert!(!auto_summary.exists(), "--no-config must skip peal.toml");
+        assert!(state_dir.join("run_summary.json").exists());
+
+        fs::remove_dir_all(&state_dir).unwrap();
+        run(args(&[])).expect("run should succeed");
+        assert!(auto_summary.exists(), "peal.toml in repo should be picked up");
+    }
+
+
+    #[test]
+    fn run_expands_plan_vars_from_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDeploy {{SERVICE_NAME}} service").unwrap();
+
+        let state_dir = dir.path().join(".peal");
+        let cli = Cli::try_parse_from([
+            "peal",
+            "run",
+            "--plan",
+            plan_path.to_str().unwrap(),
+            "--repo",
+            dir.path().to_str().unwrap(),
+            "--agent-cmd",
+            "echo",
+            "--stet-path",
+            "/nonexistent",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
+            "--var",
+            "SERVICE_NAME=billing",
+        ])
+        .unwrap();
+
+        match run(cli).expect("run should succeed") {
+            CommandOutcome::RunOk { outcome, .. } => {
+                let plan_text = &outcome.results[0].plan_text;
+                assert!(plan_text.contains("Deploy billing service"), "got: {plan_text}");
+                assert!(!plan_text.contains("{{SERVICE_NAME}}"));
+            }
+            _ => panic!("expected RunOk"),
+        }
+    }
+
+
+    #[test]
+    fn resume_reruns_renumbered_tasks_unless_trusted() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        let state_dir = dir.path().join(".peal");
+        let run_plan = |extra: &[&str]| {
+            let mut v = vec![
+                "peal", "run",
+                "--plan", plan_path.to_str().unwrap(),
+                "--repo", dir.path().to_str().unwrap(),
+                "--agent-cmd", "echo",
+                "--stet-path", "/nonexistent",
+                "--state-dir", state_dir.to_str().unwrap(),
+            ];
+            v.extend_from_slice(extra);
+            match run(Cli::try_parse_from(v).unwrap()).expect("run should succeed") {
+                CommandOutcome::RunOk { outcome, .. } => {
+                    outcome.results.iter().map(|r| r.task_index).collect::<Vec<_>>()
+                }
+                _ => panic!("expected RunOk"),
+            }
+        };
+
+        fs::write(&plan_path, "## Task 1\nA\n\n## Task 2\nB\n").unwrap();
+        assert_eq!(run_plan(&[]), vec![1, 2]);
+
+        // Inserting a task shifts A and B; their old indices now hold different content.
+        fs::write(&plan_path, "## Task 1\nNew\n\n## Task 2\nA\n\n## Task 3\nB\n").unwrap();
+        assert_eq!(run_plan(&["--on-renumber", "trust"]), vec![3]);
+        assert_eq!(run_plan(&[]), vec![1, 2]);
+        assert!(run_plan(&[]).is_empty(), "rerun tasks record their new hashes");
+    }
+
+    #[test]
+    fn state_reset_uncompletes_tasks_in_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let mut s = state::PealState::new(PathBuf::from("plan.md"), PathBuf::from("/repo"));
+        for i in 1..=5 {
+            s.mark_task_completed(i);
+        }
+        state::save_state(&s, &state_dir).unwrap();
+
+        let reset = |extra: &[&str]| {
+            let mut v = vec!["peal", "state", "reset", "--state-dir", state_dir.to_str().unwrap()];
+            v.extend_from_slice(extra);
+            run(Cli::try_parse_from(v).unwrap()).expect("state reset should succeed");
+            state::load_state(&state_dir).unwrap().unwrap().completed_task_indices
+        };
+
+        assert_eq!(reset(&["--task", "2", "--task", "4"]), vec![1, 3, 5]);
+        assert_eq!(reset(&["--from-task", "3"]), vec![1]);
+        assert_eq!(reset(&["--task", "9"]), vec![1], "unknown task leaves state unchanged");
+    }
+
+    #[test]
+    fn state_reset_without_state_file_is_ok() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join("missing");
+        let cli = Cli::try_parse_from([
+            "peal", "state", "reset", "--task", "1", "--state-dir", state_dir.to_str().unwrap(),
+        ])
+        .unwrap();
+        assert!(matches!(run(cli).unwrap(), CommandOutcome::StateOk));
+        assert!(!state_dir.exists());
+    }
+
+
+    #[test]
+    fn run_fails_on_duplicate_indices_and_renumber_write_fixes_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nFirst\n## Task 1\nSecond\n").unwrap();
+        let state_dir = dir.path().join(".peal");
+        let argv = |extra: &[&str]| {
+            let mut v = vec![
+                "peal",
+                "run",
+                "--plan",
+                plan_path.to_str().unwrap(),
+                "--repo",
+                dir.path().to_str().unwrap(),
+                "--agent-cmd",
+                "echo",
+                "--stet-path",
+                "/nonexistent",
+                "--state-dir",
+                state_dir.to_str().unwrap(),
+            ];
+            v.extend_from_slice(extra);
+            Cli::try_parse_from(v).unwrap()
+        };
+
+        let err = run(argv(&[])).unwrap_err().to_string();
+        assert!(err.contains("Task 1: lines 1, 3"), "got: {err}");
+
+        match run(argv(&["--renumber", "--write"])).unwrap() {
+            CommandOutcome::RunOk { outcome, .. } => assert_eq!(outcome.results.len(), 2),
+            _ => panic!("expected RunOk"),
+        }
+        assert_eq!(
+            fs::read_to_string(&plan_path).unwrap(),
+            "## Task 1\nFirst\n## Task 2\nSecond\n"
+        );
+    }
+
+
+    #[cfg(unix)]
+    #[test]
+    fn run_records_stet_session_and_settles_stale_one_per_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.\n").unwrap();
+        let state_dir = dir.path().join(".peal");
+
+        let bin = tempfile::tempdir().unwrap();
+        let stet = bin.path().join("stet");
+        fs::write(
+            &stet,
+            "#!/bin/sh\n\
+             d=$(dirname \"$0\")\n\
+             echo \"$1\" >> \"$d/stet.log\"\n\
+             if [ \"$1\" = finish ] && [ -f \"$d/finish_fails\" ]; then exit 1; fi\n\
+             if [ \"$1\" = run ]; then echo '{\"findings\":[]}'; fi\n",
+        )
+        .unwrap();
+        fs::set_permissions(&stet, fs::Permissions::from_mode(0o755)).unwrap();
+        let log = bin.path().join("stet.log");
+        let fails = bin.path().join("finish_fails");
+
+        let run_once = |extra: &[&str]| -> Vec<String> {
+            let _ = fs::remove_file(&log);
+            let mut argv = vec![
+                "peal",
+                "run",
+                "--plan",
+                plan_path.to_str().unwrap(),
+                "--repo",
+                dir.path().to_str().unwrap(),
+                "--agent-cmd",
+                "echo",
+                "--state-dir",
+                state_dir.to_str().unwrap(),
+                "--stet-path",
+                stet.to_str().unwrap(),
+            ];
+            argv.extend_from_slice(extra);
+            run(Cli::try_parse_from(argv).unwrap()).expect("run should succeed");
+            fs::read_to_string(&log).unwrap().lines().map(String::from).collect()
+        };
+        let recorded = || state::load_state(&state_dir).unwrap().unwrap().stet_session;
+
+        fs::write(&fails, "").unwrap();
+        assert_eq!(run_once(&[]), ["start", "run", "finish"]);
+        assert!(recorded().is_some(), "unfinished session stays recorded");
+
+        fs::remove_file(&fails).unwrap();
+        assert_eq!(
+            run_once(&[]),
+            ["finish", "start", "finish"],
+            "default policy finishes the stale session first"
+        );
+        assert_eq!(recorded(), None);
+
+        fs::write(&fails, "").unwrap();
+        run_once(&[]);
+        let stale = recorded().expect("session recorded");
+        fs::remove_file(&fails).unwrap();
+        let mut s = state::load_state(&state_dir).unwrap().unwrap();
+        s.completed_task_indices.clear();
+        state::save_state(&s, &state_dir).unwrap();
+        assert_eq!(
+            run_once(&["--on-stale-stet-session", "reattach"]),
+            ["run", "finish"],
+            "reattach skips stet start"
+        );
+        assert_eq!(recorded(), None);
+        assert!(!stale.started_at.is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn failed_stet_finish_is_recorded_and_cleaned_up_with_cleanup_stet() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.\n").unwrap();
+        let state_dir = dir.path().join(".peal");
+
+        let bin = tempfile::tempdir().unwrap();
+        let stet = bin.path().join("stet");
+        fs::write(
+            &stet,
+            "#!/bin/sh\n\
+             d=$(dirname \"$0\")\n\
+             echo \"$1\" >> \"$d/stet.log\"\n\
+             if [ \"$1\" = finish ] && [ -f \"$d/finish_fails\" ]; then echo 'worktree busy' >&2; exit 1; fi\n\
+             if [ \"$1\" = run ]; then echo '{\"findings\":[]}'; fi\n",
+        )
+        .unwrap();
+        fs::set_permissions(&stet, fs::Permissions::from_mode(0o755)).unwrap();
+        let log = bin.path().join("stet.log");
+        let fails = bin.path().join("finish_fails");
+
+        let run_once = |extra: &[&str]| {
+            let _ = fs::remove_file(&log);
+            let mut argv = vec![
+                "peal",
+                "run",
+                "--plan",
+                plan_path.to_str().unwrap(),
+                "--repo",
+                dir.path().to_str().unwrap(),
+                "--agent-cmd",
+                "echo",
+                "--state-dir",
+                state_dir.to_str().unwrap(),
+                "--stet-path",
+                stet.to_str().unwrap(),
+            ];
+            argv.extend_from_slice(extra);
+            run(Cli::try_parse_from(argv).unwrap())
+        };
+        let stet_calls = || -> Vec<String> { fs::read_to_string(&log).unwrap().lines().map(String::from).collect() };
+        let failure = || state::load_state(&state_dir).unwrap().unwrap().stet_finish_failure;
+
+        fs::write(&fails, "").unwrap();
+        run_once(&[]).expect("a failed finish does not fail the run");
+        let recorded = failure().expect("finish failure recorded");
+        assert!(recorded.detail.contains("worktree busy"), "{recorded:?}");
+        assert_eq!(recorded.run_id.as_deref(), Some(peal::run_id::current()));
+        let summary = fs::read_to_string(state_dir.join("run_summary.json")).unwrap();
+        assert!(summary.contains("\"stet_finish_failed\""), "summary: {summary}");
+
+        assert!(run_once(&["--cleanup-stet"]).is_err(), "cleanup that fails again stops the run");
+        assert_eq!(stet_calls(), ["finish"], "no new session after a failed cleanup");
+
+        fs::remove_file(&fails).unwrap();
+        run_once(&["--cleanup-stet"]).expect("cleanup succeeds");
+        assert_eq!(stet_calls(), ["finish", "start", "finish"]);
+        assert_eq!(failure(), None);
+        assert!(state::load_state(&state_dir).unwrap().unwrap().stet_session.is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn custom_stet_commands_are_expanded_and_finished_with_stet_finish_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo it.\n").unwrap();
+        let config_path = dir.path().join("peal.toml");
+        fs::write(
+            &config_path,
+            r#"command_shell = "sh"
+stet_start_ref = "main"
+stet_commands = ["echo start {{START_REF}} >> stet.log", "echo review {{TASK_INDEX}} >> stet.log; echo '{\"findings\":[]}'"]
+stet_finish_commands = ["echo finish {{RUN_ID}} >> stet.log"]
+"#,
+        )
+        .unwrap();
+        let state_dir = dir.path().join(".peal");
+
+        let argv = [
+            "peal",
+            "run",
+            "--plan",
+            plan_path.to_str().unwrap(),
+            "--repo",
+            dir.path().to_str().unwrap(),
+            "--agent-cmd",
+            "echo",
+            "--state-dir",
+            state_dir.to_str().unwrap(),
+            "--config",
+            config_path.to_str().unwrap(),
+        ];
+        run(Cli::try_parse_from(argv).unwrap()).expect("run succeeds");
+        let log = fs::read_to_string(dir.path().join("stet.log")).unwrap();
+        assert_eq!(
+            log,
+            format!("start main\nreview\nreview 1\nfinish {}\n", peal::run_id::current())
+        );
+    }
+
+    #[test]
+    fn print_config_exits_without_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo something").unwrap();
+        let state_dir = dir.path().join(".peal");
+        let (plan, repo, state) =
+            (plan_path.to_str().unwrap(), dir.path().to_str().unwrap(), state_dir.to_str().unwrap());
+        let cli = Cli::try_parse_from([
+            "peal", "run", "--plan", plan, "--repo", repo, "--agent-cmd", "echo", "--state-dir", state,
+            "--print-config",
+        ])
+        .unwrap();
+        assert!(matches!(run(cli).unwrap(), CommandOutcome::ConfigOk));
+        assert!(!state_dir.exists(), "nothing runs");
+    }
+
+    #[test]
+    fn lock_path_is_written_and_locked_runs_fail_on_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git").args(["init"]).current_dir(dir.path()).output().ok();
+        let plan_path = dir.path().join("plan.md");
+        fs::write(&plan_path, "## Task 1\nDo something").unwrap();
+        let state_dir = dir.path().join(".peal");
+        let lock_path = dir.path().join("peal.lock");
+        let (plan, repo) = (plan_path.to_str().unwrap(), dir.path().to_str().unwrap());
+        let (state, lock) = (state_dir.to_str().unwrap(), lock_path.to_str().unwrap());
+        let argv = |extra: &[&'static str]| {
+            let mut argv = vec!["peal", "run", "--plan", plan, "--repo", repo, "--agent-cmd", "echo"];
+            argv.extend(["--stet-path", "/nonexistent", "--state-dir", state, "--lock-path", lock]);
+            argv.extend(extra);
+            Cli::try_parse_from(argv).unwrap()
+        };
+
+        run(argv(&[])).expect("first run writes the lock");
+        let locked: peal::lock::RunManifest = toml::from_str(&fs::read_to_string(&lock_path).unwrap()).unwrap();
+        assert!(locked.agent_version.is_some(), "{locked:?}");
+        assert!(locked.prompts.contains_key("phase2"));
+
+        run(argv(&["--locked"])).expect("unchanged run matches the lock");
+
+        fs::write(&plan_path, "## Task 1\nDo something else").unwrap();
+        let err = run(argv(&["--locked", "--model", "other"])).unwrap_err().to_string();
+        assert!(err.contains("does not match lock file"), "{err}");
+        assert!(err.contains("plan_hash: ") && err.contains("model: (none) -> \"other\""), "{err}");
+        assert_eq!(
+            toml::from_str::<peal::lock::RunManifest>(&fs::read_to_string(&lock_path).unwrap()).unwrap(),
+            locked,
+            "--locked leaves the lock file alone"
+        );
+
+        let without_path = Cli::try_parse_from(["peal", "run", "--plan", plan, "--repo", repo, "--agent-cmd", "echo", "--locked"]);
+        let err = run(without_path.unwrap()).unwrap_err().to_string();
+        assert!(err.contains("--locked needs lock_path"), "{err}");
+    }
 }
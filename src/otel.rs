@@ -0,0 +1,625 @@
+//! OpenTelemetry trace export (OTLP/HTTP with JSON encoding).
+//!
+//! When `otel_endpoint` is configured, [`OtlpLayer`] is added to the tracing
+//! subscriber. It turns tracing spans into OTLP spans — the run is the root,
+//! with a child span per task and grandchildren per phase and stet call — and
+//! POSTs them to `<endpoint>/v1/traces`. Spans are exported in batches and
+//! whenever a root span closes, so a finished run is always flushed. A run stopped by a
+//! signal calls [`flush`] to export the spans that finished before it.
+//!
+//! The requests are made on a background thread fed through a bounded queue, so a slow or
+//! unreachable collector never holds up the thread closing a span; when the queue is full,
+//! the batch is dropped. [`flush`] waits for the thread at most [`FLUSH_TIMEOUT`].
+//!
+//! Only plain `http://` endpoints are supported (e.g. a local OpenTelemetry
+//! Collector, Jaeger, or Grafana Tempo listening on port 4318). Export is
+//! best-effort: failures and dropped batches are reported once each on stderr and never fail
+//! the run.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{Value, json};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+/// OTLP/HTTP traces path appended when the endpoint has no path.
+const TRACES_PATH: &str = "/v1/traces";
+
+/// Finished spans buffered before an export is forced.
+const MAX_BATCH_SPANS: usize = 256;
+
+/// Connect, read, and write timeout for one export request.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Batches waiting for the export thread before new ones are dropped.
+const QUEUE_BATCHES: usize = 16;
+
+/// How long [`flush`] waits for the export thread.
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Parsed `http://host[:port][/path]` collector endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl Endpoint {
+    /// Parse an OTLP endpoint URL. The port defaults to 80; an empty path (or `/`)
+    /// becomes `/v1/traces`, any other path is used as given.
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let url = url.trim();
+        let rest = if let Some(rest) = url.strip_prefix("http://") {
+            rest
+        } else if url.starts_with("https://") {
+            return Err(format!(
+                "'{url}': https is not supported; use an http:// endpoint (e.g. a local collector)"
+            ));
+        } else {
+            return Err(format!("'{url}': expected an http:// URL"));
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| format!("'{url}': invalid port '{port}'"))?;
+                (host, port)
+            }
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("'{url}': missing host"));
+        }
+        let path = if path.is_empty() || path == "/" {
+            TRACES_PATH.to_owned()
+        } else {
+            path.to_owned()
+        };
+        Ok(Self {
+            host: host.to_owned(),
+            port,
+            path,
+        })
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+/// Attribute value recorded from a span field.
+#[derive(Debug, Clone, PartialEq)]
+enum AttrValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Double(f64),
+}
+
+/// Per-span state stored in the registry's span extensions.
+#[derive(Debug)]
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    start: SystemTime,
+    attributes: Vec<(String, AttrValue)>,
+    /// Message of the last ERROR event inside the span; sets the OTLP status to error.
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+struct FinishedSpan {
+    name: &'static str,
+    data: SpanData,
+    end: SystemTime,
+}
+
+/// Records span fields as OTLP attributes.
+struct AttrVisitor<'a>(&'a mut Vec<(String, AttrValue)>);
+
+impl AttrVisitor<'_> {
+    fn set(&mut self, field: &Field, value: AttrValue) {
+        let name = field.name();
+        match self.0.iter_mut().find(|(k, _)| k == name) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((name.to_owned(), value)),
+        }
+    }
+}
+
+impl Visit for AttrVisitor<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field, AttrValue::Int(value));
+    }
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field, AttrValue::Int(i64::try_from(value).unwrap_or(i64::MAX)));
+    }
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field, AttrValue::Bool(value));
+    }
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field, AttrValue::Double(value));
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, AttrValue::Str(value.to_owned()));
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.set(field, AttrValue::Str(format!("{value:?}")));
+    }
+}
+
+/// Extracts the `message` field of an event.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = value.to_owned();
+        }
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+/// Work for the export thread.
+enum Job {
+    Export(Vec<FinishedSpan>),
+    /// Answered once every job queued before it is done.
+    Flush(mpsc::Sender<()>),
+}
+
+/// Buffers finished spans and hands full batches to the export thread.
+#[derive(Debug)]
+struct Exporter {
+    endpoint: Endpoint,
+    pending: Mutex<Vec<FinishedSpan>>,
+    queue: SyncSender<Job>,
+    dropped_warned: AtomicBool,
+}
+
+impl Exporter {
+    /// An exporter and the thread that POSTs its batches to `endpoint`.
+    fn spawn(endpoint: Endpoint) -> Self {
+        let (queue, jobs) = mpsc::sync_channel(QUEUE_BATCHES);
+        let thread_endpoint = endpoint.clone();
+        let spawned = std::thread::Builder::new()
+            .name("peal-otlp".to_owned())
+            .spawn(move || export_thread(&thread_endpoint, &jobs));
+        if let Err(e) = spawned {
+            eprintln!("warning: could not start the OTLP export thread: {e}");
+        }
+        Self {
+            endpoint,
+            pending: Mutex::new(Vec::new()),
+            queue,
+            dropped_warned: AtomicBool::new(false),
+        }
+    }
+
+    fn push(&self, span: FinishedSpan, flush: bool) {
+        let batch = {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            pending.push(span);
+            if !flush && pending.len() < MAX_BATCH_SPANS {
+                return;
+            }
+            std::mem::take(&mut *pending)
+        };
+        self.send(batch);
+    }
+
+    /// Queue `batch` for export without blocking; dropped when the queue is full.
+    fn send(&self, batch: Vec<FinishedSpan>) {
+        if batch.is_empty() {
+            return;
+        }
+        let spans = batch.len();
+        if self.queue.try_send(Job::Export(batch)).is_err()
+            && !self.dropped_warned.swap(true, Ordering::Relaxed)
+        {
+            // Not a tracing event: we are inside the subscriber.
+            eprintln!("warning: OTLP trace export to {} is behind; dropped {spans} spans", self.endpoint);
+        }
+    }
+
+    /// Queue the buffered spans and wait until the export thread has sent them, or `deadline`.
+    fn flush(&self, deadline: Instant) {
+        let batch = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        self.send(batch);
+        let (done, finished) = mpsc::channel();
+        let mut job = Job::Flush(done);
+        loop {
+            match self.queue.try_send(job) {
+                Ok(()) => break,
+                Err(TrySendError::Full(back)) if Instant::now() < deadline => {
+                    job = back;
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(_) => return,
+            }
+        }
+        let _ = finished.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Body of the export thread: POST each batch until every [`Exporter`] handle is gone.
+fn export_thread(endpoint: &Endpoint, jobs: &Receiver<Job>) {
+    let mut warned = false;
+    for job in jobs {
+        match job {
+            Job::Export(batch) => {
+                let body = encode_request(&batch).to_string();
+                if let Err(e) = post(endpoint, body.as_bytes())
+                    && !std::mem::replace(&mut warned, true)
+                {
+                    eprintln!("warning: OTLP trace export to {endpoint} failed: {e}");
+                }
+            }
+            Job::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Exporters of the layers created by this process, for [`flush`].
+static EXPORTERS: Mutex<Vec<Arc<Exporter>>> = Mutex::new(Vec::new());
+
+/// Export the finished spans still buffered, waiting for the export thread at most
+/// [`FLUSH_TIMEOUT`]. Spans that have not closed yet are not exported.
+pub fn flush() {
+    flush_within(FLUSH_TIMEOUT);
+}
+
+fn flush_within(timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    let exporters = EXPORTERS.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    for exporter in exporters {
+        exporter.flush(deadline);
+    }
+}
+
+/// Tracing layer exporting spans to an OTLP/HTTP collector.
+#[derive(Debug, Clone)]
+pub struct OtlpLayer {
+    exporter: Arc<Exporter>,
+}
+
+impl OtlpLayer {
+    pub fn new(endpoint: Endpoint) -> Self {
+        let exporter = Arc::new(Exporter::spawn(endpoint));
+        EXPORTERS.lock().unwrap_or_else(|e| e.into_inner()).push(Arc::clone(&exporter));
+        Self { exporter }
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let parent = span.parent().and_then(|p| {
+            p.extensions()
+                .get::<SpanData>()
+                .map(|d| (d.trace_id, d.span_id))
+        });
+        let (trace_id, parent_span_id) = match parent {
+            Some((trace_id, parent_id)) => (trace_id, Some(parent_id)),
+            None => (random_trace_id(), None),
+        };
+        let mut data = SpanData {
+            trace_id,
+            span_id: random_span_id(),
+            parent_span_id,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+            error: None,
+        };
+        attrs.record(&mut AttrVisitor(&mut data.attributes));
+        span.extensions_mut().insert(data);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(data) = span.extensions_mut().get_mut::<SpanData>()
+        {
+            values.record(&mut AttrVisitor(&mut data.attributes));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        if let Some(span) = ctx.event_span(event)
+            && let Some(data) = span.extensions_mut().get_mut::<SpanData>()
+        {
+            let mut message = MessageVisitor::default();
+            event.record(&mut message);
+            data.error = Some(message.0);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(data) = span.extensions_mut().remove::<SpanData>() else {
+            return;
+        };
+        let is_root = data.parent_span_id.is_none();
+        self.exporter.push(
+            FinishedSpan {
+                name: span.name(),
+                data,
+                end: SystemTime::now(),
+            },
+            is_root,
+        );
+    }
+}
+
+fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(unix_nanos(SystemTime::now()));
+    hasher.finish()
+}
+
+fn random_trace_id() -> [u8; 16] {
+    let mut id = [0u8; 16];
+    id[..8].copy_from_slice(&random_u64().to_be_bytes());
+    id[8..].copy_from_slice(&random_u64().to_be_bytes());
+    id
+}
+
+fn random_span_id() -> [u8; 8] {
+    // All-zero ids are invalid in OTLP.
+    let id = random_u64().max(1);
+    id.to_be_bytes()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unix_nanos(t: SystemTime) -> u128 {
+    t.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0)
+}
+
+fn attr_json(key: &str, value: &AttrValue) -> Value {
+    // OTLP/JSON encodes 64-bit integers as strings.
+    let value = match value {
+        AttrValue::Str(s) => json!({ "stringValue": s }),
+        AttrValue::Int(i) => json!({ "intValue": i.to_string() }),
+        AttrValue::Bool(b) => json!({ "boolValue": b }),
+        AttrValue::Double(d) => json!({ "doubleValue": d }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+/// Build an OTLP `ExportTraceServiceRequest` (JSON encoding) for the given spans.
+fn encode_request(spans: &[FinishedSpan]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|s| {
+            let mut span = json!({
+                "traceId": hex(&s.data.trace_id),
+                "spanId": hex(&s.data.span_id),
+                "name": s.name,
+                // SPAN_KIND_INTERNAL
+                "kind": 1,
+                "startTimeUnixNano": unix_nanos(s.data.start).to_string(),
+                "endTimeUnixNano": unix_nanos(s.end).to_string(),
+                "attributes": s
+                    .data
+                    .attributes
+                    .iter()
+                    .map(|(k, v)| attr_json(k, v))
+                    .collect::<Vec<_>>(),
+            });
+            if let Some(parent) = &s.data.parent_span_id {
+                span["parentSpanId"] = json!(hex(parent));
+            }
+            if let Some(message) = &s.data.error {
+                // STATUS_CODE_ERROR
+                span["status"] = json!({ "code": 2, "message": message });
+            }
+            span
+        })
+        .collect();
+
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attr_json("service.name", &AttrValue::Str("peal".to_owned()))],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "peal", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+/// POST `body` as JSON to the endpoint over HTTP/1.1 and require a 2xx status.
+fn post(endpoint: &Endpoint, body: &[u8]) -> std::io::Result<()> {
+    let addr = (endpoint.host.as_str(), endpoint.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::other("endpoint host did not resolve"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, EXPORT_TIMEOUT)?;
+    stream.set_read_timeout(Some(EXPORT_TIMEOUT))?;
+    stream.set_write_timeout(Some(EXPORT_TIMEOUT))?;
+
+    let head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        endpoint.path,
+        endpoint.host,
+        endpoint.port,
+        body.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    let mut response = Vec::new();
+    let _ = stream.take(4096).read_to_end(&mut response);
+    let status_line = String::from_utf8_lossy(&response);
+    let status_line = status_line.lines().next().unwrap_or("");
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(std::io::Error::other(format!(
+            "collector responded '{status_line}'"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn endpoint_parse_defaults_path_and_port() {
+        assert_eq!(
+            Endpoint::parse("http://localhost:4318").unwrap(),
+            Endpoint {
+                host: "localhost".to_owned(),
+                port: 4318,
+                path: "/v1/traces".to_owned()
+            }
+        );
+        let e = Endpoint::parse("http://collector/").unwrap();
+        assert_eq!((e.port, e.path.as_str()), (80, "/v1/traces"));
+        let e = Endpoint::parse("http://collector:4318/custom/traces").unwrap();
+        assert_eq!(e.path, "/custom/traces");
+    }
+
+    #[test]
+    fn endpoint_parse_rejects_unsupported_urls() {
+        assert!(Endpoint::parse("https://tempo:4318").unwrap_err().contains("https"));
+        assert!(Endpoint::parse("localhost:4318").is_err());
+        assert!(Endpoint::parse("http://:4318").is_err());
+        assert!(Endpoint::parse("http://host:port").is_err());
+    }
+
+    /// Accepts one HTTP request, answers 200, and returns the JSON body.
+    fn one_shot_collector() -> (Endpoint, std::thread::JoinHandle<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut chunk).unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                let text = String::from_utf8_lossy(&buf);
+                if let Some(split) = text.find("\r\n\r\n") {
+                    let len: usize = text[..split]
+                        .lines()
+                        .find_map(|l| l.strip_prefix("Content-Length: "))
+                        .unwrap()
+                        .parse()
+                        .unwrap();
+                    if buf.len() >= split + 4 + len {
+                        assert!(text.starts_with("POST /v1/traces HTTP/1.1"));
+                        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+                        return serde_json::from_slice(&buf[split + 4..split + 4 + len]).unwrap();
+                    }
+                }
+            }
+        });
+        (Endpoint::parse(&format!("http://127.0.0.1:{port}")).unwrap(), handle)
+    }
+
+    #[test]
+    fn exports_nested_spans_when_root_closes() {
+        let (endpoint, collector) = one_shot_collector();
+        let subscriber = tracing_subscriber::registry().with(OtlpLayer::new(endpoint));
+        tracing::subscriber::with_default(subscriber, || {
+            let run = tracing::info_span!("run", plan = "plan.md");
+            let _run = run.enter();
+            let task = tracing::info_span!("task", task_index = 2u32);
+            let _task = task.enter();
+            tracing::info_span!("phase", phase = 1u32, task_index = 2u32).in_scope(|| {
+                tracing::error!("phase 1 failed");
+            });
+        });
+
+        let body = collector.join().unwrap();
+        let spans = body["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap();
+        let by_name = |name: &str| spans.iter().find(|s| s["name"] == name).unwrap();
+        let (run, task, phase) = (by_name("run"), by_name("task"), by_name("phase"));
+
+        assert_eq!(spans.len(), 3);
+        assert_eq!(run["traceId"], task["traceId"]);
+        assert_eq!(task["traceId"], phase["traceId"]);
+        assert!(run.get("parentSpanId").is_none());
+        assert_eq!(task["parentSpanId"], run["spanId"]);
+        assert_eq!(phase["parentSpanId"], task["spanId"]);
+        assert_eq!(
+            task["attributes"],
+            json!([{ "key": "task_index", "value": { "intValue": "2" } }])
+        );
+        assert_eq!(phase["status"], json!({ "code": 2, "message": "phase 1 failed" }));
+        assert!(task.get("status").is_none());
+        assert_eq!(
+            body["resourceSpans"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+            "peal"
+        );
+    }
+    #[test]
+    fn a_stalled_collector_does_not_block_closing_spans_and_flush_gives_up() {
+        // Accepts connections and never answers.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let _held: Vec<_> = listener.incoming().collect();
+        });
+        let endpoint = Endpoint::parse(&format!("http://127.0.0.1:{port}")).unwrap();
+
+        let subscriber = tracing_subscriber::registry().with(OtlpLayer::new(endpoint));
+        let started = Instant::now();
+        tracing::subscriber::with_default(subscriber, || {
+            for task_index in 0..3u32 {
+                tracing::info_span!("run").in_scope(|| {
+                    tracing::info_span!("task", task_index).in_scope(|| {});
+                });
+            }
+        });
+        assert!(started.elapsed() < EXPORT_TIMEOUT / 5, "root spans closed in {:?}", started.elapsed());
+
+        let started = Instant::now();
+        flush_within(Duration::from_millis(300));
+        assert!(started.elapsed() < EXPORT_TIMEOUT / 2, "flush took {:?}", started.elapsed());
+    }
+}
@@ -10,17 +10,62 @@
 //! Prompt strings are built exclusively by the `prompt` module; this
 //! module only passes them as the final positional arg in the argv.
 //!
+//! Text-only calls (Phase 1, triage, task summaries) go through [`run_text_agent`], which
+//! sends the prompt to an HTTP API instead when `agent_backend = "api"`.
+//!
 //! Debug logs never include full prompt text; the prompt argument is logged as `<prompt len=N>` (PRD §13).
+//! Full prompts and agent stdout are logged only on request (`log_prompts`, `log_agent_output`);
+//! see [`log_agent_prompt`] and [`log_agent_output`].
+//!
+//! Stderr of each phase invocation is persisted to
+//! `{state_dir}/runs/{run_id}/artifacts/task-{N}/phase{P}.stderr` (tail, capped) so agent error
+//! messages survive truncated logs; see [`stderr_artifact_path`].
 
-use std::path::Path;
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use tracing::{debug, info, warn};
+use tracing::{debug, debug_span, info, warn};
 
+use crate::api_agent;
 use crate::config::PealConfig;
 use crate::error::PealError;
 use crate::prompt;
+use crate::prompt_middleware::Chain;
+use crate::rate_limit;
+use crate::retries::{self, RetryKind};
+use crate::run_id;
 use crate::subprocess::{self, CommandResult};
+use crate::triage_cache;
+
+/// Bytes of agent stdout logged per call with `log_agent_output = "truncated"`.
+pub const AGENT_OUTPUT_LOG_BYTES: usize = 2000;
+
+/// Log the full prompt of an agent call at info level when `log_prompts` is set. `call`
+/// names the call ("phase 1", "triage").
+pub(crate) fn log_agent_prompt(config: &PealConfig, call: &str, task_index: Option<u32>, prompt: &str) {
+    if config.log_prompts {
+        info!(call, task_index, prompt, "agent prompt");
+    }
+}
+
+/// Log the stdout of an agent call at info level, in full or truncated to
+/// [`AGENT_OUTPUT_LOG_BYTES`], as `log_agent_output` asks. Nothing with `"none"`.
+pub(crate) fn log_agent_output(config: &PealConfig, call: &str, task_index: Option<u32>, stdout: &str) {
+    if let Some(output) = logged_output(&config.log_agent_output, stdout) {
+        info!(call, task_index, output_len = stdout.len(), output, "agent output");
+    }
+}
+
+/// The part of `stdout` logged for a `log_agent_output` mode; `None` for `"none"`.
+fn logged_output<'a>(mode: &str, stdout: &'a str) -> Option<&'a str> {
+    match mode {
+        "full" => Some(stdout),
+        "truncated" => Some(subprocess::truncate_utf8(stdout, AGENT_OUTPUT_LOG_BYTES)),
+        _ => None,
+    }
+}
 
 /// Returns a copy of `args` with the last element replaced by `<prompt len=N>` so logs never contain full prompt text.
 fn args_for_log(args: &[String]) -> Vec<String> {
@@ -32,6 +77,142 @@ fn args_for_log(args: &[String]) -> Vec<String> {
     out
 }
 
+/// Upper bound on stderr bytes written to a per-phase artifact file (the tail is kept).
+const MAX_STDERR_ARTIFACT_BYTES: usize = 64 * 1024;
+
+/// Upper bound on stderr bytes carried in `PealError::PhaseNonZeroExit` and the run
+/// summary (the tail is kept; agents usually print the actual error last).
+pub const STDERR_EXCERPT_BYTES: usize = 2048;
+
+/// Returns the last `max` bytes of `s`, moved forward to a char boundary.
+pub fn stderr_tail(s: &str, max: usize) -> &str {
+    subprocess::tail_utf8(s, max)
+}
+
+/// Path of the stderr artifact for one phase of one task, under this run's
+/// [`run_id::run_dir`].
+pub fn stderr_artifact_path(state_dir: &Path, task_index: u32, phase: u32) -> PathBuf {
+    run_id::run_dir(state_dir)
+        .join("artifacts")
+        .join(format!("task-{task_index}"))
+        .join(format!("phase{phase}.stderr"))
+}
+
+/// Best-effort: write the (capped) stderr of the latest invocation of `phase` for a task.
+/// Empty stderr removes any artifact from an earlier attempt or run.
+fn persist_stderr(config: &PealConfig, phase: u32, task_index: u32, stderr: &str) {
+    let path = stderr_artifact_path(&config.state_dir, task_index, phase);
+    if stderr.is_empty() {
+        let _ = fs::remove_file(&path);
+        return;
+    }
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, stderr_tail(stderr, MAX_STDERR_ARTIFACT_BYTES)));
+    if let Err(e) = written {
+        warn!(phase, task_index, path = %path.display(), err = %e, "failed to persist phase stderr");
+    }
+}
+
+/// Per-file cap on a task context file (`(context: ...)`) in the Phase 1 and 2 prompts; the
+/// head of a longer file is kept.
+pub const CONTEXT_FILE_MAX_BYTES: usize = 32 * 1024;
+
+/// Cap on all of a task's context files together; files past it are left out.
+pub const CONTEXT_TOTAL_MAX_BYTES: usize = 128 * 1024;
+
+/// Read a task's context files for [`prompt::with_context`]: `(path as written, contents)`
+/// in heading order. Relative paths are resolved against `repo_path`. Each file is cut to
+/// [`CONTEXT_FILE_MAX_BYTES`] and the set to [`CONTEXT_TOTAL_MAX_BYTES`], with a note where
+/// text was cut. Unreadable files are skipped with a warning rather than failing the task.
+pub fn task_context(config: &PealConfig, task_index: u32, paths: &[String]) -> Vec<(String, String)> {
+    let mut files = Vec::new();
+    let mut budget = CONTEXT_TOTAL_MAX_BYTES;
+    for path in paths {
+        let full = config.repo_path.join(path);
+        let bytes = match fs::read(&full) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!(task_index, path = %full.display(), err = %e, "skipping unreadable task context file");
+                continue;
+            }
+        };
+        if budget == 0 {
+            warn!(task_index, path = %path, max_bytes = CONTEXT_TOTAL_MAX_BYTES, "task context size limit reached; skipping file");
+            continue;
+        }
+        let text = String::from_utf8_lossy(&bytes);
+        let limit = CONTEXT_FILE_MAX_BYTES.min(budget);
+        let kept = subprocess::truncate_utf8(&text, limit);
+        budget -= kept.len();
+        let contents = if kept.len() < text.len() {
+            debug!(task_index, path = %path, bytes = text.len(), kept = kept.len(), "truncating task context file");
+            format!("{kept}\n... (truncated: {} of {} bytes shown)", kept.len(), text.len())
+        } else {
+            kept.to_owned()
+        };
+        files.push((path.clone(), contents));
+    }
+    files
+}
+
+/// Enforce `max_prompt_tokens` before an agent call: estimate `prompt` (see
+/// [`prompt::PromptEstimate`]) and, when it is over the limit, fail with
+/// `PealError::PromptTooLarge` naming the largest of `sections`, or only warn with
+/// `on_prompt_too_large = "warn"`. `what` names the call in messages ("Phase 2 (task 3)").
+pub(crate) fn check_prompt_size(
+    config: &PealConfig,
+    what: &str,
+    prompt: &str,
+    sections: &[(&'static str, &str)],
+) -> Result<(), PealError> {
+    let Some(max) = config.max_prompt_tokens else {
+        return Ok(());
+    };
+    let estimate = prompt::PromptEstimate::new(prompt, sections);
+    debug!(call = what, estimated_tokens = estimate.total, max, "prompt size estimate");
+    if estimate.total <= max {
+        return Ok(());
+    }
+    let (largest, largest_tokens) = estimate.largest();
+    let err = PealError::PromptTooLarge {
+        what: what.to_owned(),
+        estimated: estimate.total,
+        max,
+        largest: format!("{largest} (~{largest_tokens} tokens)"),
+        breakdown: estimate.breakdown(),
+    };
+    if config.on_prompt_too_large == "warn" {
+        warn!(err = %err, "prompt over max_prompt_tokens; invoking anyway (on_prompt_too_large = warn)");
+        return Ok(());
+    }
+    Err(err)
+}
+
+/// Run a text-only (plan mode) agent call: `agent` with `args`, or, with
+/// `agent_backend = "api"`, `prompt` sent to the HTTP API (see [`crate::api_agent`]).
+pub(crate) fn run_text_agent(
+    agent: &str,
+    args: &[String],
+    config: &PealConfig,
+    prompt: &str,
+    timeout: Duration,
+) -> std::io::Result<CommandResult> {
+    if config.uses_api_backend() {
+        debug!(model = config.api_model(), "sending prompt to api backend");
+        return api_agent::complete(config, prompt, timeout);
+    }
+    crate::container::run_with_limits(
+        config,
+        agent,
+        args,
+        &config.work_dir(),
+        Some(timeout),
+        config.phase_resource_limits(),
+    )
+}
+
 /// Captured output from a successful phase invocation.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PhaseOutput {
@@ -43,7 +224,8 @@ pub struct PhaseOutput {
 
 /// Run Phase 1 (plan creation) for a single task.
 ///
-/// Builds the prompt via `prompt::phase1`, constructs the `agent` argv,
+/// Builds the prompt via `prompt::phase1` (plus the task's `context` files, see
+/// [`task_context`]), constructs the `agent` argv,
 /// invokes the subprocess, and returns the captured stdout as the plan
 /// text.  On timeout or non-zero exit, retries up to `config.phase_retry_count`
 /// times before returning an error.
@@ -52,8 +234,19 @@ pub fn run_phase1(
     config: &PealConfig,
     task_index: u32,
     task_content: &str,
+    context: &[String],
 ) -> Result<PhaseOutput, PealError> {
-    let prompt = prompt::phase1(task_content);
+    let _span = debug_span!("phase", phase = 1u32, task_index).entered();
+    let context = task_context(config, task_index, context);
+    let middleware = Chain::from_config(config)?;
+    let prompt = middleware.prompt("phase 1", prompt::with_context(prompt::phase1(task_content), &context));
+    let context_text: String = context.iter().map(|(_, c)| c.as_str()).collect();
+    check_prompt_size(
+        config,
+        &format!("Phase 1 (task {task_index})"),
+        &prompt,
+        &[("task content", task_content), ("context files", &context_text)],
+    )?;
     let args = phase1_argv(config, &prompt);
     let timeout = Duration::from_secs(config.phase_timeout_sec);
     let agent_str = agent_path.to_string_lossy();
@@ -70,12 +263,19 @@ pub fn run_phase1(
             "invoking phase 1"
         );
         debug!(phase = 1, task_index, args_for_log = ?args_for_log(&args), "phase 1 argv");
+        log_agent_prompt(config, "phase 1", Some(task_index), &prompt);
 
-        let result = subprocess::run_command(&agent_str, &args, &config.repo_path, Some(timeout))
+        rate_limit::count_agent_call(config.max_agent_calls)?;
+        rate_limit::throttle(config.agent_max_requests_per_min);
+        let mut result = run_text_agent(&agent_str, &args, config, &prompt, timeout)
             .map_err(|e| PealError::PhaseSpawnFailed {
                 phase: 1,
                 detail: e.to_string(),
             })?;
+        result.stdout = middleware.output("phase 1", result.stdout);
+
+        persist_stderr(config, 1, task_index, &result.stderr);
+        log_agent_output(config, "phase 1", Some(task_index), &result.stdout);
 
         match check_result(1, task_index, config.phase_timeout_sec, &result) {
             Ok(()) => {
@@ -85,7 +285,7 @@ pub fn run_phase1(
                 });
             }
             Err(e) => {
-                if attempt < max_attempts {
+                if attempt < max_attempts && e.is_retryable_phase_failure() {
                     warn!(
                         phase = 1,
                         task_index,
@@ -94,6 +294,7 @@ pub fn run_phase1(
                         err = %e,
                         "phase 1 failed, retrying"
                     );
+                    retries::record(RetryKind::Phase(1));
                 } else {
                     return Err(e);
                 }
@@ -108,18 +309,19 @@ pub fn run_phase1(
 ///
 /// Layout:
 /// ```text
-/// --print --plan --workspace <repo> --output-format text [--model <m>] <prompt>
+/// [<agent_cmd args>] --print --plan --workspace <repo> --output-format text [--model <m>] <prompt>
 /// ```
 /// `--model` is only added when `config.model` is set; otherwise omitted so the Cursor CLI uses its default (Auto).
 fn phase1_argv(config: &PealConfig, prompt: &str) -> Vec<String> {
-    let mut args = vec![
+    let mut args = config.agent_args();
+    args.extend([
         "--print".to_owned(),
         "--plan".to_owned(),
         "--workspace".to_owned(),
-        config.repo_path.to_string_lossy().into_owned(),
+        config.work_dir().to_string_lossy().into_owned(),
         "--output-format".to_owned(),
         "text".to_owned(),
-    ];
+    ]);
 
     if let Some(model) = &config.model {
         args.push("--model".to_owned());
@@ -132,17 +334,109 @@ fn phase1_argv(config: &PealConfig, prompt: &str) -> Vec<String> {
 
 /// Run Phase 2 (plan execution) for a single task.
 ///
-/// Builds the prompt via `prompt::phase2`, constructs the `agent` argv
+/// Builds the prompt via `prompt::phase2` (plus the task's `context` files, see
+/// [`task_context`]), constructs the `agent` argv
 /// (no `--plan` flag, includes `--sandbox`), invokes the subprocess, and
 /// returns the captured output.  On timeout or non-zero exit, retries up to
 /// `config.phase_retry_count` times before returning an error.
+///
+/// With `done_marker` set, the prompt asks the agent to end its answer with the marker, and an
+/// answer without it is run once more (`on_missing_done_marker = "retry"`) or fails with
+/// `PealError::Phase2DoneMarkerMissing`.
 pub fn run_phase2(
     agent_path: &Path,
     config: &PealConfig,
     task_index: u32,
     plan_text: &str,
+    context: &[String],
 ) -> Result<PhaseOutput, PealError> {
-    let prompt = prompt::phase2(plan_text);
+    run_phase2_prompt(agent_path, config, task_index, plan_text, context, None)
+}
+
+/// Run Phase 2 again after the first attempt changed no files (`phase2_change_check =
+/// "retry"`), with `prompt::phase2_noop_retry` and the change check's `detail`. Otherwise as
+/// [`run_phase2`].
+pub fn run_phase2_noop_retry(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    plan_text: &str,
+    context: &[String],
+    detail: &str,
+) -> Result<PhaseOutput, PealError> {
+    run_phase2_prompt(agent_path, config, task_index, plan_text, context, Some(detail))
+}
+
+fn run_phase2_prompt(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    plan_text: &str,
+    context: &[String],
+    noop_detail: Option<&str>,
+) -> Result<PhaseOutput, PealError> {
+    let Some(marker) = done_marker(config, task_index) else {
+        return invoke_phase2(agent_path, config, task_index, plan_text, context, noop_detail, None);
+    };
+    let output = invoke_phase2(agent_path, config, task_index, plan_text, context, noop_detail, Some((&marker, false)))?;
+    if has_done_marker(&output.stdout, &marker) {
+        return Ok(output);
+    }
+    let err = PealError::Phase2DoneMarkerMissing { task_index, marker: marker.clone() };
+    if config.on_missing_done_marker != "retry" {
+        return Err(err);
+    }
+    warn!(phase = 2, task_index, err = %err, "phase 2 answer has no done marker, retrying once");
+    retries::record(RetryKind::Phase(2));
+    let output = invoke_phase2(agent_path, config, task_index, plan_text, context, noop_detail, Some((&marker, true)))?;
+    if has_done_marker(&output.stdout, &marker) { Ok(output) } else { Err(err) }
+}
+
+/// `done_marker` for a task (`{{TASK_INDEX}}` expanded); `None` when unset.
+fn done_marker(config: &PealConfig, task_index: u32) -> Option<String> {
+    let marker = config.done_marker.as_deref()?;
+    Some(marker.trim().replace("{{TASK_INDEX}}", &task_index.to_string()))
+}
+
+/// Whether the agent's Phase 2 answer (the `stream-json` result text when there is one) has a
+/// line that is exactly `marker`, surrounding whitespace aside.
+fn has_done_marker(stdout: &str, marker: &str) -> bool {
+    let answer = parse_phase2_transcript(stdout).and_then(|t| t.result_text);
+    answer.as_deref().unwrap_or(stdout).lines().any(|line| line.trim() == marker)
+}
+
+/// One Phase 2 invocation (with `phase_retry_count` retries). `marker` is the done marker to
+/// ask for and whether the previous answer lacked it.
+#[allow(clippy::too_many_arguments)]
+fn invoke_phase2(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    plan_text: &str,
+    context: &[String],
+    noop_detail: Option<&str>,
+    marker: Option<(&str, bool)>,
+) -> Result<PhaseOutput, PealError> {
+    let _span = debug_span!("phase", phase = 2u32, task_index).entered();
+    let context = task_context(config, task_index, context);
+    let prompt = match noop_detail {
+        None => prompt::phase2(plan_text),
+        Some(detail) => prompt::phase2_noop_retry(plan_text, detail),
+    };
+    let prompt = prompt::with_context(prompt, &context);
+    let prompt = match marker {
+        Some((marker, missing_before)) => prompt::with_done_marker(prompt, marker, missing_before),
+        None => prompt,
+    };
+    let middleware = Chain::from_config(config)?;
+    let prompt = middleware.prompt("phase 2", prompt);
+    let context_text: String = context.iter().map(|(_, c)| c.as_str()).collect();
+    check_prompt_size(
+        config,
+        &format!("Phase 2 (task {task_index})"),
+        &prompt,
+        &[("plan text", plan_text), ("context files", &context_text)],
+    )?;
     let args = phase2_argv(config, &prompt);
     let timeout = Duration::from_secs(config.phase_timeout_sec);
     let agent_str = agent_path.to_string_lossy();
@@ -159,12 +453,26 @@ pub fn run_phase2(
             "invoking phase 2"
         );
         debug!(phase = 2, task_index, args_for_log = ?args_for_log(&args), "phase 2 argv");
-
-        let result = subprocess::run_command(&agent_str, &args, &config.repo_path, Some(timeout))
+        log_agent_prompt(config, "phase 2", Some(task_index), &prompt);
+
+        rate_limit::count_agent_call(config.max_agent_calls)?;
+        rate_limit::throttle(config.agent_max_requests_per_min);
+        let mut result = crate::container::run_with_limits(
+            config,
+            &agent_str,
+            &args,
+            &config.work_dir(),
+            Some(timeout),
+            config.phase_resource_limits(),
+        )
             .map_err(|e| PealError::PhaseSpawnFailed {
                 phase: 2,
                 detail: e.to_string(),
             })?;
+        result.stdout = middleware.output("phase 2", result.stdout);
+
+        persist_stderr(config, 2, task_index, &result.stderr);
+        log_agent_output(config, "phase 2", Some(task_index), &result.stdout);
 
         match check_result(2, task_index, config.phase_timeout_sec, &result) {
             Ok(()) => {
@@ -174,7 +482,7 @@ pub fn run_phase2(
                 });
             }
             Err(e) => {
-                if attempt < max_attempts {
+                if attempt < max_attempts && e.is_retryable_phase_failure() {
                     warn!(
                         phase = 2,
                         task_index,
@@ -183,6 +491,7 @@ pub fn run_phase2(
                         err = %e,
                         "phase 2 failed, retrying"
                     );
+                    retries::record(RetryKind::Phase(2));
                 } else {
                     return Err(e);
                 }
@@ -193,21 +502,130 @@ pub fn run_phase2(
     unreachable!("retry loop returns or errs")
 }
 
+/// Ask the agent to fix a failed `peal-verify` command of a task (see
+/// `runner::run_task_verify`).
+///
+/// One invocation with the Phase 2 argv and `prompt::verify_fix`; no retries, since the
+/// caller re-runs the command and asks again up to `max_address_rounds` times. Stderr is
+/// persisted and logged as Phase 2's.
+pub fn run_verify_fix(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    command: &str,
+    output: &str,
+) -> Result<PhaseOutput, PealError> {
+    let _span = debug_span!("phase", phase = 2u32, task_index).entered();
+    let middleware = Chain::from_config(config)?;
+    let prompt = middleware.prompt("verify fix", prompt::verify_fix(command, output));
+    check_prompt_size(
+        config,
+        &format!("verify fix (task {task_index})"),
+        &prompt,
+        &[("verify output", output)],
+    )?;
+    let args = phase2_argv(config, &prompt);
+    let agent_str = agent_path.to_string_lossy();
+    info!(phase = 2, task_index, command, "invoking agent to fix failed verify command");
+    log_agent_prompt(config, "verify fix", Some(task_index), &prompt);
+
+    rate_limit::count_agent_call(config.max_agent_calls)?;
+    rate_limit::throttle(config.agent_max_requests_per_min);
+    let mut result = crate::container::run_with_limits(
+        config,
+        &agent_str,
+        &args,
+        &config.work_dir(),
+        Some(Duration::from_secs(config.phase_timeout_sec)),
+        config.phase_resource_limits(),
+    )
+        .map_err(|e| PealError::PhaseSpawnFailed {
+            phase: 2,
+            detail: e.to_string(),
+        })?;
+    result.stdout = middleware.output("verify fix", result.stdout);
+
+    persist_stderr(config, 2, task_index, &result.stderr);
+    log_agent_output(config, "verify fix", Some(task_index), &result.stdout);
+    check_result(2, task_index, config.phase_timeout_sec, &result)?;
+    Ok(PhaseOutput {
+        stdout: result.stdout,
+        stderr: result.stderr,
+    })
+}
+
+/// Run Phase 2.5 for a task: ask the agent to resolve the conflict markers a sparse
+/// worktree's merge left in `files` (see [`crate::sparse`]), in the repo itself. One attempt;
+/// the caller checks the files and decides whether to ask again.
+pub fn run_phase2_5(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    files: &[String],
+    hunks: &str,
+) -> Result<PhaseOutput, PealError> {
+    let _span = debug_span!("phase", phase = "2.5", task_index).entered();
+    let middleware = Chain::from_config(config)?;
+    let prompt = middleware.prompt("merge conflict", prompt::merge_conflict(files, hunks));
+    check_prompt_size(
+        config,
+        &format!("merge conflict (task {task_index})"),
+        &prompt,
+        &[("conflicted hunks", hunks)],
+    )?;
+    let args = phase2_argv(config, &prompt);
+    let agent_str = agent_path.to_string_lossy();
+    info!(phase = 2, task_index, files = ?files, "invoking agent to resolve merge conflicts (phase 2.5)");
+    log_agent_prompt(config, "merge conflict", Some(task_index), &prompt);
+
+    rate_limit::count_agent_call(config.max_agent_calls)?;
+    rate_limit::throttle(config.agent_max_requests_per_min);
+    let mut result = crate::container::run_with_limits(
+        config,
+        &agent_str,
+        &args,
+        &config.work_dir(),
+        Some(Duration::from_secs(config.phase_timeout_sec)),
+        config.phase_resource_limits(),
+    )
+        .map_err(|e| PealError::PhaseSpawnFailed {
+            phase: 2,
+            detail: e.to_string(),
+        })?;
+    result.stdout = middleware.output("merge conflict", result.stdout);
+
+    persist_stderr(config, 2, task_index, &result.stderr);
+    log_agent_output(config, "merge conflict", Some(task_index), &result.stdout);
+    check_result(2, task_index, config.phase_timeout_sec, &result)?;
+    Ok(PhaseOutput {
+        stdout: result.stdout,
+        stderr: result.stderr,
+    })
+}
+
 /// Build the argv (excluding the program name) for a Phase 2 invocation.
 ///
 /// Layout:
 /// ```text
-/// --print --workspace <repo> --sandbox <sandbox> [--model <m>] <prompt>
+/// [<agent_cmd args>] --print --workspace <repo> --sandbox <sandbox> [--output-format stream-json] [--model <m>] <prompt>
 /// ```
 /// `--model` is only added when `config.model` is set; otherwise omitted for Cursor CLI default (Auto).
+/// `--output-format stream-json` is added when `phase2_change_check` is on, so the transcript
+/// lists the agent's file edits (see [`parse_phase2_transcript`]).
 fn phase2_argv(config: &PealConfig, prompt: &str) -> Vec<String> {
-    let mut args = vec![
+    let mut args = config.agent_args();
+    args.extend([
         "--print".to_owned(),
         "--workspace".to_owned(),
-        config.repo_path.to_string_lossy().into_owned(),
+        config.work_dir().to_string_lossy().into_owned(),
         "--sandbox".to_owned(),
         config.sandbox.clone(),
-    ];
+    ]);
+
+    if config.phase2_change_check != "off" {
+        args.push("--output-format".to_owned());
+        args.push("stream-json".to_owned());
+    }
 
     if let Some(model) = &config.model {
         args.push("--model".to_owned());
@@ -232,7 +650,15 @@ pub fn run_phase3(
     stet_output: &str,
     suggestions: Option<&str>,
 ) -> Result<PhaseOutput, PealError> {
-    let prompt = prompt::phase3_with_suggestions(stet_output, suggestions);
+    let _span = debug_span!("phase", phase = 3u32, task_index).entered();
+    let middleware = Chain::from_config(config)?;
+    let prompt = middleware.prompt("phase 3", prompt::phase3_with_suggestions(stet_output, suggestions));
+    check_prompt_size(
+        config,
+        &format!("Phase 3 (task {task_index})"),
+        &prompt,
+        &[("stet output", stet_output), ("suggestions", suggestions.unwrap_or(""))],
+    )?;
     let args = phase3_argv(config, &prompt);
     let timeout = Duration::from_secs(config.phase_timeout_sec);
 
@@ -251,12 +677,26 @@ pub fn run_phase3(
             "invoking phase 3"
         );
         debug!(phase = 3, task_index, args_for_log = ?args_for_log(&args), "phase 3 argv");
-
-        let result = subprocess::run_command(&agent_str, &args, &config.repo_path, Some(timeout))
+        log_agent_prompt(config, "phase 3", Some(task_index), &prompt);
+
+        rate_limit::count_agent_call(config.max_agent_calls)?;
+        rate_limit::throttle(config.agent_max_requests_per_min);
+        let mut result = crate::container::run_with_limits(
+            config,
+            &agent_str,
+            &args,
+            &config.work_dir(),
+            Some(timeout),
+            config.phase_resource_limits(),
+        )
             .map_err(|e| PealError::PhaseSpawnFailed {
                 phase: 3,
                 detail: e.to_string(),
             })?;
+        result.stdout = middleware.output("phase 3", result.stdout);
+
+        persist_stderr(config, 3, task_index, &result.stderr);
+        log_agent_output(config, "phase 3", Some(task_index), &result.stdout);
 
         match check_result(3, task_index, config.phase_timeout_sec, &result) {
             Ok(()) => {
@@ -266,7 +706,7 @@ pub fn run_phase3(
                 });
             }
             Err(e) => {
-                if attempt < max_attempts {
+                if attempt < max_attempts && e.is_retryable_phase_failure() {
                     warn!(
                         phase = 3,
                         task_index,
@@ -275,6 +715,7 @@ pub fn run_phase3(
                         err = %e,
                         "phase 3 failed, retrying"
                     );
+                    retries::record(RetryKind::Phase(3));
                 } else {
                     return Err(e);
                 }
@@ -285,19 +726,178 @@ pub fn run_phase3(
     unreachable!("retry loop returns or errs")
 }
 
+/// Agent health check (`config.agent_health_check`): one Phase 1-style invocation with a
+/// trivial prompt, bounded by `config.agent_health_check_timeout_sec`, before any task runs.
+///
+/// Succeeds when the agent exits 0 and its stdout contains `prompt::HEALTH_CHECK_TOKEN`.
+/// No retries: a misconfigured agent should fail the run immediately.
+pub fn run_health_check(agent_path: &Path, config: &PealConfig) -> Result<(), PealError> {
+    let args = phase1_argv(config, &prompt::health_check());
+    let timeout = Duration::from_secs(config.agent_health_check_timeout_sec);
+    let agent_str = agent_path.to_string_lossy();
+    let fail = |detail: String| PealError::AgentHealthCheckFailed { detail };
+
+    info!(agent = %agent_str, timeout_sec = config.agent_health_check_timeout_sec, "running agent health check");
+    debug!(args_for_log = ?args_for_log(&args), "health check argv");
+
+    rate_limit::count_agent_call(config.max_agent_calls)?;
+    rate_limit::throttle(config.agent_max_requests_per_min);
+    let result = crate::container::run_with_limits(
+        config,
+        &agent_str,
+        &args,
+        &config.work_dir(),
+        Some(timeout),
+        config.phase_resource_limits(),
+    )
+    .map_err(|e| fail(format!("failed to start '{agent_str}': {e}")))?;
+
+    if result.timed_out {
+        return Err(fail(format!(
+            "no response within {}s",
+            config.agent_health_check_timeout_sec
+        )));
+    }
+    if !result.success() {
+        if let Some(detail) = agent_auth_failure(&result) {
+            return Err(PealError::AgentAuthFailed {
+                call: "health check".to_owned(),
+                detail,
+            });
+        }
+        let stderr = stderr_tail(&result.stderr, STDERR_EXCERPT_BYTES);
+        let stderr = stderr.trim();
+        return Err(fail(if stderr.is_empty() {
+            format!("exited with code {:?}", result.exit_code)
+        } else {
+            format!("exited with code {:?}: {stderr}", result.exit_code)
+        }));
+    }
+    if !result.stdout.contains(prompt::HEALTH_CHECK_TOKEN) {
+        return Err(fail(format!(
+            "unexpected response (expected it to contain {}): {:?}",
+            prompt::HEALTH_CHECK_TOKEN,
+            stderr_tail(result.stdout.trim(), 200)
+        )));
+    }
+    info!("agent health check passed");
+    Ok(())
+}
+
+/// Ask the agent for a 2–3 sentence summary of a finished task (opt-in via
+/// `config.summarize_tasks`).
+///
+/// Uses the Phase 1 argv (plan mode, so the agent does not edit files) with the
+/// prompt from `prompt::task_summary`. Best-effort: a single attempt; on spawn
+/// failure, timeout, non-zero exit, or empty output logs a warning and returns `None`.
+pub fn run_task_summary(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    phase2_stdout: &str,
+    diff: &str,
+) -> Option<String> {
+    let middleware = match Chain::from_config(config) {
+        Ok(chain) => chain,
+        Err(e) => {
+            warn!(task_index, err = %e, "task summary skipped");
+            return None;
+        }
+    };
+    let prompt = middleware.prompt("task summary", prompt::task_summary(phase2_stdout, diff));
+    let args = phase1_argv(config, &prompt);
+    let timeout = Duration::from_secs(config.phase_timeout_sec);
+    let agent_str = agent_path.to_string_lossy();
+
+    info!(task_index, agent = %agent_str, "invoking task summary");
+    debug!(task_index, args_for_log = ?args_for_log(&args), "task summary argv");
+    log_agent_prompt(config, "task summary", Some(task_index), &prompt);
+
+    if let Err(e) = rate_limit::count_agent_call(config.max_agent_calls) {
+        warn!(task_index, err = %e, "task summary skipped");
+        return None;
+    }
+    rate_limit::throttle(config.agent_max_requests_per_min);
+    let mut result = match run_text_agent(&agent_str, &args, config, &prompt, timeout) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!(task_index, err = %e, "task summary failed to start");
+            return None;
+        }
+    };
+    result.stdout = middleware.output("task summary", result.stdout);
+    log_agent_output(config, "task summary", Some(task_index), &result.stdout);
+    if !result.success() {
+        warn!(
+            task_index,
+            exit_code = ?result.exit_code,
+            timed_out = result.timed_out,
+            "task summary failed"
+        );
+        return None;
+    }
+    let summary = result.stdout.trim();
+    if summary.is_empty() {
+        warn!(task_index, "task summary was empty");
+        return None;
+    }
+    Some(summary.to_owned())
+}
+
+/// Agent and config for the triage call: `triage_agent_cmd` replaces the agent (and its
+/// leading arguments) and `triage_model` replaces `model` and `api_model`; unset keys fall
+/// back to the main agent.
+fn triage_target<'a>(
+    agent_path: &Path,
+    config: &'a PealConfig,
+) -> Result<(PathBuf, Cow<'a, PealConfig>), PealError> {
+    let mut target = Cow::Borrowed(config);
+    let agent = match &config.triage_agent_cmd {
+        Some(cmd) => {
+            target.to_mut().agent_cmd = cmd.clone();
+            crate::container::agent_path(config, cmd)?
+        }
+        None => agent_path.to_path_buf(),
+    };
+    if let Some(model) = &config.triage_model {
+        let target = target.to_mut();
+        target.model = Some(model.clone());
+        target.api_model = Some(model.clone());
+    }
+    Ok((agent, target))
+}
+
 /// Run the triage step: send stet output to the agent with "Anything to address from this review?"
-/// Same argv and timeout as Phase 3. Used by Phase 3 auto-dismiss to get a free-form triage response.
+/// Same argv and timeout as Phase 3, run with `triage_agent_cmd` / `triage_model` when set.
+/// Used by Phase 3 auto-dismiss to get a free-form triage response.
 /// Retries on timeout or non-zero exit up to phase_3_retry_count.min(2) times; after retries exhausted,
-/// timeout → Err, non-zero → Ok(empty stdout) as before.
+/// timeout → Err, non-zero → Ok(empty stdout) as before. Successful answers are cached by stet
+/// output (see [`crate::triage_cache`]), so identical findings do not cost another agent call.
 pub fn run_phase3_triage(
     agent_path: &Path,
     config: &PealConfig,
     stet_output: &str,
 ) -> Result<PhaseOutput, PealError> {
-    let prompt = prompt::triage_prompt(stet_output);
+    let _span = debug_span!("phase", phase = 3u32, step = "triage").entered();
+    let (triage_agent, config) = triage_target(agent_path, config)?;
+    let config = config.as_ref();
+    let middleware = Chain::from_config(config)?;
+    let prompt = middleware.prompt("triage", prompt::triage_prompt(stet_output));
+    check_prompt_size(config, "Phase 3 triage", &prompt, &[("stet output", stet_output)])?;
     let args = phase3_argv(config, &prompt);
     let timeout = Duration::from_secs(config.phase_timeout_sec);
-    let agent_str = agent_path.to_string_lossy();
+    let agent_str = triage_agent.to_string_lossy();
+    let cache_key = triage_cache::enabled(config).then(|| triage_cache::key(&triage_agent, config, stet_output));
+    if let Some(key) = &cache_key {
+        let ttl = Duration::from_secs(config.triage_cache_ttl_sec);
+        if let Some(stdout) = triage_cache::lookup(&config.state_dir, key, ttl) {
+            info!(key = %key, "phase 3 triage answered from cache");
+            return Ok(PhaseOutput {
+                stdout,
+                stderr: String::new(),
+            });
+        }
+    }
     let effective_retries = config.phase_3_retry_count.min(2);
     let max_attempts = 1 + effective_retries;
 
@@ -310,12 +910,17 @@ pub fn run_phase3_triage(
             "invoking phase 3 triage"
         );
         debug!(args_for_log = ?args_for_log(&args), "phase 3 triage argv");
+        log_agent_prompt(config, "triage", None, &prompt);
 
-        let result = subprocess::run_command(&agent_str, &args, &config.repo_path, Some(timeout))
+        rate_limit::count_agent_call(config.max_agent_calls)?;
+        rate_limit::throttle(config.agent_max_requests_per_min);
+        let mut result = run_text_agent(&agent_str, &args, config, &prompt, timeout)
             .map_err(|e| PealError::PhaseSpawnFailed {
                 phase: 3,
                 detail: e.to_string(),
             })?;
+        result.stdout = middleware.output("triage", result.stdout);
+        log_agent_output(config, "triage", None, &result.stdout);
 
         if result.timed_out {
             if attempt < max_attempts {
@@ -324,6 +929,7 @@ pub fn run_phase3_triage(
                     max_attempts,
                     "phase 3 triage timed out, retrying"
                 );
+                retries::record(RetryKind::Phase(3));
             } else {
                 warn!("phase 3 triage timed out");
                 return Err(PealError::PhaseTimedOut {
@@ -334,6 +940,12 @@ pub fn run_phase3_triage(
             continue;
         }
         if !result.success() {
+            if let Some(detail) = agent_auth_failure(&result) {
+                return Err(PealError::AgentAuthFailed {
+                    call: "triage".to_owned(),
+                    detail,
+                });
+            }
             if attempt < max_attempts {
                 warn!(
                     exit_code = ?result.exit_code,
@@ -341,6 +953,7 @@ pub fn run_phase3_triage(
                     max_attempts,
                     "phase 3 triage exited with non-zero code, retrying"
                 );
+                retries::record(RetryKind::Phase(3));
             } else {
                 warn!(
                     exit_code = ?result.exit_code,
@@ -354,6 +967,9 @@ pub fn run_phase3_triage(
             continue;
         }
 
+        if let Some(key) = &cache_key {
+            triage_cache::store(&config.state_dir, key, &result.stdout);
+        }
         return Ok(PhaseOutput {
             stdout: result.stdout,
             stderr: result.stderr,
@@ -367,17 +983,18 @@ pub fn run_phase3_triage(
 ///
 /// Layout:
 /// ```text
-/// --print --workspace <repo> --sandbox <sandbox> [--model <m>] <prompt>
+/// [<agent_cmd args>] --print --workspace <repo> --sandbox <sandbox> [--model <m>] <prompt>
 /// ```
 /// `--model` is only added when `config.model` is set; otherwise omitted for Cursor CLI default (Auto).
 fn phase3_argv(config: &PealConfig, prompt: &str) -> Vec<String> {
-    let mut args = vec![
+    let mut args = config.agent_args();
+    args.extend([
         "--print".to_owned(),
         "--workspace".to_owned(),
-        config.repo_path.to_string_lossy().into_owned(),
+        config.work_dir().to_string_lossy().into_owned(),
         "--sandbox".to_owned(),
         config.sandbox.clone(),
-    ];
+    ]);
 
     if let Some(model) = &config.model {
         args.push("--model".to_owned());
@@ -388,7 +1005,166 @@ fn phase3_argv(config: &PealConfig, prompt: &str) -> Vec<String> {
     args
 }
 
-/// Validate a `CommandResult`, returning an error on timeout or non-zero exit.
+/// What a Phase 2 `stream-json` transcript says the agent did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Phase2Transcript {
+    /// Paths of files the agent's write/edit/delete tool calls touched, as reported (absolute
+    /// or workspace-relative), sorted and deduplicated. Failed tool calls are left out.
+    pub edited_files: Vec<String>,
+    /// Final answer from the `result` event, if there is one.
+    pub result_text: Option<String>,
+}
+
+/// Parse Cursor CLI `stream-json` output: one JSON event per line, with file edits reported as
+/// `{"type": "tool_call", "subtype": "completed", "tool_call": {"editToolCall": {"args": {"path": ...}, "result": ...}}}`.
+/// Returns `None` when `stdout` has no JSON events (e.g. `--output-format text`).
+pub fn parse_phase2_transcript(stdout: &str) -> Option<Phase2Transcript> {
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .filter_map(|l| serde_json::from_str::<serde_json::Value>(l.trim()).ok())
+        .filter(|v| v.get("type").and_then(serde_json::Value::as_str).is_some())
+        .collect();
+    if events.is_empty() {
+        return None;
+    }
+    let mut transcript = Phase2Transcript::default();
+    for event in &events {
+        match event.get("type").and_then(serde_json::Value::as_str) {
+            Some("result") => {
+                transcript.result_text = event
+                    .get("result")
+                    .and_then(serde_json::Value::as_str)
+                    .map(str::to_owned);
+            }
+            Some("tool_call")
+                if event.get("subtype").and_then(serde_json::Value::as_str) == Some("completed") =>
+            {
+                let Some(calls) = event.get("tool_call").and_then(serde_json::Value::as_object) else {
+                    continue;
+                };
+                for (name, call) in calls {
+                    let name = name.to_ascii_lowercase();
+                    if !["write", "edit", "delete"].iter().any(|k| name.contains(k)) {
+                        continue;
+                    }
+                    let failed = call
+                        .get("result")
+                        .is_some_and(|r| r.get("error").is_some() || r.get("rejected").is_some());
+                    let path = ["/args/path", "/args/file_path", "/args/target_file"]
+                        .iter()
+                        .find_map(|p| call.pointer(p).and_then(serde_json::Value::as_str));
+                    if let (false, Some(path)) = (failed, path) {
+                        transcript.edited_files.push(path.to_owned());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    transcript.edited_files.sort();
+    transcript.edited_files.dedup();
+    Some(transcript)
+}
+
+/// Phrases in a Phase 2 answer that mean the agent declined or found nothing to do, matched
+/// case-insensitively by [`refusal_phrase`].
+const REFUSAL_PHRASES: &[&str] = &[
+    "i cannot",
+    "i can't",
+    "i'm unable",
+    "i am unable",
+    "already implemented",
+    "already exists",
+    "already in place",
+    "no changes are needed",
+    "no changes needed",
+    "nothing to change",
+];
+
+/// The first [`REFUSAL_PHRASES`] entry in `text` (a Phase 2 answer), if any.
+pub fn refusal_phrase(text: &str) -> Option<&'static str> {
+    let text = text.to_lowercase().replace('\u{2019}', "'");
+    REFUSAL_PHRASES.iter().copied().find(|p| text.contains(p))
+}
+
+/// Error message from a Cursor CLI JSON result or error event on the last non-empty line of
+/// `output` (`{"type": "result", "is_error": true, "result": "..."}` or
+/// `{"type": "error", "message": "..."}`); `None` for anything else, including plain text.
+fn agent_reported_error(output: &str) -> Option<String> {
+    let line = output.lines().map(str::trim).rfind(|l| !l.is_empty())?;
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    let is_error = value.get("is_error").and_then(serde_json::Value::as_bool) == Some(true)
+        || value.get("type").and_then(serde_json::Value::as_str) == Some("error");
+    if !is_error {
+        return None;
+    }
+    let message = ["/error/message", "/error", "/message", "/result"]
+        .iter()
+        .find_map(|p| value.pointer(p).and_then(serde_json::Value::as_str))
+        .unwrap_or("(no message)");
+    Some(message.to_owned())
+}
+
+/// Cursor JSON error codes (`error.code`, `code`, `subtype`) meaning the agent is not logged in.
+const AUTH_ERROR_CODES: &[&str] = &[
+    "unauthenticated",
+    "unauthorized",
+    "not_authenticated",
+    "auth_required",
+    "authentication_required",
+    "authentication_error",
+    "invalid_api_key",
+    "token_expired",
+    "expired_token",
+];
+
+/// Lowercase output fragments printed by the Cursor CLI (or an HTTP API) when it is not logged
+/// in or its token expired.
+const AUTH_ERROR_SIGNATURES: &[&str] = &[
+    "not logged in",
+    "not authenticated",
+    "unauthenticated",
+    "authentication required",
+    "authentication failed",
+    "please log in",
+    "please login",
+    "agent login",
+    "invalid api key",
+    "token expired",
+    "token has expired",
+    "expired token",
+    "session expired",
+    "http 401",
+    "401 unauthorized",
+];
+
+/// The line of a failed agent call's stdout or stderr that shows it is not authenticated: a
+/// JSON event with an auth error code (`AUTH_ERROR_CODES`) or a known message
+/// (`AUTH_ERROR_SIGNATURES`). `None` when nothing matches. Only meaningful for calls that
+/// already failed; a successful call's output may quote these phrases freely.
+pub(crate) fn agent_auth_failure(result: &CommandResult) -> Option<String> {
+    let lines = || result.stdout.lines().chain(result.stderr.lines()).map(str::trim);
+    let by_code = lines().find(|line| {
+        serde_json::from_str::<serde_json::Value>(line).is_ok_and(|v| {
+            ["/error/code", "/code", "/subtype"].iter().any(|p| {
+                v.pointer(p)
+                    .and_then(serde_json::Value::as_str)
+                    .is_some_and(|code| AUTH_ERROR_CODES.contains(&code.to_ascii_lowercase().as_str()))
+            })
+        })
+    });
+    let line = by_code.or_else(|| {
+        lines().find(|line| {
+            let lower = line.to_ascii_lowercase();
+            AUTH_ERROR_SIGNATURES.iter().any(|sig| lower.contains(sig))
+        })
+    })?;
+    Some(subprocess::truncate_utf8(line, 500).to_owned())
+}
+
+/// Classify a `CommandResult`, returning an error on timeout, resource limit, an
+/// authentication failure, an error the agent reported, death by signal, or non-zero exit
+/// (checked in that order).
 fn check_result(
     phase: u32,
     task_index: u32,
@@ -400,6 +1176,42 @@ fn check_result(
         return Err(PealError::PhaseTimedOut { phase, timeout_sec });
     }
 
+    if let Some(limit) = result.limit_exceeded {
+        warn!(phase, task_index, %limit, "phase exceeded resource limit");
+        return Err(PealError::PhaseResourceLimitExceeded {
+            phase,
+            limit: limit.to_string(),
+        });
+    }
+
+    let reported =
+        agent_reported_error(&result.stdout).or_else(|| agent_reported_error(&result.stderr));
+
+    if let Some(detail) = (reported.is_some() || !result.success())
+        .then(|| agent_auth_failure(result))
+        .flatten()
+    {
+        warn!(phase, task_index, exit_code = ?result.exit_code, %detail, "agent is not authenticated");
+        return Err(PealError::AgentAuthFailed {
+            call: format!("phase {phase}"),
+            detail,
+        });
+    }
+
+    if let Some(message) = reported {
+        warn!(phase, task_index, exit_code = ?result.exit_code, %message, "agent reported an error");
+        return Err(PealError::AgentReportedError { phase, message });
+    }
+
+    if let Some(signal) = result.signal {
+        warn!(phase, task_index, signal, "phase killed by signal");
+        return Err(PealError::PhaseKilledBySignal {
+            phase,
+            signal,
+            stderr: stderr_tail(&result.stderr, STDERR_EXCERPT_BYTES).to_owned(),
+        });
+    }
+
     if !result.success() {
         warn!(
             phase,
@@ -411,7 +1223,7 @@ fn check_result(
         return Err(PealError::PhaseNonZeroExit {
             phase,
             exit_code: result.exit_code,
-            stderr: result.stderr.clone(),
+            stderr: stderr_tail(&result.stderr, STDERR_EXCERPT_BYTES).to_owned(),
         });
     }
 
@@ -443,6 +1255,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -460,6 +1274,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         }
     }
 
@@ -491,6 +1372,16 @@ mod tests {
         assert_eq!(got.len(), 3);
     }
 
+    #[test]
+    fn logged_output_follows_log_agent_output_mode() {
+        let long = "é".repeat(AGENT_OUTPUT_LOG_BYTES);
+        assert_eq!(logged_output("none", &long), None);
+        assert_eq!(logged_output("full", &long), Some(long.as_str()));
+        let truncated = logged_output("truncated", &long).unwrap();
+        assert!(truncated.len() <= AGENT_OUTPUT_LOG_BYTES && long.starts_with(truncated));
+        assert_eq!(logged_output("truncated", "short"), Some("short"));
+    }
+
     // -- argv construction tests --
 
     #[test]
@@ -533,6 +1424,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn argv_starts_with_agent_cmd_args() {
+        let mut config = test_config(None);
+        config.agent_cmd = "npx cursor-agent --some-flag".to_owned();
+
+        let phase1 = phase1_argv(&config, "Plan.");
+        assert_eq!(phase1[..3], ["cursor-agent", "--some-flag", "--print"]);
+        assert_eq!(phase2_argv(&config, "Do.")[..3], ["cursor-agent", "--some-flag", "--print"]);
+        assert_eq!(phase3_argv(&config, "Fix.")[..3], ["cursor-agent", "--some-flag", "--print"]);
+        assert_eq!(phase1.last().unwrap(), "Plan.");
+    }
+
     #[test]
     fn argv_prompt_is_last_arg() {
         let config = test_config(Some("gpt-5"));
@@ -555,6 +1458,8 @@ mod tests {
             stderr: String::new(),
             exit_code: Some(0),
             timed_out: false,
+            limit_exceeded: None,
+            signal: None,
         };
         assert!(check_result(1, 1, 300, &result).is_ok());
     }
@@ -566,6 +1471,8 @@ mod tests {
             stderr: String::new(),
             exit_code: None,
             timed_out: true,
+            limit_exceeded: None,
+            signal: None,
         };
         let err = check_result(1, 1, 300, &result).unwrap_err();
         match err {
@@ -584,6 +1491,8 @@ mod tests {
             stderr: "something went wrong".to_owned(),
             exit_code: Some(1),
             timed_out: false,
+            limit_exceeded: None,
+            signal: None,
         };
         let err = check_result(1, 1, 300, &result).unwrap_err();
         match err {
@@ -600,6 +1509,241 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_result_nonzero_exit_keeps_stderr_tail() {
+        let long = format!("{}FINAL ERROR", "noise ".repeat(2000));
+        let result = CommandResult {
+            stdout: String::new(),
+            stderr: long,
+            exit_code: Some(2),
+            timed_out: false,
+            limit_exceeded: None,
+            signal: None,
+        };
+        let err = check_result(2, 1, 300, &result).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("FINAL ERROR"), "message should include stderr tail: {msg}");
+        match err {
+            PealError::PhaseNonZeroExit { stderr, .. } => {
+                assert!(stderr.len() <= STDERR_EXCERPT_BYTES);
+                assert!(stderr.ends_with("FINAL ERROR"));
+            }
+            other => panic!("expected PhaseNonZeroExit, got: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn check_result_limit_exceeded_is_agent_failure() {
+        let result = CommandResult {
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code: None,
+            timed_out: false,
+            limit_exceeded: Some(subprocess::LimitExceeded::Memory { max_rss_mb: 512 }),
+            signal: None,
+        };
+        let err = check_result(2, 1, 300, &result).unwrap_err();
+        assert!(err.is_agent_failure());
+        assert_eq!(
+            err.to_string(),
+            "Phase 2 was killed for exceeding its memory limit of 512 MiB RSS"
+        );
+    }
+
+    #[test]
+    fn check_result_killed_by_signal() {
+        let result = CommandResult {
+            stdout: String::new(),
+            stderr: "Killed".to_owned(),
+            exit_code: None,
+            timed_out: false,
+            limit_exceeded: None,
+            signal: Some(9),
+        };
+        let err = check_result(2, 1, 300, &result).unwrap_err();
+        assert!(matches!(err, PealError::PhaseKilledBySignal { phase: 2, signal: 9, .. }), "{err:?}");
+        assert!(err.is_agent_failure());
+        assert!(err.is_retryable_phase_failure(), "SIGKILL (e.g. OOM killer) is retried");
+
+        let interrupted = CommandResult { signal: Some(2), ..result };
+        let err = check_result(2, 1, 300, &interrupted).unwrap_err();
+        assert!(!err.is_retryable_phase_failure(), "SIGINT is not retried");
+    }
+
+    #[test]
+    fn check_result_agent_reported_error_from_cursor_json() {
+        let result = CommandResult {
+            stdout: "working...\n{\"type\":\"result\",\"is_error\":true,\"result\":\"model not available\"}\n".to_owned(),
+            stderr: String::new(),
+            exit_code: Some(1),
+            timed_out: false,
+            limit_exceeded: None,
+            signal: None,
+        };
+        let err = check_result(1, 1, 300, &result).unwrap_err();
+        match &err {
+            PealError::AgentReportedError { phase, message } => {
+                assert_eq!(*phase, 1);
+                assert_eq!(message, "model not available");
+            }
+            other => panic!("expected AgentReportedError, got: {other:?}"),
+        }
+        assert!(!err.is_retryable_phase_failure());
+
+        // Exit 0 with an error event still fails; plain text or a success result does not.
+        let stderr_event = CommandResult {
+            stdout: String::new(),
+            stderr: "{\"type\":\"error\",\"error\":{\"message\":\"rate limited\"}}".to_owned(),
+            exit_code: Some(0),
+            ..result.clone()
+        };
+        assert!(matches!(
+            check_result(1, 1, 300, &stderr_event),
+            Err(PealError::AgentReportedError { ref message, .. }) if message == "rate limited"
+        ));
+        assert_eq!(agent_reported_error("{\"type\":\"result\",\"is_error\":false,\"result\":\"ok\"}"), None);
+        assert_eq!(agent_reported_error("error: something"), None);
+    }
+
+    #[test]
+    fn agent_reported_error_is_not_retried() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let agent = dir.path().join("agent-stub");
+        std::fs::write(
+            &agent,
+            "#!/bin/sh\necho x >> calls\necho '{\"type\":\"result\",\"is_error\":true,\"result\":\"auth\"}'\nexit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut config = test_config(None);
+        config.repo_path = dir.path().to_path_buf();
+        config.state_dir = dir.path().join(".peal");
+        config.phase_retry_count = 2;
+
+        let err = run_phase2(&agent, &config, 1, "plan", &[]).unwrap_err();
+        assert!(matches!(err, PealError::AgentReportedError { phase: 2, .. }), "{err:?}");
+        let calls = std::fs::read_to_string(dir.path().join("calls")).unwrap();
+        assert_eq!(calls.lines().count(), 1, "agent-reported errors are not retried");
+    }
+
+    #[test]
+    fn check_result_detects_agent_auth_failure() {
+        let result = CommandResult {
+            stdout: String::new(),
+            stderr: "Error: Not logged in. Run `agent login` to authenticate.\n".to_owned(),
+            exit_code: Some(1),
+            timed_out: false,
+            limit_exceeded: None,
+            signal: None,
+        };
+        let err = check_result(2, 1, 300, &result).unwrap_err();
+        match &err {
+            PealError::AgentAuthFailed { call, detail } => {
+                assert_eq!(call, "phase 2");
+                assert!(detail.starts_with("Error: Not logged in"), "{detail}");
+            }
+            other => panic!("expected AgentAuthFailed, got: {other:?}"),
+        }
+        assert!(!err.is_retryable_phase_failure() && !err.is_escalatable());
+        assert!(err.aborts_run());
+        assert!(format!("{err}").contains("agent login"));
+
+        // A Cursor JSON error code counts even with exit 0; takes precedence over the message.
+        let json_code = CommandResult {
+            stdout: "{\"type\":\"error\",\"error\":{\"code\":\"UNAUTHENTICATED\",\"message\":\"denied\"}}".to_owned(),
+            stderr: String::new(),
+            exit_code: Some(0),
+            ..result.clone()
+        };
+        assert!(matches!(check_result(1, 1, 300, &json_code), Err(PealError::AgentAuthFailed { .. })));
+
+        // Successful output may mention logging in; other failures stay generic.
+        let ok = CommandResult { stdout: "Added a 'not logged in' banner".to_owned(), stderr: String::new(), exit_code: Some(0), ..result.clone() };
+        assert!(check_result(2, 1, 300, &ok).is_ok());
+        let other = CommandResult { stderr: "permission denied".to_owned(), ..result };
+        assert!(matches!(check_result(2, 1, 300, &other), Err(PealError::PhaseNonZeroExit { .. })));
+    }
+
+    #[test]
+    fn agent_auth_failure_is_not_retried() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let agent = dir.path().join("agent-stub");
+        std::fs::write(&agent, "#!/bin/sh\necho x >> calls\necho 'Error: token expired' >&2\nexit 1\n").unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut config = test_config(None);
+        config.repo_path = dir.path().to_path_buf();
+        config.state_dir = dir.path().join(".peal");
+        config.phase_retry_count = 2;
+
+        let err = run_phase2(&agent, &config, 1, "plan", &[]).unwrap_err();
+        assert!(matches!(err, PealError::AgentAuthFailed { .. }), "{err:?}");
+        let calls = std::fs::read_to_string(dir.path().join("calls")).unwrap();
+        assert_eq!(calls.lines().count(), 1, "auth failures are not retried");
+    }
+
+    #[test]
+    fn task_context_reads_repo_files_within_size_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("docs")).unwrap();
+        std::fs::write(dir.path().join("docs/design.md"), "Design notes.").unwrap();
+        std::fs::write(dir.path().join("big.txt"), "x".repeat(CONTEXT_FILE_MAX_BYTES + 10)).unwrap();
+        let mut config = test_config(None);
+        config.repo_path = dir.path().to_path_buf();
+
+        let paths = ["docs/design.md", "missing.md", "big.txt"].map(str::to_owned);
+        let files = task_context(&config, 1, &paths);
+        assert_eq!(files.len(), 2, "unreadable files are skipped");
+        assert_eq!(files[0], ("docs/design.md".to_owned(), "Design notes.".to_owned()));
+        assert_eq!(files[1].0, "big.txt");
+        assert!(files[1].1.starts_with(&"x".repeat(CONTEXT_FILE_MAX_BYTES)));
+        assert!(files[1].1.ends_with(&format!("(truncated: {CONTEXT_FILE_MAX_BYTES} of {} bytes shown)", CONTEXT_FILE_MAX_BYTES + 10)));
+
+        // Files past the total budget are left out.
+        let many: Vec<String> = (0..CONTEXT_TOTAL_MAX_BYTES / CONTEXT_FILE_MAX_BYTES + 1).map(|_| "big.txt".to_owned()).collect();
+        assert_eq!(task_context(&config, 1, &many).len(), CONTEXT_TOTAL_MAX_BYTES / CONTEXT_FILE_MAX_BYTES);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_phase1_appends_task_context_to_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("design.md"), "Use the adapter pattern.").unwrap();
+        let mut config = test_config(None);
+        config.repo_path = dir.path().to_path_buf();
+        config.state_dir = dir.path().join(".peal");
+
+        let output = run_phase1(Path::new("echo"), &config, 1, "Build it.", &["design.md".to_owned()]).unwrap();
+        assert!(output.stdout.contains("Build it."));
+        assert!(output.stdout.contains("File: design.md\n---CONTEXT---\nUse the adapter pattern."), "{}", output.stdout);
+    }
+
+    #[test]
+    fn stderr_tail_respects_char_boundaries() {
+        assert_eq!(stderr_tail("short", 10), "short");
+        assert_eq!(stderr_tail("abcdef", 3), "def");
+        // 'é' is two bytes; a cut inside it moves forward to the next boundary.
+        assert_eq!(stderr_tail("aéb", 2), "b");
+    }
+
+    #[test]
+    fn persist_stderr_writes_and_clears_artifact() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(None);
+        config.state_dir = dir.path().join(".peal");
+        let path = stderr_artifact_path(&config.state_dir, 4, 2);
+        assert!(path.ends_with("artifacts/task-4/phase2.stderr"));
+
+        persist_stderr(&config, 2, 4, "rate limited");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "rate limited");
+
+        persist_stderr(&config, 2, 4, "");
+        assert!(!path.exists(), "empty stderr should remove the stale artifact");
+    }
+
     // -- Phase 2 argv construction tests --
 
     #[test]
@@ -640,6 +1784,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn phase2_argv_requests_stream_json_for_change_check() {
+        let mut config = test_config(None);
+        config.phase2_change_check = "warn".to_owned();
+        let args = phase2_argv(&config, "Execute this plan.");
+        let idx = args.iter().position(|a| a == "--output-format").unwrap();
+        assert_eq!(args[idx + 1], "stream-json");
+        assert_eq!(args.last().unwrap(), "Execute this plan.");
+    }
+
+    #[test]
+    fn parse_phase2_transcript_collects_completed_edits() {
+        let stdout = [
+            r#"{"type":"system","subtype":"init"}"#,
+            r#"{"type":"tool_call","subtype":"started","tool_call":{"writeToolCall":{"args":{"path":"/repo/started_only.rs"}}}}"#,
+            r#"{"type":"tool_call","subtype":"completed","tool_call":{"editToolCall":{"args":{"path":"src/b.rs"},"result":{"success":{}}}}}"#,
+            r#"{"type":"tool_call","subtype":"completed","tool_call":{"writeToolCall":{"args":{"path":"/repo/a.rs"},"result":{"success":{}}}}}"#,
+            r#"{"type":"tool_call","subtype":"completed","tool_call":{"editToolCall":{"args":{"path":"src/b.rs"},"result":{"success":{}}}}}"#,
+            r#"{"type":"tool_call","subtype":"completed","tool_call":{"deleteToolCall":{"args":{"path":"gone.rs"},"result":{"error":{"message":"denied"}}}}}"#,
+            r#"{"type":"tool_call","subtype":"completed","tool_call":{"readToolCall":{"args":{"path":"README.md"},"result":{"success":{}}}}}"#,
+            r#"{"type":"result","subtype":"success","is_error":false,"result":"Done."}"#,
+        ]
+        .join("\n");
+        let transcript = parse_phase2_transcript(&stdout).expect("stream-json transcript");
+        assert_eq!(transcript.edited_files, vec!["/repo/a.rs", "src/b.rs"]);
+        assert_eq!(transcript.result_text.as_deref(), Some("Done."));
+
+        assert!(parse_phase2_transcript("I edited src/a.rs.\n").is_none());
+    }
+
     #[test]
     fn phase2_argv_prompt_is_last_arg() {
         let config = test_config(Some("gpt-5"));
@@ -688,7 +1862,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -697,6 +1871,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -714,6 +1890,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let echo_path = PathBuf::from("/bin/echo");
@@ -724,7 +1967,7 @@ mod tests {
             crate::cursor::resolve_agent_cmd("echo").expect("echo must exist")
         };
 
-        let output = run_phase1(&actual_echo, &config, 1, "Build the widget.").unwrap();
+        let output = run_phase1(&actual_echo, &config, 1, "Build the widget.", &[]).unwrap();
 
         // echo receives the full argv and prints it to stdout; we verify
         // the prompt appears as the last positional arg.
@@ -759,7 +2002,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -768,6 +2011,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -785,10 +2030,77 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
-        let err = run_phase1(&false_path, &config, 1, "task").unwrap_err();
+        let err = run_phase1(&false_path, &config, 1, "task", &[]).unwrap_err();
 
         match err {
             PealError::PhaseNonZeroExit { phase, .. } => assert_eq!(phase, 1),
@@ -808,7 +2120,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             // Very short timeout to trigger kill.
             phase_timeout_sec: 1,
             phase_retry_count: 0,
@@ -818,6 +2130,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -835,13 +2149,80 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let sleep_path = crate::cursor::resolve_agent_cmd("sleep").expect("sleep must exist");
         // sleep receives all the argv (--print --plan ... 60) and tries to
         // sleep for the first numeric-looking arg; but sleep will just fail
         // or run — either way the 1s timeout will fire first.
-        let err = run_phase1(&sleep_path, &config, 1, "60").unwrap_err();
+        let err = run_phase1(&sleep_path, &config, 1, "60", &[]).unwrap_err();
 
         match err {
             PealError::PhaseTimedOut { phase, timeout_sec } => {
@@ -867,7 +2248,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -876,6 +2257,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -893,10 +2276,77 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let bad_path = PathBuf::from("/no/such/binary");
-        let err = run_phase1(&bad_path, &config, 1, "task").unwrap_err();
+        let err = run_phase1(&bad_path, &config, 1, "task", &[]).unwrap_err();
 
         match err {
             PealError::PhaseSpawnFailed { phase, detail } => {
@@ -921,7 +2371,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -930,6 +2380,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -947,6 +2399,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let echo_path = PathBuf::from("/bin/echo");
@@ -956,7 +2475,7 @@ mod tests {
             crate::cursor::resolve_agent_cmd("echo").expect("echo must exist")
         };
 
-        let output = run_phase2(&actual_echo, &config, 1, "1. Build widget\n2. Test it").unwrap();
+        let output = run_phase2(&actual_echo, &config, 1, "1. Build widget\n2. Test it", &[]).unwrap();
 
         assert!(
             output.stdout.contains("Execute the following plan"),
@@ -975,6 +2494,58 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn run_phase2_retries_once_when_the_done_marker_is_missing() {
+        use crate::testing::{FakeAgent, Reply};
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(None);
+        config.repo_path = dir.path().to_path_buf();
+        config.state_dir = dir.path().join(".peal");
+        config.done_marker = Some("PEAL_TASK_DONE: {{TASK_INDEX}}".to_owned());
+
+        let agent = FakeAgent::new(dir.path(), vec![Reply::ok("I stopped halfway.\n"), Reply::ok("Finished.\nPEAL_TASK_DONE: 4\n")]).unwrap();
+        let output = run_phase2(agent.path(), &config, 4, "1. Build widget", &[]).unwrap();
+        assert!(output.stdout.contains("PEAL_TASK_DONE: 4"));
+        let calls = agent.calls();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[0].ends_with("end your answer with this line, exactly:\nPEAL_TASK_DONE: 4\nDo not print it if you stopped early, refused, or could not finish."), "{}", calls[0]);
+        assert!(calls[1].contains("did not end with the completion line"), "{}", calls[1]);
+
+        config.on_missing_done_marker = "fail".to_owned();
+        let agent = FakeAgent::new(&dir.path().join("second"), vec![Reply::ok("PEAL_TASK_DONE: 3")]).unwrap();
+        let err = run_phase2(agent.path(), &config, 4, "1. Build widget", &[]).unwrap_err();
+        assert!(matches!(err, PealError::Phase2DoneMarkerMissing { task_index: 4, .. }), "got: {err:?}");
+        assert_eq!(agent.calls().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_phase1_applies_prompt_middleware_to_prompt_and_output() {
+        use crate::config::PromptMiddlewareSpec;
+        use crate::testing::{FakeAgent, Reply};
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(None);
+        config.repo_path = dir.path().to_path_buf();
+        config.state_dir = dir.path().join(".peal");
+        config.prompt_middleware = vec![
+            PromptMiddlewareSpec::Prefix {
+                text: Some("Follow ORG-STANDARDS.".to_owned()),
+                file: None,
+            },
+            PromptMiddlewareSpec::Redact {
+                patterns: vec![r"sk-[a-z0-9]+".to_owned()],
+            },
+        ];
+
+        let agent = FakeAgent::new(dir.path(), vec![Reply::ok("Use key sk-out99.\n")]).unwrap();
+        let output = run_phase1(agent.path(), &config, 1, "Rotate key sk-in42", &[]).unwrap();
+        assert_eq!(output.stdout, "Use key [REDACTED].\n");
+        let calls = agent.calls();
+        assert!(calls[0].contains("Follow ORG-STANDARDS.\n\n"), "{}", calls[0]);
+        assert!(calls[0].contains("Rotate key [REDACTED]") && !calls[0].contains("sk-in42"), "{}", calls[0]);
+    }
+
     #[test]
     fn run_phase2_fails_on_nonzero_exit() {
         let dir = tempfile::tempdir().unwrap();
@@ -987,7 +2558,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -996,6 +2567,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1013,10 +2586,77 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
-        let err = run_phase2(&false_path, &config, 1, "plan text").unwrap_err();
+        let err = run_phase2(&false_path, &config, 1, "plan text", &[]).unwrap_err();
 
         match err {
             PealError::PhaseNonZeroExit { phase, .. } => assert_eq!(phase, 2),
@@ -1036,7 +2676,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1045,6 +2685,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1062,10 +2704,77 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let bad_path = PathBuf::from("/no/such/binary");
-        let err = run_phase2(&bad_path, &config, 1, "plan text").unwrap_err();
+        let err = run_phase2(&bad_path, &config, 1, "plan text", &[]).unwrap_err();
 
         match err {
             PealError::PhaseSpawnFailed { phase, detail } => {
@@ -1154,7 +2863,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1163,6 +2872,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1180,6 +2891,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let echo_path = PathBuf::from("/bin/echo");
@@ -1222,7 +3000,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1231,6 +3009,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1248,6 +3028,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
@@ -1271,7 +3118,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 1,
@@ -1280,6 +3127,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1297,6 +3146,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
@@ -1320,7 +3236,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 1,
@@ -1329,6 +3245,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1346,6 +3264,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
@@ -1365,7 +3350,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1374,6 +3359,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1391,6 +3378,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let bad_path = PathBuf::from("/no/such/binary");
@@ -1417,7 +3471,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1426,6 +3480,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1443,6 +3499,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let echo_path = PathBuf::from("/bin/echo");
@@ -1485,7 +3608,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1494,6 +3617,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1511,6 +3636,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let echo_path = PathBuf::from("/bin/echo");
@@ -1533,4 +3725,212 @@ mod tests {
             output.stdout
         );
     }
+
+
+    // -- agent health check tests --
+
+    #[test]
+    fn health_check_passes_when_response_contains_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PealConfig {
+            repo_path: dir.path().to_path_buf(),
+            ..test_config(None)
+        };
+        // echo returns the prompt, which contains the token.
+        let echo = crate::cursor::resolve_agent_cmd("echo").expect("echo must exist");
+        run_health_check(&echo, &config).expect("echo agent should pass the health check");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn health_check_fails_on_bad_exit_wrong_answer_and_timeout() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = |name: &str, body: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let config = PealConfig {
+            repo_path: dir.path().to_path_buf(),
+            agent_health_check_timeout_sec: 1,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
+            ..test_config(None)
+        };
+
+        let cases = [
+            (script("crash.sh", "echo 'boom' >&2; exit 7"), "exited with code Some(7): boom"),
+            (script("wrong.sh", "echo hello"), "unexpected response"),
+            (script("slow.sh", "sleep 5"), "no response within 1s"),
+        ];
+        for (agent, expected) in cases {
+            let err = run_health_check(&agent, &config).unwrap_err();
+            assert!(matches!(err, PealError::AgentHealthCheckFailed { .. }));
+            let msg = err.to_string();
+            assert!(msg.contains("agent unreachable or misconfigured"), "got: {msg}");
+            assert!(msg.contains(expected), "expected {expected:?} in: {msg}");
+        }
+
+        let err = run_health_check(&script("auth.sh", "echo 'not logged in' >&2; exit 7"), &config).unwrap_err();
+        assert!(
+            matches!(&err, PealError::AgentAuthFailed { call, .. } if call == "health check"),
+            "{err:?}"
+        );
+    }
+
+
+    #[test]
+    #[cfg(unix)]
+    fn run_phase3_triage_uses_triage_agent_and_model_when_set() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let triage_agent = dir.path().join("triage_agent");
+        std::fs::write(&triage_agent, "#!/bin/sh\necho \"triage: $*\"\n").unwrap();
+        std::fs::set_permissions(&triage_agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = test_config(Some("main-model"));
+        config.repo_path = dir.path().to_path_buf();
+        let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
+
+        config.triage_agent_cmd = Some(triage_agent.to_str().unwrap().to_owned());
+        let out = run_phase3_triage(&false_path, &config, "stet output").unwrap();
+        assert!(out.stdout.contains("--model main-model"), "falls back to model: {}", out.stdout);
+
+        config.triage_model = Some("cheap-model".to_owned());
+        let out = run_phase3_triage(&false_path, &config, "stet output").unwrap();
+        assert!(out.stdout.starts_with("triage: "), "stdout: {}", out.stdout);
+        assert!(out.stdout.contains("--model cheap-model"), "stdout: {}", out.stdout);
+
+        config.triage_agent_cmd = Some("no-such-triage-agent".to_owned());
+        assert!(run_phase3_triage(&false_path, &config, "stet output").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_phase3_triage_reuses_cached_answer_for_identical_stet_output() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let agent = dir.path().join("counting_agent");
+        std::fs::write(&agent, "#!/bin/sh\necho call >> calls.log\necho 'nothing to address'\n").unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let calls = || std::fs::read_to_string(dir.path().join("calls.log")).unwrap().lines().count();
+
+        let mut config = test_config(None);
+        config.repo_path = dir.path().to_path_buf();
+        config.state_dir = dir.path().join(".peal");
+        config.triage_cache_ttl_sec = 3600;
+
+        let findings = r#"{"findings":[{"id":"f1"}]}"#;
+        let first = run_phase3_triage(&agent, &config, findings).unwrap();
+        let second = run_phase3_triage(&agent, &config, findings).unwrap();
+        assert_eq!((first.stdout.as_str(), second.stdout.as_str()), ("nothing to address\n", "nothing to address\n"));
+        assert_eq!(calls(), 1, "identical stet output is answered from the cache");
+        let entries: Vec<String> = std::fs::read_dir(config.state_dir.join("cache/triage"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(entries, vec![format!("{}.json", triage_cache::key(&agent, &config, findings))]);
+        assert_eq!(entries[0].len(), 64 + ".json".len(), "SHA-256 key");
+
+        run_phase3_triage(&agent, &config, r#"{"findings":[]}"#).unwrap();
+        assert_eq!(calls(), 2, "different findings miss");
+
+        config.model = Some("other-model".to_owned());
+        run_phase3_triage(&agent, &config, findings).unwrap();
+        assert_eq!(calls(), 3, "another model misses");
+
+        config.no_cache = true;
+        run_phase3_triage(&agent, &config, findings).unwrap();
+        assert_eq!(calls(), 4, "--no-cache bypasses the cache");
+    }
+
+
+    #[test]
+    fn max_prompt_tokens_blocks_oversized_prompt_before_invoking_agent() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
+        let mut config = test_config(None);
+        config.repo_path = dir.path().to_path_buf();
+        config.state_dir = dir.path().join(".peal");
+        config.max_prompt_tokens = Some(100);
+        let plan_text = "x".repeat(2000);
+
+        let err = run_phase2(&agent, &config, 3, &plan_text, &[]).unwrap_err();
+        let msg = err.to_string();
+        assert!(matches!(err, PealError::PromptTooLarge { estimated, max: 100, .. } if estimated > 500));
+        assert!(msg.starts_with("Phase 2 (task 3) prompt is ~"), "{msg}");
+        assert!(msg.contains("largest section: plan text (~500 tokens)"), "{msg}");
+        assert!(err.is_agent_failure());
+
+        // Small prompts pass; "warn" invokes the agent anyway (and `false` then fails).
+        assert!(matches!(
+            run_phase2(&agent, &config, 3, "tiny", &[]),
+            Err(PealError::PhaseNonZeroExit { .. })
+        ));
+        config.on_prompt_too_large = "warn".to_owned();
+        assert!(matches!(
+            run_phase2(&agent, &config, 3, &plan_text, &[]),
+            Err(PealError::PhaseNonZeroExit { .. })
+        ));
+    }
 }
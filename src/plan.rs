@@ -3,19 +3,28 @@
 //! **Format detection (SP-7.1):** Canonical format = at least one line matching `^## Task\s+\d+`
 //! after CRLF→LF. Phase-table format is not canonical in v1. See `is_canonical_plan_format` and
 //! `docs/implementation-plan.md` (Phase 7).
+//!
+//! **Front matter:** An optional block delimited by `---` lines at the very top of the plan
+//! carries plan-level metadata (see `PlanMetadata`). It is stripped before task parsing.
+//!
+//! **Variables:** `{{NAME}}` placeholders in the plan body are replaced from `PealConfig::vars`
+//! by `expand_vars` before normalization and parsing.
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 use std::time::Duration;
 
 use regex::Regex;
-use tracing::debug;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
 
 use crate::config::PealConfig;
 use crate::error::PealError;
+use crate::expect::Expectation;
 use crate::prompt;
-use crate::subprocess;
+use crate::rate_limit;
 
 /// Maximum snippet length (chars) for normalized-parse-failure error (SP-7.3).
 const PARSE_FAIL_SNIPPET_MAX_CHARS: usize = 500;
@@ -28,7 +37,30 @@ static HEADING_RE: OnceLock<Regex> = OnceLock::new();
 
 fn heading_re() -> &'static Regex {
     HEADING_RE.get_or_init(|| {
-        Regex::new(r"^## Task\s+(\d+)\s*(\(parallel\))?\s*$").expect("valid literal regex")
+        Regex::new(
+            r"^## Task\s+(\d+)((?:\s*\((?:parallel|tags:[^)]*|after:\s*\d+(?:\s*,\s*\d+)*\s*|context:[^)]*|dir:[^)]*|lock:[^)]*)\))*)\s*$",
+        )
+        .expect("valid literal regex")
+    })
+}
+
+static HEADING_SUFFIX_RE: OnceLock<Regex> = OnceLock::new();
+
+/// One `(parallel)`, `(tags: ...)`, `(after: ...)`, `(context: ...)`, `(dir: ...)`, or
+/// `(lock: ...)` suffix of a task heading.
+fn heading_suffix_re() -> &'static Regex {
+    HEADING_SUFFIX_RE.get_or_init(|| {
+        Regex::new(r"\((?:(parallel)|tags:([^)]*)|after:([^)]*)|context:([^)]*)|dir:([^)]*)|lock:([^)]*))\)")
+            .expect("valid literal regex")
+    })
+}
+
+static STAGE_RE: OnceLock<Regex> = OnceLock::new();
+
+/// `# Stage: <name>` or `## Stage: <name>` section heading.
+fn stage_re() -> &'static Regex {
+    STAGE_RE.get_or_init(|| {
+        Regex::new(r"^##? Stage:\s*(\S.*?)\s*$").expect("valid literal regex")
     })
 }
 
@@ -59,21 +91,57 @@ pub fn is_canonical_plan_format(content: &str) -> bool {
 /// plus optional `--model`, then the prompt as a single positional arg.
 /// On success returns the agent's stdout as the normalized plan string.
 /// On spawn failure, timeout, or non-zero exit returns a `PealError`.
+/// With `agent_backend = "api"` the prompt goes to the HTTP API instead (see [`crate::api_agent`]).
 /// When `config.normalize_prompt_path` is set, the prompt is built from that file (placeholder `{{DOC}}` replaced by document content); otherwise the built-in prompt is used.
+///
+/// On a retry, `rejected` is the previous attempt's output that failed to parse; the prompt
+/// then says why it was rejected and shows how it began (see [`prompt::normalize_retry_prompt`]).
 pub fn normalize_via_agent(
     document_content: &str,
     agent_path: &Path,
     config: &PealConfig,
+    rejected: Option<&str>,
 ) -> Result<String, PealError> {
-    let prompt = build_normalize_prompt(document_content, config)?;
+    let mut prompt = build_normalize_prompt(document_content, config)?;
+    if let Some(previous) = rejected {
+        prompt = prompt::normalize_retry_prompt(
+            prompt,
+            &normalization_rejection_reason(previous),
+            &snippet_for_parse_failure(previous),
+        );
+    }
+    let middleware = crate::prompt_middleware::Chain::from_config(config)?;
+    let prompt = middleware.prompt("normalization", prompt);
+    crate::phase::check_prompt_size(
+        config,
+        "Plan normalization",
+        &prompt,
+        &[("plan document", document_content)],
+    )?;
     let args = normalization_argv(config, &prompt);
     let timeout = Duration::from_secs(config.phase_timeout_sec);
     let agent_str = agent_path.to_string_lossy();
 
-    let result = subprocess::run_command(&agent_str, &args, &config.repo_path, Some(timeout))
-        .map_err(|e| PealError::NormalizationFailed {
-            detail: format!("spawn failed: {}", e),
-        })?;
+    crate::phase::log_agent_prompt(config, "normalization", None, &prompt);
+    rate_limit::count_agent_call(config.max_agent_calls)?;
+    rate_limit::throttle(config.agent_max_requests_per_min);
+    let mut result = if config.uses_api_backend() {
+        crate::api_agent::complete(config, &prompt, timeout)
+    } else {
+        crate::container::run_with_limits(
+            config,
+            &agent_str,
+            &args,
+            &config.repo_path,
+            Some(timeout),
+            Default::default(),
+        )
+    }
+    .map_err(|e| PealError::NormalizationFailed {
+        detail: format!("spawn failed: {}", e),
+    })?;
+    result.stdout = middleware.output("normalization", result.stdout);
+    crate::phase::log_agent_output(config, "normalization", None, &result.stdout);
 
     if result.timed_out {
         return Err(PealError::NormalizationFailed {
@@ -84,6 +152,12 @@ pub fn normalize_via_agent(
         });
     }
     if !result.success() {
+        if let Some(detail) = crate::phase::agent_auth_failure(&result) {
+            return Err(PealError::AgentAuthFailed {
+                call: "normalization".to_owned(),
+                detail,
+            });
+        }
         let snippet = result
             .stderr
             .lines()
@@ -144,6 +218,19 @@ fn snippet_for_parse_failure(normalized: &str) -> String {
     }
 }
 
+/// Why [`parse_plan_or_fail_with_snippet`] rejects `normalized`, phrased to follow "your
+/// previous output was invalid because".
+fn normalization_rejection_reason(normalized: &str) -> String {
+    match parse_plan(normalized) {
+        Err(e) => format!("it could not be parsed as a plan ({e})"),
+        Ok(_) if normalized.trim().is_empty() => "it was empty".to_owned(),
+        Ok(_) if !is_canonical_plan_format(normalized) => {
+            "it has no task headings of the form \"## Task N\"".to_owned()
+        }
+        Ok(_) => "it contains no tasks".to_owned(),
+    }
+}
+
 /// Parse normalized plan content and return an error with snippet if not canonical or no tasks (SP-7.3).
 ///
 /// Single parsing path for normalized output: uses `parse_plan` then treats
@@ -162,17 +249,75 @@ pub fn parse_plan_or_fail_with_snippet(normalized: &str) -> Result<ParsedPlan, P
     Ok(parsed)
 }
 
+/// Where the normalized plan for `plan_path` is saved: `<stem>.normalized.md` next to it
+/// (e.g. `docs/prd.md` → `docs/prd.normalized.md`).
+pub fn normalized_plan_path(plan_path: &Path) -> PathBuf {
+    let stem = plan_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "plan".to_owned());
+    plan_path.with_file_name(format!("{stem}.normalized.md"))
+}
+
+/// Let the user review the normalized plan saved at `path` before the run (`--review-normalized`).
+///
+/// With an `editor` command (`$VISUAL` / `$EDITOR`; may carry arguments, e.g. `code --wait`),
+/// opens the file in it and returns the file's content once the editor exits successfully.
+/// Without one, prints the plan to `output` and asks for confirmation on `input`; anything
+/// but `y` / `yes` rejects the plan.
+pub fn review_normalized_plan(
+    path: &Path,
+    editor: Option<&str>,
+    input: &mut dyn std::io::BufRead,
+    output: &mut dyn std::io::Write,
+) -> Result<String, PealError> {
+    let review_err = |detail: String| PealError::NormalizedPlanReviewFailed {
+        path: path.to_path_buf(),
+        detail,
+    };
+
+    if let Some(editor) = editor.map(str::trim).filter(|e| !e.is_empty()) {
+        let mut words = editor.split_whitespace();
+        let program = words.next().expect("editor is non-empty");
+        let status = std::process::Command::new(program)
+            .args(words)
+            .arg(path)
+            .status()
+            .map_err(|e| review_err(format!("could not start editor '{editor}': {e}")))?;
+        if !status.success() {
+            return Err(review_err(format!("editor '{editor}' exited with {status}")));
+        }
+        return fs::read_to_string(path).map_err(|e| review_err(e.to_string()));
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| review_err(e.to_string()))?;
+    let prompted = writeln!(output, "{}\n--- normalized plan: {} ---", content.trim_end(), path.display())
+        .and_then(|()| write!(output, "Proceed with this plan? [y/N] "))
+        .and_then(|()| output.flush());
+    prompted.map_err(|e| review_err(e.to_string()))?;
+    let mut answer = String::new();
+    input.read_line(&mut answer).map_err(|e| review_err(e.to_string()))?;
+    if matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+        Ok(content)
+    } else {
+        Err(PealError::NormalizedPlanRejected {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
 /// Build argv for the normalization invocation (same layout as Phase 1).
 /// `--model` is only added when `config.model` is set; otherwise omitted for Cursor CLI default (Auto).
 fn normalization_argv(config: &PealConfig, prompt: &str) -> Vec<String> {
-    let mut args = vec![
+    let mut args = config.agent_args();
+    args.extend([
         "--print".to_owned(),
         "--plan".to_owned(),
         "--workspace".to_owned(),
         config.repo_path.to_string_lossy().into_owned(),
         "--output-format".to_owned(),
         "text".to_owned(),
-    ];
+    ]);
     if let Some(model) = &config.model {
         args.push("--model".to_owned());
         args.push(model.clone());
@@ -187,6 +332,68 @@ pub struct Task {
     pub index: u32,
     pub content: String,
     pub parallel: bool,
+    /// Tags from a `(tags: a, b)` heading suffix, lowercased, in heading order.
+    pub tags: Vec<String>,
+    /// Name of the enclosing `# Stage: <name>` section; `None` before the first stage heading.
+    pub stage: Option<String>,
+    /// Soft ordering hints from an `(after: 3, 4)` heading suffix: within a parallel block, start
+    /// this task only once those tasks are done when the scheduler can (see
+    /// [`ParsedPlan::soft_dependency_waves`]). Not a hard dependency.
+    pub after: Vec<u32>,
+    /// Extra context files from a `(context: docs/design.md, src/api/mod.rs)` heading suffix,
+    /// as written (relative paths are relative to the repo). Phases 1 and 2 append their
+    /// contents to the prompt (see [`crate::phase::task_context`]).
+    pub context: Vec<String>,
+    /// Directories from a `(dir: services/api, libs/core)` heading suffix, as written (relative to
+    /// the repo). With `sparse_checkout`, they and the directories of `context` files make up
+    /// the task's sparse worktree (see [`crate::sparse`]).
+    pub dirs: Vec<String>,
+    /// Named locks from a `(lock: database, cache)` heading suffix, lowercased: tasks sharing a
+    /// lock name never run at the same time, even inside one parallel block (see
+    /// `runner::with_task_locks`).
+    pub locks: Vec<String>,
+    /// Verification commands from ```` ```peal-verify ```` fenced blocks in the task body, one
+    /// per non-empty line, in body order. Run after Phase 2 (see
+    /// `runner::run_task_verify`); the block stays in `content`.
+    pub verify: Vec<String>,
+    /// Checks from `expect:` lines in the task body, in body order. Evaluated once the task's
+    /// work is done (see [`crate::expect`]); the lines stay in `content`.
+    pub expect: Vec<Expectation>,
+}
+
+/// Plan-level metadata from the optional front-matter block at the top of the plan file.
+///
+/// `model`, `phase_timeout_sec`, and `post_run_timeout_sec` act as per-plan config defaults
+/// (below config file, env, and CLI). All fields are copied into the run summary.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Default model for this plan when none is set via config, env, or CLI.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase_timeout_sec: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_run_timeout_sec: Option<u64>,
+    /// Subdirectory of the repo the plan targets; the default for the `repo_subdir` option.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo_subdir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// Unrecognized keys, kept verbatim (lists joined with ", ") so plans can carry extra metadata.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, String>,
+}
+
+impl PlanMetadata {
+    /// True when the plan had no front matter (or an empty block).
+    pub fn is_empty(&self) -> bool {
+        *self == PlanMetadata::default()
+    }
 }
 
 /// An execution segment: either a sequential task or a block of parallel tasks.
@@ -205,6 +412,46 @@ pub enum Segment {
 pub struct ParsedPlan {
     pub tasks: Vec<Task>,
     pub segments: Vec<Segment>,
+    /// Metadata from the front-matter block; default when the plan has none.
+    pub metadata: PlanMetadata,
+    /// Largest task index in the parsed file, kept through `--task` / `--from-task`
+    /// filtering so tasks added at run time get indices no plan task uses.
+    pub max_task_index: u32,
+    /// Source lines of each task in the parsed content, keyed by task index (see
+    /// [`ParsedPlan::task_span`]). Tasks added at run time have none.
+    pub task_spans: BTreeMap<u32, LineSpan>,
+    /// Position of each task in `tasks`, keyed by task index, so [`ParsedPlan::task_by_index`]
+    /// is O(1) on plans with hundreds of tasks. Built with `tasks` by every constructor.
+    pub task_positions: HashMap<u32, usize>,
+}
+
+/// A 1-based, inclusive range of lines in the plan file, counting front matter.
+///
+/// A task spans its `## Task N` heading through the last non-blank line of its body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LineSpan {
+    /// The smallest span covering both `self` and `other`.
+    pub fn cover(self, other: LineSpan) -> LineSpan {
+        LineSpan {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+impl std::fmt::Display for LineSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "line {}", self.start)
+        } else {
+            write!(f, "lines {}\u{2013}{}", self.start, self.end)
+        }
+    }
 }
 
 /// Read a plan file at `path` and parse it into tasks and segments.
@@ -240,28 +487,380 @@ pub fn parse_plan_file(path: &Path) -> anyhow::Result<ParsedPlan> {
     parse_plan(&content)
 }
 
+/// Split an optional front-matter block off the top of the plan content.
+///
+/// The block starts with a `---` line as the very first line and ends at the next `---`
+/// (or `...`) line; a leading `---` that is never closed, or that encloses `## Task` headings,
+/// is treated as a horizontal rule and left in place. Supported syntax is a YAML subset: `key: value` scalars (optionally
+/// quoted), flow lists (`labels: [a, b]`), block lists (`- item` lines under `key:`),
+/// blank lines, and `#` comments. Returns default metadata and the content unchanged
+/// when there is no front matter.
+pub fn split_front_matter(content: &str) -> Result<(PlanMetadata, String), PealError> {
+    split_front_matter_with_offset(content).map(|(metadata, body, _)| (metadata, body))
+}
+
+/// [`split_front_matter`], also returning how many lines the front-matter block took
+/// (0 when there is none), i.e. the line offset of the body within `content`.
+fn split_front_matter_with_offset(content: &str) -> Result<(PlanMetadata, String, usize), PealError> {
+    let content = content.replace("\r\n", "\n");
+    let mut lines = content.lines();
+    if lines.next().map(str::trim_end) != Some("---") {
+        return Ok((PlanMetadata::default(), content, 0));
+    }
+
+    let mut block: Vec<&str> = Vec::new();
+    let mut closed = false;
+    for line in lines.by_ref() {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            closed = true;
+            break;
+        }
+        block.push(line);
+    }
+    // A leading `---` with no closing line, or one that encloses task headings, is a
+    // markdown horizontal rule rather than front matter.
+    let detect = canonical_detect_re();
+    if !closed || block.iter().any(|line| detect.is_match(line)) {
+        return Ok((PlanMetadata::default(), content, 0));
+    }
+
+    let metadata = parse_front_matter_block(&block)?;
+    let offset = block.len() + 2;
+    let body = lines.collect::<Vec<_>>().join("\n");
+    Ok((metadata, body, offset))
+}
+
+/// Parse the lines between the front-matter delimiters into `PlanMetadata`.
+fn parse_front_matter_block(lines: &[&str]) -> Result<PlanMetadata, PealError> {
+    let mut entries: Vec<(String, Vec<String>)> = Vec::new();
+    // Key whose value is a block list (`key:` followed by `- item` lines).
+    let mut list_key_open = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            if !list_key_open {
+                return Err(PealError::InvalidPlanFrontMatter {
+                    detail: format!("line {}: list item without a key", i + 2),
+                });
+            }
+            if let Some((_, values)) = entries.last_mut() {
+                values.push(unquote(item.trim()).to_owned());
+            }
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            return Err(PealError::InvalidPlanFrontMatter {
+                detail: format!("line {}: expected `key: value`, got {trimmed:?}", i + 2),
+            });
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(PealError::InvalidPlanFrontMatter {
+                detail: format!("line {}: invalid key {key:?}", i + 2),
+            });
+        }
+        let value = value.trim();
+        list_key_open = value.is_empty();
+        let values = if value.is_empty() {
+            Vec::new()
+        } else if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            inner
+                .split(',')
+                .map(|v| unquote(v.trim()).to_owned())
+                .filter(|v| !v.is_empty())
+                .collect()
+        } else {
+            vec![unquote(value).to_owned()]
+        };
+        entries.push((key.to_owned(), values));
+    }
+
+    let mut metadata = PlanMetadata::default();
+    for (key, values) in entries {
+        let scalar = values.join(", ");
+        match key.as_str() {
+            "title" => metadata.title = Some(scalar),
+            "description" => metadata.description = Some(scalar),
+            "model" => metadata.model = Some(scalar),
+            "phase_timeout_sec" => metadata.phase_timeout_sec = Some(parse_front_matter_u64(&key, &scalar)?),
+            "post_run_timeout_sec" => {
+                metadata.post_run_timeout_sec = Some(parse_front_matter_u64(&key, &scalar)?)
+            }
+            "repo_subdir" => metadata.repo_subdir = Some(PathBuf::from(scalar)),
+            "labels" => metadata.labels = values,
+            _ => {
+                metadata.extra.insert(key, scalar);
+            }
+        }
+    }
+    Ok(metadata)
+}
+
+fn parse_front_matter_u64(key: &str, value: &str) -> Result<u64, PealError> {
+    value.parse::<u64>().map_err(|e| PealError::InvalidPlanFrontMatter {
+        detail: format!("{key}: {e}"),
+    })
+}
+
+/// Strip one pair of matching surrounding quotes, if present.
+fn unquote(value: &str) -> &str {
+    for q in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(q) && value.ends_with(q) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+/// Lazy regex for plan variable placeholders: `{{NAME}}` with optional inner whitespace.
+static VAR_RE: OnceLock<Regex> = OnceLock::new();
+
+fn var_re() -> &'static Regex {
+    VAR_RE.get_or_init(|| {
+        Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").expect("valid literal regex")
+    })
+}
+
+/// Replace `{{NAME}}` placeholders in `content` with values from `vars`.
+///
+/// With no vars the content is returned unchanged. Placeholders whose name is not in
+/// `vars` are left as-is and logged once each as a warning, so plans that contain
+/// literal `{{…}}` text keep working. Substituted values are not expanded again.
+pub fn expand_vars(content: &str, vars: &BTreeMap<String, String>) -> String {
+    if vars.is_empty() {
+        return content.to_owned();
+    }
+    let mut undefined: Vec<String> = Vec::new();
+    let expanded = var_re().replace_all(content, |caps: &regex::Captures<'_>| {
+        let name = &caps[1];
+        match vars.get(name) {
+            Some(value) => value.clone(),
+            None => {
+                if !undefined.iter().any(|n| n == name) {
+                    undefined.push(name.to_owned());
+                }
+                caps[0].to_owned()
+            }
+        }
+    });
+    for name in &undefined {
+        warn!(name = %name, "plan variable not defined; placeholder left unchanged");
+    }
+    expanded.into_owned()
+}
+
+/// Task indices that appear on more than one `## Task N` heading, each with the 1-based
+/// line numbers (within `content`) of its headings, in ascending index order.
+pub fn find_duplicate_task_indices(content: &str) -> Vec<(u32, Vec<usize>)> {
+    let mut lines_by_index: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+    for (i, line) in content.lines().enumerate() {
+        if let Some(caps) = heading_re().captures(line)
+            && let Ok(index) = caps[1].parse::<u32>()
+        {
+            lines_by_index.entry(index).or_default().push(i + 1);
+        }
+    }
+    lines_by_index
+        .into_iter()
+        .filter(|(_, lines)| lines.len() > 1)
+        .collect()
+}
+
+/// Fail with `PealError::DuplicateTaskIndices` when any task index is used more than once.
+pub fn check_duplicate_task_indices(content: &str) -> Result<(), PealError> {
+    let duplicates = find_duplicate_task_indices(content);
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+    let detail = duplicates
+        .iter()
+        .map(|(index, lines)| {
+            let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+            format!("  Task {index}: lines {}", lines.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Err(PealError::DuplicateTaskIndices { detail })
+}
+
+/// Rewrite `## Task N` headings to 1, 2, 3, … in document order.
+///
+/// Only the number is replaced, so ` (parallel)` markers, task bodies, front matter, and
+/// line endings are preserved byte for byte. Returns the new content and whether it changed.
+pub fn renumber_tasks(content: &str) -> (String, bool) {
+    let mut out = String::with_capacity(content.len());
+    let mut next: u32 = 1;
+    let mut changed = false;
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        match heading_re().captures(text).and_then(|caps| caps.get(1)) {
+            Some(digits) => {
+                let renumbered = next.to_string();
+                changed |= digits.as_str() != renumbered;
+                out.push_str(&line[..digits.start()]);
+                out.push_str(&renumbered);
+                out.push_str(&line[digits.end()..]);
+                next += 1;
+            }
+            None => out.push_str(line),
+        }
+    }
+    (out, changed)
+}
+
+/// Split a `(tags: ...)` or `(lock: ...)` list on commas; names are trimmed and lowercased, empties
+/// and repeats dropped.
+fn parse_tags(list: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in list.split(',').map(|t| t.trim().to_lowercase()) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Split an `(after: ...)` list of task indices; repeats dropped. The heading pattern only
+/// accepts digits and commas here.
+fn parse_after(list: &str) -> Vec<u32> {
+    let mut after: Vec<u32> = Vec::new();
+    for index in list.split(',').filter_map(|i| i.trim().parse::<u32>().ok()) {
+        if !after.contains(&index) {
+            after.push(index);
+        }
+    }
+    after
+}
+
+/// Split a `(context: ...)` list of file paths on commas; paths are trimmed, empties and
+/// repeats dropped.
+fn parse_context(list: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for path in list.split(',').map(str::trim) {
+        if !path.is_empty() && !paths.iter().any(|p| p == path) {
+            paths.push(path.to_owned());
+        }
+    }
+    paths
+}
+
+/// Commands in the ```` ```peal-verify ```` fenced blocks of a task body: each non-empty line
+/// of a block is one shell command. An unclosed block runs to the end of the body.
+fn parse_verify(body_lines: &[&str]) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut in_block = false;
+    for line in body_lines.iter().map(|l| l.trim()) {
+        if in_block {
+            if line.starts_with("```") {
+                in_block = false;
+            } else if !line.is_empty() {
+                commands.push(line.to_owned());
+            }
+        } else if line.strip_prefix("```").is_some_and(|info| info.trim() == "peal-verify") {
+            in_block = true;
+        }
+    }
+    commands
+}
+
+/// The `expect:` lines of a task body, parsed; a malformed one is
+/// `PealError::InvalidExpectation`.
+fn parse_expect(task_index: u32, body_lines: &[&str]) -> Result<Vec<Expectation>, PealError> {
+    crate::expect::expect_lines(body_lines)
+        .into_iter()
+        .map(|spec| {
+            Expectation::parse(spec).map_err(|detail| PealError::InvalidExpectation {
+                task_index,
+                line: format!("expect: {spec}"),
+                detail,
+            })
+        })
+        .collect()
+}
+
 /// Parse plan content (already a valid UTF-8 string) into tasks and segments.
 ///
-/// Heading pattern: `^## Task\s+(\d+)\s*(\(parallel\))?\s*$` (CRLF normalised to LF).
+/// Heading pattern: `## Task N`, optionally followed by ` (parallel)`, ` (tags: a, b)`,
+/// ` (after: 3, 4)`, ` (context: docs/a.md, src/b.rs)`, ` (dir: src/api)`, and ` (lock: database)`
+/// in any order (CRLF normalised to LF).
 /// Task body runs from the line after the heading until the next heading or EOF.
-/// Tasks are returned sorted by ascending index; gaps are allowed.
+/// ```` ```peal-verify ```` blocks in a body become the task's `verify` commands and `expect:`
+/// lines its `expect` checks (a malformed one is `PealError::InvalidExpectation`).
+/// Tasks are returned sorted by ascending index; gaps are allowed, duplicate indices are
+/// rejected with `PealError::DuplicateTaskIndices` (see `renumber_tasks`).
+/// An optional front-matter block is parsed into `ParsedPlan::metadata` (see `split_front_matter`).
+/// Each task's source lines are recorded in `ParsedPlan::task_spans` (see [`LineSpan`]), so
+/// editors and integrations can point at the part of the file a problem comes from.
+///
+/// `# Stage: <name>` headings group the tasks after them into stages. A stage heading also ends
+/// the previous task's body. Stages run in document order, so tasks are ordered by stage first
+/// and index second; parallel blocks never span a stage boundary. Repeating a stage name
+/// (case-insensitively) is rejected with `PealError::DuplicateStage`.
 pub fn parse_plan(content: &str) -> anyhow::Result<ParsedPlan> {
-    let content = content.replace("\r\n", "\n");
+    check_duplicate_task_indices(content)?;
+    // Spans count lines from the top of the file, front matter included.
+    let (metadata, content, line_offset) = split_front_matter_with_offset(content)?;
     let heading_re = heading_re();
 
     let mut tasks: Vec<Task> = Vec::new();
+    let mut task_spans: BTreeMap<u32, LineSpan> = BTreeMap::new();
     let mut current_index: Option<u32> = None;
+    let mut current_span = LineSpan { start: 0, end: 0 };
     let mut current_parallel = false;
+    let mut current_tags: Vec<String> = Vec::new();
+    let mut current_after: Vec<u32> = Vec::new();
+    let mut current_context: Vec<String> = Vec::new();
+    let mut current_dirs: Vec<String> = Vec::new();
+    let mut current_locks: Vec<String> = Vec::new();
     let mut body_lines: Vec<&str> = Vec::new();
+    let mut stages: Vec<String> = Vec::new();
 
-    for line in content.lines() {
-        if let Some(caps) = heading_re.captures(line) {
+    for (i, line) in content.lines().enumerate() {
+        let line_number = line_offset + i + 1;
+        if let Some(caps) = stage_re().captures(line) {
+            if let Some(idx) = current_index.take() {
+                tasks.push(Task {
+                    index: idx,
+                    content: body_lines.join("\n").trim().to_owned(),
+                    parallel: current_parallel,
+                    tags: std::mem::take(&mut current_tags),
+                    stage: stages.last().cloned(),
+                    after: std::mem::take(&mut current_after),
+                    context: std::mem::take(&mut current_context),
+                    dirs: std::mem::take(&mut current_dirs),
+                    locks: std::mem::take(&mut current_locks),
+                    verify: parse_verify(&body_lines),
+                    expect: parse_expect(idx, &body_lines)?,
+                });
+                task_spans.insert(idx, current_span);
+            }
+            let name = caps[1].to_owned();
+            if stages.iter().any(|s| s.eq_ignore_ascii_case(&name)) {
+                return Err(PealError::DuplicateStage { name }.into());
+            }
+            stages.push(name);
+            body_lines.clear();
+        } else if let Some(caps) = heading_re.captures(line) {
             if let Some(idx) = current_index {
                 tasks.push(Task {
                     index: idx,
                     content: body_lines.join("\n").trim().to_owned(),
                     parallel: current_parallel,
+                    tags: std::mem::take(&mut current_tags),
+                    stage: stages.last().cloned(),
+                    after: std::mem::take(&mut current_after),
+                    context: std::mem::take(&mut current_context),
+                    dirs: std::mem::take(&mut current_dirs),
+                    locks: std::mem::take(&mut current_locks),
+                    verify: parse_verify(&body_lines),
+                    expect: parse_expect(idx, &body_lines)?,
                 });
+                task_spans.insert(idx, current_span);
             }
             // Capture 1 is \d+ so parse cannot fail.
             current_index = Some(
@@ -269,10 +868,32 @@ pub fn parse_plan(content: &str) -> anyhow::Result<ParsedPlan> {
                     .parse::<u32>()
                     .expect("regex guarantees digit-only capture"),
             );
-            current_parallel = caps.get(2).is_some();
+            current_span = LineSpan { start: line_number, end: line_number };
+            current_parallel = false;
+            for suffix in heading_suffix_re().captures_iter(&caps[2]) {
+                current_parallel |= suffix.get(1).is_some();
+                if let Some(list) = suffix.get(2) {
+                    current_tags = parse_tags(list.as_str());
+                }
+                if let Some(list) = suffix.get(3) {
+                    current_after = parse_after(list.as_str());
+                }
+                if let Some(list) = suffix.get(4) {
+                    current_context = parse_context(list.as_str());
+                }
+                if let Some(list) = suffix.get(5) {
+                    current_dirs = parse_context(list.as_str());
+                }
+                if let Some(list) = suffix.get(6) {
+                    current_locks = parse_tags(list.as_str());
+                }
+            }
             body_lines.clear();
         } else if current_index.is_some() {
             body_lines.push(line);
+            if !line.trim().is_empty() {
+                current_span.end = line_number;
+            }
         }
     }
 
@@ -281,14 +902,34 @@ pub fn parse_plan(content: &str) -> anyhow::Result<ParsedPlan> {
             index: idx,
             content: body_lines.join("\n").trim().to_owned(),
             parallel: current_parallel,
+            tags: current_tags,
+            stage: stages.last().cloned(),
+            after: current_after,
+            context: current_context,
+            dirs: current_dirs,
+            locks: current_locks,
+            verify: parse_verify(&body_lines),
+            expect: parse_expect(idx, &body_lines)?,
         });
+        task_spans.insert(idx, current_span);
     }
 
-    tasks.sort_by_key(|t| t.index);
+    // Tasks before the first stage heading (stage `None`) come first.
+    let stage_position =
+        |t: &Task| t.stage.as_ref().and_then(|name| stages.iter().position(|s| s == name));
+    tasks.sort_by_key(|t| (stage_position(t), t.index));
 
     let segments = compute_segments(&tasks);
-
-    Ok(ParsedPlan { tasks, segments })
+    let max_task_index = tasks.iter().map(|t| t.index).max().unwrap_or(0);
+
+    Ok(ParsedPlan {
+        task_positions: index_tasks(&tasks),
+        tasks,
+        segments,
+        metadata,
+        max_task_index,
+        task_spans,
+    })
 }
 
 impl ParsedPlan {
@@ -298,26 +939,76 @@ impl ParsedPlan {
         &self.segments
     }
 
-    /// Look up a task by its index. O(n) scan, fine for typical plan sizes (<50 tasks).
+    /// Look up a task by its index.
     pub fn task_by_index(&self, index: u32) -> Option<&Task> {
-        self.tasks.iter().find(|t| t.index == index)
+        self.task_position(index).map(|pos| &self.tasks[pos])
+    }
+
+    /// Position of the task with the given index in `tasks`, via `task_positions`. Falls back
+    /// to a scan when `tasks` was changed without updating the map.
+    pub fn task_position(&self, index: u32) -> Option<usize> {
+        self.task_positions
+            .get(&index)
+            .copied()
+            .filter(|&pos| self.tasks.get(pos).is_some_and(|t| t.index == index))
+            .or_else(|| self.tasks.iter().position(|t| t.index == index))
+    }
+
+    /// Source lines of the task with the given index, if it came from the plan file.
+    pub fn task_span(&self, index: u32) -> Option<LineSpan> {
+        self.task_spans.get(&index).copied()
+    }
+
+    /// Source lines covering every task of `segment` (first heading through last body line).
+    pub fn segment_span(&self, segment: &Segment) -> Option<LineSpan> {
+        let indices = match segment {
+            Segment::Sequential(idx) => std::slice::from_ref(idx),
+            Segment::Parallel(indices) => indices.as_slice(),
+        };
+        indices
+            .iter()
+            .filter_map(|&idx| self.task_span(idx))
+            .reduce(LineSpan::cover)
+    }
+
+    /// `Task N (lines A–B)` for messages, or just `Task N` when the task has no span.
+    pub fn describe_task(&self, index: u32) -> String {
+        match self.task_span(index) {
+            Some(span) => format!("Task {index} ({span})"),
+            None => format!("Task {index}"),
+        }
     }
 
     /// Return a new plan containing only the task with the given index.
     ///
     /// Segments are recomputed from the filtered task list.
     pub fn filter_single_task(self, index: u32) -> Result<ParsedPlan, PealError> {
+        self.filter_tasks(&[index])
+    }
+
+    /// Return a new plan containing only the tasks with the given indices, in plan order.
+    /// An index not in the plan is `PealError::TaskNotFound`.
+    ///
+    /// Segments are recomputed from the filtered task list.
+    pub fn filter_tasks(self, indices: &[u32]) -> Result<ParsedPlan, PealError> {
         let available: Vec<u32> = self.tasks.iter().map(|t| t.index).collect();
+        if let Some(&index) = indices.iter().find(|i| !available.contains(i)) {
+            return Err(PealError::TaskNotFound { index, available });
+        }
         let tasks: Vec<Task> = self
             .tasks
             .into_iter()
-            .filter(|t| t.index == index)
+            .filter(|t| indices.contains(&t.index))
             .collect();
-        if tasks.is_empty() {
-            return Err(PealError::TaskNotFound { index, available });
-        }
         let segments = compute_segments(&tasks);
-        Ok(ParsedPlan { tasks, segments })
+        Ok(ParsedPlan {
+            task_positions: index_tasks(&tasks),
+            tasks,
+            segments,
+            metadata: self.metadata,
+            max_task_index: self.max_task_index,
+            task_spans: self.task_spans,
+        })
     }
 
     /// Return a new plan containing the task at `index` and all subsequent tasks.
@@ -326,22 +1017,266 @@ impl ParsedPlan {
     /// the target index. Segments are recomputed from the filtered task list.
     pub fn filter_from_task(self, index: u32) -> Result<ParsedPlan, PealError> {
         let available: Vec<u32> = self.tasks.iter().map(|t| t.index).collect();
-        let pos = self.tasks.iter().position(|t| t.index == index);
-        match pos {
+        match self.task_position(index) {
             None => Err(PealError::TaskNotFound { index, available }),
             Some(start) => {
                 let tasks: Vec<Task> = self.tasks.into_iter().skip(start).collect();
                 let segments = compute_segments(&tasks);
-                Ok(ParsedPlan { tasks, segments })
+                Ok(ParsedPlan {
+                    task_positions: index_tasks(&tasks),
+                    tasks,
+                    segments,
+                    metadata: self.metadata,
+                    max_task_index: self.max_task_index,
+                    task_spans: self.task_spans,
+                })
             }
         }
     }
+
+    /// Return a new plan with the tasks selected by tag: with a non-empty `include`, a task
+    /// must carry at least one of those tags; a task carrying any `exclude` tag is dropped.
+    /// Tags compare case-insensitively. Segments are recomputed from the filtered task list.
+    ///
+    /// Fails with `PealError::NoTasksMatchTags` when no task is left.
+    pub fn filter_by_tags(self, include: &[String], exclude: &[String]) -> Result<ParsedPlan, PealError> {
+        let include: Vec<String> = include.iter().map(|t| t.trim().to_lowercase()).collect();
+        let exclude: Vec<String> = exclude.iter().map(|t| t.trim().to_lowercase()).collect();
+        let available: BTreeSet<String> = self.tasks.iter().flat_map(|t| t.tags.iter().cloned()).collect();
+        let tasks: Vec<Task> = self
+            .tasks
+            .into_iter()
+            .filter(|t| include.is_empty() || t.tags.iter().any(|tag| include.contains(tag)))
+            .filter(|t| !t.tags.iter().any(|tag| exclude.contains(tag)))
+            .collect();
+        if tasks.is_empty() {
+            return Err(PealError::NoTasksMatchTags {
+                include,
+                exclude,
+                available: available.into_iter().collect(),
+            });
+        }
+        let segments = compute_segments(&tasks);
+        Ok(ParsedPlan {
+            task_positions: index_tasks(&tasks),
+            tasks,
+            segments,
+            metadata: self.metadata,
+            max_task_index: self.max_task_index,
+            task_spans: self.task_spans,
+        })
+    }
+}
+
+impl ParsedPlan {
+    /// Stage names in run order, each with its tasks (in run order). Empty when the plan has
+    /// no `# Stage:` headings; tasks before the first stage heading are not listed.
+    pub fn stages(&self) -> Vec<(&str, Vec<u32>)> {
+        let mut stages: Vec<(&str, Vec<u32>)> = Vec::new();
+        for task in &self.tasks {
+            let Some(name) = task.stage.as_deref() else {
+                continue;
+            };
+            match stages.last_mut() {
+                Some((last, indices)) if *last == name => indices.push(task.index),
+                _ => stages.push((name, vec![task.index])),
+            }
+        }
+        stages
+    }
+
+    /// Return a new plan with only the tasks of stage `name` (case-insensitive).
+    /// Segments are recomputed from the filtered task list.
+    ///
+    /// Fails with `PealError::StageNotFound` when the plan has no such stage.
+    pub fn filter_by_stage(self, name: &str) -> Result<ParsedPlan, PealError> {
+        let name = name.trim();
+        let available: Vec<String> = self.stages().iter().map(|(s, _)| (*s).to_owned()).collect();
+        let tasks: Vec<Task> = self
+            .tasks
+            .into_iter()
+            .filter(|t| t.stage.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(name)))
+            .collect();
+        if tasks.is_empty() {
+            return Err(PealError::StageNotFound { name: name.to_owned(), available });
+        }
+        let segments = compute_segments(&tasks);
+        Ok(ParsedPlan {
+            task_positions: index_tasks(&tasks),
+            tasks,
+            segments,
+            metadata: self.metadata,
+            max_task_index: self.max_task_index,
+            task_spans: self.task_spans,
+        })
+    }
+}
+
+static PATH_RE: OnceLock<Regex> = OnceLock::new();
+
+/// Path-like token: contains a `/`, or ends in a file extension (`.rs`, `.toml`, `.c`, …).
+fn path_re() -> &'static Regex {
+    PATH_RE.get_or_init(|| {
+        Regex::new(
+            r"(?:[A-Za-z0-9_.-]+/)+(?:[A-Za-z0-9_.-]*[A-Za-z0-9_-])?|[A-Za-z0-9_-][A-Za-z0-9_.-]*\.(?:[A-Za-z][A-Za-z0-9]{1,7}|[chmr])\b",
+        )
+        .expect("valid literal regex")
+    })
+}
+
+static URL_RE: OnceLock<Regex> = OnceLock::new();
+
+fn url_re() -> &'static Regex {
+    URL_RE.get_or_init(|| Regex::new(r"[A-Za-z][A-Za-z0-9+.-]*://\S+").expect("valid literal regex"))
+}
+
+/// File paths mentioned in a task body (heuristic): path-like tokens with URLs removed,
+/// leading `./` and trailing `/` or `.` stripped.
+pub fn extract_paths(text: &str) -> BTreeSet<String> {
+    let text = url_re().replace_all(text, " ");
+    path_re()
+        .find_iter(&text)
+        .map(|m| {
+            let p = m.as_str().trim_start_matches("./");
+            p.trim_end_matches(['/', '.']).to_owned()
+        })
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// A path mentioned by two or more tasks of the same parallel block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParallelPathOverlap {
+    pub path: String,
+    /// Tasks in the block whose bodies mention `path`, in block order.
+    pub task_indices: Vec<u32>,
+}
+
+impl ParsedPlan {
+    /// Lint for parallel-unsafe plans: paths mentioned by more than one task in the same
+    /// parallel block (see `extract_paths`). Concurrent agents editing the same file in one
+    /// worktree tend to clobber each other's changes.
+    pub fn parallel_path_overlaps(&self) -> Vec<ParallelPathOverlap> {
+        let mut overlaps = Vec::new();
+        for segment in &self.segments {
+            let Segment::Parallel(indices) = segment else {
+                continue;
+            };
+            let mut tasks_by_path: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+            for &idx in indices {
+                let Some(task) = self.task_by_index(idx) else {
+                    continue;
+                };
+                for path in extract_paths(&task.content) {
+                    tasks_by_path.entry(path).or_default().push(idx);
+                }
+            }
+            overlaps.extend(
+                tasks_by_path
+                    .into_iter()
+                    .filter(|(_, tasks)| tasks.len() > 1)
+                    .map(|(path, task_indices)| ParallelPathOverlap { path, task_indices }),
+            );
+        }
+        overlaps
+    }
+
+    /// Partition the plan into sessions of about `size` tasks, in run order, for `session_size`.
+    /// Segments are never split: a parallel block that would cross a boundary starts the next
+    /// session, and one larger than `size` is a session of its own.
+    pub fn sessions(&self, size: usize) -> Vec<Vec<u32>> {
+        let size = size.max(1);
+        let mut sessions: Vec<Vec<u32>> = Vec::new();
+        let mut current: Vec<u32> = Vec::new();
+        for segment in &self.segments {
+            let indices = match segment {
+                Segment::Sequential(idx) => std::slice::from_ref(idx),
+                Segment::Parallel(indices) => indices.as_slice(),
+            };
+            if !current.is_empty() && current.len() + indices.len() > size {
+                sessions.push(std::mem::take(&mut current));
+            }
+            current.extend_from_slice(indices);
+        }
+        if !current.is_empty() {
+            sessions.push(current);
+        }
+        sessions
+    }
+
+    /// The first `limit` tasks in run order for which `is_completed` is false (`--limit-tasks`).
+    pub fn first_pending(&self, limit: usize, is_completed: impl Fn(u32) -> bool) -> Vec<u32> {
+        self.segments
+            .iter()
+            .flat_map(|segment| match segment {
+                Segment::Sequential(idx) => std::slice::from_ref(idx),
+                Segment::Parallel(indices) => indices.as_slice(),
+            })
+            .copied()
+            .filter(|&idx| !is_completed(idx))
+            .take(limit)
+            .collect()
+    }
+
+    /// A copy of the plan with only the tasks in `indices` (in plan order). Segments are
+    /// recomputed from the remaining tasks.
+    pub fn subset(&self, indices: &[u32]) -> ParsedPlan {
+        let tasks: Vec<Task> =
+            self.tasks.iter().filter(|t| indices.contains(&t.index)).cloned().collect();
+        ParsedPlan {
+            segments: compute_segments(&tasks),
+            task_positions: index_tasks(&tasks),
+            tasks,
+            metadata: self.metadata.clone(),
+            max_task_index: self.max_task_index,
+            task_spans: self.task_spans.clone(),
+        }
+    }
+
+    /// Split the `pending` tasks of a parallel block into waves of at most `width` tasks that
+    /// run one after another, honoring `(after: N)` hints: a task waits for a later wave while
+    /// any hinted task of `pending` has not been placed in an earlier one. Hints on tasks
+    /// outside `pending` are ignored. Waves are filled in `pending` order; when every remaining
+    /// task waits on another (a hint cycle), the first one is placed anyway so the block still
+    /// runs. With no hints this is `pending.chunks(width)`.
+    pub fn soft_dependency_waves(&self, pending: &[u32], width: usize) -> Vec<Vec<u32>> {
+        let width = width.max(1);
+        let hints = |idx: u32| -> Vec<u32> {
+            self.task_by_index(idx)
+                .map(|t| t.after.iter().copied().filter(|a| *a != idx && pending.contains(a)).collect())
+                .unwrap_or_default()
+        };
+        let mut remaining: Vec<u32> = pending.to_vec();
+        let mut placed: HashSet<u32> = HashSet::new();
+        let mut waves: Vec<Vec<u32>> = Vec::new();
+        while !remaining.is_empty() {
+            let mut wave: Vec<u32> = remaining
+                .iter()
+                .copied()
+                .filter(|&idx| hints(idx).iter().all(|a| placed.contains(a)))
+                .take(width)
+                .collect();
+            if wave.is_empty() {
+                debug!(task_index = remaining[0], "after: hints form a cycle; ignoring them for this task");
+                wave.push(remaining[0]);
+            }
+            remaining.retain(|idx| !wave.contains(idx));
+            placed.extend(wave.iter().copied());
+            waves.push(wave);
+        }
+        waves
+    }
+}
+
+/// Map each task index to its position in `tasks` (see [`ParsedPlan::task_positions`]).
+pub(crate) fn index_tasks(tasks: &[Task]) -> HashMap<u32, usize> {
+    tasks.iter().enumerate().map(|(pos, t)| (t.index, pos)).collect()
 }
 
 /// Group an ordered task list into execution segments.
 ///
-/// Consecutive tasks with `parallel == true` form one `Segment::Parallel` block
-/// (unless only one task, which is treated as `Segment::Sequential`).
+/// Consecutive tasks with `parallel == true` in the same stage form one `Segment::Parallel`
+/// block (unless only one task, which is treated as `Segment::Sequential`).
 pub(crate) fn compute_segments(tasks: &[Task]) -> Vec<Segment> {
     let mut segments: Vec<Segment> = Vec::new();
     let mut i = 0;
@@ -349,8 +1284,9 @@ pub(crate) fn compute_segments(tasks: &[Task]) -> Vec<Segment> {
     while i < tasks.len() {
         if tasks[i].parallel {
             let mut block = vec![tasks[i].index];
+            let stage = &tasks[i].stage;
             i += 1;
-            while i < tasks.len() && tasks[i].parallel {
+            while i < tasks.len() && tasks[i].parallel && tasks[i].stage == *stage {
                 block.push(tasks[i].index);
                 i += 1;
             }
@@ -396,6 +1332,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -413,6 +1351,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         }
     }
 
@@ -577,6 +1582,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn normalization_rejection_reason_names_the_problem() {
+        assert_eq!(normalization_rejection_reason("  \n"), "it was empty");
+        assert_eq!(
+            normalization_rejection_reason("Here is your plan:\n1. Do it."),
+            "it has no task headings of the form \"## Task N\""
+        );
+        let duplicate = normalization_rejection_reason("## Task 1\nA\n## Task 1\nB");
+        assert!(duplicate.starts_with("it could not be parsed as a plan ("), "{duplicate}");
+    }
+
     #[test]
     fn detected_content_still_parses_correctly() {
         let content = "## Task 1\nDo it.\n\n## Task 2 (parallel)\nOther.";
@@ -938,6 +1954,18 @@ G
         }
     }
 
+    #[test]
+    fn filter_tasks_keeps_plan_order_and_rejects_unknown_indices() {
+        let plan = make_plan_123().filter_tasks(&[3, 1]).unwrap();
+        let indices: Vec<u32> = plan.tasks.iter().map(|t| t.index).collect();
+        assert_eq!(indices, vec![1, 3]);
+        assert_eq!(plan.segments, vec![Segment::Sequential(1), Segment::Sequential(3)]);
+        assert!(matches!(
+            make_plan_123().filter_tasks(&[1, 9]),
+            Err(PealError::TaskNotFound { index: 9, .. })
+        ));
+    }
+
     #[test]
     fn filter_from_task_returns_tail() {
         let plan = make_plan_123().filter_from_task(2).unwrap();
@@ -1107,4 +2135,548 @@ D
         let plan = make_plan_123();
         assert!(plan.task_by_index(0).is_none());
     }
+
+    #[test]
+    fn task_positions_follow_filters_and_survive_task_edits() {
+        let plan = make_plan_123().filter_from_task(2).unwrap();
+        assert_eq!(plan.task_positions.len(), 2);
+        assert_eq!(plan.task_position(3), Some(1));
+        assert_eq!(plan.task_by_index(3).unwrap().content, "C");
+
+        // A caller editing `tasks` directly leaves the map stale; lookups still scan.
+        let mut plan = plan;
+        plan.tasks.remove(0);
+        assert_eq!(plan.task_by_index(3).unwrap().content, "C");
+        assert!(plan.task_by_index(2).is_none());
+    }
+
+    #[test]
+    fn front_matter_parsed_into_metadata() {
+        let content = "---\n\
+title: Login flow\n\
+description: \"Add login, with tests\"\n\
+model: gpt-5.2\n\
+phase_timeout_sec: 600\n\
+post_run_timeout_sec: 60\n\
+repo_subdir: services/auth\n\
+labels: [auth, backend]\n\
+owner: team-auth\n\
+---\n\
+## Task 1\n\
+Do it.\n";
+        let plan = parse_plan(content).unwrap();
+        let m = &plan.metadata;
+        assert_eq!(m.title.as_deref(), Some("Login flow"));
+        assert_eq!(m.description.as_deref(), Some("Add login, with tests"));
+        assert_eq!(m.model.as_deref(), Some("gpt-5.2"));
+        assert_eq!(m.phase_timeout_sec, Some(600));
+        assert_eq!(m.post_run_timeout_sec, Some(60));
+        assert_eq!(m.repo_subdir, Some(PathBuf::from("services/auth")));
+        assert_eq!(m.labels, vec!["auth", "backend"]);
+        assert_eq!(m.extra.get("owner").map(String::as_str), Some("team-auth"));
+        assert_eq!(plan.tasks.len(), 1);
+        assert_eq!(plan.tasks[0].content, "Do it.");
+    }
+
+    #[test]
+    fn front_matter_block_list_and_comments() {
+        let content = "---\r\n# plan metadata\r\nlabels:\r\n  - one\r\n  - 'two'\r\n\r\ntitle: T\r\n---\r\n## Task 1\r\nBody\r\n";
+        let (m, body) = split_front_matter(content).unwrap();
+        assert_eq!(m.labels, vec!["one", "two"]);
+        assert_eq!(m.title.as_deref(), Some("T"));
+        assert_eq!(body, "## Task 1\nBody");
+    }
+
+    #[test]
+    fn no_front_matter_returns_default_metadata() {
+        let content = "## Task 1\nBody\n";
+        let (m, body) = split_front_matter(content).unwrap();
+        assert!(m.is_empty());
+        assert_eq!(body, content);
+    }
+
+    #[test]
+    fn leading_horizontal_rule_is_not_front_matter() {
+        let content = "---\n## Task 1\nFirst\n---\n## Task 2\nSecond\n";
+        let plan = parse_plan(content).unwrap();
+        assert!(plan.metadata.is_empty());
+        assert_eq!(plan.tasks.len(), 2);
+
+        let unclosed = "---\nSome preamble\n## Task 1\nBody\n";
+        let plan = parse_plan(unclosed).unwrap();
+        assert!(plan.metadata.is_empty());
+        assert_eq!(plan.tasks.len(), 1);
+    }
+
+    #[test]
+    fn front_matter_invalid_line_returns_err() {
+        let err = split_front_matter("---\njust text\n---\n## Task 1\nx\n").unwrap_err();
+        assert!(
+            matches!(&err, PealError::InvalidPlanFrontMatter { detail } if detail.contains("line 2")),
+            "got {err:?}"
+        );
+    }
+
+    #[test]
+    fn front_matter_invalid_timeout_returns_err() {
+        let err = split_front_matter("---\nphase_timeout_sec: soon\n---\n## Task 1\nx\n").unwrap_err();
+        assert!(
+            matches!(&err, PealError::InvalidPlanFrontMatter { detail } if detail.contains("phase_timeout_sec")),
+            "got {err:?}"
+        );
+    }
+
+    #[test]
+    fn filters_preserve_metadata() {
+        let content = "---\ntitle: T\n---\n## Task 1\nA\n## Task 2\nB\n";
+        let single = parse_plan(content).unwrap().filter_single_task(2).unwrap();
+        assert_eq!(single.metadata.title.as_deref(), Some("T"));
+        let tail = parse_plan(content).unwrap().filter_from_task(1).unwrap();
+        assert_eq!(tail.metadata.title.as_deref(), Some("T"));
+    }
+
+
+    // -- expand_vars --
+
+    #[test]
+    fn expand_vars_replaces_known_placeholders() {
+        let vars: BTreeMap<String, String> =
+            [("SERVICE_NAME".to_owned(), "billing".to_owned())].into_iter().collect();
+        let out = expand_vars("## Task 1\nDeploy {{SERVICE_NAME}} and {{ SERVICE_NAME }}.", &vars);
+        assert_eq!(out, "## Task 1\nDeploy billing and billing.");
+    }
+
+    #[test]
+    fn expand_vars_leaves_undefined_and_values_unexpanded() {
+        let vars: BTreeMap<String, String> =
+            [("A".to_owned(), "{{B}}".to_owned())].into_iter().collect();
+        let out = expand_vars("{{A}} {{UNKNOWN}} {{not valid}}", &vars);
+        assert_eq!(out, "{{B}} {{UNKNOWN}} {{not valid}}");
+    }
+
+    #[test]
+    fn expand_vars_without_vars_is_identity() {
+        let content = "## Task 1\nUse {{SERVICE_NAME}}.";
+        assert_eq!(expand_vars(content, &BTreeMap::new()), content);
+    }
+
+
+    #[test]
+    fn parse_plan_rejects_duplicate_indices_with_line_numbers() {
+        let content = "## Task 1\nA\n## Task 2\nB\n## Task 2 (parallel)\nC\n## Task 1\nD\n";
+        let err = parse_plan(content).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("duplicate task indices"), "got: {msg}");
+        assert!(msg.contains("Task 1: lines 1, 7"), "got: {msg}");
+        assert!(msg.contains("Task 2: lines 3, 5"), "got: {msg}");
+    }
+
+    #[test]
+    fn parse_plan_records_task_and_segment_spans() {
+        let content = "---\ntitle: T\n---\nIntro\n## Task 1\nA\n\nmore A\n\n\n## Task 2 (parallel)\nB\n# Stage: Two\n## Task 3 (parallel)\n## Task 4 (parallel)\nD\n\n";
+        let plan = parse_plan(content).unwrap();
+
+        assert_eq!(plan.task_span(1), Some(LineSpan { start: 5, end: 8 }));
+        assert_eq!(plan.task_span(2), Some(LineSpan { start: 11, end: 12 }));
+        assert_eq!(plan.task_span(3), Some(LineSpan { start: 14, end: 14 }));
+        assert_eq!(plan.task_span(4), Some(LineSpan { start: 15, end: 16 }));
+        assert_eq!(plan.task_span(9), None);
+
+        assert_eq!(
+            plan.segment_span(&Segment::Parallel(vec![3, 4])),
+            Some(LineSpan { start: 14, end: 16 })
+        );
+        assert_eq!(plan.describe_task(1), "Task 1 (lines 5\u{2013}8)");
+        assert_eq!(plan.describe_task(3), "Task 3 (line 14)");
+        assert_eq!(plan.describe_task(9), "Task 9");
+
+        let filtered = plan.filter_single_task(2).unwrap();
+        assert_eq!(filtered.task_span(2), Some(LineSpan { start: 11, end: 12 }));
+    }
+
+    #[test]
+    fn first_pending_takes_tasks_in_run_order_skipping_completed() {
+        let plan = parse_plan("## Task 1\nA\n## Task 2 (parallel)\nB\n## Task 3 (parallel)\nC\n## Task 4\nD\n").unwrap();
+        assert_eq!(plan.first_pending(2, |i| i == 1), vec![2, 3]);
+        assert_eq!(plan.first_pending(10, |i| i <= 2), vec![3, 4]);
+        let limited = plan.subset(&plan.first_pending(2, |i| i == 1));
+        assert_eq!(limited.segments, vec![Segment::Parallel(vec![2, 3])]);
+    }
+
+    #[test]
+    fn sessions_chunk_tasks_without_splitting_parallel_blocks() {
+        let plan = parse_plan(
+            "## Task 1\nA\n## Task 2\nB\n## Task 3 (parallel)\nC\n## Task 4 (parallel)\nD\n## Task 5 (parallel)\nE\n## Task 6\nF\n",
+        )
+        .unwrap();
+
+        assert_eq!(plan.sessions(2), vec![vec![1, 2], vec![3, 4, 5], vec![6]]);
+        assert_eq!(plan.sessions(4), vec![vec![1, 2], vec![3, 4, 5, 6]]);
+        assert_eq!(plan.sessions(10), vec![vec![1, 2, 3, 4, 5, 6]]);
+
+        let session = plan.subset(&[3, 4, 5]);
+        assert_eq!(session.segments, vec![Segment::Parallel(vec![3, 4, 5])]);
+        assert_eq!(session.max_task_index, 6);
+    }
+
+    #[test]
+    fn duplicate_line_numbers_count_front_matter() {
+        let content = "---\ntitle: T\n---\n## Task 3\nA\n## Task 3\nB\n";
+        assert_eq!(find_duplicate_task_indices(content), vec![(3, vec![4, 6])]);
+        assert!(check_duplicate_task_indices("## Task 1\n## Task 2\n").is_ok());
+    }
+
+    #[test]
+    fn renumber_tasks_preserves_order_markers_and_bodies() {
+        let content = "---\ntitle: T\n---\r\nIntro\r\n## Task 5\r\nFirst\r\n## Task 2 (parallel)\r\nSecond\r\n## Task  2 (parallel)\r\nThird\r\n";
+        let (renumbered, changed) = renumber_tasks(content);
+        assert!(changed);
+        assert_eq!(
+            renumbered,
+            "---\ntitle: T\n---\r\nIntro\r\n## Task 1\r\nFirst\r\n## Task 2 (parallel)\r\nSecond\r\n## Task  3 (parallel)\r\nThird\r\n"
+        );
+        let parsed = parse_plan(&renumbered).unwrap();
+        let indices: Vec<u32> = parsed.tasks.iter().map(|t| t.index).collect();
+        assert_eq!(indices, vec![1, 2, 3]);
+        assert_eq!(parsed.segments, vec![Segment::Sequential(1), Segment::Parallel(vec![2, 3])]);
+    }
+
+    #[test]
+    fn renumber_tasks_sequential_plan_is_unchanged() {
+        let content = "## Task 1\nA\n## Task 2\nB";
+        assert_eq!(renumber_tasks(content), (content.to_owned(), false));
+    }
+
+
+    #[test]
+    fn extract_paths_finds_files_and_dirs_but_not_urls_or_prose() {
+        let paths = extract_paths(
+            "Edit `src/main.rs` and ./Cargo.toml, see https://example.com/a/b.html, \
+             e.g. version 1.2 of docs/ and the lib.c file.",
+        );
+        let got: Vec<&str> = paths.iter().map(String::as_str).collect();
+        assert_eq!(got, vec!["Cargo.toml", "docs", "lib.c", "src/main.rs"]);
+    }
+
+    #[test]
+    fn parallel_path_overlaps_only_within_parallel_blocks() {
+        let plan = parse_plan(
+            "## Task 1\nTouch src/config.rs\n\
+             ## Task 2 (parallel)\nAdd a field to src/config.rs and README.md\n\
+             ## Task 3 (parallel)\nUpdate README.md\n\
+             ## Task 4 (parallel)\nAlso edit src/config.rs\n\
+             ## Task 5\nUpdate README.md again\n",
+        )
+        .unwrap();
+        assert_eq!(
+            plan.parallel_path_overlaps(),
+            vec![
+                ParallelPathOverlap {
+                    path: "README.md".to_owned(),
+                    task_indices: vec![2, 3],
+                },
+                ParallelPathOverlap {
+                    path: "src/config.rs".to_owned(),
+                    task_indices: vec![2, 4],
+                },
+            ]
+        );
+    }
+
+
+    // -- task tags --
+
+    fn make_tagged_plan() -> ParsedPlan {
+        parse_plan(
+            "## Task 1 (tags: Backend, db)\nA\n\n\
+             ## Task 2 (parallel) (tags: frontend)\nB\n\n\
+             ## Task 3 (tags: backend) (parallel)\nC\n\n\
+             ## Task 4\nD\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_plan_reads_tags_in_either_order_with_parallel() {
+        let plan = make_tagged_plan();
+        let tags: Vec<(u32, Vec<&str>, bool)> = plan
+            .tasks
+            .iter()
+            .map(|t| (t.index, t.tags.iter().map(String::as_str).collect(), t.parallel))
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                (1, vec!["backend", "db"], false),
+                (2, vec!["frontend"], true),
+                (3, vec!["backend"], true),
+                (4, vec![], false),
+            ]
+        );
+        assert_eq!(plan.tasks[0].content, "A");
+        assert_eq!(parse_tags(" a, ,A , b"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn parse_plan_reads_after_hints_in_any_suffix_position() {
+        let plan = parse_plan(
+            "## Task 1 (parallel)\nA\n\n\
+             ## Task 2 (after: 1, 3 ,1) (parallel)\nB\n\n\
+             ## Task 3 (parallel) (tags: ui) (after: 1)\nC\n\n\
+             ## Task 4 (after: one)\nnot a heading\n",
+        )
+        .unwrap();
+        let after: Vec<(u32, Vec<u32>)> = plan.tasks.iter().map(|t| (t.index, t.after.clone())).collect();
+        assert_eq!(after, vec![(1, vec![]), (2, vec![1, 3]), (3, vec![1])]);
+        assert_eq!(plan.tasks[2].tags, vec!["ui"]);
+        assert_eq!(plan.segments, vec![Segment::Parallel(vec![1, 2, 3])]);
+        assert!(plan.tasks[2].content.contains("## Task 4 (after: one)"));
+    }
+
+    #[test]
+    fn parse_plan_reads_context_files() {
+        let plan = parse_plan(
+            "## Task 1 (context: docs/design.md, src/api/mod.rs ,docs/design.md)\nA\n\n\
+             ## Task 2 (parallel) (context: notes.md) (tags: api) (dir: services/api, libs)\nB\n\n\
+             ## Task 3\nC\n",
+        )
+        .unwrap();
+        assert_eq!(plan.tasks[0].context, vec!["docs/design.md", "src/api/mod.rs"]);
+        assert_eq!(plan.tasks[1].context, vec!["notes.md"]);
+        assert!(plan.tasks[1].parallel);
+        assert_eq!(plan.tasks[1].tags, vec!["api"]);
+        assert_eq!(plan.tasks[1].dirs, vec!["services/api", "libs"]);
+        assert!(plan.tasks[0].dirs.is_empty());
+        assert!(plan.tasks[2].context.is_empty());
+    }
+
+    #[test]
+    fn parse_lock_suffix_lowercases_and_dedups_names() {
+        let plan = parse_plan(
+            "## Task 1 (parallel) (lock: Database, cache ,database)\nA\n\n## Task 2 (lock: db) (parallel) (tags: x)\nB\n\n## Task 3 (parallel)\nC\n",
+        )
+        .unwrap();
+        let locks: Vec<Vec<String>> = plan.tasks.iter().map(|t| t.locks.clone()).collect();
+        assert_eq!(locks, vec![vec!["database", "cache"], vec!["db"], vec![]]);
+        assert!(plan.tasks[1].parallel);
+    }
+
+    #[test]
+    fn parse_plan_reads_peal_verify_blocks() {
+        let plan = parse_plan(
+            "## Task 1\nAdd foo.\n\n```peal-verify\ncargo test -p foo\n\n  cargo clippy -p foo  \n```\n\n\
+             ```sh\nnot a check\n```\n```peal-verify\nmake lint\n```\n\n\
+             ## Task 2\nB\n",
+        )
+        .unwrap();
+        assert_eq!(plan.tasks[0].verify, vec!["cargo test -p foo", "cargo clippy -p foo", "make lint"]);
+        assert!(plan.tasks[0].content.contains("```peal-verify"), "block stays in the task body");
+        assert!(plan.tasks[1].verify.is_empty());
+    }
+
+    #[test]
+    fn parse_plan_reads_expect_lines_and_rejects_bad_ones() {
+        let plan = parse_plan("## Task 1\nAdd foo.\nexpect: file exists src/foo.rs\n- expect: cmd \"cargo test\" succeeds\n").unwrap();
+        assert_eq!(
+            plan.tasks[0].expect,
+            vec![
+                Expectation::FileExists("src/foo.rs".to_owned()),
+                Expectation::Command { command: "cargo test".to_owned(), succeeds: true },
+            ]
+        );
+        assert!(plan.tasks[0].content.contains("expect: file exists"), "lines stay in the task body");
+
+        let err = parse_plan("## Task 1\nA\n## Task 2\nexpect: file exist x\n").unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<PealError>(), Some(PealError::InvalidExpectation { task_index: 2, line, .. }) if line == "expect: file exist x"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn soft_dependency_waves_defer_hinted_tasks() {
+        let plan = parse_plan(
+            "## Task 1 (parallel)\nA\n\n\
+             ## Task 2 (parallel) (after: 3)\nB\n\n\
+             ## Task 3 (parallel)\nC\n\n\
+             ## Task 4 (parallel) (after: 9)\nD\n",
+        )
+        .unwrap();
+        // Task 2 waits for task 3 even with spare capacity; the hint on task 9 is ignored.
+        assert_eq!(plan.soft_dependency_waves(&[1, 2, 3, 4], 4), vec![vec![1, 3, 4], vec![2]]);
+        assert_eq!(plan.soft_dependency_waves(&[1, 2, 3, 4], 2), vec![vec![1, 3], vec![2, 4]]);
+        assert_eq!(plan.soft_dependency_waves(&[1, 2, 3, 4], 1).concat(), vec![1, 3, 2, 4]);
+        // Task 3 is already done: the hint no longer applies.
+        assert_eq!(plan.soft_dependency_waves(&[2, 4], 2), vec![vec![2, 4]]);
+
+        let cycle = parse_plan(
+            "## Task 1 (parallel) (after: 2)\nA\n\n## Task 2 (parallel) (after: 1)\nB\n",
+        )
+        .unwrap();
+        assert_eq!(cycle.soft_dependency_waves(&[1, 2], 2), vec![vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn filter_by_tags_includes_and_excludes() {
+        let plan = make_tagged_plan()
+            .filter_by_tags(&["BACKEND".to_owned()], &[])
+            .unwrap();
+        let indices: Vec<u32> = plan.tasks.iter().map(|t| t.index).collect();
+        assert_eq!(indices, vec![1, 3]);
+        assert_eq!(plan.max_task_index, 4);
+
+        let plan = make_tagged_plan()
+            .filter_by_tags(&["backend".to_owned(), "frontend".to_owned()], &["db".to_owned()])
+            .unwrap();
+        let indices: Vec<u32> = plan.tasks.iter().map(|t| t.index).collect();
+        assert_eq!(indices, vec![2, 3]);
+        assert_eq!(plan.segments, vec![Segment::Parallel(vec![2, 3])]);
+
+        let plan = make_tagged_plan().filter_by_tags(&[], &["backend".to_owned()]).unwrap();
+        let indices: Vec<u32> = plan.tasks.iter().map(|t| t.index).collect();
+        assert_eq!(indices, vec![2, 4], "untagged tasks survive an exclude-only filter");
+    }
+
+    #[test]
+    fn filter_by_tags_errors_when_nothing_matches() {
+        let err = make_tagged_plan().filter_by_tags(&["infra".to_owned()], &[]).unwrap_err();
+        match err {
+            PealError::NoTasksMatchTags { include, available, .. } => {
+                assert_eq!(include, vec!["infra"]);
+                assert_eq!(available, vec!["backend", "db", "frontend"]);
+            }
+            other => panic!("expected NoTasksMatchTags, got: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn renumber_tasks_keeps_tag_suffix() {
+        let (out, changed) = renumber_tasks("## Task 5 (tags: db) (parallel)\nA\n");
+        assert!(changed);
+        assert_eq!(out, "## Task 1 (tags: db) (parallel)\nA\n");
+    }
+
+
+    #[test]
+    fn normalized_plan_path_sits_next_to_the_source_plan() {
+        assert_eq!(
+            normalized_plan_path(Path::new("docs/prd.md")),
+            PathBuf::from("docs/prd.normalized.md")
+        );
+        assert_eq!(normalized_plan_path(Path::new("notes")), PathBuf::from("notes.normalized.md"));
+    }
+
+    #[test]
+    fn review_without_editor_prints_plan_and_requires_yes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.normalized.md");
+        fs::write(&path, "## Task 1\nDo it.\n").unwrap();
+
+        let mut out = Vec::new();
+        let reviewed =
+            review_normalized_plan(&path, None, &mut "YES\n".as_bytes(), &mut out).unwrap();
+        assert_eq!(reviewed, "## Task 1\nDo it.\n");
+        let shown = String::from_utf8(out).unwrap();
+        assert!(shown.contains("Do it.") && shown.contains("[y/N]"), "shown: {shown}");
+
+        let err = review_normalized_plan(&path, None, &mut "\n".as_bytes(), &mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(err, PealError::NormalizedPlanRejected { .. }), "got: {err:?}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn review_with_editor_returns_edited_content() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.normalized.md");
+        fs::write(&path, "## Task 1\nDo it.\n").unwrap();
+        let editor = dir.path().join("editor.sh");
+        fs::write(&editor, "#!/bin/sh\nprintf '## Task 2\\nMore.\\n' >> \"$2\"\n").unwrap();
+        fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cmd = format!("{} --wait", editor.display());
+        let reviewed =
+            review_normalized_plan(&path, Some(&cmd), &mut "".as_bytes(), &mut Vec::new()).unwrap();
+        assert_eq!(reviewed, "## Task 1\nDo it.\n## Task 2\nMore.\n");
+
+        let err = review_normalized_plan(&path, Some("false"), &mut "".as_bytes(), &mut Vec::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("exited with"), "got: {err}");
+    }
+
+
+    #[test]
+    fn stage_headings_group_tasks_and_bound_parallel_blocks() {
+        let input = "\
+## Task 1
+Setup.
+
+# Stage: Foundation
+Stage notes are not part of any task.
+
+## Task 3 (parallel)
+C
+
+## Task 2 (parallel)
+B
+
+## Stage: Migration
+## Task 4 (parallel)
+D
+
+## Task 5 (parallel)
+E
+";
+        let plan = parse_plan(input).unwrap();
+
+        let order: Vec<u32> = plan.tasks.iter().map(|t| t.index).collect();
+        assert_eq!(order, vec![1, 2, 3, 4, 5]);
+        assert_eq!(plan.tasks[0].stage, None);
+        assert_eq!(plan.tasks[0].content, "Setup.");
+        assert_eq!(plan.tasks[1].stage.as_deref(), Some("Foundation"));
+        assert_eq!(plan.tasks[2].content, "C", "stage heading ends the previous body");
+        assert_eq!(plan.tasks[3].stage.as_deref(), Some("Migration"));
+        assert_eq!(
+            plan.segments,
+            vec![
+                Segment::Sequential(1),
+                Segment::Parallel(vec![2, 3]),
+                Segment::Parallel(vec![4, 5]),
+            ],
+            "parallel blocks never cross a stage boundary"
+        );
+        assert_eq!(plan.stages(), vec![("Foundation", vec![2, 3]), ("Migration", vec![4, 5])]);
+    }
+
+    #[test]
+    fn stages_run_in_document_order_even_against_index_order() {
+        let input = "# Stage: First\n## Task 5\nA\n# Stage: Second\n## Task 2\nB\n";
+        let plan = parse_plan(input).unwrap();
+        let order: Vec<u32> = plan.tasks.iter().map(|t| t.index).collect();
+        assert_eq!(order, vec![5, 2], "stage order wins over task index");
+        assert_eq!(plan.max_task_index, 5);
+
+        let input = "# Stage: B\n## Task 1\nA\n# Stage: A\n## Task 2\nB\n# Stage: b\n## Task 3\nC\n";
+        let err = parse_plan(input).unwrap_err();
+        assert!(err.to_string().contains("Stage 'b' appears more than once"), "{err}");
+    }
+
+    #[test]
+    fn filter_by_stage_is_case_insensitive_and_lists_stages_on_miss() {
+        let input = "# Stage: Foundation\n## Task 1\nA\n## Task 2\nB\n# Stage: Cleanup\n## Task 3\nC\n";
+        let plan = parse_plan(input).unwrap();
+
+        let filtered = plan.clone().filter_by_stage("foundation").unwrap();
+        let order: Vec<u32> = filtered.tasks.iter().map(|t| t.index).collect();
+        assert_eq!(order, vec![1, 2]);
+        assert_eq!(filtered.max_task_index, 3);
+
+        let err = plan.filter_by_stage("deploy").unwrap_err();
+        assert!(
+            matches!(&err, PealError::StageNotFound { name, available }
+                if name == "deploy" && available == &["Foundation", "Cleanup"]),
+            "{err:?}"
+        );
+    }
 }
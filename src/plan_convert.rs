@@ -0,0 +1,339 @@
+//! `peal plan convert`: translate a plan between the canonical Markdown format and a
+//! structured YAML or JSON document.
+//!
+//! The structured form is a [`PlanDocument`]: the front-matter metadata plus one entry per
+//! task with its index, parallel marker, tags, stage, `after` hints, context files, `dir`
+//! paths, and body. Converting Markdown to YAML or JSON and back yields a plan that parses to
+//! the same tasks, segments, and metadata;
+//! text outside task bodies (the preamble, text between a stage heading and its first task)
+//! is not carried over.
+//!
+//! YAML is read and written with `serde_yaml`, so any YAML document of that shape is accepted;
+//! multi-line task bodies are written as `|-` literal blocks.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::PealError;
+use crate::plan::{self, ParsedPlan, PlanMetadata};
+
+/// Format of a plan file for [`convert`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanFormat {
+    Markdown,
+    Yaml,
+    Json,
+}
+
+impl PlanFormat {
+    /// Parse a `--from` / `--to` value (`md`, `yaml`, or `json`).
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "md" | "markdown" => Some(PlanFormat::Markdown),
+            "yaml" | "yml" => Some(PlanFormat::Yaml),
+            "json" => Some(PlanFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from a file extension; anything unrecognized is Markdown.
+    pub fn from_path(path: &std::path::Path) -> Self {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| PlanFormat::parse(&e.to_ascii_lowercase()))
+            .unwrap_or(PlanFormat::Markdown)
+    }
+}
+
+/// Structured plan: metadata and tasks in run order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanDocument {
+    #[serde(default, skip_serializing_if = "PlanMetadata::is_empty")]
+    pub metadata: PlanMetadata,
+    pub tasks: Vec<PlanDocumentTask>,
+}
+
+/// One task of a [`PlanDocument`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanDocumentTask {
+    pub index: u32,
+    #[serde(default)]
+    pub parallel: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dirs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locks: Vec<String>,
+    #[serde(default)]
+    pub content: String,
+}
+
+impl PlanDocument {
+    /// Structured form of a parsed plan.
+    pub fn from_parsed(parsed: &ParsedPlan) -> Self {
+        PlanDocument {
+            metadata: parsed.metadata.clone(),
+            tasks: parsed
+                .tasks
+                .iter()
+                .map(|t| PlanDocumentTask {
+                    index: t.index,
+                    parallel: t.parallel,
+                    tags: t.tags.clone(),
+                    stage: t.stage.clone(),
+                    after: t.after.clone(),
+                    context: t.context.clone(),
+                    dirs: t.dirs.clone(),
+                    locks: t.locks.clone(),
+                    content: t.content.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Convert plan `content` from one format to another.
+pub fn convert(content: &str, from: PlanFormat, to: PlanFormat) -> Result<String, PealError> {
+    let document = read(content, from)?;
+    write(&document, to)
+}
+
+/// Read plan `content` in `format` into a [`PlanDocument`].
+pub fn read(content: &str, format: PlanFormat) -> Result<PlanDocument, PealError> {
+    match format {
+        PlanFormat::Markdown => {
+            let parsed = plan::parse_plan(content).map_err(|e| convert_err(format!("{e:#}")))?;
+            Ok(PlanDocument::from_parsed(&parsed))
+        }
+        PlanFormat::Json => serde_json::from_str(content).map_err(|e| convert_err(format!("JSON: {e}"))),
+        PlanFormat::Yaml => serde_yaml::from_str(content).map_err(|e| convert_err(format!("YAML: {e}"))),
+    }
+}
+
+/// Write `document` in `format`.
+pub fn write(document: &PlanDocument, format: PlanFormat) -> Result<String, PealError> {
+    match format {
+        PlanFormat::Markdown => to_markdown(document),
+        PlanFormat::Json => serde_json::to_string_pretty(document)
+            .map(|s| s + "\n")
+            .map_err(|e| convert_err(e.to_string())),
+        PlanFormat::Yaml => serde_yaml::to_string(document).map_err(|e| convert_err(e.to_string())),
+    }
+}
+
+fn convert_err(detail: String) -> PealError {
+    PealError::PlanConvertFailed { detail }
+}
+
+// -- Markdown --
+
+/// Canonical Markdown: front matter, tasks without a stage, then each stage in order of
+/// first appearance (the order `parse_plan` runs them in).
+fn to_markdown(document: &PlanDocument) -> Result<String, PealError> {
+    let mut out = String::new();
+    if !document.metadata.is_empty() {
+        out.push_str("---\n");
+        out.push_str(&front_matter(&document.metadata)?);
+        out.push_str("---\n\n");
+    }
+
+    let mut stages: Vec<Option<&str>> = vec![None];
+    for task in &document.tasks {
+        let stage = task.stage.as_deref();
+        if !stages.contains(&stage) {
+            stages.push(stage);
+        }
+    }
+    for stage in stages {
+        let tasks: Vec<&PlanDocumentTask> =
+            document.tasks.iter().filter(|t| t.stage.as_deref() == stage).collect();
+        if let Some(name) = stage {
+            out.push_str(&format!("# Stage: {name}\n\n"));
+        }
+        for task in tasks {
+            out.push_str(&format!("## Task {}", task.index));
+            if task.parallel {
+                out.push_str(" (parallel)");
+            }
+            if !task.tags.is_empty() {
+                out.push_str(&format!(" (tags: {})", task.tags.join(", ")));
+            }
+            if !task.after.is_empty() {
+                let after: Vec<String> = task.after.iter().map(u32::to_string).collect();
+                out.push_str(&format!(" (after: {})", after.join(", ")));
+            }
+            if !task.context.is_empty() {
+                out.push_str(&format!(" (context: {})", task.context.join(", ")));
+            }
+            if !task.dirs.is_empty() {
+                out.push_str(&format!(" (dir: {})", task.dirs.join(", ")));
+            }
+            if !task.locks.is_empty() {
+                out.push_str(&format!(" (lock: {})", task.locks.join(", ")));
+            }
+            out.push_str("\n\n");
+            if !task.content.trim().is_empty() {
+                out.push_str(task.content.trim());
+                out.push_str("\n\n");
+            }
+        }
+    }
+    Ok(out.trim_end().to_owned() + "\n")
+}
+
+/// Front-matter lines for `metadata`. Values are double-quoted, which the front-matter
+/// reader strips verbatim; it has no escapes, so values cannot span lines.
+fn front_matter(metadata: &PlanMetadata) -> Result<String, PealError> {
+    let mut entries: Vec<(&str, String)> = Vec::new();
+    let optional = [
+        ("title", metadata.title.clone()),
+        ("description", metadata.description.clone()),
+        ("model", metadata.model.clone()),
+        ("phase_timeout_sec", metadata.phase_timeout_sec.map(|n| n.to_string())),
+        ("post_run_timeout_sec", metadata.post_run_timeout_sec.map(|n| n.to_string())),
+        (
+            "repo_subdir",
+            metadata.repo_subdir.as_ref().map(|p| p.to_string_lossy().into_owned()),
+        ),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            entries.push((key, value));
+        }
+    }
+    for (key, value) in &metadata.extra {
+        entries.push((key, value.clone()));
+    }
+
+    let mut out = String::new();
+    let scalar = |key: &str, value: &str| -> Result<String, PealError> {
+        if value.contains('\n') {
+            return Err(convert_err(format!(
+                "metadata '{key}' spans several lines, which plan front matter cannot hold"
+            )));
+        }
+        Ok(format!("\"{value}\""))
+    };
+    for (key, value) in entries {
+        out.push_str(&format!("{key}: {}\n", scalar(key, &value)?));
+    }
+    if !metadata.labels.is_empty() {
+        out.push_str("labels:\n");
+        for label in &metadata.labels {
+            out.push_str(&format!("  - {}\n", scalar("labels", label)?));
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = r#"---
+title: "Billing: v2"
+model: gpt-5.2
+phase_timeout_sec: 600
+labels: [payments, "q3"]
+owner: team-billing
+---
+
+# Preamble, dropped by conversion
+
+## Task 1 (tags: setup)
+
+Create the schema.
+
+  Indented line, then a blank line and a quote: "done".
+
+# Stage: Build
+
+## Task 2 (parallel)
+API.
+
+## Task 3 (parallel) (tags: ui, web) (after: 2) (context: docs/ui.md) (dir: web) (lock: db)
+UI.
+
+## Task 4
+"#;
+
+    fn assert_same_plan(a: &str, b: &str) {
+        let (a, b) = (plan::parse_plan(a).unwrap(), plan::parse_plan(b).unwrap());
+        assert_eq!(a.tasks, b.tasks);
+        assert_eq!(a.segments, b.segments);
+        assert_eq!(a.metadata, b.metadata);
+    }
+
+    #[test]
+    fn markdown_round_trips_through_yaml_and_json() {
+        for format in [PlanFormat::Yaml, PlanFormat::Json] {
+            let structured = convert(PLAN, PlanFormat::Markdown, format).unwrap();
+            let back = convert(&structured, format, PlanFormat::Markdown).unwrap();
+            assert_same_plan(PLAN, &back);
+            assert_eq!(convert(&back, PlanFormat::Markdown, format).unwrap(), structured);
+        }
+    }
+
+    #[test]
+    fn yaml_output_shape() {
+        let yaml = convert(PLAN, PlanFormat::Markdown, PlanFormat::Yaml).unwrap();
+        assert!(yaml.starts_with("metadata:\n  title: 'Billing: v2'\n"), "{yaml}");
+        assert!(yaml.contains("  labels:\n  - payments\n  - q3\n"), "{yaml}");
+        assert!(yaml.contains("  extra:\n    owner: team-billing\n"), "{yaml}");
+        assert!(yaml.contains("  content: |-\n    Create the schema.\n\n      Indented line"), "{yaml}");
+        assert!(yaml.contains(
+            "- index: 3\n  parallel: true\n  tags:\n  - ui\n  - web\n  stage: Build\n  after:\n  - 2\n  context:\n  - docs/ui.md\n  dirs:\n  - web\n  locks:\n  - db\n  content: UI.\n"
+        ), "{yaml}");
+        assert!(yaml.ends_with("- index: 4\n  parallel: false\n  stage: Build\n  content: ''\n"), "{yaml}");
+    }
+
+    #[test]
+    fn hand_written_yaml_is_accepted() {
+        let yaml = "# plan\ntasks:\n- index: 2\n  tags: &shared [a, 'b c']\n  content: |\n    Second.\n\n    More.\n- index: 1\n  parallel: true  # comment\n  tags: *shared\n  content: First\n";
+        let doc = read(yaml, PlanFormat::Yaml).unwrap();
+        assert_eq!(doc.tasks.len(), 2);
+        assert_eq!(doc.tasks[0].content, "Second.\n\nMore.\n");
+        assert!(doc.tasks[1].parallel);
+        assert_eq!(doc.tasks[1].tags, vec!["a", "b c"]);
+
+        let md = write(&doc, PlanFormat::Markdown).unwrap();
+        let parsed = plan::parse_plan(&md).unwrap();
+        assert_eq!(parsed.tasks.iter().map(|t| t.index).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn unstaged_tasks_are_written_before_stages() {
+        let json = r#"{"tasks": [
+            {"index": 1, "stage": "Later", "content": "A"},
+            {"index": 2, "content": "B"}
+        ]}"#;
+        let md = convert(json, PlanFormat::Json, PlanFormat::Markdown).unwrap();
+        assert_eq!(md, "## Task 2\n\nB\n\n# Stage: Later\n\n## Task 1\n\nA\n");
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        assert!(matches!(
+            convert("{\"tasks\": [{\"index\": \"x\"}]}", PlanFormat::Json, PlanFormat::Markdown),
+            Err(PealError::PlanConvertFailed { .. })
+        ));
+        assert!(convert("tasks:\n  - index: 1\n bad\n", PlanFormat::Yaml, PlanFormat::Markdown).is_err());
+        let multi_line = r#"{"metadata": {"description": "a\nb"}, "tasks": []}"#;
+        assert!(convert(multi_line, PlanFormat::Json, PlanFormat::Markdown).is_err());
+    }
+
+    #[test]
+    fn format_from_path_and_name() {
+        assert_eq!(PlanFormat::from_path(std::path::Path::new("p.YML")), PlanFormat::Yaml);
+        assert_eq!(PlanFormat::from_path(std::path::Path::new("p.json")), PlanFormat::Json);
+        assert_eq!(PlanFormat::from_path(std::path::Path::new("plan")), PlanFormat::Markdown);
+        assert_eq!(PlanFormat::parse("md"), Some(PlanFormat::Markdown));
+        assert_eq!(PlanFormat::parse("toml"), None);
+    }
+}
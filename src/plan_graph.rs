@@ -0,0 +1,232 @@
+//! `peal plan graph`: render a plan's execution schedule as a Mermaid flowchart or a
+//! Graphviz DOT digraph.
+//!
+//! Each task is a node labelled with its index, the first line of its body, and its tags.
+//! Parallel blocks are grouped in a subgraph and `# Stage:` sections in an enclosing one.
+//! Segments run one after another, so every task of a segment has an edge to every task of
+//! the next segment; tasks inside a parallel block have no edges between them.
+
+use std::fmt::Write;
+
+use crate::plan::{ParsedPlan, Segment, Task};
+
+/// Longest task summary shown in a node label, in characters.
+const LABEL_SUMMARY_CHARS: usize = 40;
+
+/// Output format for [`render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Mermaid,
+    Dot,
+}
+
+impl GraphFormat {
+    /// Parse a `--format` value (`mermaid` or `dot`).
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "mermaid" => Some(GraphFormat::Mermaid),
+            "dot" => Some(GraphFormat::Dot),
+            _ => None,
+        }
+    }
+}
+
+/// Render the execution schedule of `plan` in `format`.
+pub fn render(plan: &ParsedPlan, format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Mermaid => render_mermaid(plan),
+        GraphFormat::Dot => render_dot(plan),
+    }
+}
+
+/// Task indices of each segment, in run order.
+fn segment_tasks(segment: &Segment) -> Vec<u32> {
+    match segment {
+        Segment::Sequential(idx) => vec![*idx],
+        Segment::Parallel(indices) => indices.clone(),
+    }
+}
+
+/// Node label: `Task N: <first body line>` plus `[tags]` when the task has any.
+fn label(task: &Task) -> String {
+    let first_line = task.content.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+    let mut summary: String = first_line.chars().take(LABEL_SUMMARY_CHARS).collect();
+    if first_line.chars().count() > LABEL_SUMMARY_CHARS {
+        summary.push('…');
+    }
+    let mut label = format!("Task {}", task.index);
+    if !summary.is_empty() {
+        label.push_str(": ");
+        label.push_str(&summary);
+    }
+    if !task.tags.is_empty() {
+        label.push_str(&format!(" [{}]", task.tags.join(", ")));
+    }
+    label
+}
+
+/// Consecutive segments of one stage (`None` before the first stage heading), each with its
+/// position in the schedule.
+type StageGroup<'a> = (Option<&'a str>, Vec<(usize, &'a Segment)>);
+
+/// Segments grouped by stage, in run order.
+fn by_stage(plan: &ParsedPlan) -> Vec<StageGroup<'_>> {
+    let mut groups: Vec<StageGroup<'_>> = Vec::new();
+    for (i, segment) in plan.execution_schedule().iter().enumerate() {
+        let stage = segment_tasks(segment)
+            .first()
+            .and_then(|idx| plan.task_by_index(*idx))
+            .and_then(|t| t.stage.as_deref());
+        match groups.last_mut() {
+            Some((last, segments)) if *last == stage => segments.push((i, segment)),
+            _ => groups.push((stage, vec![(i, segment)])),
+        }
+    }
+    groups
+}
+
+/// Edges from every task of each segment to every task of the next one.
+fn edges(plan: &ParsedPlan) -> Vec<(u32, u32)> {
+    let schedule = plan.execution_schedule();
+    schedule
+        .windows(2)
+        .flat_map(|pair| {
+            let to = segment_tasks(&pair[1]);
+            segment_tasks(&pair[0])
+                .into_iter()
+                .flat_map(move |from| to.clone().into_iter().map(move |to| (from, to)))
+        })
+        .collect()
+}
+
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+fn render_mermaid(plan: &ParsedPlan) -> String {
+    let mut out = String::from("flowchart TD\n");
+    let mut stage_no = 0;
+    for (stage, segments) in by_stage(plan) {
+        let indent = if stage.is_some() { "        " } else { "    " };
+        if let Some(stage) = stage {
+            stage_no += 1;
+            let title = mermaid_escape(stage);
+            let _ = writeln!(out, "    subgraph stage{stage_no}[\"Stage: {title}\"]");
+        }
+        for (i, segment) in segments {
+            let parallel = matches!(segment, Segment::Parallel(_));
+            if parallel {
+                let _ = writeln!(out, "{indent}subgraph block{}[\"parallel\"]", i + 1);
+            }
+            for idx in segment_tasks(segment) {
+                let Some(task) = plan.task_by_index(idx) else { continue };
+                let pad = if parallel { "    " } else { "" };
+                let _ = writeln!(out, "{indent}{pad}t{idx}[\"{}\"]", mermaid_escape(&label(task)));
+            }
+            if parallel {
+                let _ = writeln!(out, "{indent}end");
+            }
+        }
+        if stage.is_some() {
+            out.push_str("    end\n");
+        }
+    }
+    for (from, to) in edges(plan) {
+        let _ = writeln!(out, "    t{from} --> t{to}");
+    }
+    out
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn render_dot(plan: &ParsedPlan) -> String {
+    let mut out = String::from("digraph plan {\n    rankdir=TB;\n    node [shape=box];\n");
+    let mut stage_no = 0;
+    for (stage, segments) in by_stage(plan) {
+        let indent = if stage.is_some() { "        " } else { "    " };
+        if let Some(stage) = stage {
+            stage_no += 1;
+            let _ = writeln!(out, "    subgraph cluster_stage{stage_no} {{");
+            let _ = writeln!(out, "        label=\"Stage: {}\";", dot_escape(stage));
+        }
+        for (i, segment) in segments {
+            let parallel = matches!(segment, Segment::Parallel(_));
+            if parallel {
+                let _ = writeln!(out, "{indent}subgraph cluster_block{} {{", i + 1);
+                let _ = writeln!(out, "{indent}    label=\"parallel\";\n{indent}    style=dashed;");
+            }
+            for idx in segment_tasks(segment) {
+                let Some(task) = plan.task_by_index(idx) else { continue };
+                let pad = if parallel { "    " } else { "" };
+                let _ = writeln!(out, "{indent}{pad}t{idx} [label=\"{}\"];", dot_escape(&label(task)));
+            }
+            if parallel {
+                let _ = writeln!(out, "{indent}}}");
+            }
+        }
+        if stage.is_some() {
+            out.push_str("    }\n");
+        }
+    }
+    for (from, to) in edges(plan) {
+        let _ = writeln!(out, "    t{from} -> t{to};");
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parse_plan;
+
+    const PLAN: &str = "\
+## Task 1
+Set up the \"schema\".
+
+# Stage: Port
+## Task 2 (parallel) (tags: db)
+Port readers.
+
+## Task 3 (parallel)
+Port writers.
+
+## Task 4
+A very long first line that goes well past the label limit for nodes.
+";
+
+    #[test]
+    fn mermaid_groups_stages_and_parallel_blocks_and_links_segments() {
+        let plan = parse_plan(PLAN).unwrap();
+        let out = render(&plan, GraphFormat::Mermaid);
+
+        assert_eq!(
+            out,
+            "flowchart TD\n    \
+             t1[\"Task 1: Set up the #quot;schema#quot;.\"]\n    \
+             subgraph stage1[\"Stage: Port\"]\n        \
+             subgraph block2[\"parallel\"]\n            \
+             t2[\"Task 2: Port readers. [db]\"]\n            \
+             t3[\"Task 3: Port writers.\"]\n        \
+             end\n        \
+             t4[\"Task 4: A very long first line that goes well pa…\"]\n    \
+             end\n    \
+             t1 --> t2\n    t1 --> t3\n    t2 --> t4\n    t3 --> t4\n"
+        );
+    }
+
+    #[test]
+    fn dot_uses_clusters_and_escapes_labels() {
+        let plan = parse_plan(PLAN).unwrap();
+        let out = render(&plan, GraphFormat::Dot);
+
+        assert!(out.starts_with("digraph plan {\n"), "{out}");
+        assert!(out.contains("    t1 [label=\"Task 1: Set up the \\\"schema\\\".\"];\n"), "{out}");
+        assert!(out.contains("    subgraph cluster_stage1 {\n        label=\"Stage: Port\";\n"));
+        assert!(out.contains("        subgraph cluster_block2 {\n"), "{out}");
+        assert!(out.contains("    t2 -> t4;\n    t3 -> t4;\n}\n"), "{out}");
+        assert_eq!(GraphFormat::parse("svg"), None);
+    }
+}
@@ -16,7 +16,12 @@ pub fn plan_instructions_prompt() -> &'static str {
 
 - **Task headings:** Use exactly `## Task 1`, `## Task 2`, `## Task 3`, and so on (digit sequence). No other heading style for tasks.
 - **Optional parallel marker:** A task heading may include the suffix ` (parallel)`, e.g. `## Task 2 (parallel)`. Consecutive tasks marked `(parallel)` may be run in parallel by the orchestrator; other tasks run in order.
-- **Task body:** Everything from the line after a task heading until the next line that matches `## Task N` (or end of file) is that task's content. Use UTF-8.
+- **Optional tags:** A task heading may also include ` (tags: a, b)`, e.g. `## Task 3 (tags: backend, db)`, so runs can be limited to one area with `--tags`.
+- **Optional ordering hints:** A parallel task heading may include ` (after: N)`, e.g. `## Task 4 (parallel) (after: 2)`, to ask the orchestrator not to start it until task N of the same parallel block has finished. This is a soft hint for loosely related tasks, not a hard dependency.
+- **Optional context files:** A task heading may include ` (context: path, path)`, e.g. `## Task 5 (context: docs/design.md, src/api/mod.rs)`, naming repo files whose contents the orchestrator adds to that task's prompts as background. Use it for design notes or key modules the task body refers to but does not quote.
+- **Optional locks:** A parallel task heading may include ` (lock: name)`, e.g. `## Task 6 (parallel) (lock: database)`. Tasks naming the same lock never run at the same time, so give one to parallel tasks that contend on a shared resource (a database, a generated file).
+- **Optional stages:** For large plans, group tasks under `# Stage: <name>` headings (e.g. `# Stage: Foundation`). Stages run in the order they appear, each one finishing before the next starts; text between a stage heading and its first task is ignored.
+- **Task body:** Everything from the line after a task heading until the next line that matches `## Task N` or `# Stage:` (or end of file) is that task's content. Use UTF-8.
 - **Preamble:** You may include a title, goal, or instructions before `## Task 1`; the parser ignores it. Keep task bodies self-contained and testable.
 
 ## Example
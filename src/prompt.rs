@@ -31,15 +31,79 @@ const TASK_DELIMITER: &str = "---TASK---";
 /// Delimiter used to fence plan text inside the Phase 2 prompt.
 const PLAN_DELIMITER: &str = "---PLAN---";
 
+/// Delimiter used to fence task context files inside the Phase 1 and 2 prompts.
+const CONTEXT_DELIMITER: &str = "---CONTEXT---";
+
 /// Delimiter used to fence stet review output inside the Phase 3 prompt.
 const STET_DELIMITER: &str = "---STET---";
 
 /// Delimiter used to fence extracted suggestions inside the Phase 3 prompt.
 const SUGGESTIONS_DELIMITER: &str = "---SUGGESTIONS---";
 
+/// Delimiter used to fence a failed verification command's output inside the verify fix prompt.
+const VERIFY_DELIMITER: &str = "---VERIFY---";
+
+/// Delimiter used to fence conflicted hunks inside the merge conflict (Phase 2.5) prompt.
+const CONFLICT_DELIMITER: &str = "---CONFLICT---";
+
+/// Delimiter used to fence Phase 2 output inside the task summary prompt.
+const OUTPUT_DELIMITER: &str = "---OUTPUT---";
+
+/// Delimiter used to fence the git diff inside the task summary prompt.
+const DIFF_DELIMITER: &str = "---DIFF---";
+
 /// Delimiter used to fence the user document in the normalization prompt (SP-7.2).
 const DOC_DELIMITER: &str = "---DOC---";
 
+/// Delimiter used to fence rejected output in a normalization retry prompt.
+const PREVIOUS_OUTPUT_DELIMITER: &str = "---PREVIOUS OUTPUT---";
+
+/// Characters per token assumed by [`estimate_tokens`]; English prose and source code both
+/// come out near 4 with current model tokenizers.
+const CHARS_PER_TOKEN: u64 = 4;
+
+/// Cheap token estimate for `text`: characters / 4, rounded up. There is no tokenizer, so
+/// this catches clearly oversized prompts rather than matching a model's exact count.
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Estimated size of a built prompt, split into its payload sections and the instruction
+/// envelope around them (everything not in a section, reported as "instructions").
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptEstimate {
+    pub total: u64,
+    /// `(section, estimated tokens)`, largest first.
+    pub sections: Vec<(&'static str, u64)>,
+}
+
+impl PromptEstimate {
+    /// Estimate `prompt`, attributing the given payload `sections` (name, text) within it.
+    pub fn new(prompt: &str, sections: &[(&'static str, &str)]) -> Self {
+        let total = estimate_tokens(prompt);
+        let mut parts: Vec<(&'static str, u64)> =
+            sections.iter().map(|(name, text)| (*name, estimate_tokens(text))).collect();
+        let payload: u64 = parts.iter().map(|(_, n)| n).sum();
+        parts.push(("instructions", total.saturating_sub(payload)));
+        parts.sort_by(|a, b| b.1.cmp(&a.1));
+        Self { total, sections: parts }
+    }
+
+    /// The section contributing the most tokens.
+    pub fn largest(&self) -> (&'static str, u64) {
+        self.sections.first().copied().unwrap_or(("instructions", 0))
+    }
+
+    /// One-line breakdown, e.g. `stet output ~9000, suggestions ~800, instructions ~20`.
+    pub fn breakdown(&self) -> String {
+        self.sections
+            .iter()
+            .map(|(name, n)| format!("{name} ~{n}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
 /// Placeholder in custom normalization prompt files; replaced by the plan document content.
 pub const NORMALIZE_PROMPT_PLACEHOLDER: &str = "{{DOC}}";
 
@@ -71,6 +135,21 @@ Output rules:\n\
     )
 }
 
+/// Append feedback on a rejected normalization attempt to the normalization prompt, for the
+/// retry (`normalize_retry_count`): why the previous output was invalid and how it began
+/// (fenced with `---PREVIOUS OUTPUT---`), then a reminder of the format.
+pub fn normalize_retry_prompt(prompt: String, reason: &str, previous_output: &str) -> String {
+    format!(
+        "{prompt}\n\n\
+         Your previous output was invalid because {reason}. It began:\n\
+         {PREVIOUS_OUTPUT_DELIMITER}\n\
+         {previous_output}\n\
+         {PREVIOUS_OUTPUT_DELIMITER}\n\n\
+         Try again. Output only the plan markdown, starting with the line \"## Task 1\", with one \
+         \"## Task N\" heading per task and no preamble, explanation, or code fences."
+    )
+}
+
 /// Build the triage prompt: stet output plus the single question "Anything to address from this review?"
 /// Used by Phase 3 auto-dismiss to let the LLM decide which findings to dismiss.
 pub fn triage_prompt(stet_output: &str) -> String {
@@ -112,6 +191,114 @@ pub fn phase2(plan_text: &str) -> String {
     )
 }
 
+/// Build the Phase 2 prompt for a second attempt after the first one changed no files
+/// (`phase2_change_check = "retry"`): the plan as in [`phase2`], preceded by what went wrong.
+pub fn phase2_noop_retry(plan_text: &str, detail: &str) -> String {
+    format!(
+        "Your previous attempt at this plan did not change the repository ({detail}). \
+         Implement the plan now by editing the files; do not stop without making the changes. \
+         If part of it already exists, complete whatever is still missing.\n\n{}",
+        phase2(plan_text)
+    )
+}
+
+/// Ask for the `done_marker` at the end of a Phase 2 prompt. With `missing_before`, the
+/// previous answer lacked it, so the agent is told to check what is unfinished first.
+pub fn with_done_marker(prompt: String, marker: &str, missing_before: bool) -> String {
+    let retry = if missing_before {
+        "Your previous answer did not end with the completion line, so the work may be \
+         unfinished. Check what is still missing and finish it.\n\n"
+    } else {
+        ""
+    };
+    format!(
+        "{retry}{prompt}\n\n\
+         When the whole plan is implemented, end your answer with this line, exactly:\n\
+         {marker}\n\
+         Do not print it if you stopped early, refused, or could not finish."
+    )
+}
+
+/// Append a task's context files (`(context: ...)` heading suffix) to a Phase 1 or Phase 2
+/// prompt, as `(path, contents)` pairs in heading order.
+///
+/// Each file is fenced with `---CONTEXT---` under its path so the agent reads it as
+/// background, not instructions. `prompt` is returned unchanged when `files` is empty.
+pub fn with_context(prompt: String, files: &[(String, String)]) -> String {
+    if files.is_empty() {
+        return prompt;
+    }
+    let mut out = prompt;
+    out.push_str("\n\nBackground files for this task (for reference only):");
+    for (path, contents) in files {
+        out.push_str(&format!(
+            "\n\nFile: {path}\n\
+             {CONTEXT_DELIMITER}\n\
+             {contents}\n\
+             {CONTEXT_DELIMITER}"
+        ));
+    }
+    out
+}
+
+/// Build the prompt asking the agent to fix a failed `peal-verify` command of a task.
+///
+/// The command's output is fenced with `---VERIFY---`; the command itself is quoted inline
+/// so the agent can re-run it.
+pub fn verify_fix(command: &str, output: &str) -> String {
+    format!(
+        "The verification command `{command}` failed after this task's changes. \
+         Fix the code so that it passes, then run it again.\n\n\
+         {VERIFY_DELIMITER}\n\
+         {output}\n\
+         {VERIFY_DELIMITER}"
+    )
+}
+
+/// Build the Phase 2.5 prompt: resolve the conflict markers merging a task's sparse worktree
+/// back into the repo left in `files`. `hunks` (the marked regions with some context) is fenced
+/// with `---CONFLICT---`.
+pub fn merge_conflict(files: &[String], hunks: &str) -> String {
+    format!(
+        "This task's changes were made in a separate worktree while the repository changed, and \
+         merging them back left conflicts in: {}. In each conflict, the part between `<<<<<<< repo` \
+         and `=======` is what the repository has now, and the part between `=======` and \
+         `>>>>>>> task` is this task's change. Resolve every conflict so both intents are kept, \
+         remove all conflict markers, and make no unrelated edits.\n\n\
+         {CONFLICT_DELIMITER}\n\
+         {hunks}\n\
+         {CONFLICT_DELIMITER}",
+        files.join(", ")
+    )
+}
+
+/// Token the agent is asked to echo back in the health check prompt.
+pub const HEALTH_CHECK_TOKEN: &str = "PEAL_OK";
+
+/// Build the agent health check prompt: a trivial request whose answer must contain
+/// [`HEALTH_CHECK_TOKEN`].
+pub fn health_check() -> String {
+    format!("Health check. Do not read or change any files. Reply with exactly: {HEALTH_CHECK_TOKEN}")
+}
+
+/// Build the post-task summary prompt from Phase 2 output and the task's diff.
+///
+/// Both inputs are fenced (`---OUTPUT---`, `---DIFF---`) so the agent treats them
+/// as data. The answer is stored verbatim as the task summary.
+pub fn task_summary(phase2_stdout: &str, diff: &str) -> String {
+    format!(
+        "Summarize in 2-3 sentences what was done for this task, for a human skimming \
+         the run results. Mention the main files or components changed. \
+         Output only the summary.\n\n\
+         {OUTPUT_DELIMITER}\n\
+         {phase2_stdout}\n\
+         {OUTPUT_DELIMITER}\n\n\
+         {DIFF_DELIMITER}\n\
+         {diff}\n\
+         {DIFF_DELIMITER}"
+    )
+}
+
 /// Build the Phase 3 (address stet findings) prompt.
 ///
 /// Delegates to [`phase3_with_suggestions`] with no suggestions block.
@@ -224,6 +411,33 @@ mod tests {
         assert_eq!(delimiter_count, 2, "exactly two delimiters expected");
     }
 
+    #[test]
+    fn with_context_fences_each_file_after_the_prompt() {
+        assert_eq!(with_context(phase1("task"), &[]), phase1("task"));
+
+        let files = vec![
+            ("docs/design.md".to_owned(), "Design.".to_owned()),
+            ("src/lib.rs".to_owned(), "fn main() {}".to_owned()),
+        ];
+        let prompt = with_context(phase2("plan"), &files);
+        assert!(prompt.starts_with(&phase2("plan")));
+        assert!(prompt.contains("File: docs/design.md\n---CONTEXT---\nDesign.\n---CONTEXT---"));
+        assert!(prompt.contains("File: src/lib.rs\n---CONTEXT---\nfn main() {}\n---CONTEXT---"));
+        assert_eq!(prompt.matches("---CONTEXT---").count(), 4);
+    }
+
+    // -- Task summary tests --
+
+    #[test]
+    fn task_summary_fences_output_and_diff() {
+        let prompt = task_summary("Implemented login.", "diff --git a/src/login.rs");
+        assert_eq!(prompt.matches("---OUTPUT---").count(), 2);
+        assert_eq!(prompt.matches("---DIFF---").count(), 2);
+        assert!(prompt.contains("---OUTPUT---\nImplemented login.\n---OUTPUT---"));
+        assert!(prompt.contains("---DIFF---\ndiff --git a/src/login.rs\n---DIFF---"));
+        assert!(prompt.ends_with("---DIFF---"));
+    }
+
     // -- Phase 3 tests --
 
     #[test]
@@ -282,6 +496,20 @@ mod tests {
         assert_eq!(sugg_count, 2, "exactly two SUGGESTIONS delimiters expected");
     }
 
+    #[test]
+    fn verify_fix_fences_output_and_names_command() {
+        let prompt = verify_fix("cargo test -p foo", "test foo::bar ... FAILED");
+        assert!(prompt.contains("`cargo test -p foo`"));
+        assert!(prompt.contains("---VERIFY---\ntest foo::bar ... FAILED\n---VERIFY---"));
+    }
+
+    #[test]
+    fn merge_conflict_lists_files_and_fences_hunks() {
+        let prompt = merge_conflict(&["a.rs".to_owned(), "b.rs".to_owned()], "a.rs:3\n<<<<<<< repo");
+        assert!(prompt.contains("conflicts in: a.rs, b.rs."));
+        assert!(prompt.contains("---CONFLICT---\na.rs:3\n<<<<<<< repo\n---CONFLICT---"));
+    }
+
     #[test]
     fn phase3_with_suggestions_none_matches_phase3() {
         let a = phase3("some stet output");
@@ -349,6 +577,16 @@ mod tests {
         assert!(prompt.contains("---DOC---\n\n---DOC---"));
     }
 
+    #[test]
+    fn normalize_retry_prompt_explains_rejection_after_prompt() {
+        let base = normalize_plan_prompt("doc");
+        let prompt = normalize_retry_prompt(base.clone(), "it has no tasks", "Here is the plan:");
+        assert!(prompt.starts_with(&base));
+        assert!(prompt.contains("Your previous output was invalid because it has no tasks."));
+        assert!(prompt.contains("---PREVIOUS OUTPUT---\nHere is the plan:\n---PREVIOUS OUTPUT---"));
+        assert!(prompt.ends_with("no preamble, explanation, or code fences."));
+    }
+
     #[test]
     fn build_normalize_prompt_from_template_replaces_placeholder() {
         let template = "Convert the following:\n{{DOC}}\nEnd.";
@@ -391,4 +629,24 @@ mod tests {
             "phase 3 must not use PLAN delimiter"
         );
     }
+
+
+    #[test]
+    fn prompt_estimate_attributes_sections_and_envelope() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(estimate_tokens("ééééé"), 2, "counts characters, not bytes");
+
+        let stet = "s".repeat(4000);
+        let prompt = phase3_with_suggestions(&stet, Some("fix it"));
+        let estimate =
+            PromptEstimate::new(&prompt, &[("stet output", &stet), ("suggestions", "fix it")]);
+
+        assert_eq!(estimate.total, estimate_tokens(&prompt));
+        assert_eq!(estimate.largest(), ("stet output", 1000));
+        assert_eq!(estimate.sections.iter().map(|(_, n)| n).sum::<u64>(), estimate.total);
+        let breakdown = estimate.breakdown();
+        assert!(breakdown.starts_with("stet output ~1000, instructions ~"), "{breakdown}");
+        assert!(breakdown.ends_with("suggestions ~2"), "{breakdown}");
+    }
 }
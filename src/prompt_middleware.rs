@@ -0,0 +1,224 @@
+//! Prompt middleware: transforms applied to every agent prompt before dispatch
+//! and to agent output after capture.
+//!
+//! The chain comes from `[[prompt_middleware]]` entries in the config, in file
+//! order, followed by anything added with [`register`]. Prompts run through the
+//! chain front to back; output runs back to front, so the step closest to the
+//! agent sees its output first. Built-ins:
+//!
+//! - `redact`: replace regex matches with `[REDACTED]` in prompts and output.
+//! - `max_length`: cut the middle out of prompts over `max_chars` characters.
+//! - `prefix`: put fixed text (or a file, e.g. org coding standards) before every prompt.
+
+use std::sync::{Arc, Mutex};
+
+use regex::Regex;
+
+use crate::config::{PealConfig, PromptMiddlewareSpec};
+use crate::error::PealError;
+
+/// Replacement text for redacted matches.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// One prompt/output transform. `call` names the agent call being made
+/// (`"phase 1"`, `"phase 2"`, `"verify fix"`, `"merge conflict"`, `"phase 3"`,
+/// `"task summary"`, `"triage"`, `"normalization"`); both methods default to passing text
+/// through.
+pub trait PromptMiddleware: Send + Sync {
+    fn prompt(&self, _call: &str, prompt: String) -> String {
+        prompt
+    }
+
+    fn output(&self, _call: &str, output: String) -> String {
+        output
+    }
+}
+
+/// Replaces every match of any pattern with [`REDACTED`].
+pub struct Redact {
+    patterns: Vec<Regex>,
+}
+
+impl Redact {
+    pub fn new(patterns: Vec<Regex>) -> Self {
+        Self { patterns }
+    }
+
+    fn apply(&self, text: String) -> String {
+        self.patterns.iter().fold(text, |text, re| re.replace_all(&text, REDACTED).into_owned())
+    }
+}
+
+impl PromptMiddleware for Redact {
+    fn prompt(&self, _call: &str, prompt: String) -> String {
+        self.apply(prompt)
+    }
+
+    fn output(&self, _call: &str, output: String) -> String {
+        self.apply(output)
+    }
+}
+
+/// Keeps the first and last `max_chars / 2` characters of a longer prompt and
+/// replaces the middle with a marker saying how much was cut.
+pub struct MaxLength {
+    max_chars: usize,
+}
+
+impl MaxLength {
+    pub fn new(max_chars: usize) -> Self {
+        Self { max_chars }
+    }
+}
+
+impl PromptMiddleware for MaxLength {
+    fn prompt(&self, call: &str, prompt: String) -> String {
+        let total = prompt.chars().count();
+        if total <= self.max_chars {
+            return prompt;
+        }
+        let head = self.max_chars / 2;
+        let tail = self.max_chars - head;
+        let trimmed = total - self.max_chars;
+        tracing::warn!(call, total, max_chars = self.max_chars, "prompt_middleware: trimming prompt");
+        let head_end = prompt.char_indices().nth(head).map_or(prompt.len(), |(i, _)| i);
+        let tail_start = prompt.char_indices().nth(total - tail).map_or(prompt.len(), |(i, _)| i);
+        format!(
+            "{}\n\n[... {trimmed} characters trimmed by prompt_middleware ...]\n\n{}",
+            &prompt[..head_end],
+            &prompt[tail_start..]
+        )
+    }
+}
+
+/// Puts `text` and a blank line before every prompt.
+pub struct Prefix {
+    text: String,
+}
+
+impl Prefix {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into() }
+    }
+}
+
+impl PromptMiddleware for Prefix {
+    fn prompt(&self, _call: &str, prompt: String) -> String {
+        format!("{}\n\n{prompt}", self.text.trim_end())
+    }
+}
+
+static REGISTERED: Mutex<Vec<Arc<dyn PromptMiddleware>>> = Mutex::new(Vec::new());
+
+/// Adds `middleware` to every chain built after this call, after the configured
+/// entries. For library users embedding peal with their own transforms.
+pub fn register(middleware: Arc<dyn PromptMiddleware>) {
+    REGISTERED.lock().unwrap_or_else(|e| e.into_inner()).push(middleware);
+}
+
+/// An ordered list of middleware.
+#[derive(Clone, Default)]
+pub struct Chain {
+    steps: Vec<Arc<dyn PromptMiddleware>>,
+}
+
+impl Chain {
+    pub fn new(steps: Vec<Arc<dyn PromptMiddleware>>) -> Self {
+        Self { steps }
+    }
+
+    /// Builds the chain for `config`: its `prompt_middleware` entries, then any
+    /// [`register`]ed middleware. Prefix files are read here, relative to `repo_path`.
+    pub fn from_config(config: &PealConfig) -> Result<Self, PealError> {
+        let mut steps: Vec<Arc<dyn PromptMiddleware>> = Vec::new();
+        for (index, spec) in config.prompt_middleware.iter().enumerate() {
+            let invalid = |detail: String| PealError::InvalidPromptMiddleware { index, detail };
+            let step: Arc<dyn PromptMiddleware> = match spec {
+                PromptMiddlewareSpec::Redact { patterns } => {
+                    if patterns.is_empty() {
+                        return Err(invalid("redact needs at least one pattern".to_owned()));
+                    }
+                    let patterns = patterns
+                        .iter()
+                        .map(|p| Regex::new(p).map_err(|e| invalid(format!("pattern '{p}': {e}"))))
+                        .collect::<Result<_, _>>()?;
+                    Arc::new(Redact::new(patterns))
+                }
+                PromptMiddlewareSpec::MaxLength { max_chars } => {
+                    if *max_chars == 0 {
+                        return Err(invalid("max_length needs max_chars of at least 1".to_owned()));
+                    }
+                    Arc::new(MaxLength::new(*max_chars))
+                }
+                PromptMiddlewareSpec::Prefix { text, file } => match (text, file) {
+                    (Some(text), None) => Arc::new(Prefix::new(text.clone())),
+                    (None, Some(file)) => {
+                        let path = config.repo_path.join(file);
+                        let text = std::fs::read_to_string(&path)
+                            .map_err(|e| invalid(format!("cannot read {}: {e}", path.display())))?;
+                        Arc::new(Prefix::new(text))
+                    }
+                    _ => return Err(invalid("prefix needs exactly one of text or file".to_owned())),
+                },
+            };
+            steps.push(step);
+        }
+        steps.extend(REGISTERED.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned());
+        Ok(Self { steps })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Runs `prompt` through every step, first to last.
+    pub fn prompt(&self, call: &str, prompt: String) -> String {
+        self.steps.iter().fold(prompt, |p, step| step.prompt(call, p))
+    }
+
+    /// Runs `output` through every step, last to first.
+    pub fn output(&self, call: &str, output: String) -> String {
+        self.steps.iter().rev().fold(output, |o, step| step.output(call, o))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_replaces_matches_in_prompt_and_output() {
+        let redact = Redact::new(vec![Regex::new(r"sk-[a-z0-9]+").unwrap(), Regex::new("hunter2").unwrap()]);
+        assert_eq!(redact.prompt("phase 1", "key sk-abc123 pw hunter2".to_owned()), "key [REDACTED] pw [REDACTED]");
+        assert_eq!(redact.output("phase 1", "echo sk-zz9".to_owned()), "echo [REDACTED]");
+        assert_eq!(redact.prompt("phase 1", "nothing here".to_owned()), "nothing here");
+    }
+
+    #[test]
+    fn max_length_keeps_head_and_tail() {
+        let trim = MaxLength::new(6);
+        assert_eq!(trim.prompt("phase 2", "abcdef".to_owned()), "abcdef");
+        let out = trim.prompt("phase 2", "abcdefghijé".to_owned());
+        assert!(out.starts_with("abc\n\n[... 5 characters trimmed"), "{out}");
+        assert!(out.ends_with("\n\nijé"), "{out}");
+        // Output passes through untouched.
+        assert_eq!(trim.output("phase 2", "abcdefghij".to_owned()), "abcdefghij");
+    }
+
+    #[test]
+    fn chain_applies_prompts_in_order_and_output_in_reverse() {
+        struct Tag(&'static str);
+        impl PromptMiddleware for Tag {
+            fn prompt(&self, _call: &str, prompt: String) -> String {
+                format!("{prompt}{}", self.0)
+            }
+            fn output(&self, _call: &str, output: String) -> String {
+                format!("{output}{}", self.0)
+            }
+        }
+        let chain = Chain::new(vec![Arc::new(Tag("a")), Arc::new(Prefix::new("STANDARDS\n")), Arc::new(Tag("b"))]);
+        assert_eq!(chain.prompt("phase 1", "p".to_owned()), "STANDARDS\n\npab");
+        assert_eq!(chain.output("phase 1", "o".to_owned()), "oba");
+        assert!(Chain::default().is_empty());
+    }
+}
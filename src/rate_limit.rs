@@ -0,0 +1,243 @@
+//! Global rate limiting of Cursor CLI (agent) invocations.
+//!
+//! A token bucket per configured budget (`agent_max_requests_per_min`) is shared
+//! process-wide, so every worker thread in a parallel block draws from the same
+//! budget. Callers invoke [`throttle`] immediately before spawning the agent; it
+//! blocks until a token is available.
+//!
+//! [`count_agent_call`] goes right before it and enforces the per-run hard cap
+//! (`max_agent_calls`) with one process-wide counter.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, error};
+
+use crate::error::PealError;
+
+/// Token bucket: holds up to `capacity` tokens and refills continuously at
+/// `capacity` tokens per minute. Each agent call consumes one token.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a full bucket allowing `max_per_min` calls per minute (minimum 1).
+    pub fn new(max_per_min: u32) -> Self {
+        let capacity = f64::from(max_per_min.max(1));
+        Self {
+            capacity,
+            refill_per_sec: capacity / 60.0,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes one token as of `now`. Returns `Err(wait)` with the time until the
+    /// next token when the bucket is empty; no token is consumed in that case.
+    pub fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        bucket.last_refill = bucket.last_refill.max(now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            Err(Duration::from_secs_f64(missing / self.refill_per_sec))
+        }
+    }
+
+    /// Blocks the calling thread until a token is available, then takes it.
+    pub fn acquire(&self) {
+        loop {
+            match self.try_acquire_at(Instant::now()) {
+                Ok(()) => return,
+                Err(wait) => {
+                    debug!(wait_ms = wait.as_millis() as u64, "agent rate limit reached, waiting");
+                    std::thread::sleep(wait);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the process-wide limiter for the given budget, creating it on first use.
+/// All callers configured with the same budget share one bucket.
+pub fn shared(max_per_min: u32) -> Arc<RateLimiter> {
+    static LIMITERS: OnceLock<Mutex<HashMap<u32, Arc<RateLimiter>>>> = OnceLock::new();
+    let mut limiters = LIMITERS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    Arc::clone(
+        limiters
+            .entry(max_per_min)
+            .or_insert_with(|| Arc::new(RateLimiter::new(max_per_min))),
+    )
+}
+
+/// Waits for the global agent budget before an agent invocation.
+/// No-op when `max_per_min` is `None` or `Some(0)` (unlimited).
+pub fn throttle(max_per_min: Option<u32>) {
+    if let Some(n) = max_per_min.filter(|&n| n > 0) {
+        shared(n).acquire();
+    }
+}
+
+/// Counts agent invocations against an optional cap.
+#[derive(Debug, Default)]
+pub struct CallCounter {
+    calls: AtomicU64,
+}
+
+impl CallCounter {
+    pub const fn new() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts one call unless `max` calls were already made; returns the new count, or
+    /// `Err(count)` without counting when the cap is reached. `None` means no cap.
+    pub fn try_take(&self, max: Option<u64>) -> Result<u64, u64> {
+        self.calls
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                if max.is_some_and(|m| n >= m) { None } else { Some(n + 1) }
+            })
+            .map(|n| n + 1)
+    }
+
+    /// Calls counted so far.
+    pub fn count(&self) -> u64 {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+static AGENT_CALLS: CallCounter = CallCounter::new();
+
+/// Counts one agent invocation against `max_agent_calls`. Returns `AgentCallCapReached`
+/// (and the caller must not invoke the agent) once `max_calls` invocations were made.
+pub fn count_agent_call(max_calls: Option<u64>) -> Result<(), PealError> {
+    match AGENT_CALLS.try_take(max_calls) {
+        Ok(_) => Ok(()),
+        Err(made) => {
+            error!(calls = made, "max_agent_calls reached; not invoking the agent");
+            Err(PealError::AgentCallCapReached {
+                max: max_calls.unwrap_or(made),
+            })
+        }
+    }
+}
+
+/// Agent invocations counted so far in this process.
+pub fn agent_calls() -> u64 {
+    AGENT_CALLS.count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_bucket_allows_burst_up_to_capacity() {
+        let limiter = RateLimiter::new(3);
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(now).is_ok());
+        assert!(limiter.try_acquire_at(now).is_ok());
+        assert!(limiter.try_acquire_at(now).is_ok());
+        assert!(limiter.try_acquire_at(now).is_err());
+    }
+
+    #[test]
+    fn empty_bucket_reports_wait_until_next_token() {
+        let limiter = RateLimiter::new(60);
+        let now = Instant::now();
+        for _ in 0..60 {
+            assert!(limiter.try_acquire_at(now).is_ok());
+        }
+        let wait = limiter.try_acquire_at(now).unwrap_err();
+        assert!(
+            wait > Duration::from_millis(900) && wait <= Duration::from_secs(1),
+            "60/min refills one token per second, got {wait:?}"
+        );
+    }
+
+    #[test]
+    fn bucket_refills_over_time_without_exceeding_capacity() {
+        let limiter = RateLimiter::new(2);
+        let start = Instant::now();
+        assert!(limiter.try_acquire_at(start).is_ok());
+        assert!(limiter.try_acquire_at(start).is_ok());
+        assert!(limiter.try_acquire_at(start).is_err());
+
+        // 2/min = one token per 30s.
+        let later = start + Duration::from_secs(30);
+        assert!(limiter.try_acquire_at(later).is_ok());
+        assert!(limiter.try_acquire_at(later).is_err());
+
+        // Long idle refills only up to capacity.
+        let much_later = later + Duration::from_secs(600);
+        assert!(limiter.try_acquire_at(much_later).is_ok());
+        assert!(limiter.try_acquire_at(much_later).is_ok());
+        assert!(limiter.try_acquire_at(much_later).is_err());
+    }
+
+    #[test]
+    fn shared_returns_same_limiter_for_same_budget() {
+        let a = shared(12_345);
+        let b = shared(12_345);
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &shared(12_346)));
+    }
+
+    #[test]
+    fn shared_limiter_is_global_across_threads() {
+        let budget = 4_321;
+        let now = Instant::now();
+        let granted: usize = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    s.spawn(move || {
+                        (0..1_000)
+                            .filter(|_| shared(budget).try_acquire_at(now).is_ok())
+                            .count()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(granted, budget as usize);
+    }
+
+    #[test]
+    fn throttle_unlimited_is_noop() {
+        throttle(None);
+        throttle(Some(0));
+    }
+
+    #[test]
+    fn call_counter_stops_at_cap_without_counting() {
+        let counter = CallCounter::new();
+        assert_eq!(counter.try_take(Some(2)), Ok(1));
+        assert_eq!(counter.try_take(Some(2)), Ok(2));
+        assert_eq!(counter.try_take(Some(2)), Err(2));
+        assert_eq!(counter.count(), 2);
+        assert_eq!(counter.try_take(None), Ok(3), "no cap always counts");
+    }
+}
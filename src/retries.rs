@@ -0,0 +1,111 @@
+//! Per-task retry accounting.
+//!
+//! Retry loops (phase retries, the Phase 1 plan-text retry, `on_stet_fail = "retry_once"`)
+//! call [`record`] each time they go around again. The runner wraps each unit of task work
+//! in [`track`], which collects what was recorded on the current thread, so counts land on
+//! the task that caused them even when a parallel block runs several tasks at once on
+//! their own threads.
+
+use std::cell::Cell;
+
+use serde::{Deserialize, Serialize};
+
+/// Retries spent on one task, by source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryCounts {
+    /// Phase 1 re-invocations (phase retries and the plan-text validation retry).
+    #[serde(default)]
+    pub phase1: u32,
+    /// Phase 2 re-invocations.
+    #[serde(default)]
+    pub phase2: u32,
+    /// Phase 3 re-invocations, including triage.
+    #[serde(default)]
+    pub phase3: u32,
+    /// Stet run / address loop retries under `on_stet_fail = "retry_once"`.
+    #[serde(default)]
+    pub stet: u32,
+}
+
+impl RetryCounts {
+    /// Sum over all sources.
+    pub fn total(&self) -> u32 {
+        self.phase1 + self.phase2 + self.phase3 + self.stet
+    }
+
+    /// Adds `other` into `self`.
+    pub fn add(&mut self, other: RetryCounts) {
+        self.phase1 += other.phase1;
+        self.phase2 += other.phase2;
+        self.phase3 += other.phase3;
+        self.stet += other.stet;
+    }
+}
+
+/// What was retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryKind {
+    /// An agent phase (1, 2, or 3; triage counts as 3).
+    Phase(u32),
+    /// A stet invocation or address loop.
+    Stet,
+}
+
+thread_local! {
+    static CURRENT: Cell<RetryCounts> = const { Cell::new(RetryCounts { phase1: 0, phase2: 0, phase3: 0, stet: 0 }) };
+}
+
+/// Count one retry of `kind` against the work currently tracked on this thread.
+/// Outside [`track`] the count is dropped.
+pub fn record(kind: RetryKind) {
+    CURRENT.with(|c| {
+        let mut counts = c.get();
+        match kind {
+            RetryKind::Phase(1) => counts.phase1 += 1,
+            RetryKind::Phase(2) => counts.phase2 += 1,
+            RetryKind::Phase(_) => counts.phase3 += 1,
+            RetryKind::Stet => counts.stet += 1,
+        }
+        c.set(counts);
+    });
+}
+
+/// Run `f` and return its result with the retries recorded on this thread while it ran.
+/// Nested calls are counted by the innermost `track` only.
+pub fn track<R>(f: impl FnOnce() -> R) -> (R, RetryCounts) {
+    let outer = CURRENT.with(|c| c.replace(RetryCounts::default()));
+    let result = f();
+    let counts = CURRENT.with(|c| c.replace(outer));
+    (result, counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_collects_records_by_kind_and_restores_outer_counts() {
+        let (value, outer) = track(|| {
+            record(RetryKind::Stet);
+            let ((), inner) = track(|| {
+                record(RetryKind::Phase(1));
+                record(RetryKind::Phase(3));
+            });
+            assert_eq!(inner, RetryCounts { phase1: 1, phase2: 0, phase3: 1, stet: 0 });
+            record(RetryKind::Phase(2));
+            7
+        });
+
+        assert_eq!(value, 7);
+        assert_eq!(outer, RetryCounts { phase1: 0, phase2: 1, phase3: 0, stet: 1 });
+        assert_eq!(outer.total(), 2);
+    }
+
+    #[test]
+    fn records_on_other_threads_are_not_mixed_in() {
+        let ((), counts) = track(|| {
+            std::thread::spawn(|| record(RetryKind::Phase(1))).join().unwrap();
+        });
+        assert_eq!(counts, RetryCounts::default());
+    }
+}
@@ -0,0 +1,197 @@
+//! Per-run identifier.
+//!
+//! Every `peal run` process gets a run ID, a [ULID](https://github.com/ulid/spec): 26
+//! Crockford base32 characters, a 48-bit millisecond timestamp followed by 80 random bits, so
+//! IDs sort by start time. The ID is a field of the root `run` tracing span (and so of every
+//! event logged during the run), is recorded in `state.json` (`last_run_id`) and the run summary
+//! (`run_id`), and names the run's artifact directory, [`run_dir`] (`{state_dir}/runs/<id>/`).
+//! Several runs against one repo can then be told apart and correlated.
+//!
+//! The random part comes from `std`'s randomly keyed hasher; it is unique, not secret.
+//!
+//! Run directories pile up; [`prune_runs`] deletes the oldest ones beyond `artifact_retention`
+//! at run start. ULIDs sort by time, so the oldest runs are the ones whose IDs sort first.
+
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{info, warn};
+
+use crate::config::ArtifactRetention;
+
+/// Crockford base32 alphabet (no I, L, O, U).
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// The run ID of this process, generated on first use.
+pub fn current() -> &'static str {
+    static RUN_ID: OnceLock<String> = OnceLock::new();
+    RUN_ID.get_or_init(generate)
+}
+
+/// Directory for this run's artifacts: `{state_dir}/runs/<run id>`.
+pub fn run_dir(state_dir: &Path) -> PathBuf {
+    state_dir.join("runs").join(current())
+}
+
+/// An earlier run's directory deleted by [`prune_runs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrunedRun {
+    pub run_id: String,
+    /// Size of the deleted directory.
+    pub bytes: u64,
+}
+
+/// Apply `retention` to `{state_dir}/runs`: keep the newest `keep_runs` earlier runs, then
+/// delete the oldest remaining ones while all run directories together exceed `max_total_mb`.
+/// This run's directory is never deleted. Each deletion is logged; a directory that cannot be
+/// deleted is logged and skipped. Returns what was deleted, oldest first.
+pub fn prune_runs(state_dir: &Path, retention: &ArtifactRetention) -> Vec<PrunedRun> {
+    if retention.keep_runs.is_none() && retention.max_total_mb.is_none() {
+        return Vec::new();
+    }
+    let Ok(entries) = fs::read_dir(state_dir.join("runs")) else {
+        return Vec::new();
+    };
+    let mut runs: Vec<(String, PathBuf, u64)> = entries
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| {
+            let path = e.path();
+            let bytes = dir_size(&path);
+            (e.file_name().to_string_lossy().into_owned(), path, bytes)
+        })
+        .collect();
+    runs.sort();
+    let mut total: u64 = runs.iter().map(|(_, _, bytes)| bytes).sum();
+    runs.retain(|(id, _, _)| id != current());
+
+    let keep = retention.keep_runs.map_or(runs.len(), |n| n as usize);
+    let excess = runs.len().saturating_sub(keep);
+    let max_total = retention.max_total_mb.map(|mb| mb.saturating_mul(1024 * 1024));
+    let mut pruned = Vec::new();
+    for (i, (run_id, path, bytes)) in runs.into_iter().enumerate() {
+        let over_size = max_total.is_some_and(|max| total > max);
+        if i >= excess && !over_size {
+            break;
+        }
+        match fs::remove_dir_all(&path) {
+            Ok(()) => {
+                total -= bytes;
+                info!(run_id = %run_id, size_mb = bytes / (1024 * 1024), "deleted old run artifacts (artifact_retention)");
+                pruned.push(PrunedRun { run_id, bytes });
+            }
+            Err(e) => warn!(path = %path.display(), err = %e, "failed to delete old run artifacts"),
+        }
+    }
+    pruned
+}
+
+/// Total size of the files under `path`; symlinks are not followed.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|e| {
+            let meta = fs::symlink_metadata(e.path()).ok()?;
+            Some(if meta.is_dir() { dir_size(&e.path()) } else { meta.len() })
+        })
+        .sum()
+}
+
+/// A new ULID for the current time.
+pub fn generate() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    encode(millis, random_bits())
+}
+
+/// 80 random bits (in the low bits of the result).
+fn random_bits() -> u128 {
+    let word = |salt: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(salt);
+        hasher.write_u32(std::process::id());
+        hasher.finish()
+    };
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let high = u128::from(word(u64::from(nanos)));
+    let low = u128::from(word(!u64::from(nanos)));
+    ((high << 64) | low) & ((1u128 << 80) - 1)
+}
+
+/// ULID text for a 48-bit timestamp and 80 random bits.
+fn encode(millis: u64, random: u128) -> String {
+    let value = (u128::from(millis & 0xffff_ffff_ffff) << 80) | (random & ((1u128 << 80) - 1));
+    // 26 characters of 5 bits cover 130 bits; the top two are always zero.
+    (0..26)
+        .rev()
+        .map(|i| ALPHABET[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_matches_ulid_layout() {
+        assert_eq!(encode(0, 0), "00000000000000000000000000");
+        assert_eq!(encode(1, 0), "00000000010000000000000000");
+        assert_eq!(encode(0xffff_ffff_ffff, u128::MAX), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+        // Timestamps sort lexicographically.
+        assert!(encode(1_700_000_000_000, u128::MAX) < encode(1_700_000_000_001, 0));
+    }
+
+    #[test]
+    fn generated_ids_are_unique_and_current_is_stable() {
+        let a = generate();
+        let b = generate();
+        assert_eq!(a.len(), 26);
+        assert!(a.bytes().all(|c| ALPHABET.contains(&c)), "{a}");
+        assert_ne!(a, b);
+        assert_eq!(current(), current());
+        assert!(run_dir(Path::new(".peal")).ends_with(Path::new("runs").join(current())));
+    }
+
+    #[test]
+    fn prune_runs_keeps_newest_runs_within_the_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let runs = dir.path().join("runs");
+        // Five earlier runs, oldest first, 1 MiB each; plus this run, which is never deleted.
+        let ids: Vec<String> = (1..=5).map(|t| encode(t, 0)).collect();
+        for id in ids.iter().map(String::as_str).chain([current()]) {
+            fs::create_dir_all(runs.join(id).join("stderr")).unwrap();
+            fs::write(runs.join(id).join("stderr").join("x"), vec![0u8; 1024 * 1024]).unwrap();
+        }
+        let remaining = || {
+            let mut names: Vec<String> = fs::read_dir(&runs)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert!(prune_runs(dir.path(), &ArtifactRetention::default()).is_empty());
+        assert_eq!(remaining().len(), 6);
+
+        let keep_four = ArtifactRetention { keep_runs: Some(4), max_total_mb: None };
+        let pruned = prune_runs(dir.path(), &keep_four);
+        assert_eq!(pruned, vec![PrunedRun { run_id: ids[0].clone(), bytes: 1024 * 1024 }]);
+
+        // 5 MiB left (four earlier runs and this one); a 3 MiB cap deletes the two oldest.
+        let cap = ArtifactRetention { keep_runs: Some(10), max_total_mb: Some(3) };
+        let pruned: Vec<String> = prune_runs(dir.path(), &cap).into_iter().map(|p| p.run_id).collect();
+        assert_eq!(pruned, vec![ids[1].clone(), ids[2].clone()]);
+        assert_eq!(remaining(), vec![ids[3].clone(), ids[4].clone(), current().to_owned()]);
+    }
+}
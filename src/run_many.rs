@@ -0,0 +1,354 @@
+//! `peal run-many`: run several independent plans, each against its own repo, from one
+//! invocation.
+//!
+//! Plan N runs against repo N as a child `peal run` with the invocation's own options plus
+//! `--plan`, `--repo`, `--state-dir`, `--log-file`, and `--run-summary-path`, so every plan has
+//! its own state directory, `{state_dir}/run-many/<run id>/<N>-<plan name>/`, and with it its
+//! own stet session, log file (`peal.log`), and run summary. The child's output goes to
+//! `output.log` next to them. Without `--config` each child finds its config in its own repo,
+//! as `peal run --repo` would. Up to `--max-concurrent-plans` children run at once; a repo may
+//! appear only once, since stet keeps one session per repo.
+//!
+//! When all children have exited, the combined summary ([`RunManyReport`]) is printed and
+//! written as `summary.md` and `summary.json` next to the per-plan directories.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::bench::{model_slug, run_args_without, run_child};
+use crate::error::PealError;
+use crate::estimate::format_duration;
+use crate::{run_id, run_summary};
+
+/// `peal run` options set for each child run; the user's values are dropped.
+const OVERRIDDEN_OPTIONS: [&str; 5] = ["--plan", "--repo", "--state-dir", "--log-file", "--run-summary-path"];
+
+/// run-many-only options, dropped from the child's argv. `--plans` and `--repos` take one or
+/// more values; `--max-concurrent-plans` takes one.
+const MULTI_VALUE_OPTIONS: [&str; 2] = ["--plans", "--repos"];
+const RUN_MANY_OPTIONS: [&str; 1] = ["--max-concurrent-plans"];
+const RUN_MANY_FLAGS: [&str; 1] = ["--detach"];
+
+/// Outcome of one plan's run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlanRun {
+    pub plan: PathBuf,
+    pub repo: PathBuf,
+    /// Exit code of the child `peal run`; `None` when it was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub wall_time_ms: u64,
+    /// Task counts from the run summary; `None` when the run wrote none (it failed early).
+    pub tasks_completed: Option<usize>,
+    pub tasks_failed: Option<usize>,
+    pub tasks_with_remaining_findings: Option<usize>,
+    /// State directory of the run (state, run summary, `peal.log`, `output.log`).
+    pub state_dir: PathBuf,
+}
+
+/// Combined summary of one `peal run-many`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RunManyReport {
+    pub run_id: String,
+    /// In the order the plans were given.
+    pub results: Vec<PlanRun>,
+}
+
+impl RunManyReport {
+    /// Exit code for the whole invocation: 1 when any run failed (exit code other than 0 and 2,
+    /// or killed), else 2 when any run finished with issues, else 0.
+    pub fn exit_code(&self) -> u8 {
+        let codes: Vec<Option<i32>> = self.results.iter().map(|r| r.exit_code).collect();
+        if codes.iter().any(|c| !matches!(c, Some(0 | 2))) {
+            1
+        } else if codes.contains(&Some(2)) {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+impl fmt::Display for RunManyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |n: Option<usize>| n.map_or("-".to_owned(), |n| n.to_string());
+        writeln!(f, "# peal run-many {}", self.run_id)?;
+        writeln!(f)?;
+        writeln!(f, "| plan | repo | exit | wall time | completed | failed | remaining findings |")?;
+        writeln!(f, "|---|---|---|---|---|---|---|")?;
+        for r in &self.results {
+            writeln!(
+                f,
+                "| {} | {} | {} | {} | {} | {} | {} |",
+                r.plan.display(),
+                r.repo.display(),
+                r.exit_code.map_or("signal".to_owned(), |c| c.to_string()),
+                format_duration(r.wall_time_ms as f64 / 1000.0),
+                count(r.tasks_completed),
+                count(r.tasks_failed),
+                count(r.tasks_with_remaining_findings),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Directory of this invocation's summary and per-plan state: `{state_dir}/run-many/<run id>`.
+pub fn run_many_dir(state_dir: &Path) -> PathBuf {
+    state_dir.join("run-many").join(run_id::current())
+}
+
+/// Run `plans[i]` against `repos[i]` with the `peal` binary `exe`, at most `max_concurrent`
+/// at a time. `args` is the invocation's argv after the subcommand name; it is passed on to
+/// each child `peal run` (see [`child_args`]). Fails when the plans and repos do not pair up,
+/// a repo is missing or given twice, or a state directory cannot be created; a failed child
+/// run is just reported.
+pub fn run_many(
+    exe: &Path,
+    state_dir: &Path,
+    plans: &[PathBuf],
+    repos: &[PathBuf],
+    max_concurrent: usize,
+    args: &[OsString],
+) -> Result<RunManyReport, PealError> {
+    let failed = |detail: String| PealError::RunManyFailed { detail };
+    if plans.len() != repos.len() {
+        return Err(failed(format!(
+            "got {} plans but {} repos; give one repo per plan",
+            plans.len(),
+            repos.len()
+        )));
+    }
+    let mut seen = Vec::new();
+    for repo in repos {
+        let canonical = fs::canonicalize(repo).map_err(|e| failed(format!("repo {}: {e}", repo.display())))?;
+        if seen.contains(&canonical) {
+            return Err(failed(format!(
+                "repo {} is given more than once; each plan needs its own repo",
+                repo.display()
+            )));
+        }
+        seen.push(canonical);
+    }
+
+    let absolute = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf());
+    let out_dir = absolute(&run_many_dir(state_dir));
+    let base_args = child_args(args);
+    let mut jobs = Vec::new();
+    for (i, (plan, repo)) in plans.iter().zip(repos).enumerate() {
+        let name = plan.file_stem().map_or("plan".into(), |s| s.to_string_lossy());
+        let dir = out_dir.join(format!("{}-{}", i + 1, model_slug(&name)));
+        fs::create_dir_all(&dir).map_err(|e| failed(format!("cannot create {}: {e}", dir.display())))?;
+        let mut child = base_args.clone();
+        for (option, value) in [
+            ("--plan", absolute(plan).into_os_string()),
+            ("--repo", absolute(repo).into_os_string()),
+            ("--state-dir", dir.clone().into_os_string()),
+            ("--log-file", dir.join("peal.log").into_os_string()),
+            ("--run-summary-path", dir.join("run_summary.json").into_os_string()),
+        ] {
+            child.push(option.into());
+            child.push(value);
+        }
+        jobs.push((absolute(plan), absolute(repo), dir, child));
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<PlanRun>>> = Mutex::new(vec![None; jobs.len()]);
+    let workers = max_concurrent.clamp(1, jobs.len().max(1));
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let Some((plan, repo, dir, child)) = jobs.get(i) else { break };
+                    let result = run_one(exe, plan, repo, dir, child);
+                    results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(result);
+                }
+            });
+        }
+    });
+
+    Ok(RunManyReport {
+        run_id: run_id::current().to_owned(),
+        results: results
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .flatten()
+            .collect(),
+    })
+}
+
+/// Run one child `peal run` and read back its summary. A child that cannot be started is
+/// reported like one killed by a signal, with the reason in its `output.log`.
+fn run_one(exe: &Path, plan: &Path, repo: &Path, state_dir: &Path, args: &[OsString]) -> PlanRun {
+    info!(plan = %plan.display(), repo = %repo.display(), "run-many: running plan");
+    eprintln!(
+        "run-many: {}: running in {} (log: {})",
+        plan.display(),
+        repo.display(),
+        state_dir.join("output.log").display()
+    );
+    let start = Instant::now();
+    let exit_code = run_child(exe, args, state_dir).unwrap_or_else(|detail| {
+        let _ = fs::write(state_dir.join("output.log"), format!("{detail}\n"));
+        None
+    });
+    let wall_time = start.elapsed();
+    eprintln!(
+        "run-many: {}: exit {} after {}",
+        plan.display(),
+        exit_code.map_or("signal".to_owned(), |c| c.to_string()),
+        format_duration(wall_time.as_secs_f64())
+    );
+    let summary = run_summary::load_summary(&state_dir.join("run_summary.json")).ok();
+    PlanRun {
+        plan: plan.to_path_buf(),
+        repo: repo.to_path_buf(),
+        exit_code,
+        wall_time_ms: u64::try_from(wall_time.as_millis()).unwrap_or(u64::MAX),
+        tasks_completed: summary.as_ref().map(|s| s.tasks_completed.len()),
+        tasks_failed: summary.as_ref().map(|s| s.tasks_failed.len()),
+        tasks_with_remaining_findings: summary.as_ref().map(|s| s.tasks_with_remaining_findings.len()),
+        state_dir: state_dir.to_path_buf(),
+    }
+}
+
+/// Write `report` as `summary.md` and `summary.json` in [`run_many_dir`]; returns the Markdown
+/// path.
+pub fn write_report(state_dir: &Path, report: &RunManyReport) -> Result<PathBuf, PealError> {
+    let dir = run_many_dir(state_dir);
+    let md = dir.join("summary.md");
+    let write_err = |e: std::io::Error| PealError::RunManyFailed {
+        detail: format!("cannot write summary in {}: {e}", dir.display()),
+    };
+    fs::create_dir_all(&dir).map_err(write_err)?;
+    fs::write(&md, report.to_string()).map_err(write_err)?;
+    let json = serde_json::to_string_pretty(report).unwrap_or_default();
+    fs::write(dir.join("summary.json"), json).map_err(write_err)?;
+    Ok(md)
+}
+
+/// The invocation's argv (after the subcommand name) as `peal run` argv: `run` followed by
+/// every argument except the run-many-only ones and those in [`OVERRIDDEN_OPTIONS`].
+pub fn child_args(args: &[OsString]) -> Vec<OsString> {
+    let mut kept = Vec::new();
+    let mut iter = args.iter().peekable();
+    while let Some(arg) = iter.next() {
+        let text = arg.to_string_lossy();
+        if MULTI_VALUE_OPTIONS.contains(&text.as_ref()) {
+            while iter.next_if(|value| !value.to_string_lossy().starts_with('-')).is_some() {}
+        } else if !MULTI_VALUE_OPTIONS.iter().any(|name| text.starts_with(&format!("{name}="))) {
+            kept.push(arg.clone());
+        }
+    }
+    let options: Vec<&str> = OVERRIDDEN_OPTIONS.iter().chain(&RUN_MANY_OPTIONS).copied().collect();
+    run_args_without(&kept, &options, &RUN_MANY_FLAGS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn child_args_drop_run_many_and_overridden_options() {
+        let args = os(&[
+            "--plans", "a.md", "b.md", "--repos=rA,rB", "--max-concurrent-plans", "2", "--state-dir", "s",
+            "--model", "m", "--detach", "--parallel",
+        ]);
+        assert_eq!(child_args(&args), os(&["run", "--model", "m", "--parallel"]));
+    }
+
+    #[test]
+    fn exit_code_is_the_worst_of_the_runs() {
+        let run = |exit_code| PlanRun {
+            plan: PathBuf::from("p.md"),
+            repo: PathBuf::from("r"),
+            exit_code,
+            wall_time_ms: 0,
+            tasks_completed: None,
+            tasks_failed: None,
+            tasks_with_remaining_findings: None,
+            state_dir: PathBuf::new(),
+        };
+        let report = |codes: &[Option<i32>]| RunManyReport {
+            run_id: "r".to_owned(),
+            results: codes.iter().map(|&c| run(c)).collect(),
+        };
+        assert_eq!(report(&[Some(0), Some(0)]).exit_code(), 0);
+        assert_eq!(report(&[Some(0), Some(2)]).exit_code(), 2);
+        assert_eq!(report(&[Some(2), Some(3)]).exit_code(), 1);
+        assert_eq!(report(&[Some(0), None]).exit_code(), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_many_runs_plans_concurrently_each_with_its_own_state_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut plans = Vec::new();
+        let mut repos = Vec::new();
+        for name in ["alpha", "beta"] {
+            let repo = dir.path().join(format!("repo-{name}"));
+            fs::create_dir_all(&repo).unwrap();
+            fs::write(repo.join(format!("{name}.md")), "## Task 1\nDo it.\n").unwrap();
+            plans.push(repo.join(format!("{name}.md")));
+            repos.push(repo);
+        }
+
+        // Stand-in for `peal run`: "alpha" waits for "beta" to start (so it only succeeds when
+        // both run at once) and writes a summary; "beta" exits with issues.
+        let fake = dir.path().join("fake_peal");
+        let marker = dir.path().join("beta-started");
+        fs::write(
+            &fake,
+            format!(
+                "#!/bin/sh\nwhile [ $# -gt 0 ]; do case \"$1\" in\n\
+                 --plan) plan=$2; shift;; --log-file) log=$2; shift;; --run-summary-path) summary=$2; shift;;\n\
+                 esac; shift; done\n\
+                 touch \"$log\"\n\
+                 case \"$plan\" in *beta.md) touch '{marker}'; echo beta >&2; exit 2;; esac\n\
+                 i=0; while [ ! -f '{marker}' ]; do i=$((i+1)); [ $i -gt 100 ] && exit 1; sleep 0.05; done\n\
+                 echo '{{\"tasks_completed\":[1],\"tasks_failed\":[],\"tasks_with_remaining_findings\":[]}}' > \"$summary\"\n",
+                marker = marker.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let state_dir = dir.path().join(".peal");
+        assert!(run_many(&fake, &state_dir, &plans, &repos[..1], 2, &[]).is_err());
+        let same_repo = [repos[0].clone(), repos[0].join(".")];
+        let err = run_many(&fake, &state_dir, &plans, &same_repo, 2, &[]).unwrap_err();
+        assert!(err.to_string().contains("more than once"), "{err}");
+
+        let report = run_many(&fake, &state_dir, &plans, &repos, 2, &os(&["--model", "m"])).unwrap();
+        assert_eq!(report.results.len(), 2);
+        let alpha = &report.results[0];
+        assert_eq!((alpha.exit_code, alpha.tasks_completed), (Some(0), Some(1)));
+        assert!(alpha.state_dir.ends_with("1-alpha"));
+        assert!(alpha.state_dir.join("peal.log").is_file());
+        let beta = &report.results[1];
+        assert_eq!((beta.exit_code, beta.tasks_completed), (Some(2), None));
+        assert_eq!(fs::read_to_string(beta.state_dir.join("output.log")).unwrap(), "beta\n");
+        assert_eq!(report.exit_code(), 2);
+
+        let md = write_report(&state_dir, &report).unwrap();
+        let text = fs::read_to_string(md).unwrap();
+        assert!(text.contains("alpha.md |"), "{text}");
+        assert!(run_many_dir(&state_dir).join("summary.json").is_file());
+    }
+}
@@ -1,25 +1,44 @@
-//! Run summary: build and write run_summary.json on successful run completion.
+//! Run summary: build and write run_summary.json on successful run completion (or, marked
+//! "interrupted", when a signal stops the run), and compare two summaries (`peal diff-runs`).
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use chrono::Utc;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
+use crate::clock::{self, ClockJump};
 use crate::config::PealConfig;
-use crate::runner::RunOutcome;
+use crate::error::PealError;
+use crate::phase;
+use crate::plan::{ParsedPlan, PlanMetadata};
+use crate::retries::RetryCounts;
+use crate::run_id;
+use crate::runner::{RunOutcome, TaskFailure};
+use crate::state::PealState;
 
-/// Summary of a completed run, written when exit code is 0 or 2.
+/// Summary of a completed run, written when exit code is 0 or 2, or of an interrupted one
+/// (see [`interrupted_summary`]).
 #[derive(Debug, Clone, Serialize)]
 pub struct RunSummary {
+    /// ID of the run (see [`crate::run_id`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// "completed" when the run reached its end, "interrupted" when a signal stopped it.
+    pub status: String,
+    /// The signal that stopped an interrupted run (e.g. "SIGTERM").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interrupted_by: Option<String>,
     /// Task indices that completed without failure and without remaining findings.
     pub tasks_completed: Vec<u32>,
     /// Task indices that were attempted but failed (only non-empty when continue_with_remaining_tasks).
     pub tasks_failed: Vec<u32>,
     /// Task indices that completed phase 2 and phase 3 ran but findings_resolved == false.
     pub tasks_with_remaining_findings: Vec<u32>,
-    /// Exit code used for this run (0 or 2).
+    /// Exit code used for this run (0 or 2; 128 + the signal number when interrupted).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exit_code: Option<u8>,
     /// Plan path from config (for context).
@@ -31,13 +50,133 @@ pub struct RunSummary {
     /// ISO8601 timestamp when the run completed.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub completed_at: Option<String>,
+    /// Plan front-matter metadata, when the plan has any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan_metadata: Option<PlanMetadata>,
+    /// Stderr excerpts for phases that wrote to stderr, for tasks attempted in this run.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub phase_stderr: Vec<PhaseStderrExcerpt>,
+    /// Agent-written task summaries keyed by task index (only with `summarize_tasks`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub task_summaries: BTreeMap<u32, String>,
+    /// Retries spent per task in this run (phase, plan-text and stet retries); tasks that
+    /// never retried are absent.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub task_retries: BTreeMap<u32, RetryCounts>,
+    /// Escalation model that finished each task whose configured model failed
+    /// (`escalation_models`); tasks that never escalated are absent.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub escalated_models: BTreeMap<u32, String>,
+    /// True when Phase 3 was turned off for the run (`enable_phase3 = false` / `--no-phase3`),
+    /// so no task was reviewed.
+    #[serde(skip_serializing_if = "is_false")]
+    pub phase3_disabled: bool,
+    /// Plan normalization retries in this run (set by the caller; 0 is omitted).
+    #[serde(skip_serializing_if = "is_zero")]
+    pub normalization_retries: u32,
+    /// True when `--limit-tasks` left pending tasks for a later run (set by the caller).
+    #[serde(skip_serializing_if = "is_false")]
+    pub partial_run: bool,
+    /// Error of this run's `stet finish` when it failed and left the stet session open (set by
+    /// the caller); the next run can clean it up with `--cleanup-stet`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stet_finish_failed: Option<String>,
+    /// Per-stage breakdown for plans with `# Stage:` headings (set by the caller via
+    /// `stage_summaries`), in run order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<StageSummary>,
+    /// Why each task of `tasks_failed` failed: the phase and the first line of the error.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<TaskFailure>,
+    /// Command that reruns just the failed tasks (set by the caller; see [`rerun_command`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerun_command: Option<String>,
+    /// Wall-clock jumps seen during the run (host suspended, clock changed; see
+    /// [`crate::clock`]).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub clock_jumps: Vec<ClockJump>,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// Outcome of one plan stage: its tasks, split like the top-level task lists.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct StageSummary {
+    pub name: String,
+    /// All tasks of the stage in this run's plan, in run order.
+    pub tasks: Vec<u32>,
+    pub tasks_completed: Vec<u32>,
+    pub tasks_failed: Vec<u32>,
+    pub tasks_with_remaining_findings: Vec<u32>,
+}
+
+/// Split the summary's task lists by the stages of `plan`; empty when the plan has no stages.
+pub fn stage_summaries(plan: &ParsedPlan, summary: &RunSummary) -> Vec<StageSummary> {
+    let within = |list: &[u32], tasks: &[u32]| -> Vec<u32> {
+        tasks.iter().copied().filter(|i| list.contains(i)).collect()
+    };
+    plan.stages()
+        .into_iter()
+        .map(|(name, tasks)| StageSummary {
+            name: name.to_owned(),
+            tasks_completed: within(&summary.tasks_completed, &tasks),
+            tasks_failed: within(&summary.tasks_failed, &tasks),
+            tasks_with_remaining_findings: within(&summary.tasks_with_remaining_findings, &tasks),
+            tasks,
+        })
+        .collect()
+}
+
+/// Bounded stderr excerpt from a phase's stderr artifact.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct PhaseStderrExcerpt {
+    pub task_index: u32,
+    pub phase: u32,
+    /// Path to the persisted stderr artifact.
+    pub path: String,
+    /// Tail of the artifact, capped at `phase::STDERR_EXCERPT_BYTES`.
+    pub excerpt: String,
+}
+
+/// Collect stderr excerpts from the artifacts of the given tasks (phases 1–3, in order).
+/// Missing or empty artifacts are skipped.
+pub fn collect_phase_stderr(state_dir: &Path, task_indices: &[u32]) -> Vec<PhaseStderrExcerpt> {
+    let mut out = Vec::new();
+    for &task_index in task_indices {
+        for p in 1..=3 {
+            let path = phase::stderr_artifact_path(state_dir, task_index, p);
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let excerpt = phase::stderr_tail(&content, phase::STDERR_EXCERPT_BYTES).trim();
+            if excerpt.is_empty() {
+                continue;
+            }
+            out.push(PhaseStderrExcerpt {
+                task_index,
+                phase: p,
+                path: path.display().to_string(),
+                excerpt: excerpt.to_owned(),
+            });
+        }
+    }
+    out
 }
 
 /// Build RunSummary from RunOutcome and config. Fills tasks_completed, tasks_failed,
-/// tasks_with_remaining_findings from results and failed_task_indices; optional fields from config.
+/// tasks_with_remaining_findings from results and failed_task_indices; optional fields from config
+/// and plan metadata (omitted when the plan has no front matter). phase_stderr is read from the
+/// stderr artifacts of tasks attempted in this run.
 pub fn build_summary(
     outcome: &RunOutcome,
     config: &PealConfig,
+    plan_metadata: &PlanMetadata,
     exit_code: u8,
 ) -> RunSummary {
     let results = &outcome.results;
@@ -49,7 +188,7 @@ pub fn build_summary(
             !failed.contains(&r.task_index)
                 && r.phase3_outcome
                     .as_ref()
-                    .map_or(true, |o| o.findings_resolved)
+                    .is_none_or(|o| o.findings_resolved)
         })
         .map(|r| r.task_index)
         .collect();
@@ -59,12 +198,20 @@ pub fn build_summary(
         .filter(|r| {
             r.phase3_outcome
                 .as_ref()
-                .map_or(false, |o| !o.findings_resolved)
+                .is_some_and(|o| !o.findings_resolved)
         })
         .map(|r| r.task_index)
         .collect();
 
+    let mut attempted: Vec<u32> = results.iter().map(|r| r.task_index).collect();
+    attempted.extend(failed.iter().copied());
+    attempted.sort_unstable();
+    attempted.dedup();
+
     RunSummary {
+        run_id: Some(run_id::current().to_owned()),
+        status: "completed".to_owned(),
+        interrupted_by: None,
         tasks_completed,
         tasks_failed: failed.clone(),
         tasks_with_remaining_findings,
@@ -72,7 +219,139 @@ pub fn build_summary(
         plan_path: Some(config.plan_path.display().to_string()),
         repo_path: Some(config.repo_path.display().to_string()),
         completed_at: Some(Utc::now().to_rfc3339()),
+        plan_metadata: if plan_metadata.is_empty() {
+            None
+        } else {
+            Some(plan_metadata.clone())
+        },
+        phase_stderr: collect_phase_stderr(&config.state_dir, &attempted),
+        task_summaries: results
+            .iter()
+            .filter_map(|r| r.summary.clone().map(|s| (r.task_index, s)))
+            .collect(),
+        task_retries: outcome.task_retries.clone(),
+        escalated_models: outcome.escalated_models.clone(),
+        phase3_disabled: !config.enable_phase3,
+        normalization_retries: 0,
+        partial_run: false,
+        stet_finish_failed: None,
+        stages: Vec::new(),
+        failures: failed
+            .iter()
+            .filter_map(|idx| outcome.failures.get(idx).cloned())
+            .collect(),
+        rerun_command: None,
+        clock_jumps: clock::jumps(),
+    }
+}
+
+/// Summary of a run stopped by `signal` (e.g. "SIGTERM"), from what is on disk: the tasks
+/// `state` marks complete that were not complete when the run started (`completed_at_start`)
+/// are `tasks_completed`. Tasks still in flight are in none of the lists; their failures and
+/// findings are not known yet.
+pub fn interrupted_summary(
+    config: &PealConfig,
+    plan_metadata: &PlanMetadata,
+    completed_at_start: &[u32],
+    state: Option<&PealState>,
+    signal: &str,
+    exit_code: u8,
+) -> RunSummary {
+    let tasks_completed: Vec<u32> = state
+        .map(|s| s.completed_task_indices.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|i| !completed_at_start.contains(i))
+        .collect();
+    RunSummary {
+        run_id: Some(run_id::current().to_owned()),
+        status: "interrupted".to_owned(),
+        interrupted_by: Some(signal.to_owned()),
+        exit_code: Some(exit_code),
+        plan_path: Some(config.plan_path.display().to_string()),
+        repo_path: Some(config.repo_path.display().to_string()),
+        completed_at: Some(Utc::now().to_rfc3339()),
+        plan_metadata: if plan_metadata.is_empty() {
+            None
+        } else {
+            Some(plan_metadata.clone())
+        },
+        phase_stderr: collect_phase_stderr(&config.state_dir, &tasks_completed),
+        task_summaries: BTreeMap::new(),
+        task_retries: BTreeMap::new(),
+        escalated_models: state
+            .map(|s| {
+                s.escalated_models
+                    .iter()
+                    .filter(|(i, _)| tasks_completed.contains(i))
+                    .map(|(i, m)| (*i, m.clone()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        phase3_disabled: !config.enable_phase3,
+        normalization_retries: 0,
+        partial_run: false,
+        stet_finish_failed: None,
+        stages: Vec::new(),
+        failures: Vec::new(),
+        rerun_command: None,
+        clock_jumps: clock::jumps(),
+        tasks_completed,
+        tasks_failed: Vec::new(),
+        tasks_with_remaining_findings: Vec::new(),
+    }
+}
+
+/// `peal run` options dropped from a rerun command: the task selection, replaced by `--task`
+/// for each failed task, and `--limit-tasks`. Each takes a value.
+const RERUN_DROPPED_OPTIONS: [&str; 3] = ["--task", "--from-task", "--limit-tasks"];
+
+/// Longest error line shown per task in the failure digest.
+const DIGEST_ERROR_MAX_CHARS: usize = 200;
+
+/// The `peal run` command that reruns just the `failed` tasks: the arguments the run was
+/// started with (after `run`) minus any task selection, plus `--task N` for each failed task
+/// and `--force-rerun` (a task that failed in Phase 3 of a parallel block is already marked
+/// complete). Arguments the shell would split or expand are single-quoted.
+pub fn rerun_command(run_args: &[String], failed: &[u32]) -> String {
+    let mut words: Vec<String> = vec!["peal".to_owned(), "run".to_owned()];
+    let mut iter = run_args.iter();
+    while let Some(arg) = iter.next() {
+        if RERUN_DROPPED_OPTIONS.contains(&arg.as_str()) {
+            iter.next();
+        } else if arg != "--force-rerun"
+            && !RERUN_DROPPED_OPTIONS.iter().any(|name| arg.starts_with(&format!("{name}=")))
+        {
+            words.push(shell_word(arg));
+        }
+    }
+    for idx in failed {
+        words.push(format!("--task {idx}"));
     }
+    words.push("--force-rerun".to_owned());
+    words.join(" ")
+}
+
+fn shell_word(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | '=' | ',' | ':' | '@'));
+    if plain { arg.to_owned() } else { format!("'{}'", arg.replace('\'', r"'\''")) }
+}
+
+/// Human-readable digest of the tasks a run went on past (`continue_with_remaining_tasks`):
+/// one line per failed task with the phase and the error, then `rerun_command`. Printed at the
+/// end of the run, where the failures would otherwise have scrolled by mid-log.
+pub fn failure_digest(failures: &[TaskFailure], rerun_command: &str) -> String {
+    let mut out = format!("{} task(s) failed:\n", failures.len());
+    for failure in failures {
+        let mut error: String = failure.error.chars().take(DIGEST_ERROR_MAX_CHARS).collect();
+        if error.len() < failure.error.len() {
+            error.push('…');
+        }
+        out.push_str(&format!("  task {} [{}]: {error}\n", failure.task_index, failure.phase));
+    }
+    out.push_str(&format!("rerun just these tasks: {rerun_command}\n"));
+    out
 }
 
 /// Resolve the path to write the run summary: config.run_summary_path or state_dir/run_summary.json.
@@ -86,15 +365,15 @@ pub fn summary_path(config: &PealConfig) -> std::path::PathBuf {
 /// Write summary to the given path. Creates parent dirs if needed; writes atomically (temp then rename).
 /// Best-effort: on failure logs a warning and does not change exit code.
 pub fn write_run_summary(summary: &RunSummary, path: &Path) {
-    if let Some(parent) = path.parent() {
-        if let Err(e) = fs::create_dir_all(parent) {
-            tracing::warn!(
-                path = %path.display(),
-                err = %e,
-                "failed to create parent directory for run summary"
-            );
-            return;
-        }
+    if let Some(parent) = path.parent()
+        && let Err(e) = fs::create_dir_all(parent)
+    {
+        tracing::warn!(
+            path = %path.display(),
+            err = %e,
+            "failed to create parent directory for run summary"
+        );
+        return;
     }
 
     let json = match serde_json::to_string_pretty(summary) {
@@ -136,3 +415,305 @@ pub fn write_run_summary(summary: &RunSummary, path: &Path) {
         let _ = fs::remove_file(&tmp_path);
     }
 }
+
+/// The parts of a `run_summary.json` that `peal diff-runs` compares. Unknown and missing
+/// fields are ignored, so summaries from older or newer versions still load.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+pub struct SummarySnapshot {
+    #[serde(default)]
+    pub tasks_completed: Vec<u32>,
+    #[serde(default)]
+    pub tasks_failed: Vec<u32>,
+    #[serde(default)]
+    pub tasks_with_remaining_findings: Vec<u32>,
+    #[serde(default)]
+    pub exit_code: Option<u8>,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+    #[serde(default)]
+    pub task_retries: BTreeMap<u32, RetryCounts>,
+}
+
+/// Read a run summary written by [`write_run_summary`].
+pub fn load_summary(path: &Path) -> Result<SummarySnapshot, PealError> {
+    let read_err = |detail: String| PealError::RunSummaryReadFailed {
+        path: path.to_path_buf(),
+        detail,
+    };
+    let content = fs::read_to_string(path).map_err(|e| read_err(e.to_string()))?;
+    serde_json::from_str(&content).map_err(|e| read_err(e.to_string()))
+}
+
+/// What changed between two runs, task indices ascending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunDiff {
+    pub before_path: PathBuf,
+    pub after_path: PathBuf,
+    pub before: SummarySnapshot,
+    pub after: SummarySnapshot,
+    /// Completed (clean) in `after` but not in `before`.
+    pub newly_completed: Vec<u32>,
+    /// Completed (clean) in `before` but not in `after`.
+    pub no_longer_completed: Vec<u32>,
+    /// Findings remained in `before`; the task is clean in `after`.
+    pub findings_resolved: Vec<u32>,
+    /// Findings remain in `after` for a task that was clean or not run in `before`.
+    pub findings_new: Vec<u32>,
+    /// Findings remain in both runs.
+    pub findings_still_remaining: Vec<u32>,
+    /// Failed in `after` but not in `before`.
+    pub newly_failed: Vec<u32>,
+    /// Failed in `before` but not in `after`.
+    pub no_longer_failed: Vec<u32>,
+    /// Tasks whose retry total changed: (task, before, after).
+    pub retry_changes: Vec<(u32, u32, u32)>,
+}
+
+/// Compare the summaries at `before_path` and `after_path`.
+pub fn diff_runs(before_path: &Path, after_path: &Path) -> Result<RunDiff, PealError> {
+    let before = load_summary(before_path)?;
+    let after = load_summary(after_path)?;
+    Ok(diff_snapshots(before_path, after_path, before, after))
+}
+
+fn diff_snapshots(
+    before_path: &Path,
+    after_path: &Path,
+    before: SummarySnapshot,
+    after: SummarySnapshot,
+) -> RunDiff {
+    let set = |v: &[u32]| v.iter().copied().collect::<BTreeSet<u32>>();
+    let minus = |a: &BTreeSet<u32>, b: &BTreeSet<u32>| a.difference(b).copied().collect::<Vec<_>>();
+    let both = |a: &BTreeSet<u32>, b: &BTreeSet<u32>| a.intersection(b).copied().collect::<Vec<_>>();
+
+    let (done_a, done_b) = (set(&before.tasks_completed), set(&after.tasks_completed));
+    let (fail_a, fail_b) = (set(&before.tasks_failed), set(&after.tasks_failed));
+    let (left_a, left_b) = (
+        set(&before.tasks_with_remaining_findings),
+        set(&after.tasks_with_remaining_findings),
+    );
+
+    let tasks: BTreeSet<u32> =
+        before.task_retries.keys().chain(after.task_retries.keys()).copied().collect();
+    let retry_changes = tasks
+        .into_iter()
+        .filter_map(|t| {
+            let total = |m: &BTreeMap<u32, RetryCounts>| m.get(&t).map_or(0, RetryCounts::total);
+            let (a, b) = (total(&before.task_retries), total(&after.task_retries));
+            (a != b).then_some((t, a, b))
+        })
+        .collect();
+
+    RunDiff {
+        before_path: before_path.to_path_buf(),
+        after_path: after_path.to_path_buf(),
+        newly_completed: minus(&done_b, &done_a),
+        no_longer_completed: minus(&done_a, &done_b),
+        findings_resolved: both(&left_a, &done_b),
+        findings_new: minus(&left_b, &left_a),
+        findings_still_remaining: both(&left_a, &left_b),
+        newly_failed: minus(&fail_b, &fail_a),
+        no_longer_failed: minus(&fail_a, &fail_b),
+        retry_changes,
+        before,
+        after,
+    }
+}
+
+impl fmt::Display for RunDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let run = |label: &str, path: &Path, s: &SummarySnapshot| {
+            format!(
+                "{label}: {} (completed_at {}, exit code {})",
+                path.display(),
+                s.completed_at.as_deref().unwrap_or("?"),
+                s.exit_code.map_or("?".to_owned(), |c| c.to_string())
+            )
+        };
+        writeln!(f, "{}", run("before", &self.before_path, &self.before))?;
+        writeln!(f, "{}", run("after ", &self.after_path, &self.after))?;
+        writeln!(
+            f,
+            "completed: {} -> {}, failed: {} -> {}, with remaining findings: {} -> {}",
+            self.before.tasks_completed.len(),
+            self.after.tasks_completed.len(),
+            self.before.tasks_failed.len(),
+            self.after.tasks_failed.len(),
+            self.before.tasks_with_remaining_findings.len(),
+            self.after.tasks_with_remaining_findings.len(),
+        )?;
+
+        let lines = [
+            ("newly completed", &self.newly_completed),
+            ("no longer completed", &self.no_longer_completed),
+            ("findings resolved", &self.findings_resolved),
+            ("findings newly remaining", &self.findings_new),
+            ("findings still remaining", &self.findings_still_remaining),
+            ("newly failed", &self.newly_failed),
+            ("no longer failed", &self.no_longer_failed),
+        ];
+        let mut changed = false;
+        for (label, tasks) in lines {
+            if !tasks.is_empty() {
+                changed = true;
+                writeln!(f, "{label}: {}", task_list(tasks))?;
+            }
+        }
+        if !self.retry_changes.is_empty() {
+            changed = true;
+            let retries: Vec<String> = self
+                .retry_changes
+                .iter()
+                .map(|(t, a, b)| format!("task {t}: {a} -> {b}"))
+                .collect();
+            writeln!(f, "retries: {}", retries.join(", "))?;
+        }
+        if !changed {
+            writeln!(f, "no task-level changes")?;
+        }
+        Ok(())
+    }
+}
+
+fn task_list(tasks: &[u32]) -> String {
+    tasks.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, json: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, json).unwrap();
+        path
+    }
+
+    #[test]
+    fn diff_runs_reports_completion_findings_failures_and_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let before = write(
+            dir.path(),
+            "before.json",
+            r#"{"tasks_completed":[1],"tasks_failed":[4],"tasks_with_remaining_findings":[2,3],
+                "exit_code":2,"completed_at":"2026-01-01T00:00:00Z","task_retries":{"2":{"phase1":2}}}"#,
+        );
+        let after = write(
+            dir.path(),
+            "after.json",
+            r#"{"tasks_completed":[1,2,4],"tasks_failed":[],"tasks_with_remaining_findings":[3,5],
+                "exit_code":2,"plan_metadata":{"title":"x"}}"#,
+        );
+
+        let diff = diff_runs(&before, &after).unwrap();
+
+        assert_eq!(diff.newly_completed, vec![2, 4]);
+        assert!(diff.no_longer_completed.is_empty());
+        assert_eq!(diff.findings_resolved, vec![2]);
+        assert_eq!(diff.findings_new, vec![5]);
+        assert_eq!(diff.findings_still_remaining, vec![3]);
+        assert_eq!(diff.no_longer_failed, vec![4]);
+        assert_eq!(diff.retry_changes, vec![(2, 2, 0)]);
+
+        let text = diff.to_string();
+        assert!(text.contains("completed: 1 -> 3"), "{text}");
+        assert!(text.contains("findings resolved: 2\n"), "{text}");
+        assert!(text.contains("retries: task 2: 2 -> 0"), "{text}");
+        assert!(text.contains("completed_at ?"), "{text}");
+    }
+
+    #[test]
+    fn diff_runs_identical_summaries_and_unreadable_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = write(dir.path(), "a.json", r#"{"tasks_completed":[1]}"#);
+        let diff = diff_runs(&a, &a).unwrap();
+        assert!(diff.to_string().contains("no task-level changes"));
+
+        let err = diff_runs(&a, &dir.path().join("missing.json")).unwrap_err();
+        assert!(matches!(err, PealError::RunSummaryReadFailed { .. }), "got: {err:?}");
+        let bad = write(dir.path(), "bad.json", "not json");
+        assert!(diff_runs(&bad, &a).is_err());
+    }
+
+
+    #[test]
+    fn rerun_command_replaces_task_selection_and_quotes_arguments() {
+        let args: Vec<String> = ["--plan", "my plan.md", "--from-task", "3", "--limit-tasks=2", "--parallel", "--force-rerun"]
+            .map(str::to_owned)
+            .to_vec();
+        assert_eq!(
+            rerun_command(&args, &[4, 7]),
+            "peal run --plan 'my plan.md' --parallel --task 4 --task 7 --force-rerun"
+        );
+        assert_eq!(rerun_command(&[], &[2]), "peal run --task 2 --force-rerun");
+    }
+
+    #[test]
+    fn failure_digest_lists_phase_and_error_per_task() {
+        let failures = vec![
+            TaskFailure { task_index: 2, phase: "phase 2".to_owned(), error: "Phase 2 timed out after 600s".to_owned() },
+            TaskFailure { task_index: 5, phase: "verify".to_owned(), error: "x".repeat(300) },
+        ];
+        let digest = failure_digest(&failures, "peal run --task 2 --task 5 --force-rerun");
+        let lines: Vec<&str> = digest.lines().collect();
+        assert_eq!(lines[0], "2 task(s) failed:");
+        assert_eq!(lines[1], "  task 2 [phase 2]: Phase 2 timed out after 600s");
+        assert!(lines[2].starts_with("  task 5 [verify]: xxx") && lines[2].ends_with("x…"), "{}", lines[2]);
+        assert_eq!(lines[3], "rerun just these tasks: peal run --task 2 --task 5 --force-rerun");
+    }
+
+    #[test]
+    fn stage_summaries_split_task_lists_by_stage() {
+        let plan = crate::plan::parse_plan(
+            "## Task 1\nA\n# Stage: One\n## Task 2\nB\n## Task 3\nC\n# Stage: Two\n## Task 4\nD\n",
+        )
+        .unwrap();
+        let summary = RunSummary {
+            run_id: None,
+            status: "completed".to_owned(),
+            interrupted_by: None,
+            tasks_completed: vec![1, 2],
+            tasks_failed: vec![4],
+            tasks_with_remaining_findings: vec![3],
+            exit_code: Some(2),
+            plan_path: None,
+            repo_path: None,
+            completed_at: None,
+            plan_metadata: None,
+            phase_stderr: Vec::new(),
+            task_summaries: BTreeMap::new(),
+            task_retries: BTreeMap::new(),
+            escalated_models: BTreeMap::new(),
+            phase3_disabled: false,
+            normalization_retries: 0,
+            partial_run: false,
+            stet_finish_failed: None,
+            stages: Vec::new(),
+            failures: Vec::new(),
+            rerun_command: None,
+            clock_jumps: Vec::new(),
+        };
+
+        let stages = stage_summaries(&plan, &summary);
+
+        assert_eq!(
+            stages,
+            vec![
+                StageSummary {
+                    name: "One".to_owned(),
+                    tasks: vec![2, 3],
+                    tasks_completed: vec![2],
+                    tasks_failed: vec![],
+                    tasks_with_remaining_findings: vec![3],
+                },
+                StageSummary {
+                    name: "Two".to_owned(),
+                    tasks: vec![4],
+                    tasks_completed: vec![],
+                    tasks_failed: vec![4],
+                    tasks_with_remaining_findings: vec![],
+                },
+            ]
+        );
+    }
+}
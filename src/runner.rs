@@ -3,43 +3,69 @@
 //! SP-1.6: run Phase 1 for every task in order, capture plan text, log results.
 //! SP-2.2: sequential runner — Phase 1 → Phase 2 per task, fail-fast.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use std::process::Command;
+use std::sync::{Arc, Mutex, PoisonError, TryLockError};
 use std::time::{Duration, Instant};
 
-use tracing::{error, info, warn};
+use serde::Serialize;
+use tracing::{Span, debug, debug_span, error, info, warn};
 
+use crate::clock;
 use crate::config::PealConfig;
 use crate::error::PealError;
 use crate::phase::{self, PhaseOutput};
-use crate::plan::ParsedPlan;
-use crate::state::{self, PealState};
+use crate::plan::{ParsedPlan, Task};
+use crate::retries::{self, RetryCounts, RetryKind};
+use crate::sparse;
+use crate::state::{self, PealState, StateStore};
 use crate::stet;
+use crate::timing::{self, PhaseDurations};
+use crate::vcs::{self, Vcs};
+
+/// Whether Phase 3 saves state itself after a stet session restart or a stet failure. Threads
+/// of a parallel Phase 3 each hold a copy of state, so they leave saving to the parent, which
+/// merges their changes once they join (see [`run_parallel_phase3`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase3Saves {
+    Now,
+    Deferred,
+}
+
+impl Phase3Saves {
+    fn save(self, peal_state: &PealState, state_dir: &Path) -> Result<(), PealError> {
+        match self {
+            Phase3Saves::Now => state::save_state(peal_state, state_dir),
+            Phase3Saves::Deferred => Ok(()),
+        }
+    }
+}
 
 /// Run stet review with on_stet_fail policy. Returns Ok(Some(result)) on success,
 /// Ok(None) when policy is "skip" and run failed, Err when policy is "fail" or "retry_once" exhausted.
 fn run_stet_review_with_policy(
     stet_path: &Path,
-    repo_path: &Path,
-    extra_args: &[String],
+    config: &PealConfig,
     timeout: Option<Duration>,
-    on_stet_fail: &str,
     task_index: u32,
     peal_state: &mut PealState,
     state_dir: &Path,
+    saves: Phase3Saves,
 ) -> Result<Option<stet::StetRunResult>, PealError> {
-    let first = stet::run_review(stet_path, repo_path, extra_args, timeout);
+    let on_stet_fail = config.on_stet_fail.as_str();
+    let first = stet::run_review_for_config(stet_path, config, timeout);
     match first {
-        Ok(r) => return Ok(Some(r)),
+        Ok(r) => Ok(Some(r)),
         Err(e) => {
             if on_stet_fail == "retry_once" {
                 warn!(task_index, err = %e, "stet run failed, retrying once");
-                match stet::run_review(stet_path, repo_path, extra_args, timeout) {
+                retries::record(RetryKind::Stet);
+                match stet::run_review_for_config(stet_path, config, timeout) {
                     Ok(r) => return Ok(Some(r)),
                     Err(e2) => {
                         error!(task_index, err = %e2, "stet run failed");
-                        if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                        if let Err(save_err) = saves.save(peal_state, state_dir) {
                             error!(err = %save_err, "failed to save state after stet failure");
                         }
                         return Err(e2);
@@ -51,10 +77,10 @@ fn run_stet_review_with_policy(
                 return Ok(None);
             }
             error!(task_index, err = %e, "stet run failed");
-            if let Err(save_err) = state::save_state(peal_state, state_dir) {
+            if let Err(save_err) = saves.save(peal_state, state_dir) {
                 error!(err = %save_err, "failed to save state after stet failure");
             }
-            return Err(e);
+            Err(e)
         }
     }
 }
@@ -62,24 +88,28 @@ fn run_stet_review_with_policy(
 /// Run the last command of stet_commands with on_stet_fail policy. Same semantics as run_stet_review_with_policy.
 fn run_custom_stet_run_with_policy(
     last_command: &str,
-    repo_path: &Path,
+    config: &PealConfig,
     timeout: Option<Duration>,
-    on_stet_fail: &str,
     task_index: u32,
     peal_state: &mut PealState,
     state_dir: &Path,
+    saves: Phase3Saves,
 ) -> Result<Option<stet::StetRunResult>, PealError> {
-    let first = stet::run_review_via_command(last_command, repo_path, timeout);
+    let on_stet_fail = config.on_stet_fail.as_str();
+    let (shell, repo_path) = (config.command_shell.as_str(), config.repo_path.as_path());
+    let last_command = &stet::expand_command(last_command, config, Some(task_index));
+    let first = stet::run_review_via_command(last_command, shell, repo_path, timeout);
     match first {
-        Ok(r) => return Ok(Some(r)),
+        Ok(r) => Ok(Some(r)),
         Err(e) => {
             if on_stet_fail == "retry_once" {
                 warn!(task_index, err = %e, "custom stet run failed, retrying once");
-                match stet::run_review_via_command(last_command, repo_path, timeout) {
+                retries::record(RetryKind::Stet);
+                match stet::run_review_via_command(last_command, shell, repo_path, timeout) {
                     Ok(r) => return Ok(Some(r)),
                     Err(e2) => {
                         error!(task_index, err = %e2, "custom stet run failed");
-                        if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                        if let Err(save_err) = saves.save(peal_state, state_dir) {
                             error!(err = %save_err, "failed to save state after stet failure");
                         }
                         return Err(e2);
@@ -91,41 +121,28 @@ fn run_custom_stet_run_with_policy(
                 return Ok(None);
             }
             error!(task_index, err = %e, "custom stet run failed");
-            if let Err(save_err) = state::save_state(peal_state, state_dir) {
+            if let Err(save_err) = saves.save(peal_state, state_dir) {
                 error!(err = %save_err, "failed to save state after stet failure");
             }
-            return Err(e);
+            Err(e)
         }
     }
 }
 
-/// Run `git add -A` and `git commit -m "peal: task {task_index}..."` in repo_path.
-/// If `git commit` fails with "nothing to commit" / "working tree clean", logs and returns Ok(()) (no-op).
+/// Commit every change in repo_path as "peal: task {task_index}..." with the configured VCS
+/// (`git add -A` and `git commit` for git). When there is nothing to commit, logs and returns
+/// Ok(()) (no-op).
 fn commit_after_phase2(
+    vcs: &dyn Vcs,
     repo_path: &Path,
     task_index: u32,
     message_suffix: Option<&str>,
 ) -> Result<(), PealError> {
-    let add_result = Command::new("git")
-        .arg("-C")
-        .arg(repo_path)
-        .args(["add", "-A"])
-        .output()
-        .map_err(|e| PealError::CommitAfterPhase2Failed {
-            detail: format!("git add failed: {e}"),
-        })?;
-    if !add_result.status.success() {
-        let stderr = String::from_utf8_lossy(&add_result.stderr);
-        return Err(PealError::CommitAfterPhase2Failed {
-            detail: format!("git add failed: {}", stderr.trim()),
-        });
-    }
-
     let mut message = format!("peal: task {task_index}");
     if let Some(suffix) = message_suffix {
         let truncated = suffix.trim();
         let truncated = if truncated.len() > 80 {
-            format!("{}...", &truncated[..77])
+            format!("{}...", crate::subprocess::truncate_utf8(truncated, 77))
         } else {
             truncated.to_owned()
         };
@@ -135,36 +152,350 @@ fn commit_after_phase2(
         }
     }
 
-    let commit_result = Command::new("git")
+    match vcs.commit_all(repo_path, &message) {
+        Ok(true) => {
+            info!(task_index, "committed after phase 2");
+            Ok(())
+        }
+        Ok(false) => {
+            info!(task_index, "nothing to commit after phase 2 (working tree clean)");
+            Ok(())
+        }
+        Err(detail) => Err(PealError::CommitAfterPhase2Failed { detail }),
+    }
+}
+
+/// `git push` the current branch to `push_remote` (as `push_branch` when set) after a segment.
+/// With `on_push_fail = "warn"` a failed push is logged and the run continues.
+fn push_after_segment(config: &PealConfig, segment_tasks: &[u32]) -> Result<(), PealError> {
+    let refspec = match &config.push_branch {
+        Some(branch) => format!("HEAD:refs/heads/{branch}"),
+        None => "HEAD".to_owned(),
+    };
+    let result = match Command::new("git")
         .arg("-C")
-        .arg(repo_path)
-        .args(["commit", "-m", &message])
+        .arg(&config.repo_path)
+        .args(["push", &config.push_remote, &refspec])
         .output()
-        .map_err(|e| PealError::CommitAfterPhase2Failed {
-            detail: format!("git commit failed: {e}"),
-        })?;
+    {
+        Ok(output) if output.status.success() => {
+            info!(tasks = ?segment_tasks, remote = %config.push_remote, "pushed after segment");
+            return Ok(());
+        }
+        Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        Err(e) => e.to_string(),
+    };
+    let err = PealError::PushAfterSegmentFailed { detail: result };
+    if config.on_push_fail == "fail" {
+        return Err(err);
+    }
+    warn!(tasks = ?segment_tasks, err = %err, "push after segment failed; continuing");
+    Ok(())
+}
 
-    if commit_result.status.success() {
-        info!(task_index, "committed after phase 2");
-        return Ok(());
+/// Current commit of the repo (HEAD for git), or `None` when the VCS fails (e.g. no commit yet).
+fn head_commit(config: &PealConfig) -> Option<String> {
+    vcs::for_config(config).head(&config.repo_path)
+}
+
+/// With `rollback_failed_tasks`, the checkpoint (HEAD) to roll a sequential task back to if it
+/// fails and the run continues. `None` when the policy is off or HEAD cannot be resolved.
+fn task_checkpoint(config: &PealConfig, task_index: u32) -> Option<String> {
+    if !config.rollback_failed_tasks || !config.continue_with_remaining_tasks {
+        return None;
+    }
+    let head = head_commit(config);
+    if head.is_none() {
+        warn!(task_index, "rollback_failed_tasks: cannot resolve HEAD; task will not be rolled back");
+    }
+    head
+}
+
+/// With `stet_scope_to_task` or `phase2_change_check`, the HEAD before a task (or parallel block)
+/// starts, used to find the files it changed. `None` when both are off or HEAD cannot be resolved.
+fn scope_base(config: &PealConfig, task_index: u32) -> Option<String> {
+    if !config.stet_scope_to_task && config.phase2_change_check == "off" {
+        return None;
+    }
+    let head = head_commit(config);
+    if head.is_none() {
+        warn!(task_index, "cannot resolve HEAD; stet review is not scoped and Phase 2 changes are not checked");
+    }
+    head
+}
+
+/// `phase3_scope = "block"`: the task a parallel block's single Phase 3 review runs under, the
+/// last of `indices` for which `done` holds; its outcome stands for the whole block. `None` in
+/// task scope or when no task of the block got that far.
+fn block_review_task(config: &PealConfig, indices: &[u32], done: impl Fn(u32) -> bool) -> Option<u32> {
+    if config.phase3_scope != "block" {
+        return None;
     }
+    let task_index = indices.iter().rev().copied().find(|&idx| done(idx))?;
+    info!(task_index, block_indices = ?indices, "phase 3: one review for the whole block");
+    Some(task_index)
+}
 
-    let stderr = String::from_utf8_lossy(&commit_result.stderr);
-    let stderr_lower = stderr.to_lowercase();
-    let empty_or_clean = stderr.is_empty()
-        || stderr_lower.contains("nothing to commit")
-        || stderr_lower.contains("working tree clean")
-        || stderr_lower.contains("no changes added to commit");
-    if empty_or_clean {
-        info!(task_index, "nothing to commit after phase 2 (working tree clean)");
+/// `phase2_change_check`: compare what the Phase 2 transcript says the agent edited with the
+/// files git shows changed since `task_base`. A task that changed nothing, or none of the files
+/// it claims to have edited, is suspicious: logged with "warn", an error with "retry" and
+/// "fail" (see [`run_phase2_checked`]). When nothing changed and the agent's answer contains a
+/// refusal phrase ([`phase::refusal_phrase`], e.g. "already implemented"), the detail quotes it.
+fn check_phase2_changes(
+    config: &PealConfig,
+    state_dir: &Path,
+    task_index: u32,
+    task_base: Option<&str>,
+    phase2_stdout: &str,
+) -> Result<(), PealError> {
+    if config.phase2_change_check == "off" {
+        return Ok(());
+    }
+    let Some(changed) = task_base.and_then(|base| changed_files_since(config, state_dir, base)) else {
+        warn!(task_index, "phase2_change_check: git diff failed; Phase 2 changes not checked");
+        return Ok(());
+    };
+    let claimed: Vec<String> = phase::parse_phase2_transcript(phase2_stdout)
+        .map(|t| t.edited_files)
+        .unwrap_or_default()
+        .iter()
+        .map(|path| claimed_repo_path(config, path))
+        .collect();
+    let detail = if changed.is_empty() {
+        let mut detail = if claimed.is_empty() {
+            "the agent reported success but no files changed".to_owned()
+        } else {
+            format!(
+                "the agent reported editing {} but no files changed",
+                claimed.join(", ")
+            )
+        };
+        if let Some(phrase) = phase::refusal_phrase(&phase2_text(phase2_stdout.to_owned())) {
+            detail.push_str(&format!(" (its answer says \"{phrase}\")"));
+        }
+        detail
+    } else if !claimed.is_empty() && !claimed.iter().any(|c| changed.contains(c)) {
+        format!(
+            "the agent reported editing {} but git shows changes only in {}",
+            claimed.join(", "),
+            changed.join(", ")
+        )
+    } else {
+        debug!(task_index, changed = ?changed, claimed = ?claimed, "phase 2 changes confirmed");
         return Ok(());
+    };
+    let err = PealError::Phase2NoChanges { task_index, detail };
+    if config.phase2_change_check != "warn" {
+        return Err(err);
+    }
+    warn!(task_index, err = %err, "suspicious phase 2; marking task complete anyway");
+    Ok(())
+}
+
+/// [`check_phase2_changes`] on a Phase 2 answer. With `phase2_change_check = "retry"`, a
+/// no-op Phase 2 is run once more with a firmer prompt (`phase::run_phase2_noop_retry`) and
+/// checked again; a second no-op fails the task. Returns the stdout of the attempt that passed.
+#[allow(clippy::too_many_arguments)]
+fn run_phase2_checked(
+    agent_path: &Path,
+    config: &PealConfig,
+    task: &crate::plan::Task,
+    plan_text: &str,
+    state_dir: &Path,
+    task_base: Option<&str>,
+    stdout: String,
+) -> Result<String, PealError> {
+    let detail = match check_phase2_changes(config, state_dir, task.index, task_base, &stdout) {
+        Err(PealError::Phase2NoChanges { detail, .. }) if config.phase2_change_check == "retry" => detail,
+        other => return other.map(|()| stdout),
+    };
+    warn!(task_index = task.index, detail = %detail, "phase 2 made no changes; retrying with a firmer prompt");
+    retries::record(RetryKind::Phase(2));
+    let start = Instant::now();
+    let output = sparse::run_in_sparse_worktree(agent_path, config, task, |c| {
+        phase::run_phase2_noop_retry(agent_path, c, task.index, plan_text, &task.context, &detail)
+    });
+    timing::record(2, start.elapsed());
+    let stdout = output?.stdout;
+    check_phase2_changes(config, state_dir, task.index, task_base, &stdout)?;
+    Ok(stdout)
+}
+
+/// A file the agent reports editing as a `repo_path`-relative path. With `repo_subdir` the
+/// agent works in [`PealConfig::work_dir`], so relative paths are relative to that.
+fn claimed_repo_path(config: &PealConfig, path: &str) -> String {
+    let Some(prefix) = subdir_prefix(config) else {
+        return repo_relative(&config.repo_path, path);
+    };
+    let rel = repo_relative(&config.work_dir(), path);
+    if Path::new(&rel).is_absolute() {
+        repo_relative(&config.repo_path, &rel)
+    } else {
+        format!("{prefix}{rel}")
+    }
+}
+
+/// `repo_subdir` as a repo-relative path prefix with a trailing `/` (e.g. `services/payments/`).
+fn subdir_prefix(config: &PealConfig) -> Option<String> {
+    let parts: Vec<String> = config
+        .repo_subdir
+        .as_ref()?
+        .components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    (!parts.is_empty()).then(|| format!("{}/", parts.join("/")))
+}
+
+/// `path` (as reported by the agent) relative to `repo_path` when it is an absolute path inside
+/// the repo; otherwise unchanged.
+fn repo_relative(repo_path: &Path, path: &str) -> String {
+    let p = Path::new(path);
+    if !p.is_absolute() {
+        return path.trim_start_matches("./").to_owned();
     }
+    let repo = repo_path.canonicalize().unwrap_or_else(|_| repo_path.to_path_buf());
+    p.strip_prefix(&repo)
+        .or_else(|_| p.strip_prefix(repo_path))
+        .map(|rel| rel.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_owned())
+}
 
-    Err(PealError::CommitAfterPhase2Failed {
-        detail: format!("git commit failed: {}", stderr.trim()),
+/// Files under `repo_path` changed since `base` (committed, staged, unstaged or untracked) as
+/// repo-relative paths, without deleted files, peal's state dir, or the plan file; with
+/// `repo_subdir`, only files under it. `None` when git fails.
+fn changed_files_since(config: &PealConfig, state_dir: &Path, base: &str) -> Option<Vec<String>> {
+    let repo = &config.repo_path;
+    let exclude: Vec<String> = [state_dir, config.plan_path.as_path()]
+        .into_iter()
+        .filter_map(|own| repo_relative_pathspec(repo, own))
+        .collect();
+    let changed = vcs::for_config(config).changed_files_since(repo, base, &exclude)?;
+    Some(match subdir_prefix(config) {
+        Some(prefix) => changed.into_iter().filter(|f| f.starts_with(&prefix)).collect(),
+        None => changed,
     })
 }
 
+/// For `stet_scope_to_task`, the files the task changed since `task_base`. `None` means review
+/// unscoped (option off, no base, or git failed); an empty list means the task changed nothing.
+fn task_changed_files(
+    config: &PealConfig,
+    state_dir: &Path,
+    task_index: u32,
+    task_base: Option<&str>,
+) -> Option<Vec<String>> {
+    if !config.stet_scope_to_task {
+        return None;
+    }
+    let Some(base) = task_base else {
+        warn!(task_index, "stet_scope_to_task: no starting commit; stet review is not scoped");
+        return None;
+    };
+    let files = changed_files_since(config, state_dir, base);
+    if files.is_none() {
+        warn!(task_index, "stet_scope_to_task: git diff failed; stet review is not scoped");
+    }
+    files
+}
+
+/// `path` relative to `repo_path` as a git pathspec, when it lies inside the repo.
+fn repo_relative_pathspec(repo_path: &Path, path: &Path) -> Option<String> {
+    let repo = repo_path.canonicalize().ok()?;
+    let path = std::path::absolute(path).ok()?;
+    let path = path.canonicalize().unwrap_or(path);
+    let rel = path.strip_prefix(&repo).ok()?;
+    (!rel.as_os_str().is_empty()).then(|| rel.to_string_lossy().replace('\\', "/"))
+}
+
+/// Undo a failed task's edits (`rollback_failed_tasks`). Uncommitted and untracked changes are
+/// stashed as "peal: task N failed" (peal's state dir and plan file are left alone); if the
+/// task moved HEAD, its commits are kept under `refs/peal/failed/task-N` before
+/// `git reset --hard <checkpoint>`. Best-effort: failures are logged and the run continues.
+fn rollback_task(config: &PealConfig, state_dir: &Path, task_index: u32, checkpoint: &str) {
+    let repo = &config.repo_path;
+    let git = |args: &[String]| -> Result<(), String> {
+        let out = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(args)
+            .output()
+            .map_err(|e| e.to_string())?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&out.stderr).trim().to_owned())
+        }
+    };
+
+    let mut stash: Vec<String> = ["stash", "push", "--include-untracked", "-m"]
+        .iter()
+        .map(|s| (*s).to_owned())
+        .collect();
+    stash.push(format!("peal: task {task_index} failed"));
+    stash.extend(["--".to_owned(), ".".to_owned()]);
+    for own in [state_dir, config.plan_path.as_path()] {
+        if let Some(spec) = repo_relative_pathspec(repo, own) {
+            stash.push(format!(":(exclude){spec}"));
+        }
+    }
+    if let Err(e) = git(&stash) {
+        warn!(task_index, err = %e, "rollback: git stash failed; leaving the working tree as is");
+        return;
+    }
+
+    let head = head_commit(config);
+    if head.as_deref() != Some(checkpoint) {
+        let keep = format!("refs/peal/failed/task-{task_index}");
+        if let Some(head) = &head
+            && let Err(e) = git(&["update-ref".to_owned(), keep.clone(), head.clone()])
+        {
+            warn!(task_index, err = %e, "rollback: could not save task commits; not resetting");
+            return;
+        }
+        if let Err(e) = git(&["reset".to_owned(), "--hard".to_owned(), checkpoint.to_owned()]) {
+            warn!(task_index, err = %e, "rollback: git reset --hard failed");
+            return;
+        }
+        info!(task_index, saved_ref = %keep, "rollback: task commits saved");
+    }
+    warn!(task_index, checkpoint, "rolled back failed task to its checkpoint");
+}
+
+/// Upper bound on diff bytes sent to the agent for a task summary.
+const SUMMARY_DIFF_MAX_BYTES: usize = 16 * 1024;
+
+/// The task's changes for the summary prompt: the uncommitted diff (`git diff HEAD`), or the
+/// latest commit when the tree is clean (e.g. after `commit_after_phase2`). Capped at
+/// `SUMMARY_DIFF_MAX_BYTES`; empty when the VCS fails.
+fn task_diff(config: &PealConfig) -> String {
+    let mut diff = vcs::for_config(config).task_diff(&config.repo_path);
+    if diff.len() > SUMMARY_DIFF_MAX_BYTES {
+        let end = crate::subprocess::truncate_utf8(&diff, SUMMARY_DIFF_MAX_BYTES).len();
+        diff.truncate(end);
+        diff.push_str("\n... (truncated)");
+    }
+    diff
+}
+
+/// Post-task summary step (`config.summarize_tasks`). Best-effort: never fails the task.
+fn summarize_task(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    phase2_stdout: &str,
+) -> Option<String> {
+    if !config.summarize_tasks {
+        return None;
+    }
+    let diff = task_diff(config);
+    let summary = phase::run_task_summary(agent_path, config, task_index, phase2_stdout, &diff)?;
+    info!(task_index, summary_len = summary.len(), "task summary recorded");
+    Some(summary)
+}
+
 /// When config.validate_plan_text is true, checks plan text length (and optionally
 /// expected tokens). Returns Ok(()) if disabled or valid, Err(PealError::Phase1PlanTextInvalid)
 /// if invalid.
@@ -182,17 +513,17 @@ pub(crate) fn validate_plan_text(
             detail: "plan text is empty".into(),
         });
     }
-    if let Some(min) = config.min_plan_text_len {
-        if plan_text.len() < min {
-            return Err(PealError::Phase1PlanTextInvalid {
-                task_index,
-                detail: format!(
-                    "plan text length {} below minimum {}",
-                    plan_text.len(),
-                    min
-                ),
-            });
-        }
+    if let Some(min) = config.min_plan_text_len
+        && plan_text.len() < min
+    {
+        return Err(PealError::Phase1PlanTextInvalid {
+            task_index,
+            detail: format!(
+                "plan text length {} below minimum {}",
+                plan_text.len(),
+                min
+            ),
+        });
     }
     Ok(())
 }
@@ -211,6 +542,30 @@ pub struct TaskResult {
     pub plan_text: String,
     pub phase2_stdout: String,
     pub phase3_outcome: Option<stet::AddressLoopOutcome>,
+    /// Agent-written summary of the task, when `summarize_tasks` is enabled and the call succeeded.
+    pub summary: Option<String>,
+}
+
+/// A task that failed in a run that went on (`continue_with_remaining_tasks`), for the
+/// failure digest (see [`crate::run_summary::failure_digest`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskFailure {
+    pub task_index: u32,
+    /// What failed ([`PealError::failed_phase`]), e.g. `phase 2` or `verify`.
+    pub phase: String,
+    /// First line of the error.
+    pub error: String,
+}
+
+impl TaskFailure {
+    pub fn new(task_index: u32, err: &PealError) -> Self {
+        let error = err.to_string();
+        TaskFailure {
+            task_index,
+            phase: err.failed_phase(),
+            error: error.lines().next().unwrap_or_default().trim().to_owned(),
+        }
+    }
 }
 
 /// Outcome of a full scheduled run: task results and indices of tasks that failed
@@ -219,6 +574,31 @@ pub struct TaskResult {
 pub struct RunOutcome {
     pub results: Vec<TaskResult>,
     pub failed_task_indices: Vec<u32>,
+    /// Why each task of `failed_task_indices` failed (its last failure when retried).
+    pub failures: BTreeMap<u32, TaskFailure>,
+    /// Retries spent per task in this run, including tasks that failed; tasks that
+    /// never retried are absent.
+    pub task_retries: BTreeMap<u32, RetryCounts>,
+    /// Escalation model that finished each task of this run whose configured model
+    /// failed (`escalation_models`); tasks that never escalated are absent.
+    pub escalated_models: BTreeMap<u32, String>,
+    /// True when `peal stop` ended the run before every task ran (see [`crate::daemon`]).
+    pub stopped: bool,
+}
+
+impl RunOutcome {
+    /// Folds the outcome of a later run over another part of the plan (a `session_size`
+    /// session) into this one.
+    pub fn merge(&mut self, other: RunOutcome) {
+        self.results.extend(other.results);
+        self.failed_task_indices.extend(other.failed_task_indices);
+        self.failures.extend(other.failures);
+        for (idx, counts) in other.task_retries {
+            self.task_retries.entry(idx).or_default().add(counts);
+        }
+        self.escalated_models.extend(other.escalated_models);
+        self.stopped |= other.stopped;
+    }
 }
 
 /// Run Phase 1 (plan creation) for every task in order.
@@ -251,6 +631,7 @@ pub fn run_phase1_all(
             continue;
         }
 
+        let _span = task_span(task.index).entered();
         info!(
             task_index = task.index,
             position, task_count, "phase 1: task {position}/{task_count}"
@@ -258,7 +639,7 @@ pub fn run_phase1_all(
 
         let start = Instant::now();
 
-        let output: PhaseOutput = phase::run_phase1(agent_path, config, task.index, &task.content)
+        let output: PhaseOutput = phase::run_phase1(agent_path, config, task.index, &task.content, &task.context)
             .map_err(|e| {
                 error!(
                     task_index = task.index,
@@ -293,7 +674,8 @@ pub fn run_phase1_all(
                 err = %e,
                 "plan text validation failed, retrying phase 1 once"
             );
-            let second = phase::run_phase1(agent_path, config, task.index, &task.content)
+            retries::record(RetryKind::Phase(1));
+            let second = phase::run_phase1(agent_path, config, task.index, &task.content, &task.context)
                 .map_err(|e| {
                     error!(
                         task_index = task.index,
@@ -311,7 +693,9 @@ pub fn run_phase1_all(
             validate_plan_text(config, task.index, &output.stdout)?;
         }
 
+        peal_state.record_plan_text(task.index, &output.stdout);
         peal_state.mark_task_completed(task.index);
+        peal_state.record_task_hash(task.index, &task.content);
         state::save_state(peal_state, state_dir)?;
 
         results.push(TaskPhase1Result {
@@ -331,130 +715,61 @@ pub fn run_phase1_all(
 /// Run Phase 1 → Phase 2 → Phase 3 for a single task, mark it completed, and
 /// persist state. Extracted from the per-task body so both sequential and
 /// parallel segment branches can reuse it.
+#[allow(clippy::too_many_arguments)]
 fn run_single_task(
     agent_path: &Path,
     config: &PealConfig,
     task: &crate::plan::Task,
     peal_state: &mut PealState,
     state_dir: &Path,
+    store: &mut StateStore,
     phase3_mode: Option<&stet::StetPhase3Mode>,
     task_count: usize,
     position: usize,
 ) -> Result<TaskResult, PealError> {
-    // -- Phase 1 --
-    info!(
-        task_index = task.index,
-        position, task_count, "phase 1: task {position}/{task_count}"
-    );
-
-    let p1_start = Instant::now();
-
-    let mut p1_output: PhaseOutput =
-        phase::run_phase1(agent_path, config, task.index, &task.content).map_err(|e| {
-            error!(
-                task_index = task.index,
-                position,
-                task_count,
-                err = %e,
-                "phase 1 failed"
-            );
-            if let Err(save_err) = state::save_state(peal_state, state_dir) {
-                error!(err = %save_err, "failed to save state after phase 1 failure");
-            }
-            e
-        })?;
+    let _span = task_span(task.index).entered();
+    let task_base = scope_base(config, task.index);
 
-    let p1_duration = p1_start.elapsed();
+    peal_state.escalated_models.remove(&task.index);
 
-    info!(
-        task_index = task.index,
-        position,
-        task_count,
-        duration_ms = p1_duration.as_millis() as u64,
-        plan_text_len = p1_output.stdout.len(),
-        "phase 1 complete"
-    );
+    // -- Phase 1 --
+    let (plan_text, p1_model) = with_escalation(config, task.index, 1, |_| false, |c| {
+        run_phase1_validated(agent_path, c, task, task_count, position)
+    })
+    .inspect_err(|_| {
+        if let Err(save_err) = store.save_now(peal_state) {
+            error!(err = %save_err, "failed to save state after phase 1 failure");
+        }
+    })?;
 
-    if let Err(e) = validate_plan_text(config, task.index, &p1_output.stdout) {
-        warn!(
-            task_index = task.index,
-            position,
-            task_count,
-            err = %e,
-            "plan text validation failed, retrying phase 1 once"
-        );
-        p1_output = phase::run_phase1(agent_path, config, task.index, &task.content).map_err(
-            |e| {
-                error!(
-                    task_index = task.index,
-                    position,
-                    task_count,
-                    err = %e,
-                    "phase 1 retry failed"
-                );
-                if let Err(save_err) = state::save_state(peal_state, state_dir) {
-                    error!(err = %save_err, "failed to save state after phase 1 failure");
-                }
-                e
-            },
-        )?;
-        validate_plan_text(config, task.index, &p1_output.stdout).map_err(|e| {
-            error!(
-                task_index = task.index,
-                position,
-                task_count,
-                err = %e,
-                "plan text still invalid after retry"
-            );
-            if let Err(save_err) = state::save_state(peal_state, state_dir) {
-                error!(err = %save_err, "failed to save state after plan text validation failure");
-            }
-            e
-        })?;
-    }
+    peal_state.record_plan_text(task.index, &plan_text);
 
     // -- Phase 2 --
-    info!(
-        task_index = task.index,
-        position, task_count, "phase 2: task {position}/{task_count}"
-    );
-
-    let p2_start = Instant::now();
-
-    let p2_output: PhaseOutput =
-        phase::run_phase2(agent_path, config, task.index, &p1_output.stdout).map_err(|e| {
-            error!(
-                task_index = task.index,
-                position,
-                task_count,
-                err = %e,
-                "phase 2 failed"
-            );
-            if let Err(save_err) = state::save_state(peal_state, state_dir) {
-                error!(err = %save_err, "failed to save state after phase 2 failure");
-            }
-            e
-        })?;
-
-    let p2_duration = p2_start.elapsed();
-
-    info!(
-        task_index = task.index,
-        position,
-        task_count,
-        duration_ms = p2_duration.as_millis() as u64,
-        stdout_len = p2_output.stdout.len(),
-        "phase 2 complete"
-    );
+    let (p2_stdout, p2_model) = with_escalation(config, task.index, 2, |_| false, |c| {
+        let stdout =
+            run_phase2_raw(agent_path, c, task, &plan_text, task_count, position)?;
+        let stdout = run_phase2_checked(agent_path, c, task, &plan_text, state_dir, task_base.as_deref(), stdout)
+            .inspect_err(|e| error!(task_index = task.index, err = %e, "phase 2 made no changes"))?;
+        run_task_verify(agent_path, c, task)?;
+        Ok(stdout)
+    })
+    .inspect_err(|_| {
+        if let Err(save_err) = store.save_now(peal_state) {
+            error!(err = %save_err, "failed to save state after phase 2 failure");
+        }
+    })?;
+    if let Some(model) = p2_model.or(p1_model) {
+        peal_state.record_escalated_model(task.index, &model);
+    }
+    let p2_text = phase2_text(p2_stdout);
 
     if config.commit_after_phase2 {
-        let first_line = p1_output
-            .stdout
+        let first_line = plan_text
             .lines()
             .next()
             .map(|s| s.trim())
             .filter(|s| !s.is_empty());
-        if let Err(e) = commit_after_phase2(&config.repo_path, task.index, first_line) {
+        if let Err(e) = commit_after_phase2(&*vcs::for_config(config), &config.repo_path, task.index, first_line) {
             error!(
                 task_index = task.index,
                 position,
@@ -462,7 +777,7 @@ fn run_single_task(
                 err = %e,
                 "commit after phase 2 failed"
             );
-            if let Err(save_err) = state::save_state(peal_state, state_dir) {
+            if let Err(save_err) = store.save_now(peal_state) {
                 error!(err = %save_err, "failed to save state after commit failure");
             }
             return Err(e);
@@ -472,72 +787,193 @@ fn run_single_task(
     // -- Phase 3 (stet review + address) --
     let phase3_outcome = match phase3_mode {
         None => None,
-        Some(mode) => match mode {
-            stet::StetPhase3Mode::BuiltIn(stet_path) => {
+        Some(mode) => run_task_phase3(
+            agent_path,
+            config,
+            task.index,
+            peal_state,
+            state_dir,
+            Phase3Saves::Now,
+            mode,
+            task_count,
+            position,
+            task_base.as_deref(),
+        )?,
+    };
+
+    check_task_expectations(config, task).inspect_err(|_| {
+        if let Err(save_err) = store.save_now(peal_state) {
+            error!(err = %save_err, "failed to save state after expectation failure");
+        }
+    })?;
+
+    let summary = summarize_task(agent_path, config, task.index, &p2_text);
+
+    peal_state.mark_task_completed(task.index);
+    peal_state.record_task_hash(task.index, &task.content);
+    store.save(peal_state)?;
+
+    Ok(TaskResult {
+        task_index: task.index,
+        plan_text,
+        phase2_stdout: p2_text,
+        phase3_outcome,
+        summary,
+    })
+}
+
+/// Phase 3 for a single task: stet review (with `on_stet_fail`) then the address loop
+/// when findings are present. Returns `Ok(None)` when phase 3 was skipped for this task.
+/// `task_base` is the HEAD before the task started (see `stet_scope_to_task`).
+///
+/// When the address loop fails or leaves findings, Phase 3 is re-run with each
+/// `escalation_models` entry in turn; the model that finished it is recorded in state.
+#[allow(clippy::too_many_arguments)]
+fn run_task_phase3(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    peal_state: &mut PealState,
+    state_dir: &Path,
+    saves: Phase3Saves,
+    mode: &stet::StetPhase3Mode,
+    task_count: usize,
+    position: usize,
+    task_base: Option<&str>,
+) -> Result<Option<stet::AddressLoopOutcome>, PealError> {
+    let p3_start = clock::Stamp::now();
+    let result = with_escalation(
+        config,
+        task_index,
+        3,
+        |o: &Option<stet::AddressLoopOutcome>| o.as_ref().is_some_and(|o| !o.findings_resolved),
+        |c| {
+            run_task_phase3_once(
+                agent_path, c, task_index, peal_state, state_dir, saves, mode, task_count,
+                position, task_base,
+            )
+        },
+    );
+    clock::check_phase(task_index, 3, &p3_start);
+    let (outcome, model) = result?;
+    timing::record(3, p3_start.elapsed());
+    if let Some(model) = model {
+        peal_state.record_escalated_model(task_index, &model);
+    }
+    Ok(outcome)
+}
+
+/// One Phase 3 attempt with the given config (see [`run_task_phase3`]).
+#[allow(clippy::too_many_arguments)]
+fn run_task_phase3_once(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    peal_state: &mut PealState,
+    state_dir: &Path,
+    saves: Phase3Saves,
+    mode: &stet::StetPhase3Mode,
+    task_count: usize,
+    position: usize,
+    task_base: Option<&str>,
+) -> Result<Option<stet::AddressLoopOutcome>, PealError> {
+    let phase3_outcome = match mode {
+        stet::StetPhase3Mode::BuiltIn(stet_path) => {
             let sp = stet_path.as_path();
+            match stet::ensure_session(sp, config) {
+                Ok(stet::SessionHealth::Restarted) => {
+                    peal_state.stet_session =
+                        Some(state::StetSession::started_now(config.stet_start_ref.clone()));
+                    if let Err(save_err) = saves.save(peal_state, state_dir) {
+                        warn!(err = %save_err, "failed to save state after restarting stet session");
+                    }
+                }
+                Ok(_) => {}
+                Err(e) if config.on_stet_fail == "skip" => {
+                    warn!(task_index, err = %e, "stet session could not be restarted; phase 3 skipped");
+                    return Ok(None);
+                }
+                Err(e) => {
+                    error!(task_index, err = %e, "stet session could not be restarted");
+                    if let Err(save_err) = saves.save(peal_state, state_dir) {
+                        error!(err = %save_err, "failed to save state after stet failure");
+                    }
+                    return Err(e);
+                }
+            }
+            let scoped_config;
+            let config = match task_changed_files(config, state_dir, task_index, task_base) {
+                None => config,
+                Some(files) if files.is_empty() => {
+                    info!(task_index, "phase 3 skipped (task changed no files)");
+                    return Ok(None);
+                }
+                Some(files) => {
+                    info!(task_index, files = files.len(), "phase 3: stet run scoped to task files");
+                    let mut scoped = config.clone();
+                    scoped
+                        .stet_run_extra_args
+                        .extend(stet::path_filter_args(&config.stet_path_args, &files));
+                    scoped_config = scoped;
+                    &scoped_config
+                }
+            };
             let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
 
             info!(
-                task_index = task.index,
+                task_index,
                 position, task_count,
                 "phase 3: running stet review"
             );
 
             let stet_result = run_stet_review_with_policy(
                 sp,
-                &config.repo_path,
-                &config.stet_run_extra_args,
+                config,
                 timeout,
-                config.on_stet_fail.as_str(),
-                task.index,
+                task_index,
                 peal_state,
                 state_dir,
+                saves,
             )?;
 
             let stet_result = match stet_result {
                 Some(r) => r,
                 None => {
                     // skip: stet run failed, phase 3 skipped for this task
-                    info!(task_index = task.index, "phase 3 skipped (stet run failed)");
-                    peal_state.mark_task_completed(task.index);
-                    state::save_state(peal_state, state_dir)?;
-                    return Ok(TaskResult {
-                        task_index: task.index,
-                        plan_text: p1_output.stdout,
-                        phase2_stdout: p2_output.stdout,
-                        phase3_outcome: None,
-                    });
+                    info!(task_index, "phase 3 skipped (stet run failed)");
+                    return Ok(None);
                 }
             };
 
             if stet_result.has_findings {
-                info!(task_index = task.index, "phase 3: findings detected, starting address loop");
+                info!(task_index, "phase 3: findings detected, starting address loop");
 
-                let outcome = match stet::address_loop(agent_path, sp, config, task.index, &stet_result) {
+                let outcome = match stet::address_loop(agent_path, sp, config, task_index, &stet_result) {
                     Ok(o) => o,
                     Err(e) => {
-                        if config.on_stet_fail == "retry_once" {
-                            warn!(task_index = task.index, err = %e, "address loop failed, retrying once");
-                            match stet::address_loop(agent_path, sp, config, task.index, &stet_result) {
+                        if config.on_stet_fail == "retry_once" && !e.is_agent_failure() {
+                            warn!(task_index, err = %e, "address loop failed, retrying once");
+                            retries::record(RetryKind::Stet);
+                            match stet::address_loop(agent_path, sp, config, task_index, &stet_result) {
                                 Ok(o) => o,
                                 Err(e2) => {
-                                    error!(task_index = task.index, err = %e2, "address loop failed after retry");
-                                    if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                                    error!(task_index, err = %e2, "address loop failed after retry");
+                                    if let Err(save_err) = saves.save(peal_state, state_dir) {
                                         error!(err = %save_err, "failed to save state after address failure");
                                     }
                                     return Err(e2);
                                 }
                             }
-                        } else if config.on_stet_fail == "skip" {
-                            warn!(task_index = task.index, err = %e, "stet phase skipped");
+                        } else if config.on_stet_fail == "skip" && !e.is_agent_failure() {
+                            warn!(task_index, err = %e, "stet phase skipped");
                             stet::AddressLoopOutcome {
                                 rounds_used: 0,
                                 findings_resolved: false,
                                 last_stet_result: stet_result.clone(),
                             }
                         } else {
-                            error!(task_index = task.index, err = %e, "address loop failed");
-                            if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                            error!(task_index, err = %e, "address loop failed");
+                            if let Err(save_err) = saves.save(peal_state, state_dir) {
                                 error!(err = %save_err, "failed to save state after address failure");
                             }
                             return Err(e);
@@ -546,14 +982,14 @@ fn run_single_task(
                 };
 
                 info!(
-                    task_index = task.index,
+                    task_index,
                     rounds = outcome.rounds_used,
                     resolved = outcome.findings_resolved,
                     "phase 3 complete"
                 );
                 Some(outcome)
             } else {
-                info!(task_index = task.index, "phase 3: no findings, skipping address loop");
+                info!(task_index, "phase 3: no findings, skipping address loop");
                 Some(stet::AddressLoopOutcome {
                     rounds_used: 0,
                     findings_resolved: true,
@@ -563,88 +999,78 @@ fn run_single_task(
         }
         stet::StetPhase3Mode::CustomCommands(commands) => {
             let Some(last_cmd) = commands.last() else {
-                return Ok(TaskResult {
-                    task_index: task.index,
-                    plan_text: p1_output.stdout,
-                    phase2_stdout: p2_output.stdout,
-                    phase3_outcome: None,
-                });
+                return Ok(None);
             };
             let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
 
             info!(
-                task_index = task.index,
+                task_index,
                 position, task_count,
                 "phase 3: running custom stet command"
             );
 
             let stet_result = run_custom_stet_run_with_policy(
                 last_cmd,
-                &config.repo_path,
+                config,
                 timeout,
-                config.on_stet_fail.as_str(),
-                task.index,
+                task_index,
                 peal_state,
                 state_dir,
+                saves,
             )?;
 
             let stet_result = match stet_result {
                 Some(r) => r,
                 None => {
-                    info!(task_index = task.index, "phase 3 skipped (custom stet run failed)");
-                    peal_state.mark_task_completed(task.index);
-                    state::save_state(peal_state, state_dir)?;
-                    return Ok(TaskResult {
-                        task_index: task.index,
-                        plan_text: p1_output.stdout,
-                        phase2_stdout: p2_output.stdout,
-                        phase3_outcome: None,
-                    });
+                    info!(task_index, "phase 3 skipped (custom stet run failed)");
+                    return Ok(None);
                 }
             };
 
             if stet_result.has_findings {
-                info!(task_index = task.index, "phase 3: findings detected, starting address loop (custom)");
+                info!(task_index, "phase 3: findings detected, starting address loop (custom)");
 
-                let last_cmd = last_cmd.clone();
+                let last_cmd = stet::expand_command(last_cmd, config, Some(task_index));
                 let repo_path = config.repo_path.clone();
+                let shell = config.command_shell.clone();
                 let outcome = match stet::address_loop_custom(
                     agent_path,
                     config,
-                    task.index,
+                    task_index,
                     &stet_result,
-                    || stet::run_review_via_command(&last_cmd, &repo_path, timeout),
+                    || stet::run_review_via_command(&last_cmd, &shell, &repo_path, timeout),
                 ) {
                     Ok(o) => o,
                     Err(e) => {
-                        if config.on_stet_fail == "retry_once" {
-                            warn!(task_index = task.index, err = %e, "address loop (custom) failed, retrying once");
+                        if config.on_stet_fail == "retry_once" && !e.is_agent_failure() {
+                            warn!(task_index, err = %e, "address loop (custom) failed, retrying once");
+                            retries::record(RetryKind::Stet);
                             match stet::address_loop_custom(
                                 agent_path,
                                 config,
-                                task.index,
+                                task_index,
                                 &stet_result,
-                                || stet::run_review_via_command(&last_cmd, &repo_path, timeout),
+                                || stet::run_review_via_command(&last_cmd, &shell, &repo_path, timeout),
                             ) {
                                 Ok(o) => o,
                                 Err(e2) => {
-                                    error!(task_index = task.index, err = %e2, "address loop (custom) failed after retry");
-                                    if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                                    error!(task_index, err = %e2, "address loop (custom) failed after retry");
+                                    if let Err(save_err) = saves.save(peal_state, state_dir) {
                                         error!(err = %save_err, "failed to save state after address failure");
                                     }
                                     return Err(e2);
                                 }
                             }
-                        } else if config.on_stet_fail == "skip" {
-                            warn!(task_index = task.index, err = %e, "stet phase skipped");
+                        } else if config.on_stet_fail == "skip" && !e.is_agent_failure() {
+                            warn!(task_index, err = %e, "stet phase skipped");
                             stet::AddressLoopOutcome {
                                 rounds_used: 0,
                                 findings_resolved: false,
                                 last_stet_result: stet_result.clone(),
                             }
                         } else {
-                            error!(task_index = task.index, err = %e, "address loop (custom) failed");
-                            if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                            error!(task_index, err = %e, "address loop (custom) failed");
+                            if let Err(save_err) = saves.save(peal_state, state_dir) {
                                 error!(err = %save_err, "failed to save state after address failure");
                             }
                             return Err(e);
@@ -653,14 +1079,14 @@ fn run_single_task(
                 };
 
                 info!(
-                    task_index = task.index,
+                    task_index,
                     rounds = outcome.rounds_used,
                     resolved = outcome.findings_resolved,
                     "phase 3 complete (custom)"
                 );
                 Some(outcome)
             } else {
-                info!(task_index = task.index, "phase 3: no findings, skipping address loop");
+                info!(task_index, "phase 3: no findings, skipping address loop");
                 Some(stet::AddressLoopOutcome {
                     rounds_used: 0,
                     findings_resolved: true,
@@ -668,43 +1094,343 @@ fn run_single_task(
                 })
             }
         }
-        }
     };
 
-    peal_state.mark_task_completed(task.index);
-    state::save_state(peal_state, state_dir)?;
+    Ok(phase3_outcome)
+}
 
-    Ok(TaskResult {
-        task_index: task.index,
-        plan_text: p1_output.stdout,
-        phase2_stdout: p2_output.stdout,
-        phase3_outcome,
-    })
+/// Body of the follow-up task queued for `source_task` by `on_findings_remaining = "create_task"`.
+fn follow_up_content(source_task: u32, outcome: &stet::AddressLoopOutcome) -> String {
+    format!(
+        "Address remaining review findings for task {source_task}.\n\n\
+         The stet review below still reported findings after {} address round(s). \
+         Fix them and run the tests.\n\n```json\n{}\n```",
+        outcome.rounds_used,
+        outcome.last_stet_result.stdout.trim()
+    )
+}
+
+/// With `on_findings_remaining = "create_task"`, queues a follow-up task (persisted in
+/// state, run after the plan's tasks) when `result` ended with findings remaining.
+/// Follow-up tasks never queue further follow-ups.
+fn queue_follow_up_if_needed(
+    config: &PealConfig,
+    plan: &ParsedPlan,
+    peal_state: &mut PealState,
+    store: &mut StateStore,
+    result: &TaskResult,
+) -> Result<(), PealError> {
+    if config.on_findings_remaining != "create_task" {
+        return Ok(());
+    }
+    let Some(outcome) = result.phase3_outcome.as_ref().filter(|o| !o.findings_resolved) else {
+        return Ok(());
+    };
+    if peal_state.is_follow_up(result.task_index) {
+        warn!(
+            task_index = result.task_index,
+            "findings remain in a follow-up task; not queueing another"
+        );
+        return Ok(());
+    }
+    let next_index = peal_state
+        .follow_up_tasks
+        .iter()
+        .map(|f| f.index)
+        .chain(peal_state.completed_task_indices.iter().copied())
+        .fold(plan.max_task_index, u32::max)
+        + 1;
+    let index = peal_state.queue_follow_up(
+        result.task_index,
+        next_index,
+        follow_up_content(result.task_index, outcome),
+    );
+    info!(
+        task_index = result.task_index,
+        follow_up_index = index,
+        "findings remain; queued follow-up task"
+    );
+    store.save(peal_state)
+}
+
+/// Stage of a segment (all tasks of a segment share one; see `plan::compute_segments`).
+fn segment_stage<'a>(plan: &'a ParsedPlan, segment: &crate::plan::Segment) -> Option<&'a str> {
+    let first = match segment {
+        crate::plan::Segment::Sequential(idx) => *idx,
+        crate::plan::Segment::Parallel(indices) => *indices.first()?,
+    };
+    plan.task_by_index(first)?.stage.as_deref()
+}
+
+/// Run the `stage_commands` barrier for a finished stage in the repo, with `{stage}` expanded
+/// to its name. The first failing (or unspawnable) command fails the run.
+fn run_stage_commands(config: &PealConfig, stage: &str) -> Result<(), PealError> {
+    info!(stage, count = config.stage_commands.len(), "stage finished; running stage commands");
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    for template in &config.stage_commands {
+        let command = template.replace("{stage}", stage);
+        let failed = |detail: String| PealError::StageCommandFailed {
+            stage: stage.to_owned(),
+            command: command.trim().to_owned(),
+            detail,
+        };
+        match crate::subprocess::run_command_string_with_shell(
+            &command,
+            &config.command_shell,
+            &config.repo_path,
+            timeout,
+        ) {
+            None => {}
+            Some(Ok(result)) if result.success() => {
+                info!(stage, command = %command.trim(), "stage command succeeded");
+            }
+            Some(Ok(result)) if result.timed_out => {
+                return Err(failed(format!("timed out after {}s", config.phase_timeout_sec)));
+            }
+            Some(Ok(result)) => {
+                let stderr = result.stderr.trim();
+                let detail = match result.exit_code {
+                    Some(code) => format!("exit code {code}"),
+                    None => "killed by signal".to_owned(),
+                };
+                return Err(failed(if stderr.is_empty() { detail } else { format!("{detail}: {stderr}") }));
+            }
+            Some(Err(e)) => return Err(failed(e.to_string())),
+        }
+    }
+    Ok(())
+}
+
+/// Run a task's Phase 2 hook commands (`pre_phase2_commands` or `post_phase2_commands`, named by
+/// `hook`) in the working directory, in order, with `{{TASK_INDEX}}` replaced by the task index
+/// and `phase_timeout_sec` as the timeout. A failing command fails the task with
+/// `PealError::PhaseHookFailed`, or is logged and skipped when `on_phase_hook_fail` is "warn".
+fn run_phase_hooks(
+    config: &PealConfig,
+    hook: &'static str,
+    commands: &[String],
+    task_index: u32,
+) -> Result<(), PealError> {
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    let work_dir = config.work_dir();
+    for template in commands {
+        let command = template.replace("{{TASK_INDEX}}", &task_index.to_string());
+        let detail = match crate::subprocess::run_command_string_with_shell(
+            &command,
+            &config.command_shell,
+            &work_dir,
+            timeout,
+        ) {
+            None => continue,
+            Some(Ok(result)) if result.success() => {
+                info!(task_index, hook, command = %command.trim(), "phase hook succeeded");
+                continue;
+            }
+            Some(Ok(result)) if result.timed_out => {
+                format!("timed out after {}s", config.phase_timeout_sec)
+            }
+            Some(Ok(result)) => {
+                let stderr = result.stderr.trim();
+                let detail = match result.exit_code {
+                    Some(code) => format!("exit code {code}"),
+                    None => "killed by signal".to_owned(),
+                };
+                if stderr.is_empty() { detail } else { format!("{detail}: {stderr}") }
+            }
+            Some(Err(e)) => e.to_string(),
+        };
+        if config.on_phase_hook_fail == "warn" {
+            warn!(task_index, hook, command = %command.trim(), detail = %detail, "phase hook failed; continuing (on_phase_hook_fail=warn)");
+            continue;
+        }
+        return Err(PealError::PhaseHookFailed {
+            task_index,
+            hook,
+            command: command.trim().to_owned(),
+            detail,
+        });
+    }
+    Ok(())
+}
+
+/// Run a task's `peal-verify` commands in the repo after Phase 2, in order. When one fails,
+/// the agent is asked to fix it with the command's output (`phase::run_verify_fix`) and the
+/// command is re-run, up to `max_address_rounds` times; a command still failing then (or one
+/// that cannot be spawned) fails the task with `PealError::TaskVerifyFailed`. The time spent
+/// counts as Phase 2.
+fn run_task_verify(
+    agent_path: &Path,
+    config: &PealConfig,
+    task: &crate::plan::Task,
+) -> Result<(), PealError> {
+    if task.verify.is_empty() {
+        return Ok(());
+    }
+    let start = Instant::now();
+    let result = run_task_verify_commands(agent_path, config, task);
+    timing::record(2, start.elapsed());
+    result
+}
+
+fn run_task_verify_commands(
+    agent_path: &Path,
+    config: &PealConfig,
+    task: &crate::plan::Task,
+) -> Result<(), PealError> {
+    let task_index = task.index;
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    for command in &task.verify {
+        let mut round = 0;
+        loop {
+            let failed = |rounds: u32, detail: String| PealError::TaskVerifyFailed {
+                task_index,
+                command: command.clone(),
+                rounds,
+                detail,
+            };
+            let result = match crate::container::run_command_string(config, command, &config.work_dir(), timeout) {
+                None => break,
+                Some(Ok(result)) => result,
+                Some(Err(e)) => return Err(failed(round, e.to_string())),
+            };
+            if result.success() {
+                info!(task_index, command = %command, rounds = round, "verify command passed");
+                break;
+            }
+            let detail = if result.timed_out {
+                format!("timed out after {}s", config.phase_timeout_sec)
+            } else {
+                let stderr = phase::stderr_tail(result.stderr.trim(), phase::STDERR_EXCERPT_BYTES);
+                let detail = match result.exit_code {
+                    Some(code) => format!("exit code {code}"),
+                    None => "killed by signal".to_owned(),
+                };
+                if stderr.is_empty() { detail } else { format!("{detail}: {stderr}") }
+            };
+            if round >= config.max_address_rounds {
+                error!(task_index, command = %command, rounds = round, detail = %detail, "verify command failed");
+                return Err(failed(round, detail));
+            }
+            round += 1;
+            warn!(
+                task_index,
+                command = %command,
+                round,
+                max_rounds = config.max_address_rounds,
+                detail = %detail,
+                "verify command failed; asking agent to fix"
+            );
+            let output = format!("{}\n{}", result.stdout.trim(), result.stderr.trim());
+            phase::run_verify_fix(agent_path, config, task_index, command, output.trim())?;
+        }
+    }
+    Ok(())
+}
+
+/// Check a task's `expect:` lines once its work is done (see [`crate::expect`]). The first
+/// that fails fails the task with `PealError::TaskExpectationFailed`, unless
+/// `on_expect_fail = "warn"`, which logs each failure and goes on.
+fn check_task_expectations(config: &PealConfig, task: &crate::plan::Task) -> Result<(), PealError> {
+    let task_index = task.index;
+    for expectation in &task.expect {
+        match expectation.check(config) {
+            Ok(()) => info!(task_index, expectation = %expectation, "expectation met"),
+            Err(detail) if config.on_expect_fail == "warn" => {
+                warn!(task_index, expectation = %expectation, detail = %detail, "expectation failed");
+            }
+            Err(detail) => {
+                error!(task_index, expectation = %expectation, detail = %detail, "expectation failed");
+                return Err(PealError::TaskExpectationFailed {
+                    task_index,
+                    expectation: expectation.to_string(),
+                    detail,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Tracing span covering one task (exported as an OTLP span when `otel_endpoint` is set).
+fn task_span(task_index: u32) -> Span {
+    debug_span!("task", task_index)
+}
+
+/// Runs `attempt` with `config`, then once per `escalation_models` entry (in order, with
+/// that model) while the attempt fails with an escalatable error or `unresolved` holds.
+/// Returns the final result and the escalation model it came from, if any; the last
+/// attempt's outcome stands when every model has been tried.
+fn with_escalation<T>(
+    config: &PealConfig,
+    task_index: u32,
+    phase: u32,
+    unresolved: impl Fn(&T) -> bool,
+    mut attempt: impl FnMut(&PealConfig) -> Result<T, PealError>,
+) -> Result<(T, Option<String>), PealError> {
+    let mut result = attempt(config);
+    let mut used: Option<String> = None;
+    for model in &config.escalation_models {
+        match &result {
+            Ok(value) if !unresolved(value) => break,
+            Err(e) if !e.is_escalatable() => break,
+            Ok(_) => warn!(task_index, phase, model = %model, "findings remain, escalating model"),
+            Err(e) => warn!(task_index, phase, model = %model, err = %e, "phase failed, escalating model"),
+        }
+        let escalated = PealConfig {
+            model: Some(model.clone()),
+            ..config.clone()
+        };
+        result = attempt(&escalated);
+        used = Some(model.clone());
+    }
+    result.map(|value| (value, used))
 }
 
 /// Run Phase 1 → Phase 2 for a single task with no state mutation and no Phase 3.
 /// Each scoped thread executes this; the main thread handles state and Phase 3 after join.
+/// The returned model is the escalation model the last escalated phase succeeded with.
 fn run_phases_1_2(
     agent_path: &Path,
     config: &PealConfig,
     task: &crate::plan::Task,
     task_count: usize,
     position: usize,
-) -> Result<(String, String), PealError> {
+) -> Result<(String, String, Option<String>), PealError> {
+    let (plan_text, p1_model) = with_escalation(config, task.index, 1, |_| false, |c| {
+        run_phase1_validated(agent_path, c, task, task_count, position)
+    })?;
+    let (phase2_stdout, p2_model) = with_escalation(config, task.index, 2, |_| false, |c| {
+        let stdout = run_phase2_logged(agent_path, c, task, &plan_text, task_count, position)?;
+        run_task_verify(agent_path, c, task)?;
+        Ok(stdout)
+    })?;
+    Ok((plan_text, phase2_stdout, p2_model.or(p1_model)))
+}
+
+/// Phase 1 for a single task, retried once when plan text validation fails.
+/// Returns the plan text. No state mutation.
+fn run_phase1_validated(
+    agent_path: &Path,
+    config: &PealConfig,
+    task: &crate::plan::Task,
+    task_count: usize,
+    position: usize,
+) -> Result<String, PealError> {
     info!(
         task_index = task.index,
         position, task_count, "phase 1: task {position}/{task_count}"
     );
 
-    let p1_start = Instant::now();
-    let mut p1_output =
-        phase::run_phase1(agent_path, config, task.index, &task.content).map_err(|e| {
-            error!(
-                task_index = task.index,
-                position, task_count, err = %e, "phase 1 failed"
-            );
-            e
-        })?;
+    let p1_start = clock::Stamp::now();
+    let p1_result = phase::run_phase1(agent_path, config, task.index, &task.content, &task.context);
+    clock::check_phase(task.index, 1, &p1_start);
+    let mut p1_output = p1_result.map_err(|e| {
+        error!(
+            task_index = task.index,
+            position, task_count, err = %e, "phase 1 failed"
+        );
+        e
+    })?;
     let p1_duration = p1_start.elapsed();
 
     info!(
@@ -721,7 +1447,8 @@ fn run_phases_1_2(
             position, task_count, err = %e,
             "plan text validation failed, retrying phase 1 once"
         );
-        p1_output = phase::run_phase1(agent_path, config, task.index, &task.content).map_err(
+        retries::record(RetryKind::Phase(1));
+        p1_output = phase::run_phase1(agent_path, config, task.index, &task.content, &task.context).map_err(
             |e| {
                 error!(
                     task_index = task.index,
@@ -732,32 +1459,76 @@ fn run_phases_1_2(
         )?;
         validate_plan_text(config, task.index, &p1_output.stdout)?;
     }
+    timing::record(1, p1_start.elapsed());
+
+    Ok(p1_output.stdout)
+}
+
+/// Phase 2 for a single task from the given plan text. Returns the agent's answer
+/// (see [`phase2_text`]). No state mutation.
+fn run_phase2_logged(
+    agent_path: &Path,
+    config: &PealConfig,
+    task: &crate::plan::Task,
+    plan_text: &str,
+    task_count: usize,
+    position: usize,
+) -> Result<String, PealError> {
+    run_phase2_raw(agent_path, config, task, plan_text, task_count, position).map(phase2_text)
+}
 
+/// Phase 2 for a single task from the given plan text. Returns raw Phase 2 stdout.
+/// No state mutation.
+fn run_phase2_raw(
+    agent_path: &Path,
+    config: &PealConfig,
+    task: &crate::plan::Task,
+    plan_text: &str,
+    task_count: usize,
+    position: usize,
+) -> Result<String, PealError> {
+    let task_index = task.index;
     info!(
-        task_index = task.index,
+        task_index,
         position, task_count, "phase 2: task {position}/{task_count}"
     );
 
-    let p2_start = Instant::now();
-    let p2_output =
-        phase::run_phase2(agent_path, config, task.index, &p1_output.stdout).map_err(|e| {
-            error!(
-                task_index = task.index,
-                position, task_count, err = %e, "phase 2 failed"
-            );
-            e
-        })?;
+    run_phase_hooks(config, "pre_phase2_commands", &config.pre_phase2_commands, task_index)?;
+
+    let p2_start = clock::Stamp::now();
+    let p2_result = sparse::run_in_sparse_worktree(agent_path, config, task, |c| {
+        phase::run_phase2(agent_path, c, task_index, plan_text, &task.context)
+    });
+    clock::check_phase(task_index, 2, &p2_start);
+    let p2_output = p2_result.map_err(|e| {
+        error!(
+            task_index,
+            position, task_count, err = %e, "phase 2 failed"
+        );
+        e
+    })?;
     let p2_duration = p2_start.elapsed();
+    timing::record(2, p2_duration);
 
     info!(
-        task_index = task.index,
+        task_index,
         position, task_count,
         duration_ms = p2_duration.as_millis() as u64,
         stdout_len = p2_output.stdout.len(),
         "phase 2 complete"
     );
 
-    Ok((p1_output.stdout, p2_output.stdout))
+    run_phase_hooks(config, "post_phase2_commands", &config.post_phase2_commands, task_index)?;
+
+    Ok(p2_output.stdout)
+}
+
+/// The agent's answer from Phase 2 stdout: the `result` text of a `stream-json` transcript
+/// (see `phase2_change_check`), or the stdout itself.
+fn phase2_text(stdout: String) -> String {
+    phase::parse_phase2_transcript(&stdout)
+        .and_then(|t| t.result_text)
+        .unwrap_or(stdout)
 }
 
 /// Run Phase 1 → Phase 2 concurrently for a batch of pending tasks.
@@ -765,10 +1536,11 @@ fn run_phases_1_2(
 /// One execution stream per task (Phase 1 → Phase 2); streams are joined before
 /// the caller runs Phase 3; no shared mutable state.
 ///
-/// Tasks are chunked into groups of `max_concurrent`; within each chunk,
-/// scoped threads run one task each. After all threads in a chunk join,
+/// Tasks are chunked into groups of `max_concurrent` (ordered by `(after: N)` hints, see
+/// `ParsedPlan::soft_dependency_waves`); within each chunk, scoped threads run one task each. After all threads in a chunk join,
 /// results are partitioned into successes and failures. Processing stops
 /// after the first chunk that contains any failure.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 fn run_parallel_block(
     agent_path: &Path,
     config: &PealConfig,
@@ -777,12 +1549,14 @@ fn run_parallel_block(
     task_count: usize,
     base_position: usize,
     max_concurrent: usize,
-) -> (Vec<(u32, String, String)>, Vec<(u32, PealError)>) {
-    let mut successes: Vec<(u32, String, String)> = Vec::new();
+    task_spans: &HashMap<u32, Span>,
+) -> (Vec<(u32, String, String, Option<String>)>, Vec<(u32, PealError)>, Vec<(u32, TaskWork)>) {
+    let mut successes: Vec<(u32, String, String, Option<String>)> = Vec::new();
     let mut failures: Vec<(u32, PealError)> = Vec::new();
+    let mut block_work: Vec<(u32, TaskWork)> = Vec::new();
     let mut offset = 0;
 
-    for chunk in pending.chunks(max_concurrent) {
+    for chunk in plan.soft_dependency_waves(pending, max_concurrent) {
         std::thread::scope(|s| {
             let handles: Vec<_> = chunk
                 .iter()
@@ -793,15 +1567,22 @@ fn run_parallel_block(
                         .task_by_index(idx)
                         .expect("task index validated before parallel block");
                     s.spawn(move || {
-                        run_phases_1_2(agent_path, config, task, task_count, position)
-                            .map(|(plan_text, p2_stdout)| (idx, plan_text, p2_stdout))
-                            .map_err(|e| (idx, e))
+                        let _entered = task_spans.get(&idx).map(Span::enter);
+                        let (result, work) = track_task(|| {
+                            with_task_locks(task, || run_phases_1_2(agent_path, config, task, task_count, position))
+                        });
+                        let result = result
+                            .map(|(plan_text, p2_stdout, model)| (idx, plan_text, p2_stdout, model))
+                            .map_err(|e| (idx, e));
+                        (result, (idx, work))
                     })
                 })
                 .collect();
 
             for handle in handles {
-                match handle.join().expect("scoped thread must not panic") {
+                let (result, work) = handle.join().expect("scoped thread must not panic");
+                block_work.push(work);
+                match result {
                     Ok(success) => successes.push(success),
                     Err(failure) => failures.push(failure),
                 }
@@ -815,7 +1596,186 @@ fn run_parallel_block(
         }
     }
 
-    (successes, failures)
+    (successes, failures, block_work)
+}
+
+/// Named mutexes behind `(lock: name)` heading suffixes, created on first use and shared by
+/// every task of the process.
+static TASK_LOCKS: Mutex<BTreeMap<String, Arc<Mutex<()>>>> = Mutex::new(BTreeMap::new());
+
+/// Run `f` holding every named lock of `task`, so tasks sharing a lock name never overlap
+/// inside a parallel block. Locks are taken in name order, so two tasks with several locks in
+/// common cannot deadlock; waiting for one is logged.
+fn with_task_locks<R>(task: &Task, f: impl FnOnce() -> R) -> R {
+    let mut names: Vec<&String> = task.locks.iter().collect();
+    names.sort();
+    let mutexes: Vec<(&String, Arc<Mutex<()>>)> = {
+        let mut registry = TASK_LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+        names
+            .into_iter()
+            .map(|name| (name, Arc::clone(registry.entry(name.clone()).or_default())))
+            .collect()
+    };
+    let _guards: Vec<_> = mutexes
+        .iter()
+        .map(|(name, mutex)| match mutex.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                info!(task_index = task.index, lock = %name, "waiting for task lock");
+                mutex.lock().unwrap_or_else(PoisonError::into_inner)
+            }
+        })
+        .collect();
+    f()
+}
+
+/// Phase 3 result of one task, with the retries and time it spent.
+type Phase3Run = (Result<Option<stet::AddressLoopOutcome>, PealError>, TaskWork);
+
+/// Run Phase 3 concurrently for the tasks of a parallel block (`parallel_phase3`).
+///
+/// `tasks` are `(task index, position)` pairs, run in chunks of `max_concurrent` on scoped
+/// threads. Agent calls of different tasks overlap; stet invocations take turns (see
+/// `stet::stet_turn`). Each thread works on its own copy of `peal_state` and does not save it;
+/// escalated models and a restarted stet session are merged back, and state saved, once the
+/// threads of a chunk join.
+#[allow(clippy::too_many_arguments)]
+fn run_parallel_phase3(
+    agent_path: &Path,
+    config: &PealConfig,
+    plan: &ParsedPlan,
+    tasks: &[(u32, usize)],
+    peal_state: &mut PealState,
+    state_dir: &Path,
+    store: &mut StateStore,
+    mode: &stet::StetPhase3Mode,
+    task_count: usize,
+    max_concurrent: usize,
+    block_base: Option<&str>,
+    task_spans: &HashMap<u32, Span>,
+) -> HashMap<u32, Phase3Run> {
+    let mut done: HashMap<u32, Phase3Run> = HashMap::new();
+
+    for chunk in tasks.chunks(max_concurrent) {
+        let snapshot = peal_state.clone();
+        let joined: Vec<(u32, Phase3Run, PealState)> = std::thread::scope(|s| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|&(idx, position)| {
+                    let mut local_state = snapshot.clone();
+                    let task = plan.task_by_index(idx).expect("task index validated before parallel block");
+                    s.spawn(move || {
+                        let _entered = task_spans.get(&idx).map(Span::enter);
+                        let run = track_task(|| {
+                            with_task_locks(task, || {
+                                run_task_phase3(
+                                    agent_path, config, idx, &mut local_state, state_dir, Phase3Saves::Deferred,
+                                    mode, task_count, position, block_base,
+                                )
+                            })
+                        });
+                        (idx, run, local_state)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("scoped thread must not panic"))
+                .collect()
+        });
+
+        // Phase 3 changes the escalated model of its own task and, when it restarted the stet
+        // session, the session; everything else in the copies is the snapshot.
+        for (idx, run, local_state) in joined {
+            if let Some(model) = local_state.escalated_models.get(&idx) {
+                peal_state.record_escalated_model(idx, model);
+            }
+            if local_state.stet_session != snapshot.stet_session {
+                peal_state.stet_session = local_state.stet_session;
+            }
+            done.insert(idx, run);
+        }
+        if let Err(e) = store.save(peal_state) {
+            warn!(err = %e, "failed to save state after parallel phase 3");
+        }
+    }
+    done
+}
+
+/// Retries and phase time spent by one unit of task work (see [`track_task`]).
+#[derive(Debug, Clone, Copy, Default)]
+struct TaskWork {
+    retries: RetryCounts,
+    durations: PhaseDurations,
+}
+
+/// Run `f`, collecting the retries ([`retries::track`]) and phase time ([`timing::track`])
+/// recorded on this thread while it ran.
+fn track_task<R>(f: impl FnOnce() -> R) -> (R, TaskWork) {
+    let ((result, retries), durations) = timing::track(|| retries::track(f));
+    (result, TaskWork { retries, durations })
+}
+
+/// Adds one task's retries to this run's tally and to state, and its phase time to state
+/// along with a fresh ETA for the plan's remaining tasks. State is saved through `store`
+/// (best-effort), like the task completion that usually follows.
+fn note_task_work(
+    run_retries: &mut BTreeMap<u32, RetryCounts>,
+    plan: &ParsedPlan,
+    peal_state: &mut PealState,
+    store: &mut StateStore,
+    task_index: u32,
+    work: TaskWork,
+) {
+    let counts = work.retries;
+    let timed = work.durations.total_ms() > 0;
+    if counts.total() == 0 && !timed {
+        return;
+    }
+    if counts.total() > 0 {
+        run_retries.entry(task_index).or_default().add(counts);
+        peal_state.record_retries(task_index, counts);
+        info!(
+            task_index,
+            retries = counts.total(),
+            phase1 = counts.phase1,
+            phase2 = counts.phase2,
+            phase3 = counts.phase3,
+            stet = counts.stet,
+            "task retries"
+        );
+    }
+    if timed {
+        peal_state.record_phase_durations(task_index, work.durations);
+        let remaining_tasks = plan
+            .tasks
+            .iter()
+            .filter(|t| !peal_state.is_task_completed(t.index))
+            .count();
+        if let Some(eta) = timing::eta(&peal_state.phase_durations, remaining_tasks) {
+            info!(remaining_tasks, eta_sec = eta.as_secs(), "ETA");
+            peal_state.eta = Some(timing::Eta {
+                remaining_tasks,
+                remaining_sec: eta.as_secs(),
+                estimated_at: chrono::Utc::now().to_rfc3339(),
+            });
+        }
+    }
+    if let Err(e) = store.save(peal_state) {
+        warn!(err = %e, "failed to save state after recording task work");
+    }
+}
+
+/// Escalation models recorded in state for the tasks that finished in this run.
+fn escalated_models_for(peal_state: &PealState, results: &[TaskResult]) -> BTreeMap<u32, String> {
+    results
+        .iter()
+        .filter_map(|r| {
+            let model = peal_state.escalated_models.get(&r.task_index)?;
+            Some((r.task_index, model.clone()))
+        })
+        .collect()
 }
 
 /// If consecutive_failures >= cap, saves state (best-effort) and returns ConsecutiveTaskFailuresCapReached.
@@ -824,10 +1784,10 @@ fn check_consecutive_cap(
     consecutive_failures: u32,
     cap: u32,
     peal_state: &mut PealState,
-    state_dir: &Path,
+    store: &mut StateStore,
 ) -> Result<(), PealError> {
     if consecutive_failures >= cap {
-        let _ = state::save_state(peal_state, state_dir);
+        let _ = store.save_now(peal_state);
         return Err(PealError::ConsecutiveTaskFailuresCapReached {
             count: consecutive_failures,
             cap,
@@ -844,13 +1804,13 @@ fn apply_parallel_block_outcomes(
     consecutive_failures: &mut u32,
     cap: Option<u32>,
     peal_state: &mut PealState,
-    state_dir: &Path,
+    store: &mut StateStore,
 ) -> Result<(), PealError> {
     for &idx in indices {
         if block_failed.contains(&idx) {
             *consecutive_failures += 1;
             if let Some(cap_val) = cap {
-                check_consecutive_cap(*consecutive_failures, cap_val, peal_state, state_dir)?;
+                check_consecutive_cap(*consecutive_failures, cap_val, peal_state, store)?;
             }
         } else {
             *consecutive_failures = 0;
@@ -870,7 +1830,9 @@ fn apply_parallel_block_outcomes(
 ///   sequential execution when `config.parallel` is false or only one task
 ///   remains.
 ///
-/// State is persisted per-task (not per-segment) to enable fine-grained resume.
+/// State is persisted per-task (not per-segment) to enable fine-grained resume, through a
+/// [`StateStore`] that writes task completions at most every [`state::STATE_SAVE_INTERVAL`];
+/// failures write at once, and the store is flushed when the run ends, whichever way it ends.
 pub fn run_scheduled(
     agent_path: &Path,
     config: &PealConfig,
@@ -878,6 +1840,24 @@ pub fn run_scheduled(
     peal_state: &mut PealState,
     state_dir: &Path,
     phase3_mode: Option<stet::StetPhase3Mode>,
+) -> Result<RunOutcome, PealError> {
+    let mut store = StateStore::new(state_dir);
+    let outcome = run_segments(agent_path, config, plan, peal_state, state_dir, &mut store, phase3_mode);
+    let flushed = store.flush(peal_state);
+    let outcome = outcome?;
+    flushed?;
+    Ok(outcome)
+}
+
+/// The body of [`run_scheduled`], with its state store.
+fn run_segments(
+    agent_path: &Path,
+    config: &PealConfig,
+    plan: &ParsedPlan,
+    peal_state: &mut PealState,
+    state_dir: &Path,
+    store: &mut StateStore,
+    phase3_mode: Option<stet::StetPhase3Mode>,
 ) -> Result<RunOutcome, PealError> {
     let task_count = plan.tasks.len();
     let phase3_available = phase3_mode.is_some();
@@ -891,11 +1871,42 @@ pub fn run_scheduled(
 
     let mut results: Vec<TaskResult> = Vec::with_capacity(task_count);
     let mut failed_task_indices: Vec<u32> = Vec::new();
+    let mut failure_details: BTreeMap<u32, TaskFailure> = BTreeMap::new();
     let mut position: usize = 0;
     let mut consecutive_failures: u32 = 0;
     let cap = config.max_consecutive_task_failures;
+    let mut run_retries: BTreeMap<u32, RetryCounts> = BTreeMap::new();
+    // Tasks whose Phase 1/2 failed in a concurrent parallel block (`retry_failed_parallel_tasks`).
+    let mut parallel_failed: Vec<u32> = Vec::new();
+
+    // Stage barriers run only for stages with work left at the start of this run.
+    let gated_stages: HashSet<&str> = if config.stage_commands.is_empty() {
+        HashSet::new()
+    } else {
+        plan.tasks
+            .iter()
+            .filter(|t| !peal_state.is_task_completed(t.index))
+            .filter_map(|t| t.stage.as_deref())
+            .collect()
+    };
+    let mut current_stage: Option<&str> = None;
 
     for segment in schedule {
+        if crate::daemon::stop_requested(state_dir) {
+            return Ok(stopped_outcome(peal_state, results, failed_task_indices, failure_details, run_retries));
+        }
+        let stage = segment_stage(plan, segment);
+        let completed_before = peal_state.completed_task_indices.len();
+        if stage != current_stage {
+            if let Some(done) = current_stage.filter(|s| gated_stages.contains(s)) {
+                run_stage_commands(config, done)?;
+            }
+            if let Some(stage) = stage {
+                info!(stage, "starting stage");
+            }
+            current_stage = stage;
+        }
+
         match segment {
             crate::plan::Segment::Sequential(idx) => {
                 let idx = *idx;
@@ -916,13 +1927,18 @@ pub fn run_scheduled(
                     }
                 })?;
 
-                let result = run_single_task(
-                    agent_path, config, task, peal_state, state_dir, phase3_mode.as_ref(),
-                    task_count, position,
-                );
+                let checkpoint = task_checkpoint(config, idx);
+                let (result, work) = track_task(|| {
+                    run_single_task(
+                        agent_path, config, task, peal_state, state_dir, store, phase3_mode.as_ref(),
+                        task_count, position,
+                    )
+                });
+                note_task_work(&mut run_retries, plan, peal_state, store, idx, work);
                 match result {
                     Ok(r) => {
                         consecutive_failures = 0;
+                        queue_follow_up_if_needed(config, plan, peal_state, store, &r)?;
                         results.push(r);
                     }
                     Err(e) => {
@@ -932,15 +1948,19 @@ pub fn run_scheduled(
                                 consecutive_failures,
                                 cap_val,
                                 peal_state,
-                                state_dir,
+                                store,
                             )?;
                         }
-                        if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                        if let Err(save_err) = store.save_now(peal_state) {
                             error!(err = %save_err, "failed to save state after task failure");
                         }
-                        if config.continue_with_remaining_tasks {
+                        if config.continue_with_remaining_tasks && !e.aborts_run() {
                             failed_task_indices.push(idx);
+                            failure_details.insert(idx, TaskFailure::new(idx, &e));
                             warn!(task_index = idx, err = %e, "task failed, continuing with remaining tasks");
+                            if let Some(checkpoint) = &checkpoint {
+                                rollback_task(config, state_dir, idx, checkpoint);
+                            }
                             continue;
                         }
                         return Err(e);
@@ -976,17 +1996,35 @@ pub fn run_scheduled(
                         "parallel block: running concurrently"
                     );
 
-                    let (successes, failures) = run_parallel_block(
+                    // One span per task, created here so Phase 3 (below, on this thread)
+                    // lands in the same span as the worker thread's Phase 1 and 2.
+                    let task_spans: HashMap<u32, Span> =
+                        pending.iter().map(|&idx| (idx, task_span(idx))).collect();
+                    let block_base = scope_base(config, indices[0]);
+                    for idx in &pending {
+                        peal_state.escalated_models.remove(idx);
+                    }
+                    let (successes, mut failures, block_work) = run_parallel_block(
                         agent_path, config, plan, &pending,
-                        task_count, position, max_concurrent,
+                        task_count, position, max_concurrent, &task_spans,
                     );
+                    for (idx, work) in block_work {
+                        note_task_work(&mut run_retries, plan, peal_state, store, idx, work);
+                    }
 
                     // Persist all successful P1+P2 completions before Phase 3.
-                    for &(idx, _, _) in &successes {
-                        peal_state.mark_task_completed(idx);
+                    for (idx, plan_text, _, model) in &successes {
+                        peal_state.record_plan_text(*idx, plan_text);
+                        if let Some(model) = model {
+                            peal_state.record_escalated_model(*idx, model);
+                        }
+                        peal_state.mark_task_completed(*idx);
+                        if let Some(task) = plan.task_by_index(*idx) {
+                            peal_state.record_task_hash(*idx, &task.content);
+                        }
                     }
                     if !successes.is_empty() {
-                        state::save_state(peal_state, state_dir)?;
+                        store.save(peal_state)?;
                     }
 
                     if config.commit_after_phase2 && !successes.is_empty() {
@@ -996,7 +2034,7 @@ pub fn run_scheduled(
                             indices.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", ")
                         );
                         if let Err(e) =
-                            commit_after_phase2(&config.repo_path, first_idx, Some(&suffix))
+                            commit_after_phase2(&*vcs::for_config(config), &config.repo_path, first_idx, Some(&suffix))
                         {
                             error!(
                                 block_indices = ?indices,
@@ -1007,223 +2045,111 @@ pub fn run_scheduled(
                         }
                     }
 
-                    // Phase 3 sequentially in block task order (segment indices order).
+                    // Phase 3 in block task order (segment indices order); with `parallel_phase3`
+                    // it has already run for all tasks at once and results are taken in that order.
                     let mut successes_by_index: HashMap<u32, (String, String)> = successes
                         .into_iter()
-                        .map(|(idx, plan_text, phase2_stdout)| (idx, (plan_text, phase2_stdout)))
+                        .map(|(idx, plan_text, phase2_stdout, _)| (idx, (plan_text, phase2_stdout)))
                         .collect();
 
+                    // `phase3_scope = "block"`: one review of the whole block, under its last task.
+                    let block_review = block_review_task(config, indices, |idx| successes_by_index.contains_key(&idx));
+                    let concurrent_phase3 =
+                        config.parallel_phase3 && block_review.is_none() && successes_by_index.len() > 1;
+                    let mut phase3_runs: HashMap<u32, Phase3Run> = match phase3_mode.as_ref() {
+                        Some(mode) if concurrent_phase3 => {
+                            let phase3_tasks: Vec<(u32, usize)> = indices
+                                .iter()
+                                .filter(|idx| successes_by_index.contains_key(idx))
+                                .enumerate()
+                                .map(|(i, &idx)| (idx, position + i + 1))
+                                .collect();
+                            info!(
+                                task_count = phase3_tasks.len(),
+                                max_concurrent,
+                                "phase 3: running address loops concurrently"
+                            );
+                            run_parallel_phase3(
+                                agent_path, config, plan, &phase3_tasks, peal_state, state_dir, store, mode,
+                                task_count, max_concurrent, block_base.as_deref(), &task_spans,
+                            )
+                        }
+                        _ => HashMap::new(),
+                    };
+
                     let mut phase3_count = 0usize;
                     let mut phase3_continued_after_failure = false;
                     let mut block_p3_failures: Vec<u32> = Vec::new();
                     for idx in indices {
-                        let Some((plan_text, phase2_stdout)) = successes_by_index.remove(&idx) else {
+                        let Some((plan_text, phase2_stdout)) = successes_by_index.remove(idx) else {
                             continue;
                         };
                         phase3_count += 1;
                         position += 1;
-
-                        let phase3_result: Result<Option<stet::AddressLoopOutcome>, PealError> =
-                            (|| {
-                                match phase3_mode.as_ref() {
-                                    None => return Ok(None),
-                                    Some(stet::StetPhase3Mode::BuiltIn(stet_path)) => {
-                                        let sp = stet_path.as_path();
-                                        let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
-
-                                        info!(
-                                            task_index = idx,
-                                            position, task_count,
-                                            "phase 3: running stet review"
-                                        );
-
-                                        let stet_result = run_stet_review_with_policy(
-                                            sp,
-                                            &config.repo_path,
-                                            &config.stet_run_extra_args,
-                                            timeout,
-                                            config.on_stet_fail.as_str(),
-                                            *idx,
-                                            peal_state,
-                                            state_dir,
-                                        )?;
-
-                                        let stet_result = match stet_result {
-                                            Some(r) => r,
-                                            None => {
-                                                info!(task_index = idx, "phase 3 skipped (stet run failed)");
-                                                return Ok(None);
-                                            }
-                                        };
-
-                                        if stet_result.has_findings {
-                                            info!(task_index = idx, "phase 3: findings detected, starting address loop");
-
-                                            let outcome = match stet::address_loop(agent_path, sp, config, *idx, &stet_result) {
-                                                Ok(o) => o,
-                                                Err(e) => {
-                                                    if config.on_stet_fail == "retry_once" {
-                                                        warn!(task_index = idx, err = %e, "address loop failed, retrying once");
-                                                        match stet::address_loop(agent_path, sp, config, *idx, &stet_result) {
-                                                            Ok(o) => o,
-                                                            Err(e2) => {
-                                                                error!(task_index = idx, err = %e2, "address loop failed after retry");
-                                                                if let Err(save_err) = state::save_state(peal_state, state_dir) {
-                                                                    error!(err = %save_err, "failed to save state after address failure");
-                                                                }
-                                                                return Err(e2);
-                                                            }
-                                                        }
-                                                    } else if config.on_stet_fail == "skip" {
-                                                        warn!(task_index = idx, err = %e, "stet phase skipped");
-                                                        stet::AddressLoopOutcome {
-                                                            rounds_used: 0,
-                                                            findings_resolved: false,
-                                                            last_stet_result: stet_result.clone(),
-                                                        }
-                                                    } else {
-                                                        error!(task_index = idx, err = %e, "address loop failed");
-                                                        if let Err(save_err) = state::save_state(peal_state, state_dir) {
-                                                            error!(err = %save_err, "failed to save state after address failure");
-                                                        }
-                                                        return Err(e);
-                                                    }
-                                                }
-                                            };
-
-                                            info!(
-                                                task_index = idx,
-                                                rounds = outcome.rounds_used,
-                                                resolved = outcome.findings_resolved,
-                                                "phase 3 complete"
-                                            );
-                                            return Ok(Some(outcome));
-                                        }
-                                        info!(task_index = idx, "phase 3: no findings, skipping address loop");
-                                        return Ok(Some(stet::AddressLoopOutcome {
-                                            rounds_used: 0,
-                                            findings_resolved: true,
-                                            last_stet_result: stet_result,
-                                        }));
-                                    }
-                                    Some(stet::StetPhase3Mode::CustomCommands(commands)) => {
-                                        let Some(last_cmd) = commands.last() else {
-                                            return Ok(None);
-                                        };
-                                        let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
-
-                                        info!(
-                                            task_index = idx,
-                                            position, task_count,
-                                            "phase 3: running custom stet command"
-                                        );
-
-                                        let stet_result = run_custom_stet_run_with_policy(
-                                            last_cmd,
-                                            &config.repo_path,
-                                            timeout,
-                                            config.on_stet_fail.as_str(),
-                                            *idx,
-                                            peal_state,
-                                            state_dir,
-                                        )?;
-
-                                        let stet_result = match stet_result {
-                                            Some(r) => r,
-                                            None => {
-                                                info!(task_index = idx, "phase 3 skipped (custom stet run failed)");
-                                                return Ok(None);
-                                            }
-                                        };
-
-                                        if stet_result.has_findings {
-                                            info!(task_index = idx, "phase 3: findings detected, starting address loop (custom)");
-
-                                            let last_cmd = last_cmd.clone();
-                                            let repo_path = config.repo_path.clone();
-                                            let outcome = match stet::address_loop_custom(
-                                                agent_path,
-                                                config,
-                                                *idx,
-                                                &stet_result,
-                                                || stet::run_review_via_command(&last_cmd, &repo_path, timeout),
-                                            ) {
-                                                Ok(o) => o,
-                                                Err(e) => {
-                                                    if config.on_stet_fail == "retry_once" {
-                                                        warn!(task_index = idx, err = %e, "address loop (custom) failed, retrying once");
-                                                        match stet::address_loop_custom(
-                                                            agent_path,
-                                                            config,
-                                                            *idx,
-                                                            &stet_result,
-                                                            || stet::run_review_via_command(&last_cmd, &repo_path, timeout),
-                                                        ) {
-                                                            Ok(o) => o,
-                                                            Err(e2) => {
-                                                                error!(task_index = idx, err = %e2, "address loop (custom) failed after retry");
-                                                                if let Err(save_err) = state::save_state(peal_state, state_dir) {
-                                                                    error!(err = %save_err, "failed to save state after address failure");
-                                                                }
-                                                                return Err(e2);
-                                                            }
-                                                        }
-                                                    } else if config.on_stet_fail == "skip" {
-                                                        warn!(task_index = idx, err = %e, "stet phase skipped");
-                                                        stet::AddressLoopOutcome {
-                                                            rounds_used: 0,
-                                                            findings_resolved: false,
-                                                            last_stet_result: stet_result.clone(),
-                                                        }
-                                                    } else {
-                                                        error!(task_index = idx, err = %e, "address loop (custom) failed");
-                                                        if let Err(save_err) = state::save_state(peal_state, state_dir) {
-                                                            error!(err = %save_err, "failed to save state after address failure");
-                                                        }
-                                                        return Err(e);
-                                                    }
-                                                }
-                                            };
-
-                                            info!(
-                                                task_index = idx,
-                                                rounds = outcome.rounds_used,
-                                                resolved = outcome.findings_resolved,
-                                                "phase 3 complete (custom)"
-                                            );
-                                            return Ok(Some(outcome));
-                                        }
-                                        info!(task_index = idx, "phase 3: no findings, skipping address loop");
-                                        return Ok(Some(stet::AddressLoopOutcome {
-                                            rounds_used: 0,
-                                            findings_resolved: true,
-                                            last_stet_result: stet_result,
-                                        }));
-                                    }
-                                }
-                            })();
+                        let _entered = task_spans.get(idx).map(Span::enter);
+
+                        let (phase3_result, work): (
+                            Result<Option<stet::AddressLoopOutcome>, PealError>,
+                            TaskWork,
+                        ) = match phase3_runs.remove(idx) {
+                            Some(run) => run,
+                            None if block_review.is_some_and(|review| review != *idx) => {
+                                (Ok(None), TaskWork::default())
+                            }
+                            None => track_task(|| match phase3_mode.as_ref() {
+                                None => Ok(None),
+                                Some(mode) => run_task_phase3(
+                                    agent_path,
+                                    config,
+                                    *idx,
+                                    peal_state,
+                                    state_dir,
+                                    Phase3Saves::Now,
+                                    mode,
+                                    task_count,
+                                    position,
+                                    block_base.as_deref(),
+                                ),
+                            }),
+                        };
+                        note_task_work(&mut run_retries, plan, peal_state, store, *idx, work);
+                        let phase3_result = phase3_result.and_then(|outcome| {
+                            plan.task_by_index(*idx)
+                                .map_or(Ok(()), |task| check_task_expectations(config, task))
+                                .map(|()| outcome)
+                        });
 
                         match phase3_result {
                             Ok(phase3_outcome) => {
-                                results.push(TaskResult {
+                                let summary =
+                                    summarize_task(agent_path, config, *idx, &phase2_stdout);
+                                let r = TaskResult {
                                     task_index: *idx,
                                     plan_text,
                                     phase2_stdout,
                                     phase3_outcome,
-                                });
+                                    summary,
+                                };
+                                queue_follow_up_if_needed(config, plan, peal_state, store, &r)?;
+                                results.push(r);
                             }
                             Err(e) => {
-                                if config.continue_with_remaining_tasks {
+                                if config.continue_with_remaining_tasks && !e.aborts_run() {
                                     warn!(
                                         task_index = idx,
                                         err = %e,
                                         "phase 3 failed in parallel block; continuing with remaining segments"
                                     );
                                     failed_task_indices.push(*idx);
+                                    failure_details.insert(*idx, TaskFailure::new(*idx, &e));
                                     block_p3_failures.push(*idx);
-                                    if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                                    if let Err(save_err) = store.save_now(peal_state) {
                                         error!(err = %save_err, "failed to save state after phase 3 failure");
                                     }
+                                    // Concurrent Phase 3 already ran for the rest of the block.
+                                    if concurrent_phase3 {
+                                        continue;
+                                    }
                                     position += indices.len() - phase3_count;
                                     phase3_continued_after_failure = true;
                                     break;
@@ -1237,9 +2163,9 @@ pub fn run_scheduled(
                                         &mut consecutive_failures,
                                         cap,
                                         peal_state,
-                                        state_dir,
+                                        store,
                                     )?;
-                                    if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                                    if let Err(save_err) = store.save_now(peal_state) {
                                         error!(err = %save_err, "failed to save state after phase 3 failure");
                                     }
                                     return Err(e);
@@ -1260,7 +2186,7 @@ pub fn run_scheduled(
                         &mut consecutive_failures,
                         cap,
                         peal_state,
-                        state_dir,
+                        store,
                     )?;
 
                     let completed_in_block = indices.len() - pending.len();
@@ -1268,6 +2194,9 @@ pub fn run_scheduled(
                         position += completed_in_block + failures.len();
                     }
 
+                    if let Some(pos) = failures.iter().position(|(_, e)| e.aborts_run()) {
+                        return Err(failures.swap_remove(pos).1);
+                    }
                     if !failures.is_empty() {
                         for (fail_idx, err) in &failures {
                             warn!(
@@ -1277,12 +2206,14 @@ pub fn run_scheduled(
                             );
                             if config.continue_with_remaining_tasks {
                                 failed_task_indices.push(*fail_idx);
+                                failure_details.insert(*fail_idx, TaskFailure::new(*fail_idx, err));
+                                parallel_failed.push(*fail_idx);
                             }
                         }
-                        if !config.continue_with_remaining_tasks {
-                            if let Some((_idx, err)) = failures.into_iter().next() {
-                                return Err(err);
-                            }
+                        if !config.continue_with_remaining_tasks
+                            && let Some((_idx, err)) = failures.into_iter().next()
+                        {
+                            return Err(err);
                         }
                     }
                 } else {
@@ -1294,7 +2225,10 @@ pub fn run_scheduled(
                         "parallel block (sequential fallback)"
                     );
 
-                    for idx in &pending {
+                    let order = plan.soft_dependency_waves(&pending, 1).concat();
+                    let block_review = block_review_task(config, &order, |_| true);
+                    let block_base = block_review.and_then(|_| scope_base(config, order[0]));
+                    for idx in &order {
                         position += 1;
 
                         let task = plan.task_by_index(*idx).ok_or_else(|| {
@@ -1304,13 +2238,20 @@ pub fn run_scheduled(
                             }
                         })?;
 
-                        let result = run_single_task(
-                            agent_path, config, task, peal_state, state_dir, phase3_mode.as_ref(),
-                            task_count, position,
-                        );
+                        let (result, work) = track_task(|| {
+                            run_single_task(
+                                agent_path, config, task, peal_state, state_dir, store,
+                                phase3_mode.as_ref().filter(|_| block_review.is_none()),
+                                task_count, position,
+                            )
+                        });
+                        note_task_work(&mut run_retries, plan, peal_state, store, *idx, work);
                         match result {
                             Ok(r) => {
                                 consecutive_failures = 0;
+                                if block_review.is_none() {
+                                    queue_follow_up_if_needed(config, plan, peal_state, store, &r)?;
+                                }
                                 results.push(r);
                             }
                             Err(e) => {
@@ -1320,10 +2261,45 @@ pub fn run_scheduled(
                                         consecutive_failures,
                                         cap_val,
                                         peal_state,
-                                        state_dir,
+                                        store,
+                                    )?;
+                                }
+                                if let Err(save_err) = store.save_now(peal_state) {
+                                    error!(err = %save_err, "failed to save state after task failure");
+                                }
+                                return Err(e);
+                            }
+                        }
+                    }
+
+                    // `phase3_scope = "block"`: the block's one review, after all of its tasks.
+                    if let (Some(review_idx), Some(mode)) = (block_review, phase3_mode.as_ref()) {
+                        let _entered = task_span(review_idx).entered();
+                        let (result, work) = track_task(|| {
+                            run_task_phase3(
+                                agent_path, config, review_idx, peal_state, state_dir,
+                                Phase3Saves::Now, mode, task_count, position, block_base.as_deref(),
+                            )
+                        });
+                        note_task_work(&mut run_retries, plan, peal_state, store, review_idx, work);
+                        match result {
+                            Ok(outcome) => {
+                                if let Some(r) = results.iter_mut().rev().find(|r| r.task_index == review_idx) {
+                                    r.phase3_outcome = outcome;
+                                    queue_follow_up_if_needed(config, plan, peal_state, store, r)?;
+                                }
+                            }
+                            Err(e) => {
+                                consecutive_failures += 1;
+                                if let Some(cap_val) = cap {
+                                    check_consecutive_cap(
+                                        consecutive_failures,
+                                        cap_val,
+                                        peal_state,
+                                        store,
                                     )?;
                                 }
-                                if let Err(save_err) = state::save_state(peal_state, state_dir) {
+                                if let Err(save_err) = store.save_now(peal_state) {
                                     error!(err = %save_err, "failed to save state after task failure");
                                 }
                                 return Err(e);
@@ -1336,19 +2312,163 @@ pub fn run_scheduled(
                 }
             }
         }
+
+        if config.push_after_segment && peal_state.completed_task_indices.len() > completed_before {
+            let segment_tasks = match segment {
+                crate::plan::Segment::Sequential(idx) => vec![*idx],
+                crate::plan::Segment::Parallel(indices) => indices.clone(),
+            };
+            push_after_segment(config, &segment_tasks)?;
+        }
+    }
+    if let Some(done) = current_stage.filter(|s| gated_stages.contains(s)) {
+        run_stage_commands(config, done)?;
+    }
+
+    // -- Follow-up tasks (on_findings_remaining = "create_task"), including ones from earlier runs --
+    let follow_ups: Vec<state::FollowUpTask> = peal_state
+        .follow_up_tasks
+        .iter()
+        .filter(|f| {
+            !peal_state.is_task_completed(f.index)
+                && plan.task_by_index(f.source_task).is_some()
+                && plan.task_by_index(f.index).is_none()
+        })
+        .cloned()
+        .collect();
+    for follow_up in follow_ups {
+        if crate::daemon::stop_requested(state_dir) {
+            return Ok(stopped_outcome(peal_state, results, failed_task_indices, failure_details, run_retries));
+        }
+        position += 1;
+        info!(
+            task_index = follow_up.index,
+            source_task = follow_up.source_task,
+            "running follow-up task for remaining findings"
+        );
+        let task = crate::plan::Task {
+            index: follow_up.index,
+            content: follow_up.content,
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        };
+        let checkpoint = task_checkpoint(config, task.index);
+        let (result, work) = track_task(|| {
+            run_single_task(
+                agent_path, config, &task, peal_state, state_dir, store, phase3_mode.as_ref(),
+                task_count, position,
+            )
+        });
+        note_task_work(&mut run_retries, plan, peal_state, store, task.index, work);
+        match result {
+            Ok(r) => {
+                consecutive_failures = 0;
+                results.push(r);
+            }
+            Err(e) => {
+                consecutive_failures += 1;
+                if let Some(cap_val) = cap {
+                    check_consecutive_cap(consecutive_failures, cap_val, peal_state, store)?;
+                }
+                if let Err(save_err) = store.save_now(peal_state) {
+                    error!(err = %save_err, "failed to save state after task failure");
+                }
+                if config.continue_with_remaining_tasks && !e.aborts_run() {
+                    failed_task_indices.push(task.index);
+                    failure_details.insert(task.index, TaskFailure::new(task.index, &e));
+                    warn!(task_index = task.index, err = %e, "follow-up task failed, continuing");
+                    if let Some(checkpoint) = &checkpoint {
+                        rollback_task(config, state_dir, task.index, checkpoint);
+                    }
+                    continue;
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    // -- Retry pass: parallel failures are often transient contention; give each one more sequential try --
+    if config.retry_failed_parallel_tasks && config.continue_with_remaining_tasks && !parallel_failed.is_empty() {
+        info!(tasks = ?parallel_failed, "retrying tasks that failed in parallel blocks, sequentially");
+        for idx in parallel_failed {
+            position += 1;
+            let Some(task) = plan.task_by_index(idx) else {
+                continue;
+            };
+            let (result, work) = track_task(|| {
+                run_single_task(
+                    agent_path, config, task, peal_state, state_dir, store, phase3_mode.as_ref(),
+                    task_count, position,
+                )
+            });
+            note_task_work(&mut run_retries, plan, peal_state, store, idx, work);
+            match result {
+                Ok(r) => {
+                    info!(task_index = idx, "task succeeded on retry pass");
+                    failed_task_indices.retain(|&i| i != idx);
+                    failure_details.remove(&idx);
+                    queue_follow_up_if_needed(config, plan, peal_state, store, &r)?;
+                    results.push(r);
+                }
+                Err(e) => {
+                    if let Err(save_err) = store.save_now(peal_state) {
+                        error!(err = %save_err, "failed to save state after task failure");
+                    }
+                    if e.aborts_run() {
+                        return Err(e);
+                    }
+                    warn!(task_index = idx, err = %e, "task failed again on retry pass");
+                    failure_details.insert(idx, TaskFailure::new(idx, &e));
+                }
+            }
+        }
     }
 
     info!(
         completed = results.len(),
-        task_count, "all tasks complete"
+        task_count,
+        agent_calls = crate::rate_limit::agent_calls(),
+        "all tasks complete"
     );
-
+    let escalated_models = escalated_models_for(peal_state, &results);
     Ok(RunOutcome {
         results,
         failed_task_indices,
+        failures: failure_details,
+        task_retries: run_retries,
+        escalated_models,
+        stopped: false,
     })
 }
 
+/// Outcome of a scheduled run that `peal stop` ended before its next segment; the tasks
+/// that did not run stay pending in state for the next run.
+fn stopped_outcome(
+    peal_state: &PealState,
+    results: Vec<TaskResult>,
+    failed_task_indices: Vec<u32>,
+    failures: BTreeMap<u32, TaskFailure>,
+    task_retries: BTreeMap<u32, RetryCounts>,
+) -> RunOutcome {
+    warn!(completed = results.len(), "stop requested; ending the run before the next task");
+    let escalated_models = escalated_models_for(peal_state, &results);
+    RunOutcome {
+        results,
+        failed_task_indices,
+        failures,
+        task_retries,
+        escalated_models,
+        stopped: true,
+    }
+}
+
 /// Run the full pipeline for every task. Delegates to `run_scheduled`.
 ///
 /// Kept as a stable entry point so existing callers and tests continue to work.
@@ -1363,6 +2483,129 @@ pub fn run_all(
     run_scheduled(agent_path, config, plan, peal_state, state_dir, phase3_mode)
 }
 
+/// Run a single phase (1, 2, or 3) for every task in `plan`, ignoring completion state.
+///
+/// Developer mode for `--only-phase`: later phases use artifacts persisted by earlier
+/// runs instead of re-running earlier phases.
+/// - Phase 1 records each task's plan text in state (`last_plan_by_task`).
+/// - Phase 2 reads that plan text; a task without one fails with `MissingPlanText`.
+///   Honors `commit_after_phase2`.
+/// - Phase 3 runs stet review and the address loop against the current tree. When
+///   `phase3_mode` is `None` (stet unavailable), every task is skipped with a warning.
+///
+/// Completed-task indices are never changed. Failures follow
+/// `continue_with_remaining_tasks` like a normal run.
+pub fn run_only_phase(
+    agent_path: &Path,
+    config: &PealConfig,
+    plan: &ParsedPlan,
+    peal_state: &mut PealState,
+    state_dir: &Path,
+    phase3_mode: Option<stet::StetPhase3Mode>,
+    only_phase: u8,
+) -> Result<RunOutcome, PealError> {
+    let task_count = plan.tasks.len();
+    info!(task_count, only_phase, "starting single-phase run");
+
+    if only_phase == 3 && phase3_mode.is_none() {
+        warn!("--only-phase 3: stet is not available; nothing to run");
+    }
+
+    let mut results: Vec<TaskResult> = Vec::with_capacity(task_count);
+    let mut failed_task_indices: Vec<u32> = Vec::new();
+    let mut failure_details: BTreeMap<u32, TaskFailure> = BTreeMap::new();
+    let mut run_retries: BTreeMap<u32, RetryCounts> = BTreeMap::new();
+    let mut store = StateStore::new(state_dir);
+
+    for (i, task) in plan.tasks.iter().enumerate() {
+        let position = i + 1;
+        let _span = task_span(task.index).entered();
+        let (result, work) = track_task(|| match only_phase {
+            1 => run_phase1_validated(agent_path, config, task, task_count, position).map(
+                |plan_text| {
+                    peal_state.record_plan_text(task.index, &plan_text);
+                    TaskResult {
+                        task_index: task.index,
+                        plan_text,
+                        phase2_stdout: String::new(),
+                        phase3_outcome: None,
+                        summary: None,
+                    }
+                },
+            ),
+            2 => match peal_state.plan_text_for(task.index).map(str::to_owned) {
+                None => Err(PealError::MissingPlanText {
+                    task_index: task.index,
+                }),
+                Some(plan_text) => run_phase2_logged(
+                    agent_path, config, task, &plan_text, task_count, position,
+                )
+                .and_then(|phase2_stdout| {
+                    run_task_verify(agent_path, config, task)?;
+                    if config.commit_after_phase2 {
+                        let first_line = plan_text
+                            .lines()
+                            .next()
+                            .map(|s| s.trim())
+                            .filter(|s| !s.is_empty());
+                        commit_after_phase2(&*vcs::for_config(config), &config.repo_path, task.index, first_line)?;
+                    }
+                    Ok(TaskResult {
+                        task_index: task.index,
+                        plan_text,
+                        phase2_stdout,
+                        phase3_outcome: None,
+                        summary: None,
+                    })
+                }),
+            },
+            _ => match phase3_mode.as_ref() {
+                None => Ok(None),
+                Some(mode) => run_task_phase3(
+                    agent_path, config, task.index, peal_state, state_dir, Phase3Saves::Now,
+                    mode, task_count, position, None,
+                ),
+            }
+            .map(|phase3_outcome| TaskResult {
+                task_index: task.index,
+                plan_text: String::new(),
+                phase2_stdout: String::new(),
+                phase3_outcome,
+                summary: None,
+            }),
+        });
+        note_task_work(&mut run_retries, plan, peal_state, &mut store, task.index, work);
+
+        match result {
+            Ok(r) => results.push(r),
+            Err(e) => {
+                if let Err(save_err) = store.save_now(peal_state) {
+                    error!(err = %save_err, "failed to save state after task failure");
+                }
+                if config.continue_with_remaining_tasks && !e.aborts_run() {
+                    failed_task_indices.push(task.index);
+                    failure_details.insert(task.index, TaskFailure::new(task.index, &e));
+                    warn!(task_index = task.index, err = %e, "task failed, continuing with remaining tasks");
+                    continue;
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    store.save_now(peal_state)?;
+
+    let escalated_models = escalated_models_for(peal_state, &results);
+    Ok(RunOutcome {
+        results,
+        failed_task_indices,
+        failures: failure_details,
+        task_retries: run_retries,
+        escalated_models,
+        stopped: false,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1382,7 +2625,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: repo.join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1391,6 +2634,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1408,6 +2653,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         }
     }
 
@@ -1417,7 +2729,15 @@ mod tests {
 
     fn make_plan(tasks: Vec<Task>) -> ParsedPlan {
         let segments = crate::plan::compute_segments(&tasks);
-        ParsedPlan { tasks, segments }
+        let max_task_index = tasks.iter().map(|t| t.index).max().unwrap_or(0);
+        ParsedPlan {
+            task_positions: crate::plan::index_tasks(&tasks),
+            tasks,
+            segments,
+            metadata: Default::default(),
+            max_task_index,
+            task_spans: Default::default(),
+        }
     }
 
     fn resolve_echo() -> PathBuf {
@@ -1519,7 +2839,7 @@ mod tests {
             .unwrap();
         std::fs::write(repo.join("foo.txt"), "content").unwrap();
 
-        let result = super::commit_after_phase2(repo, 1, Some("my suffix"));
+        let result = super::commit_after_phase2(&vcs::Git::default(), repo, 1, Some("my suffix"));
         assert!(result.is_ok(), "commit should succeed: {:?}", result.err());
 
         let out = std::process::Command::new("git")
@@ -1533,6 +2853,27 @@ mod tests {
         assert!(msg.contains("my suffix"), "message should contain suffix: {}", msg);
     }
 
+    #[test]
+    fn commit_after_phase2_truncates_multibyte_suffix_safely() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git").arg("-C").arg(repo).args(args).output().unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        std::fs::write(repo.join("foo.txt"), "content").unwrap();
+
+        // 3-byte characters: byte 77 falls inside one.
+        let suffix = "日本語のテキスト".repeat(5);
+        super::commit_after_phase2(&vcs::Git::default(), repo, 1, Some(&suffix)).unwrap();
+        let out = git(&["log", "-1", "--format=%s"]);
+        let msg = String::from_utf8(out.stdout).unwrap();
+        assert!(msg.starts_with("peal: task 1 - 日本語"), "message: {msg}");
+        assert!(msg.trim_end().ends_with("..."), "message: {msg}");
+    }
+
     #[test]
     fn commit_after_phase2_nothing_to_commit_returns_ok() {
         let dir = tempfile::tempdir().unwrap();
@@ -1557,10 +2898,10 @@ mod tests {
             .output()
             .unwrap();
         std::fs::write(repo.join("bar.txt"), "x").unwrap();
-        super::commit_after_phase2(repo, 1, None).unwrap();
+        super::commit_after_phase2(&vcs::Git::default(), repo, 1, None).unwrap();
 
         // Clean tree: no further changes. Second commit should be no-op and return Ok(())
-        let result = super::commit_after_phase2(repo, 1, None);
+        let result = super::commit_after_phase2(&vcs::Git::default(), repo, 1, None);
         assert!(result.is_ok(), "nothing to commit should return Ok: {:?}", result.err());
 
         let out = std::process::Command::new("git")
@@ -1573,6 +2914,269 @@ mod tests {
         assert_eq!(count, 1, "should still have exactly one commit");
     }
 
+    #[test]
+    fn push_after_segment_pushes_to_remote_and_honors_on_push_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        let remote = dir.path().join("remote.git");
+        std::fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git").arg("-C").arg(&repo).args(args).output().unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        std::fs::write(repo.join("a.txt"), "a").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+        std::process::Command::new("git")
+            .args(["init", "--bare"])
+            .arg(&remote)
+            .output()
+            .unwrap();
+        git(&["remote", "add", "backup", remote.to_str().unwrap()]);
+
+        let plan = make_plan(vec![
+            Task { index: 1, content: "One.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "Two.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+        ]);
+        let mut config = test_config(&repo);
+        config.push_after_segment = true;
+        config.push_remote = "backup".to_owned();
+        config.push_branch = Some("peal-progress".to_owned());
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+        run_scheduled(&resolve_echo(), &config, &plan, &mut state, &state_dir, None).unwrap();
+        let pushed = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&remote)
+            .args(["rev-parse", "--verify", "refs/heads/peal-progress"])
+            .output()
+            .unwrap();
+        assert!(pushed.status.success(), "branch should exist on the remote");
+
+        // Unknown remote: warn-only by default, run error with on_push_fail = "fail".
+        config.push_remote = "nowhere".to_owned();
+        let mut state = fresh_state();
+        let state_dir = dir.path().join(".peal-warn");
+        let outcome = run_scheduled(&resolve_echo(), &config, &plan, &mut state, &state_dir, None).unwrap();
+        assert_eq!(outcome.results.len(), 2);
+
+        config.on_push_fail = "fail".to_owned();
+        let mut state = fresh_state();
+        let state_dir = dir.path().join(".peal-fail");
+        let err = run_scheduled(&resolve_echo(), &config, &plan, &mut state, &state_dir, None).unwrap_err();
+        assert!(matches!(err, PealError::PushAfterSegmentFailed { .. }), "got {err:?}");
+        assert!(state.is_task_completed(1) && !state.is_task_completed(2));
+    }
+
+    #[test]
+    fn phase2_change_check_flags_claimed_edits_that_did_not_happen() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git").arg("-C").arg(&repo).args(args).output().unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        std::fs::write(repo.join("a.txt"), "a").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+
+        // Phase 1 echoes its prompt as the plan; Phase 2 claims an edit of a.txt and makes it
+        // only when the task (and so the plan) says "Really".
+        let agent = dir.path().join("json_agent");
+        std::fs::write(
+            &agent,
+            "#!/bin/sh\n[ \"$2\" = --plan ] && { echo \"plan: $*\"; exit 0; }\n\
+             case \"$*\" in *Really*) echo changed > a.txt;; esac\n\
+             echo '{\"type\":\"tool_call\",\"subtype\":\"completed\",\"tool_call\":{\"editToolCall\":{\"args\":{\"path\":\"a.txt\"},\"result\":{\"success\":{}}}}}'\n\
+             echo '{\"type\":\"result\",\"subtype\":\"success\",\"is_error\":false,\"result\":\"Edited a.txt.\"}'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = test_config(&repo);
+        config.phase2_change_check = "fail".to_owned();
+        let state_dir = dir.path().join(".peal");
+
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "Pretend.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+        let mut state = fresh_state();
+        let err = run_scheduled(&agent, &config, &plan, &mut state, &state_dir, None).unwrap_err();
+        match &err {
+            PealError::Phase2NoChanges { task_index, detail } => {
+                assert_eq!(*task_index, 1);
+                assert!(detail.contains("a.txt"), "detail: {detail}");
+            }
+            other => panic!("expected Phase2NoChanges, got: {other:?}"),
+        }
+        assert!(!state.is_task_completed(1));
+
+        config.phase2_change_check = "warn".to_owned();
+        let mut state = fresh_state();
+        run_scheduled(&agent, &config, &plan, &mut state, &state_dir, None).unwrap();
+        assert!(state.is_task_completed(1), "warn only logs");
+
+        config.phase2_change_check = "fail".to_owned();
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "Really.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+        let mut state = fresh_state();
+        let outcome = run_scheduled(&agent, &config, &plan, &mut state, &state_dir, None).unwrap();
+        assert_eq!(outcome.results[0].phase2_stdout, "Edited a.txt.");
+    }
+
+    #[test]
+    fn phase2_change_check_retry_reruns_a_no_op_phase2_with_a_firmer_prompt() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git").arg("-C").arg(&repo).args(args).output().unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        std::fs::write(repo.join("a.txt"), "a").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+
+        // Phase 2 claims the work is done; only the retry prompt (or a "Stubborn" task) matters.
+        let agent = dir.path().join("lazy_agent");
+        std::fs::write(
+            &agent,
+            "#!/bin/sh\n[ \"$2\" = --plan ] && { echo \"plan: $*\"; exit 0; }\n\
+             case \"$*\" in *Stubborn*) ;; *\"previous attempt\"*) echo changed > a.txt;; esac\n\
+             echo 'This is already implemented.'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = test_config(&repo);
+        config.phase2_change_check = "retry".to_owned();
+        let state_dir = dir.path().join(".peal");
+        let task = |content: &str| Task {
+            index: 1,
+            content: content.to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        };
+
+        let mut state = fresh_state();
+        run_scheduled(&agent, &config, &make_plan(vec![task("Lazy.")]), &mut state, &state_dir, None).unwrap();
+        assert!(state.is_task_completed(1));
+        assert_eq!(std::fs::read_to_string(repo.join("a.txt")).unwrap(), "changed\n");
+
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "retried"]);
+        let mut state = fresh_state();
+        let err = run_scheduled(&agent, &config, &make_plan(vec![task("Stubborn.")]), &mut state, &state_dir, None)
+            .unwrap_err();
+        match &err {
+            PealError::Phase2NoChanges { task_index: 1, detail } => {
+                assert!(detail.contains("already implemented"), "detail: {detail}");
+            }
+            other => panic!("expected Phase2NoChanges, got: {other:?}"),
+        }
+        assert!(!state.is_task_completed(1));
+    }
+
+    #[test]
+    fn repo_subdir_scopes_agent_verify_and_change_check() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join("svc")).unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git").arg("-C").arg(&repo).args(args).output().unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        std::fs::write(repo.join("svc").join("keep"), "").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+
+        // Phase 2 edits x.txt in its cwd and claims it, or (for "Outside") only a file above it.
+        let agent = dir.path().join("subdir_agent");
+        std::fs::write(
+            &agent,
+            "#!/bin/sh\n[ \"$2\" = --plan ] && { echo \"plan: $*\"; exit 0; }\n\
+             case \"$*\" in *Outside*) echo x > ../outside.txt;; *) pwd > x.txt;; esac\n\
+             echo '{\"type\":\"tool_call\",\"subtype\":\"completed\",\"tool_call\":{\"editToolCall\":{\"args\":{\"path\":\"x.txt\"},\"result\":{\"success\":{}}}}}'\n\
+             echo '{\"type\":\"result\",\"subtype\":\"success\",\"is_error\":false,\"result\":\"Edited x.txt.\"}'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = test_config(&repo);
+        config.repo_subdir = Some(PathBuf::from("svc"));
+        config.phase2_change_check = "fail".to_owned();
+        let state_dir = dir.path().join(".peal");
+        let task = |content: &str| Task {
+            index: 1,
+            content: content.to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: vec!["test -f x.txt".to_owned()],
+            expect: Vec::new(),
+        };
+
+        let mut state = fresh_state();
+        run_scheduled(&agent, &config, &make_plan(vec![task("Inside.")]), &mut state, &state_dir, None).unwrap();
+        assert!(state.is_task_completed(1));
+        let cwd = std::fs::read_to_string(repo.join("svc").join("x.txt")).unwrap();
+        assert!(cwd.trim_end().ends_with("svc"), "agent ran in {cwd}");
+
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "inside"]);
+        let mut state = fresh_state();
+        let err = run_scheduled(&agent, &config, &make_plan(vec![task("Outside.")]), &mut state, &state_dir, None)
+            .unwrap_err();
+        assert!(matches!(err, PealError::Phase2NoChanges { task_index: 1, .. }), "got: {err:?}");
+    }
+
     #[test]
     fn runs_phase1_for_all_tasks() {
         let dir = tempfile::tempdir().unwrap();
@@ -1586,16 +3190,40 @@ mod tests {
                 index: 1,
                 content: "First task.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "Second task.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 3,
                 content: "Third task.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -1642,7 +3270,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1651,6 +3279,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1668,6 +3298,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
@@ -1679,11 +3376,27 @@ mod tests {
                 index: 1,
                 content: "Will fail.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "Should not run.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -1707,6 +3420,14 @@ mod tests {
             index: 42,
             content: "The only task.".to_owned(),
             parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
         }]);
 
         let results = run_phase1_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap();
@@ -1736,6 +3457,14 @@ mod tests {
             index: 1,
             content: "Task.".to_owned(),
             parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
         }]);
 
         let err = run_phase1_all(
@@ -1770,16 +3499,40 @@ mod tests {
                 index: 10,
                 content: "Ten.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 20,
                 content: "Twenty.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 30,
                 content: "Thirty.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -1804,11 +3557,27 @@ mod tests {
                 index: 1,
                 content: "First task.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "Second task.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -1837,6 +3606,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn run_all_records_task_summary_only_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "Only task.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+
+        let config = test_config(dir.path());
+        let mut state = fresh_state();
+        let results = run_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
+        assert!(results[0].summary.is_none(), "summaries are opt-in");
+
+        let config = PealConfig {
+            summarize_tasks: true,
+            ..test_config(dir.path())
+        };
+        let mut state = fresh_state();
+        let results = run_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
+        let summary = results[0].summary.as_deref().expect("summary recorded");
+        // echo returns the prompt, which fences the Phase 2 output.
+        assert!(summary.contains("---OUTPUT---"), "got: {summary}");
+    }
+
     #[test]
     fn run_all_empty_plan() {
         let dir = tempfile::tempdir().unwrap();
@@ -1863,7 +3667,7 @@ mod tests {
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1872,6 +3676,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1889,6 +3695,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
@@ -1900,11 +3773,27 @@ mod tests {
                 index: 1,
                 content: "Will fail in phase 1.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "Should not run.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -1928,6 +3817,14 @@ mod tests {
             index: 7,
             content: "The only task.".to_owned(),
             parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
         }]);
 
         let results = run_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
@@ -1956,16 +3853,40 @@ mod tests {
                 index: 10,
                 content: "Ten.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 20,
                 content: "Twenty.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 30,
                 content: "Thirty.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -1987,6 +3908,14 @@ mod tests {
             index: 1,
             content: "Build a widget.".to_owned(),
             parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
         }]);
 
         let results = run_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
@@ -2014,20 +3943,44 @@ mod tests {
                 index: 1,
                 content: "A.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "B.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 3,
                 content: "C.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
-        run_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
+        run_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap();
 
         let loaded = state::load_state(&state_dir)
             .unwrap()
@@ -2035,6 +3988,33 @@ mod tests {
         assert_eq!(loaded.completed_task_indices, vec![1, 2, 3]);
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn stop_request_ends_run_before_next_task() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let state_dir = dir.path().join(".peal");
+        std::fs::create_dir_all(&state_dir).unwrap();
+        // The agent asks for a stop while task 1 runs, as `peal stop` would.
+        let agent = dir.path().join("agent-stub");
+        std::fs::write(
+            &agent,
+            format!("#!/bin/sh\ntouch '{}'\necho \"$*\"\n", crate::daemon::stop_file_path(&state_dir).display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let plan = crate::plan::parse_plan("## Task 1\nA.\n\n## Task 2\nB.\n").unwrap();
+        let mut state = fresh_state();
+
+        let outcome = run_all(&agent, &config, &plan, &mut state, &state_dir, None).unwrap();
+
+        assert!(outcome.stopped);
+        assert_eq!(outcome.results.iter().map(|r| r.task_index).collect::<Vec<_>>(), vec![1]);
+        assert!(state.is_task_completed(1) && !state.is_task_completed(2));
+    }
+
     #[test]
     fn run_all_on_failure_persists_only_completed_tasks() {
         let dir = tempfile::tempdir().unwrap();
@@ -2064,6 +4044,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -2081,6 +4063,73 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let mut state = fresh_state();
@@ -2089,11 +4138,27 @@ mod tests {
                 index: 1,
                 content: "Will fail.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "Never reached.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -2114,16 +4179,32 @@ mod tests {
                 index: 10,
                 content: "A.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 20,
                 content: "B.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
         let mut state2 = fresh_state();
-        run_all(&echo, &good_config, &plan2, &mut state2, &state_dir, None).unwrap().results;
+        run_all(&echo, &good_config, &plan2, &mut state2, &state_dir, None).unwrap();
 
         let loaded2 = state::load_state(&state_dir)
             .unwrap()
@@ -2142,9 +4223,9 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: true },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         assert_eq!(plan.segments, vec![Segment::Parallel(vec![1, 2, 3])]);
@@ -2166,18 +4247,18 @@ mod tests {
         let mut config = test_config_parallel(dir.path());
         let mut state = fresh_state();
         let plan1 = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
-        run_scheduled(&echo, &config, &plan1, &mut state, &state_dir, None).unwrap().results;
+        run_scheduled(&echo, &config, &plan1, &mut state, &state_dir, None).unwrap();
         assert!(state.is_task_completed(1));
 
         // Run with parallel block (2, 3) that will fail; continue_with_remaining_tasks = true.
         config.agent_cmd = "false".to_owned();
         config.continue_with_remaining_tasks = true;
         let plan2 = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         let result = run_scheduled(&false_path, &config, &plan2, &mut state, &state_dir, None);
@@ -2189,6 +4270,55 @@ mod tests {
         assert!(!loaded.is_task_completed(3));
     }
 
+    #[test]
+    fn retry_failed_parallel_tasks_reruns_block_failures_sequentially() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Fails the first invocation for the "Flaky" task only; cwd is the repo.
+        let flaky_agent_in = |dir: &Path| {
+            let agent = dir.join("flaky_agent");
+            std::fs::write(
+                &agent,
+                "#!/bin/sh\ncase \"$*\" in *Flaky*) [ -f flaked ] || { touch flaked; exit 1; };; esac\necho ok\n",
+            )
+            .unwrap();
+            std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+            agent
+        };
+        let plan = make_plan(vec![
+            Task { index: 1, content: "Flaky.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "Steady.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+        ]);
+
+        for retry in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let state_dir = dir.path().join(".peal");
+            let mut state = fresh_state();
+            let agent = flaky_agent_in(dir.path());
+            let mut config = test_config_parallel(dir.path());
+            config.continue_with_remaining_tasks = true;
+            config.retry_failed_parallel_tasks = retry;
+
+            let outcome = run_scheduled(&agent, &config, &plan, &mut state, &state_dir, None).unwrap();
+            if retry {
+                assert!(outcome.failed_task_indices.is_empty(), "retry pass should recover task 1");
+                assert!(outcome.failures.is_empty());
+                let mut indices: Vec<u32> = outcome.results.iter().map(|r| r.task_index).collect();
+                indices.sort_unstable();
+                assert_eq!(indices, vec![1, 2]);
+                assert!(state.is_task_completed(1));
+            } else {
+                assert_eq!(outcome.failed_task_indices, vec![1]);
+                let failure = &outcome.failures[&1];
+                assert_eq!(failure.phase, "phase 1");
+                assert!(failure.error.starts_with("Phase 1 exited with code Some(1)"), "{}", failure.error);
+                assert!(!failure.error.contains('\n'));
+                assert!(!state.is_task_completed(1));
+            }
+            assert!(state.is_task_completed(2));
+        }
+    }
+
     #[test]
     fn run_all_creates_state_dir_if_missing() {
         let dir = tempfile::tempdir().unwrap();
@@ -2203,9 +4333,17 @@ mod tests {
             index: 1,
             content: "X.".to_owned(),
             parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
         }]);
 
-        run_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
+        run_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap();
 
         assert!(state_dir.join("state.json").exists());
     }
@@ -2227,16 +4365,40 @@ mod tests {
                 index: 1,
                 content: "Already done.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "Still pending.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 3,
                 content: "Also pending.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -2262,16 +4424,40 @@ mod tests {
                 index: 1,
                 content: "Done.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "Done.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 3,
                 content: "Pending.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -2297,11 +4483,27 @@ mod tests {
                 index: 1,
                 content: "Done.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "Done.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -2331,21 +4533,53 @@ mod tests {
                 index: 1,
                 content: "A.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "B.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 3,
                 content: "C.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 4,
                 content: "D.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -2376,11 +4610,27 @@ mod tests {
                 index: 1,
                 content: "A.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "B.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -2412,6 +4662,14 @@ mod tests {
             index: 1,
             content: "A.".to_owned(),
             parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
         }]);
 
         let results = run_all(
@@ -2465,6 +4723,14 @@ mod tests {
             index: 1,
             content: "A.".to_owned(),
             parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
         }]);
 
         let results = run_all(
@@ -2491,25 +4757,163 @@ mod tests {
     }
 
     #[test]
-    fn run_all_phase3_failure_saves_state() {
+    #[cfg(unix)]
+    fn stet_health_check_restarts_a_lost_session_before_phase_3() {
+        use std::os::unix::fs::PermissionsExt;
+
         let dir = tempfile::tempdir().unwrap();
         let echo = resolve_echo();
         let state_dir = dir.path().join(".peal");
         let mut state = fresh_state();
-        let config = test_config(dir.path());
+        let mut config = PealConfig {
+            stet_health_command: Some("test -f session".to_owned()),
+            ..test_config(dir.path())
+        };
 
-        let bad_stet = PathBuf::from("/no/such/stet-binary");
+        // `stet start` logs itself and creates the session; `stet run` finds nothing.
+        let stet_script = dir.path().join("stet-stub");
+        std::fs::write(
+            &stet_script,
+            "#!/bin/sh\n[ \"$1\" = start ] && { echo start >> starts; touch session; }\nexit 0\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&stet_script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let task = |index| Task {
+            index,
+            content: format!("Task {index}."),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        };
+        let plan = make_plan(vec![task(1), task(2)]);
+        let mode = || Some(stet::StetPhase3Mode::BuiltIn(stet_script.clone()));
+
+        let results = run_all(&echo, &config, &plan, &mut state, &state_dir, mode()).unwrap().results;
+        assert!(results.iter().all(|r| r.phase3_outcome.is_some()));
+        let starts = std::fs::read_to_string(dir.path().join("starts")).unwrap();
+        assert_eq!(starts.lines().count(), 1, "restarted before task 1 only");
+        assert!(state.stet_session.is_some());
+
+        // A session that cannot be restarted skips Phase 3 under on_stet_fail = "skip".
+        std::fs::remove_file(dir.path().join("session")).unwrap();
+        std::fs::write(&stet_script, "#!/bin/sh\n[ \"$1\" = start ] && exit 1\nexit 0\n").unwrap();
+        config.on_stet_fail = "skip".to_owned();
+        let mut state = fresh_state();
+        let results = run_all(&echo, &config, &plan, &mut state, &state_dir, mode()).unwrap().results;
+        assert!(results.iter().all(|r| r.phase3_outcome.is_none()));
 
-        let plan = make_plan(vec![
-            Task {
+        config.on_stet_fail = "fail".to_owned();
+        let mut state = fresh_state();
+        let err = run_all(&echo, &config, &plan, &mut state, &state_dir, mode()).unwrap_err();
+        assert!(matches!(err, PealError::StetStartFailed { .. }), "{err:?}");
+    }
+
+    #[test]
+    fn create_task_policy_queues_and_runs_follow_up_task() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+        let config = PealConfig {
+            on_findings_remaining: "create_task".to_owned(),
+            max_address_rounds: 1,
+            ..test_config(dir.path())
+        };
+
+        // Findings never go away.
+        let stet_script = dir.path().join("stet-stub");
+        std::fs::write(
+            &stet_script,
+            "#!/bin/sh
+\
+             echo '{\"findings\": [{\"id\": \"f1\", \"message\": \"still broken\"}]}'\n\
+             exit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&stet_script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "A.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+
+        let results = run_all(
+            &echo,
+            &config,
+            &plan,
+            &mut state,
+            &state_dir,
+            Some(stet::StetPhase3Mode::BuiltIn(stet_script.clone())),
+        )
+        .unwrap()
+        .results;
+
+        let indices: Vec<u32> = results.iter().map(|r| r.task_index).collect();
+        assert_eq!(indices, vec![1, 2], "follow-up runs after the plan's tasks");
+        assert_eq!(state.follow_up_tasks.len(), 1, "follow-ups do not queue follow-ups");
+        let follow_up = &state.follow_up_tasks[0];
+        assert_eq!((follow_up.index, follow_up.source_task), (2, 1));
+        assert!(follow_up.content.starts_with("Address remaining review findings for task 1."));
+        assert!(follow_up.content.contains("still broken"));
+        assert_eq!(state.completed_task_indices, vec![1, 2]);
+
+        let saved = state::load_state(&state_dir).unwrap().unwrap();
+        assert_eq!(saved.follow_up_tasks, state.follow_up_tasks);
+    }
+
+    #[test]
+    fn run_all_phase3_failure_saves_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+        let config = test_config(dir.path());
+
+        let bad_stet = PathBuf::from("/no/such/stet-binary");
+
+        let plan = make_plan(vec![
+            Task {
                 index: 1,
                 content: "A.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "B.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -2551,9 +4955,9 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
-            Task { index: 2, content: "B.".to_owned(), parallel: false },
-            Task { index: 3, content: "C.".to_owned(), parallel: false },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         assert_eq!(
@@ -2575,10 +4979,10 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
-            Task { index: 4, content: "D.".to_owned(), parallel: false },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 4, content: "D.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         assert_eq!(
@@ -2612,10 +5016,10 @@ mod tests {
         state.mark_task_completed(2);
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
-            Task { index: 4, content: "D.".to_owned(), parallel: false },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 4, content: "D.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         let results = run_scheduled(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
@@ -2637,10 +5041,10 @@ mod tests {
         state.mark_task_completed(2);
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
-            Task { index: 4, content: "D.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 4, content: "D.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         assert_eq!(
@@ -2671,9 +5075,9 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: false },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         // compute_segments demotes single-parallel to Sequential.
@@ -2700,9 +5104,9 @@ mod tests {
         state.mark_task_completed(3);
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         let results = run_scheduled(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
@@ -2738,6 +5142,8 @@ mod tests {
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -2755,14 +5161,81 @@ mod tests {
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "Will fail.".to_owned(), parallel: true },
-            Task { index: 2, content: "Never reached.".to_owned(), parallel: true },
-            Task { index: 3, content: "Never reached.".to_owned(), parallel: false },
+            Task { index: 1, content: "Will fail.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "Never reached.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "Never reached.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         assert_eq!(
@@ -2807,16 +5280,40 @@ mod tests {
                 index: 1,
                 content: "Fails.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 2,
                 content: "Fails.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
             Task {
                 index: 3,
                 content: "Not reached.".to_owned(),
                 parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
             },
         ]);
 
@@ -2858,9 +5355,9 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: true },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         assert_eq!(plan.segments, vec![Segment::Parallel(vec![1, 2, 3])]);
@@ -2888,9 +5385,9 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: true },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         assert_eq!(plan.segments, vec![Segment::Parallel(vec![1, 2, 3])]);
@@ -2909,9 +5406,9 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: false },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         // Single parallel task demoted to Sequential by compute_segments.
@@ -2935,9 +5432,9 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: true },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         let results = run_scheduled(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
@@ -2948,6 +5445,35 @@ mod tests {
         assert_eq!(indices, vec![1, 2, 3]);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn parallel_tasks_sharing_a_lock_never_overlap() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config_parallel(dir.path());
+        let state_dir = dir.path().join(".peal");
+        let agent = dir.path().join("agent.sh");
+        let d = dir.path().display();
+        std::fs::write(
+            &agent,
+            format!(
+                "#!/bin/sh\ncase \"$*\" in *Locked*) mkdir '{d}/busy' 2>/dev/null || touch '{d}/overlap'; sleep 0.2; rmdir '{d}/busy';; esac\necho \"$*\"\n"
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let plan = crate::plan::parse_plan(
+            "## Task 1 (parallel) (lock: db)\nLocked A.\n\n## Task 2 (parallel) (lock: DB, cache)\nLocked B.\n\n## Task 3 (parallel)\nC.\n",
+        )
+        .unwrap();
+        let mut state = fresh_state();
+
+        let results = run_scheduled(&agent, &config, &plan, &mut state, &state_dir, None).unwrap().results;
+
+        assert_eq!(results.len(), 3);
+        assert!(!dir.path().join("overlap").exists(), "tasks holding lock `db` must not run at the same time");
+    }
+
     /// SP-5.3: Phase 3 runs in block task order (segment order), not thread completion order.
     #[test]
     fn parallel_block_phase3_in_segment_order() {
@@ -2958,9 +5484,9 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: true },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         assert_eq!(plan.segments, vec![Segment::Parallel(vec![1, 2, 3])]);
@@ -2976,6 +5502,150 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn parallel_phase3_runs_block_reviews_concurrently_but_stet_takes_turns() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = PealConfig {
+            parallel_phase3: true,
+            ..test_config_parallel(dir.path())
+        };
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+
+        // Clean review that notes when another stet invocation is still running.
+        let stet_script = dir.path().join("stet-stub");
+        std::fs::write(
+            &stet_script,
+            "#!/bin/sh
+             mkdir \"$PWD/.stet_busy\" 2>/dev/null || touch \"$PWD/stet_overlap\"\n             sleep 0.2\n             rmdir \"$PWD/.stet_busy\"\n             exit 0\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&stet_script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let plan = make_plan(vec![
+            Task { index: 1, content: "A.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+        ]);
+
+        let results = run_scheduled(
+            &echo,
+            &config,
+            &plan,
+            &mut state,
+            &state_dir,
+            Some(stet::StetPhase3Mode::BuiltIn(stet_script)),
+        )
+        .unwrap()
+        .results;
+
+        let order: Vec<u32> = results.iter().map(|r| r.task_index).collect();
+        assert_eq!(order, vec![1, 2, 3], "results stay in block order");
+        assert!(results.iter().all(|r| r.phase3_outcome.as_ref().is_some_and(|o| o.findings_resolved)));
+        assert!(!dir.path().join("stet_overlap").exists(), "stet invocations must not overlap");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parallel_phase3_keeps_a_restarted_stet_session_in_saved_state() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = PealConfig {
+            parallel_phase3: true,
+            stet_health_command: Some("test -f session".to_owned()),
+            ..test_config_parallel(dir.path())
+        };
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+
+        // `stet start` creates the session; `stet run` finds nothing.
+        let stet_script = dir.path().join("stet-stub");
+        std::fs::write(&stet_script, "#!/bin/sh\n[ \"$1\" = start ] && touch session\nexit 0\n").unwrap();
+        std::fs::set_permissions(&stet_script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let plan = make_plan(vec![
+            Task { index: 1, content: "A.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+        ]);
+
+        run_scheduled(
+            &echo,
+            &config,
+            &plan,
+            &mut state,
+            &state_dir,
+            Some(stet::StetPhase3Mode::BuiltIn(stet_script)),
+        )
+        .unwrap();
+
+        assert!(state.stet_session.is_some(), "restart in a Phase 3 thread reaches the parent state");
+        let loaded = state::load_state(&state_dir).unwrap().expect("state file should exist after run");
+        assert_eq!(loaded.stet_session, state.stet_session);
+        assert_eq!(loaded.completed_task_indices, vec![1, 2, 3]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn block_phase3_scope_reviews_parallel_block_once_under_last_task() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = PealConfig {
+            phase3_scope: "block".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
+            parallel_phase3: true,
+            ..test_config_parallel(dir.path())
+        };
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+
+        // Clean review that logs each stet subcommand.
+        let stet_script = dir.path().join("stet-stub");
+        std::fs::write(&stet_script, "#!/bin/sh\necho \"$1\" >> \"$PWD/stet_calls\"\nexit 0\n").unwrap();
+        std::fs::set_permissions(&stet_script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let plan = make_plan(vec![
+            Task { index: 1, content: "A.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+        ]);
+
+        let results = run_scheduled(
+            &echo,
+            &config,
+            &plan,
+            &mut state,
+            &state_dir,
+            Some(stet::StetPhase3Mode::BuiltIn(stet_script)),
+        )
+        .unwrap()
+        .results;
+
+        let order: Vec<u32> = results.iter().map(|r| r.task_index).collect();
+        assert_eq!(order, vec![1, 2, 3]);
+        let reviewed: Vec<u32> =
+            results.iter().filter(|r| r.phase3_outcome.is_some()).map(|r| r.task_index).collect();
+        assert_eq!(reviewed, vec![3], "the block's outcome is recorded under its last task");
+        let calls = std::fs::read_to_string(dir.path().join("stet_calls")).unwrap();
+        assert_eq!(calls.lines().filter(|l| *l == "run").count(), 1, "one review per block: {calls}");
+    }
+
     #[test]
     fn parallel_block_persists_state_for_all() {
         let dir = tempfile::tempdir().unwrap();
@@ -2985,12 +5655,12 @@ mod tests {
         let mut state = fresh_state();
 
         let plan = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: true },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
-        run_scheduled(&echo, &config, &plan, &mut state, &state_dir, None).unwrap().results;
+        run_scheduled(&echo, &config, &plan, &mut state, &state_dir, None).unwrap();
 
         let loaded = state::load_state(&state_dir)
             .unwrap()
@@ -3010,16 +5680,16 @@ mod tests {
         let mut state = fresh_state();
 
         let plan_step1 = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
-        run_scheduled(&echo, &config, &plan_step1, &mut state, &state_dir, None).unwrap().results;
+        run_scheduled(&echo, &config, &plan_step1, &mut state, &state_dir, None).unwrap();
         assert!(state.is_task_completed(1));
 
         // Step 2: run with `false` agent; tasks 2,3 form a parallel block and fail.
         let plan_step2 = make_plan(vec![
-            Task { index: 1, content: "A.".to_owned(), parallel: false },
-            Task { index: 2, content: "B.".to_owned(), parallel: true },
-            Task { index: 3, content: "C.".to_owned(), parallel: true },
+            Task { index: 1, content: "A.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 3, content: "C.".to_owned(), parallel: true, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
         ]);
 
         let err = run_scheduled(&false_path, &config, &plan_step2, &mut state, &state_dir, None)
@@ -3038,4 +5708,640 @@ mod tests {
         assert!(!loaded.is_task_completed(2), "task 2 should not be completed");
         assert!(!loaded.is_task_completed(3), "task 3 should not be completed");
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn phase3_agent_failure_not_skipped_by_on_stet_fail() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+        let mut config = test_config(dir.path());
+        config.on_stet_fail = "skip".to_owned();
+
+        // Agent fails only for the Phase 3 prompt (which contains the finding message).
+        let agent = dir.path().join("agent-stub");
+        std::fs::write(
+            &agent,
+            "#!/bin/sh\ncase \"$*\" in *SENTINEL*) exit 1;; esac\necho ok\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Custom stet command: always reports one finding.
+        let stet_script = dir.path().join("stet-stub");
+        std::fs::write(
+            &stet_script,
+            "#!/bin/sh\necho '{\"findings\": [{\"id\": \"f1\", \"message\": \"SENTINEL\"}]}'\nexit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&stet_script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "A.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+
+        let err = run_all(
+            &agent,
+            &config,
+            &plan,
+            &mut state,
+            &state_dir,
+            Some(stet::StetPhase3Mode::CustomCommands(vec![stet_script
+                .to_string_lossy()
+                .into_owned()])),
+        )
+        .unwrap_err();
+
+        assert!(
+            matches!(err, PealError::PhaseNonZeroExit { phase: 3, .. }),
+            "agent failure should bypass on_stet_fail=skip, got: {err:?}"
+        );
+    }
+
+
+    // -- run_only_phase (--only-phase) tests --
+
+    #[test]
+    fn only_phase_1_records_plan_text_without_completing() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "First task.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+
+        let results = run_only_phase(&echo, &config, &plan, &mut state, &state_dir, None, 1)
+            .unwrap()
+            .results;
+
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].plan_text.is_empty());
+        assert!(results[0].phase2_stdout.is_empty());
+        assert_eq!(state.plan_text_for(1), Some(results[0].plan_text.as_str()));
+        assert!(state.completed_task_indices.is_empty());
+
+        let loaded = state::load_state(&state_dir).unwrap().unwrap();
+        assert_eq!(loaded.plan_text_for(1), state.plan_text_for(1));
+    }
+
+    #[test]
+    fn only_phase_2_uses_persisted_plan_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+        state.record_plan_text(2, "SAVED-PLAN-TEXT");
+        state.mark_task_completed(2);
+        let plan = make_plan(vec![Task {
+            index: 2,
+            content: "Second task.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+
+        let results = run_only_phase(&echo, &config, &plan, &mut state, &state_dir, None, 2)
+            .unwrap()
+            .results;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].plan_text, "SAVED-PLAN-TEXT");
+        assert!(
+            results[0].phase2_stdout.contains("SAVED-PLAN-TEXT"),
+            "phase 2 should receive the persisted plan text, got: {}",
+            results[0].phase2_stdout
+        );
+    }
+
+    #[test]
+    fn only_phase_2_without_plan_text_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "First task.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+
+        let err = run_only_phase(&echo, &config, &plan, &mut state, &state_dir, None, 2)
+            .unwrap_err();
+
+        assert!(
+            matches!(err, PealError::MissingPlanText { task_index: 1 }),
+            "expected MissingPlanText, got: {err:?}"
+        );
+    }
+
+    #[test]
+    fn run_all_records_plan_text_in_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let echo = resolve_echo();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "First task.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+
+        let results = run_all(&echo, &config, &plan, &mut state, &state_dir, None)
+            .unwrap()
+            .results;
+
+        assert_eq!(state.plan_text_for(1), Some(results[0].plan_text.as_str()));
+    }
+
+
+    #[test]
+    #[cfg(unix)]
+    fn run_all_records_per_task_retries_in_outcome_and_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+
+        // Fails the very first invocation only (task 1, phase 1); cwd is the repo.
+        let flaky_agent = dir.path().join("flaky_agent");
+        std::fs::write(
+            &flaky_agent,
+            "#!/bin/sh\n[ -f calls ] || { touch calls; exit 1; }\necho ok\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&flaky_agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = test_config(dir.path());
+        config.phase_retry_count = 1;
+
+        let plan = make_plan(vec![
+            Task {
+                index: 1,
+                content: "Flaky.".to_owned(),
+                parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
+            },
+            Task {
+                index: 2,
+                content: "Steady.".to_owned(),
+                parallel: false,
+                tags: Vec::new(),
+                stage: None,
+                after: Vec::new(),
+                context: Vec::new(),
+                dirs: Vec::new(),
+                locks: Vec::new(),
+                verify: Vec::new(),
+                expect: Vec::new(),
+            },
+        ]);
+
+        let outcome = run_all(&flaky_agent, &config, &plan, &mut state, &state_dir, None).unwrap();
+
+        let expected = RetryCounts { phase1: 1, ..Default::default() };
+        assert_eq!(outcome.results.len(), 2);
+        assert_eq!(outcome.task_retries.len(), 1, "task 2 never retried");
+        assert_eq!(outcome.task_retries[&1], expected);
+        assert_eq!(state.task_retries[&1], expected);
+        let saved = state::load_state(&state_dir).unwrap().unwrap();
+        assert_eq!(saved.task_retries, state.task_retries);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_all_records_phase_durations_and_eta_in_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+
+        let slow_agent = dir.path().join("slow_agent");
+        std::fs::write(&slow_agent, "#!/bin/sh
+sleep 0.02
+echo ok
+").unwrap();
+        std::fs::set_permissions(&slow_agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = test_config(dir.path());
+        let task = |index| Task {
+            index,
+            content: format!("Task {index}."),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        };
+        let plan = make_plan(vec![task(1), task(2)]);
+
+        run_all(&slow_agent, &config, &plan, &mut state, &state_dir, None).unwrap();
+
+        assert_eq!(state.phase_durations.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+        for durations in state.phase_durations.values() {
+            assert!(durations.phase1_ms >= 20, "{durations:?}");
+            assert!(durations.phase2_ms >= 20, "{durations:?}");
+            assert_eq!(durations.phase3_ms, 0, "no stet");
+        }
+        let eta = state.eta.clone().expect("ETA after the last task");
+        assert_eq!((eta.remaining_tasks, eta.remaining_sec), (0, 0));
+        let saved = state::load_state(&state_dir).unwrap().unwrap();
+        assert_eq!(saved.phase_durations, state.phase_durations);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn escalation_models_retry_failed_phases_and_record_the_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+
+        // Succeeds only with `--model o3-pro`; logs the model of every call (cwd is the repo).
+        let agent = dir.path().join("picky_agent");
+        std::fs::write(
+            &agent,
+            "#!/bin/sh\nm=default\nwhile [ $# -gt 0 ]; do [ \"$1\" = --model ] && m=$2; shift; done\n\
+             echo \"$m\" >> models.log\n[ \"$m\" = o3-pro ] || exit 1\necho ok\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = test_config(dir.path());
+        config.escalation_models = vec!["gpt-5.2".to_owned(), "o3-pro".to_owned()];
+
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "Hard.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+
+        let outcome = run_all(&agent, &config, &plan, &mut state, &state_dir, None).unwrap();
+
+        assert_eq!(outcome.results.len(), 1);
+        assert_eq!(outcome.escalated_models[&1], "o3-pro");
+        assert_eq!(state.escalated_models[&1], "o3-pro");
+        let log = std::fs::read_to_string(dir.path().join("models.log")).unwrap();
+        assert_eq!(
+            log.lines().collect::<Vec<_>>(),
+            ["default", "gpt-5.2", "o3-pro", "default", "gpt-5.2", "o3-pro"],
+            "each phase tries the configured model, then each escalation model in order"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn peal_verify_failures_go_back_to_the_agent_until_they_pass() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+
+        // Creates `fixed` only when asked to fix a failed verify command (cwd is the repo).
+        let agent = dir.path().join("fixing_agent");
+        std::fs::write(
+            &agent,
+            "#!/bin/sh\ncase \"$*\" in *'verification command'*) echo fix >> fixes.log; touch fixed;; esac\necho ok\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let task = |verify: &str| Task {
+            index: 1,
+            content: "Checked.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: vec!["true".to_owned(), verify.to_owned()],
+            expect: Vec::new(),
+        };
+        let config = test_config(dir.path());
+
+        let outcome =
+            run_all(&agent, &config, &make_plan(vec![task("test -f fixed")]), &mut state, &state_dir, None).unwrap();
+        assert_eq!(outcome.results.len(), 1);
+        assert!(state.completed_task_indices.contains(&1));
+        let fixes = std::fs::read_to_string(dir.path().join("fixes.log")).unwrap();
+        assert_eq!(fixes.lines().count(), 1, "one fix round");
+
+        let mut config = test_config(dir.path());
+        config.max_address_rounds = 2;
+        let err = run_all(&agent, &config, &make_plan(vec![task("test -f never")]), &mut fresh_state(), &state_dir, None)
+            .unwrap_err();
+        assert!(
+            matches!(&err, PealError::TaskVerifyFailed { task_index: 1, rounds: 2, command, .. } if command == "test -f never"),
+            "got: {err:?}"
+        );
+    }
+
+    #[test]
+    fn expectations_fail_the_task_unless_warn() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        std::fs::write(dir.path().join("present.txt"), "hello world\n").unwrap();
+        let echo = resolve_echo();
+        let plan = crate::plan::parse_plan(
+            "## Task 1\nCheck.\nexpect: file present.txt contains \"world\"\nexpect: cmd \"test -f present.txt\" succeeds\n\
+             expect: file exists absent.txt\n",
+        )
+        .unwrap();
+        assert_eq!(plan.tasks[0].expect.len(), 3);
+
+        let err = run_all(&echo, &test_config(dir.path()), &plan, &mut fresh_state(), &state_dir, None).unwrap_err();
+        assert!(
+            matches!(&err, PealError::TaskExpectationFailed { task_index: 1, expectation, detail }
+                if expectation == "file exists absent.txt" && detail == "no such file"),
+            "got: {err:?}"
+        );
+
+        let config = PealConfig { on_expect_fail: "warn".to_owned(), ..test_config(dir.path()) };
+        let mut state = fresh_state();
+        run_all(&echo, &config, &plan, &mut state, &state_dir, None).unwrap();
+        assert!(state.completed_task_indices.contains(&1));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn escalation_gives_up_after_the_last_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let mut state = fresh_state();
+        let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
+
+        let mut config = test_config(dir.path());
+        config.escalation_models = vec!["gpt-5.2".to_owned()];
+
+        let plan = make_plan(vec![Task {
+            index: 1,
+            content: "Impossible.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: Vec::new(),
+            dirs: Vec::new(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }]);
+
+        let err = run_all(&false_path, &config, &plan, &mut state, &state_dir, None).unwrap_err();
+
+        assert!(matches!(err, PealError::PhaseNonZeroExit { phase: 1, .. }), "got: {err:?}");
+        assert!(state.escalated_models.is_empty());
+    }
+
+
+    #[test]
+    #[cfg(unix)]
+    fn rollback_failed_tasks_restores_checkpoint_and_stashes_partial_work() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        let git = |args: &[&str]| {
+            let out = std::process::Command::new("git").arg("-C").arg(&repo).args(args).output();
+            assert!(out.unwrap().status.success(), "git {args:?} failed");
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        std::fs::write(repo.join("tracked.txt"), "original\n").unwrap();
+        git(&["add", "tracked.txt"]);
+        git(&["commit", "-m", "initial"]);
+
+        // Outside the repo so the stash does not pick it up.
+        let agent = dir.path().join("agent");
+        std::fs::write(
+            &agent,
+            "#!/bin/sh\ncase \"$*\" in\n  *Broken*) echo junk > junk.txt; echo mod >> tracked.txt; exit 1;;\n  *) echo ok;;\nesac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = test_config(&repo);
+        config.continue_with_remaining_tasks = true;
+        config.rollback_failed_tasks = true;
+        let state_dir = repo.join(".peal");
+        let mut state = fresh_state();
+        let plan = make_plan(vec![
+            Task { index: 1, content: "Broken.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "Fine.".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+        ]);
+
+        let outcome = run_scheduled(&agent, &config, &plan, &mut state, &state_dir, None).unwrap();
+
+        let indices: Vec<u32> = outcome.results.iter().map(|r| r.task_index).collect();
+        assert_eq!(indices, vec![2]);
+        assert!(!repo.join("junk.txt").exists(), "untracked partial work should be stashed");
+        assert_eq!(std::fs::read_to_string(repo.join("tracked.txt")).unwrap(), "original\n");
+        assert!(state_dir.join("state.json").exists(), "peal state must survive the rollback");
+        let stash = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args(["stash", "list"])
+            .output()
+            .unwrap();
+        let stash = String::from_utf8_lossy(&stash.stdout);
+        assert!(stash.contains("peal: task 1 failed"), "stash list: {stash}");
+    }
+
+
+    #[test]
+    #[cfg(unix)]
+    fn stage_commands_run_after_each_stage_and_gate_the_next() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let echo = resolve_echo();
+        let mut config = test_config(dir.path());
+        config.command_shell = "sh".to_owned();
+        config.stage_commands = vec!["echo {stage} >> stages.log".to_owned()];
+        let plan = crate::plan::parse_plan(
+            "# Stage: One\n## Task 1\nA\n## Task 2\nB\n# Stage: Two\n## Task 3\nC\n",
+        )
+        .unwrap();
+
+        let mut state = fresh_state();
+        let outcome = run_scheduled(&echo, &config, &plan, &mut state, &state_dir, None).unwrap();
+        assert_eq!(outcome.results.len(), 3);
+        let log = std::fs::read_to_string(dir.path().join("stages.log")).unwrap();
+        assert_eq!(log, "One\nTwo\n");
+
+        // A failing barrier stops the run before the next stage starts.
+        config.stage_commands = vec!["test {stage} != One".to_owned()];
+        let mut state = fresh_state();
+        let state_dir = dir.path().join(".peal2");
+        let err = run_scheduled(&echo, &config, &plan, &mut state, &state_dir, None).unwrap_err();
+        assert!(
+            matches!(&err, PealError::StageCommandFailed { stage, .. } if stage == "One"),
+            "{err:?}"
+        );
+        assert!(state.is_task_completed(2));
+        assert!(!state.is_task_completed(3), "stage Two must not start");
+    }
+
+
+    #[test]
+    #[cfg(unix)]
+    fn phase2_hooks_run_around_each_task_per_fail_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let echo = resolve_echo();
+        let mut config = test_config(dir.path());
+        config.command_shell = "sh".to_owned();
+        config.pre_phase2_commands = vec!["echo pre {{TASK_INDEX}} >> hooks.log".to_owned()];
+        config.post_phase2_commands = vec!["echo post {{TASK_INDEX}} >> hooks.log".to_owned()];
+        let plan = crate::plan::parse_plan("## Task 1\nA\n## Task 2\nB\n").unwrap();
+
+        let mut state = fresh_state();
+        let outcome = run_scheduled(&echo, &config, &plan, &mut state, &state_dir, None).unwrap();
+        assert_eq!(outcome.results.len(), 2);
+        let log = std::fs::read_to_string(dir.path().join("hooks.log")).unwrap();
+        assert_eq!(log, "pre 1\npost 1\npre 2\npost 2\n");
+
+        // A failing pre hook fails the task before Phase 2 runs...
+        config.pre_phase2_commands = vec!["test {{TASK_INDEX}} != 1".to_owned()];
+        config.post_phase2_commands.clear();
+        let mut state = fresh_state();
+        let err = run_scheduled(&echo, &config, &plan, &mut state, &dir.path().join(".peal2"), None)
+            .unwrap_err();
+        assert!(
+            matches!(&err, PealError::PhaseHookFailed { task_index: 1, hook: "pre_phase2_commands", .. }),
+            "{err:?}"
+        );
+
+        // ...unless on_phase_hook_fail is "warn".
+        config.on_phase_hook_fail = "warn".to_owned();
+        let mut state = fresh_state();
+        let outcome =
+            run_scheduled(&echo, &config, &plan, &mut state, &dir.path().join(".peal3"), None).unwrap();
+        assert_eq!(outcome.results.len(), 2);
+    }
+
+
+    #[test]
+    fn changed_files_since_lists_task_changes_but_not_peal_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        let git = |args: &[&str]| {
+            let out = std::process::Command::new("git").arg("-C").arg(repo).args(args).output();
+            assert!(out.unwrap().status.success(), "git {args:?} failed");
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        for f in ["keep.txt", "edit.txt", "gone.txt"] {
+            std::fs::write(repo.join(f), "v1\n").unwrap();
+        }
+        git(&["add", "."]);
+        git(&["commit", "-m", "initial"]);
+        let base = vcs::Git::default().head(repo).unwrap();
+
+        std::fs::write(repo.join("committed.txt"), "new\n").unwrap();
+        git(&["add", "committed.txt"]);
+        git(&["commit", "-m", "task commit"]);
+        std::fs::write(repo.join("edit.txt"), "v2\n").unwrap();
+        std::fs::remove_file(repo.join("gone.txt")).unwrap();
+        std::fs::write(repo.join("untracked.txt"), "new\n").unwrap();
+        std::fs::create_dir(repo.join(".peal")).unwrap();
+        std::fs::write(repo.join(".peal").join("state.json"), "{}").unwrap();
+
+        let mut config = test_config(repo);
+        config.plan_path = repo.join("plan.md");
+        std::fs::write(&config.plan_path, "## Task 1\nx\n").unwrap();
+        let files = changed_files_since(&config, &repo.join(".peal"), &base).unwrap();
+        assert_eq!(files, vec!["committed.txt", "edit.txt", "untracked.txt"]);
+
+        config.stet_scope_to_task = true;
+        assert_eq!(task_changed_files(&config, &repo.join(".peal"), 1, None), None);
+        assert!(changed_files_since(&config, &repo.join(".peal"), "not-a-commit").is_none());
+    }
+
+
+    #[test]
+    fn max_agent_calls_stops_the_run_even_when_continuing_past_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join(".peal");
+        let echo = resolve_echo();
+        let mut config = test_config(dir.path());
+        config.continue_with_remaining_tasks = true;
+        // A cap of 0 trips before the first call, whatever other tests have counted.
+        config.max_agent_calls = Some(0);
+        let plan = make_plan(vec![
+            Task { index: 1, content: "A".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+            Task { index: 2, content: "B".to_owned(), parallel: false, tags: Vec::new(), stage: None, after: Vec::new(), context: Vec::new(), dirs: Vec::new(), locks: Vec::new(), verify: Vec::new(), expect: Vec::new() },
+        ]);
+
+        let mut state = fresh_state();
+        let err = run_scheduled(&echo, &config, &plan, &mut state, &state_dir, None).unwrap_err();
+        assert!(matches!(err, PealError::AgentCallCapReached { max: 0 }), "{err:?}");
+        assert!(err.is_agent_failure());
+        assert!(state_dir.join("state.json").exists(), "state is persisted before aborting");
+        assert!(!state.is_task_completed(1));
+    }
 }
@@ -0,0 +1,265 @@
+//! Child process cleanup on every exit path.
+//!
+//! Agent, stet, and hook processes run through [`crate::subprocess`], which records each child
+//! until it has been waited for. Normally none is left when peal exits, but an interrupt, a
+//! termination request, or a panic can stop peal while one is running, and an agent left behind
+//! keeps spending API budget. [`install`] (called first thing by `main`) adds a panic hook and,
+//! on Unix, `SIGINT`/`SIGTERM`/`SIGHUP` handlers that kill the children still running and log
+//! which ones were reaped; `main` calls [`reap_children`] before returning too.
+//!
+//! A run that has called [`arm`] is wound down instead of just dropped: after killing the
+//! children, the signal handler has the runner write the state it has not saved yet (see
+//! [`crate::state::StateStore`]), writes the run summary with status "interrupted" (see [`crate::run_summary::interrupted_summary`]), and
+//! flushes buffered trace spans, all within `shutdown_grace_sec`; then peal exits with
+//! `128 + signal` either way. CI runners send `SIGTERM` shortly before they kill a job that hit
+//! its timeout, and this leaves a resumable state and a summary behind.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, mpsc};
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::config::PealConfig;
+use crate::plan::PlanMetadata;
+use crate::{otel, run_id, run_summary, state, subprocess};
+
+/// What an interrupted run needs to write its summary; set by [`arm`].
+#[derive(Debug, Clone)]
+struct ArmedRun {
+    config: PealConfig,
+    plan_metadata: PlanMetadata,
+    completed_at_start: Vec<u32>,
+}
+
+static ARMED_RUN: Mutex<Option<ArmedRun>> = Mutex::new(None);
+
+/// Have a signal wind the run down (see the module docs). `completed_at_start` are the tasks
+/// already complete in state when the run started, left out of the summary's `tasks_completed`.
+pub fn arm(config: &PealConfig, plan_metadata: &PlanMetadata, completed_at_start: &[u32]) {
+    *ARMED_RUN.lock().unwrap_or_else(|e| e.into_inner()) = Some(ArmedRun {
+        config: config.clone(),
+        plan_metadata: plan_metadata.clone(),
+        completed_at_start: completed_at_start.to_vec(),
+    });
+}
+
+/// A wind-down waiting for the runner's [`state::StateStore`] of this state directory to write
+/// its pending state; the store's next call answers it on the channel.
+static STATE_REQUEST: Mutex<Option<(PathBuf, mpsc::Sender<()>)>> = Mutex::new(None);
+
+/// The pending request of a wind-down for the state of `state_dir`, if any; see [`STATE_REQUEST`].
+pub(crate) fn take_state_request(state_dir: &Path) -> Option<mpsc::Sender<()>> {
+    let mut request = STATE_REQUEST.lock().unwrap_or_else(|e| e.into_inner());
+    match request.take() {
+        Some((dir, written)) if dir == state_dir => Some(written),
+        other => {
+            *request = other;
+            None
+        }
+    }
+}
+
+/// Undo [`arm`]: the run is writing its own summary.
+pub fn disarm() {
+    ARMED_RUN.lock().unwrap_or_else(|e| e.into_inner()).take();
+}
+
+/// Wind down the armed run, if any, after `signal` (e.g. "SIGTERM"): write its state and
+/// summary on a helper thread and wait at most `shutdown_grace_sec` for it.
+fn wind_down(signal: &'static str, exit_code: u8) {
+    let Some(run) = ARMED_RUN.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+    let grace = Duration::from_secs(run.config.shutdown_grace_sec);
+    if grace.is_zero() {
+        return;
+    }
+    let (done, finished) = std::sync::mpsc::channel();
+    let spawned = std::thread::Builder::new().name("peal-wind-down".to_owned()).spawn(move || {
+        let path = write_interrupted(&run, signal, exit_code, grace / 2);
+        let _ = done.send(path);
+    });
+    if spawned.is_err() {
+        return;
+    }
+    match finished.recv_timeout(grace) {
+        Ok(path) => eprintln!("peal: interrupted by {signal}; state saved, run summary at {}", path.display()),
+        Err(_) => eprintln!(
+            "peal: interrupted by {signal}; shutdown_grace_sec ({}s) elapsed before the run summary was written",
+            grace.as_secs()
+        ),
+    }
+}
+
+/// Have the runner write its pending state (waiting at most `state_wait` for it), write the
+/// interrupted run summary (to the configured path and the run directory), and flush logs and
+/// trace spans. Returns the summary path.
+fn write_interrupted(run: &ArmedRun, signal: &str, exit_code: u8, state_wait: Duration) -> PathBuf {
+    let config = &run.config;
+    warn!(signal, "run interrupted; saving state and writing the run summary");
+    let (request, written) = mpsc::channel();
+    *STATE_REQUEST.lock().unwrap_or_else(|e| e.into_inner()) = Some((config.state_dir.clone(), request));
+    if written.recv_timeout(state_wait).is_err() {
+        take_state_request(&config.state_dir);
+        warn!("the runner did not write its pending state in time; summarizing the last saved state");
+    }
+    let saved = state::load_state(&config.state_dir).ok().flatten();
+    let summary = run_summary::interrupted_summary(
+        config,
+        &run.plan_metadata,
+        &run.completed_at_start,
+        saved.as_ref(),
+        signal,
+        exit_code,
+    );
+    let path = run_summary::summary_path(config);
+    run_summary::write_run_summary(&summary, &path);
+    run_summary::write_run_summary(&summary, &run_id::run_dir(&config.state_dir).join("run_summary.json"));
+    otel::flush();
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+    path
+}
+
+/// Install the panic hook and the signal handlers; see the module docs.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        // Worker panics reach the main thread through their scope; a panicking helper thread
+        // (e.g. the status server) does not end the run, so its children are left alone.
+        if std::thread::current().name() == Some("main") {
+            reap_children("panic");
+        }
+    }));
+    #[cfg(unix)]
+    signals::install();
+}
+
+/// Kill the children still running and log each one with `reason`. Returns how many there were.
+pub fn reap_children(reason: &str) -> usize {
+    let leftovers = subprocess::terminate_live_children();
+    crate::container::remove_live();
+    for child in &leftovers {
+        if tracing::dispatcher::has_been_set() {
+            warn!(pid = child.pid, program = %child.program, reason, "killed child process still running at exit");
+        } else {
+            eprintln!("peal: killed child process {} ({}) still running at exit ({reason})", child.pid, child.program);
+        }
+    }
+    leftovers.len()
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::io::Read;
+    use std::os::fd::{AsRawFd, OwnedFd};
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// Write end of the pipe the signal handler reports to; -1 until installed.
+    static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+    const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    /// The handler only writes the signal number to the pipe (async-signal-safe); a watcher
+    /// thread does the cleanup (and winds down an armed run) and exits with `128 + signal`,
+    /// like the default action would.
+    /// Handlers are reset by `exec`, so children still get the default behavior.
+    pub(super) fn install() {
+        let Ok((mut reader, writer)) = std::io::pipe() else {
+            return;
+        };
+        let writer = OwnedFd::from(writer);
+        SIGNAL_PIPE.store(writer.as_raw_fd(), Ordering::SeqCst);
+        // The write end must stay open for the life of the process.
+        std::mem::forget(writer);
+
+        let spawned = std::thread::Builder::new().name("peal-signals".to_owned()).spawn(move || {
+            let mut byte = [0u8; 1];
+            if reader.read_exact(&mut byte).is_ok() {
+                let signal = libc::c_int::from(byte[0]);
+                super::reap_children(&format!("signal {signal}"));
+                super::wind_down(signal_name(signal), (128 + signal) as u8);
+                std::process::exit(128 + signal);
+            }
+        });
+        if spawned.is_err() {
+            return;
+        }
+
+        for signal in SIGNALS {
+            // SAFETY: the handler only calls the async-signal-safe `write`, and `action` is
+            // fully initialized before use.
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                action.sa_flags = libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(signal, &action, std::ptr::null_mut());
+            }
+        }
+    }
+
+    fn signal_name(signal: libc::c_int) -> &'static str {
+        match signal {
+            libc::SIGINT => "SIGINT",
+            libc::SIGTERM => "SIGTERM",
+            _ => "SIGHUP",
+        }
+    }
+
+    extern "C" fn on_signal(signal: libc::c_int) {
+        let byte = signal as u8;
+        // SAFETY: write is async-signal-safe; the fd is the pipe opened in `install`.
+        unsafe {
+            libc::write(SIGNAL_PIPE.load(Ordering::SeqCst), (&byte as *const u8).cast(), 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Commands};
+    use clap::Parser;
+
+    #[test]
+    fn write_interrupted_saves_pending_state_and_an_interrupted_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let Commands::Run(args) = Cli::try_parse_from(["peal", "run", "--plan", "plan.md", "--repo", "."]).unwrap().command else {
+            panic!("expected run command");
+        };
+        let mut config = PealConfig::load(None, &args).unwrap();
+        config.state_dir = dir.path().join(".peal");
+        config.run_summary_path = Some(dir.path().join("out/summary.json"));
+
+        // The runner: task 2's completion waits on a debounced save until the wind-down asks.
+        let mut store = state::StateStore::new(&config.state_dir);
+        let mut peal_state = state::PealState::new(config.plan_path.clone(), config.repo_path.clone());
+        peal_state.mark_task_completed(1);
+        store.save(&peal_state).unwrap();
+        peal_state.mark_task_completed(2);
+        store.save(&peal_state).unwrap();
+        assert!(!state::load_state(&config.state_dir).unwrap().unwrap().is_task_completed(2));
+        let runner = std::thread::spawn(move || {
+            for _ in 0..500 {
+                store.save(&peal_state).unwrap();
+                std::thread::sleep(Duration::from_millis(2));
+            }
+        });
+
+        let run = ArmedRun { config: config.clone(), plan_metadata: PlanMetadata::default(), completed_at_start: vec![1] };
+        let path = write_interrupted(&run, "SIGTERM", 143, Duration::from_secs(5));
+        runner.join().unwrap();
+
+        assert_eq!(path, dir.path().join("out/summary.json"));
+        let summary: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(summary["status"], "interrupted");
+        assert_eq!(summary["interrupted_by"], "SIGTERM");
+        assert_eq!(summary["exit_code"], 143);
+        assert_eq!(summary["tasks_completed"], serde_json::json!([2]));
+        assert!(run_id::run_dir(&config.state_dir).join("run_summary.json").is_file());
+    }
+}
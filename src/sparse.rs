@@ -0,0 +1,667 @@
+//! Sparse worktrees for Phase 2 (`sparse_checkout`).
+//!
+//! With `sparse_checkout`, a task's Phase 2 runs in a git worktree that checks out only the
+//! task's paths ([`task_paths`]) in cone mode, so the agent sees less of a large monorepo and
+//! git has less to scan. The worktree lives under the system temp directory and starts from a
+//! snapshot of the repo's working tree: uncommitted and untracked files are included (the
+//! snapshot is written through a temporary index, so the repo's own index is not touched).
+//! Once the agent is done, the worktree's changes against the snapshot are applied to the repo
+//! with `git apply` and the worktree is removed.
+//!
+//! Tasks without paths, repos not managed by git, and any failure to set up the worktree fall
+//! back to the full checkout with a warning.
+//!
+//! When the patch no longer applies because the repo changed while the agent worked (another
+//! task of a parallel block got there first), the changes are merged file by file instead
+//! (`git merge-file` of the snapshot, the repo, and the worktree). Conflicts that leaves go to
+//! Phase 2.5: the agent is asked, up to `merge_conflict_retry_count` times (at most
+//! [`MAX_MERGE_CONFLICT_ATTEMPTS`]), to resolve the marked hunks in the repo. If markers remain,
+//! the files the merge wrote are restored and the task fails with
+//! `PealError::MergeConflictUnresolved`; a patch that cannot be merged at all (binary files, a
+//! file deleted on one side and changed on the other) or `merge_conflict_retry_count = 0` fails
+//! it with `PealError::SparseApplyFailed`. Either way the patch is kept under the run directory.
+//! When Phase 2 itself fails, its partial changes are only kept as that patch: they are not
+//! applied, so no Phase 2.5 call is spent on an attempt that is thrown away.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Output};
+
+use tracing::{debug, info, warn};
+
+use crate::config::PealConfig;
+use crate::error::PealError;
+use crate::plan::Task;
+use crate::{run_id, vcs};
+
+/// Upper bound on Phase 2.5 attempts, whatever `merge_conflict_retry_count` says.
+pub const MAX_MERGE_CONFLICT_ATTEMPTS: u32 = 3;
+
+/// Lines of context around each conflict in the Phase 2.5 prompt.
+const CONFLICT_CONTEXT_LINES: usize = 3;
+
+/// Cap on the conflicted hunks put in the Phase 2.5 prompt.
+const MAX_CONFLICT_HUNK_BYTES: usize = 64 * 1024;
+
+/// Identity for the snapshot commit; it never leaves the repo's object store.
+const SNAPSHOT_IDENTITY: [&str; 4] = ["-c", "user.name=peal", "-c", "user.email=peal@localhost"];
+
+/// Directories a task's sparse worktree checks out, relative to `repo_path`: its
+/// `(dir: ...)` entries and the directories holding its `(context: ...)` files, in heading
+/// order with repeats dropped. Absolute paths and paths with `..` are skipped, as are context
+/// files at the top level (cone mode always checks those out).
+pub fn task_paths(task: &Task) -> Vec<String> {
+    let context_dirs = task
+        .context
+        .iter()
+        .filter_map(|file| Path::new(file).parent().map(|p| p.to_string_lossy().into_owned()));
+    let mut paths: Vec<String> = Vec::new();
+    for path in task.dirs.iter().cloned().chain(context_dirs) {
+        let path = path.trim_end_matches('/').to_owned();
+        let relative = Path::new(&path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !path.is_empty() && path != "." && relative && !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Path of the patch of a task's sparse Phase 2, kept when it cannot be applied to the repo.
+pub fn patch_artifact_path(state_dir: &Path, task_index: u32) -> PathBuf {
+    run_id::run_dir(state_dir)
+        .join("artifacts")
+        .join(format!("task-{task_index}"))
+        .join("phase2-sparse.patch")
+}
+
+/// Run `run` (a Phase 2 agent call) for `task`: in a sparse worktree when `sparse_checkout` is
+/// on and the task has [`task_paths`], otherwise in the repo. When `run` succeeds, the
+/// worktree's changes are applied to the repo before returning, and conflicts on the way are
+/// handed to `agent_path` (Phase 2.5). When it fails, the changes are only kept as a patch
+/// ([`patch_artifact_path`]) and the error is returned.
+pub fn run_in_sparse_worktree<T>(
+    agent_path: &Path,
+    config: &PealConfig,
+    task: &Task,
+    run: impl FnOnce(&PealConfig) -> Result<T, PealError>,
+) -> Result<T, PealError> {
+    if !config.sparse_checkout {
+        return run(config);
+    }
+    let paths = task_paths(task);
+    if paths.is_empty() {
+        debug!(task_index = task.index, "no (dir: ...) or (context: ...) paths; using the full checkout");
+        return run(config);
+    }
+    let vcs = vcs::for_config(config);
+    if vcs.name() != "git" {
+        warn!(task_index = task.index, vcs = vcs.name(), "sparse_checkout needs git; using the full checkout");
+        return run(config);
+    }
+    let worktree = match SparseWorktree::create(config, task.index, &paths) {
+        Ok(w) => w,
+        Err(detail) => {
+            warn!(
+                task_index = task.index,
+                detail = %detail,
+                "could not set up a sparse worktree; using the full checkout"
+            );
+            return run(config);
+        }
+    };
+    info!(
+        task_index = task.index,
+        worktree = %worktree.dir.display(),
+        paths = ?paths,
+        "phase 2 in a sparse worktree"
+    );
+    let output = match run(&worktree.config(config)) {
+        Ok(output) => output,
+        Err(e) => {
+            match worktree.save_patch(config) {
+                Ok(Some(patch)) => warn!(
+                    task_index = task.index,
+                    patch = %patch.display(),
+                    "phase 2 failed in a sparse worktree; its changes were kept as a patch, not applied"
+                ),
+                Ok(None) => {}
+                Err(save_err) => warn!(task_index = task.index, err = %save_err, "could not keep the failed phase 2's changes"),
+            }
+            return Err(e);
+        }
+    };
+    worktree.apply_to_repo(config, agent_path)?;
+    Ok(output)
+}
+
+/// A sparse worktree of the repo for one task; removed on drop.
+struct SparseWorktree {
+    task_index: u32,
+    /// Top level of the repo's working tree.
+    repo_root: PathBuf,
+    /// `repo_path` relative to `repo_root` (empty or ending in `/`).
+    prefix: String,
+    dir: PathBuf,
+    index_file: PathBuf,
+    /// Snapshot commit the worktree was checked out from.
+    snapshot: String,
+}
+
+impl SparseWorktree {
+    fn create(config: &PealConfig, task_index: u32, paths: &[String]) -> Result<Self, String> {
+        let repo_root = PathBuf::from(git_stdout(&config.repo_path, &["rev-parse", "--show-toplevel"], None)?);
+        let prefix = git_stdout(&config.repo_path, &["rev-parse", "--show-prefix"], None)?;
+        let root = std::env::temp_dir().join(format!("peal-sparse-{}", run_id::current()));
+        fs::create_dir_all(&root).map_err(|e| format!("create {}: {e}", root.display()))?;
+        let dir = root.join(format!("task-{task_index}"));
+        let index_file = root.join(format!("task-{task_index}.index"));
+        let _ = fs::remove_file(&index_file);
+
+        // Snapshot the working tree through a copy of the repo's index, so stat data makes
+        // `git add` fast and the real index stays as it is.
+        let git_index = PathBuf::from(git_stdout(&repo_root, &["rev-parse", "--git-path", "index"], None)?);
+        let git_index = if git_index.is_absolute() { git_index } else { repo_root.join(git_index) };
+        if git_index.exists() {
+            fs::copy(&git_index, &index_file).map_err(|e| format!("copy {}: {e}", git_index.display()))?;
+        }
+        git_stdout(&repo_root, &["add", "-A"], Some(&index_file))?;
+        let tree = git_stdout(&repo_root, &["write-tree"], Some(&index_file))?;
+        let mut commit_tree: Vec<&str> = SNAPSHOT_IDENTITY.to_vec();
+        commit_tree.extend(["commit-tree", &tree, "-p", "HEAD", "-m", "peal sparse snapshot"]);
+        let snapshot = git_stdout(&repo_root, &commit_tree, Some(&index_file))?;
+
+        let worktree = Self { task_index, repo_root, prefix, dir, index_file, snapshot };
+        if worktree.dir.exists() {
+            // Left over from an earlier attempt at this task in the same run.
+            worktree.remove();
+            let _ = fs::remove_dir_all(&worktree.dir);
+        }
+        let dir = worktree.dir.to_string_lossy().into_owned();
+        let add = ["worktree", "add", "--no-checkout", "--detach", &dir, &worktree.snapshot];
+        git_stdout(&worktree.repo_root, &add, None)?;
+        let mut set = vec!["sparse-checkout", "set", "--cone", "--"];
+        let cone: Vec<String> = paths.iter().map(|p| format!("{}{p}", worktree.prefix)).collect();
+        set.extend(cone.iter().map(String::as_str));
+        git_stdout(&worktree.dir, &set, None)?;
+        git_stdout(&worktree.dir, &["checkout", "--detach", &worktree.snapshot], None)?;
+        Ok(worktree)
+    }
+
+    /// `config` with `repo_path` moved into the worktree. The workspace directory is created when
+    /// it lies outside the checked-out paths, so the agent always has somewhere to start.
+    fn config(&self, config: &PealConfig) -> PealConfig {
+        let mut sparse = config.clone();
+        sparse.repo_path = self.dir.join(&self.prefix);
+        let _ = fs::create_dir_all(sparse.work_dir());
+        sparse
+    }
+
+    /// Write the worktree's changes since the snapshot to [`patch_artifact_path`]; `None` when
+    /// there are none.
+    fn save_patch(&self, config: &PealConfig) -> Result<Option<PathBuf>, PealError> {
+        let patch_path = patch_artifact_path(&config.state_dir, self.task_index);
+        let failed = |detail: String| PealError::SparseApplyFailed {
+            task_index: self.task_index,
+            patch: patch_path.clone(),
+            detail,
+        };
+        git_stdout(&self.dir, &["add", "-A"], None).map_err(failed)?;
+        let diff = git(&self.dir, &["diff", "--cached", "--binary", &self.snapshot], None)
+            .map_err(|e| failed(format!("git diff: {e}")))?;
+        if !diff.status.success() {
+            return Err(failed(format!("git diff failed: {}", String::from_utf8_lossy(&diff.stderr).trim())));
+        }
+        if diff.stdout.is_empty() {
+            debug!(task_index = self.task_index, "sparse worktree has no changes");
+            return Ok(None);
+        }
+        patch_path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&patch_path, &diff.stdout))
+            .map_err(|e| failed(format!("write patch: {e}")))?;
+        Ok(Some(patch_path))
+    }
+
+    /// Apply the worktree's changes since the snapshot to the repo's working tree, merging and
+    /// resolving conflicts (Phase 2.5) when the patch does not apply as it is.
+    fn apply_to_repo(&self, config: &PealConfig, agent_path: &Path) -> Result<(), PealError> {
+        let Some(patch_path) = self.save_patch(config)? else {
+            return Ok(());
+        };
+        let failed = |detail: String| PealError::SparseApplyFailed {
+            task_index: self.task_index,
+            patch: patch_path.clone(),
+            detail,
+        };
+        // git runs in the repo, so a relative `state_dir` must not stay relative.
+        let patch = std::path::absolute(&patch_path).unwrap_or_else(|_| patch_path.clone());
+        let patch = patch.to_string_lossy().into_owned();
+        let apply = ["apply", "--binary", "--whitespace=nowarn", &patch];
+        if let Err(detail) = git_stdout(&self.repo_root, &apply, None) {
+            if config.merge_conflict_retry_count == 0 {
+                return Err(failed(detail));
+            }
+            warn!(task_index = self.task_index, detail = %detail, "sparse worktree patch does not apply; merging file by file");
+            let merged = self.merge_into_repo().map_err(|e| failed(format!("{detail}; merge: {e}")))?;
+            self.resolve_conflicts(config, agent_path, &merged, &patch_path)?;
+        }
+        let _ = fs::remove_file(&patch_path);
+        debug!(task_index = self.task_index, "applied sparse worktree changes");
+        Ok(())
+    }
+
+    /// Three-way merge of each file the worktree changed: the snapshot is the base, the repo's
+    /// working tree one side and the worktree the other. Nothing is written unless every file
+    /// could be merged (with or without conflict markers).
+    fn merge_into_repo(&self) -> Result<Merged, String> {
+        let changed = git_stdout(&self.dir, &["diff", "--cached", "--name-status", "--no-renames", &self.snapshot], None)?;
+        let scratch = self.index_file.with_extension("merge");
+        fs::create_dir_all(&scratch).map_err(|e| format!("create {}: {e}", scratch.display()))?;
+        let merged = self.merge_files(&changed, &scratch);
+        let _ = fs::remove_dir_all(&scratch);
+        let (writes, conflicted) = merged?;
+
+        let mut backups = Vec::new();
+        for (path, content) in writes {
+            let target = self.repo_root.join(&path);
+            backups.push((target.clone(), fs::read(&target).ok()));
+            let written = match &content {
+                Some(bytes) => target.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::write(&target, bytes)),
+                None => fs::remove_file(&target),
+            };
+            if let Err(e) = written {
+                restore(&backups);
+                return Err(format!("write {}: {e}", target.display()));
+            }
+        }
+        Ok(Merged { conflicted, backups })
+    }
+
+    /// New contents (`None`: delete) of each changed file, and the paths left with conflicts.
+    fn merge_files(&self, changed: &str, scratch: &Path) -> Result<(Vec<FileWrite>, Vec<String>), String> {
+        let mut writes = Vec::new();
+        let mut conflicted = Vec::new();
+        for line in changed.lines() {
+            let Some((status, path)) = line.split_once('\t') else { continue };
+            let base = match status {
+                "A" => None,
+                _ => Some(git(&self.repo_root, &["show", &format!("{}:{path}", self.snapshot)], None)
+                    .map_err(|e| format!("git show: {e}"))?
+                    .stdout),
+            };
+            let theirs = match status {
+                "D" => None,
+                _ => Some(fs::read(self.dir.join(path)).map_err(|e| format!("read {path} in the worktree: {e}"))?),
+            };
+            let ours = fs::read(self.repo_root.join(path)).ok();
+            if ours == theirs {
+                continue;
+            }
+            if ours == base {
+                writes.push((path.to_owned(), theirs));
+                continue;
+            }
+            let (Some(ours), Some(theirs)) = (ours, theirs) else {
+                return Err(format!("{path} was deleted on one side and changed on the other"));
+            };
+            let files = [("repo", &ours), ("snapshot", &base.unwrap_or_default()), ("task", &theirs)]
+                .map(|(name, bytes)| (name, scratch.join(name), bytes.clone()));
+            for (_, file, bytes) in &files {
+                fs::write(file, bytes).map_err(|e| format!("write {}: {e}", file.display()))?;
+            }
+            let output = Command::new("git")
+                .args(["merge-file", "-p", "-L", "repo", "-L", "snapshot", "-L", "task"])
+                .args(files.iter().map(|(_, file, _)| file))
+                .output()
+                .map_err(|e| format!("git merge-file: {e}"))?;
+            match output.status.code() {
+                // The exit status is the number of conflicts (capped by git); a signal or a
+                // negative status is an error.
+                Some(0) => {}
+                Some(n) if n > 0 => conflicted.push(path.to_owned()),
+                _ => {
+                    return Err(format!(
+                        "{path} cannot be merged: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+            }
+            writes.push((path.to_owned(), Some(output.stdout)));
+        }
+        Ok((writes, conflicted))
+    }
+
+    /// Phase 2.5: have the agent resolve the conflicts `merged` left in the repo. When markers
+    /// remain after the last attempt, the merge is undone.
+    fn resolve_conflicts(
+        &self,
+        config: &PealConfig,
+        agent_path: &Path,
+        merged: &Merged,
+        patch_path: &Path,
+    ) -> Result<(), PealError> {
+        if merged.conflicted.is_empty() {
+            info!(task_index = self.task_index, "merged sparse worktree changes without conflicts");
+            return Ok(());
+        }
+        let attempts = config.merge_conflict_retry_count.min(MAX_MERGE_CONFLICT_ATTEMPTS);
+        let mut remaining = merged.conflicted.clone();
+        for attempt in 1..=attempts {
+            let hunks = self.conflict_hunks(&remaining);
+            // Conflicts are in the repo, so the agent works there, not in the worktree.
+            if let Err(e) = crate::phase::run_phase2_5(agent_path, config, self.task_index, &remaining, &hunks) {
+                if !e.is_retryable_phase_failure() {
+                    restore(&merged.backups);
+                    return Err(e);
+                }
+                warn!(task_index = self.task_index, attempt, err = %e, "phase 2.5 attempt failed");
+            }
+            remaining.retain(|path| has_conflict_markers(&self.repo_root.join(path)));
+            if remaining.is_empty() {
+                info!(task_index = self.task_index, attempt, "phase 2.5 resolved the merge conflicts");
+                return Ok(());
+            }
+            warn!(task_index = self.task_index, attempt, files = ?remaining, "conflict markers remain after phase 2.5");
+        }
+        restore(&merged.backups);
+        Err(PealError::MergeConflictUnresolved {
+            task_index: self.task_index,
+            files: remaining.join(", "),
+            attempts,
+            patch: patch_path.to_path_buf(),
+        })
+    }
+
+    /// The conflicted regions of `files` (repo-relative) with some context, for the prompt.
+    fn conflict_hunks(&self, files: &[String]) -> String {
+        let mut out = String::new();
+        for path in files {
+            let text = fs::read_to_string(self.repo_root.join(path)).unwrap_or_default();
+            for hunk in conflict_hunks(&text, CONFLICT_CONTEXT_LINES) {
+                out.push_str(&format!("{path}:{}\n{}\n", hunk.0, hunk.1));
+            }
+        }
+        if out.len() > MAX_CONFLICT_HUNK_BYTES {
+            let kept = crate::subprocess::truncate_utf8(&out, MAX_CONFLICT_HUNK_BYTES).len();
+            out.truncate(kept);
+            out.push_str("\n[... more conflicts not shown; open the files listed above ...]\n");
+        }
+        out
+    }
+
+    fn remove(&self) {
+        let dir = self.dir.to_string_lossy().into_owned();
+        if let Err(detail) = git_stdout(&self.repo_root, &["worktree", "remove", "--force", &dir], None) {
+            warn!(worktree = %dir, detail = %detail, "failed to remove sparse worktree");
+        }
+    }
+}
+
+impl Drop for SparseWorktree {
+    fn drop(&mut self) {
+        if self.dir.exists() {
+            self.remove();
+        }
+        let _ = fs::remove_file(&self.index_file);
+    }
+}
+
+/// A file's path and its contents to write (`None`: absent).
+type FileWrite<P = String> = (P, Option<Vec<u8>>);
+
+/// Outcome of [`SparseWorktree::merge_into_repo`].
+struct Merged {
+    /// Repo-relative paths written with conflict markers.
+    conflicted: Vec<String>,
+    /// Previous contents (`None`: absent) of every file the merge wrote.
+    backups: Vec<FileWrite<PathBuf>>,
+}
+
+fn restore(backups: &[FileWrite<PathBuf>]) {
+    for (path, content) in backups.iter().rev() {
+        let restored = match content {
+            Some(bytes) => fs::write(path, bytes),
+            None => fs::remove_file(path).or_else(|e| if e.kind() == std::io::ErrorKind::NotFound { Ok(()) } else { Err(e) }),
+        };
+        if let Err(e) = restored {
+            warn!(path = %path.display(), err = %e, "failed to restore file after an unresolved merge");
+        }
+    }
+}
+
+fn is_marker(line: &str, marker: &str) -> bool {
+    line.strip_prefix(marker).is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+fn has_conflict_markers(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|text| text.lines().any(|l| is_marker(l, "<<<<<<<") || is_marker(l, ">>>>>>>")))
+}
+
+/// Each conflict in `text` (from a `<<<<<<<` line to its `>>>>>>>` line) with `context` lines
+/// around it, as (1-based first line, lines). Overlapping regions are joined.
+pub fn conflict_hunks(text: &str, context: usize) -> Vec<(usize, String)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut start = None;
+    for (i, line) in lines.iter().enumerate() {
+        if is_marker(line, "<<<<<<<") {
+            start = Some(i);
+        } else if is_marker(line, ">>>>>>>")
+            && let Some(s) = start.take()
+        {
+            let range = (s.saturating_sub(context), (i + context + 1).min(lines.len()));
+            match ranges.last_mut() {
+                Some(last) if last.1 >= range.0 => last.1 = range.1,
+                _ => ranges.push(range),
+            }
+        }
+    }
+    ranges.into_iter().map(|(from, to)| (from + 1, lines[from..to].join("\n"))).collect()
+}
+
+fn git(dir: &Path, args: &[&str], index_file: Option<&Path>) -> std::io::Result<Output> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).args(args);
+    if let Some(index) = index_file {
+        command.env("GIT_INDEX_FILE", index);
+    }
+    command.output()
+}
+
+/// Run git and return its trimmed stdout, or a message with its stderr on failure.
+fn git_stdout(dir: &Path, args: &[&str], index_file: Option<&Path>) -> Result<String, String> {
+    let output = git(dir, args, index_file).map_err(|e| format!("git {}: {e}", args.join(" ")))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Commands};
+    use clap::Parser;
+
+    fn task(dirs: &[&str], context: &[&str]) -> Task {
+        Task {
+            index: 1,
+            content: "Do it.".to_owned(),
+            parallel: false,
+            tags: Vec::new(),
+            stage: None,
+            after: Vec::new(),
+            context: context.iter().map(|s| s.to_string()).collect(),
+            dirs: dirs.iter().map(|s| s.to_string()).collect(),
+            locks: Vec::new(),
+            verify: Vec::new(),
+            expect: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn task_paths_from_dirs_and_context_directories() {
+        let t = task(&["services/api/", "libs", "/abs", "../up"], &["docs/design.md", "notes.md", "libs/x.rs"]);
+        assert_eq!(task_paths(&t), vec!["services/api", "libs", "docs"]);
+        assert!(task_paths(&task(&[], &["notes.md"])).is_empty());
+    }
+
+    #[test]
+    fn phase2_edits_in_sparse_worktree_land_in_the_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join("a")).unwrap();
+        fs::create_dir_all(repo.join("b")).unwrap();
+        let git = |args: &[&str]| {
+            let out = Command::new("git").arg("-C").arg(&repo).args(args).output().unwrap();
+            assert!(out.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8_lossy(&out.stdout).into_owned()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        fs::write(repo.join("a/x.txt"), "x\n").unwrap();
+        fs::write(repo.join("b/y.txt"), "y\n").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+        // Uncommitted work from earlier tasks.
+        fs::write(repo.join("b/y.txt"), "y2\n").unwrap();
+        fs::write(repo.join("a/earlier.txt"), "earlier\n").unwrap();
+
+        let repo_arg = repo.to_str().unwrap();
+        let argv = ["peal", "run", "--plan", "plan.md", "--repo", repo_arg, "--sparse-checkout"];
+        let Commands::Run(args) = Cli::try_parse_from(argv).unwrap().command else {
+            panic!("expected run command");
+        };
+        let mut config = PealConfig::load(None, &args).unwrap();
+        config.state_dir = dir.path().join(".peal");
+
+        let out = run_in_sparse_worktree(Path::new("agent"), &config, &task(&["a"], &[]), |c| {
+            assert_ne!(c.repo_path, config.repo_path);
+            assert_eq!(fs::read_to_string(c.repo_path.join("a/earlier.txt")).unwrap(), "earlier\n");
+            assert!(!c.repo_path.join("b").exists(), "b/ is outside the sparse paths");
+            fs::write(c.repo_path.join("a/x.txt"), "x changed\n").unwrap();
+            fs::write(c.repo_path.join("a/new.txt"), "new\n").unwrap();
+            Ok(7)
+        })
+        .unwrap();
+        assert_eq!(out, 7);
+        assert_eq!(fs::read_to_string(repo.join("a/x.txt")).unwrap(), "x changed\n");
+        assert_eq!(fs::read_to_string(repo.join("a/new.txt")).unwrap(), "new\n");
+        assert_eq!(fs::read_to_string(repo.join("b/y.txt")).unwrap(), "y2\n");
+        assert_eq!(git(&["diff", "--cached", "--name-only"]), "", "the repo's index is untouched");
+        assert_eq!(git(&["worktree", "list"]).lines().count(), 1, "the worktree is removed");
+
+        // No paths: the full checkout.
+        run_in_sparse_worktree(Path::new("agent"), &config, &task(&[], &[]), |c| {
+            assert_eq!(c.repo_path, config.repo_path);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn conflict_hunks_with_context() {
+        let text = "a\nb\n<<<<<<< repo\nx\n=======\ny\n>>>>>>> task\nc\nd\ne\nf\ng\n<<<<<<< repo\n=======\nz\n>>>>>>> task\n";
+        let hunks = conflict_hunks(text, 1);
+        assert_eq!(
+            hunks,
+            vec![
+                (2, "b\n<<<<<<< repo\nx\n=======\ny\n>>>>>>> task\nc".to_owned()),
+                (12, "g\n<<<<<<< repo\n=======\nz\n>>>>>>> task".to_owned()),
+            ]
+        );
+        assert_eq!(conflict_hunks(text, 3).len(), 1, "overlapping context joins the hunks");
+        assert!(conflict_hunks("<<<<<<<< not a marker\n", 1).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn conflicting_sparse_changes_are_merged_and_resolved_in_phase_2_5() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join("a")).unwrap();
+        let git = |args: &[&str]| {
+            let out = Command::new("git").arg("-C").arg(&repo).args(args).output().unwrap();
+            assert!(out.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&out.stderr));
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test User"]);
+        fs::write(repo.join("a/x.txt"), "one\ntwo\nthree\nfour\nfive\n").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "initial"]);
+
+        let repo_arg = repo.to_str().unwrap();
+        let argv = ["peal", "run", "--plan", "plan.md", "--repo", repo_arg, "--sparse-checkout"];
+        let Commands::Run(args) = Cli::try_parse_from(argv).unwrap().command else {
+            panic!("expected run command");
+        };
+        let mut config = PealConfig::load(None, &args).unwrap();
+        config.state_dir = dir.path().join(".peal");
+        let x = repo.join("a/x.txt");
+        // Phase 2 sets line 2 to `task_line` in the worktree while another task replaces
+        // `repo_line` with "repo" in the repo.
+        let phase2 = |task_line: &str, repo_line: &str| {
+            let x = x.clone();
+            let (task_line, repo_line) = (task_line.to_owned(), repo_line.to_owned());
+            move |c: &PealConfig| {
+                let base = fs::read_to_string(&x).unwrap();
+                let mut lines: Vec<&str> = base.lines().collect();
+                lines[1] = &task_line;
+                fs::write(c.repo_path.join("a/x.txt"), lines.join("\n") + "\n").unwrap();
+                fs::write(&x, base.replace(&repo_line, "repo")).unwrap();
+                Ok(())
+            }
+        };
+
+        // The repo changed another line: merged without the agent.
+        let no_agent = Path::new("/nonexistent/agent");
+        run_in_sparse_worktree(no_agent, &config, &task(&["a"], &[]), phase2("task", "four")).unwrap();
+        assert_eq!(fs::read_to_string(&x).unwrap(), "one\ntask\nthree\nrepo\nfive\n");
+
+        // Same line: the agent resolves the conflict in the repo.
+        let resolver = dir.path().join("resolve.sh");
+        let resolved = "one\\nboth\\nthree\\nrepo\\nfive\\n";
+        fs::write(&resolver, format!("#!/bin/sh\nprintf '{resolved}' > '{}'\n", x.display())).unwrap();
+        fs::set_permissions(&resolver, fs::Permissions::from_mode(0o755)).unwrap();
+        run_in_sparse_worktree(&resolver, &config, &task(&["a"], &[]), phase2("task 2", "task")).unwrap();
+        assert_eq!(fs::read_to_string(&x).unwrap(), "one\nboth\nthree\nrepo\nfive\n");
+
+        // An agent that leaves the markers: the repo's file is restored and the patch kept.
+        let idle = crate::cursor::resolve_agent_cmd("true").unwrap();
+        let err = run_in_sparse_worktree(&idle, &config, &task(&["a"], &[]), phase2("task 3", "both")).unwrap_err();
+        assert!(
+            matches!(&err, PealError::MergeConflictUnresolved { attempts: 1, files, .. } if files == "a/x.txt"),
+            "got: {err:?}"
+        );
+        assert_eq!(fs::read_to_string(&x).unwrap(), "one\nrepo\nthree\nrepo\nfive\n");
+        assert!(patch_artifact_path(&config.state_dir, 1).exists());
+
+        // A failed Phase 2 keeps its changes as a patch and leaves the repo (and the agent) alone.
+        let _ = fs::remove_file(patch_artifact_path(&config.state_dir, 1));
+        let err = run_in_sparse_worktree(&resolver, &config, &task(&["a"], &[]), |c: &PealConfig| {
+            phase2("task 5", "three")(c).unwrap();
+            Err::<(), _>(PealError::PhaseNonZeroExit { phase: 2, exit_code: Some(1), stderr: String::new() })
+        })
+        .unwrap_err();
+        assert!(matches!(err, PealError::PhaseNonZeroExit { .. }), "got: {err:?}");
+        assert_eq!(fs::read_to_string(&x).unwrap(), "one\nrepo\nrepo\nrepo\nfive\n", "only the repo's own edit");
+        assert!(patch_artifact_path(&config.state_dir, 1).exists());
+
+        // Phase 2.5 off: the apply failure fails the task as before.
+        config.merge_conflict_retry_count = 0;
+        let err = run_in_sparse_worktree(&resolver, &config, &task(&["a"], &[]), phase2("task 4", "one")).unwrap_err();
+        assert!(matches!(err, PealError::SparseApplyFailed { .. }), "got: {err:?}");
+    }
+}
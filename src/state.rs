@@ -2,10 +2,15 @@ use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 
 use crate::error::PealError;
+use crate::plan::Task;
+use crate::retries::RetryCounts;
+use crate::timing::{Eta, PhaseDurations};
 
 /// Persistent state for a peal run, serialized to `.peal/state.json`.
 /// In v1, state is persisted as JSON only; PRD §10 permits TOML or JSON.
@@ -13,9 +18,9 @@ use crate::error::PealError;
 /// Tracks which tasks have been completed so that interrupted runs can resume.
 /// This struct is pure data + serialization; file I/O lives in a separate module.
 ///
-/// The optional fields `last_plan_by_task` and `last_completed_ref` are reserved
-/// for future use (e.g. re-running phase 2 without phase 1, or ref-based reporting).
-/// They are not yet populated by the runner.
+/// `last_plan_by_task` holds each task's latest Phase 1 plan text so a later
+/// `peal run --only-phase 2` can run Phase 2 without Phase 1. The optional field
+/// `last_completed_ref` is reserved for future use (e.g. ref-based reporting).
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PealState {
     /// Path to the plan file driving this run.
@@ -24,11 +29,20 @@ pub struct PealState {
     /// Path to the target repository.
     pub repo_path: PathBuf,
 
+    /// `plan_path` and `repo_path` canonicalized (absolute, symlinks resolved) when the state
+    /// was created, so a resume from another directory, or with `./plan.md` instead of an
+    /// absolute path, still matches (see [`PealState::matches_context`]). Absent in states
+    /// written before these fields existed, or when the path did not exist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_plan_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_repo_path: Option<PathBuf>,
+
     /// Sorted, deduplicated indices of successfully completed tasks.
     pub completed_task_indices: Vec<u32>,
 
-    /// Plan text produced by Phase 1, keyed by task index.
-    /// Reserved for future use (e.g. re-run phase 2 without phase 1). Not yet populated by the runner.
+    /// Plan text produced by Phase 1, keyed by task index. Written by the runner after
+    /// each successful Phase 1; read by `--only-phase 2`.
     /// `BTreeMap` keeps JSON keys in deterministic order.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_plan_by_task: Option<BTreeMap<u32, String>>,
@@ -37,26 +51,194 @@ pub struct PealState {
     /// Reserved for future use (e.g. ref-based reporting). Not yet populated by the runner.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_completed_ref: Option<String>,
+
+    /// Synthetic tasks queued by `on_findings_remaining = "create_task"`, run after the
+    /// plan's own tasks (and on resume) under their own indices.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub follow_up_tasks: Vec<FollowUpTask>,
+
+    /// The built-in stet session this run started and has not finished yet. Set right
+    /// after `stet start`, cleared after `stet finish` succeeds; when a later run finds it
+    /// set, `on_stale_stet_session` decides whether to reattach or finish it first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stet_session: Option<StetSession>,
+
+    /// The last `stet finish` that failed, leaving the session open; cleared when a later
+    /// finish succeeds. Reported in the run summary and, at the next run's start, with a
+    /// pointer to `--cleanup-stet`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stet_finish_failure: Option<StetFinishFailure>,
+
+    /// Retries spent per task, summed over every run against this state (see
+    /// [`crate::retries`]). Tasks that never retried are absent.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub task_retries: BTreeMap<u32, RetryCounts>,
+
+    /// Plan normalization retries (`normalize_retry_count`), summed over runs.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub normalization_retries: u32,
+
+    /// Model from `escalation_models` that last succeeded for a task after the configured
+    /// model failed. Tasks that never escalated are absent.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub escalated_models: BTreeMap<u32, String>,
+
+    /// Task indices of each stet session, in run order, when `session_size` splits the plan
+    /// (see [`crate::plan::ParsedPlan::sessions`]). Empty for single-session runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sessions: Vec<Vec<u32>>,
+
+    /// Content hash of each completed task (see [`task_content_hash`]), recorded when it
+    /// completed. On resume, a mismatch means the plan changed under that index, e.g. a task
+    /// was inserted and later tasks renumbered. States written before this field existed
+    /// have no hashes and are trusted.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub task_hashes: BTreeMap<u32, String>,
+
+    /// ID of the latest run against this state (see [`crate::run_id`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run_id: Option<String>,
+
+    /// Time spent in each phase per task, summed over every run against this state (see
+    /// [`crate::timing`]). Feeds the ETA.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub phase_durations: BTreeMap<u32, PhaseDurations>,
+
+    /// Latest estimate of the time left in the run, updated after each task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eta: Option<Eta>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// Stable hash of a task's content (64-bit FNV-1a over the trimmed text, as 16 hex digits).
+/// Unlike `std`'s `DefaultHasher`, the value is the same across Rust versions, so it can be
+/// persisted in state.
+pub fn task_content_hash(content: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in content.trim().bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+/// A stet review session started by peal (`stet start [ref]`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StetSession {
+    /// The ref passed to `stet start`, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_ref: Option<String>,
+    /// RFC 3339 timestamp of the `stet start` call.
+    pub started_at: String,
+}
+
+/// A failed `stet finish` recorded in state (see `PealState::stet_finish_failure`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StetFinishFailure {
+    /// The error, e.g. `stet finish failed: timed out`.
+    pub detail: String,
+    /// RFC 3339 timestamp of the failure.
+    pub failed_at: String,
+    /// Run whose finish failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+}
+
+/// A task appended at run time to address review findings left by another task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FollowUpTask {
+    /// Index of the follow-up task, after every plan task index.
+    pub index: u32,
+    /// Task whose findings remained.
+    pub source_task: u32,
+    /// Task body (instructions plus the stet JSON).
+    pub content: String,
+}
+
+impl StetSession {
+    /// A session started now with `stet start [start_ref]`.
+    pub fn started_now(start_ref: Option<String>) -> Self {
+        Self {
+            start_ref,
+            started_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+impl StetFinishFailure {
+    /// A failure of this run's `stet finish`, timestamped now.
+    pub fn now(detail: String) -> Self {
+        Self {
+            detail,
+            failed_at: chrono::Utc::now().to_rfc3339(),
+            run_id: Some(crate::run_id::current().to_owned()),
+        }
+    }
+}
+
+/// `path` made absolute with symlinks resolved; `None` when it does not exist.
+fn canonical(path: &Path) -> Option<PathBuf> {
+    fs::canonicalize(path).ok()
 }
 
 impl PealState {
     /// Creates a fresh state with no completed tasks.
     pub fn new(plan_path: PathBuf, repo_path: PathBuf) -> Self {
         Self {
+            canonical_plan_path: canonical(&plan_path),
+            canonical_repo_path: canonical(&repo_path),
             plan_path,
             repo_path,
             completed_task_indices: Vec::new(),
             last_plan_by_task: None,
             last_completed_ref: None,
+            follow_up_tasks: Vec::new(),
+            stet_session: None,
+            stet_finish_failure: None,
+            task_retries: BTreeMap::new(),
+            normalization_retries: 0,
+            escalated_models: BTreeMap::new(),
+            sessions: Vec::new(),
+            task_hashes: BTreeMap::new(),
+            last_run_id: None,
+            phase_durations: BTreeMap::new(),
+            eta: None,
         }
     }
 
-    /// Returns `true` if the loaded state matches the current plan and repo paths.
+    /// Returns `true` if the loaded state matches the current plan and repo paths: the paths
+    /// are equal as given, or they resolve to the same files. The stored side uses the
+    /// canonical paths recorded at creation, falling back to canonicalizing the raw ones (for
+    /// older states).
     ///
     /// A mismatch means the state was produced by a different run and should
     /// be discarded rather than used for resume.
     pub fn matches_context(&self, plan_path: &Path, repo_path: &Path) -> bool {
-        self.plan_path == plan_path && self.repo_path == repo_path
+        if self.plan_path == plan_path && self.repo_path == repo_path {
+            return true;
+        }
+        let stored = |recorded: &Option<PathBuf>, raw: &Path| recorded.clone().or_else(|| canonical(raw));
+        match (canonical(plan_path), canonical(repo_path)) {
+            (Some(plan), Some(repo)) => {
+                stored(&self.canonical_plan_path, &self.plan_path) == Some(plan)
+                    && stored(&self.canonical_repo_path, &self.repo_path) == Some(repo)
+            }
+            _ => false,
+        }
+    }
+
+    /// Fill in `canonical_plan_path` / `canonical_repo_path` when the state predates them,
+    /// resolving the raw paths from the current directory. Called after a resume matched.
+    pub fn record_canonical_paths(&mut self) {
+        if self.canonical_plan_path.is_none() {
+            self.canonical_plan_path = canonical(&self.plan_path);
+        }
+        if self.canonical_repo_path.is_none() {
+            self.canonical_repo_path = canonical(&self.repo_path);
+        }
     }
 
     /// Returns `true` if the given task index has been marked completed.
@@ -71,18 +253,130 @@ impl PealState {
         }
     }
 
+    /// Records the content hash of a completed task, checked on resume by
+    /// [`PealState::changed_completed_tasks`].
+    pub fn record_task_hash(&mut self, index: u32, content: &str) {
+        self.task_hashes.insert(index, task_content_hash(content));
+    }
+
+    /// Completed tasks in `tasks` whose recorded content hash differs from their current
+    /// content, in ascending order. Tasks without a recorded hash are not reported.
+    pub fn changed_completed_tasks(&self, tasks: &[Task]) -> Vec<u32> {
+        tasks
+            .iter()
+            .filter(|t| self.is_task_completed(t.index))
+            .filter(|t| {
+                self.task_hashes
+                    .get(&t.index)
+                    .is_some_and(|recorded| *recorded != task_content_hash(&t.content))
+            })
+            .map(|t| t.index)
+            .collect()
+    }
+
+    /// Un-marks every completed task for which `reset` returns true, so the next
+    /// resume reruns it. Returns the indices that were reset, in ascending order.
+    pub fn reset_tasks(&mut self, reset: impl Fn(u32) -> bool) -> Vec<u32> {
+        let (removed, kept): (Vec<u32>, Vec<u32>) = self
+            .completed_task_indices
+            .iter()
+            .partition(|&&index| reset(index));
+        self.completed_task_indices = kept;
+        for index in &removed {
+            self.task_hashes.remove(index);
+        }
+        removed
+    }
+
+    /// Records the Phase 1 plan text for a task, replacing any earlier plan.
+    pub fn record_plan_text(&mut self, index: u32, plan_text: &str) {
+        self.last_plan_by_task
+            .get_or_insert_with(BTreeMap::new)
+            .insert(index, plan_text.to_owned());
+    }
+
+    /// Returns the last recorded Phase 1 plan text for a task, if any.
+    pub fn plan_text_for(&self, index: u32) -> Option<&str> {
+        self.last_plan_by_task
+            .as_ref()
+            .and_then(|m| m.get(&index))
+            .map(String::as_str)
+    }
+
+    /// Adds `counts` to the running retry totals for a task. Empty counts are ignored.
+    pub fn record_retries(&mut self, index: u32, counts: RetryCounts) {
+        if counts.total() > 0 {
+            self.task_retries.entry(index).or_default().add(counts);
+        }
+    }
+
+    /// Adds `durations` to the time recorded for a task. Empty durations are ignored.
+    pub fn record_phase_durations(&mut self, index: u32, durations: PhaseDurations) {
+        if durations.total_ms() > 0 {
+            self.phase_durations.entry(index).or_default().add(durations);
+        }
+    }
+
+    /// Records that `model` (from `escalation_models`) succeeded for a task.
+    pub fn record_escalated_model(&mut self, index: u32, model: &str) {
+        self.escalated_models.insert(index, model.to_owned());
+    }
+
+    /// Queues a follow-up task for `source_task` at index `next_index`, or refreshes the
+    /// content of its pending (not completed) follow-up. Returns the follow-up's index.
+    pub fn queue_follow_up(&mut self, source_task: u32, next_index: u32, content: String) -> u32 {
+        let completed = self.completed_task_indices.clone();
+        if let Some(existing) = self
+            .follow_up_tasks
+            .iter_mut()
+            .find(|f| f.source_task == source_task && completed.binary_search(&f.index).is_err())
+        {
+            existing.content = content;
+            return existing.index;
+        }
+        self.follow_up_tasks.push(FollowUpTask {
+            index: next_index,
+            source_task,
+            content,
+        });
+        next_index
+    }
+
+    /// Returns `true` if `index` is a follow-up task.
+    pub fn is_follow_up(&self, index: u32) -> bool {
+        self.follow_up_tasks.iter().any(|f| f.index == index)
+    }
+
     /// Returns the canonical state file path within the given state directory.
     pub fn state_file_path(state_dir: &Path) -> PathBuf {
         state_dir.join("state.json")
     }
+
+    /// Copy of the previous state file, refreshed by each [`save_state`]; used by
+    /// [`load_state`] when `state.json` is corrupt.
+    pub fn backup_file_path(state_dir: &Path) -> PathBuf {
+        state_dir.join("state.json.bak")
+    }
 }
 
 /// Load persisted state from `state_dir/state.json`.
 ///
-/// Returns `Ok(None)` if the file does not exist or contains invalid JSON
-/// (the latter also emits a warning to stderr). Returns `Err` on unexpected
+/// Returns `Ok(None)` if the file does not exist. A corrupt file (invalid JSON, e.g. a
+/// truncated write or a merge conflict) is moved aside to `state.json.corrupt-<UTC time>` with
+/// a warning, and the last good copy in `state.json.bak` (see [`save_state`]) is used instead;
+/// `Ok(None)` (start fresh) when that is missing or corrupt too. Returns `Err` on unexpected
 /// I/O errors such as permission denied.
 pub fn load_state(state_dir: &Path) -> Result<Option<PealState>, PealError> {
+    load_state_with(state_dir, false)
+}
+
+/// Like [`load_state`], but a corrupt state file is left in place and returned as
+/// `PealError::StateCorrupt` (`strict_state`).
+pub fn load_state_strict(state_dir: &Path) -> Result<Option<PealState>, PealError> {
+    load_state_with(state_dir, true)
+}
+
+fn load_state_with(state_dir: &Path, strict: bool) -> Result<Option<PealState>, PealError> {
     let path = PealState::state_file_path(state_dir);
 
     let contents = match fs::read_to_string(&path) {
@@ -98,27 +392,142 @@ pub fn load_state(state_dir: &Path) -> Result<Option<PealState>, PealError> {
 
     match serde_json::from_str::<PealState>(&contents) {
         Ok(state) => Ok(Some(state)),
+        Err(e) if strict => Err(PealError::StateCorrupt {
+            path,
+            detail: e.to_string(),
+        }),
+        Err(e) => Ok(recover_corrupt_state(state_dir, &path, &e)),
+    }
+}
+
+/// Move the corrupt state file at `path` aside and fall back to `state.json.bak`.
+fn recover_corrupt_state(state_dir: &Path, path: &Path, err: &serde_json::Error) -> Option<PealState> {
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ");
+    let moved = state_dir.join(format!("state.json.corrupt-{stamp}"));
+    let moved_to = match fs::rename(path, &moved) {
+        Ok(()) => moved.display().to_string(),
         Err(e) => {
-            eprintln!(
-                "warning: ignoring invalid state file {}: {e}",
-                path.display()
-            );
+            tracing::warn!(path = %path.display(), err = %e, "could not move corrupt state file aside");
+            path.display().to_string()
+        }
+    };
+    eprintln!("warning: state file {} is corrupt ({err}); kept as {moved_to}", path.display());
+    tracing::warn!(path = %path.display(), kept_as = %moved_to, %err, "corrupt state file moved aside");
+
+    let backup_path = PealState::backup_file_path(state_dir);
+    let backup = fs::read_to_string(&backup_path)
+        .ok()
+        .and_then(|c| serde_json::from_str::<PealState>(&c).ok());
+    match backup {
+        Some(state) => {
+            eprintln!("warning: recovered state from {}", backup_path.display());
             tracing::warn!(
-                path = %path.display(),
-                err = %e,
-                "ignoring invalid state file"
+                backup = %backup_path.display(),
+                completed = state.completed_task_indices.len(),
+                "recovered state from backup"
             );
-            Ok(None)
+            Some(state)
+        }
+        None => {
+            eprintln!("warning: no usable state backup; starting fresh");
+            tracing::warn!(backup = %backup_path.display(), "no usable state backup; starting fresh");
+            None
         }
     }
 }
 
+/// Serializes [`save_state`] calls: concurrent Phase 3 threads (`parallel_phase3`) may save at
+/// the same time and would otherwise share the temporary file.
+static SAVE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Minimum time between two writes by [`StateStore::save`].
+pub const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Writes of one run's `state.json`, owned by the runner: routine progress (task completions,
+/// retries, phase times, the ETA) goes through [`StateStore::save`], which writes at most every
+/// [`STATE_SAVE_INTERVAL`] and otherwise only marks the store dirty; failures, the end of the
+/// run, and the like use [`StateStore::save_now`] or [`StateStore::flush`].
+///
+/// A run being wound down by a signal (see [`crate::shutdown`]) cannot reach the runner's
+/// `PealState`, so it asks for it instead: the store's next call writes the dirty state before
+/// answering.
+#[derive(Debug)]
+pub struct StateStore {
+    dir: PathBuf,
+    last_write: Option<Instant>,
+    dirty: bool,
+}
+
+impl StateStore {
+    pub fn new(state_dir: &Path) -> Self {
+        Self {
+            dir: state_dir.to_path_buf(),
+            last_write: None,
+            dirty: false,
+        }
+    }
+
+    /// The state directory this store writes to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write `state` when the last write was at least [`STATE_SAVE_INTERVAL`] ago; else mark the
+    /// store dirty for the next write.
+    pub fn save(&mut self, state: &PealState) -> Result<(), PealError> {
+        let recent = self.last_write.is_some_and(|at| at.elapsed() < STATE_SAVE_INTERVAL);
+        let result = if recent {
+            self.dirty = true;
+            Ok(())
+        } else {
+            self.write(state)
+        };
+        self.answer_wind_down(state);
+        result
+    }
+
+    /// Write `state` right away.
+    pub fn save_now(&mut self, state: &PealState) -> Result<(), PealError> {
+        let result = self.write(state);
+        self.answer_wind_down(state);
+        result
+    }
+
+    /// Write `state` when a [`StateStore::save`] was skipped since the last write.
+    pub fn flush(&mut self, state: &PealState) -> Result<(), PealError> {
+        let result = if self.dirty { self.write(state) } else { Ok(()) };
+        self.answer_wind_down(state);
+        result
+    }
+
+    fn write(&mut self, state: &PealState) -> Result<(), PealError> {
+        save_state(state, &self.dir)?;
+        self.last_write = Some(Instant::now());
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn answer_wind_down(&mut self, state: &PealState) {
+        let Some(written) = crate::shutdown::take_state_request(&self.dir) else {
+            return;
+        };
+        if self.dirty
+            && let Err(e) = self.write(state)
+        {
+            tracing::warn!(%e, "failed to write pending state");
+        }
+        let _ = written.send(());
+    }
+}
+
 /// Persist state to `state_dir/state.json`.
 ///
 /// Creates `state_dir` if it does not exist. Writes to a temporary file in
 /// the same directory and renames for atomicity; falls back to direct write
-/// if the rename fails (e.g. cross-device).
+/// if the rename fails (e.g. cross-device). The file being replaced is first copied to
+/// `state.json.bak` (best-effort) for [`load_state`] recovery.
 pub fn save_state(state: &PealState, state_dir: &Path) -> Result<(), PealError> {
+    let _save = SAVE_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
     let path = PealState::state_file_path(state_dir);
 
     fs::create_dir_all(state_dir).map_err(|e| PealError::StateWriteFailed {
@@ -152,6 +561,10 @@ pub fn save_state(state: &PealState, state_dir: &Path) -> Result<(), PealError>
 
     write_result?;
 
+    if path.is_file() {
+        let _ = fs::copy(&path, PealState::backup_file_path(state_dir));
+    }
+
     // Atomic rename; fall back to direct write on failure.
     if fs::rename(&tmp_path, &path).is_err() {
         fs::write(&path, &json).map_err(|e| PealError::StateWriteFailed {
@@ -174,6 +587,35 @@ mod tests {
         PealState::new(PathBuf::from("plan.md"), PathBuf::from("/repo"))
     }
 
+    #[test]
+    fn store_saves_wait_for_the_interval_and_flush_writes_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let completed = |i: u32| load_state(dir.path()).unwrap().unwrap().is_task_completed(i);
+        let mut store = StateStore::new(dir.path());
+        let mut state = sample_state();
+        store.save(&state).unwrap();
+        assert!(load_state(dir.path()).unwrap().is_some(), "the first save writes");
+
+        state.mark_task_completed(1);
+        store.save(&state).unwrap();
+        assert!(!completed(1), "a save within the interval waits");
+        store.flush(&state).unwrap();
+        assert!(completed(1));
+
+        state.mark_task_completed(2);
+        store.flush(&state).unwrap();
+        assert!(!completed(2), "nothing dirty, nothing written");
+        store.save_now(&state).unwrap();
+        assert!(completed(2), "save_now always writes");
+
+        state.mark_task_completed(3);
+        store.save(&state).unwrap();
+        store.save_now(&state).unwrap();
+        state.mark_task_completed(4);
+        store.flush(&state).unwrap();
+        assert!(!completed(4), "save_now leaves the store clean");
+    }
+
     #[test]
     fn new_state_has_empty_defaults() {
         let state = sample_state();
@@ -210,6 +652,26 @@ mod tests {
         assert!(!state.matches_context(Path::new("plan.md"), Path::new("/other")));
     }
 
+    #[test]
+    fn matches_context_accepts_other_spellings_of_the_same_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join("sub")).unwrap();
+        fs::write(repo.join("plan.md"), "## Task 1\nx\n").unwrap();
+
+        let state = PealState::new(repo.join("plan.md"), repo.clone());
+        assert_eq!(state.canonical_repo_path, Some(fs::canonicalize(&repo).unwrap()));
+        assert!(state.matches_context(&repo.join("sub/../plan.md"), &repo.join("sub/..")));
+        assert!(!state.matches_context(&repo.join("sub/../plan.md"), &repo.join("sub")));
+
+        // A state recorded with relative paths still matches after the run moves elsewhere.
+        let mut relative = state.clone();
+        relative.plan_path = PathBuf::from("./plan.md");
+        relative.repo_path = PathBuf::from(".");
+        assert!(relative.matches_context(&repo.join("plan.md"), &repo));
+        assert!(!relative.matches_context(&repo.join("other.md"), &repo));
+    }
+
     #[test]
     fn is_task_completed_reflects_marks() {
         let mut state = sample_state();
@@ -285,6 +747,38 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn load_state_moves_corrupt_file_aside_and_recovers_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = sample_state();
+        state.mark_task_completed(1);
+        save_state(&state, dir.path()).unwrap();
+        state.mark_task_completed(2);
+        save_state(&state, dir.path()).unwrap();
+        fs::write(dir.path().join("state.json"), "<<<<<<< HEAD\n{\"plan_path\"").unwrap();
+
+        let recovered = load_state(dir.path()).unwrap().expect("backup should be used");
+        assert_eq!(recovered.completed_task_indices, vec![1], "backup holds the previous save");
+        assert!(!dir.path().join("state.json").exists());
+        let kept: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .filter(|n| n.starts_with("state.json.corrupt-"))
+            .collect();
+        assert_eq!(kept.len(), 1, "corrupt file kept for inspection");
+    }
+
+    #[test]
+    fn load_state_strict_errors_and_leaves_corrupt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("state.json");
+        fs::write(&state_file, "{\"plan_path\": ").unwrap();
+
+        let err = load_state_strict(dir.path()).unwrap_err();
+        assert!(matches!(err, PealError::StateCorrupt { .. }), "got: {err:?}");
+        assert!(state_file.exists());
+    }
+
     #[test]
     fn load_state_roundtrips_saved_state() {
         let dir = tempfile::tempdir().unwrap();
@@ -341,4 +835,128 @@ mod tests {
         let parsed: PealState = serde_json::from_str(&raw).expect("valid JSON");
         assert_eq!(parsed, state);
     }
+
+
+    #[test]
+    fn record_plan_text_replaces_previous() {
+        let mut state = sample_state();
+        assert_eq!(state.plan_text_for(1), None);
+
+        state.record_plan_text(1, "first plan");
+        state.record_plan_text(1, "second plan");
+        state.record_plan_text(3, "other plan");
+
+        assert_eq!(state.plan_text_for(1), Some("second plan"));
+        assert_eq!(state.plan_text_for(3), Some("other plan"));
+        assert_eq!(state.plan_text_for(2), None);
+    }
+
+
+    #[test]
+    fn reset_tasks_uncompletes_matching_indices() {
+        let mut state = sample_state();
+        for i in [1, 2, 4, 7, 9] {
+            state.mark_task_completed(i);
+        }
+
+        assert_eq!(state.reset_tasks(|i| i == 4 || i == 7 || i == 5), vec![4, 7]);
+        assert_eq!(state.completed_task_indices, vec![1, 2, 9]);
+
+        assert_eq!(state.reset_tasks(|i| i >= 2), vec![2, 9]);
+        assert_eq!(state.completed_task_indices, vec![1]);
+        assert!(state.reset_tasks(|i| i >= 2).is_empty());
+    }
+
+    #[test]
+    fn changed_completed_tasks_detects_renumbered_content() {
+        let before = crate::plan::parse_plan("## Task 1\nA\n\n## Task 2\nB\n").unwrap();
+        let mut state = sample_state();
+        for task in &before.tasks {
+            state.mark_task_completed(task.index);
+            state.record_task_hash(task.index, &task.content);
+        }
+        assert!(state.changed_completed_tasks(&before.tasks).is_empty());
+
+        // A task inserted at the top shifts A and B to indices 2 and 3.
+        let after =
+            crate::plan::parse_plan("## Task 1\nNew\n\n## Task 2\nA\n\n## Task 3\nB\n").unwrap();
+        assert_eq!(state.changed_completed_tasks(&after.tasks), vec![1, 2]);
+
+        // Completed tasks without a recorded hash (older state files) are trusted.
+        state.task_hashes.remove(&1);
+        assert_eq!(state.changed_completed_tasks(&after.tasks), vec![2]);
+
+        state.reset_tasks(|i| i == 2);
+        assert!(!state.task_hashes.contains_key(&2));
+    }
+
+    #[test]
+    fn task_content_hash_is_stable_and_ignores_surrounding_whitespace() {
+        assert_eq!(task_content_hash("A"), "af63fc4c860222ec");
+        assert_eq!(task_content_hash("\nA\n"), task_content_hash("A"));
+        assert_ne!(task_content_hash("A"), task_content_hash("B"));
+    }
+
+
+    #[test]
+    fn queue_follow_up_refreshes_pending_and_adds_after_completion() {
+        let mut state = sample_state();
+        assert_eq!(state.queue_follow_up(1, 5, "first".to_owned()), 5);
+        assert_eq!(state.queue_follow_up(1, 6, "second".to_owned()), 5, "pending follow-up is reused");
+        assert_eq!(state.follow_up_tasks.len(), 1);
+        assert_eq!(state.follow_up_tasks[0].content, "second");
+        assert!(state.is_follow_up(5));
+        assert!(!state.is_follow_up(1));
+
+        state.mark_task_completed(5);
+        assert_eq!(state.queue_follow_up(1, 6, "third".to_owned()), 6);
+        assert_eq!(state.follow_up_tasks.len(), 2);
+    }
+
+    #[test]
+    fn state_without_follow_ups_omits_field_and_loads_old_files() {
+        let json = serde_json::to_string(&sample_state()).unwrap();
+        assert!(!json.contains("follow_up_tasks"));
+        let loaded: PealState = serde_json::from_str(
+            r#"{"plan_path":"plan.md","repo_path":"/repo","completed_task_indices":[1]}"#,
+        )
+        .unwrap();
+        assert!(loaded.follow_up_tasks.is_empty());
+    }
+
+
+    #[test]
+    fn record_retries_accumulates_and_skips_empty_counts() {
+        let mut state = sample_state();
+        let counts = RetryCounts { phase1: 1, phase2: 0, phase3: 0, stet: 1 };
+        state.record_retries(2, counts);
+        state.record_retries(2, RetryCounts { phase2: 2, ..Default::default() });
+        state.record_retries(3, RetryCounts::default());
+
+        assert_eq!(state.task_retries.len(), 1);
+        assert_eq!(
+            state.task_retries[&2],
+            RetryCounts { phase1: 1, phase2: 2, phase3: 0, stet: 1 }
+        );
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains(r#""task_retries":{"2":"#));
+        assert!(!json.contains("normalization_retries"));
+        let loaded: PealState = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, state);
+    }
+
+    #[test]
+    fn escalated_models_round_trip_and_are_omitted_when_empty() {
+        let mut state = sample_state();
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(!json.contains("escalated_models"));
+
+        state.record_escalated_model(2, "gpt-5.2");
+        state.record_escalated_model(2, "o3-pro");
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains(r#""escalated_models":{"2":"o3-pro"}"#));
+        let loaded: PealState = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, state);
+    }
 }
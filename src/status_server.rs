@@ -0,0 +1,610 @@
+//! Live run status over HTTP (`serve_status`).
+//!
+//! When `serve_status` is set (e.g. `127.0.0.1:7777`), peal listens on that address for the
+//! duration of the run. [`StatusLayer`] watches the run, task, and phase tracing spans and
+//! feeds a shared [`StatusHub`]; the server answers two requests:
+//!
+//! - `GET /status`: a JSON document with the persisted state (`state.json`) and the tasks and
+//!   phases running right now.
+//! - `GET /events`: a Server-Sent Events stream of `task_started`, `phase_started`,
+//!   `phase_finished`, `task_finished`, and `run_finished` events. Buffered events are replayed
+//!   on connect (from `Last-Event-ID` when the client sends one).
+//!
+//! The `/status` run object and every event payload carry the run's `run_id` (see
+//! [`crate::run_id`]). The run object also has the latest `eta` from state (see
+//! [`crate::timing`]), `null` until a task has been timed.
+//!
+//! The server speaks just enough HTTP/1.1 for `curl` and `EventSource` (one thread per
+//! connection, `Connection: close`, no TLS or auth), so bind it to loopback. It is best-effort:
+//! a client that goes away never affects the run.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde_json::{Value, json};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::state;
+
+/// Events kept for replay to clients that connect late.
+const MAX_BUFFERED_EVENTS: usize = 1000;
+
+/// Interval between SSE keep-alive comments while nothing happens.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Read timeout for a request head; also bounds writes to a stalled client.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request head accepted.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// One entry of the `/events` stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusEvent {
+    /// Increasing id, sent as the SSE `id:` (starts at 1).
+    pub id: u64,
+    /// SSE event name, e.g. `task_started`.
+    pub kind: &'static str,
+    /// JSON payload, sent as the SSE `data:`.
+    pub data: Value,
+}
+
+/// A task that is running now, and its current phase.
+#[derive(Debug)]
+struct ActiveTask {
+    /// A number, or `"2.5"` for merge-conflict resolution.
+    phase: Option<Value>,
+    step: Option<String>,
+    started: Instant,
+}
+
+#[derive(Debug)]
+struct Inner {
+    started_at: String,
+    finished: bool,
+    active: BTreeMap<u32, ActiveTask>,
+    next_id: u64,
+    events: VecDeque<StatusEvent>,
+}
+
+/// Shared run status: written by [`StatusLayer`], read by the HTTP server.
+#[derive(Debug)]
+pub struct StatusHub {
+    state_dir: PathBuf,
+    inner: Mutex<Inner>,
+    changed: Condvar,
+}
+
+impl StatusHub {
+    /// New hub; `/status` reads the persisted state from `state_dir`.
+    pub fn new(state_dir: PathBuf) -> Arc<Self> {
+        Arc::new(Self {
+            state_dir,
+            inner: Mutex::new(Inner {
+                started_at: chrono::Utc::now().to_rfc3339(),
+                finished: false,
+                active: BTreeMap::new(),
+                next_id: 1,
+                events: VecDeque::new(),
+            }),
+            changed: Condvar::new(),
+        })
+    }
+
+    /// Tracing layer that reports run, task, and phase spans to this hub.
+    pub fn layer(self: &Arc<Self>) -> StatusLayer {
+        StatusLayer { hub: Arc::clone(self) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Buffer an event; every payload also carries the run ID.
+    fn emit(&self, inner: &mut Inner, kind: &'static str, mut data: Value) {
+        if let Value::Object(fields) = &mut data {
+            fields.insert("run_id".to_owned(), json!(crate::run_id::current()));
+        }
+        let id = inner.next_id;
+        inner.next_id += 1;
+        if inner.events.len() == MAX_BUFFERED_EVENTS {
+            inner.events.pop_front();
+        }
+        inner.events.push_back(StatusEvent { id, kind, data });
+        self.changed.notify_all();
+    }
+
+    fn task_started(&self, task_index: u32) {
+        let mut inner = self.lock();
+        inner.active.insert(
+            task_index,
+            ActiveTask { phase: None, step: None, started: Instant::now() },
+        );
+        self.emit(&mut inner, "task_started", json!({ "task_index": task_index }));
+    }
+
+    fn task_finished(&self, task_index: u32, duration: Duration, error: Option<&str>) {
+        let mut inner = self.lock();
+        inner.active.remove(&task_index);
+        let data = json!({
+            "task_index": task_index,
+            "outcome": if error.is_some() { "failed" } else { "ok" },
+            "error": error,
+            "duration_ms": duration_ms(duration),
+        });
+        self.emit(&mut inner, "task_finished", data);
+    }
+
+    fn phase_started(&self, task_index: Option<u32>, phase: Value, step: Option<&str>) {
+        let mut inner = self.lock();
+        if let Some(task) = task_index.and_then(|i| inner.active.get_mut(&i)) {
+            task.phase = Some(phase.clone());
+            task.step = step.map(str::to_owned);
+        }
+        let data = json!({ "task_index": task_index, "phase": phase, "step": step });
+        self.emit(&mut inner, "phase_started", data);
+    }
+
+    fn phase_finished(&self, task_index: Option<u32>, phase: Value, step: Option<&str>, d: Duration) {
+        let mut inner = self.lock();
+        if let Some(task) = task_index.and_then(|i| inner.active.get_mut(&i))
+            && task.phase.as_ref() == Some(&phase)
+        {
+            task.phase = None;
+            task.step = None;
+        }
+        let data = json!({
+            "task_index": task_index,
+            "phase": phase,
+            "step": step,
+            "duration_ms": duration_ms(d),
+        });
+        self.emit(&mut inner, "phase_finished", data);
+    }
+
+    fn run_finished(&self, duration: Duration) {
+        let mut inner = self.lock();
+        inner.finished = true;
+        inner.active.clear();
+        self.emit(&mut inner, "run_finished", json!({ "duration_ms": duration_ms(duration) }));
+    }
+
+    /// The `/status` document: run timing and ETA, running tasks, and the persisted state
+    /// (`null` before the first save).
+    pub fn status_json(&self) -> Value {
+        let (mut run, active, last_event_id) = {
+            let inner = self.lock();
+            let active: Vec<Value> = inner
+                .active
+                .iter()
+                .map(|(index, task)| {
+                    json!({
+                        "task_index": index,
+                        "phase": task.phase,
+                        "step": task.step,
+                        "elapsed_ms": duration_ms(task.started.elapsed()),
+                    })
+                })
+                .collect();
+            let run = json!({
+                "run_id": crate::run_id::current(),
+                "started_at": inner.started_at,
+                "finished": inner.finished,
+            });
+            (run, active, inner.next_id - 1)
+        };
+        let state = match state::load_state(&self.state_dir) {
+            Ok(Some(s)) => serde_json::to_value(s).unwrap_or(Value::Null),
+            Ok(None) | Err(_) => Value::Null,
+        };
+        run["eta"] = state.get("eta").cloned().unwrap_or(Value::Null);
+        json!({ "run": run, "active": active, "state": state, "last_event_id": last_event_id })
+    }
+
+    /// Events with an id greater than `after`, waiting up to `timeout` for one to arrive.
+    /// The flag is true once the run has finished.
+    pub fn events_after(&self, after: u64, timeout: Duration) -> (Vec<StatusEvent>, bool) {
+        let pending = |inner: &Inner| inner.next_id - 1 <= after && !inner.finished;
+        let inner = self.lock();
+        let (inner, _) = self
+            .changed
+            .wait_timeout_while(inner, timeout, |i| pending(i))
+            .unwrap_or_else(|e| e.into_inner());
+        let events = inner.events.iter().filter(|e| e.id > after).cloned().collect();
+        (events, inner.finished)
+    }
+}
+
+fn duration_ms(d: Duration) -> u64 {
+    u64::try_from(d.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Bind `addr` and serve `/status` and `/events` from `hub` on a background thread for the rest
+/// of the process. Returns the bound address (useful with port 0).
+pub fn serve(addr: SocketAddr, hub: Arc<StatusHub>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    std::thread::Builder::new()
+        .name("peal-status".to_owned())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let hub = Arc::clone(&hub);
+                // A failed spawn only drops this connection.
+                let _ = std::thread::Builder::new()
+                    .name("peal-status-conn".to_owned())
+                    .spawn(move || {
+                        // Client errors (disconnects, timeouts) are not the run's problem.
+                        let _ = handle_connection(stream, &hub);
+                    });
+            }
+        })?;
+    Ok(local)
+}
+
+/// Request line and the one header the server cares about.
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    last_event_id: Option<u64>,
+}
+
+fn parse_request(head: &str) -> Option<Request> {
+    let mut lines = head.lines();
+    let mut parts = lines.next()?.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let target = parts.next()?;
+    let path = target.split('?').next().unwrap_or(target).to_owned();
+    let last_event_id = lines
+        .filter_map(|l| l.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("last-event-id"))
+        .and_then(|(_, value)| value.trim().parse().ok());
+    Some(Request { method, path, last_event_id })
+}
+
+fn read_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "request head too large"));
+        }
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+fn handle_connection(mut stream: TcpStream, hub: &StatusHub) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let head = read_head(&mut stream)?;
+    let Some(request) = parse_request(&head) else {
+        return respond(&mut stream, "400 Bad Request", "text/plain", "bad request\n");
+    };
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => {
+            let body = format!("{}\n", hub.status_json());
+            respond(&mut stream, "200 OK", "application/json", &body)
+        }
+        ("GET", "/events") => stream_events(&mut stream, hub, request.last_event_id.unwrap_or(0)),
+        ("GET", _) => {
+            respond(&mut stream, "404 Not Found", "text/plain", "try /status or /events\n")
+        }
+        _ => respond(&mut stream, "405 Method Not Allowed", "text/plain", "GET only\n"),
+    }
+}
+
+fn stream_events(stream: &mut TcpStream, hub: &StatusHub, mut cursor: u64) -> io::Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+          Connection: close\r\n\r\n",
+    )?;
+    loop {
+        let (events, finished) = hub.events_after(cursor, KEEP_ALIVE);
+        if events.is_empty() && !finished {
+            stream.write_all(b": keep-alive\n\n")?;
+        }
+        for event in &events {
+            write!(stream, "{}", SseFrame(event))?;
+            cursor = event.id;
+        }
+        stream.flush()?;
+        if finished {
+            return Ok(());
+        }
+    }
+}
+
+/// One event in SSE wire format.
+struct SseFrame<'a>(&'a StatusEvent);
+
+impl fmt::Display for SseFrame<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "id: {}\nevent: {}\ndata: {}\n\n", self.0.id, self.0.kind, self.0.data)
+    }
+}
+
+/// Which peal span a [`SpanInfo`] describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpanKind {
+    Run,
+    Task,
+    Phase,
+}
+
+/// Per-span state stored in the registry's span extensions.
+#[derive(Debug)]
+struct SpanInfo {
+    kind: SpanKind,
+    task_index: Option<u32>,
+    phase: Option<Value>,
+    step: Option<String>,
+    start: Instant,
+    /// Message of the last ERROR event inside a task span: the task failed.
+    error: Option<String>,
+}
+
+/// Records the span fields the status server reports.
+struct FieldVisitor<'a>(&'a mut SpanInfo);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let value = u32::try_from(value).ok();
+        match field.name() {
+            "task_index" => self.0.task_index = value,
+            "phase" => self.0.phase = value.map(Value::from),
+            _ => {}
+        }
+    }
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record_u64(field, u64::try_from(value).unwrap_or(u64::MAX));
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "step" => self.0.step = Some(value.to_owned()),
+            "phase" => self.0.phase = Some(Value::from(value)),
+            _ => {}
+        }
+    }
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Extracts the `message` field of an event.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = value.to_owned();
+        }
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+/// Tracing layer feeding a [`StatusHub`] from the `run`, `task`, and `phase` spans.
+#[derive(Debug, Clone)]
+pub struct StatusLayer {
+    hub: Arc<StatusHub>,
+}
+
+impl<S> Layer<S> for StatusLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let kind = match attrs.metadata().name() {
+            "run" => SpanKind::Run,
+            "task" => SpanKind::Task,
+            "phase" => SpanKind::Phase,
+            _ => return,
+        };
+        let Some(span) = ctx.span(id) else { return };
+        let mut info = SpanInfo {
+            kind,
+            task_index: None,
+            phase: None,
+            step: None,
+            start: Instant::now(),
+            error: None,
+        };
+        attrs.record(&mut FieldVisitor(&mut info));
+        if info.task_index.is_none() {
+            // Triage phases carry no task_index; take the enclosing task's.
+            info.task_index = span.scope().skip(1).find_map(|s| {
+                s.extensions().get::<SpanInfo>().filter(|i| i.kind == SpanKind::Task)?.task_index
+            });
+        }
+        match (kind, info.task_index, &info.phase) {
+            (SpanKind::Task, Some(index), _) => self.hub.task_started(index),
+            (SpanKind::Phase, task_index, Some(phase)) => {
+                self.hub.phase_started(task_index, phase.clone(), info.step.as_deref());
+            }
+            _ => {}
+        }
+        span.extensions_mut().insert(info);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        let Some(scope) = ctx.event_scope(event) else { return };
+        for span in scope {
+            let mut extensions = span.extensions_mut();
+            if let Some(info) = extensions.get_mut::<SpanInfo>()
+                && info.kind == SpanKind::Task
+            {
+                let mut message = MessageVisitor::default();
+                event.record(&mut message);
+                info.error = Some(message.0);
+                return;
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(info) = span.extensions_mut().remove::<SpanInfo>() else {
+            return;
+        };
+        let elapsed = info.start.elapsed();
+        match (info.kind, info.task_index, info.phase) {
+            (SpanKind::Run, _, _) => self.hub.run_finished(elapsed),
+            (SpanKind::Task, Some(index), _) => {
+                self.hub.task_finished(index, elapsed, info.error.as_deref());
+            }
+            (SpanKind::Phase, task_index, Some(phase)) => {
+                self.hub.phase_finished(task_index, phase, info.step.as_deref(), elapsed);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn kinds(events: &[StatusEvent]) -> Vec<&'static str> {
+        events.iter().map(|e| e.kind).collect()
+    }
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn layer_reports_task_and_phase_spans_with_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let hub = StatusHub::new(dir.path().to_path_buf());
+        let subscriber = tracing_subscriber::registry().with(hub.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _run = tracing::debug_span!("run").entered();
+            {
+                let _task = tracing::debug_span!("task", task_index = 1u32).entered();
+                let _phase =
+                    tracing::debug_span!("phase", phase = 2u32, task_index = 1u32).entered();
+                let status = hub.status_json();
+                assert_eq!(status["active"][0]["task_index"], 1);
+                assert_eq!(status["active"][0]["phase"], 2);
+            }
+            {
+                let _task = tracing::debug_span!("task", task_index = 2u32).entered();
+                let _triage =
+                    tracing::debug_span!("phase", phase = 3u32, step = "triage").entered();
+                tracing::error!("phase 3 failed");
+            }
+        });
+
+        let (events, finished) = hub.events_after(0, Duration::ZERO);
+        assert!(finished);
+        assert_eq!(
+            kinds(&events),
+            vec![
+                "task_started", "phase_started", "phase_finished", "task_finished",
+                "task_started", "phase_started", "phase_finished", "task_finished",
+                "run_finished",
+            ]
+        );
+        assert_eq!(events[3].data["outcome"], "ok");
+        assert_eq!(events[5].data["task_index"], 2, "triage inherits the task index");
+        assert_eq!(events[5].data["step"], "triage");
+        assert_eq!(events[7].data["outcome"], "failed");
+        assert_eq!(events[7].data["error"], "phase 3 failed");
+        assert_eq!(events[7].data["run_id"], crate::run_id::current());
+        assert!(hub.status_json()["active"].as_array().unwrap().is_empty());
+
+        let (later, _) = hub.events_after(7, Duration::ZERO);
+        assert_eq!(kinds(&later), vec!["task_finished", "run_finished"]);
+    }
+
+    #[test]
+    fn parse_request_reads_path_and_last_event_id() {
+        let head = "GET /events?x=1 HTTP/1.1\r\nHost: a\r\nLast-Event-ID: 42\r\n\r\n";
+        assert_eq!(
+            parse_request(head),
+            Some(Request {
+                method: "GET".to_owned(),
+                path: "/events".to_owned(),
+                last_event_id: Some(42),
+            })
+        );
+        assert_eq!(parse_request(""), None);
+    }
+
+    #[test]
+    fn server_serves_status_and_event_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut peal_state = state::PealState::new(
+            dir.path().join("plan.md"),
+            dir.path().to_path_buf(),
+        );
+        peal_state.mark_task_completed(1);
+        peal_state.eta = Some(crate::timing::Eta {
+            remaining_tasks: 2,
+            remaining_sec: 90,
+            estimated_at: "2026-01-01T00:00:00Z".to_owned(),
+        });
+        state::save_state(&peal_state, dir.path()).unwrap();
+
+        let hub = StatusHub::new(dir.path().to_path_buf());
+        hub.task_started(2);
+        hub.phase_started(Some(2), json!(1), None);
+        let addr = serve("127.0.0.1:0".parse().unwrap(), Arc::clone(&hub)).unwrap();
+
+        let status = get(addr, "/status");
+        assert!(status.starts_with("HTTP/1.1 200 OK\r\n"), "{status}");
+        let body: Value = serde_json::from_str(status.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["active"][0]["task_index"], 2);
+        assert_eq!(body["active"][0]["phase"], 1);
+        assert_eq!(body["state"]["completed_task_indices"], json!([1]));
+        assert_eq!(body["run"]["finished"], false);
+        assert_eq!(body["run"]["run_id"], crate::run_id::current());
+        assert_eq!(body["run"]["eta"]["remaining_sec"], 90);
+
+        hub.run_finished(Duration::from_millis(5));
+        let events = get(addr, "/events");
+        assert!(events.contains("Content-Type: text/event-stream"), "{events}");
+        let run_id = crate::run_id::current();
+        assert!(events.contains(&format!(
+            "id: 1\nevent: task_started\ndata: {{\"run_id\":\"{run_id}\",\"task_index\":2}}\n\n"
+        )), "{events}");
+        assert!(events.contains("event: run_finished"), "{events}");
+
+        assert!(get(addr, "/nope").starts_with("HTTP/1.1 404"));
+    }
+}
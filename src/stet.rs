@@ -14,23 +14,42 @@
 //! - Top-level array `[...]` — backward compatibility.
 //! - Object with alternate key for findings list (e.g. `"issues"`) — non-empty array treated as findings present.
 //!
-//! Peal uses `stet run --output=json` without `--stream`, so stdout is a single JSON object (or array).
-//! Streaming NDJSON is not used.
-
+//! By default peal uses `stet run --output=json` without `--stream`, so stdout is a single JSON
+//! object (or array). With `stet_stream`, peal passes `--stream` and reads NDJSON instead: each
+//! line is one finding object (optionally wrapped as `{"finding": {...}}`), and a line holding a
+//! findings array (final summary) replaces what was collected. The findings are logged as they
+//! arrive and re-assembled into `{"findings": [...]}` so the rest of phase 3 sees the canonical shape.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, IsTerminal, Write};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::sync::{Mutex, MutexGuard};
 use std::time::Duration;
 
-use tracing::{debug, info, warn};
+use tracing::{debug, debug_span, info, warn};
 
 use crate::config::{PealConfig, StetDismissPattern, STET_DISMISS_REASONS};
 use crate::cursor::is_executable;
 use crate::error::PealError;
+use crate::findings_trace::FindingsTrace;
 use crate::phase::{self, PhaseOutput};
+use crate::state::StetSession;
 use crate::subprocess;
 
 const STET_BINARY: &str = "stet";
 
+/// Held for the duration of each `stet run` and `stet dismiss` (built-in or custom command).
+/// With `parallel_phase3` several address loops run at once; stet itself works on one
+/// session per repo, so its invocations take turns while the agent calls overlap.
+static STET_LOCK: Mutex<()> = Mutex::new(());
+
+/// Wait for this thread's turn to invoke stet. A panic while holding the lock does not block
+/// later callers.
+fn stet_turn() -> MutexGuard<'static, ()> {
+    STET_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
 /// Keys (in order) used to locate the findings array in a JSON object. Canonical stet key first.
 const FINDINGS_ARRAY_KEYS: &[&str] = &["findings", "issues"];
 
@@ -96,6 +115,76 @@ pub struct StetOutput {
     pub stderr: String,
 }
 
+/// Placeholders `stet_start_ref` may contain, resolved once per run by [`resolve_start_ref`].
+pub const START_REF_PLACEHOLDERS: [&str; 2] = ["{{BASE_BRANCH}}", "{{MERGE_BASE}}"];
+
+/// Branches tried, in order, for `{{BASE_BRANCH}}` when `origin/HEAD` is not set.
+const BASE_BRANCH_CANDIDATES: [&str; 4] = ["origin/main", "origin/master", "main", "master"];
+
+/// Whether `stet_start_ref` needs [`resolve_start_ref`] (it contains a `{{...}}` placeholder).
+pub fn start_ref_is_template(start_ref: &str) -> bool {
+    start_ref.contains("{{")
+}
+
+/// Resolve the placeholders of a `stet_start_ref` template with git in `repo_path`:
+///
+/// - `{{BASE_BRANCH}}`: the branch `origin/HEAD` points to (e.g. `origin/main`), else the first
+///   of `origin/main`, `origin/master`, `main`, `master` that exists.
+/// - `{{MERGE_BASE}}`: `git merge-base HEAD <base branch>`, so the review covers everything the
+///   current branch added since it forked.
+///
+/// Any other `{{...}}` is an error, as is a placeholder git cannot resolve.
+pub fn resolve_start_ref(template: &str, repo_path: &Path) -> Result<String, PealError> {
+    let unresolved = |detail: String| PealError::StetStartRefUnresolved {
+        template: template.to_owned(),
+        detail,
+    };
+    let mut resolved = template.to_owned();
+    if START_REF_PLACEHOLDERS.iter().any(|placeholder| template.contains(placeholder)) {
+        let base = find_base_branch(repo_path).map_err(unresolved)?;
+        if resolved.contains("{{MERGE_BASE}}") {
+            let merge_base = git_output(repo_path, &["merge-base", "HEAD", &base]).map_err(unresolved)?;
+            resolved = resolved.replace("{{MERGE_BASE}}", &merge_base);
+        }
+        resolved = resolved.replace("{{BASE_BRANCH}}", &base);
+    }
+    if let Some(start) = resolved.find("{{") {
+        let rest = &resolved[start..];
+        let unknown = rest.find("}}").map_or(rest, |end| &rest[..end + 2]);
+        return Err(unresolved(format!(
+            "unknown placeholder {unknown} (expected {})",
+            START_REF_PLACEHOLDERS.join(" or ")
+        )));
+    }
+    Ok(resolved)
+}
+
+fn find_base_branch(repo_path: &Path) -> Result<String, String> {
+    if let Ok(head) = git_output(repo_path, &["symbolic-ref", "--quiet", "--short", "refs/remotes/origin/HEAD"]) {
+        return Ok(head);
+    }
+    BASE_BRANCH_CANDIDATES
+        .into_iter()
+        .find(|branch| git_output(repo_path, &["rev-parse", "--verify", "--quiet", &format!("{branch}^{{commit}}")]).is_ok())
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            format!(
+                "no base branch: origin/HEAD is not set (`git remote set-head origin --auto`) and none of {} exists",
+                BASE_BRANCH_CANDIDATES.join(", ")
+            )
+        })
+}
+
+fn git_output(repo_path: &Path, args: &[&str]) -> Result<String, String> {
+    let result = subprocess::run_command("git", args, repo_path, None).map_err(|e| format!("git {}: {e}", args.join(" ")))?;
+    let stdout = result.stdout.trim();
+    if result.success() && !stdout.is_empty() {
+        Ok(stdout.to_owned())
+    } else {
+        Err(format!("git {} failed: {}", args.join(" "), result.stderr.trim()))
+    }
+}
+
 /// Start a stet review session by invoking `stet start [ref] [extra_args...]`.
 ///
 /// - `stet_path`: absolute path to the stet binary.
@@ -110,6 +199,7 @@ pub fn start_session(
     repo_path: &Path,
     timeout: Option<Duration>,
 ) -> Result<StetOutput, PealError> {
+    let _span = debug_span!("stet", command = "start").entered();
     let stet_str = stet_path.to_string_lossy();
     let mut args: Vec<String> = vec!["start".to_owned()];
     if let Some(r) = start_ref {
@@ -124,7 +214,7 @@ pub fn start_session(
         "invoking stet start"
     );
 
-    let result = subprocess::run_command(&stet_str, &args, repo_path, timeout).map_err(|e| {
+    let result = crate::container::run_stet(&stet_str, &args, repo_path, timeout).map_err(|e| {
         PealError::StetStartFailed {
             detail: format!("spawn failed: {e}"),
         }
@@ -174,6 +264,7 @@ pub fn finish_session(
     repo_path: &Path,
     timeout: Option<Duration>,
 ) -> Result<StetOutput, PealError> {
+    let _span = debug_span!("stet", command = "finish").entered();
     let stet_str = stet_path.to_string_lossy();
     let args: Vec<String> = vec!["finish".to_owned()];
 
@@ -183,7 +274,7 @@ pub fn finish_session(
         "invoking stet finish"
     );
 
-    let result = subprocess::run_command(&stet_str, &args, repo_path, timeout).map_err(|e| {
+    let result = crate::container::run_stet(&stet_str, &args, repo_path, timeout).map_err(|e| {
         PealError::StetFinishFailed {
             detail: format!("spawn failed: {e}"),
         }
@@ -223,6 +314,141 @@ pub fn finish_session(
     })
 }
 
+/// Expand the placeholders of a `stet_commands` or `stet_finish_commands` entry:
+/// `{{REPO}}` (`repo_path`), `{{STATE_DIR}}`, `{{RUN_ID}}`, `{{START_REF}}` (`stet_start_ref`, as
+/// resolved by [`resolve_start_ref`] at run start; empty when unset), and `{{TASK_INDEX}}` (the
+/// task under review; empty outside a task, i.e. at session start and finish).
+pub fn expand_command(template: &str, config: &PealConfig, task_index: Option<u32>) -> String {
+    template
+        .replace("{{REPO}}", &config.repo_path.to_string_lossy())
+        .replace("{{STATE_DIR}}", &config.state_dir.to_string_lossy())
+        .replace("{{RUN_ID}}", crate::run_id::current())
+        .replace("{{START_REF}}", config.stet_start_ref.as_deref().unwrap_or(""))
+        .replace("{{TASK_INDEX}}", &task_index.map(|i| i.to_string()).unwrap_or_default())
+}
+
+/// End a custom-commands session: run each `stet_finish_commands` entry (expanded, see
+/// [`expand_command`]) in `repo_path`, in order. Stops at the first failure with
+/// `PealError::StetFinishFailed`; the caller treats it as best-effort like `stet finish`.
+pub fn finish_session_via_commands(config: &PealConfig) -> Result<(), PealError> {
+    let _span = debug_span!("stet", command = "finish", custom = true).entered();
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    for template in &config.stet_finish_commands {
+        let command = expand_command(template, config, None);
+        info!(command = %command.trim(), "running stet_finish_commands entry");
+        let failed = |detail: String| PealError::StetFinishFailed {
+            detail: format!("{}: {detail}", command.trim()),
+        };
+        match crate::container::run_stet_string(&command, &config.command_shell, &config.repo_path, timeout) {
+            None => {}
+            Some(Err(e)) => return Err(failed(format!("spawn failed: {e}"))),
+            Some(Ok(r)) if r.timed_out => return Err(failed("timed out".to_owned())),
+            Some(Ok(r)) if !r.success() => {
+                return Err(failed(format!("exit code {:?}: {}", r.exit_code, r.stderr.trim())));
+            }
+            Some(Ok(_)) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Deal with a stet session that an earlier run started but never finished (recorded in state).
+///
+/// With `on_stale_stet_session = "reattach"` returns `true`: the caller skips `stet start` and
+/// keeps using that session. Otherwise (`"finish"`) runs `stet finish` first, best-effort, and
+/// returns `false` so a fresh session is started.
+pub fn settle_stale_session(stet_path: &Path, config: &PealConfig, stale: &StetSession) -> bool {
+    if config.on_stale_stet_session == "reattach" {
+        info!(
+            start_ref = stale.start_ref.as_deref().unwrap_or(""),
+            started_at = %stale.started_at,
+            "reattaching to stet session left unfinished by an earlier run"
+        );
+        return true;
+    }
+    warn!(
+        start_ref = stale.start_ref.as_deref().unwrap_or(""),
+        started_at = %stale.started_at,
+        "stet session left unfinished by an earlier run; running stet finish before starting a new one"
+    );
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    if let Err(e) = finish_session(stet_path, &config.repo_path, timeout) {
+        warn!(%e, "stet finish of stale session failed (best-effort)");
+    }
+    false
+}
+
+/// Result of [`ensure_session`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionHealth {
+    /// No `stet_health_command`, or the check could not run or timed out.
+    Unchecked,
+    /// The check passed.
+    Alive,
+    /// The check failed and `stet start` ran again.
+    Restarted,
+}
+
+/// Before a task's Phase 3 with built-in stet: run `stet_health_command` and, when it exits
+/// non-zero (the session is gone, e.g. its worktree was cleaned up or someone ran
+/// `stet finish`), run `stet start` again, once more after a failure when `on_stet_fail` is
+/// `"retry_once"`. A check that cannot be spawned or times out is logged and the task goes
+/// on. Holds the stet turn, so with `parallel_phase3` one task restarts the session and the
+/// others find it alive.
+pub fn ensure_session(stet_path: &Path, config: &PealConfig) -> Result<SessionHealth, PealError> {
+    let Some(command) = config.stet_health_command.as_deref() else {
+        return Ok(SessionHealth::Unchecked);
+    };
+    let _turn = stet_turn();
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    let result = match crate::container::run_stet_string(
+        command,
+        &config.command_shell,
+        &config.repo_path,
+        timeout,
+    ) {
+        None => return Ok(SessionHealth::Unchecked),
+        Some(Err(e)) => {
+            warn!(command, err = %e, "stet health check could not run; assuming the session is alive");
+            return Ok(SessionHealth::Unchecked);
+        }
+        Some(Ok(r)) if r.timed_out => {
+            warn!(command, "stet health check timed out; assuming the session is alive");
+            return Ok(SessionHealth::Unchecked);
+        }
+        Some(Ok(r)) => r,
+    };
+    if result.success() {
+        debug!(command, "stet session is alive");
+        return Ok(SessionHealth::Alive);
+    }
+    warn!(
+        command,
+        exit_code = ?result.exit_code,
+        "stet health check failed; the stet session is gone, starting a new one"
+    );
+    let start = || {
+        start_session(
+            stet_path,
+            config.stet_start_ref.as_deref(),
+            &config.stet_start_extra_args,
+            &config.repo_path,
+            timeout,
+        )
+    };
+    match start() {
+        Ok(_) => {}
+        Err(e) if config.on_stet_fail == "retry_once" => {
+            warn!(err = %e, "stet start failed, retrying once");
+            crate::retries::record(crate::retries::RetryKind::Stet);
+            start()?;
+        }
+        Err(e) => return Err(e),
+    }
+    info!("stet session restarted");
+    Ok(SessionHealth::Restarted)
+}
+
 /// Captured output from a `stet run` invocation, including the findings heuristic result.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StetRunResult {
@@ -249,7 +475,8 @@ pub fn run_result_from_output(stdout: String, stderr: String, exit_code: Option<
 pub enum StetPhase3Mode {
     /// Use the stet binary at the given path; start/run/finish use built-in sequence.
     BuiltIn(PathBuf),
-    /// Use custom commands: session start = all commands run once by main; per-task run = last command only; no built-in finish.
+    /// Use custom commands: session start = all commands run once by main; per-task run = last
+    /// command only; finish = `stet_finish_commands`. Entries are expanded by [`expand_command`].
     CustomCommands(Vec<String>),
 }
 
@@ -267,6 +494,8 @@ pub fn run_review(
     extra_args: &[String],
     timeout: Option<Duration>,
 ) -> Result<StetRunResult, PealError> {
+    let _span = debug_span!("stet", command = "run").entered();
+    let _turn = stet_turn();
     let stet_str = stet_path.to_string_lossy();
     let mut args: Vec<String> = vec!["run".to_owned(), "--output=json".to_owned()];
     args.extend(extra_args.iter().cloned());
@@ -277,7 +506,7 @@ pub fn run_review(
         "invoking stet run"
     );
 
-    let result = subprocess::run_command(&stet_str, &args, repo_path, timeout).map_err(|e| {
+    let result = crate::container::run_stet(&stet_str, &args, repo_path, timeout).map_err(|e| {
         PealError::StetRunFailed {
             detail: format!("spawn failed: {e}"),
         }
@@ -293,8 +522,7 @@ pub fn run_review(
     let has_findings = detect_findings(result.exit_code, &result.stdout);
 
     if !has_findings && !result.stdout.is_empty() {
-        let snippet_len = result.stdout.len().min(400);
-        let snippet = &result.stdout[..snippet_len];
+        let snippet = crate::subprocess::truncate_utf8(&result.stdout, 400);
         debug!(
             stet_stdout_snippet = %snippet,
             "stet run completed with no findings; snippet of stdout for format debugging"
@@ -317,21 +545,170 @@ pub fn run_review(
     })
 }
 
+/// `stet run` arguments limiting the review to `files` (`stet_scope_to_task`): each entry of
+/// `template` is repeated per file with `{path}` replaced by that file's path.
+pub fn path_filter_args(template: &[String], files: &[String]) -> Vec<String> {
+    files
+        .iter()
+        .flat_map(|file| template.iter().map(move |arg| arg.replace("{path}", file)))
+        .collect()
+}
+
+/// Run `stet run` the way `config` asks for: streamed NDJSON when `stet_stream` is set
+/// (see [`run_review_streaming`]), otherwise a single JSON document via [`run_review`].
+pub fn run_review_for_config(
+    stet_path: &Path,
+    config: &PealConfig,
+    timeout: Option<Duration>,
+) -> Result<StetRunResult, PealError> {
+    if config.stet_stream {
+        run_review_streaming(
+            stet_path,
+            &config.repo_path,
+            &config.stet_run_extra_args,
+            timeout,
+            config.stet_stream_abort_threshold,
+        )
+    } else {
+        run_review(stet_path, &config.repo_path, &config.stet_run_extra_args, timeout)
+    }
+}
+
+/// Returns the finding object carried by one NDJSON line, if any: either the line itself
+/// (an object with an `id`) or its `"finding"` member.
+fn streamed_finding(value: &serde_json::Value) -> Option<&serde_json::Value> {
+    let obj = value.as_object()?;
+    if obj.contains_key("id") {
+        return Some(value);
+    }
+    obj.get("finding").filter(|f| f.get("id").is_some())
+}
+
+/// Run `stet run --output=json --stream [extra_args...]` and consume NDJSON findings as they arrive.
+///
+/// Each finding is logged at info level when its line is read. When `abort_threshold` is
+/// `Some(n)` and more than `n` findings have streamed in, stet is killed and the findings
+/// collected so far are returned (with `exit_code: None`). The returned stdout is the
+/// collected findings as `{"findings": [...]}`; when no line was JSON (e.g. stet without
+/// `--stream` support printed a human summary) the raw stdout is kept and
+/// [`detect_findings`] applies as usual. Spawn failures and timeouts produce
+/// `PealError::StetRunFailed`.
+pub fn run_review_streaming(
+    stet_path: &Path,
+    repo_path: &Path,
+    extra_args: &[String],
+    timeout: Option<Duration>,
+    abort_threshold: Option<u32>,
+) -> Result<StetRunResult, PealError> {
+    let _span = debug_span!("stet", command = "run", stream = true).entered();
+    let _turn = stet_turn();
+    let stet_str = stet_path.to_string_lossy();
+    let mut args: Vec<String> = vec![
+        "run".to_owned(),
+        "--output=json".to_owned(),
+        "--stream".to_owned(),
+    ];
+    args.extend(extra_args.iter().cloned());
+
+    info!(
+        stet = %stet_str,
+        cwd = %repo_path.display(),
+        abort_threshold = ?abort_threshold,
+        "invoking stet run (streaming)"
+    );
+
+    let mut findings: Vec<serde_json::Value> = Vec::new();
+    let mut saw_json = false;
+    let (result, aborted) =
+        crate::container::run_stet_streaming(&stet_str, &args, repo_path, timeout, |line| {
+            let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+                if !line.trim().is_empty() {
+                    debug!(line, "stet stream: ignoring non-JSON line");
+                }
+                return ControlFlow::Continue(());
+            };
+            saw_json = true;
+            if let Some(finding) = streamed_finding(&value) {
+                let field = |key: &str| finding.get(key).and_then(|v| v.as_str()).unwrap_or("");
+                info!(
+                    id = field("id"),
+                    file = if field("file").is_empty() { field("path") } else { field("file") },
+                    line = ?finding.get("line").and_then(|v| v.as_u64()),
+                    severity = field("severity"),
+                    message = field("message"),
+                    "stet finding"
+                );
+                findings.push(finding.clone());
+            } else if let Some(all) = findings_array_from_value(&value) {
+                findings = all.clone();
+            } else {
+                debug!(line, "stet stream: ignoring non-finding event");
+            }
+            match abort_threshold {
+                Some(n) if findings.len() > n as usize => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        })
+        .map_err(|e| PealError::StetRunFailed {
+            detail: format!("spawn failed: {e}"),
+        })?;
+
+    if result.timed_out {
+        warn!("stet run timed out");
+        return Err(PealError::StetRunFailed {
+            detail: "timed out".to_owned(),
+        });
+    }
+    if aborted {
+        warn!(
+            findings = findings.len(),
+            abort_threshold = ?abort_threshold,
+            "stet run aborted early: streamed findings exceed threshold"
+        );
+    }
+
+    let (stdout, has_findings) = if saw_json {
+        let has_findings = !findings.is_empty();
+        (serde_json::json!({ "findings": findings }).to_string(), has_findings)
+    } else {
+        let has_findings = detect_findings(result.exit_code, &result.stdout);
+        (result.stdout, has_findings)
+    };
+
+    info!(
+        exit_code = ?result.exit_code,
+        has_findings,
+        aborted,
+        stderr_len = result.stderr.len(),
+        "stet run completed (streaming)"
+    );
+
+    Ok(StetRunResult {
+        stdout,
+        stderr: result.stderr,
+        exit_code: result.exit_code,
+        has_findings,
+    })
+}
+
 /// Run a single command string (e.g. the last entry of `stet_commands`) and return a `StetRunResult`.
 /// CWD = `repo_path`; timeout applied. Spawn failure or timeout returns `PealError::StetRunFailed`.
 /// Used when phase 3 uses custom commands instead of the built-in stet binary.
 pub fn run_review_via_command(
     command: &str,
+    shell: &str,
     repo_path: &Path,
     timeout: Option<Duration>,
 ) -> Result<StetRunResult, PealError> {
+    let _span = debug_span!("stet", command = "run", custom = true).entered();
+    let _turn = stet_turn();
     let trimmed = command.trim();
     if trimmed.is_empty() {
         return Err(PealError::StetRunFailed {
             detail: "custom run command is empty".to_owned(),
         });
     }
-    let result = match subprocess::run_command_string(trimmed, repo_path, timeout) {
+    let result = match crate::container::run_stet_string(trimmed, shell, repo_path, timeout) {
         None => {
             return Err(PealError::StetRunFailed {
                 detail: "custom run command is empty".to_owned(),
@@ -383,12 +760,11 @@ pub fn detect_findings(exit_code: Option<i32>, stdout: &str) -> bool {
         if let Some(arr) = findings_array_from_value(&value) {
             return !arr.is_empty();
         }
-        if let serde_json::Value::Object(map) = &value {
-            if let Some(count) = map.get("count") {
-                if let Some(n) = count.as_u64() {
-                    return n > 0;
-                }
-            }
+        if let serde_json::Value::Object(map) = &value
+            && let Some(count) = map.get("count")
+            && let Some(n) = count.as_u64()
+        {
+            return n > 0;
         }
         return false;
     }
@@ -422,9 +798,10 @@ pub struct ParsedFinding {
     pub message: String,
     pub suggestion: Option<String>,
     pub path: Option<String>,
+    pub severity: Option<String>,
 }
 
-/// Parse stet run JSON stdout into a list of findings with id, message, suggestion, path.
+/// Parse stet run JSON stdout into a list of findings with id, message, suggestion, path, severity.
 /// Uses the same format resolution as [`findings_array_from_value`] (findings, issues, or top-level array).
 /// Returns None if not JSON or no findings array.
 pub fn parse_findings_from_run_json(stdout: &str) -> Option<Vec<ParsedFinding>> {
@@ -445,11 +822,13 @@ pub fn parse_findings_from_run_json(stdout: &str) -> Option<Vec<ParsedFinding>>
             .or_else(|| obj.get("file"))
             .and_then(|v| v.as_str())
             .map(String::from);
+        let severity = obj.get("severity").and_then(|v| v.as_str()).map(String::from);
         out.push(ParsedFinding {
             id,
             message,
             suggestion,
             path,
+            severity,
         });
     }
     Some(out)
@@ -463,6 +842,8 @@ pub(crate) fn dismiss_finding(
     reason: &str,
     timeout: Option<Duration>,
 ) {
+    let _span = debug_span!("stet", command = "dismiss", finding_id = id).entered();
+    let _turn = stet_turn();
     let stet_str = stet_path.to_string_lossy();
     let args = ["dismiss".to_owned(), id.to_owned(), reason.to_owned()];
     info!(
@@ -471,7 +852,7 @@ pub(crate) fn dismiss_finding(
         reason,
         "invoking stet dismiss"
     );
-    match subprocess::run_command(&stet_str, &args, repo_path, timeout) {
+    match crate::container::run_stet(&stet_str, &args, repo_path, timeout) {
         Ok(result) => {
             if !result.success() {
                 warn!(
@@ -490,6 +871,7 @@ pub(crate) fn dismiss_finding(
 
 /// Result of parsing the triage agent response.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum TriageResult {
     /// Dismiss all findings with the given reason.
     DismissAll(String),
@@ -586,27 +968,50 @@ pub fn dismiss_non_actionable_and_rerun(
     config: &PealConfig,
     run_stdout: &str,
 ) -> Result<StetRunResult, PealError> {
+    dismiss_and_rerun(stet_path, agent_path, config, run_stdout).map(|(result, _)| result)
+}
+
+/// [`dismiss_non_actionable_and_rerun`], also returning the `(id, reason)` pairs dismissed.
+fn dismiss_and_rerun(
+    stet_path: &Path,
+    agent_path: &Path,
+    config: &PealConfig,
+    run_stdout: &str,
+) -> Result<(StetRunResult, Vec<(String, String)>), PealError> {
     let parsed = parse_findings_from_run_json(run_stdout);
     if parsed.is_none() {
         warn!("stet run output was not valid JSON or had no findings array; skipping structured dismiss");
-        return run_review(
+        let result = run_review_for_config(
             stet_path,
-            &config.repo_path,
-            &config.stet_run_extra_args,
+            config,
             Some(Duration::from_secs(config.phase_timeout_sec)),
-        );
+        )?;
+        return Ok((result, Vec::new()));
     }
     let findings = parsed.unwrap();
     if findings.is_empty() {
-        return run_review(
+        let result = run_review_for_config(
             stet_path,
-            &config.repo_path,
-            &config.stet_run_extra_args,
+            config,
             Some(Duration::from_secs(config.phase_timeout_sec)),
+        )?;
+        return Ok((result, Vec::new()));
+    }
+
+    // Findings with a severity in triage_skip_severities are never dismissed: triage only
+    // decides about the rest.
+    let (protected, findings): (Vec<ParsedFinding>, Vec<ParsedFinding>) =
+        findings.into_iter().partition(|f| skips_triage(config, f));
+    if !protected.is_empty() {
+        info!(
+            count = protected.len(),
+            "findings with a severity in triage_skip_severities go straight to the address loop"
         );
     }
 
-    let to_dismiss: Vec<(String, String)> = if config.stet_disable_llm_triage {
+    let to_dismiss: Vec<(String, String)> = if findings.is_empty() {
+        Vec::new()
+    } else if config.stet_disable_llm_triage {
         triage_by_patterns(&findings, &config.stet_dismiss_patterns)
     } else {
         match phase::run_phase3_triage(agent_path, config, run_stdout) {
@@ -633,20 +1038,77 @@ pub fn dismiss_non_actionable_and_rerun(
         }
     };
 
-    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    let to_dismiss: Vec<(String, String)> = to_dismiss
+        .into_iter()
+        .map(|(id, reason)| (id, normalize_dismiss_reason(&reason)))
+        .filter(|(_, reason)| STET_DISMISS_REASONS.contains(&reason.as_str()))
+        .collect();
     for (id, reason) in &to_dismiss {
-        let reason = normalize_dismiss_reason(reason);
-        if STET_DISMISS_REASONS.contains(&reason.as_str()) {
-            dismiss_finding(stet_path, &config.repo_path, id, &reason, timeout);
+        info!(id, reason, "triage would dismiss finding");
+    }
+    let confirmed = {
+        // Holding the stet turn keeps parallel tasks from prompting at the same time.
+        let _turn = stet_turn();
+        confirm_dismissals(
+            config.dismiss_requires_confirmation,
+            to_dismiss.len(),
+            std::io::stdin().is_terminal(),
+            &mut std::io::stdin().lock(),
+            &mut std::io::stderr(),
+        )
+    };
+
+    let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
+    let dismissed = if confirmed {
+        for (id, reason) in &to_dismiss {
+            dismiss_finding(stet_path, &config.repo_path, id, reason, timeout);
         }
-    }
+        to_dismiss
+    } else {
+        warn!(
+            count = to_dismiss.len(),
+            limit = config.dismiss_requires_confirmation,
+            "not dismissing: triage wanted more dismissals than dismiss_requires_confirmation allows; addressing every finding"
+        );
+        Vec::new()
+    };
 
-    run_review(
-        stet_path,
-        &config.repo_path,
-        &config.stet_run_extra_args,
-        timeout,
-    )
+    Ok((run_review_for_config(stet_path, config, timeout)?, dismissed))
+}
+
+/// Whether `finding` is kept out of triage by `triage_skip_severities`.
+fn skips_triage(config: &PealConfig, finding: &ParsedFinding) -> bool {
+    finding
+        .severity
+        .as_deref()
+        .is_some_and(|severity| config.triage_skip_severities.iter().any(|s| s.eq_ignore_ascii_case(severity)))
+}
+
+/// Whether a triage pass may dismiss `count` findings under `dismiss_requires_confirmation`
+/// (`limit`). Above the limit, ask on `output` and read the answer from `input` when
+/// `interactive`; unattended, refuse, so every finding is addressed instead.
+fn confirm_dismissals(
+    limit: Option<u32>,
+    count: usize,
+    interactive: bool,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> bool {
+    let Some(limit) = limit else {
+        return true;
+    };
+    if count <= limit as usize {
+        return true;
+    }
+    if !interactive {
+        return false;
+    }
+    let prompted = write!(output, "Triage wants to dismiss {count} stet findings (limit {limit}). Dismiss them? [y/N] ")
+        .and_then(|()| output.flush());
+    let mut answer = String::new();
+    prompted.is_ok()
+        && input.read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
 }
 
 fn normalize_dismiss_reason(s: &str) -> String {
@@ -694,37 +1156,193 @@ pub struct AddressLoopOutcome {
     pub last_stet_result: StetRunResult,
 }
 
-/// Best-effort resolve current HEAD commit hash in the repo. Returns "unknown" on any failure.
-fn resolve_head_commit(repo_path: &Path) -> String {
-    let output = match Command::new("git")
-        .arg("-C")
-        .arg(repo_path)
-        .args(["rev-parse", "HEAD"])
-        .output()
-    {
-        Ok(o) if o.status.success() => o,
-        _ => return "unknown".to_owned(),
+/// Best-effort resolve the current commit of the repo (see [`crate::vcs`]). Returns "unknown"
+/// on any failure.
+fn resolve_head_commit(config: &PealConfig) -> String {
+    crate::vcs::for_config(config)
+        .head(&config.repo_path)
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+/// Build the `StetFindingsRemain` error for a task from its last stet result.
+fn findings_remain_error(
+    config: &PealConfig,
+    task_index: u32,
+    rounds: u32,
+    result: &StetRunResult,
+) -> PealError {
+    PealError::StetFindingsRemain {
+        task_index,
+        rounds,
+        remaining_count: count_findings(&result.stdout),
+        commit_hash: resolve_head_commit(config),
+        stet_review: match config.findings_summary_top_n {
+            Some(top_n) => {
+                let saved = persist_review(config, task_index, &result.stdout);
+                let mut review = summarize_review(&result.stdout, top_n as usize, saved.as_deref());
+                if !result.stderr.trim().is_empty() {
+                    review.push_str("stderr (tail):\n");
+                    review.push_str(phase::stderr_tail(&result.stderr, phase::STDERR_EXCERPT_BYTES));
+                }
+                review
+            }
+            None => format!("stdout:\n{}\nstderr:\n{}", result.stdout, result.stderr),
+        },
+    }
+}
+
+/// Path of the full stet review kept for a task whose findings remain
+/// (`findings_summary_top_n`), under this run's [`crate::run_id::run_dir`].
+pub fn review_artifact_path(state_dir: &Path, task_index: u32) -> PathBuf {
+    crate::run_id::run_dir(state_dir)
+        .join("artifacts")
+        .join(format!("task-{task_index}"))
+        .join("stet-review.json")
+}
+
+/// Best-effort: write stet's full review stdout for a task. Returns the path when written.
+fn persist_review(config: &PealConfig, task_index: u32, stdout: &str) -> Option<PathBuf> {
+    let path = review_artifact_path(&config.state_dir, task_index);
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, stdout));
+    match written {
+        Ok(()) => Some(path),
+        Err(e) => {
+            warn!(task_index, path = %path.display(), err = %e, "failed to persist stet review");
+            None
+        }
+    }
+}
+
+/// Severities in the order [`summarize_review`] lists findings; others sort after these.
+const SEVERITY_ORDER: &[&str] = &["critical", "error", "high", "warning", "medium", "low", "info"];
+
+/// Bounded summary of a stet review: the finding count by severity and by category, the
+/// `top_n` most severe findings as `path:line [severity] message`, and where the full review
+/// was saved. Output that is not a findings JSON is cut to its first `top_n` lines.
+pub fn summarize_review(stdout: &str, top_n: usize, full_review: Option<&Path>) -> String {
+    let field = |item: &serde_json::Value, key: &str| -> Option<String> {
+        item.get(key).and_then(|v| v.as_str()).map(String::from)
     };
-    let s = String::from_utf8_lossy(&output.stdout);
-    let trimmed = s.trim();
-    if trimmed.is_empty() {
-        "unknown".to_owned()
+    let mut out = String::new();
+    let value = serde_json::from_str::<serde_json::Value>(stdout).ok();
+    if let Some(items) = value.as_ref().and_then(findings_array_from_value) {
+        let mut by_severity: BTreeMap<String, usize> = BTreeMap::new();
+        let mut by_category: BTreeMap<String, usize> = BTreeMap::new();
+        for item in items {
+            let severity = field(item, "severity").unwrap_or_else(|| "unknown".to_owned());
+            *by_severity.entry(severity).or_default() += 1;
+            if let Some(category) = field(item, "category") {
+                *by_category.entry(category).or_default() += 1;
+            }
+        }
+        let counts = |map: &BTreeMap<String, usize>| {
+            map.iter().map(|(k, n)| format!("{k} {n}")).collect::<Vec<_>>().join(", ")
+        };
+        out.push_str(&format!("{} finding(s) by severity: {}\n", items.len(), counts(&by_severity)));
+        if !by_category.is_empty() {
+            out.push_str(&format!("by category: {}\n", counts(&by_category)));
+        }
+        let rank = |item: &serde_json::Value| {
+            let severity = field(item, "severity").unwrap_or_default().to_ascii_lowercase();
+            SEVERITY_ORDER.iter().position(|s| *s == severity).unwrap_or(SEVERITY_ORDER.len())
+        };
+        let mut sorted: Vec<&serde_json::Value> = items.iter().collect();
+        sorted.sort_by_key(|item| rank(item));
+        for item in sorted.iter().take(top_n) {
+            let path = field(item, "path")
+                .or_else(|| field(item, "file"))
+                .unwrap_or_else(|| "?".to_owned());
+            let location = match item.get("line").and_then(|v| v.as_u64()) {
+                Some(line) => format!("{path}:{line}"),
+                None => path,
+            };
+            let severity = field(item, "severity").unwrap_or_else(|| "unknown".to_owned());
+            let message = field(item, "message").unwrap_or_default();
+            let first_line = message.lines().next().unwrap_or("");
+            out.push_str(&format!("  {location} [{severity}] {first_line}\n"));
+        }
+        if items.len() > top_n {
+            out.push_str(&format!("  ... and {} more\n", items.len() - top_n));
+        }
     } else {
-        trimmed.to_owned()
+        let lines: Vec<&str> = stdout.lines().collect();
+        for line in lines.iter().take(top_n) {
+            out.push_str(line);
+            out.push('\n');
+        }
+        if lines.len() > top_n {
+            out.push_str(&format!("... and {} more line(s)\n", lines.len() - top_n));
+        }
+    }
+    if let Some(path) = full_review {
+        out.push_str(&format!("full review: {}\n", path.display()));
+    }
+    out
+}
+
+/// Decide the outcome of a round whose Phase 3 agent call failed after all retries
+/// (`phase_3_retry_count`). The agent may have applied part of its fix before failing,
+/// so stet is re-run first: if the review is clean the partial work is kept and the task
+/// counts as resolved; otherwise the agent error is returned (not `StetFindingsRemain`),
+/// so the failure is attributed to the agent rather than to stet.
+fn salvage_after_agent_failure(
+    task_index: u32,
+    round: u32,
+    agent_err: PealError,
+    review: StetRunResult,
+) -> Result<AddressLoopOutcome, PealError> {
+    if review.has_findings {
+        warn!(task_index, round, err = %agent_err, "phase 3 agent failed and findings remain");
+        return Err(agent_err);
     }
+    warn!(
+        task_index,
+        round,
+        err = %agent_err,
+        "phase 3 agent failed but review is clean; keeping partial changes"
+    );
+    Ok(AddressLoopOutcome {
+        rounds_used: round,
+        findings_resolved: true,
+        last_stet_result: review,
+    })
 }
 
 /// Drive the address → re-run → check loop for a single task.
 ///
 /// Bounded by `config.max_address_rounds` (default 5). Returns early
 /// when findings are resolved. After exhausting all rounds, behavior is
-/// controlled by `config.on_findings_remaining` (`"fail"` or `"warn"`).
+/// controlled by `config.on_findings_remaining`: `"fail"` returns `StetFindingsRemain`;
+/// `"warn"` and `"create_task"` return an unresolved outcome (the runner queues a
+/// follow-up task for `"create_task"`).
+/// With `max_address_rounds = 0` no triage or agent round runs and
+/// `on_findings_remaining` applies to the initial findings directly.
+/// When `config.fail_fast_findings` is set, initial findings fail the task
+/// immediately regardless of `on_findings_remaining`.
 pub fn address_loop(
     agent_path: &Path,
     stet_path: &Path,
     config: &PealConfig,
     task_index: u32,
     initial_result: &StetRunResult,
+) -> Result<AddressLoopOutcome, PealError> {
+    let mut trace = FindingsTrace::new(config, task_index);
+    trace.review(0, "initial", initial_result, &[]);
+    let outcome = address_loop_traced(agent_path, stet_path, config, task_index, initial_result, &mut trace);
+    finish_trace(&mut trace, &outcome);
+    outcome
+}
+
+fn address_loop_traced(
+    agent_path: &Path,
+    stet_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    initial_result: &StetRunResult,
+    trace: &mut FindingsTrace,
 ) -> Result<AddressLoopOutcome, PealError> {
     if !initial_result.has_findings {
         return Ok(AddressLoopOutcome {
@@ -734,6 +1352,11 @@ pub fn address_loop(
         });
     }
 
+    if config.fail_fast_findings {
+        warn!(task_index, "findings present; failing task without address rounds (fail_fast_findings)");
+        return Err(findings_remain_error(config, task_index, 0, initial_result));
+    }
+
     let timeout = Some(Duration::from_secs(config.phase_timeout_sec));
     let mut current_result = initial_result.clone();
 
@@ -745,12 +1368,13 @@ pub fn address_loop(
             "address loop: starting round"
         );
 
-        let after_dismiss = dismiss_non_actionable_and_rerun(
+        let (after_dismiss, dismissed) = dismiss_and_rerun(
             stet_path,
             agent_path,
             config,
             &current_result.stdout,
         )?;
+        trace.review(round, "after_dismiss", &after_dismiss, &dismissed);
         current_result = after_dismiss;
 
         if !current_result.has_findings {
@@ -762,14 +1386,18 @@ pub fn address_loop(
             });
         }
 
-        address_findings(agent_path, config, task_index, &current_result)?;
+        let agent_err = match address_findings(agent_path, config, task_index, &current_result) {
+            Ok(_) => None,
+            Err(e) if e.is_agent_failure() => Some(e),
+            Err(e) => return Err(e),
+        };
 
-        let new_result = run_review(
-            stet_path,
-            &config.repo_path,
-            &config.stet_run_extra_args,
-            timeout,
-        )?;
+        let new_result = run_review_for_config(stet_path, config, timeout)?;
+        trace.review(round, "after_address", &new_result, &[]);
+
+        if let Some(e) = agent_err {
+            return salvage_after_agent_failure(task_index, round, e, new_result);
+        }
 
         if !new_result.has_findings {
             info!(task_index, round, "address loop: findings resolved");
@@ -788,11 +1416,12 @@ pub fn address_loop(
         current_result = new_result;
     }
 
-    if config.on_findings_remaining == "warn" {
+    if config.on_findings_remaining != "fail" {
         warn!(
             task_index,
             rounds = config.max_address_rounds,
-            "findings remain after all address rounds; continuing (on_findings_remaining=warn)"
+            policy = %config.on_findings_remaining,
+            "findings remain after all address rounds; continuing"
         );
         return Ok(AddressLoopOutcome {
             rounds_used: config.max_address_rounds,
@@ -801,19 +1430,16 @@ pub fn address_loop(
         });
     }
 
-    Err(PealError::StetFindingsRemain {
+    Err(findings_remain_error(
+        config,
         task_index,
-        rounds: config.max_address_rounds,
-        remaining_count: count_findings(&current_result.stdout),
-        commit_hash: resolve_head_commit(&config.repo_path),
-        stet_review: format!(
-            "stdout:\n{}\nstderr:\n{}",
-            current_result.stdout, current_result.stderr
-        ),
-    })
+        config.max_address_rounds,
+        &current_result,
+    ))
 }
 
 /// Address loop for custom commands: no dismiss step; after each round we re-run the last command only.
+/// `fail_fast_findings` and `max_address_rounds = 0` behave as in [`address_loop`].
 /// `run_last_command` is typically a closure that runs the last entry of `stet_commands` via `run_review_via_command`.
 pub fn address_loop_custom<F>(
     agent_path: &Path,
@@ -822,6 +1448,24 @@ pub fn address_loop_custom<F>(
     initial_result: &StetRunResult,
     run_last_command: F,
 ) -> Result<AddressLoopOutcome, PealError>
+where
+    F: Fn() -> Result<StetRunResult, PealError>,
+{
+    let mut trace = FindingsTrace::new(config, task_index);
+    trace.review(0, "initial", initial_result, &[]);
+    let outcome = address_loop_custom_traced(agent_path, config, task_index, initial_result, run_last_command, &mut trace);
+    finish_trace(&mut trace, &outcome);
+    outcome
+}
+
+fn address_loop_custom_traced<F>(
+    agent_path: &Path,
+    config: &PealConfig,
+    task_index: u32,
+    initial_result: &StetRunResult,
+    run_last_command: F,
+    trace: &mut FindingsTrace,
+) -> Result<AddressLoopOutcome, PealError>
 where
     F: Fn() -> Result<StetRunResult, PealError>,
 {
@@ -833,6 +1477,11 @@ where
         });
     }
 
+    if config.fail_fast_findings {
+        warn!(task_index, "findings present; failing task without address rounds (fail_fast_findings, custom)");
+        return Err(findings_remain_error(config, task_index, 0, initial_result));
+    }
+
     let mut current_result = initial_result.clone();
 
     for round in 1..=config.max_address_rounds {
@@ -843,9 +1492,18 @@ where
             "address loop (custom): starting round"
         );
 
-        address_findings(agent_path, config, task_index, &current_result)?;
+        let agent_err = match address_findings(agent_path, config, task_index, &current_result) {
+            Ok(_) => None,
+            Err(e) if e.is_agent_failure() => Some(e),
+            Err(e) => return Err(e),
+        };
 
         let new_result = run_last_command()?;
+        trace.review(round, "after_address", &new_result, &[]);
+
+        if let Some(e) = agent_err {
+            return salvage_after_agent_failure(task_index, round, e, new_result);
+        }
 
         if !new_result.has_findings {
             info!(task_index, round, "address loop (custom): findings resolved");
@@ -864,11 +1522,12 @@ where
         current_result = new_result;
     }
 
-    if config.on_findings_remaining == "warn" {
+    if config.on_findings_remaining != "fail" {
         warn!(
             task_index,
             rounds = config.max_address_rounds,
-            "findings remain after all address rounds (custom); continuing (on_findings_remaining=warn)"
+            policy = %config.on_findings_remaining,
+            "findings remain after all address rounds (custom); continuing"
         );
         return Ok(AddressLoopOutcome {
             rounds_used: config.max_address_rounds,
@@ -877,26 +1536,32 @@ where
         });
     }
 
-    Err(PealError::StetFindingsRemain {
+    Err(findings_remain_error(
+        config,
         task_index,
-        rounds: config.max_address_rounds,
-        remaining_count: count_findings(&current_result.stdout),
-        commit_hash: resolve_head_commit(&config.repo_path),
-        stet_review: format!(
-            "stdout:\n{}\nstderr:\n{}",
-            current_result.stdout, current_result.stderr
-        ),
-    })
+        config.max_address_rounds,
+        &current_result,
+    ))
 }
 
-/// Best-effort count of findings from stet stdout. Falls back to 1 when
-/// the output is not structured JSON with a countable findings array.
+/// Close the findings trace with the loop's outcome; a `StetFindingsRemain` error counts as
+/// unresolved after its rounds. Other errors leave the trace without a `final` line.
+fn finish_trace(trace: &mut FindingsTrace, outcome: &Result<AddressLoopOutcome, PealError>) {
+    match outcome {
+        Ok(o) => trace.finish(o.rounds_used, o.findings_resolved),
+        Err(PealError::StetFindingsRemain { rounds, .. }) => trace.finish(*rounds, false),
+        Err(_) => {}
+    }
+}
+
+/// Best-effort count of findings from stet stdout. Falls back to 1 when
+/// the output is not structured JSON with a countable findings array.
 /// Uses the same format resolution as [`findings_array_from_value`].
 fn count_findings(stdout: &str) -> usize {
-    if let Ok(value) = serde_json::from_str::<serde_json::Value>(stdout) {
-        if let Some(arr) = findings_array_from_value(&value) {
-            return arr.len();
-        }
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(stdout)
+        && let Some(arr) = findings_array_from_value(&value)
+    {
+        return arr.len();
     }
     1
 }
@@ -936,6 +1601,28 @@ mod tests {
         script
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn finish_session_via_commands_expands_entries_and_stops_at_a_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().to_str().unwrap();
+        let argv = ["peal", "run", "--plan", "plan.md", "--repo", repo, "--command-shell", "sh", "--stet-start-ref", "HEAD~2"];
+        let crate::cli::Commands::Run(args) = <crate::cli::Cli as clap::Parser>::try_parse_from(argv).unwrap().command else {
+            panic!("expected run command");
+        };
+        let mut config = PealConfig::load(None, &args).unwrap();
+        assert_eq!(
+            expand_command("review {{START_REF}} {{TASK_INDEX}} in {{REPO}}", &config, Some(3)),
+            format!("review HEAD~2 3 in {}", dir.path().display())
+        );
+        assert_eq!(expand_command("x{{TASK_INDEX}}", &config, None), "x");
+
+        config.stet_finish_commands = vec!["echo a >> fin.log".to_owned(), "false".to_owned(), "echo b >> fin.log".to_owned()];
+        let err = finish_session_via_commands(&config).unwrap_err();
+        assert!(matches!(&err, PealError::StetFinishFailed { detail } if detail.starts_with("false:")), "{err:?}");
+        assert_eq!(std::fs::read_to_string(dir.path().join("fin.log")).unwrap(), "a\n");
+    }
+
     #[test]
     fn returns_none_when_not_on_path() {
         let result = resolve_stet_with(None, Some(OsString::from("/empty/dir/that/does/not/exist")));
@@ -964,6 +1651,7 @@ mod tests {
             use std::os::unix::fs::OpenOptionsExt;
             std::fs::OpenOptions::new()
                 .create(true)
+                .truncate(true)
                 .write(true)
                 .mode(0o755)
                 .open(&bin)
@@ -994,6 +1682,7 @@ mod tests {
             use std::os::unix::fs::OpenOptionsExt;
             std::fs::OpenOptions::new()
                 .create(true)
+                .truncate(true)
                 .write(true)
                 .mode(0o755)
                 .open(&bin)
@@ -1075,6 +1764,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolve_start_ref_follows_the_branch_point() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let out = std::process::Command::new("git").args(args).current_dir(dir.path()).output().unwrap();
+            assert!(out.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&out.stderr));
+            String::from_utf8_lossy(&out.stdout).trim().to_owned()
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["-c", "user.name=t", "-c", "user.email=t@t", "commit", "-q", "--allow-empty", "-m", "base"]);
+        let fork = git(&["rev-parse", "HEAD"]);
+        git(&["checkout", "-q", "-b", "feature"]);
+        git(&["-c", "user.name=t", "-c", "user.email=t@t", "commit", "-q", "--allow-empty", "-m", "work"]);
+
+        assert_eq!(resolve_start_ref("{{MERGE_BASE}}", dir.path()).unwrap(), fork);
+        assert_eq!(resolve_start_ref("{{BASE_BRANCH}}~0", dir.path()).unwrap(), "main~0");
+        assert_eq!(resolve_start_ref("HEAD~1", dir.path()).unwrap(), "HEAD~1");
+
+        let err = resolve_start_ref("{{BRANCH_POINT}}", dir.path()).unwrap_err();
+        assert!(err.to_string().contains("unknown placeholder {{BRANCH_POINT}}"), "{err}");
+        git(&["branch", "-q", "-m", "main", "trunk"]);
+        let err = resolve_start_ref("{{MERGE_BASE}}", dir.path()).unwrap_err();
+        assert!(matches!(err, PealError::StetStartRefUnresolved { .. }), "{err:?}");
+    }
+
     #[test]
     fn start_session_argv_with_ref() {
         let dir = tempfile::tempdir().unwrap();
@@ -1373,8 +2087,8 @@ mod tests {
     #[test]
     fn parse_triage_nothing_to_address_dismiss_all() {
         let findings = vec![
-            ParsedFinding { id: "f1".into(), message: "m1".into(), suggestion: None, path: None },
-            ParsedFinding { id: "f2".into(), message: "m2".into(), suggestion: None, path: None },
+            ParsedFinding { id: "f1".into(), message: "m1".into(), suggestion: None, path: None, severity: None },
+            ParsedFinding { id: "f2".into(), message: "m2".into(), suggestion: None, path: None, severity: None },
         ];
         let r = parse_triage_response("No, nothing to address from this review.", &findings);
         match &r {
@@ -1385,7 +2099,7 @@ mod tests {
 
     #[test]
     fn parse_triage_nothing_to_address_variants() {
-        let findings = vec![ParsedFinding { id: "f1".into(), message: "m".into(), suggestion: None, path: None }];
+        let findings = vec![ParsedFinding { id: "f1".into(), message: "m".into(), suggestion: None, path: None, severity: None }];
         for response in ["Nothing to address", "All false positives", "No findings to address.", "No."] {
             let r = parse_triage_response(response, &findings);
             assert!(matches!(r, TriageResult::DismissAll(_)), "response {:?}", response);
@@ -1395,8 +2109,8 @@ mod tests {
     #[test]
     fn parse_triage_fix_finding_abc_dismiss_rest() {
         let findings = vec![
-            ParsedFinding { id: "abc123".into(), message: "fix this".into(), suggestion: None, path: None },
-            ParsedFinding { id: "def456".into(), message: "noise".into(), suggestion: None, path: None },
+            ParsedFinding { id: "abc123".into(), message: "fix this".into(), suggestion: None, path: None, severity: None },
+            ParsedFinding { id: "def456".into(), message: "noise".into(), suggestion: None, path: None, severity: None },
         ];
         // Avoid "fix" near def456 (e.g. "false") so only abc123 is to_address.
         let r = parse_triage_response("Only finding abc123 needs a fix. The rest are noise.", &findings);
@@ -1411,7 +2125,7 @@ mod tests {
 
     #[test]
     fn parse_triage_unparseable_dismiss_none() {
-        let findings = vec![ParsedFinding { id: "f1".into(), message: "m".into(), suggestion: None, path: None }];
+        let findings = vec![ParsedFinding { id: "f1".into(), message: "m".into(), suggestion: None, path: None, severity: None }];
         assert!(matches!(parse_triage_response("", &findings), TriageResult::DismissNone));
         assert!(matches!(parse_triage_response("   \n  ", &findings), TriageResult::DismissNone));
         assert!(matches!(parse_triage_response("maybe fix something", &findings), TriageResult::DismissNone));
@@ -1421,7 +2135,7 @@ mod tests {
 
     #[test]
     fn triage_by_patterns_empty_patterns_nothing_dismissed() {
-        let findings = vec![ParsedFinding { id: "f1".into(), message: "unused".into(), suggestion: None, path: None }];
+        let findings = vec![ParsedFinding { id: "f1".into(), message: "unused".into(), suggestion: None, path: None, severity: None }];
         let out = triage_by_patterns(&findings, &[]);
         assert!(out.is_empty());
     }
@@ -1429,8 +2143,8 @@ mod tests {
     #[test]
     fn triage_by_patterns_match_message_one_dismissed() {
         let findings = vec![
-            ParsedFinding { id: "f1".into(), message: "unused variable".into(), suggestion: None, path: None },
-            ParsedFinding { id: "f2".into(), message: "other".into(), suggestion: None, path: None },
+            ParsedFinding { id: "f1".into(), message: "unused variable".into(), suggestion: None, path: None, severity: None },
+            ParsedFinding { id: "f2".into(), message: "other".into(), suggestion: None, path: None, severity: None },
         ];
         let patterns = vec![StetDismissPattern { pattern: "unused".to_string(), reason: "false_positive".to_string() }];
         let out = triage_by_patterns(&findings, &patterns);
@@ -1481,35 +2195,15 @@ mod tests {
     /// Stet stub: on "run" prints empty findings JSON; on "dismiss" exits 0.
     #[cfg(unix)]
     fn stet_stub_empty_on_run(dir: &tempfile::TempDir) -> PathBuf {
-        use std::os::unix::fs::PermissionsExt;
-        let script = dir.path().join("stet_stub.sh");
-        std::fs::write(
-            &script,
-            r#"#!/bin/sh
-[ "$1" = "run" ] && printf '%s\n' '{"findings":[]}'
-exit 0
-"#,
-        )
-        .unwrap();
-        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
-        script
+        let stet = crate::testing::FakeStet::new(dir.path(), vec![crate::testing::Reply::findings(&[])]).unwrap();
+        stet.path().to_path_buf()
     }
 
     /// Agent stub that prints "Nothing to address from this review." for LLM triage tests.
     #[cfg(unix)]
     fn agent_stub_nothing_to_address(dir: &tempfile::TempDir) -> PathBuf {
-        use std::os::unix::fs::PermissionsExt;
-        let script = dir.path().join("agent_triage.sh");
-        std::fs::write(
-            &script,
-            r#"#!/bin/sh
-printf '%s\n' "Nothing to address from this review."
-exit 0
-"#,
-        )
-        .unwrap();
-        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
-        script
+        let reply = crate::testing::Reply::ok("Nothing to address from this review.\n");
+        crate::testing::FakeAgent::new(dir.path(), vec![reply]).unwrap().path().to_path_buf()
     }
 
     #[test]
@@ -1527,7 +2221,7 @@ exit 0
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1536,6 +2230,8 @@ exit 0
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1553,6 +2249,73 @@ exit 0
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
         let run_stdout = r#"{"findings":[{"id":"f1","message":"unused"}]}"#;
         let result = dismiss_non_actionable_and_rerun(&stet_path, &agent_path, &config, run_stdout).unwrap();
@@ -1563,7 +2326,7 @@ exit 0
     #[cfg(unix)]
     fn dismiss_non_actionable_and_rerun_rule_pattern_dismisses_then_rerun() {
         let dir = tempfile::tempdir().unwrap();
-        let stet_path = stet_stub_empty_on_run(&dir);
+        let stet = crate::testing::FakeStet::new(dir.path(), vec![crate::testing::Reply::findings(&[])]).unwrap();
         let agent_path = agent_stub_nothing_to_address(&dir); // not used when LLM triage disabled
         let config = crate::config::PealConfig {
             agent_cmd: "true".to_owned(),
@@ -1574,7 +2337,7 @@ exit 0
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1583,6 +2346,8 @@ exit 0
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1603,10 +2368,129 @@ exit 0
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
         let run_stdout = r#"{"findings":[{"id":"f1","message":"unused variable"}]}"#;
-        let result = dismiss_non_actionable_and_rerun(&stet_path, &agent_path, &config, run_stdout).unwrap();
+        let result = dismiss_non_actionable_and_rerun(stet.path(), &agent_path, &config, run_stdout).unwrap();
         assert!(!result.has_findings, "expected no findings after pattern-dismiss and rerun");
+        assert_eq!(stet.calls("dismiss"), ["dismiss f1 false_positive"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn dismiss_non_actionable_and_rerun_never_dismisses_skipped_severities() {
+        use crate::cli::{Cli, Commands};
+        use clap::Parser;
+
+        let dir = tempfile::tempdir().unwrap();
+        let stet = crate::testing::FakeStet::new(dir.path(), vec![crate::testing::Reply::findings(&[])]).unwrap();
+        let agent = crate::testing::FakeAgent::new(
+            dir.path(),
+            vec![crate::testing::Reply::ok("Nothing to address from this review.\n")],
+        )
+        .unwrap();
+        let argv = ["peal", "run", "--plan", "plan.md", "--repo", ".", "--triage-skip-severities", "Error, critical"];
+        let Commands::Run(args) = Cli::try_parse_from(argv).unwrap().command else {
+            panic!("expected run command");
+        };
+        let mut config = PealConfig::load(None, &args).unwrap();
+        assert_eq!(config.triage_skip_severities, ["Error", "critical"]);
+        config.repo_path = dir.path().to_path_buf();
+        config.state_dir = dir.path().join(".peal");
+        config.no_cache = true;
+
+        let run_stdout = r#"{"findings":[{"id":"f1","severity":"error","message":"x"},{"id":"f2","severity":"info","message":"y"}]}"#;
+        dismiss_non_actionable_and_rerun(stet.path(), agent.path(), &config, run_stdout).unwrap();
+        assert_eq!(stet.calls("dismiss"), ["dismiss f2 false_positive"]);
+        assert_eq!(agent.calls().len(), 1);
+
+        // Nothing left to triage: the agent is not asked at all.
+        let run_stdout = r#"{"findings":[{"id":"f3","severity":"CRITICAL","message":"z"}]}"#;
+        dismiss_non_actionable_and_rerun(stet.path(), agent.path(), &config, run_stdout).unwrap();
+        assert_eq!(stet.calls("dismiss"), ["dismiss f2 false_positive"]);
+        assert_eq!(agent.calls().len(), 1);
+    }
+
+    #[test]
+    fn confirm_dismissals_asks_above_the_limit_and_refuses_unattended() {
+        let mut out = Vec::new();
+        assert!(confirm_dismissals(None, 50, false, &mut "".as_bytes(), &mut out));
+        assert!(confirm_dismissals(Some(3), 3, false, &mut "".as_bytes(), &mut out));
+        assert!(out.is_empty(), "no prompt at or under the limit");
+
+        assert!(!confirm_dismissals(Some(3), 4, false, &mut "y\n".as_bytes(), &mut out));
+        assert!(out.is_empty(), "no prompt when unattended");
+
+        assert!(confirm_dismissals(Some(3), 4, true, &mut "yes\n".as_bytes(), &mut out));
+        let prompt = String::from_utf8(out).unwrap();
+        assert!(prompt.contains("dismiss 4 stet findings (limit 3)"), "{prompt}");
+        assert!(!confirm_dismissals(Some(3), 4, true, &mut "\n".as_bytes(), &mut Vec::new()));
     }
 
     // -- run_review integration tests --
@@ -1695,6 +2579,102 @@ exit 0
         assert_eq!(actual, expected);
     }
 
+    // -- run_review_streaming tests --
+
+    #[cfg(unix)]
+    fn ndjson_stet_script(dir: &tempfile::TempDir, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = dir.path().join("stream-stet");
+        std::fs::write(&script, format!("#!/bin/sh\necho \"$@\" > args.txt\n{body}")).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        script
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_review_streaming_collects_ndjson_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = ndjson_stet_script(
+            &dir,
+            "echo '{\"type\":\"progress\",\"files\":2}'\n\
+             echo '{\"id\":\"f1\",\"file\":\"a.rs\",\"line\":3,\"message\":\"one\"}'\n\
+             echo '{\"finding\":{\"id\":\"f2\",\"message\":\"two\"}}'\n\
+             exit 1\n",
+        );
+
+        let result = run_review_streaming(
+            &script,
+            dir.path(),
+            &["--verify".to_owned()],
+            Some(Duration::from_secs(10)),
+            None,
+        )
+        .unwrap();
+
+        let args = std::fs::read_to_string(dir.path().join("args.txt")).unwrap();
+        assert_eq!(args.trim(), "run --output=json --stream --verify");
+        assert!(result.has_findings);
+        assert_eq!(result.exit_code, Some(1));
+        let parsed = parse_findings_from_run_json(&result.stdout).unwrap();
+        let ids: Vec<&str> = parsed.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, ["f1", "f2"]);
+        assert_eq!(parsed[0].path.as_deref(), Some("a.rs"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_review_streaming_summary_line_replaces_collected_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = ndjson_stet_script(
+            &dir,
+            "echo '{\"id\":\"f1\",\"message\":\"one\"}'\n\
+             echo '{\"findings\":[]}'\n",
+        );
+
+        let result = run_review_streaming(&script, dir.path(), &[], Some(Duration::from_secs(10)), None)
+            .unwrap();
+
+        assert!(!result.has_findings);
+        assert_eq!(parse_findings_from_run_json(&result.stdout), Some(Vec::new()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_review_streaming_aborts_once_threshold_exceeded() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = ndjson_stet_script(
+            &dir,
+            "echo '{\"id\":\"f1\",\"message\":\"one\"}'\n\
+             echo '{\"id\":\"f2\",\"message\":\"two\"}'\n\
+             sleep 60\n\
+             echo '{\"id\":\"f3\",\"message\":\"three\"}'\n",
+        );
+
+        let start = std::time::Instant::now();
+        let result =
+            run_review_streaming(&script, dir.path(), &[], Some(Duration::from_secs(30)), Some(1))
+                .unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(20), "stet should be killed early");
+        assert!(result.has_findings);
+        assert_eq!(result.exit_code, None);
+        assert_eq!(parse_findings_from_run_json(&result.stdout).unwrap().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_review_streaming_falls_back_to_raw_output_without_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = ndjson_stet_script(&dir, "echo '0 finding(s).'\n");
+
+        let result = run_review_streaming(&script, dir.path(), &[], Some(Duration::from_secs(10)), None)
+            .unwrap();
+
+        assert!(!result.has_findings);
+        assert_eq!(result.stdout.trim(), "0 finding(s).");
+    }
+
     // -- extract_suggestions tests --
 
     #[test]
@@ -1787,7 +2767,7 @@ exit 0
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1796,6 +2776,8 @@ exit 0
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1813,6 +2795,73 @@ exit 0
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let stet_result = StetRunResult {
@@ -1860,7 +2909,7 @@ exit 0
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1869,6 +2918,8 @@ exit 0
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1886,6 +2937,73 @@ exit 0
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let stet_result = StetRunResult {
@@ -1945,8 +3063,98 @@ exit 0
         assert_eq!(count_findings(stdout), 1);
     }
 
+    #[test]
+    fn summarize_review_counts_and_lists_most_severe_first() {
+        let stdout = r#"{"findings":[
+            {"id":"f1","file":"a.rs","line":3,"severity":"info","category":"style","message":"nit"},
+            {"id":"f2","file":"b.rs","line":9,"severity":"error","category":"bug","message":"null deref\nmore"},
+            {"id":"f3","path":"c.rs","severity":"warning","category":"bug","message":"unchecked"}
+        ]}"#;
+        let summary = summarize_review(stdout, 2, Some(Path::new("/s/runs/r/artifacts/task-1/stet-review.json")));
+        assert_eq!(
+            summary,
+            "3 finding(s) by severity: error 1, info 1, warning 1\n\
+             by category: bug 2, style 1\n  \
+             b.rs:9 [error] null deref\n  \
+             c.rs [warning] unchecked\n  \
+             ... and 1 more\n\
+             full review: /s/runs/r/artifacts/task-1/stet-review.json\n"
+        );
+
+        let plain = summarize_review("one\ntwo\nthree", 2, None);
+        assert_eq!(plain, "one\ntwo\n... and 1 more line(s)\n");
+    }
+
+    #[test]
+    fn findings_remain_error_summarizes_and_saves_full_review() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().to_str().unwrap();
+        let argv = ["peal", "run", "--plan", "plan.md", "--repo", repo, "--findings-summary-top-n", "1"];
+        let crate::cli::Commands::Run(args) = <crate::cli::Cli as clap::Parser>::try_parse_from(argv).unwrap().command else {
+            panic!("expected run command");
+        };
+        let mut config = PealConfig::load(None, &args).unwrap();
+        config.state_dir = dir.path().join(".peal");
+        let stdout = r#"{"findings":[{"id":"a","file":"x.rs","line":1,"severity":"warning","message":"m"},{"id":"b","severity":"warning","message":"n"}]}"#;
+        let result = run_result_from_output(stdout.to_owned(), "stet: 2 findings".to_owned(), Some(1));
+
+        let PealError::StetFindingsRemain { remaining_count, stet_review, .. } = findings_remain_error(&config, 2, 1, &result) else {
+            panic!("expected StetFindingsRemain");
+        };
+        assert_eq!(remaining_count, 2);
+        assert!(stet_review.contains("x.rs:1 [warning] m\n  ... and 1 more"), "{stet_review}");
+        assert!(stet_review.ends_with("stderr (tail):\nstet: 2 findings"), "{stet_review}");
+        let saved = review_artifact_path(&config.state_dir, 2);
+        assert!(stet_review.contains(&saved.display().to_string()), "{stet_review}");
+        assert_eq!(std::fs::read_to_string(saved).unwrap(), stdout);
+    }
+
     // -- address_loop tests --
 
+    #[test]
+    #[cfg(unix)]
+    fn address_loop_traces_each_review_with_dismissals_and_fixes() {
+        use crate::cli::{Cli, Commands};
+        use crate::testing::{FakeAgent, FakeStet, Reply};
+        use clap::Parser;
+
+        let dir = tempfile::tempdir().unwrap();
+        let stet = FakeStet::new(dir.path(), vec![Reply::findings(&[("f2", "missing check")]), Reply::findings(&[])]).unwrap();
+        let agent = FakeAgent::new(dir.path(), vec![Reply::ok("Added the check.\n")]).unwrap();
+        let argv = ["peal", "run", "--plan", "plan.md", "--repo", ".", "--stet-disable-llm-triage", "true"];
+        let Commands::Run(args) = Cli::try_parse_from(argv).unwrap().command else {
+            panic!("expected run command");
+        };
+        let mut config = PealConfig::load(None, &args).unwrap();
+        config.repo_path = dir.path().to_path_buf();
+        config.state_dir = dir.path().join(".peal");
+        config.stet_dismiss_patterns = vec![StetDismissPattern {
+            pattern: "unused".to_owned(),
+            reason: "false_positive".to_owned(),
+        }];
+
+        let initial = run_result_from_output(
+            r#"{"findings":[{"id":"f1","message":"unused import"},{"id":"f2","message":"missing check"}]}"#.to_owned(),
+            String::new(),
+            Some(0),
+        );
+        let outcome = address_loop(agent.path(), stet.path(), &config, 3, &initial).unwrap();
+        assert!(outcome.findings_resolved);
+
+        let trace = crate::findings_trace::load(&config, 3);
+        let steps: Vec<(u32, &str)> = trace.iter().map(|e| (e.round, e.step.as_str())).collect();
+        assert_eq!(steps, [(0, "initial"), (1, "after_dismiss"), (1, "after_address"), (1, "final")]);
+        assert_eq!(trace[0].new, ["f1", "f2"]);
+        assert_eq!(trace[1].findings.as_deref(), Some(&["f2".to_owned()][..]));
+        assert_eq!(trace[1].dismissed[0].id, "f1");
+        assert_eq!(trace[1].dismissed[0].reason, "false_positive");
+        assert!(trace[1].fixed.is_empty());
+        assert_eq!(trace[2].fixed, ["f2"]);
+        assert_eq!(trace[3].findings_resolved, Some(true));
+        let saved = crate::findings_trace::trace_dir(&config, 3).join(trace[0].review.as_deref().unwrap());
+        assert!(std::fs::read_to_string(saved).unwrap().contains("unused import"));
+    }
+
     #[test]
     fn address_loop_exits_immediately_when_no_findings() {
         let dir = tempfile::tempdir().unwrap();
@@ -1967,7 +3175,7 @@ exit 0
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -1976,6 +3184,8 @@ exit 0
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -1993,6 +3203,73 @@ exit 0
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let initial = StetRunResult {
@@ -2030,7 +3307,7 @@ exit 0
             model: None,
             max_address_rounds: 3,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -2039,6 +3316,8 @@ exit 0
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -2056,6 +3335,73 @@ exit 0
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let initial = StetRunResult {
@@ -2091,7 +3437,7 @@ exit 0
             model: None,
             max_address_rounds: 2,
             on_findings_remaining: "fail".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -2100,6 +3446,8 @@ exit 0
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -2117,6 +3465,73 @@ exit 0
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let initial = StetRunResult {
@@ -2233,7 +3648,7 @@ exit 0
             model: None,
             max_address_rounds: 2,
             on_findings_remaining: "warn".to_owned(),
-            state_dir: PathBuf::from(".peal"),
+            state_dir: dir.path().join(".peal"),
             phase_timeout_sec: 30,
             phase_retry_count: 0,
             phase_3_retry_count: 0,
@@ -2242,6 +3657,8 @@ exit 0
             continue_with_remaining_tasks: false,
             log_level: None,
             log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
             stet_path: None,
             stet_start_ref: None,
             stet_start_extra_args: vec![],
@@ -2259,6 +3676,73 @@ exit 0
             run_summary_path: None,
             max_consecutive_task_failures: None,
             commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
         };
 
         let initial = StetRunResult {
@@ -2273,4 +3757,537 @@ exit 0
         assert_eq!(outcome.rounds_used, 2);
         assert!(!outcome.findings_resolved);
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn address_loop_fail_fast_findings_fails_without_rounds() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Agent and stet both fail if invoked: the gate must not call either.
+        let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
+
+        let config = crate::config::PealConfig {
+            agent_cmd: "false".to_owned(),
+            plan_path: PathBuf::from("plan.md"),
+            repo_path: dir.path().to_path_buf(),
+            stet_commands: vec![],
+            sandbox: "disabled".to_owned(),
+            model: None,
+            max_address_rounds: 2,
+            on_findings_remaining: "warn".to_owned(),
+            state_dir: dir.path().join(".peal"),
+            phase_timeout_sec: 30,
+            phase_retry_count: 0,
+            phase_3_retry_count: 0,
+            parallel: false,
+            max_parallel: 4,
+            continue_with_remaining_tasks: false,
+            log_level: None,
+            log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
+            stet_path: None,
+            stet_start_ref: None,
+            stet_start_extra_args: vec![],
+            stet_run_extra_args: vec![],
+            stet_disable_llm_triage: false,
+            stet_dismiss_patterns: vec![],
+            on_stet_fail: "fail".to_owned(),
+            post_run_commands: vec![],
+            post_run_timeout_sec: None,
+            normalize_plan: false,
+            normalize_retry_count: 0,
+            normalize_prompt_path: None,
+            validate_plan_text: false,
+            min_plan_text_len: None,
+            run_summary_path: None,
+            max_consecutive_task_failures: None,
+            commit_after_phase2: false,
+            fail_fast_findings: true,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
+        };
+
+        let initial = StetRunResult {
+            stdout: "warning: bad code".to_owned(),
+            stderr: String::new(),
+            exit_code: Some(1),
+            has_findings: true,
+        };
+
+        let err = address_loop(&false_path, &false_path, &config, 4, &initial).unwrap_err();
+        match err {
+            PealError::StetFindingsRemain {
+                task_index,
+                rounds,
+                ..
+            } => {
+                assert_eq!(task_index, 4);
+                assert_eq!(rounds, 0);
+            }
+            other => panic!("expected StetFindingsRemain, got: {other:?}"),
+        }
+
+        let err = address_loop_custom(&false_path, &config, 4, &initial, || {
+            panic!("custom command must not be re-run in fail-fast mode")
+        })
+        .unwrap_err();
+        assert!(matches!(err, PealError::StetFindingsRemain { rounds: 0, .. }), "got {err:?}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn address_loop_zero_rounds_applies_on_findings_remaining() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
+
+        let config = crate::config::PealConfig {
+            agent_cmd: "false".to_owned(),
+            plan_path: PathBuf::from("plan.md"),
+            repo_path: dir.path().to_path_buf(),
+            stet_commands: vec![],
+            sandbox: "disabled".to_owned(),
+            model: None,
+            max_address_rounds: 0,
+            on_findings_remaining: "warn".to_owned(),
+            state_dir: dir.path().join(".peal"),
+            phase_timeout_sec: 30,
+            phase_retry_count: 0,
+            phase_3_retry_count: 0,
+            parallel: false,
+            max_parallel: 4,
+            continue_with_remaining_tasks: false,
+            log_level: None,
+            log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
+            stet_path: None,
+            stet_start_ref: None,
+            stet_start_extra_args: vec![],
+            stet_run_extra_args: vec![],
+            stet_disable_llm_triage: false,
+            stet_dismiss_patterns: vec![],
+            on_stet_fail: "fail".to_owned(),
+            post_run_commands: vec![],
+            post_run_timeout_sec: None,
+            normalize_plan: false,
+            normalize_retry_count: 0,
+            normalize_prompt_path: None,
+            validate_plan_text: false,
+            min_plan_text_len: None,
+            run_summary_path: None,
+            max_consecutive_task_failures: None,
+            commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
+        };
+
+        let initial = StetRunResult {
+            stdout: "warning: bad code".to_owned(),
+            stderr: String::new(),
+            exit_code: Some(1),
+            has_findings: true,
+        };
+
+        let outcome = address_loop(&false_path, &false_path, &config, 1, &initial).unwrap();
+        assert_eq!(outcome.rounds_used, 0);
+        assert!(!outcome.findings_resolved);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn address_loop_salvages_partial_work_when_agent_fails_but_review_clean() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Agent always fails, as if it timed out after applying a fix.
+        let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
+
+        let config = crate::config::PealConfig {
+            agent_cmd: "false".to_owned(),
+            plan_path: PathBuf::from("plan.md"),
+            repo_path: dir.path().to_path_buf(),
+            stet_commands: vec![],
+            sandbox: "disabled".to_owned(),
+            model: None,
+            max_address_rounds: 2,
+            on_findings_remaining: "fail".to_owned(),
+            state_dir: dir.path().join(".peal"),
+            phase_timeout_sec: 30,
+            phase_retry_count: 0,
+            phase_3_retry_count: 0,
+            parallel: false,
+            max_parallel: 4,
+            continue_with_remaining_tasks: false,
+            log_level: None,
+            log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
+            stet_path: None,
+            stet_start_ref: None,
+            stet_start_extra_args: vec![],
+            stet_run_extra_args: vec![],
+            stet_disable_llm_triage: false,
+            stet_dismiss_patterns: vec![],
+            on_stet_fail: "fail".to_owned(),
+            post_run_commands: vec![],
+            post_run_timeout_sec: None,
+            normalize_plan: false,
+            normalize_retry_count: 0,
+            normalize_prompt_path: None,
+            validate_plan_text: false,
+            min_plan_text_len: None,
+            run_summary_path: None,
+            max_consecutive_task_failures: None,
+            commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
+        };
+
+        let initial = StetRunResult {
+            stdout: "warning: bad code".to_owned(),
+            stderr: String::new(),
+            exit_code: Some(1),
+            has_findings: true,
+        };
+
+        let outcome = address_loop_custom(&false_path, &config, 2, &initial, || {
+            Ok(run_result_from_output(String::new(), String::new(), Some(0)))
+        })
+        .unwrap();
+        assert!(outcome.findings_resolved);
+        assert_eq!(outcome.rounds_used, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn address_loop_returns_agent_error_when_agent_fails_and_findings_remain() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let false_path = crate::cursor::resolve_agent_cmd("false").expect("false must exist");
+
+        let config = crate::config::PealConfig {
+            agent_cmd: "false".to_owned(),
+            plan_path: PathBuf::from("plan.md"),
+            repo_path: dir.path().to_path_buf(),
+            stet_commands: vec![],
+            sandbox: "disabled".to_owned(),
+            model: None,
+            max_address_rounds: 2,
+            on_findings_remaining: "fail".to_owned(),
+            state_dir: dir.path().join(".peal"),
+            phase_timeout_sec: 30,
+            phase_retry_count: 0,
+            phase_3_retry_count: 0,
+            parallel: false,
+            max_parallel: 4,
+            continue_with_remaining_tasks: false,
+            log_level: None,
+            log_file: None,
+            log_max_size_mb: None,
+            log_keep_files: 5,
+            stet_path: None,
+            stet_start_ref: None,
+            stet_start_extra_args: vec![],
+            stet_run_extra_args: vec![],
+            stet_disable_llm_triage: false,
+            stet_dismiss_patterns: vec![],
+            on_stet_fail: "fail".to_owned(),
+            post_run_commands: vec![],
+            post_run_timeout_sec: None,
+            normalize_plan: false,
+            normalize_retry_count: 0,
+            normalize_prompt_path: None,
+            validate_plan_text: false,
+            min_plan_text_len: None,
+            run_summary_path: None,
+            max_consecutive_task_failures: None,
+            commit_after_phase2: false,
+            fail_fast_findings: false,
+            agent_max_requests_per_min: None,
+            phase_max_rss_mb: None,
+            phase_max_cpu_sec: None,
+            vars: Default::default(),
+            summarize_tasks: false,
+            agent_health_check: false,
+            agent_health_check_timeout_sec: 60,
+            otel_endpoint: None,
+            stet_stream: false,
+            stet_stream_abort_threshold: None,
+            command_shell: "none".to_owned(),
+            on_stale_stet_session: "finish".to_owned(),
+            agent_backend: "cli".to_owned(),
+            api_base_url: "https://api.openai.com/v1".to_owned(),
+            api_key_env: "OPENAI_API_KEY".to_owned(),
+            api_model: None,
+            triage_agent_cmd: None,
+            triage_model: None,
+            no_git_checks: false,
+            rollback_failed_tasks: false,
+            serve_status: None,
+            stage_commands: vec![],
+            max_prompt_tokens: None,
+            on_prompt_too_large: "fail".to_owned(),
+            stet_scope_to_task: false,
+            stet_path_args: vec!["{path}".to_owned()],
+            max_agent_calls: None,
+            retry_failed_parallel_tasks: false,
+            env_file: None,
+            push_after_segment: false,
+            push_remote: "origin".to_owned(),
+            push_branch: None,
+            on_push_fail: "warn".to_owned(),
+            phase2_change_check: "off".to_owned(),
+            escalation_models: vec![],
+            enable_phase3: true,
+            session_size: None,
+            model_rates: Default::default(),
+            parallel_phase3: false,
+            strict_state: false,
+            log_prompts: false,
+            log_agent_output: "none".to_owned(),
+            on_renumber: "rerun".to_owned(),
+            vcs: "auto".to_owned(),
+            stet_health_command: None,
+            artifact_retention: Default::default(),
+            repo_subdir: None,
+            triage_cache_ttl_sec: 0,
+            no_cache: false,
+            pre_phase2_commands: Vec::new(),
+            post_phase2_commands: Vec::new(),
+            on_phase_hook_fail: "fail".to_owned(),
+            stet_finish_commands: Vec::new(),
+            findings_summary_top_n: None,
+            sparse_checkout: false,
+            lock_path: None,
+            phase3_scope: "task".to_owned(),
+            on_expect_fail: "fail".to_owned(),
+            dismiss_requires_confirmation: None,
+            done_marker: None,
+            on_missing_done_marker: "retry".to_owned(),
+            triage_skip_severities: Vec::new(),
+            shutdown_grace_sec: 10,
+            prompt_middleware: Vec::new(),
+            execution: Default::default(),
+            merge_conflict_retry_count: 1,
+        };
+
+        let initial = StetRunResult {
+            stdout: "warning: bad code".to_owned(),
+            stderr: String::new(),
+            exit_code: Some(1),
+            has_findings: true,
+        };
+
+        let err = address_loop_custom(&false_path, &config, 2, &initial, || Ok(initial.clone()))
+            .unwrap_err();
+        assert!(
+            matches!(err, PealError::PhaseNonZeroExit { phase: 3, .. }),
+            "expected phase 3 agent error, got: {err:?}"
+        );
+    }
+
+
+    #[test]
+    fn path_filter_args_repeats_template_per_file() {
+        let files = vec!["src/a.rs".to_owned(), "b c.rs".to_owned()];
+        assert_eq!(path_filter_args(&["{path}".to_owned()], &files), vec!["src/a.rs", "b c.rs"]);
+        let flagged = path_filter_args(&["--path".to_owned(), "{path}".to_owned()], &files);
+        assert_eq!(flagged, vec!["--path", "src/a.rs", "--path", "b c.rs"]);
+        assert!(path_filter_args(&["{path}".to_owned()], &[]).is_empty());
+    }
 }
@@ -2,13 +2,34 @@
 //!
 //! Spawns child processes directly via `execvp` semantics — no intermediate
 //! shell — and captures stdout/stderr into bounded buffers.
+//!
+//! Optional [`ResourceLimits`] supervise the child on Unix: CPU time via
+//! `RLIMIT_CPU` (set in the child before exec, inherited by its descendants) and
+//! resident memory via a watchdog that sums the RSS of the child's process group
+//! and kills the whole group when the limit is exceeded (Linux only).
+//!
+//! Every child spawned here is recorded in a registry until it has been waited for, so exit
+//! paths that leave one running (a signal, a panic) can kill it with
+//! [`terminate_live_children`] instead of orphaning it.
+//!
+//! Output is captured as raw bytes and decoded once, with invalid UTF-8 replaced by U+FFFD
+//! (so agents and tools running under non-UTF-8 locales cannot make peal panic). Text handed
+//! on to logs, errors, and prompts is cut with [`truncate_utf8`] / [`tail_utf8`], which never
+//! split a multi-byte character.
 
 use std::ffi::OsStr;
-use std::io::Read;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::ops::ControlFlow;
+use std::collections::BTreeMap;
 use std::path::Path;
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use std::time::{Duration, Instant};
 
+use tracing::warn;
+
 /// Upper bound on bytes read from each of stdout / stderr to prevent
 /// unbounded memory use (10 MiB).
 const MAX_OUTPUT_BYTES: u64 = 10 * 1024 * 1024;
@@ -16,6 +37,109 @@ const MAX_OUTPUT_BYTES: u64 = 10 * 1024 * 1024;
 /// Polling interval while waiting for a child process with a timeout.
 const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
+/// Interval between memory checks by the RSS watchdog.
+const MEMORY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Children spawned by this module that have not been waited for yet: pid → (program, whether
+/// the child leads its own process group).
+static LIVE_CHILDREN: Mutex<BTreeMap<u32, (String, bool)>> = Mutex::new(BTreeMap::new());
+
+fn live_children() -> MutexGuard<'static, BTreeMap<u32, (String, bool)>> {
+    LIVE_CHILDREN.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Keeps a spawned child in [`LIVE_CHILDREN`] until it is dropped, after the child was waited for.
+struct LiveChild(u32);
+
+impl LiveChild {
+    fn register(child: &Child, program: &str, own_group: bool) -> Self {
+        live_children().insert(child.id(), (program.to_owned(), own_group));
+        LiveChild(child.id())
+    }
+}
+
+impl Drop for LiveChild {
+    fn drop(&mut self) {
+        live_children().remove(&self.0);
+    }
+}
+
+/// A child process still running when peal gave up on it (see [`terminate_live_children`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeftoverChild {
+    pub pid: u32,
+    pub program: String,
+}
+
+/// Kill every child spawned by this module that has not been waited for (its whole process
+/// group when it has one) and return them, so they can be logged. On non-Unix platforms the
+/// children are only reported.
+pub fn terminate_live_children() -> Vec<LeftoverChild> {
+    terminate_live_children_where(|_| true)
+}
+
+/// [`terminate_live_children`] for the children whose program matches `filter`.
+fn terminate_live_children_where(filter: impl Fn(&str) -> bool) -> Vec<LeftoverChild> {
+    let mut live = live_children();
+    let pids: Vec<u32> = live.iter().filter(|(_, (program, _))| filter(program)).map(|(&pid, _)| pid).collect();
+    pids.into_iter()
+        .filter_map(|pid| live.remove(&pid).map(|entry| (pid, entry)))
+        .map(|(pid, (program, own_group))| {
+            kill_pid(pid, own_group);
+            LeftoverChild { pid, program }
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn kill_pid(pid: u32, own_group: bool) {
+    // SAFETY: kill has no memory-safety preconditions; the pid is a child that has not been
+    // waited for, so it cannot have been reused.
+    unsafe {
+        if own_group {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+        libc::kill(pid as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_pid(_pid: u32, _own_group: bool) {}
+
+/// Optional per-invocation resource limits. `None` fields are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Maximum resident memory (MiB) of the child and its process group. Linux only.
+    pub max_rss_mb: Option<u64>,
+    /// Maximum CPU seconds per process (`RLIMIT_CPU`). Unix only.
+    pub max_cpu_sec: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Returns `true` when no limit is set.
+    pub fn is_empty(&self) -> bool {
+        self.max_rss_mb.is_none() && self.max_cpu_sec.is_none()
+    }
+}
+
+/// Which resource limit stopped the child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// The RSS watchdog killed the process group.
+    Memory { max_rss_mb: u64 },
+    /// The kernel stopped the child with `SIGXCPU`.
+    Cpu { max_cpu_sec: u64 },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::Memory { max_rss_mb } => write!(f, "memory limit of {max_rss_mb} MiB RSS"),
+            LimitExceeded::Cpu { max_cpu_sec } => write!(f, "CPU limit of {max_cpu_sec}s"),
+        }
+    }
+}
+
 /// Captured output from a subprocess invocation.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CommandResult {
@@ -25,13 +149,18 @@ pub struct CommandResult {
     /// report an exit code (e.g. signal termination on Unix).
     pub exit_code: Option<i32>,
     pub timed_out: bool,
+    /// Set when the child was stopped for exceeding a [`ResourceLimits`] value.
+    pub limit_exceeded: Option<LimitExceeded>,
+    /// Signal that terminated the child (Unix), when it was not killed by peal itself
+    /// for a timeout or memory limit.
+    pub signal: Option<i32>,
 }
 
 impl CommandResult {
     /// Returns `true` when the process exited with code 0 and was not killed
-    /// by timeout.
+    /// by timeout or a resource limit.
     pub fn success(&self) -> bool {
-        self.exit_code == Some(0) && !self.timed_out
+        self.exit_code == Some(0) && !self.timed_out && self.limit_exceeded.is_none()
     }
 }
 
@@ -46,12 +175,68 @@ pub fn run_command<S: AsRef<OsStr>>(
     cwd: &Path,
     timeout: Option<Duration>,
 ) -> std::io::Result<CommandResult> {
-    let mut child = Command::new(program)
+    run_command_with_limits(program, args, cwd, timeout, ResourceLimits::default())
+}
+
+/// Like [`run_command`], additionally enforcing `limits` (see the module docs).
+///
+/// When `max_rss_mb` is set the child runs in its own process group so the
+/// watchdog can measure and kill it together with its descendants; on timeout
+/// the whole group is killed too. Limits that the platform cannot enforce are
+/// ignored with a warning.
+pub fn run_command_with_limits<S: AsRef<OsStr>>(
+    program: &str,
+    args: &[S],
+    cwd: &Path,
+    timeout: Option<Duration>,
+    limits: ResourceLimits,
+) -> std::io::Result<CommandResult> {
+    run_supervised(program, args, cwd, timeout, limits, None)
+}
+
+/// Like [`run_command`], writing `input` to the child's stdin (then closing it).
+///
+/// Used to hand secrets such as API keys to a child without putting them in its argv.
+pub fn run_command_with_input<S: AsRef<OsStr>>(
+    program: &str,
+    args: &[S],
+    cwd: &Path,
+    timeout: Option<Duration>,
+    input: &str,
+) -> std::io::Result<CommandResult> {
+    run_supervised(program, args, cwd, timeout, ResourceLimits::default(), Some(input))
+}
+
+fn run_supervised<S: AsRef<OsStr>>(
+    program: &str,
+    args: &[S],
+    cwd: &Path,
+    timeout: Option<Duration>,
+    limits: ResourceLimits,
+    input: Option<&str>,
+) -> std::io::Result<CommandResult> {
+    let mut command = Command::new(program);
+    command
         .args(args)
         .current_dir(cwd)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::inherit() })
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
+        .stderr(Stdio::piped());
+    let own_group = apply_limits(&mut command, limits);
+    let mut child = command.spawn()?;
+    let _live = LiveChild::register(&child, program, own_group);
+
+    // Feed stdin on its own thread so a child that writes before reading cannot deadlock
+    // us; a child that exits without reading just makes the write fail, which is ignored.
+    let stdin_handle = match (input, child.stdin.take()) {
+        (Some(input), Some(mut stdin)) => {
+            let input = input.to_owned();
+            Some(std::thread::spawn(move || {
+                let _ = stdin.write_all(input.as_bytes());
+            }))
+        }
+        _ => None,
+    };
 
     // Take the pipe handles so we can read them on dedicated threads,
     // avoiding deadlock when both pipes fill their OS buffers.
@@ -62,7 +247,11 @@ pub fn run_command<S: AsRef<OsStr>>(
     let stdout_handle = std::thread::spawn(move || read_bounded(child_stdout));
     let stderr_handle = std::thread::spawn(move || read_bounded(child_stderr));
 
-    let (timed_out, exit_code) = wait_with_timeout(&mut child, timeout)?;
+    let (timed_out, status, memory_exceeded) =
+        wait_supervised(&mut child, timeout, limits.max_rss_mb.filter(|_| own_group), own_group)?;
+    let limit_exceeded = memory_exceeded.or_else(|| cpu_limit_hit(status, limits));
+    let exit_code = status.and_then(|s| s.code());
+    let signal = exit_signal(status);
 
     let stdout = stdout_handle
         .join()
@@ -70,17 +259,249 @@ pub fn run_command<S: AsRef<OsStr>>(
     let stderr = stderr_handle
         .join()
         .map_err(|e| std::io::Error::other(format!("stderr reader thread panicked: {e:?}")))??;
+    if let Some(handle) = stdin_handle {
+        let _ = handle.join();
+    }
 
     Ok(CommandResult {
         stdout,
         stderr,
         exit_code,
         timed_out,
+        limit_exceeded,
+        signal,
     })
 }
 
+/// Like [`run_command`], but hands each stdout line (without its line terminator) to
+/// `on_line` as soon as it arrives.
+///
+/// When `on_line` returns [`ControlFlow::Break`] the child is killed and the returned flag
+/// is `true`; the result then holds the stdout read so far and `exit_code: None`. The
+/// timeout covers the whole invocation, as in [`run_command`]. On Unix the child runs in
+/// its own process group, which is killed as a whole on abort or timeout.
+pub fn run_command_streaming<S, F>(
+    program: &str,
+    args: &[S],
+    cwd: &Path,
+    timeout: Option<Duration>,
+    mut on_line: F,
+) -> std::io::Result<(CommandResult, bool)>
+where
+    S: AsRef<OsStr>,
+    F: FnMut(&str) -> ControlFlow<()>,
+{
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .current_dir(cwd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Its own process group, so an early stop also kills descendants holding the pipes open.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let own_group = cfg!(unix);
+    let mut child = command.spawn()?;
+    let _live = LiveChild::register(&child, program, own_group);
+    let child_stdout = child.stdout.take().expect("stdout was piped");
+    let child_stderr = child.stderr.take().expect("stderr was piped");
+    let stderr_handle = std::thread::spawn(move || read_bounded(child_stderr));
+
+    // Lines are read on their own thread so the deadline can be enforced while stdout is quiet.
+    let (tx, rx) = mpsc::channel::<String>();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(child_stdout);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if tx.send(decode_lossy(std::mem::take(&mut buf), false)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let deadline = timeout.map(|d| Instant::now() + d);
+    let mut stdout = String::new();
+    let mut aborted = false;
+    let mut timed_out = false;
+    loop {
+        let received = match deadline {
+            Some(d) => rx.recv_timeout(d.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(line) => {
+                if (stdout.len() as u64) < MAX_OUTPUT_BYTES {
+                    stdout.push_str(&line);
+                }
+                if on_line(line.trim_end_matches(['\r', '\n'])).is_break() {
+                    aborted = true;
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                timed_out = true;
+                break;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    let status = if aborted || timed_out {
+        kill_child(&mut child, own_group);
+        let _ = child.wait();
+        None
+    } else {
+        let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        let (late, status, _) = wait_supervised(&mut child, remaining, None, own_group)?;
+        timed_out = late;
+        status
+    };
+
+    let stderr = stderr_handle
+        .join()
+        .map_err(|e| std::io::Error::other(format!("stderr reader thread panicked: {e:?}")))??;
+
+    Ok((
+        CommandResult {
+            stdout,
+            stderr,
+            exit_code: status.and_then(|s| s.code()),
+            timed_out,
+            limit_exceeded: None,
+            signal: exit_signal(status),
+        },
+        aborted,
+    ))
+}
+
+/// Configure `command` for `limits`. Returns `true` when the child gets its own
+/// process group (memory watchdog active).
+#[cfg(unix)]
+fn apply_limits(command: &mut Command, limits: ResourceLimits) -> bool {
+    use std::os::unix::process::CommandExt;
+
+    if let Some(cpu) = limits.max_cpu_sec {
+        let soft = cpu.max(1) as libc::rlim_t;
+        // SAFETY: the closure runs in the forked child before exec and only calls
+        // setrlimit, which is async-signal-safe.
+        unsafe {
+            command.pre_exec(move || {
+                // Hard limit a few seconds above soft: SIGXCPU first, SIGKILL if ignored.
+                let rl = libc::rlimit {
+                    rlim_cur: soft,
+                    rlim_max: soft.saturating_add(5),
+                };
+                if libc::setrlimit(libc::RLIMIT_CPU, &rl) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    if limits.max_rss_mb.is_some() {
+        if cfg!(target_os = "linux") {
+            command.process_group(0);
+            return true;
+        }
+        warn!("max_rss_mb is only enforced on Linux; ignoring");
+    }
+    false
+}
+
+#[cfg(not(unix))]
+fn apply_limits(_command: &mut Command, limits: ResourceLimits) -> bool {
+    if !limits.is_empty() {
+        warn!("resource limits are only enforced on Unix; ignoring");
+    }
+    false
+}
+
+/// Returns the CPU limit as exceeded when the child was stopped by `SIGXCPU`.
+#[cfg(unix)]
+fn cpu_limit_hit(status: Option<ExitStatus>, limits: ResourceLimits) -> Option<LimitExceeded> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let max_cpu_sec = limits.max_cpu_sec?;
+    (status?.signal() == Some(libc::SIGXCPU)).then_some(LimitExceeded::Cpu { max_cpu_sec })
+}
+
+#[cfg(not(unix))]
+fn cpu_limit_hit(_status: Option<ExitStatus>, _limits: ResourceLimits) -> Option<LimitExceeded> {
+    None
+}
+
+/// Signal number that terminated the child, if it died from one.
+#[cfg(unix)]
+fn exit_signal(status: Option<ExitStatus>) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    status?.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: Option<ExitStatus>) -> Option<i32> {
+    None
+}
+
+/// Kill the child; with `own_group`, kill its whole process group.
+#[cfg(unix)]
+fn kill_child(child: &mut Child, own_group: bool) {
+    if own_group {
+        // SAFETY: kill has no memory-safety preconditions; a negative pid targets
+        // the process group created for this child.
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+}
+
+#[cfg(not(unix))]
+fn kill_child(child: &mut Child, _own_group: bool) {
+    let _ = child.kill();
+}
+
+/// Total resident memory in bytes of all processes in process group `pgid`.
+#[cfg(target_os = "linux")]
+fn process_group_rss_bytes(pgid: u32) -> u64 {
+    // SAFETY: sysconf has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().bytes().all(|b| b.is_ascii_digit()))
+        .filter_map(|e| std::fs::read_to_string(e.path().join("stat")).ok())
+        .filter_map(|stat| {
+            // Fields after the parenthesised command name: state ppid pgrp ... (rss is the 22nd).
+            let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+            let pgrp: u32 = fields.get(2)?.parse().ok()?;
+            let rss_pages: u64 = fields.get(21)?.parse().ok()?;
+            (pgrp == pgid).then_some(rss_pages * page_size)
+        })
+        .sum()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_group_rss_bytes(_pgid: u32) -> u64 {
+    0
+}
+
 /// Wait for the child to exit. If `timeout` is `Some`, poll with `try_wait`
-/// and kill the child when the deadline is exceeded.
+/// and kill the child when the deadline is exceeded. If `max_rss_mb` is `Some`,
+/// also check the process group's RSS every [`MEMORY_POLL_INTERVAL`] and kill the
+/// group when it exceeds the limit.
+///
+/// Returns `(timed_out, exit status, memory limit exceeded)`; the status is
+/// `None` when peal killed the child.
 ///
 /// # Race Condition Note
 ///
@@ -88,38 +509,105 @@ pub fn run_command<S: AsRef<OsStr>>(
 /// decide to kill it due to timeout. In this case, we might report a timeout
 /// even if the process finished. This is acceptable for our use case: if it's
 /// that close to the timeout, treating it as a timeout is safe.
-fn wait_with_timeout(
+fn wait_supervised(
     child: &mut Child,
     timeout: Option<Duration>,
-) -> std::io::Result<(bool, Option<i32>)> {
-    match timeout {
-        None => {
-            let status = child.wait()?;
-            Ok((false, status.code()))
+    max_rss_mb: Option<u64>,
+    own_group: bool,
+) -> std::io::Result<(bool, Option<ExitStatus>, Option<LimitExceeded>)> {
+    if timeout.is_none() && max_rss_mb.is_none() {
+        let status = child.wait()?;
+        return Ok((false, Some(status), None));
+    }
+
+    let deadline = timeout.map(|d| Instant::now() + d);
+    let mut next_memory_check = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((false, Some(status), None));
         }
-        Some(duration) => {
-            let deadline = Instant::now() + duration;
-            loop {
-                if let Some(status) = child.try_wait()? {
-                    return Ok((false, status.code()));
-                }
-                if Instant::now() >= deadline {
-                    let _ = child.kill();
-                    let _ = child.wait();
-                    return Ok((true, None));
-                }
-                std::thread::sleep(POLL_INTERVAL);
+        let now = Instant::now();
+        if deadline.is_some_and(|d| now >= d) {
+            kill_child(child, own_group);
+            let _ = child.wait();
+            return Ok((true, None, None));
+        }
+        if let Some(max) = max_rss_mb
+            && now >= next_memory_check
+        {
+            next_memory_check = now + MEMORY_POLL_INTERVAL;
+            let rss = process_group_rss_bytes(child.id());
+            if rss > max.saturating_mul(1024 * 1024) {
+                warn!(pid = child.id(), rss_bytes = rss, max_rss_mb = max, "child exceeded memory limit, killing process group");
+                kill_child(child, own_group);
+                let _ = child.wait();
+                return Ok((false, None, Some(LimitExceeded::Memory { max_rss_mb: max })));
             }
         }
+        std::thread::sleep(POLL_INTERVAL);
     }
 }
 
 /// Read up to [`MAX_OUTPUT_BYTES`] from `reader`, returning the result as a
-/// (possibly lossy) UTF-8 string.
-fn read_bounded(reader: impl Read) -> std::io::Result<String> {
+/// (possibly lossy) UTF-8 string. Output past the cap is read and discarded so the child
+/// never blocks on a full pipe.
+fn read_bounded(mut reader: impl Read) -> std::io::Result<String> {
     let mut buf = Vec::new();
-    reader.take(MAX_OUTPUT_BYTES).read_to_end(&mut buf)?;
-    Ok(String::from_utf8_lossy(&buf).into_owned())
+    (&mut reader).take(MAX_OUTPUT_BYTES).read_to_end(&mut buf)?;
+    let capped = buf.len() as u64 == MAX_OUTPUT_BYTES;
+    if capped {
+        std::io::copy(&mut reader, &mut std::io::sink())?;
+    }
+    Ok(decode_lossy(buf, capped))
+}
+
+/// Decode captured output as UTF-8, replacing invalid sequences with U+FFFD. When `cut` is
+/// true the bytes were cut at an arbitrary limit, so a multi-byte character left incomplete at
+/// the end is dropped rather than turned into a replacement character.
+pub fn decode_lossy(mut bytes: Vec<u8>, cut: bool) -> String {
+    if cut {
+        // Back up over continuation bytes to the last lead byte; drop it if its sequence is short.
+        let tail_start = bytes.len().saturating_sub(3);
+        if let Some(lead) = (tail_start..bytes.len()).rev().find(|&i| bytes[i] & 0xC0 != 0x80) {
+            let needed = match bytes[lead] {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            if bytes.len() - lead < needed {
+                bytes.truncate(lead);
+            }
+        }
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    }
+}
+
+/// The longest prefix of `s` that is at most `max` bytes and ends on a char boundary.
+pub fn truncate_utf8(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// The longest suffix of `s` that is at most `max` bytes and starts on a char boundary.
+pub fn tail_utf8(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut start = s.len() - max;
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+    &s[start..]
 }
 
 /// Parse a single command string into program and args (first token = program, rest = args).
@@ -143,6 +631,27 @@ pub fn run_command_string(
     Some(run_command(program, &args, cwd, timeout))
 }
 
+/// Run a configured command string according to `command_shell`: `"sh"` runs the whole
+/// string via `sh -c` (so `&&`, pipes, redirection and quoting work); any other value
+/// (`"none"`) behaves like [`run_command_string`].
+///
+/// Returns `None` if the string is empty or only whitespace (caller should skip).
+pub fn run_command_string_with_shell(
+    command: &str,
+    shell: &str,
+    cwd: &Path,
+    timeout: Option<Duration>,
+) -> Option<std::io::Result<CommandResult>> {
+    let trimmed = command.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if shell == "sh" {
+        return Some(run_command("sh", &["-c", trimmed], cwd, timeout));
+    }
+    run_command_string(trimmed, cwd, timeout)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -220,6 +729,47 @@ mod tests {
         assert_eq!(result.stderr.trim(), "err");
     }
 
+    #[test]
+    fn captures_cjk_emoji_and_invalid_utf8() {
+        // printf with octal escapes: "日本語 🚀" then a lone 0xFF byte and a truncated 3-byte sequence.
+        let script = r"printf '\346\227\245\346\234\254\350\252\236 \360\237\232\200\n'; printf 'bad \377 end \344\270' >&2";
+        let result = run_command("sh", &["-c", script], &tmp_dir(), None).unwrap();
+        assert_eq!(result.stdout, "日本語 🚀\n");
+        assert_eq!(result.stderr, "bad \u{FFFD} end \u{FFFD}");
+
+        let (streamed, _) =
+            run_command_streaming("sh", &["-c", r"printf '\344\270\255\n\377\n'"], &tmp_dir(), None, |_| {
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(streamed.stdout, "中\n\u{FFFD}\n");
+    }
+
+    #[test]
+    fn decode_lossy_drops_a_character_cut_at_the_limit() {
+        let mut bytes = "ok 🚀".as_bytes().to_vec();
+        bytes.truncate(bytes.len() - 1);
+        assert_eq!(decode_lossy(bytes.clone(), true), "ok ");
+        assert_eq!(decode_lossy(bytes, false), "ok \u{FFFD}");
+        assert_eq!(decode_lossy("日本".as_bytes().to_vec(), true), "日本");
+        assert_eq!(decode_lossy(vec![b'a', 0xFF], true), "a\u{FFFD}");
+    }
+
+    #[test]
+    fn truncation_helpers_respect_char_boundaries() {
+        let text = "ab日本語🚀";
+        for max in 0..=text.len() + 1 {
+            let head = truncate_utf8(text, max);
+            let tail = tail_utf8(text, max);
+            assert!(head.len() <= max && text.starts_with(head), "head at {max}: {head:?}");
+            assert!(tail.len() <= max && text.ends_with(tail), "tail at {max}: {tail:?}");
+        }
+        assert_eq!(truncate_utf8(text, 4), "ab");
+        assert_eq!(truncate_utf8(text, 5), "ab日");
+        assert_eq!(tail_utf8(text, 6), "🚀");
+        assert_eq!(tail_utf8(text, 7), "語🚀");
+    }
+
     #[test]
     fn spawn_failure_returns_io_error() {
         let result = run_command("nonexistent-binary-xyz", &[] as &[&str], &tmp_dir(), None);
@@ -235,6 +785,8 @@ mod tests {
             stderr: String::new(),
             exit_code: Some(0),
             timed_out: false,
+            limit_exceeded: None,
+            signal: None,
         };
         assert!(ok.success());
 
@@ -243,6 +795,8 @@ mod tests {
             stderr: String::new(),
             exit_code: Some(1),
             timed_out: false,
+            limit_exceeded: None,
+            signal: None,
         };
         assert!(!failed.success());
 
@@ -251,6 +805,8 @@ mod tests {
             stderr: String::new(),
             exit_code: None,
             timed_out: true,
+            limit_exceeded: None,
+            signal: None,
         };
         assert!(!timed_out.success());
     }
@@ -277,4 +833,187 @@ mod tests {
 
         assert_eq!(result.exit_code, Some(0));
     }
+
+
+    #[cfg(unix)]
+    #[test]
+    fn cpu_limit_stops_busy_loop() {
+        let limits = ResourceLimits {
+            max_rss_mb: None,
+            max_cpu_sec: Some(1),
+        };
+        let result = run_command_with_limits(
+            "sh",
+            &["-c", "while :; do :; done"],
+            &tmp_dir(),
+            Some(Duration::from_secs(30)),
+            limits,
+        )
+        .unwrap();
+
+        assert!(!result.timed_out, "CPU limit should stop the child before the timeout");
+        assert_eq!(result.limit_exceeded, Some(LimitExceeded::Cpu { max_cpu_sec: 1 }));
+        assert!(!result.success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn live_children_are_registered_until_waited_and_can_be_terminated() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("linger");
+        std::fs::write(&script, "#!/bin/sh\nexec sleep 30\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let program = script.to_str().unwrap().to_owned();
+        let is_linger = |p: &str| p == program;
+
+        let handle = {
+            let program = program.clone();
+            std::thread::spawn(move || run_command(&program, &[] as &[&str], &tmp_dir(), None))
+        };
+        let start = Instant::now();
+        while !live_children().values().any(|(p, _)| is_linger(p)) {
+            assert!(start.elapsed() < Duration::from_secs(5), "child was never registered");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let killed = terminate_live_children_where(is_linger);
+        assert_eq!(killed.len(), 1);
+        assert_eq!(killed[0].program, program);
+        let result = handle.join().unwrap().unwrap();
+        assert_eq!(result.signal, Some(libc::SIGKILL));
+        assert!(!live_children().values().any(|(p, _)| is_linger(p)), "waited children are dropped");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn memory_limit_kills_process_group() {
+        let limits = ResourceLimits {
+            max_rss_mb: Some(32),
+            max_cpu_sec: None,
+        };
+        // `tail` buffers a newline-free stream in memory; the shell's child grows past the limit.
+        let result = run_command_with_limits(
+            "sh",
+            &["-c", "head -c 2000000000 /dev/zero | tail -n 1 >/dev/null"],
+            &tmp_dir(),
+            Some(Duration::from_secs(60)),
+            limits,
+        )
+        .unwrap();
+
+        assert!(!result.timed_out);
+        assert_eq!(result.limit_exceeded, Some(LimitExceeded::Memory { max_rss_mb: 32 }));
+        assert!(!result.success());
+    }
+
+    #[test]
+    fn limits_within_budget_succeed() {
+        let limits = ResourceLimits {
+            max_rss_mb: Some(1024),
+            max_cpu_sec: Some(30),
+        };
+        let result =
+            run_command_with_limits("echo", &["ok"], &tmp_dir(), None, limits).unwrap();
+
+        assert!(result.success());
+        assert_eq!(result.stdout.trim(), "ok");
+        assert_eq!(result.limit_exceeded, None);
+    }
+
+
+    #[test]
+    fn streaming_delivers_lines_as_they_arrive() {
+        let mut lines = Vec::new();
+        let (result, aborted) = run_command_streaming(
+            "sh",
+            &["-c", "echo one; echo two"],
+            &tmp_dir(),
+            Some(Duration::from_secs(5)),
+            |line| {
+                lines.push(line.to_owned());
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+
+        assert!(!aborted);
+        assert_eq!(lines, ["one", "two"]);
+        assert_eq!(result.stdout, "one\ntwo\n");
+        assert!(result.success());
+    }
+
+    #[test]
+    fn streaming_break_kills_child_early() {
+        let start = Instant::now();
+        let (result, aborted) = run_command_streaming(
+            "sh",
+            &["-c", "echo first; exec sleep 60"],
+            &tmp_dir(),
+            Some(Duration::from_secs(30)),
+            |_| ControlFlow::Break(()),
+        )
+        .unwrap();
+
+        assert!(aborted);
+        assert!(!result.timed_out);
+        assert_eq!(result.exit_code, None);
+        assert_eq!(result.stdout, "first\n");
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn streaming_timeout_kills_quiet_child() {
+        let (result, aborted) = run_command_streaming(
+            "sleep",
+            &["60"],
+            &tmp_dir(),
+            Some(Duration::from_millis(200)),
+            |_| ControlFlow::Continue(()),
+        )
+        .unwrap();
+
+        assert!(!aborted);
+        assert!(result.timed_out);
+    }
+
+
+    #[cfg(unix)]
+    #[test]
+    fn run_command_string_with_shell_sh_supports_operators_and_quotes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let result = run_command_string_with_shell(
+            "cd sub && echo 'a  b' > out.txt && cat out.txt",
+            "sh",
+            dir.path(),
+            None,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert!(result.success(), "stderr: {}", result.stderr);
+        assert_eq!(result.stdout, "a  b\n");
+    }
+
+    #[test]
+    fn run_command_string_with_shell_none_splits_on_whitespace() {
+        let result = run_command_string_with_shell("echo 'a  b' && true", "none", &tmp_dir(), None)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(result.stdout.trim(), "'a b' && true");
+        assert!(run_command_string_with_shell("  ", "sh", &tmp_dir(), None).is_none());
+    }
+
+
+    #[test]
+    #[cfg(unix)]
+    fn run_command_with_input_feeds_stdin() {
+        let result =
+            run_command_with_input("cat", &[] as &[&str], &tmp_dir(), None, "from stdin\n").unwrap();
+        assert!(result.success());
+        assert_eq!(result.stdout, "from stdin\n");
+    }
 }
@@ -0,0 +1,260 @@
+//! Scriptable fake agent and stet executables for tests.
+//!
+//! [`FakeAgent`] and [`FakeStet`] write a small shell script (and its canned replies) into a
+//! directory, typically a temp dir, and point peal at it: `agent_cmd` or `stet_path` set to
+//! [`FakeAgent::path`] / [`FakeStet::path`]. Each invocation takes the next [`Reply`] of its
+//! sequence, so a test can script "fail once, then succeed" or "findings, then a clean review";
+//! once the sequence is used up the last reply repeats. A reply sets stdout, stderr, the exit
+//! code, and a latency to sleep before answering. Every invocation's arguments are recorded and
+//! can be read back with `calls`.
+//!
+//! The agent has one sequence for all invocations (Phase 1, Phase 2, Phase 3, triage). The fake
+//! stet has one per subcommand (`run`, `start`, `dismiss`, ...); a subcommand without replies
+//! exits 0 and prints nothing. Invocations may run concurrently (parallel tasks); they take
+//! replies in the order they arrive. Unix only.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// One canned response of a fake executable.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reply {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+    /// Slept before printing anything.
+    pub latency: Duration,
+}
+
+impl Reply {
+    /// Exit 0 printing `stdout`.
+    pub fn ok(stdout: impl Into<String>) -> Self {
+        Reply {
+            stdout: stdout.into(),
+            ..Reply::default()
+        }
+    }
+
+    /// Exit with `exit_code` printing `stderr`.
+    pub fn fail(exit_code: i32, stderr: impl Into<String>) -> Self {
+        Reply {
+            stderr: stderr.into(),
+            exit_code,
+            ..Reply::default()
+        }
+    }
+
+    /// A `stet run --output=json` review with one finding per `(id, message)`; exits 1 when
+    /// there are findings, like stet.
+    pub fn findings(findings: &[(&str, &str)]) -> Self {
+        let items: Vec<serde_json::Value> = findings
+            .iter()
+            .map(|(id, message)| serde_json::json!({ "id": id, "message": message }))
+            .collect();
+        Reply {
+            stdout: format!("{}\n", serde_json::json!({ "findings": items })),
+            exit_code: i32::from(!findings.is_empty()),
+            ..Reply::default()
+        }
+    }
+
+    /// The same reply, answered after `latency`.
+    pub fn after(self, latency: Duration) -> Self {
+        Reply { latency, ..self }
+    }
+}
+
+/// A fake agent executable; see the module docs.
+#[derive(Debug, Clone)]
+pub struct FakeAgent {
+    script: FakeScript,
+}
+
+impl FakeAgent {
+    /// Write the fake agent into `dir` (under `fake-agent/`), answering with `replies` in turn.
+    pub fn new(dir: &Path, replies: Vec<Reply>) -> io::Result<Self> {
+        let script = FakeScript::create(dir, "fake-agent", "channel=agent")?;
+        script.set_replies("agent", &replies)?;
+        Ok(FakeAgent { script })
+    }
+
+    /// Path of the executable, for `agent_cmd`.
+    pub fn path(&self) -> &Path {
+        &self.script.path
+    }
+
+    /// Arguments of each invocation so far (joined with spaces), in order.
+    pub fn calls(&self) -> Vec<String> {
+        self.script.calls("agent")
+    }
+}
+
+/// A fake stet executable; see the module docs.
+#[derive(Debug, Clone)]
+pub struct FakeStet {
+    script: FakeScript,
+}
+
+impl FakeStet {
+    /// Write the fake stet into `dir` (under `fake-stet/`), answering `stet run` with `runs` in
+    /// turn. Other subcommands succeed silently until given replies with [`FakeStet::on`].
+    pub fn new(dir: &Path, runs: Vec<Reply>) -> io::Result<Self> {
+        let script = FakeScript::create(
+            dir,
+            "fake-stet",
+            "case \"$1\" in ''|*/*|.*) channel=other;; *) channel=\"$1\";; esac",
+        )?;
+        script.set_replies("run", &runs)?;
+        Ok(FakeStet { script })
+    }
+
+    /// Answer `subcommand` with `replies` in turn, replacing any earlier replies for it.
+    pub fn on(&self, subcommand: &str, replies: Vec<Reply>) -> io::Result<()> {
+        self.script.set_replies(subcommand, &replies)
+    }
+
+    /// Path of the executable, for `stet_path`.
+    pub fn path(&self) -> &Path {
+        &self.script.path
+    }
+
+    /// Arguments of each `subcommand` invocation so far (joined with spaces, subcommand
+    /// included), in order.
+    pub fn calls(&self, subcommand: &str) -> Vec<String> {
+        self.script.calls(subcommand)
+    }
+}
+
+/// The script and its data directory: `<root>/<channel>/` holds `count`, `call-N`, and the
+/// replies (`reply-N.out`, `.err`, `.code`, `.delay`; `reply-last.*` answers past the end).
+#[derive(Debug, Clone)]
+struct FakeScript {
+    root: PathBuf,
+    path: PathBuf,
+}
+
+impl FakeScript {
+    /// `select_channel` is shell code that sets `channel` from the arguments.
+    fn create(dir: &Path, name: &str, select_channel: &str) -> io::Result<Self> {
+        use std::os::unix::fs::PermissionsExt;
+        let root = dir.join(name);
+        fs::create_dir_all(&root)?;
+        let quoted_root = format!("'{}'", root.to_string_lossy().replace('\'', r"'\''"));
+        let script = format!(
+            r#"#!/bin/sh
+root={quoted_root}
+{select_channel}
+d="$root/$channel"
+mkdir -p "$d"
+until mkdir "$root/.lock" 2>/dev/null; do sleep 0.01; done
+n=$(( $(cat "$d/count" 2>/dev/null || echo 0) + 1 ))
+echo "$n" > "$d/count"
+rmdir "$root/.lock"
+printf '%s' "$*" > "$d/call-$n"
+r="$d/reply-$n"
+[ -e "$r.code" ] || r="$d/reply-last"
+[ -e "$r.code" ] || exit 0
+[ -s "$r.delay" ] && sleep "$(cat "$r.delay")"
+cat "$r.out"
+cat "$r.err" >&2
+exit "$(cat "$r.code")"
+"#
+        );
+        let path = root.join(name);
+        fs::write(&path, script)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        Ok(FakeScript { root, path })
+    }
+
+    fn set_replies(&self, channel: &str, replies: &[Reply]) -> io::Result<()> {
+        let dir = self.root.join(channel);
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with("reply-") {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        let numbered = replies.iter().enumerate().map(|(i, reply)| ((i + 1).to_string(), reply));
+        for (name, reply) in numbered.chain(replies.last().map(|reply| ("last".to_owned(), reply))) {
+            let file = |ext: &str| dir.join(format!("reply-{name}.{ext}"));
+            fs::write(file("out"), &reply.stdout)?;
+            fs::write(file("err"), &reply.stderr)?;
+            let latency = reply.latency;
+            let delay = if latency.is_zero() {
+                String::new()
+            } else {
+                format!("{}.{:03}", latency.as_secs(), latency.subsec_millis())
+            };
+            fs::write(file("delay"), delay)?;
+            // Written last: the script treats a reply as present once its exit code is.
+            fs::write(file("code"), reply.exit_code.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn calls(&self, channel: &str) -> Vec<String> {
+        let dir = self.root.join(channel);
+        let count: usize = fs::read_to_string(dir.join("count"))
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(0);
+        (1..=count)
+            .map(|n| fs::read_to_string(dir.join(format!("call-{n}"))).unwrap_or_default())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::subprocess;
+
+    fn run(path: &Path, args: &[&str], dir: &Path) -> (String, String, Option<i32>) {
+        let args: Vec<String> = args.iter().map(|a| (*a).to_owned()).collect();
+        let result = subprocess::run_command(&path.to_string_lossy(), &args, dir, None).unwrap();
+        (result.stdout, result.stderr, result.exit_code)
+    }
+
+    #[test]
+    fn fake_agent_answers_in_sequence_then_repeats_the_last_reply() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = FakeAgent::new(dir.path(), vec![Reply::fail(3, "boom"), Reply::ok("done")]).unwrap();
+
+        assert_eq!(run(agent.path(), &["--print", "first"], dir.path()), (String::new(), "boom".to_owned(), Some(3)));
+        assert_eq!(run(agent.path(), &["second"], dir.path()).0, "done");
+        assert_eq!(run(agent.path(), &["third"], dir.path()).0, "done");
+        assert_eq!(agent.calls(), ["--print first", "second", "third"]);
+    }
+
+    #[test]
+    fn fake_stet_keeps_a_sequence_per_subcommand() {
+        let dir = tempfile::tempdir().unwrap();
+        let stet = FakeStet::new(dir.path(), vec![Reply::findings(&[("f1", "unused")]), Reply::findings(&[])]).unwrap();
+
+        let (stdout, _, code) = run(stet.path(), &["run", "--output=json"], dir.path());
+        assert!(stdout.contains("\"f1\""), "{stdout}");
+        assert_eq!(code, Some(1));
+        assert_eq!(run(stet.path(), &["dismiss", "f1", "false_positive"], dir.path()), (String::new(), String::new(), Some(0)));
+        assert_eq!(run(stet.path(), &["run"], dir.path()).2, Some(0));
+
+        stet.on("start", vec![Reply::fail(1, "dirty worktree")]).unwrap();
+        assert_eq!(run(stet.path(), &["start", "HEAD"], dir.path()).2, Some(1));
+        assert_eq!(stet.calls("run"), ["run --output=json", "run"]);
+        assert_eq!(stet.calls("dismiss"), ["dismiss f1 false_positive"]);
+        assert_eq!(stet.calls("finish"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reply_latency_delays_the_answer() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = FakeAgent::new(dir.path(), vec![Reply::ok("slow").after(Duration::from_millis(200))]).unwrap();
+        let start = Instant::now();
+        assert_eq!(run(agent.path(), &[], dir.path()).0, "slow");
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
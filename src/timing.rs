@@ -0,0 +1,144 @@
+//! Per-task phase timing and the run's ETA.
+//!
+//! Like [`crate::retries`], the runner's phase helpers call [`record`] with the time each
+//! Phase 1, 2, or 3 took (retries and escalations included), and the runner wraps each unit
+//! of task work in [`track`] to collect what was recorded on the current thread. Totals are
+//! persisted per task in state (`phase_durations`), so they survive across runs.
+//!
+//! After each unit of work the runner calls [`eta`]: the average time of each phase over the
+//! [`ETA_WINDOW`] most recent tasks (by task index) with a sample for it, times the tasks that
+//! have not completed yet. The result is logged and saved as `eta` in state, which `/status`
+//! reports (see [`crate::status_server`]).
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Tasks (the most recent by index) averaged for the ETA.
+pub const ETA_WINDOW: usize = 10;
+
+/// Time spent in each phase of one task, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseDurations {
+    #[serde(default)]
+    pub phase1_ms: u64,
+    #[serde(default)]
+    pub phase2_ms: u64,
+    /// Phase 3 including the stet runs and address rounds.
+    #[serde(default)]
+    pub phase3_ms: u64,
+}
+
+impl PhaseDurations {
+    /// Sum over all phases.
+    pub fn total_ms(&self) -> u64 {
+        self.phase1_ms + self.phase2_ms + self.phase3_ms
+    }
+
+    /// Adds `other` into `self`.
+    pub fn add(&mut self, other: PhaseDurations) {
+        self.phase1_ms += other.phase1_ms;
+        self.phase2_ms += other.phase2_ms;
+        self.phase3_ms += other.phase3_ms;
+    }
+}
+
+/// Estimated time left in a run, as saved in state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Eta {
+    /// Plan tasks not completed yet.
+    pub remaining_tasks: usize,
+    /// Estimated seconds until they are done.
+    pub remaining_sec: u64,
+    /// RFC 3339 timestamp of the estimate.
+    pub estimated_at: String,
+}
+
+thread_local! {
+    static CURRENT: Cell<PhaseDurations> = const { Cell::new(PhaseDurations { phase1_ms: 0, phase2_ms: 0, phase3_ms: 0 }) };
+}
+
+/// Add `elapsed` to `phase` (1, 2, or 3) of the work currently tracked on this thread.
+/// Outside [`track`] the time is dropped.
+pub fn record(phase: u32, elapsed: Duration) {
+    let ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+    CURRENT.with(|c| {
+        let mut durations = c.get();
+        match phase {
+            1 => durations.phase1_ms += ms,
+            2 => durations.phase2_ms += ms,
+            _ => durations.phase3_ms += ms,
+        }
+        c.set(durations);
+    });
+}
+
+/// Run `f` and return its result with the phase time recorded on this thread while it ran.
+/// Nested calls are counted by the innermost `track` only.
+pub fn track<R>(f: impl FnOnce() -> R) -> (R, PhaseDurations) {
+    let outer = CURRENT.with(|c| c.replace(PhaseDurations::default()));
+    let result = f();
+    let durations = CURRENT.with(|c| c.replace(outer));
+    (result, durations)
+}
+
+/// Estimated time for `remaining` tasks from the recorded `durations`: per phase, the
+/// average over the last [`ETA_WINDOW`] tasks that spent time in it. `None` until some
+/// task has been timed.
+pub fn eta(durations: &BTreeMap<u32, PhaseDurations>, remaining: usize) -> Option<Duration> {
+    let recent: Vec<&PhaseDurations> = durations.values().rev().take(ETA_WINDOW).collect();
+    if recent.iter().all(|d| d.total_ms() == 0) {
+        return None;
+    }
+    let average = |ms: fn(&PhaseDurations) -> u64| {
+        let samples: Vec<u64> = recent.iter().map(|d| ms(d)).filter(|&ms| ms > 0).collect();
+        if samples.is_empty() {
+            0
+        } else {
+            samples.iter().sum::<u64>() / samples.len() as u64
+        }
+    };
+    let per_task = average(|d| d.phase1_ms) + average(|d| d.phase2_ms) + average(|d| d.phase3_ms);
+    Some(Duration::from_millis(per_task.saturating_mul(remaining as u64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_collects_time_by_phase_and_restores_outer_durations() {
+        let ((), outer) = track(|| {
+            record(1, Duration::from_millis(5));
+            let ((), inner) = track(|| {
+                record(2, Duration::from_millis(7));
+                record(2, Duration::from_millis(3));
+            });
+            assert_eq!(inner, PhaseDurations { phase2_ms: 10, ..Default::default() });
+            record(3, Duration::from_millis(2));
+        });
+        assert_eq!(outer, PhaseDurations { phase1_ms: 5, phase2_ms: 0, phase3_ms: 2 });
+        assert_eq!(outer.total_ms(), 7);
+        record(1, Duration::from_secs(1));
+        assert_eq!(track(|| ()).1, PhaseDurations::default());
+    }
+
+    #[test]
+    fn eta_averages_each_phase_over_recent_tasks() {
+        let mut durations = BTreeMap::new();
+        assert_eq!(eta(&durations, 3), None);
+        durations.insert(1, PhaseDurations { phase1_ms: 1000, phase2_ms: 3000, phase3_ms: 0 });
+        durations.insert(2, PhaseDurations { phase1_ms: 3000, phase2_ms: 5000, phase3_ms: 6000 });
+        // Phase 3 averages over task 2 only: (2000 + 4000 + 6000) per task.
+        assert_eq!(eta(&durations, 3), Some(Duration::from_secs(36)));
+        assert_eq!(eta(&durations, 0), Some(Duration::ZERO));
+
+        // Only the last ETA_WINDOW tasks count.
+        for index in 10..10 + ETA_WINDOW as u32 {
+            durations.insert(index, PhaseDurations { phase1_ms: 1000, ..Default::default() });
+        }
+        assert_eq!(eta(&durations, 2), Some(Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,108 @@
+//! Cache of Phase 3 triage answers.
+//!
+//! When an address round makes no progress, the next `stet run` prints the same findings and
+//! triage would ask the agent the same question again. [`crate::phase::run_phase3_triage`]
+//! looks the prompt up here first: entries live in `{state_dir}/cache/triage/<key>.json`,
+//! keyed by the SHA-256 of the stet findings JSON, the triage agent, its backend, and its
+//! model, and expire after `triage_cache_ttl_sec`. The rest of the prompt is left out of the
+//! key, so a template or context change does not empty the cache. Only successful answers
+//! are stored. `--no-cache` (`no_cache`) bypasses the cache for a run; a TTL of 0 turns it off.
+//!
+//! The cache is best-effort: unreadable, malformed, or expired entries are misses, and a
+//! failed write is logged and ignored.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config::PealConfig;
+use crate::hash::sha256_hex;
+
+/// One cached triage answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    /// RFC 3339 timestamp of the agent call.
+    created_at: String,
+    stdout: String,
+}
+
+/// True when triage answers may be read from and written to the cache for this run.
+pub fn enabled(config: &PealConfig) -> bool {
+    !config.no_cache && config.triage_cache_ttl_sec > 0
+}
+
+/// Cache key for a triage call: SHA-256 of the agent, backend, models, and stet findings
+/// JSON, so a different agent or model never reuses an answer.
+pub fn key(agent: &Path, config: &PealConfig, findings: &str) -> String {
+    let input = format!(
+        "{}\n{}\n{}\n{}\n{findings}",
+        agent.display(),
+        config.agent_backend,
+        config.model.as_deref().unwrap_or(""),
+        config.api_model.as_deref().unwrap_or(""),
+    );
+    sha256_hex(input.as_bytes())
+}
+
+fn entry_path(state_dir: &Path, key: &str) -> PathBuf {
+    state_dir.join("cache").join("triage").join(format!("{key}.json"))
+}
+
+/// The cached answer for `key` if there is one younger than `ttl`.
+pub fn lookup(state_dir: &Path, key: &str, ttl: Duration) -> Option<String> {
+    let text = fs::read_to_string(entry_path(state_dir, key)).ok()?;
+    let entry: Entry = serde_json::from_str(&text).ok()?;
+    let created = chrono::DateTime::parse_from_rfc3339(&entry.created_at).ok()?;
+    let age = chrono::Utc::now().signed_duration_since(created).to_std().unwrap_or_default();
+    if age > ttl {
+        debug!(key, age_sec = age.as_secs(), "triage cache entry expired");
+        return None;
+    }
+    Some(entry.stdout)
+}
+
+/// Store `stdout` as the answer for `key` (best-effort).
+pub fn store(state_dir: &Path, key: &str, stdout: &str) {
+    let path = entry_path(state_dir, key);
+    let entry = Entry {
+        created_at: chrono::Utc::now().to_rfc3339(),
+        stdout: stdout.to_owned(),
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, serde_json::to_string(&entry).unwrap_or_default()));
+    if let Err(e) = result {
+        warn!(path = %path.display(), err = %e, "failed to write triage cache entry");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_until_they_expire() {
+        let dir = tempfile::tempdir().unwrap();
+        let ttl = Duration::from_secs(60);
+        assert_eq!(lookup(dir.path(), "abc", ttl), None);
+
+        store(dir.path(), "abc", "nothing to address");
+        assert!(dir.path().join("cache/triage/abc.json").is_file());
+        assert_eq!(lookup(dir.path(), "abc", ttl).as_deref(), Some("nothing to address"));
+
+        let old = Entry {
+            created_at: (chrono::Utc::now() - chrono::Duration::seconds(120)).to_rfc3339(),
+            stdout: "stale".to_owned(),
+        };
+        fs::write(entry_path(dir.path(), "abc"), serde_json::to_string(&old).unwrap()).unwrap();
+        assert_eq!(lookup(dir.path(), "abc", ttl), None);
+        assert_eq!(lookup(dir.path(), "abc", Duration::from_secs(300)).as_deref(), Some("stale"));
+
+        fs::write(entry_path(dir.path(), "abc"), "not json").unwrap();
+        assert_eq!(lookup(dir.path(), "abc", ttl), None);
+    }
+}
@@ -0,0 +1,479 @@
+//! Version control backends (`vcs`).
+//!
+//! peal needs a handful of operations from the target repository's VCS: check that the repo is
+//! a working copy, resolve the current commit (the base for `stet_scope_to_task` and
+//! `phase2_change_check`, the commit named in `StetFindingsRemain`), list the files changed
+//! since such a base, commit everything (`commit_after_phase2`), and show the task's diff
+//! (`summarize_tasks`). [`Vcs`] has one implementation per supported system:
+//!
+//! - [`Git`] (`git`).
+//! - [`Jujutsu`] (`jj`): the current commit is the working-copy commit `@`, which jj snapshots
+//!   on every command, so a base taken before a task and diffed against `@` afterwards covers
+//!   exactly the task's edits. Committing is `jj commit`.
+//! - [`Sapling`] (`sl`): the current commit is `.`; committing is `sl commit -A`.
+//!
+//! `vcs = "auto"` (the default) picks the backend with [`detect`]. `push_after_segment` and
+//! `rollback_failed_tasks` use git plumbing directly and are rejected by config validation for
+//! the other backends.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use crate::config::PealConfig;
+use crate::error::PealError;
+
+/// Default `vcs` setting.
+pub const DEFAULT_VCS: &str = "auto";
+
+/// Accepted `vcs` settings.
+pub const VCS_SETTINGS: [&str; 4] = ["auto", "git", "jj", "sapling"];
+
+/// Operations peal needs from the repository's version control system.
+pub trait Vcs: fmt::Debug + Send + Sync {
+    /// Name as used in the `vcs` setting: "git", "jj", or "sapling".
+    fn name(&self) -> &'static str;
+
+    /// Fails unless `path` is inside a working copy.
+    fn check_repo(&self, path: &Path) -> Result<(), PealError>;
+
+    /// Id of the current commit, or `None` when it cannot be resolved (e.g. no commit yet).
+    fn head(&self, repo: &Path) -> Option<String>;
+
+    /// Files under `repo` changed since commit `base`, committed or not and including new
+    /// files, as paths relative to `repo`. Deleted files and paths in (or under) `exclude`
+    /// (relative to `repo`) are left out. `None` when the VCS fails.
+    fn changed_files_since(&self, repo: &Path, base: &str, exclude: &[String]) -> Option<Vec<String>>;
+
+    /// Commit every change in `repo` with `message`. `Ok(false)` when there was nothing to
+    /// commit; `Err` carries the VCS's error output.
+    fn commit_all(&self, repo: &Path, message: &str) -> Result<bool, String>;
+
+    /// Diff of the uncommitted changes, or of the latest commit when there are none. Empty
+    /// when the VCS fails.
+    fn task_diff(&self, repo: &Path) -> String;
+}
+
+/// The backend for a `vcs` setting; "auto" (or anything unknown) uses [`detect`].
+pub fn resolve(setting: &str, repo: &Path) -> Box<dyn Vcs> {
+    match setting {
+        "git" => Box::new(Git::default()),
+        "jj" => Box::new(Jujutsu::default()),
+        "sapling" => Box::new(Sapling::default()),
+        _ => detect(repo),
+    }
+}
+
+/// The backend for `config.vcs` and `config.repo_path`.
+pub fn for_config(config: &PealConfig) -> Box<dyn Vcs> {
+    resolve(&config.vcs, &config.repo_path)
+}
+
+/// Backend of the working copy containing `repo`: the nearest directory (from `repo` up) with
+/// a `.jj` (Jujutsu, also when colocated with git), `.sl` (Sapling), or `.git` entry decides.
+/// Git when there is none.
+pub fn detect(repo: &Path) -> Box<dyn Vcs> {
+    let repo = std::path::absolute(repo).unwrap_or_else(|_| repo.to_path_buf());
+    for dir in repo.ancestors() {
+        if dir.join(".jj").is_dir() {
+            return Box::new(Jujutsu::default());
+        }
+        if dir.join(".sl").is_dir() {
+            return Box::new(Sapling::default());
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    Box::new(Git::default())
+}
+
+/// Git, through the `git` binary.
+#[derive(Debug, Clone)]
+pub struct Git {
+    program: String,
+}
+
+impl Default for Git {
+    fn default() -> Self {
+        Self { program: "git".to_owned() }
+    }
+}
+
+impl Git {
+    /// Git through another binary (tests use a missing one).
+    pub fn with_program(program: &str) -> Self {
+        Self { program: program.to_owned() }
+    }
+
+    fn git(&self, repo: &Path, args: &[&str]) -> io::Result<Output> {
+        Command::new(&self.program).arg("-C").arg(repo).args(args).output()
+    }
+
+    fn stdout(&self, repo: &Path, args: &[&str]) -> Option<String> {
+        stdout_of(self.git(repo, args))
+    }
+}
+
+impl Vcs for Git {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn check_repo(&self, path: &Path) -> Result<(), PealError> {
+        let output = match self.git(path, &["rev-parse", "--is-inside-work-tree"]) {
+            Ok(o) => o,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(PealError::GitNotFound),
+            Err(_) => return Err(PealError::RepoNotGitRepo { path: path.to_path_buf() }),
+        };
+        if output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "true" {
+            Ok(())
+        } else {
+            Err(PealError::RepoNotGitRepo { path: path.to_path_buf() })
+        }
+    }
+
+    fn head(&self, repo: &Path) -> Option<String> {
+        non_empty(self.stdout(repo, &["rev-parse", "--verify", "HEAD"])?)
+    }
+
+    fn changed_files_since(&self, repo: &Path, base: &str, exclude: &[String]) -> Option<Vec<String>> {
+        let mut pathspec = vec!["--".to_owned(), ".".to_owned()];
+        pathspec.extend(exclude.iter().map(|spec| format!(":(exclude){spec}")));
+        let git_lines = |args: &[&str]| -> Option<Vec<String>> {
+            let mut args: Vec<&str> = args.to_vec();
+            args.extend(pathspec.iter().map(String::as_str));
+            Some(lines(&self.stdout(repo, &args)?))
+        };
+        let mut files = git_lines(&["diff", "--name-only", "--relative", "--diff-filter=d", base])?;
+        files.extend(git_lines(&["ls-files", "--others", "--exclude-standard"])?);
+        files.sort();
+        files.dedup();
+        Some(files)
+    }
+
+    fn commit_all(&self, repo: &Path, message: &str) -> Result<bool, String> {
+        let add = self.git(repo, &["add", "-A"]).map_err(|e| format!("git add failed: {e}"))?;
+        if !add.status.success() {
+            return Err(format!("git add failed: {}", String::from_utf8_lossy(&add.stderr).trim()));
+        }
+        let commit = self
+            .git(repo, &["commit", "-m", message])
+            .map_err(|e| format!("git commit failed: {e}"))?;
+        if commit.status.success() {
+            return Ok(true);
+        }
+        let stderr = String::from_utf8_lossy(&commit.stderr);
+        let stderr_lower = stderr.to_lowercase();
+        let empty_or_clean = stderr.is_empty()
+            || stderr_lower.contains("nothing to commit")
+            || stderr_lower.contains("working tree clean")
+            || stderr_lower.contains("no changes added to commit");
+        if empty_or_clean {
+            return Ok(false);
+        }
+        Err(format!("git commit failed: {}", stderr.trim()))
+    }
+
+    fn task_diff(&self, repo: &Path) -> String {
+        let diff = self.stdout(repo, &["diff", "HEAD"]).unwrap_or_default();
+        if diff.trim().is_empty() {
+            return self.stdout(repo, &["show", "HEAD"]).unwrap_or_default();
+        }
+        diff
+    }
+}
+
+/// Jujutsu, through the `jj` binary.
+#[derive(Debug, Clone)]
+pub struct Jujutsu {
+    program: String,
+}
+
+impl Default for Jujutsu {
+    fn default() -> Self {
+        Self { program: "jj".to_owned() }
+    }
+}
+
+impl Jujutsu {
+    fn stdout(&self, repo: &Path, args: &[&str]) -> Option<String> {
+        stdout_of(run_in(&self.program, repo, args))
+    }
+}
+
+impl Vcs for Jujutsu {
+    fn name(&self) -> &'static str {
+        "jj"
+    }
+
+    fn check_repo(&self, path: &Path) -> Result<(), PealError> {
+        check_root(self.name(), &self.program, path)
+    }
+
+    fn head(&self, repo: &Path) -> Option<String> {
+        non_empty(self.stdout(repo, &["log", "--no-graph", "-r", "@", "-T", "commit_id"])?)
+    }
+
+    fn changed_files_since(&self, repo: &Path, base: &str, exclude: &[String]) -> Option<Vec<String>> {
+        let summary = self.stdout(repo, &["diff", "--summary", "--from", base, "--to", "@", "."])?;
+        Some(summary_files(&summary, exclude))
+    }
+
+    fn commit_all(&self, repo: &Path, message: &str) -> Result<bool, String> {
+        let empty = self
+            .stdout(repo, &["log", "--no-graph", "-r", "@", "-T", "empty"])
+            .ok_or_else(|| "jj log failed".to_owned())?;
+        if empty.trim() == "true" {
+            return Ok(false);
+        }
+        checked(run_in(&self.program, repo, &["commit", "-m", message]), "jj commit").map(|()| true)
+    }
+
+    fn task_diff(&self, repo: &Path) -> String {
+        let diff = self.stdout(repo, &["diff", "--git"]).unwrap_or_default();
+        if diff.trim().is_empty() {
+            return self.stdout(repo, &["diff", "--git", "-r", "@-"]).unwrap_or_default();
+        }
+        diff
+    }
+}
+
+/// Sapling, through the `sl` binary.
+#[derive(Debug, Clone)]
+pub struct Sapling {
+    program: String,
+}
+
+impl Default for Sapling {
+    fn default() -> Self {
+        Self { program: "sl".to_owned() }
+    }
+}
+
+impl Sapling {
+    fn stdout(&self, repo: &Path, args: &[&str]) -> Option<String> {
+        stdout_of(run_in(&self.program, repo, args))
+    }
+}
+
+impl Vcs for Sapling {
+    fn name(&self) -> &'static str {
+        "sapling"
+    }
+
+    fn check_repo(&self, path: &Path) -> Result<(), PealError> {
+        check_root(self.name(), &self.program, path)
+    }
+
+    fn head(&self, repo: &Path) -> Option<String> {
+        let node = non_empty(self.stdout(repo, &["log", "-r", ".", "-T", "{node}"])?)?;
+        // The null commit of an empty repository.
+        (!node.bytes().all(|b| b == b'0')).then_some(node)
+    }
+
+    fn changed_files_since(&self, repo: &Path, base: &str, exclude: &[String]) -> Option<Vec<String>> {
+        let status = self.stdout(repo, &["status", "--rev", base, "-mau", "-n", "."])?;
+        let mut files: Vec<String> = lines(&status)
+            .into_iter()
+            .filter(|f| !is_excluded(f, exclude))
+            .collect();
+        files.sort();
+        files.dedup();
+        Some(files)
+    }
+
+    fn commit_all(&self, repo: &Path, message: &str) -> Result<bool, String> {
+        let output = run_in(&self.program, repo, &["commit", "-A", "-m", message])
+            .map_err(|e| format!("sl commit failed: {e}"))?;
+        if output.status.success() {
+            return Ok(true);
+        }
+        let text = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        if text.to_lowercase().contains("nothing changed") {
+            return Ok(false);
+        }
+        Err(format!("sl commit failed: {}", text.trim()))
+    }
+
+    fn task_diff(&self, repo: &Path) -> String {
+        let diff = self.stdout(repo, &["diff"]).unwrap_or_default();
+        if diff.trim().is_empty() {
+            return self.stdout(repo, &["show", "."]).unwrap_or_default();
+        }
+        diff
+    }
+}
+
+/// Run `program args` with `repo` as the working directory (jj and Sapling print paths
+/// relative to it).
+fn run_in(program: &str, repo: &Path, args: &[&str]) -> io::Result<Output> {
+    Command::new(program).args(args).current_dir(repo).output()
+}
+
+/// stdout of a successful command.
+fn stdout_of(output: io::Result<Output>) -> Option<String> {
+    output
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+}
+
+/// `Err` with the command's stderr unless it succeeded.
+fn checked(output: io::Result<Output>, what: &str) -> Result<(), String> {
+    let output = output.map_err(|e| format!("{what} failed: {e}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("{what} failed: {}", String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// `check_repo` for backends whose `root` command fails outside a working copy.
+fn check_root(vcs: &str, program: &str, path: &Path) -> Result<(), PealError> {
+    let not_repo = || PealError::RepoNotVcsRepo { vcs: vcs.to_owned(), path: path.to_path_buf() };
+    match run_in(program, path, &["root"]) {
+        Ok(o) if o.status.success() => Ok(()),
+        Ok(_) => Err(not_repo()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(PealError::VcsNotFound {
+            vcs: vcs.to_owned(),
+            program: program.to_owned(),
+        }),
+        Err(_) => Err(not_repo()),
+    }
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_owned())
+}
+
+fn lines(s: &str) -> Vec<String> {
+    s.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// True when `path` is one of `exclude` or lies under one.
+fn is_excluded(path: &str, exclude: &[String]) -> bool {
+    exclude.iter().any(|ex| {
+        path == ex || path.strip_prefix(ex.as_str()).is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// Changed, added, and renamed-to files from `jj diff --summary` lines (`M path`, `A path`,
+/// `D path`, `R dir/{old => new}`), without deletions and `exclude`d paths, sorted.
+fn summary_files(summary: &str, exclude: &[String]) -> Vec<String> {
+    let mut files: Vec<String> = summary
+        .lines()
+        .filter_map(|line| {
+            let (status, path) = line.trim().split_once(' ')?;
+            match status {
+                "D" => None,
+                "R" | "C" => Some(renamed_to(path)),
+                _ => Some(path.to_owned()),
+            }
+        })
+        .filter(|f| !is_excluded(f, exclude))
+        .collect();
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Destination of a `dir/{old => new}/rest` rename.
+fn renamed_to(path: &str) -> String {
+    let (Some(open), Some(close)) = (path.find('{'), path.rfind('}')) else {
+        return path.to_owned();
+    };
+    let inner = &path[open + 1..close];
+    let new = inner.split_once(" => ").map_or(inner, |(_, new)| new);
+    let joined = format!("{}{new}{}", &path[..open], &path[close + 1..]);
+    joined.replace("//", "/").trim_start_matches('/').to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(repo: &Path, args: &[&str]) {
+        let out = Command::new("git").arg("-C").arg(repo).args(args).output().unwrap();
+        assert!(out.status.success(), "git {args:?}: {}", String::from_utf8_lossy(&out.stderr));
+    }
+
+    #[test]
+    fn detect_prefers_the_nearest_marker_and_defaults_to_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("a/b");
+        std::fs::create_dir_all(&sub).unwrap();
+        assert_eq!(detect(&sub).name(), "git");
+
+        std::fs::create_dir(dir.path().join(".sl")).unwrap();
+        assert_eq!(detect(&sub).name(), "sapling");
+        std::fs::create_dir(dir.path().join("a/.jj")).unwrap();
+        assert_eq!(detect(&sub).name(), "jj");
+        std::fs::create_dir(sub.join(".git")).unwrap();
+        assert_eq!(detect(&sub).name(), "git", "a nested git repo stops the search");
+
+        assert_eq!(resolve("sapling", &sub).name(), "sapling");
+        assert_eq!(resolve("auto", dir.path()).name(), "sapling");
+    }
+
+    #[test]
+    fn check_repo_reports_missing_binaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = Git::with_program("peal-test-no-such-git").check_repo(dir.path()).unwrap_err();
+        assert!(matches!(err, PealError::GitNotFound), "got: {err:?}");
+        assert!(err.to_string().contains("--no-git-checks"));
+
+        let jj = Jujutsu { program: "peal-test-no-such-jj".to_owned() };
+        let err = jj.check_repo(dir.path()).unwrap_err();
+        assert!(matches!(err, PealError::VcsNotFound { .. }), "got: {err:?}");
+        assert!(err.to_string().contains("peal-test-no-such-jj"), "{err}");
+    }
+
+    #[test]
+    fn jj_summary_lists_changed_files_without_deletions() {
+        let summary = "M src/lib.rs\nA new.txt\nD gone.txt\nR src/{old.rs => new.rs}\nA .peal/state.json\n";
+        assert_eq!(
+            summary_files(summary, &[".peal".to_owned()]),
+            vec!["new.txt", "src/lib.rs", "src/new.rs"]
+        );
+        assert_eq!(renamed_to("{a.txt => b.txt}"), "b.txt");
+        assert_eq!(renamed_to("x/{ => y}/z.rs"), "x/y/z.rs");
+        assert!(is_excluded("plan.md", &["plan.md".to_owned()]));
+        assert!(!is_excluded(".pealx", &[".peal".to_owned()]));
+    }
+
+    #[test]
+    fn git_backend_commits_and_lists_changes_since_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        git(repo, &["init", "-q"]);
+        git(repo, &["config", "user.email", "peal@example.com"]);
+        git(repo, &["config", "user.name", "peal"]);
+        let vcs = Git::default();
+        vcs.check_repo(repo).unwrap();
+        assert_eq!(vcs.head(repo), None, "no commit yet");
+
+        std::fs::write(repo.join("a.txt"), "a\n").unwrap();
+        assert_eq!(vcs.commit_all(repo, "first"), Ok(true));
+        assert_eq!(vcs.commit_all(repo, "again"), Ok(false), "nothing to commit");
+        let base = vcs.head(repo).unwrap();
+
+        std::fs::write(repo.join("a.txt"), "b\n").unwrap();
+        std::fs::write(repo.join("new.txt"), "n\n").unwrap();
+        std::fs::create_dir(repo.join(".peal")).unwrap();
+        std::fs::write(repo.join(".peal/state.json"), "{}").unwrap();
+        let changed = vcs.changed_files_since(repo, &base, &[".peal".to_owned()]).unwrap();
+        assert_eq!(changed, vec!["a.txt", "new.txt"]);
+        assert!(vcs.task_diff(repo).contains("+b"));
+    }
+}